@@ -0,0 +1,474 @@
+//! Parsing a document into an arena, for reading documents which are never changed.
+//! This module is only available with the `arena` feature.
+//!
+//! An [`ArenaDocument`] puts all of its nodes, text and attributes in a [`Bump`]
+//! arena, which hands out memory by moving a pointer along large blocks, so parsing
+//! makes very few allocations and dropping the arena frees the whole document at
+//! once. This suits scraping, where many documents are parsed, read and thrown
+//! away. The nodes can not be changed, use [`ArenaDocument::to_document`] to get
+//! an [`HtmlDocument`] when they need to be.
+//!
+//! Most of the attributes of a scraped page are never read, so with
+//! [`ArenaOptions::lazy_attributes`] the attributes of each tag are kept as the
+//! text they were written as, and only split into names and values the first time
+//! they are read from that tag.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::arena::{ArenaDocument, Bump};
+//! let bump = Bump::new();
+//! let html = r#"<ul><li class="item">One</li><li class="item" hidden>Two</li></ul>"#;
+//! let doc = ArenaDocument::parse(html, &bump).unwrap();
+//! let items = doc.find_tags("li");
+//! assert_eq!(items.len(), 2);
+//! assert_eq!(items[1].text(), "Two");
+//! assert_eq!(items[1].attribute("hidden"), Some(""));
+//! assert_eq!(doc.to_document(), html.parse().unwrap());
+//! ```
+use crate::error::{ParseErrorKind, ParseHtmlError};
+use crate::memfind::find_byte;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag};
+use bumpalo::collections::Vec as BumpVec;
+pub use bumpalo::Bump;
+use std::cell::OnceCell;
+use std::fmt;
+
+/// The elements which never have any contents or an end tag.
+const VOID_TAGS: [&str; 16] = [
+    "area", "base", "br", "col", "command", "embed", "hr", "img", "input", "keygen", "link",
+    "meta", "param", "source", "track", "wbr",
+];
+
+/// Options for [`ArenaDocument::parse_with`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArenaOptions {
+    /// Split the attributes of a tag into names and values when they are first
+    /// read, rather than while parsing.
+    pub lazy_attributes: bool,
+}
+
+impl ArenaOptions {
+    pub fn new() -> ArenaOptions {
+        ArenaOptions::default()
+    }
+
+    pub fn lazy_attributes(mut self, lazy_attributes: bool) -> ArenaOptions {
+        self.lazy_attributes = lazy_attributes;
+        self
+    }
+}
+
+/// A document whose nodes are all in an arena.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaDocument<'b> {
+    /// The doctype string from the document, usually "html".
+    pub doctype: &'b str,
+    /// The nodes at the top level of the document.
+    pub nodes: &'b [ArenaNode<'b>],
+}
+
+/// A node of an [`ArenaDocument`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArenaNode<'b> {
+    Tag(&'b ArenaTag<'b>),
+    Text(&'b str),
+    Comment(&'b str),
+}
+
+/// A tag of an [`ArenaDocument`]. Unlike a [`HtmlTag`] the `class` and `id`
+/// attributes are kept with the others.
+pub struct ArenaTag<'b> {
+    pub tag: &'b str,
+    pub contents: &'b [ArenaNode<'b>],
+    /// The text of the attributes which have not been split yet.
+    raw_attributes: &'b str,
+    attributes: OnceCell<&'b [(&'b str, &'b str)]>,
+    bump: &'b Bump,
+}
+
+impl<'b> ArenaDocument<'b> {
+    /// Parses the document into the arena.
+    pub fn parse(html: &str, bump: &'b Bump) -> Result<ArenaDocument<'b>, ParseHtmlError> {
+        ArenaDocument::parse_with(html, bump, &ArenaOptions::default())
+    }
+
+    /// Parses the document into the arena using the options.
+    pub fn parse_with(
+        html: &str,
+        bump: &'b Bump,
+        options: &ArenaOptions,
+    ) -> Result<ArenaDocument<'b>, ParseHtmlError> {
+        let mut parser = ArenaParser {
+            html,
+            pos: 0,
+            bump,
+            doctype: "",
+            lazy_attributes: options.lazy_attributes,
+        };
+        let nodes = parser.parse_nodes(None)?;
+        Ok(ArenaDocument {
+            doctype: parser.doctype,
+            nodes,
+        })
+    }
+
+    /// Finds all of the tags with the name, in document order.
+    pub fn find_tags(&self, name: &str) -> Vec<&'b ArenaTag<'b>> {
+        let mut found = vec![];
+        find_tags_in(self.nodes, name, &mut found);
+        found
+    }
+
+    /// Copies the document out of the arena.
+    pub fn to_document(&self) -> HtmlDocument {
+        let mut doc = HtmlDocument::new();
+        doc.doctype = self.doctype.to_owned();
+        doc.nodes = self.nodes.iter().map(ArenaNode::to_node).collect();
+        doc
+    }
+}
+
+impl<'b> ArenaNode<'b> {
+    /// Copies the node out of the arena.
+    pub fn to_node(&self) -> HtmlNode {
+        match self {
+            ArenaNode::Tag(tag) => HtmlNode::Tag(tag.to_tag()),
+            ArenaNode::Text(text) => HtmlNode::Text(text.to_string()),
+            ArenaNode::Comment(comment) => HtmlNode::Comment(comment.to_string()),
+        }
+    }
+}
+
+impl<'b> ArenaTag<'b> {
+    /// Gets the attributes in the order they were written.
+    pub fn attributes(&self) -> &'b [(&'b str, &'b str)] {
+        self.attributes.get_or_init(|| {
+            let mut attributes = BumpVec::new_in(self.bump);
+            read_attributes(self.raw_attributes, |name, value| {
+                attributes.push((name, value))
+            });
+            attributes.into_bump_slice()
+        })
+    }
+
+    /// Gets the value of the attribute, which is empty for an attribute without a
+    /// value.
+    pub fn attribute(&self, name: &str) -> Option<&'b str> {
+        self.attributes()
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = &'b str> {
+        self.attribute("class")
+            .unwrap_or_default()
+            .split_ascii_whitespace()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &'b str> {
+        self.attribute("id")
+            .unwrap_or_default()
+            .split_ascii_whitespace()
+    }
+
+    /// Gets all of the text inside the tag.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        push_text(self.contents, &mut text);
+        text
+    }
+
+    /// Copies the tag and its contents out of the arena.
+    pub fn to_tag(&self) -> HtmlTag {
+        let mut tag = HtmlTag::new(self.tag);
+        tag.classes = self.classes().map(str::to_owned).collect();
+        tag.ids = self.ids().map(str::to_owned).collect();
+        for (name, value) in self.attributes() {
+            if *name != "class" && *name != "id" {
+                tag.attributes.insert(name.to_string(), value.to_string());
+            }
+        }
+        tag.contents = self.contents.iter().map(ArenaNode::to_node).collect();
+        tag
+    }
+}
+
+impl fmt::Debug for ArenaTag<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArenaTag")
+            .field("tag", &self.tag)
+            .field("attributes", &self.attributes())
+            .field("contents", &self.contents)
+            .finish()
+    }
+}
+
+impl PartialEq for ArenaTag<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+            && self.attributes() == other.attributes()
+            && self.contents == other.contents
+    }
+}
+
+/// Reads the attributes of a start tag from the start of the text, calling
+/// `found` with the name and value of each. Returns the length of the attributes
+/// and whether the tag was closed with `/>`, or None if the text ends before the
+/// `>` at the end of the tag.
+fn read_attributes<'a, F: FnMut(&'a str, &'a str)>(
+    text: &'a str,
+    mut found: F,
+) -> Option<(usize, bool)> {
+    let skip_whitespace = |pos: usize| pos + text[pos..].len() - text[pos..].trim_start().len();
+    let read_name = |pos: usize, ends: &[u8]| {
+        let len = text[pos..]
+            .bytes()
+            .position(|b| b.is_ascii_whitespace() || ends.contains(&b))
+            .unwrap_or(text.len() - pos);
+        &text[pos..pos + len]
+    };
+    let mut pos = 0;
+    loop {
+        pos = skip_whitespace(pos);
+        let rest = &text[pos..];
+        if rest.starts_with('>') {
+            return Some((pos, false));
+        } else if rest.starts_with("/>") {
+            return Some((pos, true));
+        } else if rest.is_empty() {
+            return None;
+        }
+        let name = read_name(pos, b"=/>");
+        if name.is_empty() {
+            // a stray / or =
+            pos += 1;
+            continue;
+        }
+        pos = skip_whitespace(pos + name.len());
+        let mut value = "";
+        if text[pos..].starts_with('=') {
+            pos = skip_whitespace(pos + 1);
+            value = match text.as_bytes().get(pos) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let len = find_byte(&text.as_bytes()[pos + 1..], quote)?;
+                    pos += len + 2;
+                    &text[pos - len - 1..pos - 1]
+                }
+                _ => {
+                    let value = read_name(pos, b">");
+                    pos += value.len();
+                    value
+                }
+            };
+        }
+        found(name, value);
+    }
+}
+
+fn find_tags_in<'b>(nodes: &'b [ArenaNode<'b>], name: &str, found: &mut Vec<&'b ArenaTag<'b>>) {
+    for node in nodes {
+        if let ArenaNode::Tag(tag) = node {
+            if tag.tag == name {
+                found.push(tag);
+            }
+            find_tags_in(tag.contents, name, found);
+        }
+    }
+}
+
+fn push_text(nodes: &[ArenaNode], text: &mut String) {
+    for node in nodes {
+        match node {
+            ArenaNode::Tag(tag) => push_text(tag.contents, text),
+            ArenaNode::Text(t) => text.push_str(t),
+            ArenaNode::Comment(_) => {}
+        }
+    }
+}
+
+struct ArenaParser<'s, 'b> {
+    html: &'s str,
+    /// The byte offset of the next char to read.
+    pos: usize,
+    bump: &'b Bump,
+    doctype: &'b str,
+    lazy_attributes: bool,
+}
+
+impl<'s, 'b> ArenaParser<'s, 'b> {
+    fn rest(&self) -> &'s str {
+        &self.html[self.pos..]
+    }
+
+    fn error<S: Into<String>>(&self, msg: S) -> ParseHtmlError {
+        ParseHtmlError::with_msg(msg).at_offset(self.pos)
+    }
+
+    fn unexpected_end(&self, what: &str) -> ParseHtmlError {
+        self.error(format!("end of file while reading {}", what))
+            .with_kind(ParseErrorKind::UnexpectedEnd)
+    }
+
+    /// Reads up to the byte, returning the text before it and moving past it.
+    fn read_to(&mut self, byte: u8, what: &str) -> Result<&'s str, ParseHtmlError> {
+        let rest = self.rest();
+        let len = find_byte(rest.as_bytes(), byte).ok_or_else(|| self.unexpected_end(what))?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+
+    /// Reads up to whitespace or one of the bytes, without moving past it.
+    fn read_name(&mut self, ends: &[u8]) -> &'s str {
+        let rest = self.rest();
+        let len = rest
+            .bytes()
+            .position(|b| b.is_ascii_whitespace() || ends.contains(&b))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// Parses nodes until the end tag, or the end of the document if there is none.
+    fn parse_nodes(
+        &mut self,
+        end_tag: Option<&str>,
+    ) -> Result<&'b [ArenaNode<'b>], ParseHtmlError> {
+        let mut nodes = BumpVec::new_in(self.bump);
+        loop {
+            let rest = self.rest();
+            let text_len = find_byte(rest.as_bytes(), b'<').unwrap_or(rest.len());
+            if text_len > 0 {
+                nodes.push(ArenaNode::Text(self.bump.alloc_str(&rest[..text_len])));
+                self.pos += text_len;
+            }
+            if self.pos == self.html.len() {
+                return match end_tag {
+                    Some(tag) => Err(self.unexpected_end(&format!("the contents of '{}'", tag))),
+                    None => Ok(nodes.into_bump_slice()),
+                };
+            }
+            // move past the <
+            self.pos += 1;
+            let rest = self.rest();
+            if rest.starts_with('/') {
+                self.pos += 1;
+                let name = self.read_to(b'>', "an end tag")?.trim_end();
+                return match end_tag {
+                    Some(tag) if tag == name => Ok(nodes.into_bump_slice()),
+                    Some(tag) => Err(self.error(format!(
+                        "Incorrect end tag found '{}' but expected '{}'.",
+                        name, tag
+                    ))),
+                    None => Err(self.error(format!("Found end tag '{}' before start tag.", name))),
+                };
+            } else if let Some(comment) = rest.strip_prefix("!--") {
+                let len = comment
+                    .find("-->")
+                    .ok_or_else(|| self.unexpected_end("a comment"))?;
+                nodes.push(ArenaNode::Comment(self.bump.alloc_str(&comment[..len])));
+                self.pos += 3 + len + 3;
+            } else if rest.starts_with("!DOCTYPE ") {
+                if end_tag.is_some() {
+                    return Err(self.error("DOCTYPE element found in middle of content"));
+                }
+                self.pos += "!DOCTYPE ".len();
+                self.doctype = self.bump.alloc_str(self.read_to(b'>', "the DOCTYPE")?);
+            } else {
+                let tag = self.parse_tag()?;
+                nodes.push(ArenaNode::Tag(self.bump.alloc(tag)));
+            }
+        }
+    }
+
+    /// Parses a tag and its contents, where the `<` has just been read.
+    fn parse_tag(&mut self) -> Result<ArenaTag<'b>, ParseHtmlError> {
+        let name = self.read_name(b"/>");
+        if name.is_empty() {
+            return Err(self.error("Expected a tag name after '<'"));
+        }
+        let rest = self.rest();
+        let attributes = OnceCell::new();
+        let mut raw_attributes = "";
+        let (len, closed) = if self.lazy_attributes {
+            let end = read_attributes(rest, |_, _| {});
+            raw_attributes = end.map_or("", |(len, _)| self.bump.alloc_str(&rest[..len]));
+            end
+        } else {
+            let mut found = BumpVec::new_in(self.bump);
+            let end = read_attributes(rest, |name, value| {
+                found.push((&*self.bump.alloc_str(name), &*self.bump.alloc_str(value)))
+            });
+            let _ = attributes.set(found.into_bump_slice());
+            end
+        }
+        .ok_or_else(|| self.unexpected_end(&format!("the tag '{}'", name)))?;
+        self.pos += len + if closed { 2 } else { 1 };
+        let contents = if closed || VOID_TAGS.contains(&name) {
+            &[]
+        } else {
+            self.parse_nodes(Some(name))?
+        };
+        Ok(ArenaTag {
+            tag: self.bump.alloc_str(name),
+            contents,
+            raw_attributes,
+            attributes,
+            bump: self.bump,
+        })
+    }
+}
+
+#[cfg(test)]
+mod arena_tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let html = concat!(
+            "<!DOCTYPE html><!-- a page --><html><head><meta charset=utf-8>",
+            "<title>Page</title></head><body class='main wide' id=top>",
+            "<p>Some <b>bold</b> text<br></p><img src=\"a.png\" alt=''></body></html>"
+        );
+        let bump = Bump::new();
+        let doc = ArenaDocument::parse(html, &bump).unwrap();
+        assert_eq!(doc.doctype, "html");
+        assert_eq!(doc.nodes[0], ArenaNode::Comment(" a page "));
+        let body = doc.find_tags("body")[0];
+        assert_eq!(body.classes().collect::<Vec<_>>(), vec!["main", "wide"]);
+        assert_eq!(body.ids().collect::<Vec<_>>(), vec!["top"]);
+        assert_eq!(body.text(), "Some bold text");
+        let img = doc.find_tags("img")[0];
+        assert_eq!(img.attributes(), &[("src", "a.png"), ("alt", "")]);
+        assert!(doc.find_tags("br")[0].contents.is_empty());
+        assert_eq!(doc.to_document(), html.parse::<HtmlDocument>().unwrap());
+        let doc = ArenaDocument::parse("<p>a<span/>b</p>", &bump).unwrap();
+        assert_eq!(doc.find_tags("p")[0].contents.len(), 3);
+    }
+
+    #[test]
+    fn lazy_attributes_test() {
+        let html = r#"<div a = "1 > 0" b='x'c><input disabled value=on/></div>"#;
+        let bump = Bump::new();
+        let options = ArenaOptions::new().lazy_attributes(true);
+        let lazy = ArenaDocument::parse_with(html, &bump, &options).unwrap();
+        let div = lazy.find_tags("div")[0];
+        assert!(div.attributes.get().is_none());
+        assert_eq!(div.attribute("c"), Some(""));
+        assert_eq!(div.attributes(), &[("a", "1 > 0"), ("b", "x"), ("c", "")]);
+        assert_eq!(lazy, ArenaDocument::parse(html, &bump).unwrap());
+        let input = lazy.find_tags("input")[0];
+        assert_eq!(input.attributes(), &[("disabled", ""), ("value", "on/")]);
+    }
+
+    #[test]
+    fn parse_error_test() {
+        let bump = Bump::new();
+        let err = ArenaDocument::parse("<div><p>text</div>", &bump).unwrap_err();
+        assert_eq!(err.msg, "Incorrect end tag found 'div' but expected 'p'.");
+        assert_eq!(err.offset(), Some(18));
+        let err = ArenaDocument::parse("<div class=\"a", &bump).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedEnd);
+    }
+}
@@ -0,0 +1,233 @@
+//! Typed access to the `role` and `aria-*` attributes of a tag, for accessibility
+//! checks.
+//!
+//! The getters read the attribute values as defined by WAI-ARIA: `true`/`false`
+//! states are [`bool`], states which can also be `mixed` are [`AriaTristate`] and
+//! id references are lists of ids. A value which is missing, empty or not valid
+//! for the attribute is None, which ARIA treats the same as the attribute not
+//! being there.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::aria::AriaTristate;
+//! use hb_html::objects::HtmlTag;
+//! let mut tag = r#"<div role="checkbox switch" aria-checked=mixed aria-labelledby="a b"></div>"#
+//!     .parse::<HtmlTag>()
+//!     .unwrap();
+//! assert_eq!(tag.role(), Some("checkbox"));
+//! assert_eq!(tag.aria_checked(), Some(AriaTristate::Mixed));
+//! assert_eq!(tag.aria_labelledby(), vec!["a", "b"]);
+//! assert_eq!(tag.aria_hidden(), None);
+//! tag.set_aria_hidden(Some(true));
+//! assert_eq!(tag.attributes["aria-hidden"], "true");
+//! ```
+use crate::objects::HtmlTag;
+
+/// The value of an ARIA state which can be true, false or mixed, eg `aria-checked`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AriaTristate {
+    True,
+    False,
+    Mixed,
+}
+
+impl AriaTristate {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AriaTristate::True => "true",
+            AriaTristate::False => "false",
+            AriaTristate::Mixed => "mixed",
+        }
+    }
+}
+
+impl HtmlTag {
+    /// Gets the value of the attribute, with surrounding whitespace removed, or
+    /// None if it is missing or empty.
+    fn token_attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .get(name)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Sets the attribute to the value, or removes it for None.
+    fn set_optional_attribute(&mut self, name: &str, value: Option<&str>) {
+        match value {
+            Some(v) => {
+                self.attributes.insert(name.to_owned(), v.to_owned());
+            }
+            None => {
+                self.attributes.remove(name);
+            }
+        }
+    }
+
+    /// Gets an ARIA `true`/`false` state by the full attribute name, eg
+    /// `aria-busy`.
+    pub fn aria_bool(&self, name: &str) -> Option<bool> {
+        match self.token_attribute(name)? {
+            v if v.eq_ignore_ascii_case("true") => Some(true),
+            v if v.eq_ignore_ascii_case("false") => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Sets an ARIA `true`/`false` state by the full attribute name, removing the
+    /// attribute for None.
+    pub fn set_aria_bool(&mut self, name: &str, value: Option<bool>) {
+        self.set_optional_attribute(name, value.map(|v| if v { "true" } else { "false" }));
+    }
+
+    /// Gets an ARIA state which can be `true`, `false` or `mixed` by the full
+    /// attribute name, eg `aria-pressed`.
+    pub fn aria_tristate(&self, name: &str) -> Option<AriaTristate> {
+        match self.token_attribute(name)? {
+            v if v.eq_ignore_ascii_case("mixed") => Some(AriaTristate::Mixed),
+            _ => self.aria_bool(name).map(|v| match v {
+                true => AriaTristate::True,
+                false => AriaTristate::False,
+            }),
+        }
+    }
+
+    /// Sets an ARIA tristate by the full attribute name, removing the attribute for
+    /// None.
+    pub fn set_aria_tristate(&mut self, name: &str, value: Option<AriaTristate>) {
+        self.set_optional_attribute(name, value.as_ref().map(AriaTristate::as_str));
+    }
+
+    /// Gets the ids in an ARIA id reference list by the full attribute name, eg
+    /// `aria-controls`.
+    pub fn aria_id_refs(&self, name: &str) -> Vec<&str> {
+        self.attributes
+            .get(name)
+            .map(|v| v.split_ascii_whitespace().collect())
+            .unwrap_or_default()
+    }
+
+    /// Gets the role of the element, which is the first of the space separated
+    /// roles in the `role` attribute. Later roles are fallbacks for user agents
+    /// which do not support the first.
+    pub fn role(&self) -> Option<&str> {
+        self.attributes.get("role")?.split_ascii_whitespace().next()
+    }
+
+    /// Sets the `role` attribute, removing it for None.
+    pub fn set_role(&mut self, role: Option<&str>) {
+        self.set_optional_attribute("role", role);
+    }
+
+    pub fn aria_label(&self) -> Option<&str> {
+        self.token_attribute("aria-label")
+    }
+
+    pub fn set_aria_label(&mut self, label: Option<&str>) {
+        self.set_optional_attribute("aria-label", label);
+    }
+
+    pub fn aria_labelledby(&self) -> Vec<&str> {
+        self.aria_id_refs("aria-labelledby")
+    }
+
+    pub fn aria_describedby(&self) -> Vec<&str> {
+        self.aria_id_refs("aria-describedby")
+    }
+
+    pub fn aria_hidden(&self) -> Option<bool> {
+        self.aria_bool("aria-hidden")
+    }
+
+    pub fn set_aria_hidden(&mut self, hidden: Option<bool>) {
+        self.set_aria_bool("aria-hidden", hidden);
+    }
+
+    pub fn aria_disabled(&self) -> Option<bool> {
+        self.aria_bool("aria-disabled")
+    }
+
+    pub fn set_aria_disabled(&mut self, disabled: Option<bool>) {
+        self.set_aria_bool("aria-disabled", disabled);
+    }
+
+    pub fn aria_expanded(&self) -> Option<bool> {
+        self.aria_bool("aria-expanded")
+    }
+
+    pub fn set_aria_expanded(&mut self, expanded: Option<bool>) {
+        self.set_aria_bool("aria-expanded", expanded);
+    }
+
+    pub fn aria_checked(&self) -> Option<AriaTristate> {
+        self.aria_tristate("aria-checked")
+    }
+
+    pub fn set_aria_checked(&mut self, checked: Option<AriaTristate>) {
+        self.set_aria_tristate("aria-checked", checked);
+    }
+
+    pub fn aria_pressed(&self) -> Option<AriaTristate> {
+        self.aria_tristate("aria-pressed")
+    }
+
+    pub fn set_aria_pressed(&mut self, pressed: Option<AriaTristate>) {
+        self.set_aria_tristate("aria-pressed", pressed);
+    }
+
+    /// Gets the `aria-level` of a heading or tree item, which must be 1 or more.
+    pub fn aria_level(&self) -> Option<u32> {
+        self.token_attribute("aria-level")?
+            .parse()
+            .ok()
+            .filter(|l| *l > 0)
+    }
+}
+
+#[cfg(test)]
+mod aria_tests {
+    use super::*;
+
+    #[test]
+    fn aria_getters_test() {
+        let tag = HtmlTag::new("button").attributes(vec![
+            ("aria-hidden", " TRUE "),
+            ("aria-expanded", "yes"),
+            ("aria-pressed", "false"),
+            ("aria-label", "  "),
+            ("aria-level", "0"),
+            ("aria-describedby", " d1\td2 "),
+            ("role", ""),
+        ]);
+        assert_eq!(tag.aria_hidden(), Some(true));
+        // values which are not valid are the same as not setting the attribute
+        assert_eq!(tag.aria_expanded(), None);
+        assert_eq!(tag.aria_disabled(), None);
+        assert_eq!(tag.aria_pressed(), Some(AriaTristate::False));
+        assert_eq!(tag.aria_label(), None);
+        assert_eq!(tag.aria_level(), None);
+        assert_eq!(tag.aria_describedby(), vec!["d1", "d2"]);
+        assert!(tag.aria_labelledby().is_empty());
+        assert_eq!(tag.role(), None);
+    }
+
+    #[test]
+    fn aria_setters_test() {
+        let mut tag = HtmlTag::new("li");
+        tag.set_role(Some("treeitem"));
+        tag.set_aria_expanded(Some(false));
+        tag.set_aria_checked(Some(AriaTristate::Mixed));
+        tag.set_aria_label(Some("Item"));
+        tag.attributes
+            .insert("aria-level".to_owned(), "2".to_owned());
+        assert_eq!(tag.role(), Some("treeitem"));
+        assert_eq!(tag.attributes["aria-expanded"], "false");
+        assert_eq!(tag.attributes["aria-checked"], "mixed");
+        assert_eq!(tag.aria_label(), Some("Item"));
+        assert_eq!(tag.aria_level(), Some(2));
+        tag.set_aria_expanded(None);
+        tag.set_role(None);
+        assert!(!tag.attributes.contains_key("aria-expanded"));
+        assert!(!tag.attributes.contains_key("role"));
+    }
+}
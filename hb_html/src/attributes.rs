@@ -0,0 +1,448 @@
+//! Reading and changing the attributes of a tag by name.
+//!
+//! A [`HtmlTag`] keeps its `id` and `class` attributes split into lists and the
+//! other attributes in a map, so code working with attributes by name has to
+//! handle all three. [`Attributes`] (from [`HtmlTag::attrs`]) and
+//! [`AttributesMut`] (from [`HtmlTag::attrs_mut`]) treat them the same. Names are
+//! looked up ignoring ASCII case, as browsers do, and the attributes are iterated
+//! in a fixed order: `id`, `class` and then the others sorted by name.
+//!
+//! The classes are changed with a [`ClassListMut`], from [`HtmlTag::class_list_mut`]
+//! or [`AttributesMut::classes_mut`], which works on the list of classes the
+//! `class` attribute is written from, so the attribute always matches it.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlTag;
+//! let mut tag = r#"<a id="top" class="nav link" HREF="/">Home</a>"#
+//!     .parse::<HtmlTag>()
+//!     .unwrap();
+//! let attrs = tag.attrs();
+//! assert_eq!(attrs.get("href").as_deref(), Some("/"));
+//! assert_eq!(attrs.id(), Some("top"));
+//! assert!(attrs.classes().contains("link"));
+//! let names: Vec<&str> = attrs.iter().map(|(name, _)| name).collect();
+//! assert_eq!(names, vec!["id", "class", "HREF"]);
+//!
+//! let mut attrs = tag.attrs_mut();
+//! attrs.set("href", "/home");
+//! assert_eq!(attrs.remove("Class").as_deref(), Some("nav link"));
+//! assert_eq!(tag.get_attribute("HREF").as_deref(), Some("/home"));
+//! assert!(tag.classes.is_empty());
+//! ```
+use crate::objects::{HtmlNode, HtmlTag};
+use crate::parsing::parse_attibute_value;
+use std::borrow::Cow;
+use std::fmt;
+
+/// The attributes of a tag, including `id` and `class`.
+#[derive(Debug, Clone, Copy)]
+pub struct Attributes<'a> {
+    tag: &'a HtmlTag,
+}
+
+impl<'a> Attributes<'a> {
+    /// Gets the value of the attribute with the name, ignoring case. The ids and
+    /// classes are joined back into a space separated value.
+    pub fn get(&self, name: &str) -> Option<Cow<'a, str>> {
+        if name.eq_ignore_ascii_case("id") {
+            joined(&self.tag.ids)
+        } else if name.eq_ignore_ascii_case("class") {
+            joined(&self.tag.classes)
+        } else {
+            let key = find_key(self.tag, name)?;
+            Some(Cow::Borrowed(self.tag.attributes[key].as_str()))
+        }
+    }
+
+    /// Returns true if the tag has the attribute, ignoring the case of the name.
+    pub fn contains(&self, name: &str) -> bool {
+        if name.eq_ignore_ascii_case("id") {
+            !self.tag.ids.is_empty()
+        } else if name.eq_ignore_ascii_case("class") {
+            !self.tag.classes.is_empty()
+        } else {
+            find_key(self.tag, name).is_some()
+        }
+    }
+
+    /// Iterates over the names and values of the attributes, `id` and `class`
+    /// first and then the others sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, Cow<'a, str>)> {
+        let mut others: Vec<(&'a String, &'a String)> = self.tag.attributes.iter().collect();
+        others.sort();
+        joined(&self.tag.ids)
+            .map(|v| ("id", v))
+            .into_iter()
+            .chain(joined(&self.tag.classes).map(|v| ("class", v)))
+            .chain(
+                others
+                    .into_iter()
+                    .map(|(k, v)| (k.as_str(), Cow::Borrowed(v.as_str()))),
+            )
+    }
+
+    /// The number of attributes.
+    pub fn len(&self) -> usize {
+        self.tag.attributes.len()
+            + usize::from(!self.tag.ids.is_empty())
+            + usize::from(!self.tag.classes.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the first id of the tag.
+    pub fn id(&self) -> Option<&'a str> {
+        self.tag.ids.first().map(String::as_str)
+    }
+
+    /// Gets the classes of the tag.
+    pub fn classes(&self) -> ClassList<'a> {
+        ClassList {
+            classes: &self.tag.classes,
+        }
+    }
+}
+
+/// The attributes of a tag, which can be changed.
+#[derive(Debug)]
+pub struct AttributesMut<'a> {
+    tag: &'a mut HtmlTag,
+}
+
+impl AttributesMut<'_> {
+    /// Gets a view of the attributes for reading them.
+    pub fn as_attributes(&self) -> Attributes<'_> {
+        Attributes { tag: self.tag }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Cow<'_, str>> {
+        self.as_attributes().get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.as_attributes().contains(name)
+    }
+
+    /// Sets the value of the attribute with the name. An attribute which already
+    /// has the name ignoring case keeps the way its name is written.
+    pub fn set<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        let name = name.into();
+        let value = value.into();
+        if name.eq_ignore_ascii_case("id") {
+            self.tag.ids = parse_attibute_value(value);
+        } else if name.eq_ignore_ascii_case("class") {
+            self.tag.classes = parse_attibute_value(value);
+        } else {
+            let key = find_key(self.tag, &name).cloned().unwrap_or(name);
+            self.tag.attributes.insert(key, value);
+        }
+    }
+
+    /// Removes the attribute with the name, ignoring case, returning its value if
+    /// it was present.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let removed = if name.eq_ignore_ascii_case("id") {
+            std::mem::take(&mut self.tag.ids)
+        } else if name.eq_ignore_ascii_case("class") {
+            std::mem::take(&mut self.tag.classes)
+        } else {
+            let key = find_key(self.tag, name)?.clone();
+            return self.tag.attributes.remove(&key);
+        };
+        joined(&removed).map(Cow::into_owned)
+    }
+
+    /// Gets the classes of the tag for changing them.
+    pub fn classes_mut(&mut self) -> ClassListMut<'_> {
+        self.tag.class_list_mut()
+    }
+}
+
+/// The classes of a tag, in the order they were written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClassList<'a> {
+    classes: &'a [String],
+}
+
+impl<'a> ClassList<'a> {
+    /// Returns true if the tag has the class. Class names are case sensitive.
+    pub fn contains(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> {
+        self.classes.iter().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+}
+
+impl fmt::Display for ClassList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.classes.join(" "))
+    }
+}
+
+/// The classes of a tag, which can be changed, as the `classList` of an element
+/// is in the DOM. Each of the methods which take classes take one or more class
+/// names separated by whitespace.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ClassListMut<'a> {
+    classes: &'a mut Vec<String>,
+}
+
+impl ClassListMut<'_> {
+    /// Gets a view of the classes for reading them.
+    pub fn as_class_list(&self) -> ClassList<'_> {
+        ClassList {
+            classes: self.classes,
+        }
+    }
+
+    /// Returns true if the tag has all of the classes.
+    pub fn contains(&self, classes: &str) -> bool {
+        let list = self.as_class_list();
+        classes.split_ascii_whitespace().all(|c| list.contains(c))
+    }
+
+    /// Adds the classes which the tag does not already have to the end, returning
+    /// true if any were added.
+    pub fn add(&mut self, classes: &str) -> bool {
+        let mut added = false;
+        for class in classes.split_ascii_whitespace() {
+            if !self.classes.iter().any(|c| c == class) {
+                self.classes.push(class.to_owned());
+                added = true;
+            }
+        }
+        added
+    }
+
+    /// Removes the classes, returning true if any were removed.
+    pub fn remove(&mut self, classes: &str) -> bool {
+        let before = self.classes.len();
+        let remove: Vec<&str> = classes.split_ascii_whitespace().collect();
+        self.classes.retain(|c| !remove.contains(&c.as_str()));
+        self.classes.len() != before
+    }
+
+    /// Removes each of the classes the tag has and adds the others, returning true
+    /// if the tag has all of them afterwards.
+    pub fn toggle(&mut self, classes: &str) -> bool {
+        let mut all_present = true;
+        for class in classes.split_ascii_whitespace() {
+            if !self.remove(class) {
+                self.classes.push(class.to_owned());
+            } else {
+                all_present = false;
+            }
+        }
+        all_present
+    }
+
+    /// Adds the classes if `on` is true and removes them otherwise.
+    pub fn set(&mut self, classes: &str, on: bool) {
+        if on {
+            self.add(classes);
+        } else {
+            self.remove(classes);
+        }
+    }
+
+    /// Puts the new class where the old one was, returning false if the tag does
+    /// not have the old class. If the tag already has the new class the old one is
+    /// just removed.
+    pub fn replace(&mut self, old: &str, new: &str) -> bool {
+        let Some(i) = self.classes.iter().position(|c| c == old) else {
+            return false;
+        };
+        if self.classes.iter().any(|c| c == new) {
+            self.classes.remove(i);
+        } else {
+            self.classes[i] = new.to_owned();
+        }
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.classes.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.classes.iter().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+}
+
+impl fmt::Display for ClassListMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_class_list().fmt(f)
+    }
+}
+
+impl HtmlTag {
+    /// Gets the classes of the tag for changing them, see [`ClassListMut`].
+    pub fn class_list_mut(&mut self) -> ClassListMut<'_> {
+        ClassListMut {
+            classes: &mut self.classes,
+        }
+    }
+
+    /// Gets the attributes of the tag by name, see [`Attributes`].
+    pub fn attrs(&self) -> Attributes<'_> {
+        Attributes { tag: self }
+    }
+
+    /// Gets the attributes of the tag for changing them, see [`AttributesMut`].
+    pub fn attrs_mut(&mut self) -> AttributesMut<'_> {
+        AttributesMut { tag: self }
+    }
+}
+
+impl HtmlNode {
+    /// Gets the attributes of the node, or None if it is not a tag.
+    pub fn attrs(&self) -> Option<Attributes<'_>> {
+        match self {
+            HtmlNode::Tag(t) => Some(t.attrs()),
+            _ => None,
+        }
+    }
+
+    /// Gets the attributes of the node for changing them, or None if it is not a
+    /// tag.
+    pub fn attrs_mut(&mut self) -> Option<AttributesMut<'_>> {
+        match self {
+            HtmlNode::Tag(t) => Some(t.attrs_mut()),
+            _ => None,
+        }
+    }
+}
+
+/// Joins the ids or classes into the value of their attribute, None if there are
+/// none.
+fn joined(values: &[String]) -> Option<Cow<'_, str>> {
+    match values {
+        [] => None,
+        [value] => Some(Cow::Borrowed(value.as_str())),
+        _ => Some(Cow::Owned(values.join(" "))),
+    }
+}
+
+/// Finds the key of the attribute with the name ignoring case, preferring one
+/// which is written the same.
+fn find_key<'t>(tag: &'t HtmlTag, name: &str) -> Option<&'t String> {
+    match tag.attributes.get_key_value(name) {
+        Some((key, _)) => Some(key),
+        None => tag
+            .attributes
+            .keys()
+            .filter(|k| k.eq_ignore_ascii_case(name))
+            .min(),
+    }
+}
+
+#[cfg(test)]
+mod attributes_tests {
+    use super::*;
+
+    #[test]
+    fn attributes_test() {
+        let tag = r#"<input id="a b" class="x" Type="text" data-b="2" data-a="1" disabled>"#
+            .parse::<HtmlTag>()
+            .unwrap();
+        let attrs = tag.attrs();
+        assert_eq!(attrs.len(), 6);
+        assert_eq!(attrs.get("ID").as_deref(), Some("a b"));
+        assert_eq!(attrs.get("type").as_deref(), Some("text"));
+        assert_eq!(attrs.get("disabled").as_deref(), Some(""));
+        assert_eq!(attrs.get("name"), None);
+        assert!(attrs.contains("TYPE") && attrs.contains("class") && !attrs.contains("style"));
+        assert_eq!(attrs.id(), Some("a"));
+        assert_eq!(
+            attrs.iter().collect::<Vec<_>>(),
+            vec![
+                ("id", Cow::Borrowed("a b")),
+                ("class", Cow::Borrowed("x")),
+                ("Type", Cow::Borrowed("text")),
+                ("data-a", Cow::Borrowed("1")),
+                ("data-b", Cow::Borrowed("2")),
+                ("disabled", Cow::Borrowed("")),
+            ]
+        );
+        let node = HtmlNode::Text("text".to_owned());
+        assert!(node.attrs().is_none());
+        assert!(HtmlTag::new("p").attrs().is_empty());
+    }
+
+    #[test]
+    fn attributes_mut_test() {
+        let mut node =
+            HtmlNode::Tag(r#"<img Src="a.png" class="wide">"#.parse::<HtmlTag>().unwrap());
+        let mut attrs = node.attrs_mut().unwrap();
+        attrs.set("SRC", "b.png");
+        attrs.set("ID", "hero");
+        attrs.set("class", " wide  dark ");
+        attrs.set("alt", "");
+        assert_eq!(attrs.get("src").as_deref(), Some("b.png"));
+        assert_eq!(attrs.remove("srC").as_deref(), Some("b.png"));
+        assert_eq!(attrs.remove("src"), None);
+        assert!(attrs.contains("alt"));
+        match &node {
+            HtmlNode::Tag(t) => {
+                assert_eq!(t.ids, vec!["hero"]);
+                assert_eq!(t.classes, vec!["wide", "dark"]);
+                assert!(!t.attributes.contains_key("Src"));
+                assert_eq!(t.attrs().classes().to_string(), "wide dark");
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            node.to_html_string(),
+            r#"<img id="hero" class="wide dark" alt=""></img>"#
+        );
+    }
+
+    #[test]
+    fn class_list_mut_test() {
+        let mut tag = r#"<p class="a b">x</p>"#.parse::<HtmlTag>().unwrap();
+        let mut classes = tag.class_list_mut();
+        assert!(classes.add("c  a d"));
+        assert!(!classes.add("b"));
+        assert_eq!(classes.to_string(), "a b c d");
+        assert!(classes.remove("a x"));
+        assert!(!classes.remove("x"));
+        assert!(classes.contains("d b") && !classes.contains("a"));
+        // b is removed and e is added, so they are not all present
+        assert!(!classes.toggle("b e"));
+        assert!(classes.toggle("b"));
+        classes.set("d", false);
+        classes.set("f", true);
+        assert!(classes.replace("c", "g"));
+        assert!(classes.replace("g", "e"));
+        assert!(!classes.replace("x", "y"));
+        assert_eq!(classes.iter().collect::<Vec<_>>(), vec!["e", "b", "f"]);
+        assert_eq!(tag.get_attribute("class").as_deref(), Some("e b f"));
+
+        let mut attrs = tag.attrs_mut();
+        attrs.classes_mut().clear();
+        assert!(!attrs.contains("class"));
+        assert_eq!(HtmlNode::Tag(tag).to_html_string(), "<p>x</p>");
+    }
+}
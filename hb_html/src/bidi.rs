@@ -0,0 +1,224 @@
+//! Working out the direction of text from the `dir` attribute, as browsers do.
+//!
+//! An element with `dir="ltr"` or `dir="rtl"` has that direction. An element with
+//! `dir="auto"`, and a `<bdi>` without a `dir`, takes the direction of the first
+//! character with a strong direction in its text, skipping the text of `<bdi>`,
+//! `<script>`, `<style>` and `<textarea>` elements and of elements with their own
+//! `dir`. Everything else inherits the direction of its parent, and the top of the
+//! document is left-to-right.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::bidi::Direction;
+//! use hb_html::objects::HtmlDocument;
+//! let doc = r#"<div dir="rtl"><p>שלום</p><p dir="auto"><b>123</b> hello</p><bdi>مرحبا</bdi></div>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! assert_eq!(doc.resolved_direction(&[0]), Some(Direction::Rtl));
+//! assert_eq!(doc.resolved_direction(&[0, 0, 0]), Some(Direction::Rtl));
+//! // the digits are not strong, so the direction comes from "hello"
+//! assert_eq!(doc.resolved_direction(&[0, 1]), Some(Direction::Ltr));
+//! assert_eq!(doc.resolved_direction(&[0, 1, 0]), Some(Direction::Ltr));
+//! assert_eq!(doc.resolved_direction(&[0, 2]), Some(Direction::Rtl));
+//! assert_eq!(doc.resolved_direction(&[1]), None);
+//! ```
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag};
+use std::fmt;
+
+/// The direction of text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Left-to-right, eg English.
+    Ltr,
+    /// Right-to-left, eg Arabic and Hebrew.
+    Rtl,
+}
+
+impl Direction {
+    /// Gets the value of the `dir` attribute for the direction.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Ltr => "ltr",
+            Direction::Rtl => "rtl",
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The blocks of characters with a strong right-to-left direction: Hebrew,
+/// Arabic, Syriac, Thaana, NKo and the other scripts near them, and their
+/// presentation forms.
+const RTL_RANGES: [(u32, u32); 5] = [
+    (0x0590, 0x08FF),
+    (0xFB1D, 0xFDFF),
+    (0xFE70, 0xFEFF),
+    (0x10800, 0x10FFF),
+    (0x1E800, 0x1EFFF),
+];
+
+/// Gets the strong direction of a character, or None if it does not have one
+/// (such as digits, punctuation and whitespace). Letters outside the right-to-left
+/// scripts are treated as left-to-right.
+pub fn char_direction(c: char) -> Option<Direction> {
+    let n = c as u32;
+    // the right-to-left and arabic letter marks, and the left-to-right mark
+    if c == '\u{200F}' || c == '\u{061C}' {
+        return Some(Direction::Rtl);
+    }
+    if c == '\u{200E}' {
+        return Some(Direction::Ltr);
+    }
+    if !c.is_alphabetic() {
+        return None;
+    }
+    if RTL_RANGES
+        .iter()
+        .any(|(start, end)| (*start..=*end).contains(&n))
+    {
+        Some(Direction::Rtl)
+    } else {
+        Some(Direction::Ltr)
+    }
+}
+
+/// Gets the direction of the first character with a strong direction in the text.
+pub fn first_strong_direction(text: &str) -> Option<Direction> {
+    text.chars().find_map(char_direction)
+}
+
+/// Gets the direction set by the `dir` attribute, if it is `ltr` or `rtl`.
+fn explicit_direction(tag: &HtmlTag) -> Option<Direction> {
+    match tag.attributes.get("dir") {
+        Some(dir) if dir.trim().eq_ignore_ascii_case("ltr") => Some(Direction::Ltr),
+        Some(dir) if dir.trim().eq_ignore_ascii_case("rtl") => Some(Direction::Rtl),
+        _ => None,
+    }
+}
+
+/// Returns true if the element takes its direction from its text.
+fn is_auto(tag: &HtmlTag) -> bool {
+    match tag.attributes.get("dir") {
+        Some(dir) => dir.trim().eq_ignore_ascii_case("auto"),
+        None => tag.tag.eq_ignore_ascii_case("bdi"),
+    }
+}
+
+/// Returns true if the element has a `dir` of `ltr`, `rtl` or `auto`.
+fn has_valid_dir(tag: &HtmlTag) -> bool {
+    tag.attributes.get("dir").is_some_and(|dir| {
+        ["ltr", "rtl", "auto"]
+            .iter()
+            .any(|d| dir.trim().eq_ignore_ascii_case(d))
+    })
+}
+
+fn first_strong_in_nodes(nodes: &[HtmlNode]) -> Option<Direction> {
+    nodes.iter().find_map(|node| match node {
+        HtmlNode::Text(t) => first_strong_direction(t),
+        HtmlNode::Tag(t) => {
+            let skipped = ["bdi", "script", "style", "textarea"]
+                .iter()
+                .any(|s| t.tag.eq_ignore_ascii_case(s));
+            if skipped || has_valid_dir(t) {
+                None
+            } else {
+                first_strong_in_nodes(&t.contents)
+            }
+        }
+        _ => None,
+    })
+}
+
+impl HtmlTag {
+    /// Gets the direction the element would have with `dir="auto"`, from the first
+    /// strong character of its text (or the `value` of an `<input>` or the text of
+    /// a `<textarea>`). Returns None if there is no strong character, in which case
+    /// the element has the direction of its parent.
+    pub fn auto_direction(&self) -> Option<Direction> {
+        if self.tag.eq_ignore_ascii_case("input") {
+            return self
+                .attributes
+                .get("value")
+                .and_then(|v| first_strong_direction(v));
+        }
+        if self.tag.eq_ignore_ascii_case("textarea") {
+            return first_strong_direction(&self.text());
+        }
+        first_strong_in_nodes(&self.contents)
+    }
+}
+
+impl HtmlDocument {
+    /// Works out the direction of the node at the path, following the `dir`
+    /// attributes of it and its ancestors. Text and other nodes have the direction
+    /// of their parent. Returns None if the path does not point at a node.
+    pub fn resolved_direction(&self, path: &[usize]) -> Option<Direction> {
+        self.node_at(path)?;
+        let mut direction = Direction::Ltr;
+        let mut nodes = &self.nodes;
+        for i in path {
+            if let HtmlNode::Tag(tag) = &nodes[*i] {
+                direction = match explicit_direction(tag) {
+                    Some(explicit) => explicit,
+                    None if is_auto(tag) => tag.auto_direction().unwrap_or(direction),
+                    None => direction,
+                };
+                nodes = &tag.contents;
+            }
+        }
+        Some(direction)
+    }
+}
+
+#[cfg(test)]
+mod bidi_tests {
+    use super::*;
+
+    #[test]
+    fn char_direction_test() {
+        assert_eq!(char_direction('a'), Some(Direction::Ltr));
+        assert_eq!(char_direction('ж'), Some(Direction::Ltr));
+        assert_eq!(char_direction('א'), Some(Direction::Rtl));
+        assert_eq!(char_direction('ب'), Some(Direction::Rtl));
+        assert_eq!(char_direction('\u{200F}'), Some(Direction::Rtl));
+        assert_eq!(char_direction('\u{200E}'), Some(Direction::Ltr));
+        // arabic-indic digits and punctuation are not strong
+        assert_eq!(char_direction('٣'), None);
+        assert_eq!(char_direction('1'), None);
+        assert_eq!(char_direction(' '), None);
+        assert_eq!(
+            first_strong_direction("12, (שלום) abc"),
+            Some(Direction::Rtl)
+        );
+        assert_eq!(first_strong_direction("42!"), None);
+    }
+
+    #[test]
+    fn resolved_direction_test() {
+        let doc = concat!(
+            r#"<html dir="rtl"><body><div dir="auto"><span dir="ltr">abc</span><bdi>abc</bdi>"#,
+            r#"<script>x</script> 12 </div><p dir="auto"><input dir="auto" value="שלום"></p>"#,
+            r#"<p dir="bogus">x</p><bdi>x</bdi></body></html>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let dir = |path: &[usize]| doc.resolved_direction(path);
+        // the span, bdi and script are skipped and there is nothing strong left,
+        // so the div keeps the direction of its parent
+        assert_eq!(dir(&[0, 0, 0]), Some(Direction::Rtl));
+        assert_eq!(dir(&[0, 0, 0, 0]), Some(Direction::Ltr));
+        assert_eq!(dir(&[0, 0, 0, 1]), Some(Direction::Ltr));
+        assert_eq!(dir(&[0, 0, 1, 0]), Some(Direction::Rtl));
+        assert_eq!(dir(&[0, 0, 2]), Some(Direction::Rtl));
+        assert_eq!(dir(&[0, 0, 3]), Some(Direction::Ltr));
+        assert_eq!(dir(&[0, 0, 3, 0]), Some(Direction::Ltr));
+        assert_eq!(dir(&[0, 0, 9]), None);
+        assert_eq!(Direction::Rtl.to_string(), "rtl");
+    }
+}
@@ -0,0 +1,235 @@
+//! Removing the parts of a page which are repeated across a site, such as the
+//! navigation, footer and cookie banner, leaving the content of the page.
+//!
+//! A [`Boilerplate`] is trained on pages from the same site. It counts the pages
+//! each element appears on, where elements are the same if they have the same
+//! structure and text (see [`HtmlTag::structural_hash`](crate::objects::HtmlTag::structural_hash)).
+//! Elements which appear on most of the pages are boilerplate, and
+//! [`Boilerplate::strip_boilerplate`] removes them from a page, which is done
+//! before extracting the text of an article so that the menus are not part of it.
+//!
+//! The `<html>`, `<head>` and `<body>` elements and the contents of the `<head>`
+//! are never removed.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::boilerplate::BoilerplateOptions;
+//! use hb_html::workspace::Workspace;
+//! let nav = r#"<nav><a href="/">Home</a><a href="/blog">Blog</a></nav>"#;
+//! let mut site = Workspace::new();
+//! for (name, text) in [("a.html", "First"), ("b.html", "Second"), ("c.html", "Third")] {
+//!     site.insert_source(name, &format!("<body>{}<main><p>{}</p></main></body>", nav, text));
+//! }
+//! let boilerplate = site.boilerplate(&BoilerplateOptions::new());
+//! let mut page = site.get("b.html").unwrap().clone();
+//! assert_eq!(boilerplate.strip_boilerplate(&mut page), 1);
+//! assert_eq!(page.to_html_string(), "<body><main><p>Second</p></main></body>");
+//! ```
+use crate::objects::{HtmlDocument, HtmlNode, NodePath};
+use crate::structure::element_hashes;
+use crate::workspace::Workspace;
+use std::collections::{HashMap, HashSet};
+
+/// The elements which are never boilerplate, as they hold the whole page.
+const PAGE_TAGS: [&str; 3] = ["html", "head", "body"];
+
+/// Options for finding boilerplate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoilerplateOptions {
+    /// The share of the pages an element must appear on to be boilerplate, from 0
+    /// to 1.
+    pub min_share: f64,
+    /// The number of pages an element must appear on to be boilerplate, so that a
+    /// single page does not make everything on it boilerplate.
+    pub min_pages: usize,
+    /// The number of elements in a boilerplate element, including itself, so that
+    /// common small elements such as `<hr>` are left alone.
+    pub min_elements: usize,
+}
+
+impl Default for BoilerplateOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BoilerplateOptions {
+    pub fn new() -> BoilerplateOptions {
+        BoilerplateOptions {
+            min_share: 0.6,
+            min_pages: 2,
+            min_elements: 2,
+        }
+    }
+
+    pub fn min_share(mut self, min_share: f64) -> BoilerplateOptions {
+        self.min_share = min_share;
+        self
+    }
+
+    pub fn min_pages(mut self, min_pages: usize) -> BoilerplateOptions {
+        self.min_pages = min_pages;
+        self
+    }
+
+    pub fn min_elements(mut self, min_elements: usize) -> BoilerplateOptions {
+        self.min_elements = min_elements;
+        self
+    }
+}
+
+/// The number of pages of a site each element appears on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Boilerplate {
+    options: BoilerplateOptions,
+    pages: usize,
+    counts: HashMap<u64, usize>,
+}
+
+impl Boilerplate {
+    pub fn new(options: BoilerplateOptions) -> Boilerplate {
+        Boilerplate {
+            options,
+            pages: 0,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Trains on the pages.
+    pub fn learn<'a, I: IntoIterator<Item = &'a HtmlDocument>>(
+        pages: I,
+        options: BoilerplateOptions,
+    ) -> Boilerplate {
+        let mut boilerplate = Boilerplate::new(options);
+        for page in pages {
+            boilerplate.add_page(page);
+        }
+        boilerplate
+    }
+
+    /// Counts the elements of another page. An element which appears more than
+    /// once on the page is only counted once.
+    pub fn add_page(&mut self, page: &HtmlDocument) {
+        let hashes: HashSet<u64> = element_hashes(page, true)
+            .into_iter()
+            .filter(|(_, _, elements)| *elements >= self.options.min_elements)
+            .map(|(_, hash, _)| hash)
+            .collect();
+        for hash in hashes {
+            *self.counts.entry(hash).or_default() += 1;
+        }
+        self.pages += 1;
+    }
+
+    /// The number of pages trained on.
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+
+    fn is_boilerplate(&self, hash: u64, elements: usize) -> bool {
+        let count = self.counts.get(&hash).copied().unwrap_or_default();
+        elements >= self.options.min_elements
+            && count >= self.options.min_pages
+            && count as f64 >= self.options.min_share * self.pages as f64
+    }
+
+    /// Finds the boilerplate elements of the page, in document order. Elements
+    /// inside a boilerplate element are not included.
+    pub fn find(&self, page: &HtmlDocument) -> Vec<NodePath> {
+        let mut found: Vec<NodePath> = vec![];
+        let mut kept: Vec<NodePath> = vec![];
+        for (path, hash, elements) in element_hashes(page, true) {
+            if found.iter().chain(&kept).any(|p| path.starts_with(p)) {
+                continue;
+            }
+            let name = match page.node_at(&path) {
+                Some(HtmlNode::Tag(tag)) => tag.tag.to_ascii_lowercase(),
+                _ => continue,
+            };
+            if name == "head" {
+                kept.push(path);
+            } else if !PAGE_TAGS.contains(&name.as_str()) && self.is_boilerplate(hash, elements) {
+                found.push(path);
+            }
+        }
+        found
+    }
+
+    /// Removes the boilerplate elements from the page, returning how many were
+    /// removed.
+    pub fn strip_boilerplate(&self, page: &mut HtmlDocument) -> usize {
+        let found = self.find(page);
+        // later siblings are removed first so the paths of the others stay the same
+        for path in found.iter().rev() {
+            if let Some(siblings) = page.siblings_at_mut(path) {
+                siblings.remove(path[path.len() - 1]);
+            }
+        }
+        found.len()
+    }
+}
+
+impl Workspace {
+    /// Trains a [`Boilerplate`] on all of the documents in the workspace.
+    pub fn boilerplate(&self, options: &BoilerplateOptions) -> Boilerplate {
+        Boilerplate::learn(
+            self.names().filter_map(|name| self.get(name)),
+            options.clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod boilerplate_tests {
+    use super::*;
+
+    fn page(content: &str, banner: bool) -> HtmlDocument {
+        let banner = if banner {
+            r#"<div class="cookies"><p>We use cookies</p><button>OK</button></div>"#
+        } else {
+            ""
+        };
+        format!(
+            concat!(
+                "<html><head><title>Site</title></head><body>{}",
+                r#"<nav><ul><li><a href="/">Home</a></li></ul></nav><main>{}</main>"#,
+                "<footer><p>(c) <b>Site</b></p></footer></body></html>"
+            ),
+            banner, content
+        )
+        .parse::<HtmlDocument>()
+        .unwrap()
+    }
+
+    #[test]
+    fn strip_boilerplate_test() {
+        let pages = [
+            page("<h1>One</h1><p>A <b>b</b></p>", true),
+            page("<h1>Two</h1><p>C <b>d</b></p>", true),
+            page("<h1>Three</h1>", false),
+            page("<h1>Four</h1><p>A <b>b</b></p>", true),
+        ];
+        let boilerplate = Boilerplate::learn(&pages, BoilerplateOptions::new());
+        assert_eq!(boilerplate.pages(), 4);
+        // the banner is on 3 of the 4 pages, and the paragraph on only 2
+        assert_eq!(
+            boilerplate.find(&pages[0]),
+            vec![vec![0, 1, 0], vec![0, 1, 1], vec![0, 1, 3]]
+        );
+        let mut first = pages[0].clone();
+        assert_eq!(boilerplate.strip_boilerplate(&mut first), 3);
+        assert_eq!(
+            first.to_html_string(),
+            concat!(
+                "<html><head><title>Site</title></head>",
+                "<body><main><h1>One</h1><p>A <b>b</b></p></main></body></html>"
+            )
+        );
+        let strict = Boilerplate::learn(&pages, BoilerplateOptions::new().min_share(1.0));
+        assert_eq!(strict.find(&pages[0]), vec![vec![0, 1, 1], vec![0, 1, 3]]);
+        // a page trained on its own has no boilerplate
+        let single = Boilerplate::learn(&pages[..1], BoilerplateOptions::new());
+        assert!(single.find(&pages[0]).is_empty());
+    }
+}
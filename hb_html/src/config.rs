@@ -0,0 +1,289 @@
+//! Loads [`Pipeline`] and [`SanitizePolicy`] definitions from TOML or JSON files,
+//! so sanitization allowlists and rewrite rules can be changed without recompiling.
+//! This module is only available with the `serde` feature.
+//!
+//! Any sanitizer policy fields which are left out of the file use the values from
+//! [`SanitizePolicy::default`].
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::config::PipelineConfig;
+//! use hb_html::objects::HtmlDocument;
+//! let config = PipelineConfig::from_toml_str(
+//!     r#"
+//! stop_on_error = true
+//!
+//! [[passes]]
+//! type = "sanitize"
+//! strip_comments = false
+//!
+//! [[passes]]
+//! type = "rewrite-urls"
+//! rules = [{ from_prefix = "/img/", to_prefix = "https://cdn.example/img/" }]
+//! "#,
+//! )
+//! .unwrap();
+//! let mut doc = r#"<img src="/img/a.png">"#.parse::<HtmlDocument>().unwrap();
+//! config.build().run(&mut doc).unwrap();
+//! assert_eq!(
+//!     doc.nodes[0].to_html_string(),
+//!     r#"<img src="https://cdn.example/img/a.png"></img>"#
+//! );
+//! ```
+use crate::error::ConfigError;
+use crate::pipeline::{Pipeline, PipelineOptions};
+use crate::rewrite::{RewriteUrls, UrlRewriteRule};
+use crate::sanitize::{Sanitize, SanitizePolicy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The definition of a single pass in a [`PipelineConfig`], selected by the `type` key.
+/// A key which is not one of the settings of the pass is an error, so a misspelled
+/// setting is not silently left at its default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum PassConfig {
+    Sanitize(Box<SanitizePolicy>),
+    RewriteUrls {
+        /// The attributes to rewrite, defaults to [`crate::rewrite::URL_ATTRIBUTES`].
+        #[serde(default)]
+        attributes: Option<Vec<String>>,
+        #[serde(default)]
+        rules: Vec<UrlRewriteRule>,
+    },
+}
+
+impl PassConfig {
+    /// Adds the pass described by the config to the end of the pipeline.
+    pub fn add_to(&self, pipeline: Pipeline) -> Pipeline {
+        match self {
+            PassConfig::Sanitize(policy) => pipeline.add(Sanitize::new(policy.as_ref().clone())),
+            PassConfig::RewriteUrls { attributes, rules } => {
+                let mut pass = RewriteUrls::new();
+                if let Some(attributes) = attributes {
+                    pass = pass.attributes(attributes.clone());
+                }
+                pass.rules = rules.clone();
+                pipeline.add(pass)
+            }
+        }
+    }
+}
+
+/// The definition of a [`Pipeline`], the pipeline options are at the top level of
+/// the file alongside the list of passes. A key which is not one of these is an
+/// error.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "PipelineConfigFile", into = "PipelineConfigFile")]
+pub struct PipelineConfig {
+    pub options: PipelineOptions,
+    pub passes: Vec<PassConfig>,
+}
+
+/// How a [`PipelineConfig`] is written in a file. The options are listed here
+/// rather than flattened into it, as serde can not reject the unknown keys of a
+/// struct with a flattened field.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PipelineConfigFile {
+    stop_on_error: bool,
+    settings: HashMap<String, String>,
+    passes: Vec<PassConfig>,
+}
+
+impl Default for PipelineConfigFile {
+    fn default() -> Self {
+        PipelineConfig::default().into()
+    }
+}
+
+impl From<PipelineConfigFile> for PipelineConfig {
+    fn from(file: PipelineConfigFile) -> Self {
+        PipelineConfig {
+            options: PipelineOptions {
+                stop_on_error: file.stop_on_error,
+                settings: file.settings,
+            },
+            passes: file.passes,
+        }
+    }
+}
+
+impl From<PipelineConfig> for PipelineConfigFile {
+    fn from(config: PipelineConfig) -> Self {
+        PipelineConfigFile {
+            stop_on_error: config.options.stop_on_error,
+            settings: config.options.settings,
+            passes: config.passes,
+        }
+    }
+}
+
+/// The formats which config files can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Works out the format from the extension of the path.
+    pub fn from_path(path: &Path) -> Result<ConfigFormat, ConfigError> {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("json") => Ok(ConfigFormat::Json),
+            _ => Err(ConfigError::with_msg(format!(
+                "could not work out the format of {} as it does not end in .toml or .json",
+                path.display()
+            ))),
+        }
+    }
+}
+
+fn parse_str<T: serde::de::DeserializeOwned>(
+    s: &str,
+    format: ConfigFormat,
+) -> Result<T, ConfigError> {
+    match format {
+        ConfigFormat::Toml => toml::from_str(s)
+            .map_err(|e| ConfigError::with_msg(format!("invalid TOML config: {}", e))),
+        ConfigFormat::Json => serde_json::from_str(s)
+            .map_err(|e| ConfigError::with_msg(format!("invalid JSON config: {}", e))),
+    }
+}
+
+fn load_file<T: serde::de::DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, ConfigError> {
+    let path = path.as_ref();
+    let format = ConfigFormat::from_path(path)?;
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        ConfigError::with_msg(format!("could not read {} because {}", path.display(), e))
+    })?;
+    parse_str(&contents, format).map_err(|e| {
+        ConfigError::with_msg(format!("could not load {} because {}", path.display(), e))
+    })
+}
+
+impl PipelineConfig {
+    pub fn from_toml_str(s: &str) -> Result<PipelineConfig, ConfigError> {
+        parse_str(s, ConfigFormat::Toml)
+    }
+
+    pub fn from_json_str(s: &str) -> Result<PipelineConfig, ConfigError> {
+        parse_str(s, ConfigFormat::Json)
+    }
+
+    /// Loads the config from a .toml or .json file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PipelineConfig, ConfigError> {
+        load_file(path)
+    }
+
+    /// Creates the pipeline described by the config.
+    pub fn build(&self) -> Pipeline {
+        self.passes
+            .iter()
+            .fold(Pipeline::new().options(self.options.clone()), |p, pass| {
+                pass.add_to(p)
+            })
+    }
+}
+
+impl SanitizePolicy {
+    /// Loads a sanitizer policy from a .toml or .json file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SanitizePolicy, ConfigError> {
+        load_file(path)
+    }
+}
+
+/// Loads the pipeline described by the .toml or .json file.
+pub fn load_pipeline<P: AsRef<Path>>(path: P) -> Result<Pipeline, ConfigError> {
+    Ok(PipelineConfig::load(path)?.build())
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use crate::objects::HtmlDocument;
+
+    #[test]
+    fn config_json_test() {
+        let config = PipelineConfig::from_json_str(
+            r#"{
+                "stop_on_error": false,
+                "settings": {"site": "example"},
+                "passes": [
+                    {"type": "sanitize", "allowed_tags": ["p"], "strip_comments": true},
+                    {"type": "rewrite-urls", "attributes": ["data-src"], "rules": [{"from_prefix": "a", "to_prefix": "b"}]}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(!config.options.stop_on_error);
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(PipelineConfig::from_json_str(&json).unwrap(), config);
+        // keys which are left out take their default values
+        assert_eq!(
+            PipelineConfig::from_json_str("{}").unwrap(),
+            PipelineConfig::default()
+        );
+        assert_eq!(config.options.settings["site"], "example");
+        match &config.passes[0] {
+            PassConfig::Sanitize(policy) => {
+                assert_eq!(policy.allowed_tags.len(), 1);
+                // fields that are not provided come from the default policy
+                assert!(policy.removed_with_contents.contains("script"));
+            }
+            p => panic!("expected sanitize pass but found {:?}", p),
+        }
+        let mut pipeline = config.build();
+        assert_eq!(pipeline.pass_names(), vec!["sanitize", "rewrite-urls"]);
+        let mut doc = "<div><p>x</p></div>".parse::<HtmlDocument>().unwrap();
+        pipeline.run(&mut doc).unwrap();
+        assert_eq!(doc.nodes[0].to_html_string(), "<p>x</p>");
+    }
+
+    #[test]
+    fn config_errors_test() {
+        assert!(format!(
+            "{}",
+            PipelineConfig::from_json_str(r#"{"passes": [{"type": "unknown"}]}"#).unwrap_err()
+        )
+        .starts_with("Config Error: 'invalid JSON config: unknown variant `unknown`"));
+        // a misspelled setting is rejected rather than left at its default
+        for passes in [
+            r#"[{"type": "sanitize", "allowed_tag": ["p"]}]"#,
+            r#"[{"type": "rewrite-urls", "rule": []}]"#,
+            r#"[{"type": "rewrite-urls", "rules": [{"from": "a", "to_prefix": "b"}]}]"#,
+        ] {
+            let config = format!(r#"{{"passes": {}}}"#, passes);
+            let error = format!("{}", PipelineConfig::from_json_str(&config).unwrap_err());
+            assert!(error.contains("unknown field"), "{}", error);
+        }
+        // and so is an unknown key at the top level
+        let error = PipelineConfig::from_toml_str("stop_on_eror = false\n").unwrap_err();
+        assert!(format!("{}", error).contains("unknown field `stop_on_eror`"));
+        let error =
+            parse_str::<SanitizePolicy>("allowed_tag = [\"b\"]", ConfigFormat::Toml).unwrap_err();
+        assert!(format!("{}", error).contains("unknown field `allowed_tag`"));
+        assert_eq!(
+            format!("{}", PipelineConfig::load("pipeline.yaml").unwrap_err()),
+            "Config Error: 'could not work out the format of pipeline.yaml as it does not end in .toml or .json'"
+        );
+    }
+
+    #[test]
+    fn config_load_file_test() {
+        let path = std::env::temp_dir().join(format!("hb_html_policy_{}.toml", std::process::id()));
+        std::fs::write(&path, "allowed_tags = [\"b\"]\nstrip_comments = false\n").unwrap();
+        let policy = SanitizePolicy::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(policy.allowed_tags.contains("b"));
+        assert!(!policy.strip_comments);
+    }
+}
@@ -0,0 +1,723 @@
+//! Tracking how closely the parser matches browsers on real pages.
+//!
+//! A corpus is a set of pages, each with the tree a browser built for it exported
+//! as JSON. [`CorpusRunner`] parses each page, compares the document with the
+//! browser's tree and reports how often each construct diverges, so a run can be
+//! saved and compared with later runs using [`CorpusReport::regressions`]. This
+//! module is only available with the `serde` feature.
+//!
+//! The trees are exported in the format of [`BrowserTree`], which a headless
+//! browser can produce with:
+//!
+//! ```js
+//! const dump = (n) =>
+//!   n.nodeType === 1
+//!     ? { type: "element", tag: n.localName,
+//!         attributes: Object.fromEntries([...n.attributes].map((a) => [a.name, a.value])),
+//!         children: [...(n.content ?? n).childNodes].map(dump).filter(Boolean) }
+//!     : n.nodeType === 3 ? { type: "text", text: n.data }
+//!     : n.nodeType === 8 ? { type: "comment", text: n.data }
+//!     : null;
+//! JSON.stringify({ doctype: document.doctype?.name ?? null,
+//!                  children: [dump(document.documentElement)] });
+//! ```
+//!
+//! Browsers add `<html>`, `<head>`, `<body>` and `<tbody>` elements when a page
+//! leaves them out, which this crate does not. By default these are removed from
+//! the browser's tree, keeping their contents, when the parsed document has no
+//! element with the same name.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::conformance::{BrowserTree, CorpusCase, CorpusRunner};
+//! let expected = BrowserTree::from_json_str(
+//!     r#"{"doctype": null, "children": [{"type": "element", "tag": "html", "children": [
+//!         {"type": "element", "tag": "head"},
+//!         {"type": "element", "tag": "body", "children": [
+//!             {"type": "element", "tag": "p", "attributes": {"class": "a"},
+//!              "children": [{"type": "text", "text": "1 < 2"}]}
+//!         ]}
+//!     ]}]}"#,
+//! )
+//! .unwrap();
+//! let case = CorpusCase::new("escaped", r#"<p class="a">1 &lt; 2</p>"#, expected);
+//! let report = CorpusRunner::new().run(&[case]);
+//! // the text is decoded, as it is by the browser, so the whole page matches
+//! assert_eq!(report.constructs["p"].diverged, 0);
+//! assert_eq!(report.divergence_rate("#text"), 0.0);
+//! assert_eq!(report.matching_cases, 1);
+//! ```
+use crate::error::ConfigError;
+use crate::objects::{HtmlDocument, HtmlNode, NodePath};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// The elements which browsers add when they are left out of a page.
+const IMPLIED_ELEMENTS: [&str; 4] = ["html", "head", "body", "tbody"];
+
+/// A node of a tree exported from a browser.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum DomNode {
+    Element {
+        tag: String,
+        #[serde(default)]
+        attributes: BTreeMap<String, String>,
+        #[serde(default)]
+        children: Vec<DomNode>,
+    },
+    Text {
+        text: String,
+    },
+    Comment {
+        text: String,
+    },
+}
+
+impl DomNode {
+    /// Converts a node of a parsed document, with the tag and attribute names in
+    /// lower case as a browser has them. Template tags are text to a browser.
+    pub fn from_html(node: &HtmlNode) -> DomNode {
+        match node {
+            HtmlNode::Tag(tag) => {
+                let mut attributes: BTreeMap<String, String> = tag
+                    .attributes
+                    .iter()
+                    .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+                    .collect();
+                for name in ["id", "class"] {
+                    if let Some(value) = tag.get_attribute(name) {
+                        attributes.insert(name.to_owned(), value);
+                    }
+                }
+                DomNode::Element {
+                    tag: tag.tag.to_ascii_lowercase(),
+                    attributes,
+                    children: tag.contents.iter().map(DomNode::from_html).collect(),
+                }
+            }
+            HtmlNode::Text(text) | HtmlNode::Template(text) => DomNode::Text { text: text.clone() },
+            HtmlNode::Comment(text) => DomNode::Comment { text: text.clone() },
+        }
+    }
+
+    /// Gets the name the node is counted under in a [`CorpusReport`]: the tag of
+    /// an element, `#text` or `#comment`.
+    pub fn construct(&self) -> &str {
+        match self {
+            DomNode::Element { tag, .. } => tag,
+            DomNode::Text { .. } => "#text",
+            DomNode::Comment { .. } => "#comment",
+        }
+    }
+}
+
+/// The tree a browser built for a page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrowserTree {
+    /// The name of the doctype, eg `html`, if the page had one.
+    #[serde(default)]
+    pub doctype: Option<String>,
+    #[serde(default)]
+    pub children: Vec<DomNode>,
+}
+
+impl BrowserTree {
+    pub fn from_json_str(s: &str) -> Result<BrowserTree, ConfigError> {
+        serde_json::from_str(s)
+            .map_err(|e| ConfigError::with_msg(format!("invalid browser tree: {}", e)))
+    }
+
+    /// Converts a parsed document, so it can be compared with a browser's tree.
+    pub fn from_document(doc: &HtmlDocument) -> BrowserTree {
+        let doctype = doc.doctype.split_whitespace().next();
+        BrowserTree {
+            doctype: doctype.map(|d| d.to_ascii_lowercase()),
+            children: doc.nodes.iter().map(DomNode::from_html).collect(),
+        }
+    }
+}
+
+/// A page of the corpus and the tree a browser built for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorpusCase {
+    pub name: String,
+    pub html: String,
+    pub expected: BrowserTree,
+}
+
+impl CorpusCase {
+    pub fn new<N: Into<String>, H: Into<String>>(
+        name: N,
+        html: H,
+        expected: BrowserTree,
+    ) -> CorpusCase {
+        CorpusCase {
+            name: name.into(),
+            html: html.into(),
+            expected,
+        }
+    }
+}
+
+/// Loads the corpus in the directory, where each `<name>.html` page has its tree
+/// in `<name>.json`. The cases are sorted by name, and pages without a tree are
+/// an error so they are not silently left out.
+pub fn load_corpus<P: AsRef<Path>>(dir: P) -> Result<Vec<CorpusCase>, ConfigError> {
+    let dir = dir.as_ref();
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|e| {
+            ConfigError::with_msg(format!("could not read {} because {}", path.display(), e))
+        })
+    };
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        ConfigError::with_msg(format!("could not read {} because {}", dir.display(), e))
+    })?;
+    let mut cases = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("html") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let tree_path = path.with_extension("json");
+        let expected = BrowserTree::from_json_str(&read(&tree_path)?).map_err(|e| {
+            ConfigError::with_msg(format!(
+                "could not load {} because {}",
+                tree_path.display(),
+                e
+            ))
+        })?;
+        cases.push(CorpusCase::new(name, read(&path)?, expected));
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cases)
+}
+
+/// How the parsed document differs from the browser's tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DivergenceKind {
+    /// The page could not be parsed.
+    ParseError(String),
+    Doctype {
+        expected: Option<String>,
+        found: Option<String>,
+    },
+    /// The browser has a node which the document does not.
+    MissingNode,
+    /// The document has a node which the browser does not.
+    ExtraNode,
+    /// The nodes are different elements or different kinds of node.
+    DifferentNode {
+        expected: String,
+        found: String,
+    },
+    Attributes {
+        expected: BTreeMap<String, String>,
+        found: BTreeMap<String, String>,
+    },
+    Text {
+        expected: String,
+        found: String,
+    },
+}
+
+/// A place where a page was parsed differently to the browser.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Divergence {
+    /// The name of the [`CorpusCase`].
+    pub case: String,
+    /// The path of the node in the browser's tree, after the implied elements are
+    /// removed, or in the document for an [`DivergenceKind::ExtraNode`].
+    pub path: NodePath,
+    pub construct: String,
+    pub kind: DivergenceKind,
+}
+
+/// The number of times a construct was found in the browsers' trees and the
+/// number of those which the documents did not match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConstructStats {
+    pub seen: usize,
+    pub diverged: usize,
+}
+
+impl ConstructStats {
+    /// Gets the share of the construct which diverged, from 0 to 1.
+    pub fn rate(&self) -> f64 {
+        if self.seen == 0 {
+            0.0
+        } else {
+            self.diverged as f64 / self.seen as f64
+        }
+    }
+}
+
+/// The result of running a corpus, which can be stored as JSON to compare with
+/// later runs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CorpusReport {
+    pub cases: usize,
+    /// The number of cases without any divergences.
+    pub matching_cases: usize,
+    /// The stats of each construct, keyed by [`DomNode::construct`], with
+    /// `#document` for parsing each page and `#doctype` for pages with one.
+    pub constructs: BTreeMap<String, ConstructStats>,
+    pub divergences: Vec<Divergence>,
+}
+
+impl CorpusReport {
+    /// Gets the divergence rate of the construct, which is 0 if it was not seen.
+    pub fn divergence_rate(&self, construct: &str) -> f64 {
+        self.constructs
+            .get(construct)
+            .map_or(0.0, ConstructStats::rate)
+    }
+
+    /// Finds the constructs which diverge more often than in the earlier report.
+    pub fn regressions(&self, baseline: &CorpusReport) -> Vec<String> {
+        self.constructs
+            .iter()
+            .filter(|(name, stats)| stats.rate() > baseline.divergence_rate(name))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Writes a line for each construct which diverged, worst first, eg
+    /// `table: 3/10 (30.0%)`.
+    pub fn summary(&self) -> String {
+        let mut diverged: Vec<(&String, &ConstructStats)> = self
+            .constructs
+            .iter()
+            .filter(|(_, stats)| stats.diverged > 0)
+            .collect();
+        diverged.sort_by(|a, b| b.1.rate().total_cmp(&a.1.rate()).then(a.0.cmp(b.0)));
+        let mut summary = format!("{}/{} cases match\n", self.matching_cases, self.cases);
+        for (name, stats) in diverged {
+            summary.push_str(&format!(
+                "{}: {}/{} ({:.1}%)\n",
+                name,
+                stats.diverged,
+                stats.seen,
+                stats.rate() * 100.0
+            ));
+        }
+        summary
+    }
+
+    fn record(&mut self, construct: &str, diverged: bool) {
+        let stats = self.constructs.entry(construct.to_owned()).or_default();
+        stats.seen += 1;
+        if diverged {
+            stats.diverged += 1;
+        }
+    }
+}
+
+/// Parses the pages of a corpus and compares them with the browsers' trees.
+///
+/// The children of two elements are compared in order, so a missing node makes
+/// the nodes after it diverge too. The contents of an element are not compared
+/// when it is a different element to the browser's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorpusRunner {
+    /// Leave out text which is only whitespace, which browsers keep between
+    /// elements.
+    pub ignore_whitespace_text: bool,
+    pub ignore_comments: bool,
+    /// Remove the elements browsers add when they are left out of a page.
+    pub implied_elements: bool,
+}
+
+impl Default for CorpusRunner {
+    fn default() -> Self {
+        CorpusRunner {
+            ignore_whitespace_text: true,
+            ignore_comments: false,
+            implied_elements: true,
+        }
+    }
+}
+
+impl CorpusRunner {
+    pub fn new() -> CorpusRunner {
+        CorpusRunner::default()
+    }
+
+    pub fn ignore_whitespace_text(mut self, ignore_whitespace_text: bool) -> CorpusRunner {
+        self.ignore_whitespace_text = ignore_whitespace_text;
+        self
+    }
+
+    pub fn ignore_comments(mut self, ignore_comments: bool) -> CorpusRunner {
+        self.ignore_comments = ignore_comments;
+        self
+    }
+
+    pub fn implied_elements(mut self, implied_elements: bool) -> CorpusRunner {
+        self.implied_elements = implied_elements;
+        self
+    }
+
+    /// Parses and compares each case.
+    pub fn run(&self, cases: &[CorpusCase]) -> CorpusReport {
+        let mut report = CorpusReport::default();
+        for case in cases {
+            self.run_case(case, &mut report);
+        }
+        report
+    }
+
+    /// Parses and compares the case, adding the result to the report.
+    pub fn run_case(&self, case: &CorpusCase, report: &mut CorpusReport) {
+        let start = report.divergences.len();
+        report.cases += 1;
+        let mut comparison = Comparison {
+            case: &case.name,
+            report,
+        };
+        match case.html.parse::<HtmlDocument>() {
+            Ok(doc) => {
+                let found = BrowserTree::from_document(&doc);
+                if case.expected.doctype.is_some() || found.doctype.is_some() {
+                    let expected = case
+                        .expected
+                        .doctype
+                        .as_ref()
+                        .map(|d| d.to_ascii_lowercase());
+                    let matches = expected == found.doctype;
+                    if !matches {
+                        comparison.diverge(
+                            vec![],
+                            "#doctype",
+                            DivergenceKind::Doctype {
+                                expected,
+                                found: found.doctype.clone(),
+                            },
+                        );
+                    }
+                    comparison.report.record("#doctype", !matches);
+                }
+                let missing = implied_missing(&doc);
+                let implied = self.implied_elements.then_some(&missing);
+                let found = self.prepare(&found.children, None);
+                let expected = self.prepare(&case.expected.children, implied);
+                comparison.report.record("#document", false);
+                comparison.children(&mut vec![], &expected, &found);
+            }
+            Err(e) => {
+                comparison.diverge(
+                    vec![],
+                    "#document",
+                    DivergenceKind::ParseError(e.to_string()),
+                );
+                comparison.report.record("#document", true);
+            }
+        }
+        if report.divergences.len() == start {
+            report.matching_cases += 1;
+        }
+    }
+
+    /// Removes the nodes which are not compared and the implied elements.
+    fn prepare(&self, nodes: &[DomNode], implied: Option<&HashSet<&str>>) -> Vec<DomNode> {
+        let mut prepared = vec![];
+        for node in nodes {
+            match node {
+                DomNode::Element {
+                    tag,
+                    attributes,
+                    children,
+                } => {
+                    let children = self.prepare(children, implied);
+                    if implied.is_some_and(|i| i.contains(tag.as_str())) {
+                        prepared.extend(children);
+                    } else {
+                        prepared.push(DomNode::Element {
+                            tag: tag.clone(),
+                            attributes: attributes.clone(),
+                            children,
+                        });
+                    }
+                }
+                DomNode::Text { text } if self.ignore_whitespace_text && text.trim().is_empty() => {
+                }
+                DomNode::Comment { .. } if self.ignore_comments => {}
+                _ => prepared.push(node.clone()),
+            }
+        }
+        prepared
+    }
+}
+
+/// Gets the implied elements which the document does not have.
+fn implied_missing(doc: &HtmlDocument) -> HashSet<&'static str> {
+    fn remove_found(nodes: &[HtmlNode], missing: &mut HashSet<&'static str>) {
+        for node in nodes {
+            if let HtmlNode::Tag(tag) = node {
+                missing.retain(|name| !tag.tag.eq_ignore_ascii_case(name));
+                remove_found(&tag.contents, missing);
+            }
+        }
+    }
+    let mut missing = IMPLIED_ELEMENTS.into_iter().collect();
+    remove_found(&doc.nodes, &mut missing);
+    missing
+}
+
+struct Comparison<'a> {
+    case: &'a str,
+    report: &'a mut CorpusReport,
+}
+
+impl Comparison<'_> {
+    fn diverge(&mut self, path: NodePath, construct: &str, kind: DivergenceKind) {
+        self.report.divergences.push(Divergence {
+            case: self.case.to_owned(),
+            path,
+            construct: construct.to_owned(),
+            kind,
+        });
+    }
+
+    fn children(&mut self, path: &mut NodePath, expected: &[DomNode], found: &[DomNode]) {
+        for (i, expected_node) in expected.iter().enumerate() {
+            path.push(i);
+            let construct = expected_node.construct().to_owned();
+            let matches = match found.get(i) {
+                Some(found_node) => self.node(path, expected_node, found_node),
+                None => {
+                    self.diverge(path.clone(), &construct, DivergenceKind::MissingNode);
+                    false
+                }
+            };
+            self.report.record(&construct, !matches);
+            path.pop();
+        }
+        for (i, found_node) in found.iter().enumerate().skip(expected.len()) {
+            path.push(i);
+            self.diverge(
+                path.clone(),
+                found_node.construct(),
+                DivergenceKind::ExtraNode,
+            );
+            path.pop();
+        }
+    }
+
+    /// Compares the nodes and their contents, returning whether the node itself
+    /// matches.
+    fn node(&mut self, path: &mut NodePath, expected: &DomNode, found: &DomNode) -> bool {
+        let construct = expected.construct();
+        match (expected, found) {
+            (
+                DomNode::Element {
+                    tag,
+                    attributes,
+                    children,
+                },
+                DomNode::Element {
+                    tag: found_tag,
+                    attributes: found_attributes,
+                    children: found_children,
+                },
+            ) if tag == found_tag => {
+                let matches = attributes == found_attributes;
+                if !matches {
+                    self.diverge(
+                        path.clone(),
+                        construct,
+                        DivergenceKind::Attributes {
+                            expected: attributes.clone(),
+                            found: found_attributes.clone(),
+                        },
+                    );
+                }
+                self.children(path, children, found_children);
+                matches
+            }
+            (DomNode::Text { text }, DomNode::Text { text: found_text })
+            | (DomNode::Comment { text }, DomNode::Comment { text: found_text }) => {
+                let matches = text == found_text;
+                if !matches {
+                    self.diverge(
+                        path.clone(),
+                        construct,
+                        DivergenceKind::Text {
+                            expected: text.clone(),
+                            found: found_text.clone(),
+                        },
+                    );
+                }
+                matches
+            }
+            _ => {
+                self.diverge(
+                    path.clone(),
+                    construct,
+                    DivergenceKind::DifferentNode {
+                        expected: construct.to_owned(),
+                        found: found.construct().to_owned(),
+                    },
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod conformance_tests {
+    use super::*;
+
+    fn element(tag: &str, attributes: &[(&str, &str)], children: Vec<DomNode>) -> DomNode {
+        DomNode::Element {
+            tag: tag.to_owned(),
+            attributes: attributes
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            children,
+        }
+    }
+
+    fn text(text: &str) -> DomNode {
+        DomNode::Text {
+            text: text.to_owned(),
+        }
+    }
+
+    fn page(children: Vec<DomNode>) -> BrowserTree {
+        BrowserTree {
+            doctype: Some("html".to_owned()),
+            children: vec![element(
+                "html",
+                &[],
+                vec![
+                    element("head", &[], vec![]),
+                    text("\n"),
+                    element("body", &[], children),
+                ],
+            )],
+        }
+    }
+
+    #[test]
+    fn matching_case_test() {
+        let case = CorpusCase::new(
+            "simple",
+            "<!DOCTYPE html>\n<DIV ID=main class=\"a b\"><p>x</p>\n</DIV>",
+            page(vec![element(
+                "div",
+                &[("id", "main"), ("class", "a b")],
+                vec![element("p", &[], vec![text("x")]), text("\n")],
+            )]),
+        );
+        let report = CorpusRunner::new().run(std::slice::from_ref(&case));
+        assert_eq!(report.divergences, vec![]);
+        assert_eq!(report.matching_cases, 1);
+        assert_eq!(
+            report.constructs["div"],
+            ConstructStats {
+                seen: 1,
+                diverged: 0
+            }
+        );
+        assert_eq!(report.constructs["#doctype"].seen, 1);
+        // without removing the implied elements the page does not match
+        let report = CorpusRunner::new().implied_elements(false).run(&[case]);
+        assert_eq!(
+            report.divergences[0].kind,
+            DivergenceKind::DifferentNode {
+                expected: "html".to_owned(),
+                found: "div".to_owned()
+            }
+        );
+        assert_eq!(report.matching_cases, 0);
+    }
+
+    #[test]
+    fn divergence_test() {
+        let cases = vec![
+            CorpusCase::new(
+                "table",
+                "<table><tr><td>1</td></tr></table><p a=1></p>",
+                page(vec![
+                    element(
+                        "table",
+                        &[],
+                        vec![element(
+                            "tbody",
+                            &[],
+                            vec![element(
+                                "tr",
+                                &[],
+                                vec![element("td", &[], vec![text("1")])],
+                            )],
+                        )],
+                    ),
+                    element("p", &[("a", "2")], vec![]),
+                    element("span", &[], vec![]),
+                ]),
+            ),
+            CorpusCase::new("broken", "<div></p>", page(vec![])),
+        ];
+        let report = CorpusRunner::new().run(&cases);
+        assert_eq!(report.cases, 2);
+        assert_eq!(report.matching_cases, 0);
+        // the tbody is implied, so the table matches
+        assert_eq!(report.divergence_rate("table"), 0.0);
+        assert_eq!(report.divergence_rate("td"), 0.0);
+        assert_eq!(report.divergence_rate("p"), 1.0);
+        assert_eq!(report.divergence_rate("#document"), 0.5);
+        let kinds: Vec<(&str, &DivergenceKind)> = report
+            .divergences
+            .iter()
+            .map(|d| (d.construct.as_str(), &d.kind))
+            .collect();
+        assert!(matches!(
+            kinds[..],
+            [
+                ("#doctype", DivergenceKind::Doctype { .. }),
+                ("p", DivergenceKind::Attributes { .. }),
+                ("span", DivergenceKind::MissingNode),
+                ("#document", DivergenceKind::ParseError(_)),
+            ]
+        ));
+        assert_eq!(report.divergences[2].path, vec![2]);
+        assert!(report.summary().starts_with("0/2 cases match\n"));
+        assert!(report.summary().contains("p: 1/1 (100.0%)\n"));
+
+        let mut baseline = report.clone();
+        baseline.constructs.get_mut("p").unwrap().seen = 2;
+        assert_eq!(report.regressions(&baseline), vec!["p".to_owned()]);
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<CorpusReport>(&json).unwrap(), report);
+    }
+
+    #[test]
+    fn load_corpus_test() {
+        let dir = std::env::temp_dir().join(format!("hb_html_corpus_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.html"), "<p>b</p>").unwrap();
+        std::fs::write(
+            dir.join("b.json"),
+            r#"{"children": [{"type": "element", "tag": "p", "children": [{"type": "text", "text": "b"}]}]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("a.html"), "<p>a</p>").unwrap();
+        std::fs::write(dir.join("a.json"), r#"{"children": []}"#).unwrap();
+        let cases = load_corpus(&dir).unwrap();
+        let names: Vec<&str> = cases.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(CorpusRunner::new().run(&cases).matching_cases, 1);
+        std::fs::write(dir.join("c.html"), "<p>c</p>").unwrap();
+        assert!(load_corpus(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
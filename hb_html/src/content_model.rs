@@ -0,0 +1,612 @@
+//! Which elements can contain which other elements, and which attributes each
+//! element supports.
+//!
+//! The tables cover the commonly used HTML elements and follow the content models
+//! of the HTML standard, simplified to the element categories needed for editor
+//! completion. Unknown elements are treated as allowing any flow content.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::content_model::{allowed_children, attributes};
+//! let children = allowed_children(&["html", "body", "ul"]);
+//! assert_eq!(children, vec!["li", "script", "template"]);
+//! // <a> is transparent, so it allows what its parent allows
+//! assert!(allowed_children(&["p", "a"]).contains(&"em"));
+//! assert!(!allowed_children(&["p", "a"]).contains(&"div"));
+//! assert!(attributes("a").contains(&"href"));
+//! assert!(attributes("a").contains(&"class"));
+//! ```
+use ContentCategory::{Flow, Metadata, Phrasing};
+
+/// The categories of content used by the content models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentCategory {
+    /// Elements which go in the `<head>`, eg `<title>`.
+    Metadata,
+    /// Elements which go in the `<body>`.
+    Flow,
+    /// The text of a document and the elements which mark it up, eg `<em>`.
+    Phrasing,
+}
+
+/// What an element can contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentModel {
+    /// No content and no end tag, eg `<br>`.
+    Empty,
+    /// Only text, eg `<title>` and `<script>`.
+    Text,
+    /// Whatever the parent of the element can contain, eg `<a>`.
+    Transparent,
+    /// Elements in any of the categories.
+    Categories(&'static [ContentCategory]),
+    /// Only the elements listed.
+    Tags(&'static [&'static str]),
+}
+
+/// The content model of an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementInfo {
+    pub tag: &'static str,
+    /// The categories the element belongs to, which decide where it can be used.
+    pub categories: &'static [ContentCategory],
+    pub content: ContentModel,
+    /// The attributes supported by the element, other than the global attributes.
+    pub attributes: &'static [&'static str],
+}
+
+/// The attributes which are supported by every element.
+pub const GLOBAL_ATTRIBUTES: [&str; 22] = [
+    "accesskey",
+    "autocapitalize",
+    "autofocus",
+    "class",
+    "contenteditable",
+    "dir",
+    "draggable",
+    "enterkeyhint",
+    "hidden",
+    "id",
+    "inert",
+    "inputmode",
+    "is",
+    "lang",
+    "nonce",
+    "popover",
+    "role",
+    "slot",
+    "spellcheck",
+    "style",
+    "tabindex",
+    "title",
+];
+
+const FLOW: &[ContentCategory] = &[Flow];
+const FLOW_PHRASING: &[ContentCategory] = &[Flow, Phrasing];
+const ALL: &[ContentCategory] = &[Metadata, Flow, Phrasing];
+const FLOW_CONTENT: ContentModel = ContentModel::Categories(&[Flow]);
+const PHRASING_CONTENT: ContentModel = ContentModel::Categories(&[Phrasing]);
+
+const fn element(
+    tag: &'static str,
+    categories: &'static [ContentCategory],
+    content: ContentModel,
+    attributes: &'static [&'static str],
+) -> ElementInfo {
+    ElementInfo {
+        tag,
+        categories,
+        content,
+        attributes,
+    }
+}
+
+/// The elements which are known, in alphabetical order.
+pub const ELEMENTS: &[ElementInfo] = &[
+    element(
+        "a",
+        FLOW_PHRASING,
+        ContentModel::Transparent,
+        &[
+            "download",
+            "href",
+            "hreflang",
+            "ping",
+            "referrerpolicy",
+            "rel",
+            "target",
+            "type",
+        ],
+    ),
+    element("abbr", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("address", FLOW, FLOW_CONTENT, &[]),
+    element("article", FLOW, FLOW_CONTENT, &[]),
+    element("aside", FLOW, FLOW_CONTENT, &[]),
+    element(
+        "audio",
+        FLOW_PHRASING,
+        ContentModel::Tags(&["source", "track"]),
+        &[
+            "autoplay",
+            "controls",
+            "crossorigin",
+            "loop",
+            "muted",
+            "preload",
+            "src",
+        ],
+    ),
+    element("b", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "base",
+        &[Metadata],
+        ContentModel::Empty,
+        &["href", "target"],
+    ),
+    element("bdi", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("bdo", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("blockquote", FLOW, FLOW_CONTENT, &["cite"]),
+    element("body", &[], FLOW_CONTENT, &[]),
+    element("br", FLOW_PHRASING, ContentModel::Empty, &[]),
+    element(
+        "button",
+        FLOW_PHRASING,
+        PHRASING_CONTENT,
+        &[
+            "disabled",
+            "form",
+            "formaction",
+            "formenctype",
+            "formmethod",
+            "formnovalidate",
+            "formtarget",
+            "name",
+            "type",
+            "value",
+        ],
+    ),
+    element(
+        "canvas",
+        FLOW_PHRASING,
+        ContentModel::Transparent,
+        &["height", "width"],
+    ),
+    element("caption", &[], FLOW_CONTENT, &[]),
+    element("cite", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("code", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("col", &[], ContentModel::Empty, &["span"]),
+    element(
+        "colgroup",
+        &[],
+        ContentModel::Tags(&["col", "template"]),
+        &["span"],
+    ),
+    element("data", FLOW_PHRASING, PHRASING_CONTENT, &["value"]),
+    element("dd", &[], FLOW_CONTENT, &[]),
+    element(
+        "del",
+        FLOW_PHRASING,
+        ContentModel::Transparent,
+        &["cite", "datetime"],
+    ),
+    element("details", FLOW, FLOW_CONTENT, &["name", "open"]),
+    element("dfn", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("dialog", FLOW, FLOW_CONTENT, &["open"]),
+    element("div", FLOW, FLOW_CONTENT, &[]),
+    element(
+        "dl",
+        FLOW,
+        ContentModel::Tags(&["dd", "div", "dt", "script", "template"]),
+        &[],
+    ),
+    element("dt", &[], FLOW_CONTENT, &[]),
+    element("em", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "embed",
+        FLOW_PHRASING,
+        ContentModel::Empty,
+        &["height", "src", "type", "width"],
+    ),
+    element(
+        "fieldset",
+        FLOW,
+        FLOW_CONTENT,
+        &["disabled", "form", "name"],
+    ),
+    element("figcaption", &[], FLOW_CONTENT, &[]),
+    element("figure", FLOW, FLOW_CONTENT, &[]),
+    element("footer", FLOW, FLOW_CONTENT, &[]),
+    element(
+        "form",
+        FLOW,
+        FLOW_CONTENT,
+        &[
+            "accept-charset",
+            "action",
+            "autocomplete",
+            "enctype",
+            "method",
+            "name",
+            "novalidate",
+            "rel",
+            "target",
+        ],
+    ),
+    element("h1", FLOW, PHRASING_CONTENT, &[]),
+    element("h2", FLOW, PHRASING_CONTENT, &[]),
+    element("h3", FLOW, PHRASING_CONTENT, &[]),
+    element("h4", FLOW, PHRASING_CONTENT, &[]),
+    element("h5", FLOW, PHRASING_CONTENT, &[]),
+    element("h6", FLOW, PHRASING_CONTENT, &[]),
+    element("head", &[], ContentModel::Categories(&[Metadata]), &[]),
+    element("header", FLOW, FLOW_CONTENT, &[]),
+    element("hr", FLOW, ContentModel::Empty, &[]),
+    element("html", &[], ContentModel::Tags(&["head", "body"]), &[]),
+    element("i", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "iframe",
+        FLOW_PHRASING,
+        ContentModel::Empty,
+        &[
+            "allow",
+            "allowfullscreen",
+            "height",
+            "loading",
+            "name",
+            "referrerpolicy",
+            "sandbox",
+            "src",
+            "srcdoc",
+            "width",
+        ],
+    ),
+    element(
+        "img",
+        FLOW_PHRASING,
+        ContentModel::Empty,
+        &[
+            "alt",
+            "crossorigin",
+            "decoding",
+            "fetchpriority",
+            "height",
+            "ismap",
+            "loading",
+            "referrerpolicy",
+            "sizes",
+            "src",
+            "srcset",
+            "usemap",
+            "width",
+        ],
+    ),
+    element(
+        "input",
+        FLOW_PHRASING,
+        ContentModel::Empty,
+        &[
+            "accept",
+            "alt",
+            "autocomplete",
+            "checked",
+            "dirname",
+            "disabled",
+            "form",
+            "formaction",
+            "formenctype",
+            "formmethod",
+            "formnovalidate",
+            "formtarget",
+            "height",
+            "list",
+            "max",
+            "maxlength",
+            "min",
+            "minlength",
+            "multiple",
+            "name",
+            "pattern",
+            "placeholder",
+            "readonly",
+            "required",
+            "size",
+            "src",
+            "step",
+            "type",
+            "value",
+            "width",
+        ],
+    ),
+    element(
+        "ins",
+        FLOW_PHRASING,
+        ContentModel::Transparent,
+        &["cite", "datetime"],
+    ),
+    element("kbd", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("label", FLOW_PHRASING, PHRASING_CONTENT, &["for"]),
+    element("legend", &[], PHRASING_CONTENT, &[]),
+    element("li", &[], FLOW_CONTENT, &["value"]),
+    element(
+        "link",
+        &[Metadata],
+        ContentModel::Empty,
+        &[
+            "as",
+            "crossorigin",
+            "href",
+            "hreflang",
+            "integrity",
+            "media",
+            "referrerpolicy",
+            "rel",
+            "sizes",
+            "type",
+        ],
+    ),
+    element("main", FLOW, FLOW_CONTENT, &[]),
+    element("mark", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "meta",
+        &[Metadata],
+        ContentModel::Empty,
+        &["charset", "content", "http-equiv", "media", "name"],
+    ),
+    element(
+        "meter",
+        FLOW_PHRASING,
+        PHRASING_CONTENT,
+        &["high", "low", "max", "min", "optimum", "value"],
+    ),
+    element("nav", FLOW, FLOW_CONTENT, &[]),
+    element("noscript", ALL, FLOW_CONTENT, &[]),
+    element(
+        "ol",
+        FLOW,
+        ContentModel::Tags(&["li", "script", "template"]),
+        &["reversed", "start", "type"],
+    ),
+    element(
+        "optgroup",
+        &[],
+        ContentModel::Tags(&["option"]),
+        &["disabled", "label"],
+    ),
+    element(
+        "option",
+        &[],
+        ContentModel::Text,
+        &["disabled", "label", "selected", "value"],
+    ),
+    element(
+        "output",
+        FLOW_PHRASING,
+        PHRASING_CONTENT,
+        &["for", "form", "name"],
+    ),
+    element("p", FLOW, PHRASING_CONTENT, &[]),
+    element(
+        "picture",
+        FLOW_PHRASING,
+        ContentModel::Tags(&["source", "img"]),
+        &[],
+    ),
+    element("pre", FLOW, PHRASING_CONTENT, &[]),
+    element(
+        "progress",
+        FLOW_PHRASING,
+        PHRASING_CONTENT,
+        &["max", "value"],
+    ),
+    element("q", FLOW_PHRASING, PHRASING_CONTENT, &["cite"]),
+    element("s", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("samp", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "script",
+        ALL,
+        ContentModel::Text,
+        &[
+            "async",
+            "crossorigin",
+            "defer",
+            "integrity",
+            "nomodule",
+            "referrerpolicy",
+            "src",
+            "type",
+        ],
+    ),
+    element("section", FLOW, FLOW_CONTENT, &[]),
+    element(
+        "select",
+        FLOW_PHRASING,
+        ContentModel::Tags(&["option", "optgroup"]),
+        &[
+            "autocomplete",
+            "disabled",
+            "form",
+            "multiple",
+            "name",
+            "required",
+            "size",
+        ],
+    ),
+    element("slot", FLOW_PHRASING, ContentModel::Transparent, &["name"]),
+    element("small", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "source",
+        &[],
+        ContentModel::Empty,
+        &["height", "media", "sizes", "src", "srcset", "type", "width"],
+    ),
+    element("span", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("strong", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("style", &[Metadata], ContentModel::Text, &["media"]),
+    element("sub", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element("summary", &[], PHRASING_CONTENT, &[]),
+    element("sup", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "table",
+        FLOW,
+        ContentModel::Tags(&["caption", "colgroup", "thead", "tbody", "tfoot", "tr"]),
+        &[],
+    ),
+    element("tbody", &[], ContentModel::Tags(&["tr"]), &[]),
+    element("td", &[], FLOW_CONTENT, &["colspan", "headers", "rowspan"]),
+    element(
+        "template",
+        ALL,
+        ContentModel::Categories(ALL),
+        &["shadowrootmode"],
+    ),
+    element(
+        "textarea",
+        FLOW_PHRASING,
+        ContentModel::Text,
+        &[
+            "autocomplete",
+            "cols",
+            "dirname",
+            "disabled",
+            "form",
+            "maxlength",
+            "minlength",
+            "name",
+            "placeholder",
+            "readonly",
+            "required",
+            "rows",
+            "wrap",
+        ],
+    ),
+    element("tfoot", &[], ContentModel::Tags(&["tr"]), &[]),
+    element(
+        "th",
+        &[],
+        FLOW_CONTENT,
+        &["abbr", "colspan", "headers", "rowspan", "scope"],
+    ),
+    element("thead", &[], ContentModel::Tags(&["tr"]), &[]),
+    element("time", FLOW_PHRASING, PHRASING_CONTENT, &["datetime"]),
+    element("title", &[Metadata], ContentModel::Text, &[]),
+    element("tr", &[], ContentModel::Tags(&["td", "th"]), &[]),
+    element(
+        "track",
+        &[],
+        ContentModel::Empty,
+        &["default", "kind", "label", "src", "srclang"],
+    ),
+    element("u", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "ul",
+        FLOW,
+        ContentModel::Tags(&["li", "script", "template"]),
+        &[],
+    ),
+    element("var", FLOW_PHRASING, PHRASING_CONTENT, &[]),
+    element(
+        "video",
+        FLOW_PHRASING,
+        ContentModel::Tags(&["source", "track"]),
+        &[
+            "autoplay",
+            "controls",
+            "crossorigin",
+            "height",
+            "loop",
+            "muted",
+            "playsinline",
+            "poster",
+            "preload",
+            "src",
+            "width",
+        ],
+    ),
+    element("wbr", FLOW_PHRASING, ContentModel::Empty, &[]),
+];
+
+/// Gets the content model of the element, or None if the element is not known.
+pub fn element_info(tag: &str) -> Option<&'static ElementInfo> {
+    ELEMENTS
+        .binary_search_by(|e| e.tag.cmp(tag))
+        .ok()
+        .map(|i| &ELEMENTS[i])
+}
+
+/// Gets the elements which can be children of the last element of the ancestors,
+/// which are the tag names of the elements from the top of the document. The
+/// ancestors are needed as transparent elements allow what their parent allows.
+/// With no ancestors the top of a document can contain `<html>` or, for fragments,
+/// any flow content.
+pub fn allowed_children(ancestors: &[&str]) -> Vec<&'static str> {
+    let model = ancestors
+        .iter()
+        .rev()
+        .map(|tag| element_info(tag).map_or(FLOW_CONTENT, |e| e.content))
+        .find(|model| *model != ContentModel::Transparent);
+    match model {
+        Some(ContentModel::Empty) | Some(ContentModel::Text) => vec![],
+        Some(ContentModel::Tags(tags)) => tags.to_vec(),
+        Some(ContentModel::Categories(categories)) => ELEMENTS
+            .iter()
+            .filter(|e| e.categories.iter().any(|c| categories.contains(c)))
+            .map(|e| e.tag)
+            .collect(),
+        // everything up to the top of the document was transparent
+        Some(ContentModel::Transparent) | None => {
+            let mut tags = vec!["html"];
+            tags.extend(allowed_children(&["body"]));
+            tags
+        }
+    }
+}
+
+/// Gets the attributes supported by the element, the global attributes followed
+/// by the attributes for the element.
+pub fn attributes(tag: &str) -> Vec<&'static str> {
+    let mut attributes = GLOBAL_ATTRIBUTES.to_vec();
+    if let Some(e) = element_info(tag) {
+        attributes.extend(e.attributes);
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod content_model_tests {
+    use super::*;
+
+    #[test]
+    fn elements_sorted_test() {
+        // element_info relies on the table being sorted
+        assert!(ELEMENTS.windows(2).all(|w| w[0].tag < w[1].tag));
+        assert_eq!(element_info("p").unwrap().content, PHRASING_CONTENT);
+        assert_eq!(element_info("custom-element"), None);
+    }
+
+    #[test]
+    fn allowed_children_test() {
+        assert_eq!(allowed_children(&["html"]), vec!["head", "body"]);
+        let head = allowed_children(&["html", "head"]);
+        assert!(head.contains(&"title") && head.contains(&"script"));
+        assert!(!head.contains(&"div"));
+        let p = allowed_children(&["p"]);
+        assert!(p.contains(&"span") && !p.contains(&"div") && !p.contains(&"li"));
+        assert!(allowed_children(&["div", "a"]).contains(&"div"));
+        assert!(allowed_children(&["br"]).is_empty());
+        assert!(allowed_children(&["title"]).is_empty());
+        // unknown elements allow flow content
+        assert!(allowed_children(&["my-widget"]).contains(&"div"));
+        let top = allowed_children(&[]);
+        assert_eq!(top[0], "html");
+        assert!(top.contains(&"p"));
+    }
+
+    #[test]
+    fn attributes_test() {
+        let img = attributes("img");
+        assert!(img.contains(&"id") && img.contains(&"src") && img.contains(&"alt"));
+        assert!(!img.contains(&"href"));
+        assert_eq!(attributes("my-widget"), GLOBAL_ATTRIBUTES.to_vec());
+    }
+}
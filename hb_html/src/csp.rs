@@ -0,0 +1,223 @@
+//! Allowing the inline scripts and styles of a document in a Content Security
+//! Policy.
+//!
+//! A policy without `'unsafe-inline'` blocks every inline `<script>` and `<style>`
+//! unless it carries a nonce from the policy or its contents match a hash in the
+//! policy. [`InlineCsp`] gives each inline element the nonce, or hashes their
+//! contents with the `sri` feature, and returns the [`CspSources`] to put in the
+//! `script-src` and `style-src` directives of the `Content-Security-Policy`
+//! header.
+//!
+//! The hashes are of the contents as [`HtmlNode::to_html_string`](crate::objects::HtmlNode::to_html_string) writes them, so
+//! the document must be written out after any later changes to the elements are
+//! made.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::csp::InlineCsp;
+//! use hb_html::objects::HtmlDocument;
+//! let mut doc = r#"<head><style>p{}</style><script src="/a.js"></script><script>go()</script></head>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let sources = InlineCsp::nonce("r4nd0m").apply(&mut doc);
+//! assert_eq!(
+//!     doc.to_html_string(),
+//!     r#"<head><style nonce="r4nd0m">p{}</style><script src="/a.js"></script><script nonce="r4nd0m">go()</script></head>"#
+//! );
+//! assert_eq!(
+//!     sources.header_value(),
+//!     "script-src 'nonce-r4nd0m'; style-src 'nonce-r4nd0m'"
+//! );
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlTag};
+use crate::pipeline::{Pass, PassContext};
+#[cfg(feature = "sri")]
+use crate::sri::SriAlgorithm;
+use crate::visit::{Visit, VisitAction, VisitContext};
+
+/// How inline elements are allowed by the policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InlineSource {
+    /// Add a `nonce` attribute with the value to each inline element.
+    Nonce(String),
+    /// Hash the contents of each inline element with the algorithm.
+    #[cfg(feature = "sri")]
+    Hash(SriAlgorithm),
+}
+
+/// The sources which allow the inline elements of a document, such as
+/// `'nonce-abc'` or `'sha256-<base64 digest>'`, without duplicates.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CspSources {
+    pub script_src: Vec<String>,
+    pub style_src: Vec<String>,
+}
+
+impl CspSources {
+    /// Gets the `script-src` and `style-src` directives for the sources, leaving
+    /// out directives with no sources.
+    pub fn header_value(&self) -> String {
+        let mut directives = vec![];
+        for (name, sources) in [
+            ("script-src", &self.script_src),
+            ("style-src", &self.style_src),
+        ] {
+            if !sources.is_empty() {
+                directives.push(format!("{} {}", name, sources.join(" ")));
+            }
+        }
+        directives.join("; ")
+    }
+
+    fn add(sources: &mut Vec<String>, source: String) {
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+}
+
+/// A [`Pass`] which allows the inline `<script>` and `<style>` elements of a
+/// document in a Content Security Policy. The pass stores the directives in the
+/// `csp.script-src` and `csp.style-src` values of the [`PassContext`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineCsp {
+    pub source: InlineSource,
+}
+
+impl InlineCsp {
+    /// Allows the inline elements by giving them the nonce. The nonce must be new
+    /// for each response and not guessable.
+    pub fn nonce<T: Into<String>>(nonce: T) -> InlineCsp {
+        InlineCsp {
+            source: InlineSource::Nonce(nonce.into()),
+        }
+    }
+
+    /// Allows the inline elements by the hashes of their contents.
+    #[cfg(feature = "sri")]
+    pub fn hash(algorithm: SriAlgorithm) -> InlineCsp {
+        InlineCsp {
+            source: InlineSource::Hash(algorithm),
+        }
+    }
+
+    /// Adds the nonces to the document, or hashes the inline elements, returning
+    /// the sources for the policy.
+    pub fn apply(&self, doc: &mut HtmlDocument) -> CspSources {
+        let mut visitor = CspVisitor {
+            source: &self.source,
+            sources: CspSources::default(),
+        };
+        doc.visit(&mut visitor);
+        visitor.sources
+    }
+}
+
+struct CspVisitor<'a> {
+    source: &'a InlineSource,
+    sources: CspSources,
+}
+
+impl Visit for CspVisitor<'_> {
+    fn enter_element(&mut self, tag: &mut HtmlTag, _ctx: &VisitContext) -> VisitAction {
+        let sources = match tag.tag.to_ascii_lowercase().as_str() {
+            "script" if !tag.attributes.contains_key("src") => &mut self.sources.script_src,
+            "style" => &mut self.sources.style_src,
+            _ => return VisitAction::Continue,
+        };
+        let source = match self.source {
+            InlineSource::Nonce(nonce) => {
+                tag.attributes.insert("nonce".to_owned(), nonce.clone());
+                format!("'nonce-{}'", nonce)
+            }
+            #[cfg(feature = "sri")]
+            InlineSource::Hash(algorithm) => {
+                let contents: String = tag.contents.iter().map(|n| n.to_html_string()).collect();
+                format!(
+                    "'{}-{}'",
+                    algorithm.prefix(),
+                    algorithm.digest(contents.as_bytes())
+                )
+            }
+        };
+        CspSources::add(sources, source);
+        // the contents are text, there are no more elements to allow
+        VisitAction::SkipChildren
+    }
+}
+
+impl Pass for InlineCsp {
+    fn name(&self) -> &str {
+        "inline-csp"
+    }
+
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError> {
+        let sources = self.apply(doc);
+        for (name, values) in [
+            ("csp.script-src", &sources.script_src),
+            ("csp.style-src", &sources.style_src),
+        ] {
+            if !values.is_empty() {
+                ctx.values.insert(name.to_owned(), values.join(" "));
+            }
+        }
+        ctx.info(
+            format!(
+                "allowed {} script and {} style sources",
+                sources.script_src.len(),
+                sources.style_src.len()
+            ),
+            None,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod csp_tests {
+    use super::*;
+    use crate::pipeline::Pipeline;
+
+    #[test]
+    fn nonce_test() {
+        let mut doc =
+            r#"<div><script type="module">import "/a.js";</script><p style="x"></p></div>"#
+                .parse::<HtmlDocument>()
+                .unwrap();
+        let report = Pipeline::new()
+            .add(InlineCsp::nonce("n1"))
+            .run(&mut doc)
+            .unwrap();
+        assert_eq!(report.values["csp.script-src"], "'nonce-n1'");
+        // there are no style elements, only a style attribute which nonces do not allow
+        assert!(!report.values.contains_key("csp.style-src"));
+        assert_eq!(doc.find("script[nonce=n1]").nodes().len(), 1);
+        assert_eq!(doc.find("p[nonce]").nodes().len(), 0);
+        assert_eq!(CspSources::default().header_value(), "");
+    }
+
+    #[cfg(feature = "sri")]
+    #[test]
+    fn hash_test() {
+        let mut doc = "<head><script>a()</script><script>a()</script><style>p{}</style></head>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let expected = doc.clone();
+        let sources = InlineCsp::hash(SriAlgorithm::Sha256).apply(&mut doc);
+        // hashing does not change the document
+        assert_eq!(doc, expected);
+        assert_eq!(
+            sources.script_src,
+            vec![format!(
+                "'{}'",
+                crate::sri::integrity_value(b"a()", SriAlgorithm::Sha256)
+            )]
+        );
+        assert_eq!(
+            sources.style_src,
+            vec!["'sha256-gG2yISYereRMiG2lMXrbiUgi0Ubw9p7QCeWcroOvy9Y='".to_owned()]
+        );
+    }
+}
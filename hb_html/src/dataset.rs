@@ -0,0 +1,293 @@
+//! DOM style access to the `data-*`, `hidden` and `contenteditable` attributes of
+//! a tag.
+//!
+//! [`HtmlTag::dataset`] works like `element.dataset` in the DOM: the attribute
+//! `data-foo-bar` has the key `fooBar`. A `-` followed by a lowercase letter is
+//! removed and the letter is made uppercase, and going the other way each
+//! uppercase letter in a key becomes a `-` followed by the letter in lowercase.
+//!
+//! The values are strings, [`Dataset::parse_num`], [`Dataset::parse_float`] and
+//! [`Dataset::parse_bool`] read them as other types.
+//!
+//! [`HtmlTag::hidden`] and [`HtmlTag::content_editable`] read the `hidden` and
+//! `contenteditable` attributes as the DOM `hidden` and `contentEditable`
+//! properties do.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlTag;
+//! let mut tag = r#"<li data-item-id=42 data-price=" 9.5" data-in-stock></li>"#
+//!     .parse::<HtmlTag>()
+//!     .unwrap();
+//! let data = tag.dataset();
+//! assert_eq!(data.keys(), vec!["inStock", "itemId", "price"]);
+//! assert_eq!(data.parse_num::<u32>("itemId"), Some(42));
+//! assert_eq!(data.parse_float("price"), Some(9.5));
+//! assert_eq!(data.parse_bool("inStock"), Some(true));
+//! tag.set_data("itemId", "43");
+//! assert_eq!(tag.attributes["data-item-id"], "43");
+//! ```
+use crate::objects::HtmlTag;
+use hb_parse::parser_funcs::{ParsableInts, ParsableNums};
+use hb_parse::{CommonParserFunctions, NumOptions, ParseResult, SourceCore, StrParser};
+use std::str::FromStr;
+
+/// Converts the name of a `data-*` attribute to its dataset key, or None if the
+/// attribute is not a `data-*` attribute.
+pub fn attribute_to_key(name: &str) -> Option<String> {
+    let rest = name.strip_prefix("data-")?;
+    let mut key = String::with_capacity(rest.len());
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(next) if c == '-' && next.is_ascii_lowercase() => {
+                key.push(next.to_ascii_uppercase());
+                chars.next();
+            }
+            _ => key.push(c),
+        }
+    }
+    Some(key)
+}
+
+/// Converts a dataset key to the name of its `data-*` attribute.
+pub fn key_to_attribute(key: &str) -> String {
+    let mut name = String::from("data-");
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            name.push('-');
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// Parses the whole value, allowing whitespace around it.
+fn parse_whole<T, F: FnOnce(&mut StrParser) -> ParseResult<T>>(value: &str, parse: F) -> Option<T> {
+    let mut parser = StrParser::new(value);
+    let parsed = parse(&mut parser).ok()?;
+    parser.skip_whitespace().ok()?;
+    match parser.peek() {
+        Ok(None) => Some(parsed),
+        _ => None,
+    }
+}
+
+/// The state of the `contenteditable` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentEditable {
+    /// `contenteditable`, `contenteditable=""` or `contenteditable="true"`.
+    True,
+    /// `contenteditable="false"`.
+    False,
+    /// `contenteditable="plaintext-only"`, where only the text can be edited.
+    PlaintextOnly,
+    /// No attribute or a value which is not valid, so the element is editable if
+    /// its parent is.
+    Inherit,
+}
+
+/// The state of the `hidden` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hidden {
+    /// No `hidden` attribute.
+    Visible,
+    /// `hidden` with any value other than `until-found`.
+    Hidden,
+    /// `hidden="until-found"`, where the content is shown when it is found by a
+    /// search in the page or a fragment link.
+    UntilFound,
+}
+
+/// The `data-*` attributes of a tag, found by their dataset keys.
+#[derive(Debug, Clone, Copy)]
+pub struct Dataset<'a> {
+    tag: &'a HtmlTag,
+}
+
+impl<'a> Dataset<'a> {
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.tag
+            .attributes
+            .get(&key_to_attribute(key))
+            .map(|v| v.as_str())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Gets all of the keys in alphabetical order.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .tag
+            .attributes
+            .keys()
+            .filter_map(|name| attribute_to_key(name))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Gets all of the keys and values, in alphabetical order of the keys.
+    pub fn iter(&self) -> Vec<(String, &'a str)> {
+        let mut entries: Vec<(String, &'a str)> = self
+            .tag
+            .attributes
+            .iter()
+            .filter_map(|(name, value)| Some((attribute_to_key(name)?, value.as_str())))
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.tag
+            .attributes
+            .keys()
+            .filter(|name| name.starts_with("data-"))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the value as an integer, or None if the value is not an integer.
+    pub fn parse_num<N: ParsableNums + ParsableInts + FromStr>(&self, key: &str) -> Option<N> {
+        parse_whole(self.get(key)?, |p| p.parse_num())
+    }
+
+    /// Reads the value as a finite float, or None if the value is not a number.
+    pub fn parse_float(&self, key: &str) -> Option<f64> {
+        parse_whole(self.get(key)?, |p| {
+            p.parse_float_with(&NumOptions::default())
+        })
+    }
+
+    /// Reads the value as a bool. An empty value is true, as the attribute is there,
+    /// otherwise the value must be `true` or `false` ignoring case.
+    pub fn parse_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)?.trim() {
+            "" => Some(true),
+            v if v.eq_ignore_ascii_case("true") => Some(true),
+            v if v.eq_ignore_ascii_case("false") => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl HtmlTag {
+    pub fn hidden(&self) -> Hidden {
+        match self.attributes.get("hidden") {
+            None => Hidden::Visible,
+            Some(v) if v.eq_ignore_ascii_case("until-found") => Hidden::UntilFound,
+            Some(_) => Hidden::Hidden,
+        }
+    }
+
+    pub fn content_editable(&self) -> ContentEditable {
+        match self
+            .attributes
+            .get("contenteditable")
+            .map(|v| v.to_ascii_lowercase())
+        {
+            None => ContentEditable::Inherit,
+            Some(v) => match v.as_str() {
+                "" | "true" => ContentEditable::True,
+                "false" => ContentEditable::False,
+                "plaintext-only" => ContentEditable::PlaintextOnly,
+                _ => ContentEditable::Inherit,
+            },
+        }
+    }
+
+    /// Gets the `data-*` attributes of the tag by their dataset keys.
+    pub fn dataset(&self) -> Dataset<'_> {
+        Dataset { tag: self }
+    }
+
+    /// Sets the `data-*` attribute for the dataset key.
+    pub fn set_data<K: AsRef<str>, V: Into<String>>(&mut self, key: K, value: V) {
+        self.attributes
+            .insert(key_to_attribute(key.as_ref()), value.into());
+    }
+
+    /// Removes the `data-*` attribute for the dataset key, returning its value if it
+    /// was present.
+    pub fn remove_data(&mut self, key: &str) -> Option<String> {
+        self.attributes.remove(&key_to_attribute(key))
+    }
+}
+
+#[cfg(test)]
+mod dataset_tests {
+    use super::*;
+
+    #[test]
+    fn dataset_keys_test() {
+        assert_eq!(attribute_to_key("data-foo-bar").unwrap(), "fooBar");
+        assert_eq!(attribute_to_key("data-x").unwrap(), "x");
+        // only a - before a lowercase letter is removed
+        assert_eq!(attribute_to_key("data-a-1-b--c").unwrap(), "a-1B-C");
+        assert_eq!(attribute_to_key("title"), None);
+        assert_eq!(key_to_attribute("fooBar"), "data-foo-bar");
+        assert_eq!(key_to_attribute("x"), "data-x");
+    }
+
+    #[test]
+    fn hidden_and_content_editable_test() {
+        let tag = r#"<div hidden contenteditable="PlainText-Only"></div>"#
+            .parse::<HtmlTag>()
+            .unwrap();
+        assert_eq!(tag.hidden(), Hidden::Hidden);
+        assert_eq!(tag.content_editable(), ContentEditable::PlaintextOnly);
+        let tag =
+            HtmlTag::new("p").attributes(vec![("hidden", "until-found"), ("contenteditable", "")]);
+        assert_eq!(tag.hidden(), Hidden::UntilFound);
+        assert_eq!(tag.content_editable(), ContentEditable::True);
+        let tag = HtmlTag::new("p").attributes(vec![("contenteditable", "yes")]);
+        assert_eq!(tag.hidden(), Hidden::Visible);
+        assert_eq!(tag.content_editable(), ContentEditable::Inherit);
+    }
+
+    #[test]
+    fn dataset_values_test() {
+        let mut tag = HtmlTag::new("div").attributes(vec![
+            ("data-count", "-3"),
+            ("data-size", "12px"),
+            ("data-open", "FALSE"),
+            ("data-ratio", "nope"),
+            ("title", "t"),
+        ]);
+        let data = tag.dataset();
+        assert_eq!(data.len(), 4);
+        assert_eq!(data.parse_num::<i64>("count"), Some(-3));
+        assert_eq!(data.parse_num::<u8>("count"), None);
+        // the whole value must be the number
+        assert_eq!(data.parse_num::<i32>("size"), None);
+        assert_eq!(data.get("size"), Some("12px"));
+        assert_eq!(data.parse_bool("open"), Some(false));
+        assert_eq!(data.parse_bool("ratio"), None);
+        assert_eq!(data.parse_float("ratio"), None);
+        assert_eq!(data.parse_num::<i32>("missing"), None);
+        assert_eq!(data.parse_float("count"), Some(-3.0));
+        assert_eq!(
+            data.iter(),
+            vec![
+                ("count".to_owned(), "-3"),
+                ("open".to_owned(), "FALSE"),
+                ("ratio".to_owned(), "nope"),
+                ("size".to_owned(), "12px"),
+            ]
+        );
+        assert_eq!(tag.remove_data("ratio"), Some("nope".to_owned()));
+        tag.set_data("userName", "ann");
+        assert_eq!(tag.attributes["data-user-name"], "ann");
+        assert!(!tag.dataset().contains_key("ratio"));
+        assert!(HtmlTag::new("p").dataset().is_empty());
+    }
+}
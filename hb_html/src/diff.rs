@@ -0,0 +1,354 @@
+//! Finding the changes between two versions of a document, and making the same
+//! changes to another version.
+//!
+//! [`diff`] compares two documents node by node and gives a [`ChangeSet`] of
+//! [`OverlayMutation`]s. [`apply`] makes those changes to a document, which can be
+//! a copy of the first one that has been changed in other ways since, such as a
+//! generated page which was then edited by hand. The path of each change is
+//! resolved again first, by finding the node it points at in the base of the
+//! change set among the siblings at the same place in the document, so a change
+//! still applies when nodes have been added or removed before it.
+//!
+//! A change whose node can not be found, or has been changed in both versions, is
+//! reported as a [`Conflict`] with the same kind of error as
+//! [`OverlayMutation::apply`] gives for a path which does not resolve, and the
+//! other changes are still made.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::diff::{apply, diff};
+//! use hb_html::objects::HtmlDocument;
+//! let base = "<ul><li>a</li><li>b</li></ul>".parse::<HtmlDocument>().unwrap();
+//! let ours = "<ul><li>a</li><li class=x>b</li></ul>".parse::<HtmlDocument>().unwrap();
+//! let changes = diff(&base, &ours);
+//! // their copy has another item at the start, so the change moves along with b
+//! let mut theirs = "<ul><li>z</li><li>a</li><li>b</li></ul>"
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! apply(&mut theirs, &changes).unwrap();
+//! assert_eq!(
+//!     theirs.to_html_string(),
+//!     r#"<ul><li>z</li><li>a</li><li class="x">b</li></ul>"#
+//! );
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::overlay::OverlayMutation;
+
+/// The changes which turn one document into another, from [`diff`], along with the
+/// document they were found from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeSet {
+    /// The document the changes are made to, which is used to find the nodes they
+    /// are for in another version of it.
+    pub base: HtmlDocument,
+    /// The changes, whose paths are resolved against the base after all of the
+    /// previous changes have been made. A node is only inserted after all of the
+    /// nodes of the base which are in the same element.
+    pub mutations: Vec<OverlayMutation>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.mutations.is_empty()
+    }
+}
+
+/// A change of a [`ChangeSet`] which could not be made.
+#[derive(Debug)]
+pub struct Conflict {
+    /// The index of the change in [`ChangeSet::mutations`].
+    pub index: usize,
+    pub error: HtmlDocError,
+}
+
+/// Finds the changes which turn the document `before` into `after`. Nodes are
+/// compared by their place in the tree: a tag with the same name has its attributes
+/// and contents compared, any other node which is different is replaced, and nodes
+/// are added or removed at the end of the contents they are in.
+pub fn diff(before: &HtmlDocument, after: &HtmlDocument) -> ChangeSet {
+    let mut mutations = vec![];
+    diff_contents(&before.nodes, &after.nodes, &mut vec![], &mut mutations);
+    ChangeSet {
+        base: before.clone(),
+        mutations,
+    }
+}
+
+/// Makes the changes to the document, skipping the ones which conflict with how it
+/// differs from the base of the change set and returning them once the others have
+/// been made.
+pub fn apply(doc: &mut HtmlDocument, changes: &ChangeSet) -> Result<(), Vec<Conflict>> {
+    // the paths are all resolved before anything is changed, as the changes are
+    // ordered so that none of them moves the nodes of the ones after it
+    let resolved: Vec<Result<OverlayMutation, HtmlDocError>> = changes
+        .mutations
+        .iter()
+        .map(|m| resolve_mutation(&changes.base, doc, m))
+        .collect();
+    let mut conflicts = vec![];
+    for (index, mutation) in resolved.into_iter().enumerate() {
+        if let Err(error) = mutation.and_then(|m| m.apply(doc)) {
+            conflicts.push(Conflict { index, error });
+        }
+    }
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(conflicts)
+    }
+}
+
+/// Adds the changes between two lists of siblings, ordered so that no change moves
+/// the nodes of the changes after it: nodes are added or removed at the end first,
+/// and then the other nodes are compared from the last to the first.
+fn diff_contents(
+    before: &[HtmlNode],
+    after: &[HtmlNode],
+    path: &mut NodePath,
+    mutations: &mut Vec<OverlayMutation>,
+) {
+    for (index, node) in after.iter().enumerate().skip(before.len()) {
+        mutations.push(OverlayMutation::InsertChild {
+            path: path.clone(),
+            index,
+            node: node.clone(),
+        });
+    }
+    for index in (after.len()..before.len()).rev() {
+        path.push(index);
+        mutations.push(OverlayMutation::Remove { path: path.clone() });
+        path.pop();
+    }
+    for index in (0..before.len().min(after.len())).rev() {
+        path.push(index);
+        diff_node(&before[index], &after[index], path, mutations);
+        path.pop();
+    }
+}
+
+fn diff_node(
+    before: &HtmlNode,
+    after: &HtmlNode,
+    path: &mut NodePath,
+    mutations: &mut Vec<OverlayMutation>,
+) {
+    if same_node(before, after) {
+        return;
+    }
+    match (before, after) {
+        (HtmlNode::Tag(b), HtmlNode::Tag(a)) if b.tag == a.tag => {
+            let mut names = attribute_names(b);
+            names.extend(attribute_names(a));
+            names.sort();
+            names.dedup();
+            for name in names {
+                let path = path.clone();
+                match a.get_attribute(&name) {
+                    value if value == b.get_attribute(&name) => {}
+                    Some(value) => {
+                        mutations.push(OverlayMutation::SetAttribute { path, name, value })
+                    }
+                    None => mutations.push(OverlayMutation::RemoveAttribute { path, name }),
+                }
+            }
+            diff_contents(&b.contents, &a.contents, path, mutations);
+        }
+        _ => mutations.push(OverlayMutation::Replace {
+            path: path.clone(),
+            node: after.clone(),
+        }),
+    }
+}
+
+/// Checks whether the nodes are the same, including the contents of tags which
+/// the `==` of [`HtmlTag`] does not compare.
+fn same_node(a: &HtmlNode, b: &HtmlNode) -> bool {
+    match (a, b) {
+        (HtmlNode::Tag(a), HtmlNode::Tag(b)) => {
+            a == b
+                && a.contents.len() == b.contents.len()
+                && a.contents
+                    .iter()
+                    .zip(&b.contents)
+                    .all(|(a, b)| same_node(a, b))
+        }
+        _ => a == b,
+    }
+}
+
+fn attribute_names(tag: &HtmlTag) -> Vec<String> {
+    let mut names: Vec<String> = tag.attributes.keys().cloned().collect();
+    if !tag.ids.is_empty() {
+        names.push("id".to_owned());
+    }
+    if !tag.classes.is_empty() {
+        names.push("class".to_owned());
+    }
+    names
+}
+
+/// Gets the mutation with its path changed to point at the same node in the
+/// document as it did in the base.
+fn resolve_mutation(
+    base: &HtmlDocument,
+    doc: &HtmlDocument,
+    mutation: &OverlayMutation,
+) -> Result<OverlayMutation, HtmlDocError> {
+    let mut resolved = mutation.clone();
+    match &mut resolved {
+        OverlayMutation::SetAttribute { path, .. }
+        | OverlayMutation::RemoveAttribute { path, .. }
+        | OverlayMutation::SetText { path, .. }
+        | OverlayMutation::Replace { path, .. }
+        | OverlayMutation::Remove { path } => *path = resolve_path(base, doc, path, true)?,
+        OverlayMutation::InsertChild { path, index, .. } => {
+            let base_len = contents(base, path).map_or(0, |c| c.len());
+            *path = resolve_path(base, doc, path, false)?;
+            let len = contents(doc, path).map_or(0, |c| c.len());
+            *index = len + index.saturating_sub(base_len);
+        }
+    }
+    Ok(resolved)
+}
+
+/// Finds the node in the document which is at the path in the base. At each level
+/// the nearest sibling which is the same as the one in the base is used, or, for
+/// the elements the node is inside, the nearest with the same tag name if none are
+/// the same. The node itself must be the same as it is in the base if `exact` is
+/// set, as otherwise it has been changed in both.
+fn resolve_path(
+    base: &HtmlDocument,
+    doc: &HtmlDocument,
+    path: &[usize],
+    exact: bool,
+) -> Result<NodePath, HtmlDocError> {
+    let mut resolved = vec![];
+    for (depth, &index) in path.iter().enumerate() {
+        let base_node = contents(base, &path[..depth])
+            .and_then(|c| c.get(index))
+            .ok_or_else(|| no_node_error(path))?;
+        let siblings = contents(doc, &resolved).ok_or_else(|| no_node_error(path))?;
+        let nearest = |same: &dyn Fn(&HtmlNode) -> bool| {
+            siblings
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| same(node))
+                .min_by_key(|(i, _)| i.abs_diff(index))
+                .map(|(i, _)| i)
+        };
+        let mut found = nearest(&|node| same_node(node, base_node));
+        let is_last = depth + 1 == path.len();
+        if found.is_none() && !(is_last && exact) {
+            found = nearest(&|node| match (node, base_node) {
+                (HtmlNode::Tag(a), HtmlNode::Tag(b)) => a.tag == b.tag,
+                _ => false,
+            });
+        }
+        match found {
+            Some(i) => resolved.push(i),
+            None => {
+                return Err(HtmlDocError::with_msg(format!(
+                    "the node at {:?} has been changed or removed",
+                    &path[..=depth]
+                )))
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+/// Gets the top level nodes of the document if the path is empty, or the contents
+/// of the tag at the path.
+fn contents<'a>(doc: &'a HtmlDocument, path: &[usize]) -> Option<&'a Vec<HtmlNode>> {
+    if path.is_empty() {
+        return Some(&doc.nodes);
+    }
+    match doc.node_at(path)? {
+        HtmlNode::Tag(t) => Some(&t.contents),
+        _ => None,
+    }
+}
+
+fn no_node_error(path: &[usize]) -> HtmlDocError {
+    HtmlDocError::with_msg(format!("there is no node at {:?}", path))
+}
+
+#[cfg(test)]
+mod diff_tests {
+    use super::*;
+
+    fn parse(source: &str) -> HtmlDocument {
+        source.parse::<HtmlDocument>().unwrap()
+    }
+
+    #[test]
+    fn diff_apply_test() {
+        let before = parse(r#"<div id=a class=x><p>one</p><p>two</p><i>three</i></div><br>"#);
+        let after = parse(r#"<div id=b><p>one!</p><b>two</b></div><br><hr><p>end</p>"#);
+        let changes = diff(&before, &after);
+        assert_eq!(
+            changes.mutations[..3],
+            [
+                OverlayMutation::InsertChild {
+                    path: vec![],
+                    index: 2,
+                    node: after.nodes[2].clone(),
+                },
+                OverlayMutation::InsertChild {
+                    path: vec![],
+                    index: 3,
+                    node: after.nodes[3].clone(),
+                },
+                OverlayMutation::RemoveAttribute {
+                    path: vec![0],
+                    name: "class".to_owned(),
+                },
+            ]
+        );
+        let mut doc = before.clone();
+        apply(&mut doc, &changes).unwrap();
+        assert_eq!(doc.to_html_string(), after.to_html_string());
+        assert!(diff(&after, &after).is_empty());
+        assert!(!diff(&parse("<p>a</p>"), &parse("<p>b</p>")).is_empty());
+    }
+
+    #[test]
+    fn apply_to_changed_document_test() {
+        let base = parse("<ul><li>a</li><li>b</li><li>c</li></ul>");
+        let ours = parse("<ul><li>a</li><li>B</li><li>c</li><li>d</li></ul>");
+        let changes = diff(&base, &ours);
+        // their copy has a new first item, and has changed the last one
+        let mut theirs = parse("<ul><li>z</li><li>a</li><li>b</li><li>C</li></ul>");
+        apply(&mut theirs, &changes).unwrap();
+        assert_eq!(
+            theirs.to_html_string(),
+            "<ul><li>z</li><li>a</li><li>B</li><li>C</li><li>d</li></ul>"
+        );
+
+        // the b was changed in both, so the change conflicts
+        let mut theirs = parse("<ul><li>a</li><li>b!</li><li>c</li></ul>");
+        let conflicts = apply(&mut theirs, &changes).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            changes.mutations[conflicts[0].index],
+            OverlayMutation::Replace {
+                path: vec![0, 1, 0],
+                node: HtmlNode::Text("B".to_owned()),
+            }
+        );
+        assert_eq!(
+            conflicts[0].error.to_string(),
+            "Html Doc Error: 'the node at [0, 1, 0] has been changed or removed'"
+        );
+        // the other changes are still made
+        assert_eq!(
+            theirs.to_html_string(),
+            "<ul><li>a</li><li>b!</li><li>c</li><li>d</li></ul>"
+        );
+        let mut other = parse("<p>x</p>");
+        assert_eq!(apply(&mut other, &changes).unwrap_err().len(), 2);
+        assert_eq!(other.to_html_string(), "<p>x</p>");
+    }
+}
@@ -0,0 +1,412 @@
+//! Moving around the tree of a [`HtmlDocument`].
+//!
+//! A parsed document is a tree of [`HtmlNode`]s: the top level nodes of the
+//! document each hold their contents, and the doctype is kept on the document.
+//! The nodes only point down to their contents, so a [`NodeRef`] pairs a node
+//! with its [`NodePath`] in the document, which is enough to move up to its
+//! parent and across to its siblings as well as down to its children.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! let doc = "<ul><li>One</li><li>Two</li></ul>".parse::<HtmlDocument>().unwrap();
+//! let first = doc.node_ref(&[0, 0]).unwrap();
+//! let second = first.next_sibling().unwrap();
+//! assert_eq!(second.node().text(), "Two");
+//! assert_eq!(second.previous_sibling(), Some(first.clone()));
+//! assert_eq!(second.parent().unwrap().tag().unwrap().tag, "ul");
+//! let items: Vec<String> = first.parent().unwrap().children().map(|c| c.node().text()).collect();
+//! assert_eq!(items, vec!["One", "Two"]);
+//! ```
+//!
+//! # Changing the tree
+//!
+//! The tree is changed through the document with the node paths, using
+//! [`HtmlDocument::append_child`], [`HtmlDocument::insert_before`],
+//! [`HtmlDocument::remove_child`], [`HtmlDocument::replace_with`] and
+//! [`HtmlDocument::set_text`]. As the nodes do not point at their parents or
+//! siblings there are no links to keep up to date, but the paths of the nodes after
+//! an inserted or removed node change, so paths found before the change should be
+//! looked up again.
+//!
+//! ```
+//! use hb_html::objects::{HtmlDocument, HtmlNode, HtmlTag};
+//! let mut doc = "<ul><li>One</li><li>Two</li></ul>".parse::<HtmlDocument>().unwrap();
+//! let three = doc
+//!     .append_child(&[0], HtmlNode::Tag(HtmlTag::new("li")))
+//!     .unwrap();
+//! doc.set_text(&three, "Three").unwrap();
+//! doc.insert_before(&[0, 0], HtmlNode::Tag(HtmlTag::new("li"))).unwrap();
+//! doc.set_text(&[0, 0], "Zero").unwrap();
+//! let two = doc.remove_child(&[0, 2]).unwrap();
+//! assert_eq!(two.text(), "Two");
+//! doc.replace_with(&[0, 1], two).unwrap();
+//! assert_eq!(
+//!     doc.to_html_string(),
+//!     "<ul><li>Zero</li><li>Two</li><li>Three</li></ul>"
+//! );
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::serialize::is_raw_text_element;
+
+/// A node of a document along with where it is in the document.
+#[derive(Debug, Clone)]
+pub struct NodeRef<'a> {
+    doc: &'a HtmlDocument,
+    path: NodePath,
+    node: &'a HtmlNode,
+}
+
+impl<'a> NodeRef<'a> {
+    fn at(doc: &'a HtmlDocument, path: NodePath) -> Option<NodeRef<'a>> {
+        let node = doc.node_at(&path)?;
+        Some(NodeRef { doc, path, node })
+    }
+
+    pub fn node(&self) -> &'a HtmlNode {
+        self.node
+    }
+
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    pub fn document(&self) -> &'a HtmlDocument {
+        self.doc
+    }
+
+    /// Gets the tag, if the node is a tag.
+    pub fn tag(&self) -> Option<&'a HtmlTag> {
+        match self.node {
+            HtmlNode::Tag(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// Gets the tag which the node is in, or None for a top level node.
+    pub fn parent(&self) -> Option<NodeRef<'a>> {
+        let (_, parent) = self.path.split_last()?;
+        NodeRef::at(self.doc, parent.to_vec())
+    }
+
+    /// Gets the tags which the node is in, starting with its parent.
+    pub fn ancestors(&self) -> impl Iterator<Item = NodeRef<'a>> {
+        std::iter::successors(self.parent(), NodeRef::parent)
+    }
+
+    /// Gets the contents of the node, which is empty for anything but a tag.
+    pub fn children(&self) -> impl Iterator<Item = NodeRef<'a>> + '_ {
+        let count = self.tag().map_or(0, |t| t.contents.len());
+        (0..count).filter_map(move |i| self.child(i))
+    }
+
+    /// Gets the child at the index of the contents of the node.
+    pub fn child(&self, i: usize) -> Option<NodeRef<'a>> {
+        let mut path = self.path.clone();
+        path.push(i);
+        NodeRef::at(self.doc, path)
+    }
+
+    pub fn first_child(&self) -> Option<NodeRef<'a>> {
+        self.child(0)
+    }
+
+    pub fn last_child(&self) -> Option<NodeRef<'a>> {
+        self.child(self.tag()?.contents.len().checked_sub(1)?)
+    }
+
+    pub fn next_sibling(&self) -> Option<NodeRef<'a>> {
+        let mut path = self.path.clone();
+        *path.last_mut()? += 1;
+        NodeRef::at(self.doc, path)
+    }
+
+    pub fn previous_sibling(&self) -> Option<NodeRef<'a>> {
+        let mut path = self.path.clone();
+        let last = path.last_mut()?;
+        *last = last.checked_sub(1)?;
+        NodeRef::at(self.doc, path)
+    }
+}
+
+/// Nodes are equal if they are at the same place in the same document.
+impl PartialEq for NodeRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.doc, other.doc) && self.path == other.path
+    }
+}
+
+impl Eq for NodeRef<'_> {}
+
+impl HtmlDocument {
+    /// Gets the node at the path, see [`HtmlDocument::node_at`], for moving to the
+    /// nodes around it.
+    pub fn node_ref(&self, path: &[usize]) -> Option<NodeRef<'_>> {
+        NodeRef::at(self, path.to_vec())
+    }
+
+    /// Gets the top level nodes of the document.
+    pub fn root_nodes(&self) -> impl Iterator<Item = NodeRef<'_>> {
+        (0..self.nodes.len()).filter_map(move |i| NodeRef::at(self, vec![i]))
+    }
+
+    /// Adds the node to the end of the contents of the tag at the path, or to the
+    /// end of the document for an empty path, returning the path of the node.
+    pub fn append_child(
+        &mut self,
+        parent: &[usize],
+        node: HtmlNode,
+    ) -> Result<NodePath, HtmlDocError> {
+        let contents = if parent.is_empty() {
+            &mut self.nodes
+        } else {
+            match self.node_at_mut(parent) {
+                Some(HtmlNode::Tag(t)) => &mut t.contents,
+                _ => return Err(no_tag_error(parent)),
+            }
+        };
+        contents.push(node);
+        let mut path = parent.to_vec();
+        path.push(contents.len() - 1);
+        Ok(path)
+    }
+
+    /// Inserts the node before the node at the path, so it takes the path and the
+    /// nodes after it move along. A path one past the last child of a tag adds the
+    /// node to the end of its contents.
+    pub fn insert_before(&mut self, path: &[usize], node: HtmlNode) -> Result<(), HtmlDocError> {
+        let i = *path.last().ok_or_else(|| no_node_error(path))?;
+        match self.siblings_at_mut(path) {
+            Some(siblings) if i <= siblings.len() => {
+                siblings.insert(i, node);
+                Ok(())
+            }
+            _ => Err(no_node_error(path)),
+        }
+    }
+
+    /// Removes the node at the path, returning it.
+    pub fn remove_child(&mut self, path: &[usize]) -> Result<HtmlNode, HtmlDocError> {
+        let i = *path.last().ok_or_else(|| no_node_error(path))?;
+        match self.siblings_at_mut(path) {
+            Some(siblings) if i < siblings.len() => Ok(siblings.remove(i)),
+            _ => Err(no_node_error(path)),
+        }
+    }
+
+    /// Puts the node in place of the node at the path, returning the node which was
+    /// replaced.
+    pub fn replace_with(
+        &mut self,
+        path: &[usize],
+        node: HtmlNode,
+    ) -> Result<HtmlNode, HtmlDocError> {
+        match self.node_at_mut(path) {
+            Some(old) => Ok(std::mem::replace(old, node)),
+            None => Err(no_node_error(path)),
+        }
+    }
+
+    /// Sets the text of the node at the path, see [`HtmlNode::set_text`].
+    pub fn set_text<T: Into<String>>(
+        &mut self,
+        path: &[usize],
+        text: T,
+    ) -> Result<(), HtmlDocError> {
+        match self.node_at_mut(path) {
+            Some(node) => node.set_text(text),
+            None => Err(no_node_error(path)),
+        }
+    }
+}
+
+impl HtmlTag {
+    /// Adds the node to the end of the contents of the tag.
+    pub fn append_child(&mut self, node: HtmlNode) {
+        self.contents.push(node);
+    }
+
+    /// Replaces the contents of the tag with the text. The text is kept as it reads
+    /// and escaped when the document is written, so any markup in it is written as
+    /// text. The text of elements such as `<script>` is written without escaping,
+    /// so it can not contain the end tag of the element.
+    pub fn set_text<T: Into<String>>(&mut self, text: T) -> Result<(), HtmlDocError> {
+        let text = text.into();
+        if is_raw_text_element(&self.tag) {
+            let end_tag = format!("</{}", self.tag.to_ascii_lowercase());
+            if text.to_ascii_lowercase().contains(&end_tag) {
+                return Err(HtmlDocError::with_msg(format!(
+                    "the text of a <{}> can not contain its end tag",
+                    self.tag
+                )));
+            }
+        }
+        self.contents = vec![HtmlNode::Text(text)];
+        Ok(())
+    }
+}
+
+impl HtmlNode {
+    /// Adds the node to the end of the contents of this node, which must be a tag.
+    pub fn append_child(&mut self, node: HtmlNode) -> Result<(), HtmlDocError> {
+        match self {
+            HtmlNode::Tag(t) => {
+                t.append_child(node);
+                Ok(())
+            }
+            _ => Err(HtmlDocError::with_msg("only a tag can have children")),
+        }
+    }
+
+    /// Replaces the contents of a tag with the text, see [`HtmlTag::set_text`], or
+    /// the text of a text node or comment. The text of a comment can not contain
+    /// `-->` or `--!>`, which would end it. A template can not have its text set.
+    pub fn set_text<T: Into<String>>(&mut self, text: T) -> Result<(), HtmlDocError> {
+        match self {
+            HtmlNode::Tag(t) => t.set_text(text)?,
+            HtmlNode::Text(t) => *t = text.into(),
+            HtmlNode::Comment(c) => {
+                let text = text.into();
+                if text.contains("-->") || text.contains("--!>") {
+                    return Err(HtmlDocError::with_msg(
+                        "the text of a comment can not contain the end of a comment",
+                    ));
+                }
+                *c = text;
+            }
+            HtmlNode::Template(_) => {
+                return Err(HtmlDocError::with_msg("can not set the text of a template"))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn no_node_error(path: &[usize]) -> HtmlDocError {
+    HtmlDocError::with_msg(format!("there is no node at {:?}", path))
+}
+
+fn no_tag_error(path: &[usize]) -> HtmlDocError {
+    HtmlDocError::with_msg(format!("there is no tag at {:?}", path))
+}
+
+#[cfg(test)]
+mod dom_tests {
+    use super::*;
+
+    #[test]
+    fn navigation_test() {
+        let doc = "<!-- c --><div><h1>Title</h1>text<p>a <b>b</b></p></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let roots: Vec<NodeRef> = doc.root_nodes().collect();
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].node(), &HtmlNode::Comment(" c ".to_owned()));
+        assert!(roots[0].parent().is_none() && roots[0].previous_sibling().is_none());
+        assert_eq!(roots[0].next_sibling(), Some(roots[1].clone()));
+
+        let div = &roots[1];
+        assert_eq!(div.children().count(), 3);
+        assert_eq!(div.first_child().unwrap().tag().unwrap().tag, "h1");
+        let p = div.last_child().unwrap();
+        assert_eq!(p.path(), &[1, 2]);
+        assert_eq!(p.previous_sibling().unwrap().node().text(), "text");
+        assert!(p.next_sibling().is_none());
+
+        let b_text = p.child(1).unwrap().first_child().unwrap();
+        assert_eq!(b_text.node(), &HtmlNode::Text("b".to_owned()));
+        assert!(b_text.first_child().is_none() && b_text.last_child().is_none());
+        let ancestors: Vec<&str> = b_text
+            .ancestors()
+            .map(|a| a.tag().unwrap().tag.as_str())
+            .collect();
+        assert_eq!(ancestors, vec!["b", "p", "div"]);
+        assert!(doc.node_ref(&[1, 5]).is_none());
+        // the same place in another document is a different node
+        let copy = doc.clone();
+        assert_ne!(copy.node_ref(&[1]), doc.node_ref(&[1]));
+    }
+
+    #[test]
+    fn mutation_test() {
+        let mut doc = "<div><p>a</p><span>c</span></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        assert_eq!(
+            doc.append_child(&[], HtmlNode::Text("end".to_owned()))
+                .unwrap(),
+            vec![1]
+        );
+        doc.insert_before(&[0, 2], HtmlNode::Tag(HtmlTag::new("hr")))
+            .unwrap();
+        doc.set_text(&[0, 1], " changed ").unwrap();
+        let mut b = HtmlNode::Tag(HtmlTag::new("b"));
+        b.append_child(HtmlNode::Text("bold".to_owned())).unwrap();
+        let p = doc.replace_with(&[0, 0], b).unwrap();
+        assert_eq!(p.text(), "a");
+        assert_eq!(
+            doc.to_html_string(),
+            "<div><b>bold</b><span> changed </span><hr></hr></div>end"
+        );
+        // the navigation sees the changed tree
+        let hr = doc.node_ref(&[0, 2]).unwrap();
+        assert_eq!(hr.previous_sibling().unwrap().node().text(), " changed ");
+
+        let errors = [
+            doc.append_child(&[1], HtmlNode::Text("x".to_owned()))
+                .unwrap_err(),
+            doc.insert_before(&[0, 4], HtmlNode::Text("x".to_owned()))
+                .unwrap_err(),
+            doc.remove_child(&[0, 3]).unwrap_err(),
+            doc.remove_child(&[]).unwrap_err(),
+            doc.replace_with(&[2], HtmlNode::Text("x".to_owned()))
+                .unwrap_err(),
+            doc.set_text(&[0, 0, 1], "x").unwrap_err(),
+        ];
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Html Doc Error: 'there is no tag at [1]'",
+                "Html Doc Error: 'there is no node at [0, 4]'",
+                "Html Doc Error: 'there is no node at [0, 3]'",
+                "Html Doc Error: 'there is no node at []'",
+                "Html Doc Error: 'there is no node at [2]'",
+                "Html Doc Error: 'there is no node at [0, 0, 1]'",
+            ]
+        );
+        assert_eq!(
+            doc.remove_child(&[1]).unwrap(),
+            HtmlNode::Text("end".to_owned())
+        );
+        assert!(HtmlNode::Template("{{ x }}".to_owned())
+            .set_text("y")
+            .is_err());
+    }
+
+    #[test]
+    fn set_text_test() {
+        let mut doc = "<div><p>a</p><!-- c --><script>x</script></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        // markup in the text is written as text
+        doc.set_text(&[0, 0], "<img src=x onerror=alert(1)>")
+            .unwrap();
+        assert_eq!(
+            doc.node_at(&[0, 0]).unwrap().to_html_string(),
+            "<p>&lt;img src=x onerror=alert(1)&gt;</p>"
+        );
+        assert!(doc.set_text(&[0, 1], "a -> b").is_ok());
+        for text in ["-->", "a --!> b"] {
+            assert!(doc.set_text(&[0, 1], text).is_err());
+        }
+        assert!(doc.set_text(&[0, 2], "if (a < b) {}").is_ok());
+        assert!(doc.set_text(&[0, 2], "</SCRIPT><img>").is_err());
+        assert_eq!(
+            doc.to_html_string(),
+            "<div><p>&lt;img src=x onerror=alert(1)&gt;</p><!-- a -> b --!><script>if (a < b) {}</script></div>"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::hash::fnv1a_64;
 use std::error::Error;
 
 pub struct HtmlMatchError {
@@ -53,14 +54,207 @@ impl std::fmt::Debug for HtmlDocError {
     }
 }
 
-#[derive(PartialEq)]
+/// How the parts of an error message which came from the input are shown when
+/// the error is displayed, so that logging an error does not copy large or
+/// sensitive input into the logs.
+///
+/// Input is found as the text in single quotes, eg `'<div'` in `unexpected tag
+/// '<div'`, so the parse errors of this crate quote every piece of input in their
+/// messages. Control characters anywhere in the message are escaped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    /// The most chars of each piece of input which are shown.
+    pub max_input_len: usize,
+    /// The most chars of the whole message which are shown.
+    pub max_len: usize,
+    /// Escape control characters, eg a newline is shown as `\n`.
+    pub escape_control: bool,
+    /// Show a hash of each piece of input instead of the input, so that errors
+    /// about the same input can still be matched up.
+    pub hash_input: bool,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Redaction {
+    /// Creates the redaction used by default, which shows the first 64 chars of
+    /// each piece of input and the first 512 chars of the message.
+    pub fn new() -> Redaction {
+        Redaction {
+            max_input_len: 64,
+            max_len: 512,
+            escape_control: true,
+            hash_input: false,
+        }
+    }
+
+    /// Creates a redaction which shows the message as it is.
+    pub fn none() -> Redaction {
+        Redaction {
+            max_input_len: usize::MAX,
+            max_len: usize::MAX,
+            escape_control: false,
+            hash_input: false,
+        }
+    }
+
+    pub fn max_input_len(mut self, max_input_len: usize) -> Redaction {
+        self.max_input_len = max_input_len;
+        self
+    }
+
+    pub fn max_len(mut self, max_len: usize) -> Redaction {
+        self.max_len = max_len;
+        self
+    }
+
+    pub fn escape_control(mut self, escape_control: bool) -> Redaction {
+        self.escape_control = escape_control;
+        self
+    }
+
+    pub fn hash_input(mut self, hash_input: bool) -> Redaction {
+        self.hash_input = hash_input;
+        self
+    }
+
+    /// Applies the redaction to a message.
+    pub fn apply(&self, msg: &str) -> String {
+        let mut output = String::with_capacity(msg.len().min(self.max_len));
+        let mut rest = msg;
+        while let Some((before, input, after)) = next_quoted(rest) {
+            output.push_str(before);
+            output.push('\'');
+            if self.hash_input {
+                let hash = fnv1a_64(input.as_bytes());
+                output.push_str(&format!("#{:016x}", hash));
+            } else {
+                output.push_str(&truncate_chars(input, self.max_input_len));
+            }
+            output.push('\'');
+            rest = after;
+        }
+        output.push_str(rest);
+        if self.escape_control {
+            output = output
+                .chars()
+                .map(|c| {
+                    if c.is_control() {
+                        c.escape_default().to_string()
+                    } else {
+                        c.to_string()
+                    }
+                })
+                .collect();
+        }
+        truncate_chars(&output, self.max_len)
+    }
+}
+
+/// Cuts the string to the number of chars, saying how many were left out.
+fn truncate_chars(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((end, _)) => format!("{}...[{} more chars]", &s[..end], s[end..].chars().count()),
+        None => s.to_owned(),
+    }
+}
+
+/// Finds the first text in single quotes, returning the text before it, the
+/// quoted text and the text after it. Apostrophes within words are not quotes.
+fn next_quoted(s: &str) -> Option<(&str, &str, &str)> {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric());
+    let start = s
+        .match_indices('\'')
+        .map(|(i, _)| i)
+        .find(|i| !is_word(s[..*i].chars().next_back()))?;
+    let inner = &s[start + 1..];
+    let end = inner
+        .match_indices('\'')
+        .map(|(i, _)| i)
+        .find(|i| !is_word(inner[i + 1..].chars().next()))?;
+    Some((&s[..start], &inner[..end], &inner[end + 1..]))
+}
+
+/// What sort of input a [`ParseHtmlError`] is about, so that errors can be
+/// counted and grouped without reading their messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ParseErrorKind {
+    /// The markup is not valid.
+    Syntax,
+    /// The input ended before something was finished, eg a tag or a quoted value.
+    UnexpectedEnd,
+    /// A CSS selector is not valid.
+    Selector,
+    /// The value of an attribute, a media query or a character reference is not valid.
+    Value,
+    /// The document was parsed but is not written back the same as its source, see
+    /// [`ParseOptions::verify_lossless`](crate::options::ParseOptions::verify_lossless).
+    Lossy,
+}
+
 pub struct ParseHtmlError {
     pub msg: String,
+    /// The byte offset into the source where parsing stopped, if it is known.
+    offset: Option<usize>,
+    kind: ParseErrorKind,
+    /// The context added to the error, starting with the outermost.
+    frames: Vec<String>,
+    redaction: Redaction,
 }
 
 impl ParseHtmlError {
     pub fn new(msg: String) -> ParseHtmlError {
-        ParseHtmlError { msg: msg }
+        ParseHtmlError {
+            msg: msg,
+            offset: None,
+            kind: ParseErrorKind::Syntax,
+            frames: vec![],
+            redaction: Redaction::new(),
+        }
+    }
+
+    /// Creates an error of the kind [`ParseErrorKind::Value`].
+    pub(crate) fn invalid_value<S: Into<String>>(msg: S) -> ParseHtmlError {
+        ParseHtmlError::with_msg(msg).with_kind(ParseErrorKind::Value)
+    }
+
+    /// Sets the kind of the error, keeping any kind other than
+    /// [`ParseErrorKind::Syntax`] which was set closer to the error.
+    pub fn with_kind(mut self, kind: ParseErrorKind) -> ParseHtmlError {
+        if self.kind == ParseErrorKind::Syntax {
+            self.kind = kind;
+        }
+        self
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Gets the context which was added to the error, starting with the outermost.
+    pub fn frames(&self) -> &[String] {
+        &self.frames
+    }
+
+    /// Sets how the message is redacted when the error is displayed. The `msg`
+    /// and the Debug output are always the full message.
+    pub fn with_redaction(mut self, redaction: Redaction) -> ParseHtmlError {
+        self.redaction = redaction;
+        self
+    }
+
+    /// Gets the message as it would be displayed with the redaction provided.
+    pub fn redacted(&self, redaction: &Redaction) -> String {
+        format!("Html Doc Error: '{}'", redaction.apply(&self.msg))
     }
 
     pub fn with_msg<S: Into<String>>(msg: S) -> ParseHtmlError {
@@ -68,17 +262,88 @@ impl ParseHtmlError {
     }
 
     pub fn add_context<S: Into<String>>(mut self, msg: S) -> ParseHtmlError {
-        self.msg = format!("{} because {}", msg.into(), self.msg);
+        let msg = msg.into();
+        self.msg = format!("{} because {}", msg, self.msg);
+        self.frames.insert(0, msg);
+        self
+    }
+
+    /// Records the byte offset into the source where the error happened, keeping
+    /// any offset which was already recorded closer to the error.
+    pub fn at_offset(mut self, offset: usize) -> ParseHtmlError {
+        if self.offset.is_none() {
+            self.offset = Some(offset);
+        }
         self
     }
+
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Gets the line and column (both starting from 1) of the error in the source
+    /// it was parsed from.
+    pub fn line_col(&self, src: &str) -> Option<(usize, usize)> {
+        let offset = self.offset?;
+        let before = src.get(..offset)?;
+        let line = before.matches('\n').count() + 1;
+        let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        Some((line, col))
+    }
 }
 
 impl std::fmt::Display for ParseHtmlError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        write!(f, "Html Doc Error: '{}'", self.msg)?;
+        write!(f, "{}", self.redacted(&self.redaction))?;
         Ok(())
     }
 }
+/// Errors are equal if they have the same message and offset. The kind and frames
+/// come from the same places as the message, and the redaction only changes how
+/// it is shown.
+impl PartialEq for ParseHtmlError {
+    fn eq(&self, other: &Self) -> bool {
+        self.msg == other.msg && self.offset == other.offset
+    }
+}
+
+/// Writes the error as a JSON object for structured logging, with its kind,
+/// severity, message, position and frames. The message and frames are redacted
+/// in the same way as when the error is displayed.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ParseHtmlError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json(None).serialize(serializer)
+    }
+}
+
+impl ParseHtmlError {
+    /// Gets the error as a JSON value, see the Serialize impl. If the source the
+    /// error came from is provided, the position includes its line and column.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self, src: Option<&str>) -> serde_json::Value {
+        let position = self.offset.map(|offset| {
+            let mut position = serde_json::json!({ "offset": offset });
+            if let Some((line, column)) = src.and_then(|src| self.line_col(src)) {
+                position["line"] = line.into();
+                position["column"] = column.into();
+            }
+            position
+        });
+        serde_json::json!({
+            "kind": self.kind,
+            "severity": "error",
+            "message": self.redaction.apply(&self.msg),
+            "position": position,
+            "frames": self
+                .frames
+                .iter()
+                .map(|f| self.redaction.apply(f))
+                .collect::<Vec<String>>(),
+        })
+    }
+}
+
 impl std::fmt::Debug for ParseHtmlError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         write!(f, "Html Doc Error: '{}'", self.msg)?;
@@ -86,6 +351,39 @@ impl std::fmt::Debug for ParseHtmlError {
     }
 }
 
+pub struct ConfigError {
+    msg: String,
+}
+
+impl ConfigError {
+    pub fn new(msg: String) -> ConfigError {
+        ConfigError { msg: msg }
+    }
+
+    pub fn with_msg<S: Into<String>>(msg: S) -> ConfigError {
+        return ConfigError::new(msg.into());
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(f, "Config Error: '{}'", self.msg)?;
+        Ok(())
+    }
+}
+impl std::fmt::Debug for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(f, "Config Error: '{}'", self.msg)?;
+        Ok(())
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
 impl Error for ParseHtmlError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         None
@@ -103,3 +401,112 @@ impl Error for HtmlDocError {
         None
     }
 }
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+
+    #[test]
+    fn redaction_test() {
+        let long = "x".repeat(100);
+        let err = ParseHtmlError::with_msg(format!(
+            "could not parse '{}' because it doesn't close\nat '<a\u{7}>'",
+            long
+        ));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Html Doc Error: 'could not parse '{}...[36 more chars]' because it doesn't close\\nat '<a\\u{{7}}>''",
+                "x".repeat(64)
+            )
+        );
+        // the full message is still available
+        assert!(err.msg.contains(&long));
+        assert!(format!("{:?}", err).contains(&long));
+        let hashed = err.redacted(&Redaction::new().hash_input(true).escape_control(false));
+        assert!(!hashed.contains("xxx"));
+        assert!(hashed.contains("close\nat '#"));
+        assert_eq!(
+            err.redacted(&Redaction::new().hash_input(true)),
+            ParseHtmlError::with_msg(err.msg.clone()).redacted(&Redaction::new().hash_input(true))
+        );
+        let err = err.with_redaction(Redaction::none());
+        assert_eq!(err.to_string(), format!("Html Doc Error: '{}'", err.msg));
+        assert_eq!(
+            Redaction::new().max_len(10).apply("0123456789abc"),
+            "0123456789...[3 more chars]"
+        );
+    }
+
+    #[test]
+    fn redact_parse_errors_test() {
+        use crate::objects::{CssSelector, HtmlDocument, HtmlTag};
+        // the input is quoted in each message, so none of it is shown when hashed
+        let errors = [
+            "<p>a</p></secret>".parse::<HtmlDocument>().unwrap_err(),
+            "<!DOCTYPE html><!DOCTYPE secret>"
+                .parse::<HtmlDocument>()
+                .unwrap_err(),
+            "</secret>".parse::<HtmlTag>().unwrap_err(),
+            "<p>a</secret>".parse::<HtmlDocument>().unwrap_err(),
+            "<secret>".parse::<HtmlDocument>().unwrap_err(),
+            "p:secret".parse::<CssSelector>().unwrap_err(),
+        ];
+        for err in errors {
+            assert!(err.msg.contains("secret"), "{}", err.msg);
+            let hashed = err.redacted(&Redaction::new().hash_input(true));
+            assert!(!hashed.contains("secret"), "{}", hashed);
+        }
+        assert_eq!(
+            "<p>a</p></secret>".parse::<HtmlDocument>().unwrap_err().msg,
+            "Found end tag 'secret' before start tag."
+        );
+    }
+
+    #[test]
+    fn kind_and_frames_test() {
+        let err = ParseHtmlError::with_msg("end of string")
+            .with_kind(ParseErrorKind::UnexpectedEnd)
+            .add_context("could not parse tag")
+            .with_kind(ParseErrorKind::Selector)
+            .add_context("could not parse document");
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedEnd);
+        assert_eq!(
+            err.frames(),
+            ["could not parse document", "could not parse tag"]
+        );
+        assert_eq!(
+            ParseHtmlError::with_msg("x")
+                .with_kind(ParseErrorKind::Value)
+                .kind(),
+            ParseErrorKind::Value
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn to_json_test() {
+        let src = "<div>\n<p class=";
+        let err = ParseHtmlError::with_msg("no value for 'class'")
+            .with_kind(ParseErrorKind::UnexpectedEnd)
+            .at_offset(15)
+            .add_context("could not parse <p>");
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["kind"], "unexpected-end");
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["position"], serde_json::json!({ "offset": 15 }));
+        assert_eq!(json["frames"], serde_json::json!(["could not parse <p>"]));
+        assert_eq!(
+            err.to_json(Some(src))["position"],
+            serde_json::json!({ "offset": 15, "line": 2, "column": 10 })
+        );
+        let err = ParseHtmlError::with_msg("x").with_redaction(Redaction::new().hash_input(true));
+        assert_eq!(err.to_json(None)["position"], serde_json::Value::Null);
+        let err = ParseHtmlError::with_msg("bad 'secret'")
+            .with_redaction(Redaction::new().hash_input(true));
+        assert!(!err.to_json(None)["message"]
+            .as_str()
+            .unwrap()
+            .contains("secret"));
+    }
+}
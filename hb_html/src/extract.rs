@@ -0,0 +1,679 @@
+//! Finding phone numbers and postal codes in the text of a document.
+//!
+//! The extractors are tolerant, so they find tokens which look like phone numbers
+//! or postal codes for the countries provided rather than checking they exist.
+//! Each token found is returned with the path of the text node it is in and its
+//! byte range within that text.
+//!
+//! The text nodes can also be extracted with [`TextExtractOptions`], which keep,
+//! strip or replace control characters, zero-width characters and emoji.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::extract::{PhonePattern, PostalPattern};
+//! use hb_html::objects::HtmlDocument;
+//! let doc = "<p>Call <b>+44 20 7946 0958</b> or write to SW1A 1AA.</p>"
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let phones = doc.extract_phone_numbers(&[PhonePattern::gb()]);
+//! assert_eq!(phones[0].text, "+44 20 7946 0958");
+//! assert_eq!(phones[0].path, vec![0, 1, 0]);
+//! let postcodes = doc.extract_postal_codes(&[PostalPattern::gb()]);
+//! assert_eq!(postcodes[0].text, "SW1A 1AA");
+//! assert_eq!(postcodes[0].range, 13..21);
+//! ```
+use crate::objects::{HtmlDocument, HtmlNode, NodePath};
+use std::ops::{Range, RangeInclusive};
+
+/// The tags whose text is not shown, so is not searched.
+const HIDDEN_TEXT_TAGS: [&str; 2] = ["script", "style"];
+
+/// What the phone numbers of a country look like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhonePattern {
+    /// The name of the country, used as the country of the tokens found.
+    pub country: String,
+    /// The digits after the `+` (or `00`) in international numbers, eg "44".
+    pub calling_code: String,
+    /// The digits which can be before a national number, eg the "0" in "020 7946 0958".
+    pub trunk_prefix: Option<String>,
+    /// The number of digits allowed after the calling code or trunk prefix.
+    pub national_digits: RangeInclusive<usize>,
+}
+
+impl PhonePattern {
+    pub fn new<C: Into<String>, D: Into<String>>(
+        country: C,
+        calling_code: D,
+        trunk_prefix: Option<&str>,
+        national_digits: RangeInclusive<usize>,
+    ) -> PhonePattern {
+        PhonePattern {
+            country: country.into(),
+            calling_code: calling_code.into(),
+            trunk_prefix: trunk_prefix.map(|t| t.to_owned()),
+            national_digits,
+        }
+    }
+
+    pub fn us() -> PhonePattern {
+        PhonePattern::new("US", "1", Some("1"), 10..=10)
+    }
+
+    pub fn gb() -> PhonePattern {
+        PhonePattern::new("GB", "44", Some("0"), 9..=10)
+    }
+
+    pub fn de() -> PhonePattern {
+        PhonePattern::new("DE", "49", Some("0"), 6..=11)
+    }
+
+    pub fn fr() -> PhonePattern {
+        PhonePattern::new("FR", "33", Some("0"), 9..=9)
+    }
+
+    pub fn au() -> PhonePattern {
+        PhonePattern::new("AU", "61", Some("0"), 9..=9)
+    }
+
+    /// Checks whether the digits of a number match the pattern. International is
+    /// true if the number started with a `+`.
+    fn matches(&self, digits: &str, international: bool) -> bool {
+        let national = if international {
+            digits.strip_prefix(self.calling_code.as_str())
+        } else if let Some(n) = digits
+            .strip_prefix("00")
+            .and_then(|d| d.strip_prefix(self.calling_code.as_str()))
+        {
+            Some(n)
+        } else {
+            // the trunk prefix is optional, eg US numbers are written with and without the 1
+            Some(
+                self.trunk_prefix
+                    .as_deref()
+                    .and_then(|t| digits.strip_prefix(t))
+                    .filter(|n| self.national_digits.contains(&n.len()))
+                    .unwrap_or(digits),
+            )
+        };
+        national.is_some_and(|n| self.national_digits.contains(&n.len()))
+    }
+}
+
+/// What the postal codes of a country look like.
+///
+/// Each format is a mask where `#` is a digit, `@` is a letter and any other
+/// character must appear as it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostalPattern {
+    /// The name of the country, used as the country of the tokens found.
+    pub country: String,
+    pub formats: Vec<String>,
+}
+
+impl PostalPattern {
+    pub fn new<C: Into<String>>(country: C, formats: &[&str]) -> PostalPattern {
+        PostalPattern {
+            country: country.into(),
+            formats: formats.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    pub fn us() -> PostalPattern {
+        PostalPattern::new("US", &["#####-####", "#####"])
+    }
+
+    pub fn gb() -> PostalPattern {
+        PostalPattern::new(
+            "GB",
+            &[
+                "@# #@@", "@## #@@", "@@# #@@", "@@## #@@", "@#@ #@@", "@@#@ #@@",
+            ],
+        )
+    }
+
+    pub fn ca() -> PostalPattern {
+        PostalPattern::new("CA", &["@#@ #@#", "@#@#@#"])
+    }
+
+    pub fn de() -> PostalPattern {
+        PostalPattern::new("DE", &["#####"])
+    }
+
+    pub fn fr() -> PostalPattern {
+        PostalPattern::new("FR", &["#####"])
+    }
+
+    pub fn nl() -> PostalPattern {
+        PostalPattern::new("NL", &["#### @@", "####@@"])
+    }
+
+    pub fn au() -> PostalPattern {
+        PostalPattern::new("AU", &["####"])
+    }
+
+    /// Gets the length in bytes of the longest format which matches the start of the text.
+    fn match_len(&self, text: &str) -> Option<usize> {
+        self.formats
+            .iter()
+            .filter_map(|format| {
+                let mut chars = text.char_indices();
+                for f in format.chars() {
+                    let (_, c) = chars.next()?;
+                    let is_match = match f {
+                        '#' => c.is_ascii_digit(),
+                        '@' => c.is_ascii_alphabetic(),
+                        f => c.eq_ignore_ascii_case(&f),
+                    };
+                    if !is_match {
+                        return None;
+                    }
+                }
+                Some(chars.next().map_or(text.len(), |(i, _)| i))
+            })
+            .max()
+    }
+}
+
+/// A token found in a piece of text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenSpan {
+    /// The byte range of the token in the text.
+    pub range: Range<usize>,
+    /// The country of the pattern which matched.
+    pub country: String,
+}
+
+/// A token found in a text node of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedToken {
+    /// The path of the text node.
+    pub path: NodePath,
+    /// The byte range of the token in the text node.
+    pub range: Range<usize>,
+    pub text: String,
+    pub country: String,
+}
+
+/// Checks that there is not a letter or digit directly before the byte position.
+fn is_token_start(text: &str, i: usize) -> bool {
+    !text[..i]
+        .chars()
+        .next_back()
+        .is_some_and(|c| c.is_alphanumeric())
+}
+
+/// Checks that there is not a letter or digit at the byte position.
+fn is_token_end(text: &str, i: usize) -> bool {
+    !text[i..]
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric())
+}
+
+fn is_phone_separator(c: char) -> bool {
+    matches!(c, ' ' | '-' | '.' | '(' | ')' | '/')
+}
+
+/// Finds the tokens in the text which look like a phone number for one of the
+/// patterns. A phone number is a run of digits which can start with a `+` and have
+/// short runs of spaces, `-`, `.`, `/` or brackets between them.
+pub fn phone_spans(text: &str, patterns: &[PhonePattern]) -> Vec<TokenSpan> {
+    let mut spans = vec![];
+    let mut i = 0;
+    while i < text.len() {
+        let c = match text[i..].chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        let starts_number = c.is_ascii_digit() || c == '+' || c == '(';
+        if !starts_number || !is_token_start(text, i) {
+            i += c.len_utf8();
+            continue;
+        }
+        let mut digits = String::new();
+        let mut end = i;
+        let mut separators = 0;
+        for (j, c) in text[i..].char_indices() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                end = i + j + 1;
+                separators = 0;
+            } else if (j == 0 && c == '+') || (is_phone_separator(c) && separators < 2) {
+                separators += 1;
+            } else {
+                break;
+            }
+        }
+        // a closing bracket straight after the digits is part of the number
+        if text[end..].starts_with(')') && text[i..end].contains('(') {
+            end += 1;
+        }
+        if end > i && is_token_end(text, end) {
+            let international = c == '+';
+            if let Some(pattern) = patterns.iter().find(|p| p.matches(&digits, international)) {
+                spans.push(TokenSpan {
+                    range: i..end,
+                    country: pattern.country.clone(),
+                });
+            }
+        }
+        i = end.max(i + c.len_utf8());
+    }
+    spans
+}
+
+/// Finds the tokens in the text which match a format of one of the patterns.
+pub fn postal_spans(text: &str, patterns: &[PostalPattern]) -> Vec<TokenSpan> {
+    let mut spans = vec![];
+    let mut i = 0;
+    while i < text.len() {
+        let c = match text[i..].chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        if is_token_start(text, i) {
+            let found = patterns
+                .iter()
+                .filter_map(|p| p.match_len(&text[i..]).map(|len| (len, p)))
+                .filter(|(len, _)| is_token_end(text, i + len))
+                .max_by_key(|(len, _)| *len);
+            if let Some((len, pattern)) = found {
+                spans.push(TokenSpan {
+                    range: i..i + len,
+                    country: pattern.country.clone(),
+                });
+                i += len;
+                continue;
+            }
+        }
+        i += c.len_utf8();
+    }
+    spans
+}
+
+/// What is done with a kind of character when text is extracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CharPolicy {
+    Keep,
+    Strip,
+    /// Replace each character (or emoji sequence) with the string.
+    Replace(String),
+}
+
+/// A function which gets the text to use in place of an emoji sequence.
+pub type EmojiFn = Box<dyn Fn(&str) -> String>;
+
+/// Options for cleaning the text of a document as it is extracted. Everything is
+/// kept by default.
+pub struct TextExtractOptions {
+    /// Control characters other than whitespace, eg NUL and the C1 controls.
+    pub control: CharPolicy,
+    /// Zero-width and invisible formatting characters, eg zero-width spaces,
+    /// joiners, bidi controls, soft hyphens and byte order marks. Zero-width joiners
+    /// within an emoji are part of the emoji.
+    pub zero_width: CharPolicy,
+    /// Emoji, including their modifiers, variation selectors and joined sequences.
+    pub emoji: CharPolicy,
+    emoji_fn: Option<EmojiFn>,
+}
+
+impl Default for TextExtractOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextExtractOptions {
+    pub fn new() -> TextExtractOptions {
+        TextExtractOptions {
+            control: CharPolicy::Keep,
+            zero_width: CharPolicy::Keep,
+            emoji: CharPolicy::Keep,
+            emoji_fn: None,
+        }
+    }
+
+    pub fn control(mut self, policy: CharPolicy) -> TextExtractOptions {
+        self.control = policy;
+        self
+    }
+
+    pub fn zero_width(mut self, policy: CharPolicy) -> TextExtractOptions {
+        self.zero_width = policy;
+        self
+    }
+
+    pub fn emoji(mut self, policy: CharPolicy) -> TextExtractOptions {
+        self.emoji = policy;
+        self
+    }
+
+    /// Sets a function which is called with each emoji sequence to get the text to
+    /// use in its place. This is used instead of the emoji policy.
+    pub fn emoji_with<F: Fn(&str) -> String + 'static>(mut self, f: F) -> TextExtractOptions {
+        self.emoji_fn = Some(Box::new(f));
+        self
+    }
+
+    /// Cleans the text using the options.
+    pub fn clean(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut i = 0;
+        while i < text.len() {
+            let c = text[i..].chars().next().unwrap_or_default();
+            let emoji_len = emoji_len(&text[i..]);
+            if emoji_len > 0 {
+                let emoji = &text[i..i + emoji_len];
+                match &self.emoji_fn {
+                    Some(f) => output.push_str(&f(emoji)),
+                    None => apply_policy(&self.emoji, emoji, &mut output),
+                }
+                i += emoji_len;
+                continue;
+            }
+            let policy = if is_zero_width(c) {
+                &self.zero_width
+            } else if c.is_control() && !c.is_whitespace() {
+                &self.control
+            } else {
+                &CharPolicy::Keep
+            };
+            apply_policy(policy, &text[i..i + c.len_utf8()], &mut output);
+            i += c.len_utf8();
+        }
+        output
+    }
+}
+
+fn apply_policy(policy: &CharPolicy, s: &str, output: &mut String) {
+    match policy {
+        CharPolicy::Keep => output.push_str(s),
+        CharPolicy::Strip => {}
+        CharPolicy::Replace(replacement) => output.push_str(replacement),
+    }
+}
+
+/// Checks for the zero-width and invisible formatting characters.
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{AD}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{206F}'
+            | '\u{FEFF}'
+    )
+}
+
+/// Checks for the characters which are shown as emoji, leaving out the ones
+/// (such as digits and `©`) which are only emoji with a variation selector.
+fn is_emoji_base(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1F000}'..='\u{1FAFF}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{2B00}'..='\u{2BFF}'
+            | '\u{231A}'..='\u{23FF}'
+            | '\u{3030}'
+            | '\u{303D}'
+            | '\u{3297}'
+            | '\u{3299}'
+    )
+}
+
+/// Checks for the characters which change the emoji before them.
+fn is_emoji_modifier(c: char) -> bool {
+    matches!(
+        c,
+        '\u{FE0E}' | '\u{FE0F}' | '\u{20E3}' | '\u{1F3FB}'..='\u{1F3FF}' | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+/// Gets the length in bytes of the emoji sequence at the start of the text, or 0
+/// if the text does not start with an emoji.
+fn emoji_len(text: &str) -> usize {
+    let mut chars = text.char_indices().peekable();
+    let first = match chars.next() {
+        Some((_, c)) => c,
+        None => return 0,
+    };
+    let mut len = first.len_utf8();
+    if !is_emoji_base(first) {
+        // keycaps such as 1️⃣ and symbols such as ©️ are emoji with a variation selector
+        let rest = &text[len..];
+        let keycap = first.is_ascii_digit() || first == '#' || first == '*';
+        if keycap && (rest.starts_with("\u{FE0F}\u{20E3}") || rest.starts_with('\u{20E3}')) {
+            return len + rest.find('\u{20E3}').unwrap_or(0) + '\u{20E3}'.len_utf8();
+        }
+        if !keycap && !first.is_alphanumeric() && rest.starts_with('\u{FE0F}') {
+            return len + '\u{FE0F}'.len_utf8();
+        }
+        return 0;
+    }
+    // a flag is a pair of regional indicators
+    if is_regional_indicator(first) {
+        if let Some((i, c)) = chars.peek().copied() {
+            if is_regional_indicator(c) {
+                return i + c.len_utf8();
+            }
+        }
+        return len;
+    }
+    while let Some((i, c)) = chars.next() {
+        if is_emoji_modifier(c) {
+            len = i + c.len_utf8();
+        } else if c == '\u{200D}' {
+            match chars.peek().copied() {
+                Some((j, next)) if is_emoji_base(next) => {
+                    chars.next();
+                    len = j + next.len_utf8();
+                }
+                _ => break,
+            }
+        } else {
+            break;
+        }
+    }
+    len
+}
+
+fn collect_text_nodes<'a>(
+    nodes: &'a [HtmlNode],
+    path: &mut NodePath,
+    found: &mut Vec<(NodePath, &'a str)>,
+) {
+    for (i, node) in nodes.iter().enumerate() {
+        path.push(i);
+        match node {
+            HtmlNode::Text(t) => found.push((path.clone(), t.as_str())),
+            HtmlNode::Tag(t) if !HIDDEN_TEXT_TAGS.contains(&t.tag.as_str()) => {
+                collect_text_nodes(&t.contents, path, found)
+            }
+            _ => {}
+        }
+        path.pop();
+    }
+}
+
+impl HtmlDocument {
+    /// Gets the path and text of all of the text nodes in the document, in document
+    /// order. The text inside `<script>` and `<style>` elements is left out.
+    pub fn text_nodes(&self) -> Vec<(NodePath, &str)> {
+        let mut found = vec![];
+        collect_text_nodes(&self.nodes, &mut vec![], &mut found);
+        found
+    }
+
+    /// Gets the path and text of the text nodes like [`HtmlDocument::text_nodes`],
+    /// with the text cleaned using the options. Text nodes which are left empty are
+    /// left out.
+    pub fn text_nodes_with(&self, options: &TextExtractOptions) -> Vec<(NodePath, String)> {
+        self.text_nodes()
+            .into_iter()
+            .map(|(path, text)| (path, options.clean(text)))
+            .filter(|(_, text)| !text.is_empty())
+            .collect()
+    }
+
+    /// Finds the phone numbers in the text of the document. See [`phone_spans`].
+    pub fn extract_phone_numbers(&self, patterns: &[PhonePattern]) -> Vec<ExtractedToken> {
+        self.extract_tokens(|text| phone_spans(text, patterns))
+    }
+
+    /// Finds the postal codes in the text of the document. See [`postal_spans`].
+    pub fn extract_postal_codes(&self, patterns: &[PostalPattern]) -> Vec<ExtractedToken> {
+        self.extract_tokens(|text| postal_spans(text, patterns))
+    }
+
+    fn extract_tokens<F: Fn(&str) -> Vec<TokenSpan>>(&self, find: F) -> Vec<ExtractedToken> {
+        let mut tokens = vec![];
+        for (path, text) in self.text_nodes() {
+            for span in find(text) {
+                tokens.push(ExtractedToken {
+                    path: path.clone(),
+                    text: text[span.range.clone()].to_owned(),
+                    range: span.range,
+                    country: span.country,
+                });
+            }
+        }
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod extract_tests {
+    use super::*;
+
+    fn phone_texts(text: &str, patterns: &[PhonePattern]) -> Vec<String> {
+        phone_spans(text, patterns)
+            .into_iter()
+            .map(|s| text[s.range].to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn phone_spans_test() {
+        let patterns = [PhonePattern::us(), PhonePattern::gb()];
+        let text = "Call (555) 123-4567, 1-800-555-0199 or +44 20 7946 0958. \
+                    UK office: 020 7946 0958 / 0044 20 7946 0958.";
+        assert_eq!(
+            phone_texts(text, &patterns),
+            vec![
+                "(555) 123-4567",
+                "1-800-555-0199",
+                "+44 20 7946 0958",
+                "020 7946 0958",
+                "0044 20 7946 0958"
+            ]
+        );
+        let spans = phone_spans("+1 555 123 4567", &patterns);
+        assert_eq!(spans[0].country, "US");
+        assert_eq!(spans[0].range, 0..15);
+    }
+
+    #[test]
+    fn phone_spans_rejects_test() {
+        let patterns = [PhonePattern::us()];
+        for text in [
+            "12345",
+            "order 555-1234",
+            "+44 20 7946 0958",
+            "id555 123 4567",
+            "555 123 4567abc",
+            "5551234567890",
+        ] {
+            assert_eq!(
+                phone_texts(text, &patterns),
+                Vec::<String>::new(),
+                "{} should not match",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn postal_spans_test() {
+        let patterns = [
+            PostalPattern::us(),
+            PostalPattern::gb(),
+            PostalPattern::ca(),
+        ];
+        let text = "Zip 90210-1234, ec1a 1bb, M5V 3L9 and 02134.";
+        let spans = postal_spans(text, &patterns);
+        let found: Vec<(&str, &str)> = spans
+            .iter()
+            .map(|s| (&text[s.range.clone()], s.country.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("90210-1234", "US"),
+                ("ec1a 1bb", "GB"),
+                ("M5V 3L9", "CA"),
+                ("02134", "US")
+            ]
+        );
+        // tokens must not be part of longer words or numbers
+        assert!(postal_spans("123456 A90210 SW1A1AA", &patterns).is_empty());
+    }
+
+    #[test]
+    fn extract_from_document_test() {
+        let doc = "<div><p>Tel: 020 7946 0958</p><script>var t = '020 7946 0958';</script>\
+                   <p>Café, <i>SW1A 1AA</i> – 020 7946 0000</p></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let phones = doc.extract_phone_numbers(&[PhonePattern::gb()]);
+        assert_eq!(phones.len(), 2);
+        assert_eq!(phones[0].path, vec![0, 0, 0]);
+        assert_eq!(phones[0].range, 5..18);
+        assert_eq!(phones[1].path, vec![0, 2, 2]);
+        assert_eq!(phones[1].text, "020 7946 0000");
+        let postcodes = doc.extract_postal_codes(&[PostalPattern::gb()]);
+        assert_eq!(postcodes.len(), 1);
+        assert_eq!(postcodes[0].path, vec![0, 2, 1, 0]);
+        assert_eq!(postcodes[0].country, "GB");
+    }
+
+    #[test]
+    fn clean_text_test() {
+        let text = "a\u{0}b\u{200B}c \u{1F44D}\u{1F3FD} \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}!\u{1F1EC}\u{1F1E7} 1\u{FE0F}\u{20E3} ©\u{FE0F}©\t";
+        assert_eq!(TextExtractOptions::new().clean(text), text);
+        let options = TextExtractOptions::new()
+            .control(CharPolicy::Strip)
+            .zero_width(CharPolicy::Replace(" ".to_owned()))
+            .emoji(CharPolicy::Replace("<e>".to_owned()));
+        assert_eq!(options.clean(text), "ab c <e> <e>!<e> <e> <e>©\t");
+        let options = TextExtractOptions::new()
+            .emoji(CharPolicy::Strip)
+            .emoji_with(|e| format!("[{}]", e.chars().count()));
+        assert_eq!(
+            options.clean("\u{2764}\u{FE0F} x\u{200D} \u{1F600}"),
+            "[2] x\u{200D} [1]"
+        );
+    }
+
+    #[test]
+    fn text_nodes_with_test() {
+        let doc = "<div><p>Hi \u{1F44B}</p><p>\u{200B}</p><script>\u{1F600}</script></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let options = TextExtractOptions::new()
+            .emoji(CharPolicy::Strip)
+            .zero_width(CharPolicy::Strip);
+        assert_eq!(
+            doc.text_nodes_with(&options),
+            vec![(vec![0, 0, 0], "Hi ".to_owned())]
+        );
+        assert_eq!(doc.text_nodes().len(), 2);
+    }
+}
@@ -0,0 +1,331 @@
+//! Rewrites the references to assets, such as stylesheets, scripts and images, to
+//! their fingerprinted (hashed) file names so that they can be cached forever.
+//!
+//! [`FingerprintAssets`] maps the original path of each asset in the site to the
+//! path of the hashed file. URLs are found in the [`URL_ATTRIBUTES`], in `srcset`
+//! and `imagesrcset`, and in the CSS `url()`s of `style` attributes and `<style>`
+//! elements. Relative URLs are resolved against the name of the file they are in,
+//! and the query and fragment of a URL are kept.
+//!
+//! References to paths in the site which have no hashed file are reported, as the
+//! asset will be served without a fingerprint. [`Workspace::fingerprint_assets`]
+//! rewrites every file of a workspace and does not report links to its pages.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::fingerprint::FingerprintAssets;
+//! use hb_html::objects::HtmlDocument;
+//! let mut doc = r#"<div><img src="../img/logo.png" srcset="../img/logo.png 1x, ../img/logo@2x.png 2x"></div>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let assets = FingerprintAssets::new().asset("/img/logo.png", "/img/logo.8d3f.png");
+//! let report = assets.rewrite("blog/post.html", &mut doc);
+//! assert_eq!(report.rewritten, 2);
+//! assert_eq!(report.unmapped[0].url, "../img/logo@2x.png");
+//! let img = doc.find("img").nodes()[0].clone();
+//! assert!(img.to_html_string().contains(r#"src="../img/logo.8d3f.png""#));
+//! ```
+//!
+//! [`Workspace::fingerprint_assets`]: crate::workspace::Workspace::fingerprint_assets
+use crate::hints::css_url_ranges;
+use crate::objects::{HtmlDocument, HtmlTag, NodePath};
+use crate::rewrite::URL_ATTRIBUTES;
+use crate::srcset::srcset_url_ranges;
+use crate::visit::{Visit, VisitAction, VisitContext};
+use crate::workspace::site_path;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// A reference to a path in the site which has no fingerprinted file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmappedAsset {
+    /// The name of the file with the reference.
+    pub file: String,
+    /// The path to the element (or the text of a `<style>` element) with the reference.
+    pub path: NodePath,
+    /// The URL as it is written in the document.
+    pub url: String,
+}
+
+/// What was changed by [`FingerprintAssets::rewrite`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FingerprintReport {
+    /// The number of URLs which were rewritten.
+    pub rewritten: usize,
+    pub unmapped: Vec<UnmappedAsset>,
+}
+
+impl FingerprintReport {
+    /// Adds the results of another rewrite to this report.
+    pub fn merge(&mut self, other: FingerprintReport) {
+        self.rewritten += other.rewritten;
+        self.unmapped.extend(other.unmapped);
+    }
+}
+
+/// The fingerprinted file of each asset, and where to look for references to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FingerprintAssets {
+    /// The path of the hashed file, by the original path of the asset. The paths
+    /// are in the site without a leading `/`, eg `css/site.css`.
+    pub assets: BTreeMap<String, String>,
+    /// The attributes which contain a single URL.
+    pub attributes: Vec<String>,
+    /// Whether the URLs in `srcset` and `imagesrcset` attributes are rewritten.
+    pub srcset: bool,
+    /// Whether the `url()`s in `style` attributes and `<style>` elements are rewritten.
+    pub css: bool,
+}
+
+impl Default for FingerprintAssets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FingerprintAssets {
+    /// Creates an empty map which rewrites all of the [`URL_ATTRIBUTES`], srcsets and CSS.
+    pub fn new() -> FingerprintAssets {
+        FingerprintAssets {
+            assets: BTreeMap::new(),
+            attributes: URL_ATTRIBUTES.iter().map(|a| a.to_string()).collect(),
+            srcset: true,
+            css: true,
+        }
+    }
+
+    /// Creates the map from the original and hashed path of each asset.
+    pub fn from_map<I, F, T>(assets: I) -> FingerprintAssets
+    where
+        I: IntoIterator<Item = (F, T)>,
+        F: Into<String>,
+        T: Into<String>,
+    {
+        assets
+            .into_iter()
+            .fold(FingerprintAssets::new(), |map, (from, to)| {
+                map.asset(from, to)
+            })
+    }
+
+    /// Adds the hashed path of an asset. A leading `/` on either path is ignored.
+    pub fn asset<F: Into<String>, T: Into<String>>(mut self, from: F, to: T) -> FingerprintAssets {
+        let from = from.into().trim_start_matches('/').to_owned();
+        let to = to.into().trim_start_matches('/').to_owned();
+        self.assets.insert(from, to);
+        self
+    }
+
+    /// Sets the attributes which contain a single URL.
+    pub fn attributes<T: Into<String>>(mut self, attributes: Vec<T>) -> FingerprintAssets {
+        self.attributes = attributes.into_iter().map(|a| a.into()).collect();
+        self
+    }
+
+    /// Sets whether the URLs in `srcset` and `imagesrcset` attributes are rewritten.
+    pub fn srcset(mut self, srcset: bool) -> FingerprintAssets {
+        self.srcset = srcset;
+        self
+    }
+
+    /// Sets whether the `url()`s in CSS are rewritten.
+    pub fn css(mut self, css: bool) -> FingerprintAssets {
+        self.css = css;
+        self
+    }
+
+    /// Gets the fingerprinted URL for a URL in the file with the name provided.
+    /// Returns Err with the path in the site if the URL is in the site but there
+    /// is no hashed file for it, and Ok(None) for URLs outside the site.
+    ///
+    /// When the hashed file is in the same directory, only the file name of the
+    /// URL is changed so relative URLs stay relative, otherwise the new URL starts
+    /// with `/`.
+    pub fn fingerprinted_url(&self, from: &str, url: &str) -> Result<Option<String>, String> {
+        let path = match site_path(from, url) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let hashed = match self.assets.get(&path) {
+            Some(hashed) => hashed,
+            None => return Err(path),
+        };
+        let url = url.trim();
+        let end = url.find(['?', '#']).unwrap_or(url.len());
+        let (url_path, suffix) = url.split_at(end);
+        let directory = |p: &str| p.rfind('/').map(|i| p[..=i].to_owned()).unwrap_or_default();
+        let new_path = if directory(&path) == directory(hashed) && !url_path.ends_with('/') {
+            let file_name = &hashed[directory(hashed).len()..];
+            format!("{}{}", directory(url_path), file_name)
+        } else {
+            format!("/{}", hashed)
+        };
+        Ok(Some(format!("{}{}", new_path, suffix)))
+    }
+
+    /// Rewrites the asset URLs in the document, which is the file with the name provided.
+    pub fn rewrite(&self, name: &str, doc: &mut HtmlDocument) -> FingerprintReport {
+        let mut visitor = FingerprintVisitor {
+            assets: self,
+            name,
+            report: FingerprintReport::default(),
+        };
+        doc.visit(&mut visitor);
+        visitor.report
+    }
+}
+
+struct FingerprintVisitor<'a> {
+    assets: &'a FingerprintAssets,
+    name: &'a str,
+    report: FingerprintReport,
+}
+
+impl FingerprintVisitor<'_> {
+    /// Rewrites the URLs at the ranges of the value.
+    fn rewrite_ranges(&mut self, value: &mut String, ranges: Vec<Range<usize>>, path: &NodePath) {
+        // the ranges are replaced from the end so the earlier ranges stay valid
+        for range in ranges.into_iter().rev() {
+            let url = &value[range.clone()];
+            match self.assets.fingerprinted_url(self.name, url) {
+                Ok(Some(new_url)) => {
+                    if new_url != url {
+                        value.replace_range(range, &new_url);
+                        self.report.rewritten += 1;
+                    }
+                }
+                Ok(None) => {}
+                Err(_) => self.report.unmapped.push(UnmappedAsset {
+                    file: self.name.to_owned(),
+                    path: path.clone(),
+                    url: url.to_owned(),
+                }),
+            }
+        }
+    }
+}
+
+impl Visit for FingerprintVisitor<'_> {
+    fn enter_element(&mut self, tag: &mut HtmlTag, ctx: &VisitContext) -> VisitAction {
+        let assets = self.assets;
+        for attribute in &assets.attributes {
+            if let Some(value) = tag.attributes.get_mut(attribute) {
+                let start = value.len() - value.trim_start().len();
+                let range = start..value.trim_end().len().max(start);
+                if !range.is_empty() {
+                    self.rewrite_ranges(value, vec![range], &ctx.path);
+                }
+            }
+        }
+        if assets.srcset {
+            for attribute in ["srcset", "imagesrcset"] {
+                if let Some(value) = tag.attributes.get_mut(attribute) {
+                    let ranges = srcset_url_ranges(value);
+                    self.rewrite_ranges(value, ranges, &ctx.path);
+                }
+            }
+        }
+        if assets.css {
+            if let Some(value) = tag.attributes.get_mut("style") {
+                let ranges = css_url_ranges(value);
+                self.rewrite_ranges(value, ranges, &ctx.path);
+            }
+        }
+        VisitAction::Continue
+    }
+
+    fn text(&mut self, text: &mut String, ctx: &VisitContext) -> VisitAction {
+        let in_style = ctx
+            .ancestors
+            .last()
+            .is_some_and(|t| t.eq_ignore_ascii_case("style"));
+        if self.assets.css && in_style {
+            let ranges = css_url_ranges(text);
+            self.rewrite_ranges(text, ranges, &ctx.path);
+        }
+        VisitAction::Continue
+    }
+}
+
+#[cfg(test)]
+mod fingerprint_tests {
+    use super::*;
+    use crate::objects::HtmlNode;
+
+    fn attribute(doc: &HtmlDocument, selector: &str, name: &str) -> String {
+        match &doc.find(selector).nodes()[0] {
+            HtmlNode::Tag(t) => t.attributes[name].clone(),
+            _ => panic!("expected a tag"),
+        }
+    }
+
+    #[test]
+    fn fingerprinted_url_test() {
+        let assets = FingerprintAssets::from_map([
+            ("css/site.css", "css/site.1a2b.css"),
+            ("/fonts/a.woff2", "/static/a.9f.woff2"),
+        ]);
+        let url = |from, url| assets.fingerprinted_url(from, url);
+        assert_eq!(
+            url("index.html", "css/site.css?v=1"),
+            Ok(Some("css/site.1a2b.css?v=1".to_owned()))
+        );
+        assert_eq!(
+            url("blog/post.html", "../css/site.css"),
+            Ok(Some("../css/site.1a2b.css".to_owned()))
+        );
+        assert_eq!(
+            url("css/site.css", "../fonts/a.woff2#x"),
+            Ok(Some("/static/a.9f.woff2#x".to_owned()))
+        );
+        assert_eq!(url("index.html", "https://cdn.example/a.css"), Ok(None));
+        assert_eq!(url("index.html", "data:image/png;base64,AA"), Ok(None));
+        assert_eq!(
+            url("blog/post.html", "js/app.js"),
+            Err("blog/js/app.js".to_owned())
+        );
+    }
+
+    #[test]
+    fn rewrite_test() {
+        let mut doc = concat!(
+            r#"<html><head><link rel="stylesheet" href="/css/site.css">"#,
+            r#"<style>@font-face{src:url("/fonts/a.woff2") format("woff2"), url(/fonts/a.ttf)}</style>"#,
+            r#"</head><body><div style="background: url('../img/bg.png')"></div>"#,
+            r#"<img srcset="../img/a.png 1x,../img/a@2x.png 2x"><a href="https://other.example/">x</a>"#,
+            r#"</body></html>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let assets = FingerprintAssets::new()
+            .asset("css/site.css", "css/site.1a2b.css")
+            .asset("fonts/a.woff2", "fonts/a.77.woff2")
+            .asset("img/bg.png", "img/bg.e3.png")
+            .asset("img/a.png", "img/a.01.png")
+            .asset("img/a@2x.png", "img/a@2x.02.png");
+        let report = assets.rewrite("blog/index.html", &mut doc);
+        assert_eq!(report.rewritten, 5);
+        assert_eq!(
+            report
+                .unmapped
+                .iter()
+                .map(|u| u.url.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["/fonts/a.ttf"]
+        );
+        assert_eq!(attribute(&doc, "link", "href"), "/css/site.1a2b.css");
+        assert_eq!(
+            attribute(&doc, "div", "style"),
+            "background: url('../img/bg.e3.png')"
+        );
+        assert_eq!(
+            attribute(&doc, "img", "srcset"),
+            "../img/a.01.png 1x,../img/a@2x.02.png 2x"
+        );
+        assert!(doc.find("style").nodes()[0]
+            .to_html_string()
+            .contains(r#"url("/fonts/a.77.woff2") format("woff2")"#));
+        assert_eq!(attribute(&doc, "a", "href"), "https://other.example/");
+    }
+}
@@ -0,0 +1,351 @@
+//! Saved HTTP responses for tests and benchmarks which need real pages without
+//! going to the network.
+//!
+//! A [`Fixture`] is a response saved as two files in a directory: `<name>.headers`
+//! holds the status line and the headers, as they are sent over HTTP, and
+//! `<name>.body` holds the body as it was received. [`Fixture::save`] records a
+//! response fetched with any client, and [`load_fixtures`] loads them back.
+//!
+//! The body is decoded as a browser would, see [`sniff_encoding`], before it is
+//! parsed by [`Fixture::document`] and its text extracted by
+//! [`Fixture::text_nodes`], so tests go through the same steps as a page
+//! downloaded from a site.
+//!
+//! This module needs the `fixtures` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::extract::TextExtractOptions;
+//! use hb_html::fixture::{load_fixtures, EncodingSource, Fixture};
+//! let dir = std::env::temp_dir().join("hb_html_fixture_doc");
+//! Fixture::new("cafe", b"<p>Caf\xe9</p>".to_vec())
+//!     .header("Content-Type", "text/html; charset=ISO-8859-1")
+//!     .save(&dir)
+//!     .unwrap();
+//! let fixtures = load_fixtures(&dir).unwrap();
+//! assert_eq!(fixtures[0].status, 200);
+//! let (encoding, source) = fixtures[0].encoding();
+//! assert_eq!((encoding.name(), source), ("windows-1252", EncodingSource::ContentType));
+//! let text = fixtures[0].text_nodes(&TextExtractOptions::new()).unwrap();
+//! assert_eq!(text[0].1, "Café");
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+use crate::error::{ConfigError, ParseHtmlError};
+use crate::extract::TextExtractOptions;
+use crate::objects::{HtmlDocument, NodePath};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use std::path::Path;
+
+/// The number of bytes at the start of the body which are searched for a `<meta>`
+/// with the charset, as browsers do.
+const PRESCAN_LEN: usize = 1024;
+
+/// Where the encoding of a body came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EncodingSource {
+    /// A byte order mark at the start of the body.
+    Bom,
+    /// The charset of the `Content-Type` header.
+    ContentType,
+    /// A `<meta charset>` or `<meta http-equiv="Content-Type">` in the body.
+    Meta,
+    /// None of the above, so UTF-8 if the body is valid UTF-8 and windows-1252
+    /// otherwise.
+    Default,
+}
+
+/// Gets the charset parameter of a `Content-Type` value, without quotes.
+fn charset_param(value: &str) -> Option<&str> {
+    value.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("charset") {
+            Some(value.trim().trim_matches(['"', '\'']))
+        } else {
+            None
+        }
+    })
+}
+
+/// Finds the charset of the first `<meta>` in the start of the body which has one.
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let start = String::from_utf8_lossy(&body[..body.len().min(PRESCAN_LEN)]).to_ascii_lowercase();
+    let mut rest = start.as_str();
+    while let Some(i) = rest.find("<meta") {
+        rest = &rest[i + 5..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        let value = tag
+            .find("charset")
+            .and_then(|j| tag[j + 7..].trim_start().strip_prefix('='));
+        if let Some(value) = value {
+            let value = value.trim_start().trim_start_matches(['"', '\'']);
+            let end = value
+                .find(|c: char| c == '"' || c == '\'' || c == ';' || c == '/' || c.is_whitespace())
+                .unwrap_or(value.len());
+            // a page declaring utf-16 in ascii is not really utf-16
+            return Encoding::for_label(&value.as_bytes()[..end]).map(|e| {
+                if e.name().starts_with("UTF-16") {
+                    UTF_8
+                } else {
+                    e
+                }
+            });
+        }
+    }
+    None
+}
+
+/// Works out the encoding of a HTML body in the same order as browsers: a byte
+/// order mark, then the charset of the `Content-Type` header, then a charset
+/// declared by a `<meta>` in the first 1024 bytes. Charsets which are not known
+/// are skipped.
+pub fn sniff_encoding(
+    body: &[u8],
+    content_type: Option<&str>,
+) -> (&'static Encoding, EncodingSource) {
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return (encoding, EncodingSource::Bom);
+    }
+    if let Some(encoding) = content_type
+        .and_then(charset_param)
+        .and_then(|c| Encoding::for_label(c.as_bytes()))
+    {
+        return (encoding, EncodingSource::ContentType);
+    }
+    if let Some(encoding) = meta_charset(body) {
+        return (encoding, EncodingSource::Meta);
+    }
+    if std::str::from_utf8(body).is_ok() {
+        (UTF_8, EncodingSource::Default)
+    } else {
+        (WINDOWS_1252, EncodingSource::Default)
+    }
+}
+
+/// Decodes a HTML body using the encoding from [`sniff_encoding`]. Bytes which are
+/// not valid in the encoding become U+FFFD.
+pub fn decode_html(body: &[u8], content_type: Option<&str>) -> String {
+    let (encoding, _) = sniff_encoding(body, content_type);
+    encoding.decode_with_bom_removal(body).0.into_owned()
+}
+
+/// A saved HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    /// The name of the files, without the extension.
+    pub name: String,
+    pub status: u16,
+    /// The headers in the order they were received.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Fixture {
+    /// Creates a response with a status of 200 and no headers.
+    pub fn new<N: Into<String>>(name: N, body: Vec<u8>) -> Fixture {
+        Fixture {
+            name: name.into(),
+            status: 200,
+            headers: vec![],
+            body,
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Fixture {
+        self.status = status;
+        self
+    }
+
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Fixture {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Gets the value of the first header with the name, ignoring case.
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Loads the response with the name from the directory.
+    pub fn load<P: AsRef<Path>>(dir: P, name: &str) -> Result<Fixture, ConfigError> {
+        let dir = dir.as_ref();
+        let path = dir.join(format!("{}.headers", name));
+        let head = std::fs::read_to_string(&path).map_err(|e| {
+            ConfigError::with_msg(format!("could not read {} because {}", path.display(), e))
+        })?;
+        let path = dir.join(format!("{}.body", name));
+        let body = std::fs::read(&path).map_err(|e| {
+            ConfigError::with_msg(format!("could not read {} because {}", path.display(), e))
+        })?;
+        let mut fixture = Fixture::new(name, body);
+        for (i, line) in head.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if i == 0 && line.starts_with("HTTP/") {
+                fixture.status = line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| {
+                        ConfigError::with_msg(format!(
+                            "{}.headers has an invalid status line",
+                            name
+                        ))
+                    })?;
+                continue;
+            }
+            let (header, value) = line.split_once(':').ok_or_else(|| {
+                ConfigError::with_msg(format!(
+                    "{}.headers has a line which is not a header: {}",
+                    name, line
+                ))
+            })?;
+            fixture
+                .headers
+                .push((header.trim().to_owned(), value.trim().to_owned()));
+        }
+        Ok(fixture)
+    }
+
+    /// Writes the response to the directory, creating it if needed.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<(), ConfigError> {
+        let dir = dir.as_ref();
+        let write_error = |path: &Path, e: std::io::Error| {
+            ConfigError::with_msg(format!("could not write {} because {}", path.display(), e))
+        };
+        std::fs::create_dir_all(dir).map_err(|e| write_error(dir, e))?;
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        let path = dir.join(format!("{}.headers", self.name));
+        std::fs::write(&path, head).map_err(|e| write_error(&path, e))?;
+        let path = dir.join(format!("{}.body", self.name));
+        std::fs::write(&path, &self.body).map_err(|e| write_error(&path, e))
+    }
+
+    /// Gets the encoding of the body and where it came from.
+    pub fn encoding(&self) -> (&'static Encoding, EncodingSource) {
+        sniff_encoding(&self.body, self.header_value("content-type"))
+    }
+
+    /// Gets the body decoded to text.
+    pub fn text(&self) -> String {
+        decode_html(&self.body, self.header_value("content-type"))
+    }
+
+    pub fn document(&self) -> Result<HtmlDocument, ParseHtmlError> {
+        self.text().parse::<HtmlDocument>()
+    }
+
+    /// Parses the body and gets its text nodes, see
+    /// [`HtmlDocument::text_nodes_with`].
+    pub fn text_nodes(
+        &self,
+        options: &TextExtractOptions,
+    ) -> Result<Vec<(NodePath, String)>, ParseHtmlError> {
+        Ok(self.document()?.text_nodes_with(options))
+    }
+}
+
+/// Loads all of the responses in the directory, sorted by name. A `.headers`
+/// file without a `.body` file is an error so it is not silently left out.
+pub fn load_fixtures<P: AsRef<Path>>(dir: P) -> Result<Vec<Fixture>, ConfigError> {
+    let dir = dir.as_ref();
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        ConfigError::with_msg(format!("could not read {} because {}", dir.display(), e))
+    })?;
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("headers"))
+        .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names.iter().map(|name| Fixture::load(dir, name)).collect()
+}
+
+#[cfg(test)]
+mod fixture_tests {
+    use super::*;
+
+    #[test]
+    fn sniff_encoding_test() {
+        let sniff = |body: &[u8], content_type| {
+            let (encoding, source) = sniff_encoding(body, content_type);
+            (encoding.name(), source)
+        };
+        assert_eq!(
+            sniff(b"\xef\xbb\xbf<p>", Some("text/html; charset=latin1")),
+            ("UTF-8", EncodingSource::Bom)
+        );
+        assert_eq!(
+            sniff(b"\xff\xfe<\x00", None),
+            ("UTF-16LE", EncodingSource::Bom)
+        );
+        assert_eq!(
+            sniff(
+                b"<meta charset=utf-8>",
+                Some("text/html; charset=\"Shift_JIS\"")
+            ),
+            ("Shift_JIS", EncodingSource::ContentType)
+        );
+        assert_eq!(
+            sniff(b"<meta charset=utf-8>", Some("text/html; charset=bogus")),
+            ("UTF-8", EncodingSource::Meta)
+        );
+        assert_eq!(
+            sniff(
+                br#"<head><META http-equiv="Content-Type" content="text/html; charset=KOI8-R">"#,
+                None
+            ),
+            ("KOI8-R", EncodingSource::Meta)
+        );
+        assert_eq!(
+            sniff(b"<meta charset='utf-16le'>", None),
+            ("UTF-8", EncodingSource::Meta)
+        );
+        assert_eq!(
+            sniff(b"caf\xc3\xa9", None),
+            ("UTF-8", EncodingSource::Default)
+        );
+        assert_eq!(
+            sniff(b"caf\xe9", Some("text/html")),
+            ("windows-1252", EncodingSource::Default)
+        );
+        assert_eq!(decode_html(b"\xef\xbb\xbf\xe2\x82\xac", None), "€");
+    }
+
+    #[test]
+    fn fixture_test() {
+        let dir = std::env::temp_dir().join(format!("hb_html_fixtures_{}", std::process::id()));
+        let body = b"<html><head><meta charset=\"windows-1251\"></head><body><h1>\xcf\xf0\xe8\xe2\xe5\xf2</h1></body></html>";
+        Fixture::new("b-cyrillic", body.to_vec())
+            .status(404)
+            .header("Content-Type", "text/html")
+            .header("Set-Cookie", "a=1; Path=/")
+            .save(&dir)
+            .unwrap();
+        Fixture::new("a-empty", vec![]).save(&dir).unwrap();
+        let fixtures = load_fixtures(&dir).unwrap();
+        assert_eq!(fixtures.len(), 2);
+        assert_eq!(fixtures[0].name, "a-empty");
+        assert!(fixtures[0].headers.is_empty());
+        let page = &fixtures[1];
+        assert_eq!(page.status, 404);
+        assert_eq!(page.header_value("set-cookie"), Some("a=1; Path=/"));
+        assert_eq!(page.encoding().1, EncodingSource::Meta);
+        let text = page.text_nodes(&TextExtractOptions::new()).unwrap();
+        assert_eq!(text, vec![(vec![0, 1, 0, 0], "Привет".to_owned())]);
+        std::fs::write(dir.join("c-missing.headers"), "HTTP/1.1 200\r\n").unwrap();
+        assert!(load_fixtures(&dir).is_err());
+        std::fs::write(dir.join("c-missing.body"), "").unwrap();
+        std::fs::write(dir.join("c-missing.headers"), "HTTP/1.1 OK\r\n").unwrap();
+        assert!(load_fixtures(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
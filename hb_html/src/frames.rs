@@ -0,0 +1,322 @@
+//! Putting a page back together from the documents of its frames.
+//!
+//! A page with `<iframe>` (or `<frame>`) elements is shown as more than one
+//! document. [`aggregate_frames`] finds the frames of a document, gets the source
+//! of each from a fetcher provided by the caller, and parses it, doing the same
+//! for the frames of the frames up to [`FrameOptions::max_depth`]. Nothing is
+//! fetched by the crate itself, so the fetcher can read from a cache, a crawl
+//! archive or the network as suits the caller, returning None for a frame it can
+//! not get.
+//!
+//! The `src` of a frame is resolved against the URL of the document it is in,
+//! starting with [`FrameOptions::base_url`]. An `<iframe srcdoc>` is parsed from
+//! the attribute without calling the fetcher, and frames without a URL to load,
+//! such as `about:blank`, are left out.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::frames::{aggregate_frames, FrameContent};
+//! use hb_html::objects::HtmlDocument;
+//! let doc = r#"<div><iframe src="ad.html"></iframe><iframe srcdoc="<p>Hi</p>"></iframe></div>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let page = aggregate_frames(doc, |url| match url {
+//!     "ad.html" => Some("<a href=/buy>Buy</a>".to_owned()),
+//!     _ => None,
+//! });
+//! assert_eq!(page.frames.len(), 2);
+//! assert_eq!(page.frames[0].path, vec![0, 0]);
+//! match &page.frames[0].content {
+//!     FrameContent::Document(ad) => assert_eq!(ad.document.nodes[0].text(), "Buy"),
+//!     other => panic!("{:?}", other),
+//! }
+//! let texts: Vec<String> = page.documents().iter().map(|d| d.nodes[0].text()).collect();
+//! assert_eq!(texts, vec!["", "Buy", "Hi"]);
+//! ```
+use crate::error::ParseHtmlError;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::options::ParseOptions;
+use crate::resources::resolve_specifier;
+
+/// Options for [`aggregate_frames_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameOptions {
+    /// How deep frames inside frames are loaded, 1 being only the frames of the
+    /// document. Frames deeper than this are [`FrameContent::TooDeep`], which also
+    /// stops pages which frame themselves.
+    pub max_depth: usize,
+    /// The URL of the document, which the URLs of its frames are resolved against.
+    pub base_url: String,
+    /// The options the documents of the frames are parsed with.
+    pub parse_options: ParseOptions,
+}
+
+impl Default for FrameOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameOptions {
+    pub fn new() -> FrameOptions {
+        FrameOptions {
+            max_depth: 4,
+            base_url: String::new(),
+            parse_options: ParseOptions::default(),
+        }
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> FrameOptions {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn base_url<S: Into<String>>(mut self, base_url: S) -> FrameOptions {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn parse_options(mut self, parse_options: ParseOptions) -> FrameOptions {
+        self.parse_options = parse_options;
+        self
+    }
+}
+
+/// A document along with the documents of its frames.
+#[derive(Debug, PartialEq)]
+pub struct FramedDocument {
+    /// The URL the document was loaded from, which is the base URL of the options
+    /// for the top document and the URL of the document it is in for a `srcdoc`.
+    pub url: String,
+    pub document: HtmlDocument,
+    /// The frames of the document, in document order.
+    pub frames: Vec<Frame>,
+}
+
+/// A frame of a [`FramedDocument`].
+#[derive(Debug, PartialEq)]
+pub struct Frame {
+    /// The path of the `<iframe>` or `<frame>` element in the document it is in.
+    pub path: NodePath,
+    /// The resolved URL of the frame, or None for a `srcdoc`.
+    pub url: Option<String>,
+    pub content: FrameContent,
+}
+
+/// What was loaded for a [`Frame`].
+#[derive(Debug, PartialEq)]
+pub enum FrameContent {
+    Document(FramedDocument),
+    /// The fetcher returned None for the URL.
+    NotFetched,
+    /// The frame is deeper than [`FrameOptions::max_depth`], so was not fetched.
+    TooDeep,
+    /// The source of the frame could not be parsed.
+    Error(ParseHtmlError),
+}
+
+impl FramedDocument {
+    /// Gets the document and the documents of all of its frames which were loaded,
+    /// with each document followed by the documents of its frames.
+    pub fn documents(&self) -> Vec<&HtmlDocument> {
+        let mut documents = vec![&self.document];
+        for frame in &self.frames {
+            if let FrameContent::Document(d) = &frame.content {
+                documents.extend(d.documents());
+            }
+        }
+        documents
+    }
+}
+
+/// Loads the frames of the document with the fetcher using the default options,
+/// see [`aggregate_frames_with`].
+pub fn aggregate_frames<F: FnMut(&str) -> Option<String>>(
+    doc: HtmlDocument,
+    fetcher: F,
+) -> FramedDocument {
+    aggregate_frames_with(doc, &FrameOptions::new(), fetcher)
+}
+
+/// Loads the frames of the document, calling the fetcher with the resolved URL of
+/// each frame to get its source, which is parsed and has its frames loaded in
+/// turn. The fetcher returns None for frames which can not be fetched.
+pub fn aggregate_frames_with<F: FnMut(&str) -> Option<String>>(
+    doc: HtmlDocument,
+    options: &FrameOptions,
+    mut fetcher: F,
+) -> FramedDocument {
+    load_frames(doc, options.base_url.clone(), 1, options, &mut fetcher)
+}
+
+fn load_frames<F: FnMut(&str) -> Option<String>>(
+    document: HtmlDocument,
+    url: String,
+    depth: usize,
+    options: &FrameOptions,
+    fetcher: &mut F,
+) -> FramedDocument {
+    let mut found = vec![];
+    find_frames(&document.nodes, &mut vec![], &mut found);
+    let mut frames = vec![];
+    for (path, tag) in found {
+        let (frame_url, source) = match tag.attributes.get("srcdoc") {
+            Some(srcdoc) => (None, Some(srcdoc.clone())),
+            None => match tag.attributes.get("src").and_then(|s| frame_url(&url, s)) {
+                Some(src) => (Some(src), None),
+                None => continue,
+            },
+        };
+        let content = if depth > options.max_depth {
+            FrameContent::TooDeep
+        } else {
+            let source = source.or_else(|| fetcher(frame_url.as_deref().unwrap_or_default()));
+            let frame_base = frame_url.clone().unwrap_or_else(|| url.clone());
+            match source.map(|s| HtmlDocument::parse_with(&s, &options.parse_options)) {
+                None => FrameContent::NotFetched,
+                Some(Ok(d)) => {
+                    FrameContent::Document(load_frames(d, frame_base, depth + 1, options, fetcher))
+                }
+                Some(Err(e)) => FrameContent::Error(e.add_context(format!(
+                    "in the frame at '{}'",
+                    frame_url.as_deref().unwrap_or("srcdoc")
+                ))),
+            }
+        };
+        frames.push(Frame {
+            path,
+            url: frame_url,
+            content,
+        });
+    }
+    FramedDocument {
+        url,
+        document,
+        frames,
+    }
+}
+
+/// Adds the paths of the `<iframe>` and `<frame>` elements in the nodes to found.
+/// The contents of an iframe are only shown by browsers which do not support
+/// frames, so are not searched.
+fn find_frames<'a>(
+    nodes: &'a [HtmlNode],
+    path: &mut NodePath,
+    found: &mut Vec<(NodePath, &'a HtmlTag)>,
+) {
+    for (i, node) in nodes.iter().enumerate() {
+        if let HtmlNode::Tag(tag) = node {
+            path.push(i);
+            if tag.tag.eq_ignore_ascii_case("iframe") || tag.tag.eq_ignore_ascii_case("frame") {
+                found.push((path.clone(), tag));
+            } else {
+                find_frames(&tag.contents, path, found);
+            }
+            path.pop();
+        }
+    }
+}
+
+/// Resolves the `src` of a frame against the URL of the document it is in, or
+/// None if there is nothing to load.
+fn frame_url(base: &str, src: &str) -> Option<String> {
+    let src = src.trim();
+    let scheme = src
+        .split_once(':')
+        .map(|(s, _)| s)
+        .filter(|s| !s.contains('/'));
+    match scheme {
+        _ if src.is_empty() || src.starts_with('#') => None,
+        Some(s) if s.eq_ignore_ascii_case("about") || s.eq_ignore_ascii_case("javascript") => None,
+        Some(_) => Some(src.to_owned()),
+        None if src.starts_with("//") || src.starts_with('/') || src.starts_with('.') => {
+            Some(resolve_specifier(base, src))
+        }
+        None => Some(resolve_specifier(base, &format!("./{}", src))),
+    }
+}
+
+#[cfg(test)]
+mod frames_tests {
+    use super::*;
+
+    #[test]
+    fn frame_url_test() {
+        let base = "https://example.com/news/today.html";
+        assert_eq!(
+            frame_url(base, "ad.html").as_deref(),
+            Some("https://example.com/news/ad.html")
+        );
+        assert_eq!(
+            frame_url(base, "../ads/1.html").as_deref(),
+            Some("https://example.com/ads/1.html")
+        );
+        assert_eq!(
+            frame_url(base, "/embed?v=1").as_deref(),
+            Some("https://example.com/embed?v=1")
+        );
+        assert_eq!(
+            frame_url(base, "https://video.example/e/1").as_deref(),
+            Some("https://video.example/e/1")
+        );
+        assert_eq!(frame_url("", "ad.html").as_deref(), Some("ad.html"));
+        for src in ["", " about:blank", "javascript:void(0)", "#top"] {
+            assert_eq!(frame_url(base, src), None, "url of {:?}", src);
+        }
+    }
+
+    #[test]
+    fn aggregate_frames_test() {
+        let doc = r#"<div><iframe src="a.html"></iframe><iframe src="missing.html"></iframe><iframe src="about:blank"></iframe></div><frame src="/broken.html"></frame>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let mut fetched = vec![];
+        let options = FrameOptions::new()
+            .base_url("https://example.com/dir/page.html")
+            .max_depth(2);
+        let page = aggregate_frames_with(doc, &options, |url| {
+            fetched.push(url.to_owned());
+            match url {
+                // the frame loads itself, which stops at the depth limit
+                "https://example.com/dir/a.html" => {
+                    Some(r#"<p>A</p><iframe src="a.html"></iframe>"#.to_owned())
+                }
+                "https://example.com/broken.html" => Some("<p>a</q>".to_owned()),
+                _ => None,
+            }
+        });
+        assert_eq!(
+            fetched,
+            vec![
+                "https://example.com/dir/a.html",
+                "https://example.com/dir/a.html",
+                "https://example.com/dir/missing.html",
+                "https://example.com/broken.html",
+            ]
+        );
+        let paths: Vec<&[usize]> = page.frames.iter().map(|f| f.path.as_slice()).collect();
+        assert_eq!(paths, vec![&[0, 0][..], &[0, 1], &[1]]);
+        let a = match &page.frames[0].content {
+            FrameContent::Document(a) => a,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(a.url, "https://example.com/dir/a.html");
+        let inner = match &a.frames[0].content {
+            FrameContent::Document(inner) => inner,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(inner.frames[0].content, FrameContent::TooDeep);
+        assert_eq!(page.frames[1].content, FrameContent::NotFetched);
+        match &page.frames[2].content {
+            FrameContent::Error(e) => {
+                assert_eq!(
+                    e.frames(),
+                    &["in the frame at 'https://example.com/broken.html'"]
+                )
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(page.documents().len(), 3);
+    }
+}
@@ -0,0 +1,12 @@
+//! Hashing which gives the same value on every platform and in every version, for
+//! keys and fingerprints which are saved or shown to users.
+
+/// The 64 bit FNV-1a hash of the bytes.
+pub(crate) fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
@@ -0,0 +1,343 @@
+//! Splitting the source of a document into classified spans for syntax highlighting.
+//!
+//! [`highlight`] reads the source in the same way as the parser, but instead of
+//! building a document it reports the kind and byte range of each piece of the
+//! source. It never fails, so it can be used on documents which are being edited
+//! and do not parse yet. The whitespace between the parts of a tag is not part of
+//! any span.
+//!
+//! The contents of `<script>` and `<style>` elements are raw text, so are a single
+//! text span up to the end tag, and the contents of `<title>` and `<textarea>` can
+//! only contain text and character references.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::highlight::{highlight, TokenKind};
+//! let source = r#"<a href="/">Tom &amp; Jerry</a>"#;
+//! let found: Vec<(TokenKind, &str)> = highlight(source)
+//!     .into_iter()
+//!     .map(|t| (t.kind, &source[t.range]))
+//!     .collect();
+//! assert_eq!(
+//!     found,
+//!     vec![
+//!         (TokenKind::Tag, "<a"),
+//!         (TokenKind::AttributeName, "href"),
+//!         (TokenKind::AttributeValue, "\"/\""),
+//!         (TokenKind::Tag, ">"),
+//!         (TokenKind::Text, "Tom "),
+//!         (TokenKind::Entity, "&amp;"),
+//!         (TokenKind::Text, " Jerry"),
+//!         (TokenKind::Tag, "</a>"),
+//!     ]
+//! );
+//! ```
+use crate::memfind::find_byte;
+use std::ops::Range;
+
+/// The elements whose content is read as text up to their end tag.
+const RAW_TEXT_TAGS: [&str; 2] = ["script", "style"];
+/// The elements whose content is read as text and character references up to
+/// their end tag.
+const ESCAPABLE_RAW_TEXT_TAGS: [&str; 2] = ["textarea", "title"];
+
+/// The kind of a [`HighlightToken`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// The `<name` at the start of a start tag, the `>` or `/>` at its end, or a
+    /// whole end tag.
+    Tag,
+    AttributeName,
+    /// The value of an attribute including its quotes.
+    AttributeValue,
+    /// A character reference, eg `&amp;` or `&#169;`.
+    Entity,
+    Comment,
+    Doctype,
+    Text,
+}
+
+/// A classified span of the source of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightToken {
+    pub kind: TokenKind,
+    /// The byte range of the token in the source.
+    pub range: Range<usize>,
+}
+
+/// Gets the length in bytes of the character reference at the start of the text,
+/// if there is one. Named references must end with a `;`.
+fn entity_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    if bytes.first() != Some(&b'&') {
+        return None;
+    }
+    let (start, is_valid): (usize, fn(&u8) -> bool) = match bytes.get(1) {
+        Some(b'#') if matches!(bytes.get(2), Some(b'x') | Some(b'X')) => (3, u8::is_ascii_hexdigit),
+        Some(b'#') => (2, u8::is_ascii_digit),
+        Some(b) if b.is_ascii_alphabetic() => (1, u8::is_ascii_alphanumeric),
+        _ => return None,
+    };
+    let len = bytes[start..].iter().take_while(|b| is_valid(b)).count();
+    if len == 0 {
+        return None;
+    }
+    let end = start + len;
+    if bytes.get(end) == Some(&b';') {
+        Some(end + 1)
+    } else if start == 1 {
+        None
+    } else {
+        Some(end)
+    }
+}
+
+/// Collects the tokens as the source is read.
+struct TokenSink<'a> {
+    source: &'a str,
+    tokens: Vec<HighlightToken>,
+}
+
+impl TokenSink<'_> {
+    fn push(&mut self, kind: TokenKind, range: Range<usize>) {
+        if !range.is_empty() {
+            self.tokens.push(HighlightToken { kind, range });
+        }
+    }
+
+    /// Adds the range as tokens of the kind, split around any character references.
+    fn push_with_entities(&mut self, kind: TokenKind, range: Range<usize>) {
+        let mut start = range.start;
+        let mut i = range.start;
+        while let Some(j) = find_byte(&self.source.as_bytes()[i..range.end], b'&') {
+            i += j;
+            match entity_len(&self.source[i..range.end]) {
+                Some(len) => {
+                    self.push(kind, start..i);
+                    self.push(TokenKind::Entity, i..i + len);
+                    i += len;
+                    start = i;
+                }
+                None => i += 1,
+            }
+        }
+        self.push(kind, start..range.end);
+    }
+}
+
+/// Finds the first byte at or after the position which matches, or the end of the text.
+fn find_from<F: Fn(u8) -> bool>(text: &str, from: usize, f: F) -> usize {
+    text.as_bytes()[from..]
+        .iter()
+        .position(|b| f(*b))
+        .map_or(text.len(), |i| from + i)
+}
+
+/// Finds the first of the byte at or after the position, or the end of the text.
+fn find_byte_from(text: &str, from: usize, byte: u8) -> usize {
+    find_byte(&text.as_bytes()[from..], byte).map_or(text.len(), |i| from + i)
+}
+
+/// Finds the end of the string at or after the position, or the end of the text.
+fn find_str_end(text: &str, from: usize, s: &str) -> usize {
+    text[from..]
+        .find(s)
+        .map_or(text.len(), |i| from + i + s.len())
+}
+
+/// Reads a start tag, the `<` is at the position. Returns the position after the
+/// tag and the name of the tag in lowercase.
+fn read_start_tag(sink: &mut TokenSink, start: usize) -> (usize, String) {
+    let source = sink.source;
+    let bytes = source.as_bytes();
+    let is_name_end = |b: u8| b.is_ascii_whitespace() || b == b'>' || b == b'/';
+    let name_end = find_from(source, start + 1, is_name_end);
+    sink.push(TokenKind::Tag, start..name_end);
+    let name = source[start + 1..name_end].to_ascii_lowercase();
+    let mut i = name_end;
+    loop {
+        i = find_from(source, i, |b| !b.is_ascii_whitespace());
+        match bytes.get(i) {
+            None => return (i, name),
+            Some(b'>') => {
+                sink.push(TokenKind::Tag, i..i + 1);
+                return (i + 1, name);
+            }
+            Some(b'/') if bytes.get(i + 1) == Some(&b'>') => {
+                sink.push(TokenKind::Tag, i..i + 2);
+                return (i + 2, name);
+            }
+            Some(b'/') => {
+                i += 1;
+                continue;
+            }
+            Some(_) => {}
+        }
+        let attr_end = find_from(source, i + 1, |b| {
+            b.is_ascii_whitespace() || b == b'=' || b == b'>' || b == b'/'
+        });
+        sink.push(TokenKind::AttributeName, i..attr_end);
+        i = find_from(source, attr_end, |b| !b.is_ascii_whitespace());
+        if bytes.get(i) != Some(&b'=') {
+            continue;
+        }
+        i = find_from(source, i + 1, |b| !b.is_ascii_whitespace());
+        let value_end = match bytes.get(i) {
+            Some(q @ b'"') | Some(q @ b'\'') => {
+                let q = *q;
+                (find_byte_from(source, i + 1, q) + 1).min(source.len())
+            }
+            _ => find_from(source, i, |b| b.is_ascii_whitespace() || b == b'>'),
+        };
+        sink.push_with_entities(TokenKind::AttributeValue, i..value_end);
+        i = value_end;
+    }
+}
+
+/// Splits the source of a document into highlight tokens, in source order.
+pub fn highlight(source: &str) -> Vec<HighlightToken> {
+    let bytes = source.as_bytes();
+    let mut sink = TokenSink {
+        source,
+        tokens: vec![],
+    };
+    let mut text_start = 0;
+    let mut i = 0;
+    while let Some(j) = find_byte(&bytes[i..], b'<') {
+        i += j;
+        let rest = &source[i..];
+        let next = bytes.get(i + 1).copied().unwrap_or_default();
+        let end = if rest.starts_with("<!--") {
+            sink.push_with_entities(TokenKind::Text, text_start..i);
+            let end = find_str_end(source, i + 4, "-->");
+            sink.push(TokenKind::Comment, i..end);
+            end
+        } else if rest.len() >= 9 && rest.as_bytes()[..9].eq_ignore_ascii_case(b"<!doctype") {
+            sink.push_with_entities(TokenKind::Text, text_start..i);
+            let end = (find_byte_from(source, i, b'>') + 1).min(source.len());
+            sink.push(TokenKind::Doctype, i..end);
+            end
+        } else if next == b'!' || next == b'?' {
+            // anything else starting <! or <? is read as a comment
+            sink.push_with_entities(TokenKind::Text, text_start..i);
+            let end = (find_byte_from(source, i, b'>') + 1).min(source.len());
+            sink.push(TokenKind::Comment, i..end);
+            end
+        } else if next == b'/' && bytes.get(i + 2).is_some_and(|b| b.is_ascii_alphabetic()) {
+            sink.push_with_entities(TokenKind::Text, text_start..i);
+            let end = (find_byte_from(source, i, b'>') + 1).min(source.len());
+            sink.push(TokenKind::Tag, i..end);
+            end
+        } else if next.is_ascii_alphabetic() {
+            sink.push_with_entities(TokenKind::Text, text_start..i);
+            let (mut end, name) = read_start_tag(&mut sink, i);
+            let is_raw = RAW_TEXT_TAGS.contains(&name.as_str());
+            if is_raw || ESCAPABLE_RAW_TEXT_TAGS.contains(&name.as_str()) {
+                // the content runs until the matching end tag
+                let end_tag = format!("</{}", name);
+                let content_end = source[end..]
+                    .to_ascii_lowercase()
+                    .find(&end_tag)
+                    .map_or(source.len(), |j| end + j);
+                if is_raw {
+                    sink.push(TokenKind::Text, end..content_end);
+                } else {
+                    sink.push_with_entities(TokenKind::Text, end..content_end);
+                }
+                end = content_end;
+            }
+            end
+        } else {
+            // a < which does not start a tag is text
+            i += 1;
+            continue;
+        };
+        i = end;
+        text_start = end;
+    }
+    sink.push_with_entities(TokenKind::Text, text_start..source.len());
+    sink.tokens
+}
+
+#[cfg(test)]
+mod highlight_tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<(TokenKind, &str)> {
+        highlight(source)
+            .into_iter()
+            .map(|t| (t.kind, &source[t.range]))
+            .collect()
+    }
+
+    #[test]
+    fn highlight_document_test() {
+        use TokenKind::*;
+        let source =
+            "<!DOCTYPE html>\n<!-- hi -->\n<p class='a &amp; b' hidden id=x/>1 < 2&#169;&nbsp</p>";
+        assert_eq!(
+            kinds(source),
+            vec![
+                (Doctype, "<!DOCTYPE html>"),
+                (Text, "\n"),
+                (Comment, "<!-- hi -->"),
+                (Text, "\n"),
+                (Tag, "<p"),
+                (AttributeName, "class"),
+                (AttributeValue, "'a "),
+                (Entity, "&amp;"),
+                (AttributeValue, " b'"),
+                (AttributeName, "hidden"),
+                (AttributeName, "id"),
+                (AttributeValue, "x/"),
+                (Tag, ">"),
+                (Text, "1 < 2"),
+                (Entity, "&#169;"),
+                (Text, "&nbsp"),
+                (Tag, "</p>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_raw_text_test() {
+        use TokenKind::*;
+        let source = "<script>if (a<b) { x = '</p>&amp;'; }</SCRIPT><title>A &amp; <b></title>";
+        assert_eq!(
+            kinds(source),
+            vec![
+                (Tag, "<script"),
+                (Tag, ">"),
+                (Text, "if (a<b) { x = '</p>&amp;'; }"),
+                (Tag, "</SCRIPT>"),
+                (Tag, "<title"),
+                (Tag, ">"),
+                (Text, "A "),
+                (Entity, "&amp;"),
+                (Text, " <b>"),
+                (Tag, "</title>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_unfinished_test() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("<div a=\"b"),
+            vec![(Tag, "<div"), (AttributeName, "a"), (AttributeValue, "\"b")]
+        );
+        assert_eq!(kinds("<!-- x"), vec![(Comment, "<!-- x")]);
+        assert_eq!(
+            kinds("<style>p {"),
+            vec![(Tag, "<style"), (Tag, ">"), (Text, "p {")]
+        );
+        assert_eq!(
+            kinds("a <br/> é"),
+            vec![(Text, "a "), (Tag, "<br"), (Tag, "/>"), (Text, " é")]
+        );
+        assert_eq!(kinds("<!é>"), vec![(Comment, "<!é>")]);
+        assert!(highlight("").is_empty());
+    }
+}
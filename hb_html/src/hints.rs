@@ -0,0 +1,597 @@
+//! Suggesting `<link>` resource hints for the critical resources of a document.
+//!
+//! [`resource_hints`] looks at a document and its [`ResourceGraph`] for the
+//! resources which a browser finds late but needs early:
+//!
+//! - Fonts named by `url()` in inline `<style>` elements, which are only fetched
+//!   once the browser knows some text uses them, are preloaded.
+//! - The hero image, the first `<img>` with `fetchpriority="high"` or otherwise
+//!   the first image which is not lazy, is preloaded with its `srcset` and
+//!   `sizes`. Images inside `<picture>` are left alone as the browser might pick
+//!   one of the `<source>` elements instead.
+//! - First party module scripts which are only found by following imports, or
+//!   which are in the `<body>`, get `modulepreload`, and classic scripts in the
+//!   `<body>` are preloaded.
+//! - The third party origins of the stylesheets, scripts, fonts and hero image
+//!   get `preconnect`, up to a limit, and other third party origins get
+//!   `dns-prefetch`.
+//!
+//! Hints which the document already has are not suggested again, and
+//! [`HtmlDocument::insert_resource_hints`] adds the suggestions to the `<head>`.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::hints::{resource_hints, HintOptions, HintRel};
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::resources::ResourceGraph;
+//! let mut doc = r#"<html><head><style>@font-face { src: url("/f/inter.woff2"); }</style>
+//! <script type="module" src="/js/app.js"></script></head>
+//! <body><img src="https://img.example/hero.jpg"></body></html>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let graph = ResourceGraph::from_document_with_loader(&doc, |url| match url {
+//!     "/js/app.js" => Some("import './nav.js';".to_owned()),
+//!     _ => None,
+//! });
+//! let hints = resource_hints(&doc, &graph, &HintOptions::new());
+//! let found: Vec<(HintRel, &str)> = hints.iter().map(|h| (h.rel, h.href.as_str())).collect();
+//! assert_eq!(
+//!     found,
+//!     vec![
+//!         (HintRel::Preconnect, "https://img.example"),
+//!         (HintRel::Preload, "/f/inter.woff2"),
+//!         (HintRel::Preload, "https://img.example/hero.jpg"),
+//!         (HintRel::ModulePreload, "/js/nav.js"),
+//!     ]
+//! );
+//! assert_eq!(doc.insert_resource_hints(&hints), 4);
+//! // the hints are now in the document, so there is nothing more to suggest
+//! assert!(resource_hints(&doc, &graph, &HintOptions::new()).is_empty());
+//! ```
+use std::ops::Range;
+
+use crate::normalize::after_charset;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::resources::{same_origin, url_origin, ResourceGraph, ResourceKind};
+
+/// The `rel` of a resource hint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HintRel {
+    Preconnect,
+    DnsPrefetch,
+    Preload,
+    ModulePreload,
+}
+
+impl HintRel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HintRel::Preconnect => "preconnect",
+            HintRel::DnsPrefetch => "dns-prefetch",
+            HintRel::Preload => "preload",
+            HintRel::ModulePreload => "modulepreload",
+        }
+    }
+}
+
+/// Why a hint was suggested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HintReason {
+    Font,
+    HeroImage,
+    Script,
+    /// A connection to a third party origin which the document loads from.
+    ThirdPartyOrigin,
+}
+
+/// A suggested `<link>` element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceHint {
+    pub rel: HintRel,
+    pub href: String,
+    pub reason: HintReason,
+    /// The `as` attribute of preloads, eg `font`.
+    pub as_type: Option<String>,
+    /// The `type` attribute, eg `font/woff2`.
+    pub mime_type: Option<String>,
+    /// Whether the resource is fetched with CORS, which fonts always are.
+    pub crossorigin: bool,
+    pub image_srcset: Option<String>,
+    pub image_sizes: Option<String>,
+}
+
+impl ResourceHint {
+    fn new<T: Into<String>>(rel: HintRel, href: T, reason: HintReason) -> ResourceHint {
+        ResourceHint {
+            rel,
+            href: href.into(),
+            reason,
+            as_type: None,
+            mime_type: None,
+            crossorigin: false,
+            image_srcset: None,
+            image_sizes: None,
+        }
+    }
+
+    /// Creates the `<link>` element for the hint.
+    pub fn to_tag(&self) -> HtmlTag {
+        let mut attributes = vec![("rel", self.rel.as_str()), ("href", self.href.as_str())];
+        let optional = [
+            ("as", &self.as_type),
+            ("type", &self.mime_type),
+            ("imagesrcset", &self.image_srcset),
+            ("imagesizes", &self.image_sizes),
+        ];
+        for (name, value) in &optional {
+            if let Some(value) = value {
+                attributes.push((name, value.as_str()));
+            }
+        }
+        if self.crossorigin {
+            attributes.push(("crossorigin", ""));
+        }
+        HtmlTag::new("link").attributes(attributes)
+    }
+}
+
+/// The options for [`resource_hints`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintOptions {
+    /// The origin of the site, eg `https://example.com`, so its absolute URLs are
+    /// first party. Relative URLs are always first party.
+    pub site_origin: Option<String>,
+    pub fonts: bool,
+    pub hero_image: bool,
+    pub scripts: bool,
+    /// The most origins to preconnect to, other origins get `dns-prefetch`.
+    pub max_preconnects: usize,
+}
+
+impl Default for HintOptions {
+    fn default() -> Self {
+        HintOptions {
+            site_origin: None,
+            fonts: true,
+            hero_image: true,
+            scripts: true,
+            max_preconnects: 4,
+        }
+    }
+}
+
+impl HintOptions {
+    pub fn new() -> HintOptions {
+        HintOptions::default()
+    }
+
+    pub fn site_origin<T: Into<String>>(mut self, site_origin: T) -> HintOptions {
+        self.site_origin = Some(site_origin.into());
+        self
+    }
+
+    pub fn fonts(mut self, fonts: bool) -> HintOptions {
+        self.fonts = fonts;
+        self
+    }
+
+    pub fn hero_image(mut self, hero_image: bool) -> HintOptions {
+        self.hero_image = hero_image;
+        self
+    }
+
+    pub fn scripts(mut self, scripts: bool) -> HintOptions {
+        self.scripts = scripts;
+        self
+    }
+
+    pub fn max_preconnects(mut self, max_preconnects: usize) -> HintOptions {
+        self.max_preconnects = max_preconnects;
+        self
+    }
+
+    /// Gets the origin of the URL if it is a third party origin.
+    fn third_party_origin<'a>(&self, url: &'a str) -> Option<&'a str> {
+        let origin = url_origin(url)?;
+        match &self.site_origin {
+            Some(site) if same_origin(origin, site) => None,
+            _ => Some(origin),
+        }
+    }
+}
+
+/// Calls the function with each tag, its path and the names of its ancestors.
+pub(crate) fn walk_tags<'a, F: FnMut(&'a HtmlTag, &NodePath, &[&'a str])>(
+    nodes: &'a [HtmlNode],
+    path: &mut NodePath,
+    ancestors: &mut Vec<&'a str>,
+    f: &mut F,
+) {
+    for (i, node) in nodes.iter().enumerate() {
+        if let HtmlNode::Tag(tag) = node {
+            path.push(i);
+            f(tag, path, ancestors);
+            ancestors.push(&tag.tag);
+            walk_tags(&tag.contents, path, ancestors, f);
+            ancestors.pop();
+            path.pop();
+        }
+    }
+}
+
+/// Gets the font type of a URL from its extension.
+fn font_type(url: &str) -> Option<&'static str> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match extension.as_str() {
+        "woff2" => Some("font/woff2"),
+        "woff" => Some("font/woff"),
+        "ttf" => Some("font/ttf"),
+        "otf" => Some("font/otf"),
+        _ => None,
+    }
+}
+
+/// Finds the URLs of the `url()` functions in CSS.
+fn css_urls(css: &str) -> Vec<&str> {
+    css_url_ranges(css).into_iter().map(|r| &css[r]).collect()
+}
+
+/// Gets where the non-empty `url()` values in the CSS are, without quotes.
+pub(crate) fn css_url_ranges(css: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut offset = 0;
+    while let Some(start) = css[offset..].find("url(") {
+        let inner = offset + start + 4;
+        let end = match css[inner..].find(')') {
+            Some(end) => inner + end,
+            None => break,
+        };
+        let value = &css[inner..end];
+        let url = value.trim().trim_matches(['"', '\'']).trim();
+        if !url.is_empty() {
+            // the url is a sub-slice of the value, so its offset is known
+            let url_start = inner + (url.as_ptr() as usize - value.as_ptr() as usize);
+            ranges.push(url_start..url_start + url.len());
+        }
+        offset = end;
+    }
+    ranges
+}
+
+/// Suggests the resource hints for the document, using the graph of its
+/// resources. The preconnects come first, then the preloads and then the
+/// dns-prefetches.
+pub fn resource_hints(
+    doc: &HtmlDocument,
+    graph: &ResourceGraph,
+    options: &HintOptions,
+) -> Vec<ResourceHint> {
+    let mut existing: Vec<(String, String)> = vec![];
+    let mut fonts = vec![];
+    let mut hero: Option<&HtmlTag> = None;
+    let mut hero_is_priority = false;
+    let mut body_scripts: Vec<NodePath> = vec![];
+    walk_tags(
+        &doc.nodes,
+        &mut vec![],
+        &mut vec![],
+        &mut |tag, path, ancestors| match tag.tag.to_ascii_lowercase().as_str() {
+            "link" => {
+                if let (Some(rel), Some(href)) =
+                    (tag.attributes.get("rel"), tag.attributes.get("href"))
+                {
+                    for rel in rel.split_ascii_whitespace() {
+                        existing.push((rel.to_ascii_lowercase(), href.clone()));
+                    }
+                }
+            }
+            "style" if options.fonts => {
+                for url in css_urls(&tag.text()) {
+                    if font_type(url).is_some() && !fonts.contains(&url.to_owned()) {
+                        fonts.push(url.to_owned());
+                    }
+                }
+            }
+            "img" if options.hero_image => {
+                let in_picture = ancestors
+                    .last()
+                    .is_some_and(|a| a.eq_ignore_ascii_case("picture"));
+                let is_priority = tag
+                    .attributes
+                    .get("fetchpriority")
+                    .is_some_and(|p| p.eq_ignore_ascii_case("high"));
+                let is_lazy = tag
+                    .attributes
+                    .get("loading")
+                    .is_some_and(|l| l.eq_ignore_ascii_case("lazy"));
+                let better = match hero {
+                    None => !is_lazy,
+                    Some(_) => is_priority && !hero_is_priority,
+                };
+                if better && !in_picture && tag.attributes.contains_key("src") {
+                    hero = Some(tag);
+                    hero_is_priority = is_priority;
+                }
+            }
+            "script" if ancestors.iter().any(|a| a.eq_ignore_ascii_case("body")) => {
+                body_scripts.push(path.clone());
+            }
+            _ => {}
+        },
+    );
+
+    let mut preloads = vec![];
+    // the third party origins which are needed to render the page, and whether
+    // they are fetched with CORS
+    let mut critical: Vec<(String, bool)> = vec![];
+    let add_critical = |url: &str, cors: bool, critical: &mut Vec<(String, bool)>| {
+        if let Some(origin) = options.third_party_origin(url) {
+            match critical.iter_mut().find(|(o, _)| same_origin(o, origin)) {
+                Some((_, c)) => *c |= cors,
+                None => critical.push((origin.to_owned(), cors)),
+            }
+        }
+    };
+    for url in fonts {
+        add_critical(&url, true, &mut critical);
+        let mut hint = ResourceHint::new(HintRel::Preload, url.as_str(), HintReason::Font);
+        hint.as_type = Some("font".to_owned());
+        hint.mime_type = font_type(&url).map(|t| t.to_owned());
+        hint.crossorigin = true;
+        preloads.push(hint);
+    }
+    if let Some(img) = hero {
+        let src = &img.attributes["src"];
+        add_critical(src, false, &mut critical);
+        let mut hint = ResourceHint::new(HintRel::Preload, src.as_str(), HintReason::HeroImage);
+        hint.as_type = Some("image".to_owned());
+        hint.image_srcset = img.attributes.get("srcset").cloned();
+        hint.image_sizes = img.attributes.get("sizes").cloned();
+        preloads.push(hint);
+    }
+    let mut others = vec![];
+    for resource in &graph.resources {
+        match resource.kind {
+            ResourceKind::Stylesheet | ResourceKind::Script | ResourceKind::ModuleScript => {
+                add_critical(&resource.url, false, &mut critical);
+            }
+            _ => {
+                if let Some(origin) = options.third_party_origin(&resource.url) {
+                    others.push(origin.to_owned());
+                }
+            }
+        }
+        if !options.scripts || options.third_party_origin(&resource.url).is_some() {
+            continue;
+        }
+        let in_body = resource
+            .path
+            .as_ref()
+            .is_some_and(|p| body_scripts.contains(p));
+        let hint = match resource.kind {
+            ResourceKind::ModuleScript if resource.path.is_none() || in_body => ResourceHint::new(
+                HintRel::ModulePreload,
+                resource.url.as_str(),
+                HintReason::Script,
+            ),
+            ResourceKind::Script if in_body => {
+                let mut hint =
+                    ResourceHint::new(HintRel::Preload, resource.url.as_str(), HintReason::Script);
+                hint.as_type = Some("script".to_owned());
+                hint
+            }
+            _ => continue,
+        };
+        preloads.push(hint);
+    }
+
+    let has_connection_hint = |origin: &str| {
+        existing.iter().any(|(rel, href)| {
+            (rel == "preconnect" || rel == "dns-prefetch") && same_origin(href, origin)
+        })
+    };
+    let mut hints = vec![];
+    let mut prefetches = vec![];
+    for (origin, cors) in critical {
+        if has_connection_hint(&origin) {
+            continue;
+        }
+        if hints.len() < options.max_preconnects {
+            let mut hint =
+                ResourceHint::new(HintRel::Preconnect, origin, HintReason::ThirdPartyOrigin);
+            hint.crossorigin = cors;
+            hints.push(hint);
+        } else {
+            prefetches.push(origin);
+        }
+    }
+    for origin in others {
+        if !has_connection_hint(&origin) && !prefetches.iter().any(|o| same_origin(o, &origin)) {
+            prefetches.push(origin);
+        }
+    }
+    let exists = |hint: &ResourceHint| {
+        existing
+            .iter()
+            .any(|(rel, href)| rel == hint.rel.as_str() && *href == hint.href)
+    };
+    hints.extend(preloads.into_iter().filter(|h| !exists(h)));
+    hints.extend(prefetches.into_iter().map(|origin| {
+        ResourceHint::new(HintRel::DnsPrefetch, origin, HintReason::ThirdPartyOrigin)
+    }));
+    hints
+}
+
+impl HtmlDocument {
+    /// Adds `<link>` elements for the hints to the start of the `<head>`, after any
+    /// `<meta charset>`, leaving out hints which the document already has. Returns
+    /// the number of links added, which is 0 if the document has no `<head>`.
+    pub fn insert_resource_hints(&mut self, hints: &[ResourceHint]) -> usize {
+        let head = match self.head_path().and_then(|p| self.node_at_mut(&p)) {
+            Some(HtmlNode::Tag(t)) => t,
+            _ => return 0,
+        };
+        let start = after_charset(head);
+        let mut added = 0;
+        for hint in hints {
+            let exists = head.contents.iter().any(|n| match n {
+                HtmlNode::Tag(t) if t.tag.eq_ignore_ascii_case("link") => {
+                    t.attributes.get("href") == Some(&hint.href)
+                        && t.attributes.get("rel").is_some_and(|r| {
+                            r.split_ascii_whitespace()
+                                .any(|r| r.eq_ignore_ascii_case(hint.rel.as_str()))
+                        })
+                }
+                _ => false,
+            });
+            if !exists {
+                head.contents
+                    .insert(start + added, HtmlNode::Tag(hint.to_tag()));
+                added += 1;
+            }
+        }
+        added
+    }
+}
+
+#[cfg(test)]
+mod hints_tests {
+    use super::*;
+    use crate::serialize::{AttributeOrder, SerializeOptions};
+
+    #[test]
+    fn css_urls_test() {
+        assert_eq!(
+            css_urls(
+                r#"a{background:url( "x.png" )} @font-face{src:url('f.woff2?v=2') format("woff2"),url(f.ttf)}"#
+            ),
+            vec!["x.png", "f.woff2?v=2", "f.ttf"]
+        );
+        assert!(css_urls("url(").is_empty());
+        assert_eq!(font_type("f.WOFF2?v=2"), Some("font/woff2"));
+        assert_eq!(font_type("x.png"), None);
+    }
+
+    #[test]
+    fn resource_hints_test() {
+        let doc = concat!(
+            r#"<html><head><link rel="preconnect" href="https://fonts.example">"#,
+            r#"<link rel="stylesheet" href="https://fonts.example/css">"#,
+            r#"<link rel="stylesheet" href="https://site.example/site.css">"#,
+            r#"<link rel="prefetch" href="https://next.example/page">"#,
+            r#"<style>@font-face{src:url(https://fonts.example/a.woff2)}</style>"#,
+            r#"<script src="https://a.example/a.js"></script></head><body>"#,
+            r#"<picture><source srcset="x.avif"><img src="in-picture.png"></picture>"#,
+            r#"<img src="lazy.png" loading="lazy"><img src="first.png">"#,
+            r#"<img src="https://img.example/hero.png" srcset="hero-2x.png 2x" fetchpriority="high">"#,
+            r#"<script src="/body.js"></script><script src="https://b.example/b.js"></script>"#,
+            r#"<script type="module" src="/m.js"></script></body></html>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let graph = ResourceGraph::from_document(&doc);
+        let hints = resource_hints(
+            &doc,
+            &graph,
+            &HintOptions::new()
+                .site_origin("https://site.example")
+                .max_preconnects(2),
+        );
+        let found: Vec<(HintRel, HintReason, &str)> = hints
+            .iter()
+            .map(|h| (h.rel, h.reason, h.href.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    HintRel::Preconnect,
+                    HintReason::ThirdPartyOrigin,
+                    "https://img.example"
+                ),
+                (
+                    HintRel::Preconnect,
+                    HintReason::ThirdPartyOrigin,
+                    "https://a.example"
+                ),
+                (
+                    HintRel::Preload,
+                    HintReason::Font,
+                    "https://fonts.example/a.woff2"
+                ),
+                (
+                    HintRel::Preload,
+                    HintReason::HeroImage,
+                    "https://img.example/hero.png"
+                ),
+                (HintRel::Preload, HintReason::Script, "/body.js"),
+                (HintRel::ModulePreload, HintReason::Script, "/m.js"),
+                (
+                    HintRel::DnsPrefetch,
+                    HintReason::ThirdPartyOrigin,
+                    "https://b.example"
+                ),
+                (
+                    HintRel::DnsPrefetch,
+                    HintReason::ThirdPartyOrigin,
+                    "https://next.example"
+                ),
+            ]
+        );
+        let options = SerializeOptions::new().attribute_order(AttributeOrder::Alphabetical);
+        assert_eq!(
+            HtmlNode::Tag(hints[2].to_tag()).to_html_string_with(&options),
+            r#"<link as="font" crossorigin="" href="https://fonts.example/a.woff2" rel="preload" type="font/woff2"></link>"#
+        );
+        assert_eq!(hints[3].image_srcset.as_deref(), Some("hero-2x.png 2x"));
+
+        let hints = resource_hints(
+            &doc,
+            &graph,
+            &HintOptions::new()
+                .fonts(false)
+                .hero_image(false)
+                .scripts(false)
+                .max_preconnects(0),
+        );
+        assert!(hints.iter().all(|h| h.rel == HintRel::DnsPrefetch));
+    }
+
+    #[test]
+    fn insert_resource_hints_test() {
+        let mut doc = r#"<head><meta charset="utf-8"><title>x</title></head>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let hints = vec![
+            ResourceHint::new(
+                HintRel::Preconnect,
+                "https://a.example",
+                HintReason::ThirdPartyOrigin,
+            ),
+            ResourceHint::new(
+                HintRel::DnsPrefetch,
+                "https://b.example",
+                HintReason::ThirdPartyOrigin,
+            ),
+        ];
+        assert_eq!(doc.insert_resource_hints(&hints), 2);
+        assert_eq!(doc.insert_resource_hints(&hints), 0);
+        let head = match &doc.nodes[0] {
+            HtmlNode::Tag(t) => t,
+            _ => panic!("expected the head"),
+        };
+        let names: Vec<String> = head
+            .contents
+            .iter()
+            .map(|n| match n {
+                HtmlNode::Tag(t) => t.get_attribute("rel").unwrap_or(t.tag.clone()),
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(names, vec!["meta", "preconnect", "dns-prefetch", "title"]);
+        let mut fragment = "<p>x</p>".parse::<HtmlDocument>().unwrap();
+        assert_eq!(fragment.insert_resource_hints(&hints), 0);
+    }
+}
@@ -0,0 +1,320 @@
+//! A log of the changes made to a document, with undo and redo.
+//!
+//! [`DocumentHistory`] owns a document and changes it with
+//! [`OverlayMutation`]s, the same changes a
+//! [`DocumentOverlay`](crate::overlay::DocumentOverlay) records. Before each
+//! mutation is applied the mutation which reverses it is worked out from the
+//! document, so [`DocumentHistory::undo`] and [`DocumentHistory::redo`] can step
+//! back and forth through the log. [`DocumentHistory::export`] writes the log as
+//! text, one mutation per line, for checking what an automated rewrite did.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::history::DocumentHistory;
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::overlay::OverlayMutation;
+//! let doc = "<p class=a>One</p>".parse::<HtmlDocument>().unwrap();
+//! let mut history = DocumentHistory::new(doc.clone());
+//! history
+//!     .apply(OverlayMutation::SetText { path: vec![0], text: "Two".to_owned() })
+//!     .unwrap();
+//! history.apply(OverlayMutation::Remove { path: vec![0] }).unwrap();
+//! assert!(history.document().nodes.is_empty());
+//! assert!(history.undo().unwrap());
+//! assert_eq!(history.document().nodes[0].text(), "Two");
+//! assert!(history.undo().unwrap());
+//! assert_eq!(history.document(), &doc);
+//! assert!(history.redo().unwrap());
+//! assert_eq!(history.export(), "set-text [0] \"Two\"\n");
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlNode};
+use crate::overlay::OverlayMutation;
+
+/// A mutation in the log of a [`DocumentHistory`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub mutation: OverlayMutation,
+    /// The mutation which puts the document back as it was before the mutation.
+    pub inverse: OverlayMutation,
+}
+
+/// A document along with the log of the mutations made to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentHistory {
+    document: HtmlDocument,
+    /// The mutations which have been applied, in order.
+    done: Vec<LogEntry>,
+    /// The mutations which have been undone, the last undone being last.
+    undone: Vec<LogEntry>,
+}
+
+impl DocumentHistory {
+    pub fn new(document: HtmlDocument) -> DocumentHistory {
+        DocumentHistory {
+            document,
+            done: vec![],
+            undone: vec![],
+        }
+    }
+
+    pub fn document(&self) -> &HtmlDocument {
+        &self.document
+    }
+
+    pub fn into_document(self) -> HtmlDocument {
+        self.document
+    }
+
+    /// Applies the mutation and adds it to the log, throwing away the mutations
+    /// which were undone. Nothing is changed if the mutation can not be applied.
+    pub fn apply(&mut self, mutation: OverlayMutation) -> Result<(), HtmlDocError> {
+        let inverse = inverse_of(&mutation, &self.document)?;
+        mutation.apply(&mut self.document)?;
+        self.done.push(LogEntry { mutation, inverse });
+        self.undone.clear();
+        Ok(())
+    }
+
+    /// Reverses the last mutation, returning false if there is nothing to undo.
+    pub fn undo(&mut self) -> Result<bool, HtmlDocError> {
+        let Some(entry) = self.done.pop() else {
+            return Ok(false);
+        };
+        entry.inverse.apply(&mut self.document)?;
+        self.undone.push(entry);
+        Ok(true)
+    }
+
+    /// Applies the last mutation which was undone again, returning false if there
+    /// is nothing to redo.
+    pub fn redo(&mut self) -> Result<bool, HtmlDocError> {
+        let Some(entry) = self.undone.pop() else {
+            return Ok(false);
+        };
+        entry.mutation.apply(&mut self.document)?;
+        self.done.push(entry);
+        Ok(true)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Gets the mutations which have been applied and not undone, in order.
+    pub fn log(&self) -> &[LogEntry] {
+        &self.done
+    }
+
+    /// Writes the mutations of the log, one per line.
+    pub fn export(&self) -> String {
+        self.done
+            .iter()
+            .map(|e| format!("{}\n", e.mutation))
+            .collect()
+    }
+}
+
+/// Works out the mutation which reverses the mutation, from the document before it
+/// is applied.
+fn inverse_of(
+    mutation: &OverlayMutation,
+    doc: &HtmlDocument,
+) -> Result<OverlayMutation, HtmlDocError> {
+    let node_at = |path: &[usize]| {
+        doc.node_at(path)
+            .ok_or_else(|| HtmlDocError::with_msg(format!("there is no node at {:?}", path)))
+    };
+    let tag_at = |path: &[usize]| match node_at(path)? {
+        HtmlNode::Tag(t) => Ok(t),
+        _ => Err(HtmlDocError::with_msg(format!(
+            "there is no tag at {:?}",
+            path
+        ))),
+    };
+    let inverse = match mutation {
+        OverlayMutation::SetAttribute { path, name, .. }
+        | OverlayMutation::RemoveAttribute { path, name } => {
+            match tag_at(path)?.get_attribute(name) {
+                Some(value) => OverlayMutation::SetAttribute {
+                    path: path.clone(),
+                    name: name.clone(),
+                    value,
+                },
+                None => OverlayMutation::RemoveAttribute {
+                    path: path.clone(),
+                    name: name.clone(),
+                },
+            }
+        }
+        OverlayMutation::SetText { path, .. } | OverlayMutation::Replace { path, .. } => {
+            OverlayMutation::Replace {
+                path: path.clone(),
+                node: node_at(path)?.clone(),
+            }
+        }
+        OverlayMutation::Remove { path } => {
+            let (index, parent) = path
+                .split_last()
+                .ok_or_else(|| HtmlDocError::with_msg("there is no node at []"))?;
+            OverlayMutation::InsertChild {
+                path: parent.to_vec(),
+                index: *index,
+                node: node_at(path)?.clone(),
+            }
+        }
+        OverlayMutation::InsertChild { path, index, .. } => {
+            let mut child = path.clone();
+            child.push(*index);
+            OverlayMutation::Remove { path: child }
+        }
+    };
+    Ok(inverse)
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+    use crate::objects::HtmlTag;
+
+    #[test]
+    fn undo_redo_test() {
+        let doc = "<div id=main><p class=a>One</p><p>Two</p></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let mut history = DocumentHistory::new(doc.clone());
+        let mutations = vec![
+            OverlayMutation::SetAttribute {
+                path: vec![0, 0],
+                name: "class".to_owned(),
+                value: "b c".to_owned(),
+            },
+            OverlayMutation::SetAttribute {
+                path: vec![0, 1],
+                name: "title".to_owned(),
+                value: "t".to_owned(),
+            },
+            OverlayMutation::RemoveAttribute {
+                path: vec![0],
+                name: "id".to_owned(),
+            },
+            OverlayMutation::InsertChild {
+                path: vec![0],
+                index: 0,
+                node: HtmlNode::Tag(HtmlTag::new("h1")),
+            },
+            OverlayMutation::SetText {
+                path: vec![0, 1],
+                text: "Changed".to_owned(),
+            },
+            OverlayMutation::Replace {
+                path: vec![0, 2],
+                node: HtmlNode::Text("text".to_owned()),
+            },
+            OverlayMutation::Remove { path: vec![0, 0] },
+        ];
+        let mut states = vec![doc.clone()];
+        for mutation in mutations {
+            history.apply(mutation).unwrap();
+            states.push(history.document().clone());
+        }
+        assert_eq!(
+            history.document().to_html_string(),
+            "<div><p class=\"b c\">Changed</p>text</div>"
+        );
+        // undo all the way back, checking each state on the way
+        for state in states.iter().rev().skip(1) {
+            assert!(history.undo().unwrap());
+            assert_eq!(history.document(), state);
+        }
+        assert!(!history.undo().unwrap());
+        for state in states.iter().skip(1) {
+            assert!(history.redo().unwrap());
+            assert_eq!(history.document(), state);
+        }
+        assert!(!history.redo().unwrap() && history.can_undo());
+
+        // a new mutation after an undo throws away what was undone
+        history.undo().unwrap();
+        history
+            .apply(OverlayMutation::Remove { path: vec![0] })
+            .unwrap();
+        assert!(!history.can_redo());
+        assert_eq!(history.log().len(), 7);
+    }
+
+    #[test]
+    fn invalid_mutation_test() {
+        let doc = "<p>One</p>".parse::<HtmlDocument>().unwrap();
+        let mut history = DocumentHistory::new(doc.clone());
+        let err = history
+            .apply(OverlayMutation::RemoveAttribute {
+                path: vec![0, 0],
+                name: "id".to_owned(),
+            })
+            .unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Html Doc Error: 'there is no tag at [0, 0]'"
+        );
+        assert!(history
+            .apply(OverlayMutation::InsertChild {
+                path: vec![0],
+                index: 2,
+                node: HtmlNode::Text("x".to_owned()),
+            })
+            .is_err());
+        assert!(history.log().is_empty());
+        assert_eq!(history.into_document(), doc);
+    }
+
+    #[test]
+    fn export_test() {
+        let doc = "<ul><li>a</li></ul>".parse::<HtmlDocument>().unwrap();
+        let mut history = DocumentHistory::new(doc);
+        let li = HtmlNode::Tag("<li>b</li>".parse::<HtmlTag>().unwrap());
+        for mutation in [
+            OverlayMutation::InsertChild {
+                path: vec![0],
+                index: 1,
+                node: li,
+            },
+            OverlayMutation::SetAttribute {
+                path: vec![0],
+                name: "class".to_owned(),
+                value: "list".to_owned(),
+            },
+            OverlayMutation::RemoveAttribute {
+                path: vec![0],
+                name: "class".to_owned(),
+            },
+            OverlayMutation::SetText {
+                path: vec![0, 0],
+                text: "say \"hi\"".to_owned(),
+            },
+            OverlayMutation::Replace {
+                path: vec![0, 1, 0],
+                node: HtmlNode::Comment(" c ".to_owned()),
+            },
+            OverlayMutation::Remove { path: vec![0, 1] },
+        ] {
+            history.apply(mutation).unwrap();
+        }
+        assert_eq!(
+            history.export(),
+            concat!(
+                "insert-child [0] 1 <li>b</li>\n",
+                "set-attribute [0] class=\"list\"\n",
+                "remove-attribute [0] class\n",
+                "set-text [0, 0] \"say \\\"hi\\\"\"\n",
+                "replace [0, 1, 0] <!--  c  --!>\n",
+                "remove [0, 1]\n",
+            )
+        );
+    }
+}
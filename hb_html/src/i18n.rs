@@ -0,0 +1,352 @@
+//! Extracting the text of a document for translation, and putting the translated
+//! text back into a copy of the document.
+//!
+//! Each [`TextSegment`] is a text node or a translatable attribute such as `alt`,
+//! with a key which is a hash of its normalized text and where it is in the
+//! document, so the keys stay the same when the document is extracted again.
+//! Text inside scripts, styles, code and elements with `translate="no"` is left
+//! out, as is text without any letters, such as numbers and punctuation.
+//!
+//! The text is decoded, as the parser decodes character references such as
+//! `&amp;` by default, and translations are put into the document as text which
+//! is escaped again when it is written.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::i18n::I18nOptions;
+//! use hb_html::objects::HtmlDocument;
+//! use std::collections::HashMap;
+//! let doc = r#"<div><h1>  Hello,   world </h1><p translate="no">hb_html</p><img alt="A cat" src="cat.png"><p>42</p></div>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let options = I18nOptions::new();
+//! let segments = doc.text_segments(&options);
+//! assert_eq!(segments.len(), 2);
+//! assert_eq!(segments[0].text, "Hello, world");
+//! assert_eq!(segments[1].attribute.as_deref(), Some("alt"));
+//! let translations = HashMap::from([(segments[0].key.clone(), "Bonjour, le monde".to_owned())]);
+//! let (french, untranslated) = doc.translated(&translations, &options);
+//! assert_eq!(untranslated, vec![segments[1].clone()]);
+//! assert!(french.to_html_string().starts_with("<div><h1>  Bonjour, le monde </h1>"));
+//! // the original document is not changed
+//! assert_eq!(doc.text_segments(&options), segments);
+//! ```
+use crate::error::HtmlDocError;
+use crate::hash::fnv1a_64;
+use crate::objects::{HtmlDocument, HtmlNode, NodePath};
+use crate::pipeline::{Pass, PassContext};
+use std::collections::HashMap;
+
+/// The tags whose text is not translated by default.
+pub const UNTRANSLATED_TAGS: [&str; 6] = ["script", "style", "code", "kbd", "samp", "var"];
+
+/// The attributes which are translated by default.
+pub const TRANSLATABLE_ATTRIBUTES: [&str; 4] = ["alt", "title", "placeholder", "aria-label"];
+
+/// Options for finding the text segments of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct I18nOptions {
+    /// The tags whose contents (and attributes) are not translated.
+    pub skip_tags: Vec<String>,
+    /// The attributes whose values are translated.
+    pub attributes: Vec<String>,
+}
+
+impl Default for I18nOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl I18nOptions {
+    /// Creates the options with the [`UNTRANSLATED_TAGS`] and [`TRANSLATABLE_ATTRIBUTES`].
+    pub fn new() -> I18nOptions {
+        I18nOptions {
+            skip_tags: UNTRANSLATED_TAGS.iter().map(|t| t.to_string()).collect(),
+            attributes: TRANSLATABLE_ATTRIBUTES
+                .iter()
+                .map(|a| a.to_string())
+                .collect(),
+        }
+    }
+
+    /// Sets the tags whose contents are not translated.
+    pub fn skip_tags<T: Into<String>>(mut self, tags: Vec<T>) -> I18nOptions {
+        self.skip_tags = tags.into_iter().map(|t| t.into()).collect();
+        self
+    }
+
+    /// Sets the attributes whose values are translated.
+    pub fn attributes<T: Into<String>>(mut self, attributes: Vec<T>) -> I18nOptions {
+        self.attributes = attributes.into_iter().map(|a| a.into()).collect();
+        self
+    }
+}
+
+/// A piece of text in a document which can be translated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSegment {
+    /// The key of the segment, see [`segment_key`].
+    pub key: String,
+    /// The path to the text node, or to the element for an attribute.
+    pub path: NodePath,
+    /// The name of the attribute, or None for a text node.
+    pub attribute: Option<String>,
+    /// The text with the whitespace at the ends removed and the rest collapsed to
+    /// single spaces.
+    pub text: String,
+}
+
+/// Collapses the whitespace in the text to single spaces and trims the ends.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Gets the key for a segment, which is the 64 bit FNV-1a hash of the normalized
+/// text, the path and the attribute name as 16 hex digits.
+pub fn segment_key(text: &str, path: &[usize], attribute: Option<&str>) -> String {
+    let path = path
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<String>>()
+        .join(".");
+    let input = format!("{}\u{1f}{}\u{1f}{}", text, path, attribute.unwrap_or(""));
+    format!("{:016x}", fnv1a_64(input.as_bytes()))
+}
+
+/// Returns true if the text has something to translate, rather than only
+/// numbers, punctuation and whitespace.
+fn is_translatable(text: &str) -> bool {
+    text.chars().any(char::is_alphabetic)
+}
+
+fn collect_segments(
+    nodes: &[HtmlNode],
+    options: &I18nOptions,
+    path: &mut NodePath,
+    found: &mut Vec<TextSegment>,
+) {
+    for (i, node) in nodes.iter().enumerate() {
+        path.push(i);
+        match node {
+            HtmlNode::Text(t) if is_translatable(t) => {
+                let text = normalize(t);
+                found.push(TextSegment {
+                    key: segment_key(&text, path, None),
+                    path: path.clone(),
+                    attribute: None,
+                    text,
+                });
+            }
+            HtmlNode::Tag(t) => {
+                let skipped = options
+                    .skip_tags
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(&t.tag))
+                    || t.attributes
+                        .get("translate")
+                        .is_some_and(|v| v.trim().eq_ignore_ascii_case("no"));
+                if !skipped {
+                    for attribute in &options.attributes {
+                        if let Some(value) = t.attributes.get(attribute) {
+                            if is_translatable(value) {
+                                let text = normalize(value);
+                                found.push(TextSegment {
+                                    key: segment_key(&text, path, Some(attribute)),
+                                    path: path.clone(),
+                                    attribute: Some(attribute.clone()),
+                                    text,
+                                });
+                            }
+                        }
+                    }
+                    collect_segments(&t.contents, options, path, found);
+                }
+            }
+            _ => {}
+        }
+        path.pop();
+    }
+}
+
+impl HtmlDocument {
+    /// Gets the segments of the document which can be translated, in document order.
+    pub fn text_segments(&self, options: &I18nOptions) -> Vec<TextSegment> {
+        let mut found = vec![];
+        collect_segments(&self.nodes, options, &mut vec![], &mut found);
+        found
+    }
+
+    /// Replaces the text of each segment which has a translation, by the key of
+    /// the segment. The whitespace around the text of a text node is kept. Returns
+    /// the segments which had no translation.
+    pub fn translate(
+        &mut self,
+        translations: &HashMap<String, String>,
+        options: &I18nOptions,
+    ) -> Vec<TextSegment> {
+        let mut untranslated = vec![];
+        for segment in self.text_segments(options) {
+            let translation = match translations.get(&segment.key) {
+                Some(translation) => translation,
+                None => {
+                    untranslated.push(segment);
+                    continue;
+                }
+            };
+            match (self.node_at_mut(&segment.path), &segment.attribute) {
+                (Some(HtmlNode::Text(t)), None) => {
+                    let start = t.len() - t.trim_start().len();
+                    let end = t.trim_end().len();
+                    t.replace_range(start..end, translation);
+                }
+                (Some(HtmlNode::Tag(t)), Some(attribute)) => {
+                    t.attributes.insert(attribute.clone(), translation.clone());
+                }
+                _ => {}
+            }
+        }
+        untranslated
+    }
+
+    /// Gets a copy of the document with the translations put in, and the segments
+    /// which had no translation. See [`HtmlDocument::translate`].
+    pub fn translated(
+        &self,
+        translations: &HashMap<String, String>,
+        options: &I18nOptions,
+    ) -> (HtmlDocument, Vec<TextSegment>) {
+        let mut doc = self.clone();
+        let untranslated = doc.translate(translations, options);
+        (doc, untranslated)
+    }
+}
+
+/// A [`Pass`] which finds the text segments of the document, keeping them in
+/// `segments` for after the pipeline has run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtractSegments {
+    pub options: I18nOptions,
+    pub segments: Vec<TextSegment>,
+}
+
+impl ExtractSegments {
+    pub fn new(options: I18nOptions) -> ExtractSegments {
+        ExtractSegments {
+            options,
+            segments: vec![],
+        }
+    }
+}
+
+impl Pass for ExtractSegments {
+    fn name(&self) -> &str {
+        "extract-segments"
+    }
+
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError> {
+        self.segments = doc.text_segments(&self.options);
+        ctx.info(format!("found {} text segments", self.segments.len()), None);
+        Ok(())
+    }
+}
+
+/// A [`Pass`] which puts translations into the document, with a warning for each
+/// segment which has no translation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InjectTranslations {
+    pub options: I18nOptions,
+    /// The translated text, by the key of the segment.
+    pub translations: HashMap<String, String>,
+}
+
+impl InjectTranslations {
+    pub fn new(translations: HashMap<String, String>, options: I18nOptions) -> InjectTranslations {
+        InjectTranslations {
+            options,
+            translations,
+        }
+    }
+}
+
+impl Pass for InjectTranslations {
+    fn name(&self) -> &str {
+        "inject-translations"
+    }
+
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError> {
+        for segment in doc.translate(&self.translations, &self.options) {
+            ctx.warning(
+                format!("no translation for {} \"{}\"", segment.key, segment.text),
+                Some(segment.path),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod i18n_tests {
+    use super::*;
+    use crate::pipeline::Pipeline;
+
+    #[test]
+    fn text_segments_test() {
+        let doc = concat!(
+            r#"<div><p title="Greeting">Hi <b>there</b></p><pre><code>let x = 1;</code></pre>"#,
+            r#"<script>go()</script><span>3.50 - 4</span>"#,
+            r#"<section translate="no"><p>Acme</p></section><input placeholder="Search"></div>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let segments = doc.text_segments(&I18nOptions::new());
+        let found: Vec<(&str, &NodePath, Option<&str>)> = segments
+            .iter()
+            .map(|s| (s.text.as_str(), &s.path, s.attribute.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Greeting", &vec![0, 0], Some("title")),
+                ("Hi", &vec![0, 0, 0], None),
+                ("there", &vec![0, 0, 1, 0], None),
+                ("Search", &vec![0, 5], Some("placeholder")),
+            ]
+        );
+        // the keys depend on the text and where it is
+        assert_eq!(segments[1].key, segment_key("Hi", &[0, 0, 0], None));
+        assert_ne!(segments[1].key, segment_key("Hi", &[0, 0, 1], None));
+        assert_ne!(
+            segments[1].key,
+            segment_key("Hi", &[0, 0, 0], Some("title"))
+        );
+        let options = I18nOptions::new().skip_tags(Vec::<String>::new());
+        assert!(doc
+            .text_segments(&options)
+            .iter()
+            .any(|s| s.text == "let x = 1;"));
+    }
+
+    #[test]
+    fn inject_translations_test() {
+        let mut doc = r#"<div><p title="Greeting">Hi <b>there</b></p></div>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let segments = doc.text_segments(&I18nOptions::new());
+        let translations = HashMap::from([
+            (segments[0].key.clone(), "Salutation".to_owned()),
+            (segments[1].key.clone(), "Salut".to_owned()),
+        ]);
+        let report = Pipeline::new()
+            .add(InjectTranslations::new(translations, I18nOptions::new()))
+            .run(&mut doc)
+            .unwrap();
+        assert_eq!(
+            doc.to_html_string(),
+            r#"<div><p title="Salutation">Salut <b>there</b></p></div>"#
+        );
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].path, Some(vec![0, 0, 1, 0]));
+    }
+}
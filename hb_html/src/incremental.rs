@@ -0,0 +1,206 @@
+//! Keeping a parsed document up to date as its source is edited.
+//!
+//! An [`EditableDocument`] keeps the source of a document along with the byte range
+//! of each top level node. When the source is edited only the top level nodes
+//! around the edit are parsed again and patched into the tree, unless the edit
+//! could change how the rest of the document is parsed (eg it touches the doctype)
+//! in which case the whole document is parsed again.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::incremental::{EditableDocument, Reparse};
+//! let mut editable = EditableDocument::new("<p>one</p><p>two</p><p>three</p>").unwrap();
+//! let kind = editable.reparse_edit(13..16, "2").unwrap();
+//! assert_eq!(kind, Reparse::Partial { nodes: 1..2, inserted: 1 });
+//! assert_eq!(editable.source(), "<p>one</p><p>2</p><p>three</p>");
+//! assert_eq!(editable.document().nodes[1].text(), "2");
+//! ```
+use crate::error::ParseHtmlError;
+use crate::objects::{parse_document_with_spans, HtmlDocument};
+use std::ops::Range;
+
+/// How the document was updated after an edit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reparse {
+    /// The top level nodes in the range (from before the edit) were replaced by the
+    /// number of nodes provided.
+    Partial {
+        nodes: Range<usize>,
+        inserted: usize,
+    },
+    /// The whole document was parsed again.
+    Full,
+}
+
+/// A parsed document along with its source, which can be edited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditableDocument {
+    source: String,
+    doc: HtmlDocument,
+    spans: Vec<Range<usize>>,
+}
+
+impl EditableDocument {
+    pub fn new<S: Into<String>>(source: S) -> Result<EditableDocument, ParseHtmlError> {
+        let source = source.into();
+        let (doc, spans) = parse_document_with_spans(&source)?;
+        Ok(EditableDocument { source, doc, spans })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn document(&self) -> &HtmlDocument {
+        &self.doc
+    }
+
+    pub fn into_document(self) -> HtmlDocument {
+        self.doc
+    }
+
+    /// The byte range in the source of each of the top level nodes of the document.
+    pub fn node_spans(&self) -> &[Range<usize>] {
+        &self.spans
+    }
+
+    /// Replaces the byte range of the source with the new text and updates the
+    /// document. If the new source can not be parsed the error is returned and the
+    /// source and document are left as they were.
+    pub fn reparse_edit(
+        &mut self,
+        range: Range<usize>,
+        new_text: &str,
+    ) -> Result<Reparse, ParseHtmlError> {
+        if range.start > range.end
+            || !self.source.is_char_boundary(range.start)
+            || !self.source.is_char_boundary(range.end)
+        {
+            return Err(ParseHtmlError::with_msg(format!(
+                "the range {:?} is not a valid range of the source",
+                range
+            )));
+        }
+        let mut source = self.source.clone();
+        source.replace_range(range.clone(), new_text);
+        // nodes which touch the edit are parsed again, as the edit could join them
+        // to the text around them
+        let first = self.spans.partition_point(|s| s.end < range.start);
+        let last = self.spans.partition_point(|s| s.start <= range.end);
+        // the start of the document is only parsed again if the edit is before the
+        // first node, as it can contain the doctype
+        let region_start = if first > 0 {
+            self.spans[first - 1].end
+        } else {
+            match self.spans.first() {
+                Some(s) if range.start >= s.start => s.start,
+                _ => 0,
+            }
+        };
+        let region_end = self.spans.get(last).map_or(self.source.len(), |s| s.start);
+        let delta = new_text.len() as isize - range.len() as isize;
+        let new_region_end = (region_end as isize + delta) as usize;
+        match parse_document_with_spans(&source[region_start..new_region_end]) {
+            // a doctype in the region could clash with one elsewhere, so parse everything
+            Ok((region, spans)) if region.doctype.is_empty() => {
+                let inserted = region.nodes.len();
+                self.doc.nodes.splice(first..last, region.nodes);
+                let shifted = self.spans[last..]
+                    .iter()
+                    .map(|s| (s.start as isize + delta) as usize..(s.end as isize + delta) as usize)
+                    .collect::<Vec<_>>();
+                self.spans.splice(
+                    first..,
+                    spans
+                        .into_iter()
+                        .map(|s| s.start + region_start..s.end + region_start)
+                        .chain(shifted),
+                );
+                self.source = source;
+                Ok(Reparse::Partial {
+                    nodes: first..last,
+                    inserted,
+                })
+            }
+            _ => {
+                let (doc, spans) = parse_document_with_spans(&source)?;
+                self.source = source;
+                self.doc = doc;
+                self.spans = spans;
+                Ok(Reparse::Full)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod incremental_tests {
+    use super::*;
+
+    /// Checks the incremental result is the same as parsing the whole source.
+    fn assert_matches_full_parse(editable: &EditableDocument) {
+        let (doc, spans) = parse_document_with_spans(editable.source()).unwrap();
+        assert_eq!(editable.document(), &doc);
+        assert_eq!(editable.node_spans(), spans.as_slice());
+    }
+
+    #[test]
+    fn reparse_edit_partial_test() {
+        let mut editable =
+            EditableDocument::new("<!DOCTYPE html><div>a</div>\n<p>b</p>\n<p>c</p>").unwrap();
+        assert_eq!(editable.node_spans(), &[15..27, 28..36, 37..45]);
+        // inside a node
+        let kind = editable.reparse_edit(31..32, "bee").unwrap();
+        assert_eq!(
+            kind,
+            Reparse::Partial {
+                nodes: 1..2,
+                inserted: 1
+            }
+        );
+        assert_matches_full_parse(&editable);
+        // between nodes, splitting nothing but touching both
+        let kind = editable.reparse_edit(38..38, "<i>x</i>").unwrap();
+        assert_eq!(
+            kind,
+            Reparse::Partial {
+                nodes: 1..2,
+                inserted: 2
+            }
+        );
+        assert_matches_full_parse(&editable);
+        // removing a node
+        let kind = editable.reparse_edit(15..27, "").unwrap();
+        assert_eq!(
+            kind,
+            Reparse::Partial {
+                nodes: 0..1,
+                inserted: 0
+            }
+        );
+        assert_matches_full_parse(&editable);
+        assert_eq!(editable.document().doctype, "html");
+    }
+
+    #[test]
+    fn reparse_edit_full_test() {
+        let mut editable = EditableDocument::new("<!DOCTYPE html><p>a</p>").unwrap();
+        assert_eq!(editable.reparse_edit(10..14, "svg").unwrap(), Reparse::Full);
+        assert_eq!(editable.document().doctype, "svg");
+        assert_matches_full_parse(&editable);
+    }
+
+    #[test]
+    fn reparse_edit_errors_test() {
+        let mut editable = EditableDocument::new("<p>é</p>").unwrap();
+        let before = editable.clone();
+        assert!(editable.reparse_edit(4..5, "").is_err());
+        let (start, end) = (3, 2);
+        assert!(editable.reparse_edit(start..end, "").is_err());
+        assert!(editable.reparse_edit(0..1, "").is_err());
+        // a tag which is no longer closed
+        assert!(editable.reparse_edit(6..10, "").is_err());
+        assert_eq!(editable, before);
+    }
+}
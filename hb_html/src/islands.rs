@@ -0,0 +1,465 @@
+//! Finding the islands of HTML in text which is mostly not HTML, such as Markdown
+//! or the comments in source code.
+//!
+//! [`islands`] splits the text into [`Segment`]s which are either text or HTML,
+//! with the spans they came from, so the HTML can be parsed and sanitized on its
+//! own while the text is left to whatever renders it. Markup is found in the same
+//! way as inline HTML in CommonMark: start and end tags, comments, processing
+//! instructions, declarations and CDATA sections. A `<` which does not start any of
+//! these, such as the one in `a < b` or `<https://example.com>`, is text.
+//!
+//! A start tag is put in the same island as everything up to its end tag, so an
+//! island can be parsed as a whole element. A start tag without an end tag, or a
+//! stray end tag, is an island on its own, which the strict parser will not
+//! accept, so [`Segment::parse`] takes the [`ParseOptions`] to repair them with.
+//! Islands which follow each other with nothing between them are one segment, so
+//! the segments are always text and HTML in turn.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::islands::{islands, SegmentKind};
+//! use hb_html::options::ParseOptions;
+//! use hb_html::sanitize::{sanitize, SanitizePolicy};
+//! let text = "Some *markdown* with <b onclick=\"evil()\">bold</b> text\nand a < b.";
+//! let segments: Vec<_> = islands(text).collect();
+//! let found: Vec<(SegmentKind, &str)> = segments.iter().map(|s| (s.kind, s.text)).collect();
+//! assert_eq!(
+//!     found,
+//!     vec![
+//!         (SegmentKind::Text, "Some *markdown* with "),
+//!         (SegmentKind::Html, "<b onclick=\"evil()\">bold</b>"),
+//!         (SegmentKind::Text, " text\nand a < b."),
+//!     ]
+//! );
+//! assert_eq!((segments[1].span.start, segments[1].span.col), (21, 22));
+//! let mut island = segments[1].parse(&ParseOptions::new()).unwrap();
+//! sanitize(&mut island, &SanitizePolicy::default());
+//! assert_eq!(island.to_html_string(), "<b>bold</b>");
+//! ```
+use crate::error::ParseHtmlError;
+use crate::objects::{HtmlDocument, HtmlNode};
+use crate::options::ParseOptions;
+use crate::source_map::{LineCounter, Span};
+use std::ops::Range;
+
+const VOID_TAGS: [&str; 16] = [
+    "area", "base", "br", "col", "command", "embed", "hr", "img", "input", "keygen", "link",
+    "meta", "param", "source", "track", "wbr",
+];
+
+/// Options for finding the islands of HTML in text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IslandOptions {
+    /// Leave out the markup in Markdown code spans and after a backslash, which
+    /// Markdown shows as it is.
+    pub markdown: bool,
+    /// Make each tag its own island rather than finding the end tag of each start
+    /// tag, as markdown-it does.
+    pub single_tags: bool,
+}
+
+impl IslandOptions {
+    pub fn new() -> IslandOptions {
+        IslandOptions::default()
+    }
+
+    pub fn markdown(mut self, markdown: bool) -> IslandOptions {
+        self.markdown = markdown;
+        self
+    }
+
+    pub fn single_tags(mut self, single_tags: bool) -> IslandOptions {
+        self.single_tags = single_tags;
+        self
+    }
+}
+
+/// Whether a [`Segment`] is text or HTML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SegmentKind {
+    Text,
+    Html,
+}
+
+/// A part of the text found by [`islands`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment<'a> {
+    pub kind: SegmentKind,
+    pub text: &'a str,
+    /// Where the segment is in the text which was split.
+    pub span: Span,
+}
+
+impl Segment<'_> {
+    pub fn is_html(&self) -> bool {
+        self.kind == SegmentKind::Html
+    }
+
+    /// Parses an HTML segment, or makes a document of a single text node for a text
+    /// segment. The offsets in an error are from the start of the segment.
+    pub fn parse(&self, options: &ParseOptions) -> Result<HtmlDocument, ParseHtmlError> {
+        match self.kind {
+            SegmentKind::Html => HtmlDocument::parse_with(self.text, options),
+            SegmentKind::Text => {
+                let mut doc = HtmlDocument::new();
+                if !self.text.is_empty() {
+                    doc.nodes.push(HtmlNode::Text(self.text.to_owned()));
+                }
+                Ok(doc)
+            }
+        }
+    }
+}
+
+/// Splits the text into text and HTML segments with the default options.
+pub fn islands(text: &str) -> Islands<'_> {
+    islands_with(text, &IslandOptions::default())
+}
+
+/// Splits the text into text and HTML segments.
+pub fn islands_with<'a>(text: &'a str, options: &IslandOptions) -> Islands<'a> {
+    Islands {
+        text,
+        options: options.clone(),
+        pos: 0,
+        next_island: None,
+        lines: LineCounter::new(text),
+    }
+}
+
+/// An iterator over the segments of a text, made by [`islands`].
+pub struct Islands<'a> {
+    text: &'a str,
+    options: IslandOptions,
+    pos: usize,
+    /// The island found after the text segment which was last returned.
+    next_island: Option<Range<usize>>,
+    lines: LineCounter<'a>,
+}
+
+impl<'a> Iterator for Islands<'a> {
+    type Item = Segment<'a>;
+
+    fn next(&mut self) -> Option<Segment<'a>> {
+        if self.pos >= self.text.len() {
+            return None;
+        }
+        let island = match self.next_island.take() {
+            Some(island) => Some(island),
+            None => self.find_island(self.pos),
+        };
+        let (kind, range) = match island {
+            Some(island) if island.start == self.pos => (SegmentKind::Html, island),
+            Some(island) => {
+                let range = self.pos..island.start;
+                self.next_island = Some(island);
+                (SegmentKind::Text, range)
+            }
+            None => (SegmentKind::Text, self.pos..self.text.len()),
+        };
+        self.pos = range.end;
+        Some(Segment {
+            kind,
+            text: &self.text[range.clone()],
+            span: self.lines.span(range),
+        })
+    }
+}
+
+impl Islands<'_> {
+    /// Finds the next island from the offset, joined with any which come straight
+    /// after it.
+    fn find_island(&self, from: usize) -> Option<Range<usize>> {
+        let bytes = self.text.as_bytes();
+        let mut i = from;
+        let start = loop {
+            match bytes.get(i)? {
+                b'<' => {
+                    if let Some(end) = self.island_at(i) {
+                        break i..end;
+                    }
+                    i += 1;
+                }
+                b'\\' if self.options.markdown => i += 2,
+                b'`' if self.options.markdown => i = code_span_end(self.text, i),
+                _ => i += 1,
+            }
+        };
+        let mut island = start;
+        while let Some(end) = self.island_at(island.end) {
+            island.end = end;
+        }
+        Some(island)
+    }
+
+    /// Gets the end of the island starting at the offset, if there is one.
+    fn island_at(&self, start: usize) -> Option<usize> {
+        let (markup, end) = markup_at(self.text, start)?;
+        let name = match markup {
+            Markup::StartTag {
+                name,
+                closed: false,
+            } if !self.options.single_tags && !VOID_TAGS.contains(&name.as_str()) => name,
+            _ => return Some(end),
+        };
+        // find the end tag which matches the start tag
+        let mut depth = 1;
+        let mut i = end;
+        while let Some(found) = self.text[i..].find('<') {
+            i += found;
+            match markup_at(self.text, i) {
+                Some((markup, markup_end)) => {
+                    match markup {
+                        Markup::StartTag {
+                            name: n,
+                            closed: false,
+                        } if n == name => depth += 1,
+                        Markup::EndTag { name: n } if n == name => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        return Some(markup_end);
+                    }
+                    i = markup_end;
+                }
+                None => i += 1,
+            }
+        }
+        Some(end)
+    }
+}
+
+/// Gets the end of the Markdown code span starting at the offset, or the end of
+/// its backticks if it is not closed.
+fn code_span_end(text: &str, start: usize) -> usize {
+    let ticks = text[start..].bytes().take_while(|b| *b == b'`').count();
+    let mut i = start + ticks;
+    while let Some(found) = text[i..].find('`') {
+        let run = text[i + found..].bytes().take_while(|b| *b == b'`').count();
+        if run == ticks {
+            return i + found + run;
+        }
+        i += found + run;
+    }
+    start + ticks
+}
+
+/// A piece of markup found by [`markup_at`], the names of tags are lowercase.
+enum Markup {
+    StartTag { name: String, closed: bool },
+    EndTag { name: String },
+    Other,
+}
+
+/// Reads the markup at the offset, returning it and where it ends.
+fn markup_at(text: &str, start: usize) -> Option<(Markup, usize)> {
+    let rest = &text[start..];
+    let bytes = rest.as_bytes();
+    if bytes.first() != Some(&b'<') {
+        return None;
+    }
+    let delimited = |open: &str, close: &str| -> Option<(Markup, usize)> {
+        let body = rest.strip_prefix(open)?;
+        let i = body.find(close)?;
+        Some((Markup::Other, start + open.len() + i + close.len()))
+    };
+    match bytes.get(1)? {
+        b'!' if rest.starts_with("<!-->") => Some((Markup::Other, start + 5)),
+        b'!' if rest.starts_with("<!--->") => Some((Markup::Other, start + 6)),
+        b'!' if rest.starts_with("<!--") => delimited("<!--", "-->"),
+        b'!' if rest.starts_with("<![CDATA[") => delimited("<![CDATA[", "]]>"),
+        b'!' if bytes.get(2).is_some_and(u8::is_ascii_alphabetic) => delimited("<!", ">"),
+        b'?' => delimited("<?", "?>"),
+        b'/' => {
+            let name_len = tag_name_len(&bytes[2..])?;
+            let mut i = 2 + name_len;
+            i += whitespace_len(&bytes[i..]);
+            if bytes.get(i) != Some(&b'>') {
+                return None;
+            }
+            let name = rest[2..2 + name_len].to_ascii_lowercase();
+            Some((Markup::EndTag { name }, start + i + 1))
+        }
+        _ => {
+            let name_len = tag_name_len(&bytes[1..])?;
+            let mut i = 1 + name_len;
+            loop {
+                let space = whitespace_len(&bytes[i..]);
+                match bytes.get(i + space)? {
+                    b'>' => {
+                        i += space + 1;
+                        break;
+                    }
+                    b'/' if bytes.get(i + space + 1) == Some(&b'>') => {
+                        let name = rest[1..1 + name_len].to_ascii_lowercase();
+                        return Some((
+                            Markup::StartTag { name, closed: true },
+                            start + i + space + 2,
+                        ));
+                    }
+                    _ if space > 0 => i += space + attribute_len(&bytes[i + space..])?,
+                    _ => return None,
+                }
+            }
+            let name = rest[1..1 + name_len].to_ascii_lowercase();
+            Some((
+                Markup::StartTag {
+                    name,
+                    closed: false,
+                },
+                start + i,
+            ))
+        }
+    }
+}
+
+/// Gets the length of the tag name at the start of the bytes: a letter followed
+/// by letters, digits and hyphens.
+fn tag_name_len(bytes: &[u8]) -> Option<usize> {
+    if !bytes.first()?.is_ascii_alphabetic() {
+        return None;
+    }
+    Some(
+        bytes
+            .iter()
+            .take_while(|b| b.is_ascii_alphanumeric() || **b == b'-')
+            .count(),
+    )
+}
+
+fn whitespace_len(bytes: &[u8]) -> usize {
+    bytes.iter().take_while(|b| b.is_ascii_whitespace()).count()
+}
+
+/// Gets the length of the attribute at the start of the bytes, with its value if
+/// it has one.
+fn attribute_len(bytes: &[u8]) -> Option<usize> {
+    let first = bytes.first()?;
+    if !(first.is_ascii_alphabetic() || *first == b'_' || *first == b':') {
+        return None;
+    }
+    let mut i = bytes
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b':' | b'-'))
+        .count();
+    let space = whitespace_len(&bytes[i..]);
+    if bytes.get(i + space) != Some(&b'=') {
+        return Some(i);
+    }
+    i += space + 1;
+    i += whitespace_len(&bytes[i..]);
+    match bytes.get(i)? {
+        quote @ (b'"' | b'\'') => {
+            let len = bytes[i + 1..].iter().position(|b| b == quote)?;
+            Some(i + len + 2)
+        }
+        _ => {
+            let len = bytes[i..]
+                .iter()
+                .take_while(|b| !b.is_ascii_whitespace() && !b"\"'=<>`".contains(b))
+                .count();
+            if len == 0 {
+                None
+            } else {
+                Some(i + len)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod islands_tests {
+    use super::*;
+    use crate::repair::RepairStrategy;
+
+    fn split<'a>(text: &'a str, options: &IslandOptions) -> Vec<(SegmentKind, &'a str)> {
+        islands_with(text, options)
+            .map(|s| (s.kind, s.text))
+            .collect()
+    }
+
+    #[test]
+    fn markup_test() {
+        use SegmentKind::{Html, Text};
+        let text = "a <br/><!-- c --> b <?php x ?> <![CDATA[ <p> ]]><!DOCTYPE html>\
+            <https://example.com> <a href=x title='y'> 1 < 2 <p =x> </a >";
+        assert_eq!(
+            split(text, &IslandOptions::new()),
+            vec![
+                (Text, "a "),
+                (Html, "<br/><!-- c -->"),
+                (Text, " b "),
+                (Html, "<?php x ?>"),
+                (Text, " "),
+                (Html, "<![CDATA[ <p> ]]><!DOCTYPE html>"),
+                (Text, "<https://example.com> "),
+                (Html, "<a href=x title='y'> 1 < 2 <p =x> </a >"),
+            ]
+        );
+        let segments: Vec<Segment> = islands("x\n  <i>y</i>").collect();
+        assert_eq!(
+            segments[1].span,
+            Span {
+                start: 4,
+                end: 12,
+                line: 2,
+                col: 3
+            }
+        );
+    }
+
+    #[test]
+    fn nesting_test() {
+        use SegmentKind::{Html, Text};
+        let text = "<div><div>a</div></DIV>b</div> <b>c <i>d</i> e";
+        assert_eq!(
+            split(text, &IslandOptions::new()),
+            vec![
+                (Html, "<div><div>a</div></DIV>"),
+                (Text, "b"),
+                (Html, "</div>"),
+                (Text, " "),
+                (Html, "<b>"),
+                (Text, "c "),
+                (Html, "<i>d</i>"),
+                (Text, " e"),
+            ]
+        );
+        assert_eq!(
+            split(text, &IslandOptions::new().single_tags(true))[..4],
+            [
+                (Html, "<div><div>"),
+                (Text, "a"),
+                (Html, "</div></DIV>"),
+                (Text, "b"),
+            ]
+        );
+        // a lone start tag needs the parser to close it
+        let segments: Vec<Segment> = islands("<b>c <i>d</i>").collect();
+        assert!(segments[0].parse(&ParseOptions::new()).is_err());
+        let stray = islands("</b>").next().unwrap();
+        let options = ParseOptions::new().repair(RepairStrategy::Spec);
+        assert!(stray.parse(&options).unwrap().nodes.is_empty());
+        assert_eq!(
+            segments[1].parse(&options).unwrap().nodes,
+            vec![HtmlNode::Text("c ".to_owned())]
+        );
+    }
+
+    #[test]
+    fn markdown_test() {
+        use SegmentKind::{Html, Text};
+        let text = "`<b>` and ``a ` <i>`` \\<em>x</em> ```<u>";
+        assert_eq!(
+            split(text, &IslandOptions::new().markdown(true)),
+            vec![
+                (Text, "`<b>` and ``a ` <i>`` \\<em>x"),
+                (Html, "</em>"),
+                (Text, " ```"),
+                (Html, "<u>"),
+            ]
+        );
+        assert_eq!(split(text, &IslandOptions::new())[1], (Html, "<b>"));
+    }
+}
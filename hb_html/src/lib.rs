@@ -21,8 +21,72 @@
 //! query.find_with_tag("div").find_with_tag("p");
 //! ```
 
+#[cfg(feature = "arena")]
+pub mod arena;
+pub mod aria;
+pub mod attributes;
+pub mod bidi;
+pub mod boilerplate;
+#[cfg(feature = "serde")]
+pub mod config;
+#[cfg(feature = "serde")]
+pub mod conformance;
+pub mod content_model;
+pub mod csp;
+pub mod dataset;
+pub mod diff;
+pub mod dom;
+pub mod entities;
 pub mod error;
+pub mod extract;
+pub mod fingerprint;
+#[cfg(feature = "fixtures")]
+pub mod fixture;
+pub mod frames;
+mod hash;
+pub mod highlight;
+pub mod hints;
+pub mod history;
+pub mod i18n;
+pub mod incremental;
+pub mod islands;
+pub mod live;
+pub mod lsp;
+pub mod media;
+mod memfind;
+pub mod metrics;
+pub mod namespace;
+pub mod node_tree;
+pub mod normalize;
 pub mod objects;
+pub mod optimize;
+pub mod options;
+pub mod overlay;
 mod parsing;
 mod parsing_new;
+pub mod partial;
+pub mod pipeline;
 pub mod querying;
+pub mod repair;
+pub mod resources;
+pub mod rewrite;
+pub mod safe_html;
+pub mod sanitize;
+#[cfg(feature = "aho-corasick")]
+pub mod scan;
+pub mod search;
+pub mod serialize;
+pub mod shortcode;
+pub mod small_string;
+pub mod source_map;
+pub mod srcset;
+#[cfg(feature = "sri")]
+pub mod sri;
+pub mod stable_id;
+pub mod stream;
+pub mod structure;
+pub mod style;
+pub mod testgen;
+mod tree_construction;
+pub mod visit;
+pub mod workspace;
@@ -0,0 +1,269 @@
+//! Keeping the matches of a set of selectors up to date as a document is changed,
+//! for editors and live previews which can not afford to search the whole tree
+//! after every change.
+//!
+//! [`LiveSelectors`] holds parsed selectors along with the paths of the elements
+//! each one matches. After the document is changed, [`LiveSelectors::update`] is
+//! given the paths of the nodes which changed and only matches the elements which
+//! the change could affect again: the siblings of each changed node and all of
+//! their descendants. This covers the combinators and refiners such as
+//! `:last-child` which look at the siblings and ancestors of an element.
+//!
+//! When nodes are inserted or removed, the later siblings move to new paths, so
+//! the parent of the nodes is the node which changed.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::live::LiveSelectors;
+//! use hb_html::objects::{HtmlDocument, HtmlNode, HtmlTag};
+//! let mut doc = r#"<ul><li class="done">a</li><li>b</li></ul>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let mut live = LiveSelectors::new();
+//! let done = live.add_str("li.done", &doc).unwrap();
+//! let last = live.add_str("li:last-child", &doc).unwrap();
+//! assert_eq!(live.matches(done).len(), 1);
+//! // add a third item to the list
+//! if let Some(HtmlNode::Tag(ul)) = doc.node_at_mut(&[0]) {
+//!     let li = r#"<li class="done">c</li>"#.parse::<HtmlTag>().unwrap();
+//!     ul.contents.push(HtmlNode::Tag(li));
+//! }
+//! let changes = live.update(&doc, &[vec![0]]);
+//! assert_eq!(changes.len(), 2);
+//! assert_eq!(changes[0].selector, done);
+//! assert_eq!(changes[0].added, vec![vec![0, 2]]);
+//! assert_eq!(changes[1].selector, last);
+//! assert_eq!(changes[1].removed, vec![vec![0, 1]]);
+//! assert_eq!(changes[1].added, vec![vec![0, 2]]);
+//! ```
+use crate::error::ParseHtmlError;
+use crate::objects::{CssSelector, HtmlDocument, HtmlNode, NodePath};
+use crate::querying::HtmlQueryResult;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/// How the matches of one of the selectors changed after an update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorChange {
+    /// The id of the selector, as returned by [`LiveSelectors::add`].
+    pub selector: usize,
+    /// The elements which match now but did not before, in document order.
+    pub added: Vec<NodePath>,
+    /// The elements which matched before but do not now, in document order.
+    pub removed: Vec<NodePath>,
+}
+
+/// A set of selectors and the elements they match in a document.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiveSelectors {
+    selectors: Vec<CssSelector>,
+    matches: Vec<BTreeSet<NodePath>>,
+}
+
+/// Calls the function with a query result for each element in the nodes and
+/// their descendants, in document order.
+fn walk_elements<'a, F: FnMut(&HtmlQueryResult<'a>)>(
+    nodes: &'a Vec<HtmlNode>,
+    result: &mut HtmlQueryResult<'a>,
+    f: &mut F,
+) {
+    for (i, node) in nodes.iter().enumerate() {
+        if let HtmlNode::Tag(tag) = node {
+            result.path.push((nodes, i));
+            f(result);
+            walk_elements(&tag.contents, result, f);
+            result.path.pop();
+        }
+    }
+}
+
+/// Gets a query result pointing at the element at the path, along with the list
+/// of its children, or the top level of the document for an empty path.
+fn element_at<'a>(
+    doc: &'a HtmlDocument,
+    path: &[usize],
+) -> Option<(HtmlQueryResult<'a>, &'a Vec<HtmlNode>)> {
+    let mut result = HtmlQueryResult { path: vec![] };
+    let mut nodes = &doc.nodes;
+    for i in path {
+        match nodes.get(*i) {
+            Some(HtmlNode::Tag(tag)) => {
+                result.path.push((nodes, *i));
+                nodes = &tag.contents;
+            }
+            _ => return None,
+        }
+    }
+    Some((result, nodes))
+}
+
+/// Finds the matches of the selector among the descendants of the element at the
+/// path (or the whole document for an empty path).
+fn matches_under(doc: &HtmlDocument, selector: &CssSelector, path: &[usize]) -> Vec<NodePath> {
+    let mut found = vec![];
+    if let Some((mut result, children)) = element_at(doc, path) {
+        walk_elements(children, &mut result, &mut |r| {
+            if r.matches(selector) {
+                found.push(r.node_path());
+            }
+        });
+    }
+    found
+}
+
+impl LiveSelectors {
+    pub fn new() -> LiveSelectors {
+        LiveSelectors::default()
+    }
+
+    /// Adds a selector, finding its matches in the document. Returns the id of the
+    /// selector, which is used to get its matches.
+    pub fn add(&mut self, selector: CssSelector, doc: &HtmlDocument) -> usize {
+        let matches = matches_under(doc, &selector, &[]).into_iter().collect();
+        self.selectors.push(selector);
+        self.matches.push(matches);
+        self.selectors.len() - 1
+    }
+
+    /// Parses and adds a selector, see [`LiveSelectors::add`].
+    pub fn add_str(&mut self, selector: &str, doc: &HtmlDocument) -> Result<usize, ParseHtmlError> {
+        Ok(self.add(CssSelector::from_str(selector)?, doc))
+    }
+
+    pub fn selector(&self, id: usize) -> Option<&CssSelector> {
+        self.selectors.get(id)
+    }
+
+    /// Gets the paths of the elements the selector matches, in document order.
+    /// There are no matches for an id which was not returned by add.
+    pub fn matches(&self, id: usize) -> Vec<NodePath> {
+        self.matches
+            .get(id)
+            .map(|m| m.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Finds the matches again in the parts of the document which the changes to
+    /// the nodes at the paths could affect, returning the selectors whose matches
+    /// changed, in the order they were added.
+    pub fn update(&mut self, doc: &HtmlDocument, changed: &[NodePath]) -> Vec<SelectorChange> {
+        // the siblings of a changed node and their descendants are the descendants
+        // of its parent, and a region inside another region is already covered
+        let mut regions: Vec<NodePath> = changed
+            .iter()
+            .map(|p| p[..p.len().saturating_sub(1)].to_vec())
+            .collect();
+        regions.sort();
+        regions.dedup();
+        let regions: Vec<NodePath> = regions
+            .iter()
+            .filter(|r| {
+                !regions
+                    .iter()
+                    .any(|o| o.len() < r.len() && r.starts_with(o))
+            })
+            .cloned()
+            .collect();
+        let mut changes = vec![];
+        for (id, selector) in self.selectors.iter().enumerate() {
+            let matches = &mut self.matches[id];
+            let old: BTreeSet<NodePath> = matches
+                .iter()
+                .filter(|m| {
+                    regions
+                        .iter()
+                        .any(|r| m.len() > r.len() && m.starts_with(r))
+                })
+                .cloned()
+                .collect();
+            let new: BTreeSet<NodePath> = regions
+                .iter()
+                .flat_map(|r| matches_under(doc, selector, r))
+                .collect();
+            if old == new {
+                continue;
+            }
+            for path in &old {
+                matches.remove(path);
+            }
+            matches.extend(new.iter().cloned());
+            changes.push(SelectorChange {
+                selector: id,
+                added: new.difference(&old).cloned().collect(),
+                removed: old.difference(&new).cloned().collect(),
+            });
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod live_tests {
+    use super::*;
+    use crate::querying::HtmlQueryable;
+
+    /// Checks the live matches are the same as searching the whole document.
+    fn assert_matches_full_search(live: &LiveSelectors, doc: &HtmlDocument) {
+        for id in 0..live.selectors.len() {
+            let mut query = doc.nodes.query();
+            query.find(live.selector(id).unwrap());
+            let mut expected: Vec<NodePath> = query.results.iter().map(|r| r.node_path()).collect();
+            expected.sort();
+            assert_eq!(live.matches(id), expected);
+        }
+    }
+
+    #[test]
+    fn update_test() {
+        let mut doc = concat!(
+            r#"<main><div id="a"><p>one</p><p class="x">two</p></div>"#,
+            r#"<div id="b"><span>three</span></div></main>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let mut live = LiveSelectors::new();
+        for selector in ["div p.x", "p:last-child", "div > span", ".x ~ p", "main"] {
+            live.add_str(selector, &doc).unwrap();
+        }
+        assert_matches_full_search(&live, &doc);
+        // change the class of the first paragraph
+        if let Some(HtmlNode::Tag(p)) = doc.node_at_mut(&[0, 0, 0]) {
+            p.classes = vec!["x".to_owned()];
+        }
+        if let Some(HtmlNode::Tag(p)) = doc.node_at_mut(&[0, 0, 1]) {
+            p.classes.clear();
+        }
+        let changes = live.update(&doc, &[vec![0, 0, 0], vec![0, 0, 1]]);
+        assert_eq!(
+            changes,
+            vec![
+                SelectorChange {
+                    selector: 0,
+                    added: vec![vec![0, 0, 0]],
+                    removed: vec![vec![0, 0, 1]],
+                },
+                SelectorChange {
+                    selector: 3,
+                    added: vec![vec![0, 0, 1]],
+                    removed: vec![],
+                },
+            ]
+        );
+        assert_matches_full_search(&live, &doc);
+        // move the span out of the second div
+        let span = match doc.node_at_mut(&[0, 1]) {
+            Some(HtmlNode::Tag(div)) => div.contents.remove(0),
+            _ => panic!("expected the div"),
+        };
+        if let Some(HtmlNode::Tag(main)) = doc.node_at_mut(&[0]) {
+            main.contents.push(span);
+        }
+        let changes = live.update(&doc, &[vec![0]]);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].removed, vec![vec![0, 1, 0]]);
+        assert!(changes[0].added.is_empty());
+        assert_matches_full_search(&live, &doc);
+        assert!(live.update(&doc, &[vec![0, 2]]).is_empty());
+    }
+}
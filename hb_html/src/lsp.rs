@@ -0,0 +1,627 @@
+//! Helpers for using the crate as the backend of a language server.
+//!
+//! The crate works with byte offsets into the source, but the Language Server
+//! Protocol uses line and column positions where the column is counted in UTF-16
+//! code units. A [`LineIndex`] is built once for the source of a document and
+//! converts between byte offsets, char offsets and [`Position`]s.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::lsp::{LineIndex, Position};
+//! let index = LineIndex::new("<p>\n  😀 <b>x</b>\n</p>");
+//! // the emoji is 4 bytes in UTF-8 but 2 code units in UTF-16
+//! assert_eq!(index.position(11), Some(Position::new(1, 5)));
+//! assert_eq!(index.offset(Position::new(1, 5)), Some(11));
+//! assert_eq!(index.char_offset(11), Some(8));
+//! ```
+//!
+//! An [`LspDocument`] keeps a parsed document with its source, its [`SourceMap`]
+//! and a [`LineIndex`], and provides the folding ranges and symbols an editor
+//! needs to show an outline of the document.
+//!
+//! ```
+//! use hb_html::lsp::{FoldingRangeKind, LspDocument, SymbolKind};
+//! let doc = LspDocument::new("<form id=login>\n  <h2>Sign in</h2>\n</form>").unwrap();
+//! let folds = doc.folding_ranges();
+//! assert_eq!((folds[0].start_line, folds[0].end_line), (0, 1));
+//! assert_eq!(folds[0].kind, FoldingRangeKind::Region);
+//! let symbols = doc.symbols();
+//! assert_eq!(symbols[0].name, "#login");
+//! assert_eq!(symbols[0].kind, SymbolKind::Form);
+//! assert_eq!(symbols[1].name, "Sign in");
+//! // inside the start tag of the form, so its attributes can be completed
+//! assert!(doc.completions_at(6).attributes.contains(&"action"));
+//! // in the content of the heading, which can only contain phrasing content
+//! assert!(doc.completions_at(24).tags.contains(&"em"));
+//! ```
+use crate::content_model::{allowed_children, attributes};
+use crate::error::ParseHtmlError;
+use crate::objects::{HtmlDocument, HtmlNode, NodePath};
+use crate::source_map::{NodeSpan, SourceMap};
+use std::ops::Range;
+
+/// A position in a document as a zero based line and the number of UTF-16 code
+/// units from the start of the line, which is how the Language Server Protocol
+/// describes positions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, character: usize) -> Position {
+        Position { line, character }
+    }
+}
+
+/// A range between two [`Position`]s, the end is exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PositionRange {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// A char in a line which is not a single byte in UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WideChar {
+    /// The byte offset of the char from the start of the line.
+    col: usize,
+    len_utf8: usize,
+    len_utf16: usize,
+}
+
+/// Converts between byte offsets, char offsets and line/UTF-16 column positions
+/// in a piece of text. Lines can end with `\n`, `\r\n` or `\r`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    len: usize,
+    /// The byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    /// The byte offset of the end of each line, before the line ending.
+    line_ends: Vec<usize>,
+    /// The number of chars before the start of each line.
+    line_start_chars: Vec<usize>,
+    /// The chars in each line which are more than one byte.
+    wide_chars: Vec<Vec<WideChar>>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        let mut index = LineIndex {
+            len: text.len(),
+            line_starts: vec![0],
+            line_ends: vec![],
+            line_start_chars: vec![0],
+            wide_chars: vec![vec![]],
+        };
+        let mut chars = text.char_indices().peekable();
+        let mut char_count = 0;
+        let mut line_start = 0;
+        while let Some((i, c)) = chars.next() {
+            char_count += 1;
+            if c == '\r' && matches!(chars.peek(), Some((_, '\n'))) {
+                index.line_ends.push(i);
+                chars.next();
+                char_count += 1;
+                line_start = i + 2;
+            } else if c == '\n' || c == '\r' {
+                index.line_ends.push(i);
+                line_start = i + 1;
+            } else {
+                if !c.is_ascii() {
+                    if let Some(wide) = index.wide_chars.last_mut() {
+                        wide.push(WideChar {
+                            col: i - line_start,
+                            len_utf8: c.len_utf8(),
+                            len_utf16: c.len_utf16(),
+                        });
+                    }
+                }
+                continue;
+            }
+            index.line_starts.push(line_start);
+            index.line_start_chars.push(char_count);
+            index.wide_chars.push(vec![]);
+        }
+        index.line_ends.push(text.len());
+        index
+    }
+
+    /// The length of the text in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Gets the byte range of the line, not including the line ending.
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        Some(*self.line_starts.get(line)?..self.line_ends[line])
+    }
+
+    /// Gets the line containing the byte offset and the byte offset from the start
+    /// of the line, or None if the offset is past the end of the text or is inside
+    /// a char.
+    fn line_and_col(&self, offset: usize) -> Option<(usize, usize)> {
+        if offset > self.len {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|s| *s <= offset) - 1;
+        let col = offset - self.line_starts[line];
+        let splits_char = self.wide_chars[line]
+            .iter()
+            .any(|w| w.col < col && col < w.col + w.len_utf8);
+        if splits_char {
+            None
+        } else {
+            Some((line, col))
+        }
+    }
+
+    /// Converts a byte offset to a line and UTF-16 column.
+    pub fn position(&self, offset: usize) -> Option<Position> {
+        let (line, col) = self.line_and_col(offset)?;
+        let extra: usize = self.wide_chars[line]
+            .iter()
+            .filter(|w| w.col < col)
+            .map(|w| w.len_utf8 - w.len_utf16)
+            .sum();
+        Some(Position::new(line, col - extra))
+    }
+
+    /// Converts a byte range to a [`PositionRange`].
+    pub fn range(&self, range: Range<usize>) -> Option<PositionRange> {
+        Some(PositionRange {
+            start: self.position(range.start)?,
+            end: self.position(range.end)?,
+        })
+    }
+
+    /// Converts a line and UTF-16 column to a byte offset. As in the Language Server
+    /// Protocol, a column past the end of the line is the end of the line. Returns
+    /// None if the line does not exist or the column is inside a char.
+    pub fn offset(&self, position: Position) -> Option<usize> {
+        let line_start = *self.line_starts.get(position.line)?;
+        let mut extra = 0;
+        for w in &self.wide_chars[position.line] {
+            let w_start = w.col - extra;
+            if position.character <= w_start {
+                break;
+            }
+            if position.character < w_start + w.len_utf16 {
+                return None;
+            }
+            extra += w.len_utf8 - w.len_utf16;
+        }
+        Some((line_start + position.character + extra).min(self.line_ends[position.line]))
+    }
+
+    /// Converts a byte offset to the number of chars before it.
+    pub fn char_offset(&self, offset: usize) -> Option<usize> {
+        let (line, col) = self.line_and_col(offset)?;
+        let extra: usize = self.wide_chars[line]
+            .iter()
+            .filter(|w| w.col < col)
+            .map(|w| w.len_utf8 - 1)
+            .sum();
+        Some(self.line_start_chars[line] + col - extra)
+    }
+
+    /// Converts a number of chars from the start of the text to a byte offset.
+    pub fn offset_of_char(&self, char_offset: usize) -> Option<usize> {
+        let line = self.line_start_chars.partition_point(|s| *s <= char_offset) - 1;
+        let mut col = char_offset - self.line_start_chars[line];
+        for w in &self.wide_chars[line] {
+            if col <= w.col {
+                break;
+            }
+            col += w.len_utf8 - 1;
+        }
+        let offset = self.line_starts[line] + col;
+        let next_line = self.line_starts.get(line + 1).copied();
+        if offset > next_line.unwrap_or(self.len) {
+            return None;
+        }
+        Some(offset)
+    }
+}
+
+/// What a [`FoldingRange`] folds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FoldingRangeKind {
+    /// The content of an element.
+    Region,
+    Comment,
+}
+
+/// A range of lines which an editor can fold. The end line is the last line which
+/// is hidden, so the end tag of a folded element stays visible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FoldingRange {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub kind: FoldingRangeKind,
+}
+
+/// The kind of element a [`DocumentSymbol`] is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    /// A `<h1>` to `<h6>` element, named by its text.
+    Heading,
+    /// A `<form>` element, named by its id or name.
+    Form,
+    /// Any other element with an id, named by the id.
+    Id,
+}
+
+/// An element to show in the outline of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub path: NodePath,
+    /// The range of the whole element.
+    pub range: PositionRange,
+    /// The range of the start tag of the element.
+    pub selection_range: PositionRange,
+}
+
+/// What can be written at a position in a document, from [`LspDocument::completions_at`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Completions {
+    /// The path of the element the position is in, or None at the top of the document.
+    pub element: Option<NodePath>,
+    /// The elements which can be added at the position.
+    pub tags: Vec<&'static str>,
+    /// The attributes which can be added, if the position is in a start tag.
+    pub attributes: Vec<&'static str>,
+}
+
+/// A parsed document with its source, for answering requests from an editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LspDocument {
+    source: String,
+    doc: HtmlDocument,
+    source_map: SourceMap,
+    line_index: LineIndex,
+}
+
+impl LspDocument {
+    pub fn new<S: Into<String>>(source: S) -> Result<LspDocument, ParseHtmlError> {
+        let source = source.into();
+        let (doc, source_map) = HtmlDocument::parse_with_source_map(&source)?;
+        let line_index = LineIndex::new(&source);
+        Ok(LspDocument {
+            source,
+            doc,
+            source_map,
+            line_index,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn document(&self) -> &HtmlDocument {
+        &self.doc
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.line_index
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_index.position(offset).map_or(0, |p| p.line)
+    }
+
+    /// Gets the ranges of lines which can be folded, which are the content of
+    /// elements and comments which cover more than one line, in document order.
+    pub fn folding_ranges(&self) -> Vec<FoldingRange> {
+        let mut ranges = vec![];
+        for span in &self.source_map {
+            let (end, kind) = match (self.doc.node_at(&span.path), &span.content) {
+                // the end tag is left visible
+                (Some(HtmlNode::Tag(_)), Some(content)) => (
+                    self.line_of(content.end).saturating_sub(1),
+                    FoldingRangeKind::Region,
+                ),
+                (Some(HtmlNode::Comment(_)), _) => {
+                    (self.line_of(span.range.end), FoldingRangeKind::Comment)
+                }
+                _ => continue,
+            };
+            let start_line = self.line_of(span.range.start);
+            if end > start_line {
+                ranges.push(FoldingRange {
+                    start_line,
+                    end_line: end,
+                    kind,
+                });
+            }
+        }
+        ranges
+    }
+
+    /// Gets the headings, forms and elements with ids in the document, in document
+    /// order. An element is only listed once, as a heading before a form before an id.
+    pub fn symbols(&self) -> Vec<DocumentSymbol> {
+        let mut symbols = vec![];
+        for span in &self.source_map {
+            let tag = match self.doc.node_at(&span.path) {
+                Some(HtmlNode::Tag(t)) => t,
+                _ => continue,
+            };
+            let id = tag.ids.first().map(|id| format!("#{}", id));
+            let (name, kind) = match tag.tag.as_str() {
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    let text = tag.text().split_whitespace().collect::<Vec<_>>().join(" ");
+                    (text, SymbolKind::Heading)
+                }
+                "form" => {
+                    let name = id
+                        .or_else(|| tag.attributes.get("name").cloned())
+                        .unwrap_or_else(|| "form".to_owned());
+                    (name, SymbolKind::Form)
+                }
+                _ => match id {
+                    Some(id) => (id, SymbolKind::Id),
+                    None => continue,
+                },
+            };
+            if let Some(symbol) = self.symbol(name, kind, span) {
+                symbols.push(symbol);
+            }
+        }
+        symbols
+    }
+
+    /// Gets the elements and attributes which can be written at the byte offset,
+    /// using the tables in [`crate::content_model`]. In the start tag of an element
+    /// the attributes it supports are returned, and in the content of an element
+    /// the elements it can contain. Nothing can be written in an end tag, a comment
+    /// or the text of elements like `<script>`.
+    pub fn completions_at(&self, offset: usize) -> Completions {
+        let span = self
+            .source_map
+            .iter()
+            .filter(|s| s.range.start < offset && offset < s.range.end)
+            .filter(|s| matches!(self.doc.node_at(&s.path), Some(HtmlNode::Tag(_))))
+            .max_by_key(|s| s.path.len());
+        let span = match span {
+            Some(span) => span,
+            None => {
+                let in_comment = self
+                    .source_map
+                    .iter()
+                    .any(|s| s.range.start < offset && offset < s.range.end);
+                return Completions {
+                    tags: if in_comment {
+                        vec![]
+                    } else {
+                        allowed_children(&[])
+                    },
+                    ..Completions::default()
+                };
+            }
+        };
+        let ancestors: Vec<&str> = (1..=span.path.len())
+            .filter_map(|len| match self.doc.node_at(&span.path[..len]) {
+                Some(HtmlNode::Tag(t)) => Some(t.tag.as_str()),
+                _ => None,
+            })
+            .collect();
+        let tag = ancestors.last().copied().unwrap_or_default();
+        let mut completions = Completions {
+            element: Some(span.path.clone()),
+            ..Completions::default()
+        };
+        match &span.content {
+            Some(content) if offset < content.start => completions.attributes = attributes(tag),
+            None => completions.attributes = attributes(tag),
+            Some(content) if offset <= content.end => {
+                // a comment inside the element
+                let in_child = self.source_map.iter().any(|s| {
+                    s.path.len() == span.path.len() + 1
+                        && s.path.starts_with(&span.path)
+                        && s.range.start < offset
+                        && offset < s.range.end
+                        && matches!(self.doc.node_at(&s.path), Some(HtmlNode::Comment(_)))
+                });
+                if !in_child {
+                    completions.tags = allowed_children(&ancestors);
+                }
+            }
+            // in the end tag
+            Some(_) => {}
+        }
+        completions
+    }
+
+    fn symbol(&self, name: String, kind: SymbolKind, span: &NodeSpan) -> Option<DocumentSymbol> {
+        let start_tag_end = span.content.as_ref().map_or(span.range.end, |c| c.start);
+        Some(DocumentSymbol {
+            name,
+            kind,
+            path: span.path.clone(),
+            range: self.line_index.range(span.range.clone())?,
+            selection_range: self.line_index.range(span.range.start..start_tag_end)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod lsp_tests {
+    use super::*;
+
+    #[test]
+    fn line_index_line_endings_test() {
+        let index = LineIndex::new("a\nbc\r\nd\re");
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_range(1), Some(2..4));
+        assert_eq!(index.line_range(3), Some(8..9));
+        assert_eq!(index.line_range(4), None);
+        assert_eq!(index.position(0), Some(Position::new(0, 0)));
+        assert_eq!(index.position(1), Some(Position::new(0, 1)));
+        assert_eq!(index.position(2), Some(Position::new(1, 0)));
+        assert_eq!(index.position(6), Some(Position::new(2, 0)));
+        assert_eq!(index.position(9), Some(Position::new(3, 1)));
+        assert_eq!(index.position(10), None);
+        // columns past the end of the line are clamped
+        assert_eq!(index.offset(Position::new(1, 10)), Some(4));
+        assert_eq!(index.offset(Position::new(5, 0)), None);
+        assert_eq!(index.char_offset(6), Some(6));
+        assert_eq!(index.offset_of_char(6), Some(6));
+        assert!(LineIndex::new("").is_empty());
+        assert_eq!(LineIndex::new("").position(0), Some(Position::new(0, 0)));
+    }
+
+    #[test]
+    fn line_index_wide_chars_test() {
+        // é is 2 bytes and 1 UTF-16 unit, 😀 is 4 bytes and 2 UTF-16 units
+        let text = "é😀x\n😀é";
+        let index = LineIndex::new(text);
+        for (offset, position, chars) in [
+            (0, Position::new(0, 0), 0),
+            (2, Position::new(0, 1), 1),
+            (6, Position::new(0, 3), 2),
+            (7, Position::new(0, 4), 3),
+            (8, Position::new(1, 0), 4),
+            (12, Position::new(1, 2), 5),
+            (14, Position::new(1, 3), 6),
+        ] {
+            assert_eq!(index.position(offset), Some(position), "offset {}", offset);
+            assert_eq!(index.offset(position), Some(offset), "{:?}", position);
+            assert_eq!(index.char_offset(offset), Some(chars), "offset {}", offset);
+            assert_eq!(index.offset_of_char(chars), Some(offset), "chars {}", chars);
+        }
+        // inside a char
+        assert_eq!(index.position(1), None);
+        assert_eq!(index.position(4), None);
+        assert_eq!(index.offset(Position::new(0, 2)), None);
+        assert_eq!(index.char_offset(9), None);
+        assert_eq!(index.offset_of_char(7), None);
+        assert_eq!(
+            index.range(2..12),
+            Some(PositionRange {
+                start: Position::new(0, 1),
+                end: Position::new(1, 2)
+            })
+        );
+    }
+
+    #[test]
+    fn folding_ranges_test() {
+        let source = "<html>\n<body>\n<!-- a\ncomment -->\n<p>one line</p>\n<ul>\n  <li>a</li>\n</ul></body>\n</html>";
+        let doc = LspDocument::new(source).unwrap();
+        let folds: Vec<(usize, usize, FoldingRangeKind)> = doc
+            .folding_ranges()
+            .iter()
+            .map(|f| (f.start_line, f.end_line, f.kind))
+            .collect();
+        assert_eq!(
+            folds,
+            vec![
+                (0, 7, FoldingRangeKind::Region),
+                (1, 6, FoldingRangeKind::Region),
+                (2, 3, FoldingRangeKind::Comment),
+                (5, 6, FoldingRangeKind::Region),
+            ]
+        );
+    }
+
+    #[test]
+    fn symbols_test() {
+        let source = "<div id=top class=x>\n<h1> Main\n  <b>title</b></h1>\n<form name=search><input id=q></form>\n<form></form></div>";
+        let doc = LspDocument::new(source).unwrap();
+        let symbols = doc.symbols();
+        let found: Vec<(&str, SymbolKind)> =
+            symbols.iter().map(|s| (s.name.as_str(), s.kind)).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("#top", SymbolKind::Id),
+                ("Main title", SymbolKind::Heading),
+                ("search", SymbolKind::Form),
+                ("#q", SymbolKind::Id),
+                ("form", SymbolKind::Form),
+            ]
+        );
+        assert_eq!(symbols[1].path, vec![0, 1]);
+        assert_eq!(
+            symbols[1].range,
+            PositionRange {
+                start: Position::new(1, 0),
+                end: Position::new(2, 19)
+            }
+        );
+        assert_eq!(
+            symbols[0].selection_range,
+            PositionRange {
+                start: Position::new(0, 0),
+                end: Position::new(0, 20)
+            }
+        );
+        assert!(LspDocument::new("<p>").is_err());
+    }
+
+    #[test]
+    fn completions_at_test() {
+        let source =
+            "<html><head></head><body><ul class=a>\n<li>x</li><!-- c --></ul><br></body></html>";
+        let doc = LspDocument::new(source).unwrap();
+        let offset = |s: &str| source.find(s).unwrap();
+        // in the content of the list, between the items and at the end
+        let ul = doc.completions_at(offset("\n<li>"));
+        assert_eq!(ul.element, Some(vec![0, 1, 0]));
+        assert_eq!(ul.tags, vec!["li", "script", "template"]);
+        assert!(ul.attributes.is_empty());
+        assert_eq!(doc.completions_at(offset("</ul>")).tags, ul.tags);
+        // in the text of a list item
+        let li = doc.completions_at(offset("x</li>"));
+        assert_eq!(li.element, Some(vec![0, 1, 0, 1]));
+        assert!(li.tags.contains(&"p"));
+        // in the start tags
+        let start = doc.completions_at(offset("class=a"));
+        assert!(start.tags.is_empty());
+        assert!(start.attributes.contains(&"class"));
+        assert!(doc.completions_at(offset("br>")).attributes.contains(&"id"));
+        // the head and body
+        let head = doc.completions_at(offset("</head>"));
+        assert!(head.tags.contains(&"title") && !head.tags.contains(&"p"));
+        assert_eq!(
+            doc.completions_at(offset("<head>")).tags,
+            vec!["head", "body"]
+        );
+        // nothing in comments or end tags
+        assert_eq!(
+            doc.completions_at(offset(" c ")),
+            Completions {
+                element: Some(vec![0, 1, 0]),
+                ..Completions::default()
+            }
+        );
+        assert_eq!(
+            doc.completions_at(offset("ul><br>") + 1),
+            Completions {
+                element: Some(vec![0, 1, 0]),
+                ..Completions::default()
+            }
+        );
+        // the top of the document
+        assert_eq!(doc.completions_at(0).element, None);
+        assert_eq!(doc.completions_at(0).tags[0], "html");
+    }
+}
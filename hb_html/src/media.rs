@@ -0,0 +1,832 @@
+//! Parsing and evaluation of media queries, as used in the `media` attribute and
+//! the conditions in the `sizes` attribute.
+//!
+//! Only the features which depend on the size and pixel density of the viewport are
+//! supported, any other feature is parsed but never matches.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::media::{MediaCondition, MediaQueryList, Viewport};
+//! let condition = "(max-width: 600px) and (orientation: portrait)"
+//!     .parse::<MediaCondition>()
+//!     .unwrap();
+//! assert!(condition.evaluate(&Viewport::new(400.0, 800.0)));
+//! assert!(!condition.evaluate(&Viewport::new(800.0, 400.0)));
+//!
+//! let queries = "print, screen and (400px <= width < 40em)"
+//!     .parse::<MediaQueryList>()
+//!     .unwrap();
+//! assert!(queries.evaluate(&Viewport::new(500.0, 500.0)));
+//! ```
+use crate::error::ParseHtmlError;
+use std::str::FromStr;
+
+/// The properties of the display which media queries are evaluated against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    /// Width in CSS pixels.
+    pub width: f64,
+    /// Height in CSS pixels.
+    pub height: f64,
+    /// The number of device pixels per CSS pixel.
+    pub device_pixel_ratio: f64,
+    /// The size of 1em/1rem in CSS pixels.
+    pub font_size: f64,
+    /// The media type, eg screen or print.
+    pub media_type: String,
+}
+
+impl Viewport {
+    /// Creates a screen viewport with a pixel ratio of 1 and a 16px font size.
+    pub fn new(width: f64, height: f64) -> Viewport {
+        Viewport {
+            width,
+            height,
+            device_pixel_ratio: 1.0,
+            font_size: 16.0,
+            media_type: "screen".to_owned(),
+        }
+    }
+
+    pub fn device_pixel_ratio(mut self, ratio: f64) -> Viewport {
+        self.device_pixel_ratio = ratio;
+        self
+    }
+
+    pub fn font_size(mut self, font_size: f64) -> Viewport {
+        self.font_size = font_size;
+        self
+    }
+
+    pub fn media_type<S: Into<String>>(mut self, media_type: S) -> Viewport {
+        self.media_type = media_type.into();
+        self
+    }
+}
+
+/// The units a [`Length`] can be in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    Px,
+    Em,
+    Rem,
+    Vw,
+    Vh,
+    Vmin,
+    Vmax,
+}
+
+/// A CSS length such as `600px` or `30em`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    pub value: f64,
+    pub unit: LengthUnit,
+}
+
+impl Length {
+    pub fn new(value: f64, unit: LengthUnit) -> Length {
+        Length { value, unit }
+    }
+
+    /// Creates a length from a number and the unit, or None if the unit is not a length unit.
+    pub fn from_unit(value: f64, unit: &str) -> Option<Length> {
+        let unit = match unit.to_ascii_lowercase().as_str() {
+            "px" => LengthUnit::Px,
+            "em" => LengthUnit::Em,
+            "rem" => LengthUnit::Rem,
+            "vw" => LengthUnit::Vw,
+            "vh" => LengthUnit::Vh,
+            "vmin" => LengthUnit::Vmin,
+            "vmax" => LengthUnit::Vmax,
+            _ => return None,
+        };
+        Some(Length { value, unit })
+    }
+
+    /// Gets the length in CSS pixels for the viewport.
+    pub fn to_px(&self, viewport: &Viewport) -> f64 {
+        let scale = match self.unit {
+            LengthUnit::Px => 1.0,
+            LengthUnit::Em | LengthUnit::Rem => viewport.font_size,
+            LengthUnit::Vw => viewport.width / 100.0,
+            LengthUnit::Vh => viewport.height / 100.0,
+            LengthUnit::Vmin => viewport.width.min(viewport.height) / 100.0,
+            LengthUnit::Vmax => viewport.width.max(viewport.height) / 100.0,
+        };
+        self.value * scale
+    }
+}
+
+impl FromStr for Length {
+    type Err = ParseHtmlError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = MediaParser::new(s)?;
+        let length = match parser.next() {
+            Some(Token::Dimension(v, unit)) => Length::from_unit(v, &unit),
+            Some(Token::Number(0.0)) => Some(Length::new(0.0, LengthUnit::Px)),
+            _ => None,
+        };
+        match (length, parser.next()) {
+            (Some(l), None) => Ok(l),
+            _ => Err(ParseHtmlError::invalid_value(format!(
+                "'{}' is not a valid length",
+                s
+            ))),
+        }
+    }
+}
+
+/// The comparisons used in media features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Comparison {
+    /// The comparison with the sides swapped, eg `a < b` is the same as `b > a`.
+    fn flip(self) -> Comparison {
+        match self {
+            Comparison::Lt => Comparison::Gt,
+            Comparison::Le => Comparison::Ge,
+            Comparison::Eq => Comparison::Eq,
+            Comparison::Ge => Comparison::Le,
+            Comparison::Gt => Comparison::Lt,
+        }
+    }
+
+    fn compare(self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Lt => a < b,
+            Comparison::Le => a <= b,
+            Comparison::Eq => a == b,
+            Comparison::Ge => a >= b,
+            Comparison::Gt => a > b,
+        }
+    }
+}
+
+/// The value a media feature is compared with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaValue {
+    Length(Length),
+    Number(f64),
+    /// A ratio such as `16/9`.
+    Ratio(f64, f64),
+    /// A resolution in dots per CSS pixel.
+    Resolution(f64),
+    Ident(String),
+}
+
+/// A test on a single feature of the media, eg `(min-width: 600px)`.
+/// The min- and max- prefixes and the range syntax are turned into comparisons,
+/// so `(min-width: 600px)` is the test `width >= 600px`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFeature {
+    pub name: String,
+    /// The comparisons which must all be true, no comparisons means the feature
+    /// is used in the boolean context, eg `(color)`.
+    pub tests: Vec<(Comparison, MediaValue)>,
+}
+
+impl MediaFeature {
+    fn length_px(value: &MediaValue, viewport: &Viewport) -> Option<f64> {
+        match value {
+            MediaValue::Length(l) => Some(l.to_px(viewport)),
+            MediaValue::Number(n) if *n == 0.0 => Some(0.0),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the feature matches the viewport. Unknown features never match.
+    pub fn evaluate(&self, viewport: &Viewport) -> bool {
+        let actual = match self.name.as_str() {
+            "width" => MediaValue::Length(Length::new(viewport.width, LengthUnit::Px)),
+            "height" => MediaValue::Length(Length::new(viewport.height, LengthUnit::Px)),
+            "aspect-ratio" => MediaValue::Ratio(viewport.width, viewport.height),
+            "resolution" => MediaValue::Resolution(viewport.device_pixel_ratio),
+            "device-pixel-ratio" => MediaValue::Number(viewport.device_pixel_ratio),
+            "orientation" => MediaValue::Ident(
+                if viewport.height >= viewport.width {
+                    "portrait"
+                } else {
+                    "landscape"
+                }
+                .to_owned(),
+            ),
+            _ => return false,
+        };
+        if self.tests.is_empty() {
+            return match actual {
+                MediaValue::Length(l) => l.value != 0.0,
+                _ => true,
+            };
+        }
+        self.tests.iter().all(|(comparison, expected)| {
+            let values = match (&actual, expected) {
+                (MediaValue::Length(_), _) => MediaFeature::length_px(expected, viewport)
+                    .map(|e| (MediaFeature::length_px(&actual, viewport).unwrap_or(0.0), e)),
+                (MediaValue::Ratio(w, h), MediaValue::Ratio(ew, eh)) => Some((w * eh, ew * h)),
+                (MediaValue::Ratio(w, h), MediaValue::Number(n)) => Some((*w, n * h)),
+                (MediaValue::Resolution(r), MediaValue::Resolution(e)) => Some((*r, *e)),
+                (MediaValue::Number(r), MediaValue::Number(e)) => Some((*r, *e)),
+                (MediaValue::Ident(a), MediaValue::Ident(e)) => {
+                    return *comparison == Comparison::Eq && a.eq_ignore_ascii_case(e);
+                }
+                _ => None,
+            };
+            match values {
+                Some((a, e)) => comparison.compare(a, e),
+                None => false,
+            }
+        })
+    }
+}
+
+/// A media condition such as `(min-width: 600px) and (not (orientation: portrait))`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaCondition {
+    Feature(MediaFeature),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+}
+
+impl MediaCondition {
+    /// Returns true if the condition matches the viewport.
+    pub fn evaluate(&self, viewport: &Viewport) -> bool {
+        match self {
+            MediaCondition::Feature(f) => f.evaluate(viewport),
+            MediaCondition::Not(c) => !c.evaluate(viewport),
+            MediaCondition::And(cs) => cs.iter().all(|c| c.evaluate(viewport)),
+            MediaCondition::Or(cs) => cs.iter().any(|c| c.evaluate(viewport)),
+        }
+    }
+}
+
+impl FromStr for MediaCondition {
+    type Err = ParseHtmlError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = MediaParser::new(s)?;
+        let condition = parser.parse_condition()?;
+        parser.expect_end()?;
+        Ok(condition)
+    }
+}
+
+/// A single media query such as `not print and (color)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQuery {
+    /// The query was preceded by `not`.
+    pub negated: bool,
+    /// The media type, None if no type was given (which is the same as `all`).
+    pub media_type: Option<String>,
+    pub condition: Option<MediaCondition>,
+}
+
+impl MediaQuery {
+    /// Returns true if the query matches the viewport.
+    pub fn evaluate(&self, viewport: &Viewport) -> bool {
+        let type_matches = match &self.media_type {
+            None => true,
+            Some(t) => t == "all" || t.eq_ignore_ascii_case(&viewport.media_type),
+        };
+        let matches = type_matches
+            && self
+                .condition
+                .as_ref()
+                .map(|c| c.evaluate(viewport))
+                .unwrap_or(true);
+        matches != self.negated
+    }
+}
+
+/// A comma separated list of media queries, which matches if any of the queries match.
+/// An empty list always matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQueryList {
+    pub queries: Vec<MediaQuery>,
+}
+
+impl MediaQueryList {
+    pub fn evaluate(&self, viewport: &Viewport) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|q| q.evaluate(viewport))
+    }
+}
+
+impl FromStr for MediaQueryList {
+    type Err = ParseHtmlError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = MediaParser::new(s)?;
+        let mut queries = vec![];
+        if parser.peek().is_none() {
+            return Ok(MediaQueryList { queries });
+        }
+        loop {
+            queries.push(parser.parse_query()?);
+            match parser.next() {
+                None => break,
+                Some(Token::Comma) => {}
+                Some(t) => {
+                    return Err(ParseHtmlError::invalid_value(format!(
+                        "expected ',' between media queries but found '{:?}'",
+                        t
+                    )))
+                }
+            }
+        }
+        Ok(MediaQueryList { queries })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Colon,
+    Comma,
+    Slash,
+    Compare(Comparison),
+    Number(f64),
+    Dimension(f64, String),
+    Ident(String),
+}
+
+/// A recursive descent parser over the tokens of a media query.
+struct MediaParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl MediaParser {
+    fn new(s: &str) -> Result<MediaParser, ParseHtmlError> {
+        Ok(MediaParser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(i)) if i.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseHtmlError> {
+        match self.next() {
+            Some(t) if t == expected => Ok(()),
+            t => Err(ParseHtmlError::invalid_value(format!(
+                "expected '{:?}' in media query but found '{:?}'",
+                expected, t
+            ))),
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), ParseHtmlError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(t) => Err(ParseHtmlError::invalid_value(format!(
+                "unexpected '{:?}' at the end of the media condition",
+                t
+            ))),
+        }
+    }
+
+    fn parse_query(&mut self) -> Result<MediaQuery, ParseHtmlError> {
+        let mut query = MediaQuery {
+            negated: false,
+            media_type: None,
+            condition: None,
+        };
+        if self.peek() == Some(&Token::Open)
+            || (self.peek_keyword("not") && self.peek_at(1) == Some(&Token::Open))
+        {
+            query.condition = Some(self.parse_condition()?);
+            return Ok(query);
+        }
+        if self.peek_keyword("not") {
+            self.next();
+            query.negated = true;
+        } else if self.peek_keyword("only") {
+            self.next();
+        }
+        match self.next() {
+            Some(Token::Ident(t)) => query.media_type = Some(t.to_ascii_lowercase()),
+            t => {
+                return Err(ParseHtmlError::invalid_value(format!(
+                    "expected a media type but found '{:?}'",
+                    t
+                )))
+            }
+        }
+        if self.peek_keyword("and") {
+            self.next();
+            query.condition = Some(self.parse_condition_without_or()?);
+        }
+        Ok(query)
+    }
+
+    /// Parses a condition which can not contain `or` at the top level, as used after
+    /// a media type.
+    fn parse_condition_without_or(&mut self) -> Result<MediaCondition, ParseHtmlError> {
+        if self.peek_keyword("not") {
+            self.next();
+            return Ok(MediaCondition::Not(Box::new(self.parse_in_parens()?)));
+        }
+        let mut conditions = vec![self.parse_in_parens()?];
+        while self.peek_keyword("and") {
+            self.next();
+            conditions.push(self.parse_in_parens()?);
+        }
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            MediaCondition::And(conditions)
+        })
+    }
+
+    fn parse_condition(&mut self) -> Result<MediaCondition, ParseHtmlError> {
+        if self.peek_keyword("not") {
+            self.next();
+            return Ok(MediaCondition::Not(Box::new(self.parse_in_parens()?)));
+        }
+        let first = self.parse_in_parens()?;
+        let joiner = if self.peek_keyword("and") {
+            "and"
+        } else if self.peek_keyword("or") {
+            "or"
+        } else {
+            return Ok(first);
+        };
+        let mut conditions = vec![first];
+        while self.peek_keyword(joiner) {
+            self.next();
+            conditions.push(self.parse_in_parens()?);
+        }
+        if self.peek_keyword("and") || self.peek_keyword("or") {
+            return Err(ParseHtmlError::invalid_value(
+                "'and' and 'or' can not be mixed in a media condition without brackets",
+            ));
+        }
+        Ok(if joiner == "and" {
+            MediaCondition::And(conditions)
+        } else {
+            MediaCondition::Or(conditions)
+        })
+    }
+
+    fn parse_in_parens(&mut self) -> Result<MediaCondition, ParseHtmlError> {
+        self.expect(Token::Open)?;
+        let is_nested = match self.peek() {
+            Some(Token::Open) => true,
+            Some(Token::Ident(i)) if i.eq_ignore_ascii_case("not") => {
+                self.peek_at(1) == Some(&Token::Open)
+            }
+            _ => false,
+        };
+        let condition = if is_nested {
+            self.parse_condition()?
+        } else {
+            MediaCondition::Feature(self.parse_feature()?)
+        };
+        self.expect(Token::Close)?;
+        Ok(condition)
+    }
+
+    fn parse_value(&mut self) -> Result<MediaValue, ParseHtmlError> {
+        let value = match self.next() {
+            Some(Token::Number(n)) => {
+                if self.peek() == Some(&Token::Slash) {
+                    self.next();
+                    match self.next() {
+                        Some(Token::Number(d)) => MediaValue::Ratio(n, d),
+                        t => {
+                            return Err(ParseHtmlError::invalid_value(format!(
+                                "expected the second number of a ratio but found '{:?}'",
+                                t
+                            )))
+                        }
+                    }
+                } else {
+                    MediaValue::Number(n)
+                }
+            }
+            Some(Token::Dimension(n, unit)) => match Length::from_unit(n, &unit) {
+                Some(l) => MediaValue::Length(l),
+                None => match unit.to_ascii_lowercase().as_str() {
+                    "dppx" | "x" => MediaValue::Resolution(n),
+                    "dpi" => MediaValue::Resolution(n / 96.0),
+                    "dpcm" => MediaValue::Resolution(n * 2.54 / 96.0),
+                    _ => {
+                        return Err(ParseHtmlError::invalid_value(format!(
+                            "unknown unit '{}' in media query",
+                            unit
+                        )))
+                    }
+                },
+            },
+            Some(Token::Ident(i)) => MediaValue::Ident(i.to_ascii_lowercase()),
+            t => {
+                return Err(ParseHtmlError::invalid_value(format!(
+                    "expected a value in media feature but found '{:?}'",
+                    t
+                )))
+            }
+        };
+        Ok(value)
+    }
+
+    fn parse_feature(&mut self) -> Result<MediaFeature, ParseHtmlError> {
+        // the range syntax can start with a value, eg (400px < width < 800px)
+        if let Some(Token::Ident(name)) = self.peek().cloned() {
+            self.next();
+            let (name, comparison) = feature_name(&name);
+            let mut feature = MediaFeature {
+                name,
+                tests: vec![],
+            };
+            match self.peek() {
+                Some(Token::Colon) => {
+                    self.next();
+                    let value = self.parse_value()?;
+                    feature
+                        .tests
+                        .push((comparison.unwrap_or(Comparison::Eq), value));
+                }
+                Some(Token::Compare(c)) if comparison.is_none() => {
+                    let c = *c;
+                    self.next();
+                    feature.tests.push((c, self.parse_value()?));
+                }
+                _ if comparison.is_some() => {
+                    return Err(ParseHtmlError::invalid_value(format!(
+                        "the min/max media feature '{}' must have a value",
+                        feature.name
+                    )))
+                }
+                _ => {}
+            }
+            return Ok(feature);
+        }
+        let first = self.parse_value()?;
+        let first_comparison = match self.next() {
+            Some(Token::Compare(c)) => c,
+            t => {
+                return Err(ParseHtmlError::invalid_value(format!(
+                    "expected a comparison in media feature but found '{:?}'",
+                    t
+                )))
+            }
+        };
+        let name = match self.next() {
+            Some(Token::Ident(name)) => name.to_ascii_lowercase(),
+            t => {
+                return Err(ParseHtmlError::invalid_value(format!(
+                    "expected a media feature name but found '{:?}'",
+                    t
+                )))
+            }
+        };
+        let mut feature = MediaFeature {
+            name,
+            tests: vec![(first_comparison.flip(), first)],
+        };
+        if let Some(Token::Compare(c)) = self.peek() {
+            let c = *c;
+            self.next();
+            feature.tests.push((c, self.parse_value()?));
+        }
+        Ok(feature)
+    }
+}
+
+/// Splits the min-/max- prefix off a feature name, also handling the
+/// -webkit-min-device-pixel-ratio style names.
+fn feature_name(name: &str) -> (String, Option<Comparison>) {
+    let name = name.to_ascii_lowercase();
+    let unprefixed = name.strip_prefix("-webkit-").unwrap_or(&name);
+    if let Some(n) = unprefixed.strip_prefix("min-") {
+        (n.to_owned(), Some(Comparison::Ge))
+    } else if let Some(n) = unprefixed.strip_prefix("max-") {
+        (n.to_owned(), Some(Comparison::Le))
+    } else {
+        (unprefixed.to_owned(), None)
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ParseHtmlError> {
+    let mut tokens = vec![];
+    let mut chs = s.chars().peekable();
+    while let Some(c) = chs.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ':' => Token::Colon,
+            ',' => Token::Comma,
+            '/' => Token::Slash,
+            '=' => Token::Compare(Comparison::Eq),
+            '<' | '>' => {
+                let or_equal = chs.peek() == Some(&'=');
+                if or_equal {
+                    chs.next();
+                }
+                Token::Compare(match (c, or_equal) {
+                    ('<', false) => Comparison::Lt,
+                    ('<', true) => Comparison::Le,
+                    ('>', false) => Comparison::Gt,
+                    _ => Comparison::Ge,
+                })
+            }
+            c if c.is_ascii_digit() || c == '.' || c == '+' => {
+                let mut number = String::from(c);
+                while let Some(&d) = chs.peek() {
+                    if !(d.is_ascii_digit() || d == '.') {
+                        break;
+                    }
+                    number.push(d);
+                    chs.next();
+                }
+                let value = number.parse::<f64>().map_err(|_| {
+                    ParseHtmlError::invalid_value(format!("'{}' is not a valid number", number))
+                })?;
+                let mut unit = String::new();
+                while let Some(&u) = chs.peek() {
+                    if !(u.is_alphabetic() || u == '%') {
+                        break;
+                    }
+                    unit.push(u);
+                    chs.next();
+                }
+                if unit.is_empty() {
+                    Token::Number(value)
+                } else {
+                    Token::Dimension(value, unit)
+                }
+            }
+            c if c.is_alphabetic() || c == '-' || c == '_' => {
+                let mut ident = String::from(c);
+                while let Some(&i) = chs.peek() {
+                    if !(i.is_alphanumeric() || i == '-' || i == '_') {
+                        break;
+                    }
+                    ident.push(i);
+                    chs.next();
+                }
+                Token::Ident(ident)
+            }
+            c => {
+                return Err(ParseHtmlError::invalid_value(format!(
+                    "unexpected character '{}' in media query '{}'",
+                    c, s
+                )))
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod media_tests {
+    use super::*;
+
+    fn feature(name: &str, tests: Vec<(Comparison, MediaValue)>) -> MediaCondition {
+        MediaCondition::Feature(MediaFeature {
+            name: name.to_owned(),
+            tests,
+        })
+    }
+
+    fn px(value: f64) -> MediaValue {
+        MediaValue::Length(Length::new(value, LengthUnit::Px))
+    }
+
+    #[test]
+    fn media_condition_parse_test() {
+        assert_eq!(
+            "(max-width: 600px)".parse::<MediaCondition>().unwrap(),
+            feature("width", vec![(Comparison::Le, px(600.0))])
+        );
+        assert_eq!(
+            "(400px < width <= 800px)"
+                .parse::<MediaCondition>()
+                .unwrap(),
+            feature(
+                "width",
+                vec![(Comparison::Gt, px(400.0)), (Comparison::Le, px(800.0))]
+            )
+        );
+        assert_eq!(
+            "not ((color) or (aspect-ratio: 16/9))"
+                .parse::<MediaCondition>()
+                .unwrap(),
+            MediaCondition::Not(Box::new(MediaCondition::Or(vec![
+                feature("color", vec![]),
+                feature(
+                    "aspect-ratio",
+                    vec![(Comparison::Eq, MediaValue::Ratio(16.0, 9.0))]
+                ),
+            ])))
+        );
+        assert_eq!(
+            "(-webkit-min-device-pixel-ratio: 2)"
+                .parse::<MediaCondition>()
+                .unwrap(),
+            feature(
+                "device-pixel-ratio",
+                vec![(Comparison::Ge, MediaValue::Number(2.0))]
+            )
+        );
+        for invalid in [
+            "",
+            "max-width: 600px",
+            "(max-width: 600px) and (color) or (hover)",
+            "(max-width)",
+            "(width: 10parsecs)",
+            "(width: 600px",
+            "(width: 600px) extra",
+        ] {
+            assert!(
+                invalid.parse::<MediaCondition>().is_err(),
+                "{} should not parse",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn media_condition_evaluate_test() {
+        let phone = Viewport::new(375.0, 812.0).device_pixel_ratio(3.0);
+        let desktop = Viewport::new(1440.0, 900.0);
+        let tests = [
+            ("(max-width: 600px)", true, false),
+            ("(min-width: 40em)", false, true),
+            ("(width >= 50vh)", false, true),
+            ("(orientation: landscape)", false, true),
+            ("(min-resolution: 2dppx)", true, false),
+            ("(min-resolution: 192dpi)", true, false),
+            ("(aspect-ratio > 1/1)", false, true),
+            ("(min-aspect-ratio: 16/9)", false, false),
+            ("(width)", true, true),
+            ("(hover)", false, false),
+            ("not (hover)", true, true),
+            ("(max-width: 600px) or (orientation: landscape)", true, true),
+            ("(min-width: 0) and (max-width: 1000px)", true, false),
+        ];
+        for (condition, on_phone, on_desktop) in tests {
+            let c = condition.parse::<MediaCondition>().unwrap();
+            assert_eq!(c.evaluate(&phone), on_phone, "{} on phone", condition);
+            assert_eq!(c.evaluate(&desktop), on_desktop, "{} on desktop", condition);
+        }
+    }
+
+    #[test]
+    fn media_query_list_test() {
+        let screen = Viewport::new(500.0, 500.0);
+        let print = Viewport::new(500.0, 500.0).media_type("print");
+        let tests = [
+            ("", true, true),
+            ("all", true, true),
+            ("print", false, true),
+            ("not print", true, false),
+            ("only screen and (max-width: 600px)", true, false),
+            ("screen and (min-width: 600px), print", false, true),
+            ("(max-width: 600px)", true, true),
+        ];
+        for (queries, on_screen, on_print) in tests {
+            let q = queries.parse::<MediaQueryList>().unwrap();
+            assert_eq!(q.evaluate(&screen), on_screen, "{} on screen", queries);
+            assert_eq!(q.evaluate(&print), on_print, "{} on print", queries);
+        }
+        assert!("screen and".parse::<MediaQueryList>().is_err());
+        assert!("screen (color)".parse::<MediaQueryList>().is_err());
+    }
+
+    #[test]
+    fn length_test() {
+        let viewport = Viewport::new(1000.0, 500.0).font_size(10.0);
+        assert_eq!("2em".parse::<Length>().unwrap().to_px(&viewport), 20.0);
+        assert_eq!("50vw".parse::<Length>().unwrap().to_px(&viewport), 500.0);
+        assert_eq!("10vmin".parse::<Length>().unwrap().to_px(&viewport), 50.0);
+        assert_eq!("0".parse::<Length>().unwrap().to_px(&viewport), 0.0);
+        assert!("10".parse::<Length>().is_err());
+        assert!("10px 10px".parse::<Length>().is_err());
+    }
+}
@@ -0,0 +1,34 @@
+//! Finding bytes in text, which is most of the time spent reading large documents.
+//!
+//! With the `simd` feature the searches use the `memchr` crate, which compares
+//! many bytes at once using the SIMD instructions of the CPU. Without it they are
+//! plain loops, which give the same results.
+
+/// Finds the first position of the byte in the text.
+pub(crate) fn find_byte(text: &[u8], byte: u8) -> Option<usize> {
+    #[cfg(feature = "simd")]
+    {
+        memchr::memchr(byte, text)
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        text.iter().position(|b| *b == byte)
+    }
+}
+
+#[cfg(test)]
+mod memfind_tests {
+    use super::*;
+
+    #[test]
+    fn find_test() {
+        // long enough that the SIMD searches use their vector loops
+        let text = format!("{}é<p class=\"a\">&amp;", "x".repeat(100));
+        let bytes = text.as_bytes();
+        assert_eq!(find_byte(bytes, b'<'), Some(102));
+        assert_eq!(find_byte(bytes, b'>'), Some(114));
+        assert_eq!(find_byte(bytes, b'!'), None);
+        assert_eq!(find_byte(&bytes[114..], b'>'), Some(0));
+        assert_eq!(find_byte(b"", b'a'), None);
+    }
+}
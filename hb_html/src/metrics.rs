@@ -0,0 +1,347 @@
+//! Hooks for counting what the parser does, so that services can export parse
+//! throughput and error rates without wrapping every call to the parser.
+//!
+//! A [`Metrics`] object set with [`set_metrics`] is called by every parse of a
+//! document, whichever function started it, with the number of bytes parsed and
+//! the error if the parse failed. It is also told about errors which were
+//! recovered from, such as invalid character references decoded in
+//! [`ReferenceStrictness::Lenient`](crate::entities::ReferenceStrictness::Lenient)
+//! mode. [`CountingMetrics`] keeps totals which can be read by an exporter.
+//!
+//! A pathological document can have millions of recovered errors, so the metrics
+//! can limit how many of them are reported for each document with
+//! [`Metrics::diagnostic_limits`]. The errors over the limits are not reported one
+//! at a time, but counted and given to [`Metrics::recovered_errors_dropped`] once
+//! the document has been parsed.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::metrics::{clear_metrics, set_metrics, CountingMetrics};
+//! use hb_html::objects::HtmlDocument;
+//! use std::sync::Arc;
+//! let counts = Arc::new(CountingMetrics::new());
+//! set_metrics(counts.clone());
+//! "<p>one</p>".parse::<HtmlDocument>().unwrap();
+//! assert!("<p>two</b>".parse::<HtmlDocument>().is_err());
+//! clear_metrics();
+//! assert_eq!(counts.documents(), 2);
+//! assert_eq!(counts.bytes(), 20);
+//! assert_eq!(counts.errors(), 1);
+//! ```
+use crate::error::{ParseErrorKind, ParseHtmlError};
+use crate::pipeline::DiagnosticLimits;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Receives counts from the parser. All of the methods do nothing by default.
+///
+/// The methods are called from whichever thread is parsing, so they should be
+/// quick, eg adding to an atomic counter.
+pub trait Metrics: Send + Sync {
+    /// Called after a document has been parsed, whether or not it was valid.
+    fn bytes_parsed(&self, _bytes: usize) {}
+    /// Called when parsing a document fails.
+    fn parse_error(&self, _error: &ParseHtmlError) {}
+    /// Called when an error in the input was recovered from rather than returned.
+    fn recovered_error(&self, _kind: ParseErrorKind) {}
+    /// Called after a document has been parsed with the number of recovered errors
+    /// of a kind which were not reported because of the [`DiagnosticLimits`].
+    fn recovered_errors_dropped(&self, _kind: ParseErrorKind, _count: usize) {}
+    /// The limits on the recovered errors reported for each document, which are
+    /// read when parsing of a document starts. By default every error is reported.
+    fn diagnostic_limits(&self) -> DiagnosticLimits {
+        DiagnosticLimits::new()
+    }
+}
+
+static METRICS: RwLock<Option<Arc<dyn Metrics>>> = RwLock::new(None);
+
+/// Sets the metrics which are called by the parser, replacing any already set.
+pub fn set_metrics(metrics: Arc<dyn Metrics>) {
+    if let Ok(mut current) = METRICS.write() {
+        *current = Some(metrics);
+    }
+}
+
+/// Stops calling the metrics which were set.
+pub fn clear_metrics() {
+    if let Ok(mut current) = METRICS.write() {
+        *current = None;
+    }
+}
+
+/// Calls the function with the metrics, if any are set.
+fn with_metrics<F: FnOnce(&dyn Metrics)>(f: F) {
+    if let Ok(current) = METRICS.read() {
+        if let Some(metrics) = current.as_ref() {
+            f(metrics.as_ref());
+        }
+    }
+}
+
+/// The recovered errors of the document being parsed on a thread.
+pub(crate) struct DocumentErrors {
+    limits: DiagnosticLimits,
+    kept: usize,
+    /// The number of errors recovered from and dropped, by kind.
+    seen: HashMap<ParseErrorKind, (usize, usize)>,
+}
+
+impl DocumentErrors {
+    /// Counts an error, returning true if it should be reported.
+    fn keeps(&mut self, kind: ParseErrorKind) -> bool {
+        let counts = self.seen.entry(kind).or_default();
+        counts.0 += 1;
+        if self.limits.keeps(counts.0, self.kept) {
+            self.kept += 1;
+            true
+        } else {
+            counts.1 += 1;
+            false
+        }
+    }
+}
+
+thread_local! {
+    static DOCUMENT: RefCell<Option<DocumentErrors>> = const { RefCell::new(None) };
+}
+
+/// Starts counting the recovered errors of a document against the limits of the
+/// metrics. Returns the errors of the document which was already being parsed on
+/// this thread, if any, to be given back to [`record_parse`].
+pub(crate) fn start_document() -> Option<DocumentErrors> {
+    let mut document = None;
+    with_metrics(|m| {
+        document = Some(DocumentErrors {
+            limits: m.diagnostic_limits(),
+            kept: 0,
+            seen: HashMap::new(),
+        })
+    });
+    DOCUMENT.with(|d| d.replace(document))
+}
+
+/// Records the result of parsing a document of the length provided, and the
+/// number of its recovered errors which were dropped.
+pub(crate) fn record_parse<T>(
+    bytes: usize,
+    result: &Result<T, ParseHtmlError>,
+    previous: Option<DocumentErrors>,
+) {
+    let document = DOCUMENT.with(|d| d.replace(previous));
+    with_metrics(|m| {
+        if let Some(document) = document {
+            for (kind, (_, dropped)) in document.seen {
+                if dropped > 0 {
+                    m.recovered_errors_dropped(kind, dropped);
+                }
+            }
+        }
+        m.bytes_parsed(bytes);
+        if let Err(e) = result {
+            m.parse_error(e);
+        }
+    });
+}
+
+/// Records an error which was recovered from, unless it is over the limits for
+/// the document being parsed.
+pub(crate) fn record_recovered(kind: ParseErrorKind) {
+    let keep = DOCUMENT.with(|d| match d.borrow_mut().as_mut() {
+        Some(document) => document.keeps(kind),
+        None => true,
+    });
+    if keep {
+        with_metrics(|m| m.recovered_error(kind));
+    }
+}
+
+/// [`Metrics`] which keep running totals.
+#[derive(Debug, Default)]
+pub struct CountingMetrics {
+    documents: AtomicU64,
+    bytes: AtomicU64,
+    errors: AtomicU64,
+    recovered: AtomicU64,
+    dropped: AtomicU64,
+    limits: DiagnosticLimits,
+}
+
+impl CountingMetrics {
+    pub fn new() -> CountingMetrics {
+        CountingMetrics::default()
+    }
+
+    /// Sets the limits on the recovered errors counted for each document.
+    pub fn with_limits(mut self, limits: DiagnosticLimits) -> CountingMetrics {
+        self.limits = limits;
+        self
+    }
+
+    /// The number of documents parsed.
+    pub fn documents(&self) -> u64 {
+        self.documents.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes parsed.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The number of documents which could not be parsed.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// The number of errors which were recovered from.
+    pub fn recovered(&self) -> u64 {
+        self.recovered.load(Ordering::Relaxed)
+    }
+
+    /// The number of recovered errors which were dropped because of the limits.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Metrics for CountingMetrics {
+    fn bytes_parsed(&self, bytes: usize) {
+        self.documents.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn parse_error(&self, _error: &ParseHtmlError) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn recovered_error(&self, _kind: ParseErrorKind) {
+        self.recovered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn recovered_errors_dropped(&self, _kind: ParseErrorKind, count: usize) {
+        self.dropped.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    fn diagnostic_limits(&self) -> DiagnosticLimits {
+        self.limits.clone()
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+    use crate::entities::{decode_numeric_reference, ReferenceStrictness};
+    use crate::objects::HtmlDocument;
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    /// Only counts what happens on one thread, as other tests parse at the same time.
+    struct ThreadMetrics {
+        thread: ThreadId,
+        counts: CountingMetrics,
+        kinds: Mutex<Vec<ParseErrorKind>>,
+    }
+
+    impl ThreadMetrics {
+        fn new(counts: CountingMetrics) -> ThreadMetrics {
+            ThreadMetrics {
+                thread: thread::current().id(),
+                counts,
+                kinds: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    impl Metrics for ThreadMetrics {
+        fn bytes_parsed(&self, bytes: usize) {
+            if thread::current().id() == self.thread {
+                self.counts.bytes_parsed(bytes);
+            }
+        }
+        fn parse_error(&self, error: &ParseHtmlError) {
+            if thread::current().id() == self.thread {
+                self.counts.parse_error(error);
+                self.kinds.lock().unwrap().push(error.kind());
+            }
+        }
+        fn recovered_error(&self, kind: ParseErrorKind) {
+            if thread::current().id() == self.thread {
+                self.counts.recovered_error(kind);
+                self.kinds.lock().unwrap().push(kind);
+            }
+        }
+        fn recovered_errors_dropped(&self, kind: ParseErrorKind, count: usize) {
+            if thread::current().id() == self.thread {
+                self.counts.recovered_errors_dropped(kind, count);
+            }
+        }
+        fn diagnostic_limits(&self) -> DiagnosticLimits {
+            self.counts.diagnostic_limits()
+        }
+    }
+
+    #[test]
+    fn metrics_test() {
+        let metrics = Arc::new(ThreadMetrics::new(CountingMetrics::new()));
+        set_metrics(metrics.clone());
+        HtmlDocument::parse_with_source_map("<div></div>").unwrap();
+        assert!("<div".parse::<HtmlDocument>().is_err());
+        for reference in ["&#0;", "&#x41", "&#65;"] {
+            decode_numeric_reference(reference, ReferenceStrictness::Lenient).unwrap();
+        }
+        assert!(decode_numeric_reference("&#0;", ReferenceStrictness::Strict).is_err());
+        clear_metrics();
+        "<p></p>".parse::<HtmlDocument>().unwrap();
+        assert_eq!(metrics.counts.documents(), 2);
+        assert_eq!(metrics.counts.bytes(), 15);
+        assert_eq!(metrics.counts.errors(), 1);
+        assert_eq!(metrics.counts.recovered(), 2);
+        assert_eq!(
+            *metrics.kinds.lock().unwrap(),
+            vec![
+                ParseErrorKind::UnexpectedEnd,
+                ParseErrorKind::Value,
+                ParseErrorKind::Value
+            ]
+        );
+    }
+
+    #[test]
+    fn diagnostic_limits_test() {
+        let html = format!("<p>{}</p>", "&#0;".repeat(1000));
+        let limits = DiagnosticLimits::new().max_per_kind(3).sample_every(100);
+        let metrics = Arc::new(ThreadMetrics::new(
+            CountingMetrics::new().with_limits(limits),
+        ));
+        set_metrics(metrics.clone());
+        let doc = html.parse::<HtmlDocument>().unwrap();
+        html.parse::<HtmlDocument>().unwrap();
+        // errors outside of a document are not limited
+        for _ in 0..20 {
+            decode_numeric_reference("&#0;", ReferenceStrictness::Lenient).unwrap();
+        }
+        clear_metrics();
+        assert_eq!(
+            doc.to_html_string(),
+            format!("<p>{}</p>", "\u{FFFD}".repeat(1000))
+        );
+        assert_eq!(metrics.counts.documents(), 2);
+        // 3 from each document, then one in every 100 of the other 997
+        assert_eq!(metrics.counts.recovered(), 2 * (3 + 9) + 20);
+        assert_eq!(metrics.counts.dropped(), 2 * (1000 - 12));
+
+        let limits = DiagnosticLimits::new().max_total(5);
+        let metrics = Arc::new(ThreadMetrics::new(
+            CountingMetrics::new().with_limits(limits),
+        ));
+        set_metrics(metrics.clone());
+        html.parse::<HtmlDocument>().unwrap();
+        clear_metrics();
+        assert_eq!(metrics.counts.recovered(), 5);
+        assert_eq!(metrics.counts.dropped(), 995);
+        assert_eq!(metrics.kinds.lock().unwrap().len(), 5);
+    }
+}
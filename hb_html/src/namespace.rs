@@ -0,0 +1,215 @@
+//! XML namespaces of elements and attributes.
+//!
+//! Attribute names are kept as they are written, so prefixed attributes such as
+//! `xlink:href` and `xml:lang` and the `xmlns` / `xmlns:prefix` declarations are
+//! ordinary attributes which are written back out unchanged.
+//!
+//! [`HtmlDocument::namespaces_at`] finds the namespaces which are in scope for an
+//! element: the `xml` and `xmlns` prefixes which are always bound, the default
+//! namespace and the prefixes declared on the element and its ancestors. As in
+//! HTML parsing, `<svg>` and `<math>` start the SVG and MathML namespaces without
+//! an `xmlns` attribute and other elements are in the XHTML namespace.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::namespace::{Namespaces, SVG_NAMESPACE, XLINK_NAMESPACE};
+//! use hb_html::objects::HtmlDocument;
+//! let doc = r##"<div><svg xmlns:xlink="http://www.w3.org/1999/xlink"><use xlink:href="#a"></use></svg></div>"##
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let scope = doc.namespaces_at(&[0, 0, 0]).unwrap();
+//! assert_eq!(scope.default_namespace(), Some(SVG_NAMESPACE));
+//! assert_eq!(scope.resolve_prefix("xlink"), Some(XLINK_NAMESPACE));
+//! assert_eq!(
+//!     Namespaces::split_name("xlink:href"),
+//!     (Some("xlink"), "href")
+//! );
+//! ```
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag};
+use std::collections::BTreeMap;
+
+pub const XHTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+pub const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+pub const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+pub const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+pub const XMLNS_NAMESPACE: &str = "http://www.w3.org/2000/xmlns/";
+
+/// Gets the namespace of a prefix which is commonly used in HTML documents
+/// without being declared, eg `xlink` in SVG.
+pub fn well_known_prefix(prefix: &str) -> Option<&'static str> {
+    match prefix {
+        "xml" => Some(XML_NAMESPACE),
+        "xmlns" => Some(XMLNS_NAMESPACE),
+        "xlink" => Some(XLINK_NAMESPACE),
+        _ => None,
+    }
+}
+
+/// The namespaces in scope for an element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespaces {
+    default: Option<String>,
+    prefixes: BTreeMap<String, String>,
+}
+
+impl Default for Namespaces {
+    /// The namespaces at the top of a document, where only the `xml` and `xmlns`
+    /// prefixes are bound.
+    fn default() -> Self {
+        Namespaces {
+            default: None,
+            prefixes: BTreeMap::from([
+                ("xml".to_owned(), XML_NAMESPACE.to_owned()),
+                ("xmlns".to_owned(), XMLNS_NAMESPACE.to_owned()),
+            ]),
+        }
+    }
+}
+
+impl Namespaces {
+    pub fn new() -> Namespaces {
+        Namespaces::default()
+    }
+
+    /// Splits a qualified name such as `xlink:href` into its prefix and local name.
+    pub fn split_name(name: &str) -> (Option<&str>, &str) {
+        match name.split_once(':') {
+            Some((prefix, local)) if !prefix.is_empty() && !local.is_empty() => {
+                (Some(prefix), local)
+            }
+            _ => (None, name),
+        }
+    }
+
+    /// Gets the namespace of elements without a prefix.
+    pub fn default_namespace(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    pub fn resolve_prefix(&self, prefix: &str) -> Option<&str> {
+        self.prefixes.get(prefix).map(|ns| ns.as_str())
+    }
+
+    /// Gets the bound prefixes and their namespaces in alphabetical order of the
+    /// prefix.
+    pub fn prefixes(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.prefixes
+            .iter()
+            .map(|(p, ns)| (p.as_str(), ns.as_str()))
+    }
+
+    /// Gets the namespace of the element, from its prefix or the default namespace.
+    pub fn element_namespace(&self, tag: &str) -> Option<&str> {
+        match Namespaces::split_name(tag) {
+            (Some(prefix), _) => self.resolve_prefix(prefix),
+            (None, _) => self.default_namespace(),
+        }
+    }
+
+    /// Gets the namespace of the attribute. Attributes without a prefix are not in
+    /// a namespace, other than `xmlns` itself.
+    pub fn attribute_namespace(&self, name: &str) -> Option<&str> {
+        match Namespaces::split_name(name) {
+            (Some(prefix), _) => self.resolve_prefix(prefix),
+            (None, "xmlns") => Some(XMLNS_NAMESPACE),
+            (None, _) => None,
+        }
+    }
+
+    /// Binds the prefix to the namespace.
+    pub fn declare<P: Into<String>, N: Into<String>>(&mut self, prefix: P, namespace: N) {
+        self.prefixes.insert(prefix.into(), namespace.into());
+    }
+
+    /// Gets the namespaces in scope for the tag, when these are the namespaces in
+    /// scope for its parent.
+    pub fn enter(&self, tag: &HtmlTag) -> Namespaces {
+        let mut scope = self.clone();
+        let implied = match tag.tag.to_ascii_lowercase().as_str() {
+            "svg" => Some(SVG_NAMESPACE),
+            "math" => Some(MATHML_NAMESPACE),
+            _ if scope.default.is_none() => Some(XHTML_NAMESPACE),
+            _ => None,
+        };
+        if let Some(ns) = implied {
+            scope.default = Some(ns.to_owned());
+        }
+        for (name, value) in &tag.attributes {
+            match Namespaces::split_name(name) {
+                (None, "xmlns") if !value.is_empty() => scope.default = Some(value.clone()),
+                (Some("xmlns"), prefix) if !matches!(prefix, "xml" | "xmlns") => {
+                    scope.declare(prefix, value.as_str());
+                }
+                _ => {}
+            }
+        }
+        scope
+    }
+}
+
+impl HtmlDocument {
+    /// Gets the namespaces in scope for the tag at the path, or None if there is no
+    /// tag at the path.
+    pub fn namespaces_at(&self, path: &[usize]) -> Option<Namespaces> {
+        let mut scope = Namespaces::new();
+        let mut nodes = &self.nodes;
+        for i in path {
+            match nodes.get(*i)? {
+                HtmlNode::Tag(t) => {
+                    scope = scope.enter(t);
+                    nodes = &t.contents;
+                }
+                _ => return None,
+            }
+        }
+        Some(scope)
+    }
+}
+
+#[cfg(test)]
+mod namespace_tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_at_test() {
+        let doc = r#"<html xml:lang="en" xmlns:og="http://ogp.me/ns#"><math><mi>x</mi></math><svg xmlns:e="urn:e"><e:thing xmlns="urn:d"><g></g></e:thing></svg><p></p></html>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let html = doc.namespaces_at(&[0]).unwrap();
+        assert_eq!(html.default_namespace(), Some(XHTML_NAMESPACE));
+        assert_eq!(html.resolve_prefix("og"), Some("http://ogp.me/ns#"));
+        assert_eq!(html.attribute_namespace("xml:lang"), Some(XML_NAMESPACE));
+        assert_eq!(html.attribute_namespace("lang"), None);
+        assert_eq!(
+            html.prefixes().map(|(p, _)| p).collect::<Vec<_>>(),
+            vec!["og", "xml", "xmlns"]
+        );
+        let mi = doc.namespaces_at(&[0, 0, 0]).unwrap();
+        assert_eq!(mi.default_namespace(), Some(MATHML_NAMESPACE));
+        let thing = doc.namespaces_at(&[0, 1, 0]).unwrap();
+        assert_eq!(thing.element_namespace("e:thing"), Some("urn:e"));
+        assert_eq!(
+            doc.namespaces_at(&[0, 1, 0, 0])
+                .unwrap()
+                .default_namespace(),
+            Some("urn:d")
+        );
+        // the svg namespace does not leak to siblings
+        let p = doc.namespaces_at(&[0, 2]).unwrap();
+        assert_eq!(p.default_namespace(), Some(XHTML_NAMESPACE));
+        assert_eq!(p.resolve_prefix("e"), None);
+        assert_eq!(doc.namespaces_at(&[0, 0, 0, 0]), None);
+        assert_eq!(doc.namespaces_at(&[5]), None);
+        assert_eq!(doc.namespaces_at(&[]), Some(Namespaces::new()));
+    }
+
+    #[test]
+    fn split_name_test() {
+        assert_eq!(Namespaces::split_name("xml:lang"), (Some("xml"), "lang"));
+        assert_eq!(Namespaces::split_name("href"), (None, "href"));
+        assert_eq!(Namespaces::split_name(":click"), (None, ":click"));
+        assert_eq!(Namespaces::split_name("v-on:"), (None, "v-on:"));
+    }
+}
@@ -0,0 +1,608 @@
+//! A document kept as a flat list of nodes, which link to each other by
+//! [`NodeId`].
+//!
+//! An [`HtmlDocument`] owns its nodes as a tree of `Vec`s, which is simple to walk
+//! down but has no way back up: a [`NodePath`] is the only handle on a node, and it
+//! changes when a node before it is added or removed. A [`NodeTree`] keeps every
+//! node in one `Vec<NodeData>`, each with the IDs of its parent, children and
+//! siblings. A [`NodeId`] is an index into the `Vec`, so it stays the same while
+//! the tree is changed, it can be held while other nodes are borrowed, and moving
+//! to the parent or the next sibling of a node is a single lookup.
+//!
+//! Nodes are only ever added to the list. A node which is detached keeps its ID and
+//! can be put back anywhere, but is not part of the document until it is.
+//! [`NodeTree::to_document`] copies the nodes which are still in the tree back out
+//! as an [`HtmlDocument`].
+//!
+//! The contents of the [`HtmlTag`] of a tag node are always empty, the children of
+//! the node are in the tree instead.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::node_tree::NodeTree;
+//! use hb_html::objects::{HtmlDocument, HtmlNode};
+//! let doc = "<ul><li>One</li><li>Two</li></ul>".parse::<HtmlDocument>().unwrap();
+//! let mut tree = NodeTree::from_document(&doc);
+//! let list = tree.roots().next().unwrap();
+//! let one = tree.first_child(list).unwrap();
+//! let two = tree.next_sibling(one).unwrap();
+//! assert_eq!(tree.parent(two), Some(list));
+//! assert_eq!(tree.text(two), "Two");
+//!
+//! // copy the first item and put the copy at the end
+//! let copy = tree.deep_clone(one);
+//! tree.append_child(list, copy).unwrap();
+//! let three = tree.append_child(copy, HtmlNode::Text(" again".to_owned()));
+//! assert!(three.is_ok());
+//! tree.insert_before(one, two).unwrap();
+//! assert_eq!(
+//!     tree.to_document().to_html_string(),
+//!     "<ul><li>Two</li><li>One</li><li>One again</li></ul>"
+//! );
+//! assert_eq!(tree.path(copy), Some(vec![0, 2]));
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+
+/// The handle of a node in a [`NodeTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// The position of the node in the list of the tree.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/// A node of a [`NodeTree`] with the links to the nodes around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeData {
+    node: HtmlNode,
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+    /// False while the node is detached, or inside a detached node.
+    attached: bool,
+}
+
+impl NodeData {
+    fn new(node: HtmlNode) -> NodeData {
+        NodeData {
+            node,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
+            attached: false,
+        }
+    }
+
+    /// The node without its children.
+    pub fn node(&self) -> &HtmlNode {
+        &self.node
+    }
+}
+
+/// A document whose nodes are kept in a list and refer to each other by ID.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeTree {
+    /// The doctype string from the document, usually "html".
+    pub doctype: String,
+    nodes: Vec<NodeData>,
+    first_root: Option<NodeId>,
+    last_root: Option<NodeId>,
+}
+
+impl NodeTree {
+    /// Creates an empty tree.
+    pub fn new() -> NodeTree {
+        NodeTree::default()
+    }
+
+    /// Copies the nodes of the document into a tree.
+    pub fn from_document(doc: &HtmlDocument) -> NodeTree {
+        let mut tree = NodeTree {
+            doctype: doc.doctype.clone(),
+            ..NodeTree::default()
+        };
+        for node in &doc.nodes {
+            let id = tree.add(node.clone());
+            tree.attach(None, None, id);
+        }
+        tree
+    }
+
+    /// Copies the nodes which are in the tree out into a document.
+    pub fn to_document(&self) -> HtmlDocument {
+        let mut doc = HtmlDocument::new();
+        doc.doctype = self.doctype.clone();
+        doc.nodes = self.roots().map(|id| self.to_node(id)).collect();
+        doc
+    }
+
+    /// Copies the node out of the tree with all of its descendants.
+    pub fn to_node(&self, id: NodeId) -> HtmlNode {
+        let mut node = self.nodes[id.0].node.clone();
+        if let HtmlNode::Tag(tag) = &mut node {
+            tag.contents = self.children(id).map(|c| self.to_node(c)).collect();
+        }
+        node
+    }
+
+    /// Adds the node to the list without putting it in the tree, returning its ID.
+    /// The contents of a tag are added as its children.
+    pub fn add(&mut self, mut node: HtmlNode) -> NodeId {
+        let contents = match &mut node {
+            HtmlNode::Tag(tag) => std::mem::take(&mut tag.contents),
+            _ => vec![],
+        };
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(NodeData::new(node));
+        for child in contents {
+            let child = self.add(child);
+            self.link(Some(id), None, child);
+        }
+        id
+    }
+
+    /// The number of nodes in the list, including the ones which are detached.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Gets the node with the ID and its links. IDs are only given out by the tree
+    /// they are used with, so this panics if the ID is from another tree.
+    pub fn get(&self, id: NodeId) -> &NodeData {
+        &self.nodes[id.0]
+    }
+
+    /// Gets the node, without its children, to change it. The contents of a tag
+    /// which are set here are not part of the tree and are ignored.
+    pub fn node_mut(&mut self, id: NodeId) -> &mut HtmlNode {
+        &mut self.nodes[id.0].node
+    }
+
+    /// Gets the tag of the node if it is one.
+    pub fn tag(&self, id: NodeId) -> Option<&HtmlTag> {
+        match &self.nodes[id.0].node {
+            HtmlNode::Tag(tag) => Some(tag),
+            _ => None,
+        }
+    }
+
+    /// Gets the text of the node and its descendants.
+    pub fn text(&self, id: NodeId) -> String {
+        match &self.nodes[id.0].node {
+            HtmlNode::Tag(_) => self
+                .descendants(id)
+                .map(|d| self.nodes[d.0].node.text())
+                .collect(),
+            node => node.text(),
+        }
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].first_child
+    }
+
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].last_child
+    }
+
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].previous_sibling
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].next_sibling
+    }
+
+    /// True if the node is part of the document, rather than detached or only
+    /// added to the list.
+    pub fn is_attached(&self, id: NodeId) -> bool {
+        self.nodes[id.0].attached
+    }
+
+    /// Iterates over the nodes at the top level of the document.
+    pub fn roots(&self) -> Siblings<'_> {
+        Siblings {
+            tree: self,
+            next: self.first_root,
+        }
+    }
+
+    pub fn children(&self, id: NodeId) -> Siblings<'_> {
+        Siblings {
+            tree: self,
+            next: self.nodes[id.0].first_child,
+        }
+    }
+
+    /// Iterates over the parent of the node, its parent and so on.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), move |&a| self.parent(a))
+    }
+
+    /// Iterates over the descendants of the node in document order, not including
+    /// the node.
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_> {
+        Descendants {
+            tree: self,
+            root: id,
+            next: self.first_child(id),
+        }
+    }
+
+    /// Gets the path of the node in the document, None if it is not attached.
+    pub fn path(&self, id: NodeId) -> Option<NodePath> {
+        if !self.is_attached(id) {
+            return None;
+        }
+        let mut path: NodePath = std::iter::once(id)
+            .chain(self.ancestors(id))
+            .map(|n| self.siblings_before(n))
+            .collect();
+        path.reverse();
+        Some(path)
+    }
+
+    /// Gets the ID of the node at the path in the document.
+    pub fn node_at(&self, path: &[usize]) -> Option<NodeId> {
+        let (first, rest) = path.split_first()?;
+        let mut id = self.roots().nth(*first)?;
+        for i in rest {
+            id = self.children(id).nth(*i)?;
+        }
+        Some(id)
+    }
+
+    /// Adds the node to the end of the children of the parent, or moves it there if
+    /// it is already in the list, returning its ID.
+    pub fn append_child<N: Into<NewNode>>(
+        &mut self,
+        parent: NodeId,
+        node: N,
+    ) -> Result<NodeId, HtmlDocError> {
+        let node = node.into();
+        self.check_parent(parent, &node)?;
+        let id = self.node_id(node);
+        self.detach(id);
+        self.attach(Some(parent), None, id);
+        Ok(id)
+    }
+
+    /// Adds the node to the end of the top level of the document, or moves it there
+    /// if it is already in the list, returning its ID.
+    pub fn append_root<N: Into<NewNode>>(&mut self, node: N) -> NodeId {
+        let id = self.node_id(node);
+        self.detach(id);
+        self.attach(None, None, id);
+        id
+    }
+
+    /// Puts the node before the sibling, adding it to the list or moving it from
+    /// where it was, returning its ID.
+    pub fn insert_before<N: Into<NewNode>>(
+        &mut self,
+        sibling: NodeId,
+        node: N,
+    ) -> Result<NodeId, HtmlDocError> {
+        let node = node.into();
+        if node == NewNode::Id(sibling) {
+            return Err(HtmlDocError::with_msg(
+                "can not insert a node before itself",
+            ));
+        }
+        let parent = self.parent(sibling);
+        if let Some(parent) = parent {
+            self.check_parent(parent, &node)?;
+        } else if !self.is_attached(sibling) {
+            return Err(HtmlDocError::with_msg(
+                "can not insert a node before one which is not in the tree",
+            ));
+        }
+        let id = self.node_id(node);
+        self.detach(id);
+        self.attach(parent, Some(sibling), id);
+        Ok(id)
+    }
+
+    /// Takes the node and its descendants out of the tree. It keeps its ID, so it
+    /// can be put back with [`NodeTree::append_child`] or
+    /// [`NodeTree::insert_before`].
+    pub fn detach(&mut self, id: NodeId) {
+        let data = &self.nodes[id.0];
+        let (parent, previous, next) = (data.parent, data.previous_sibling, data.next_sibling);
+        if !data.attached && parent.is_none() {
+            return;
+        }
+        match previous {
+            Some(p) => self.nodes[p.0].next_sibling = next,
+            None => *self.first_child_mut(parent) = next,
+        }
+        match next {
+            Some(n) => self.nodes[n.0].previous_sibling = previous,
+            None => *self.last_child_mut(parent) = previous,
+        }
+        let data = &mut self.nodes[id.0];
+        data.parent = None;
+        data.previous_sibling = None;
+        data.next_sibling = None;
+        self.set_attached(id, false);
+    }
+
+    /// Puts the node in place of the old node, which is detached.
+    pub fn replace_with<N: Into<NewNode>>(
+        &mut self,
+        old: NodeId,
+        node: N,
+    ) -> Result<NodeId, HtmlDocError> {
+        let id = self.insert_before(old, node)?;
+        self.detach(old);
+        Ok(id)
+    }
+
+    /// Adds a copy of the node and its descendants to the list, returning the ID of
+    /// the copy, which is detached.
+    pub fn deep_clone(&mut self, id: NodeId) -> NodeId {
+        let copy = NodeId(self.nodes.len());
+        self.nodes
+            .push(NodeData::new(self.nodes[id.0].node.clone()));
+        let children: Vec<NodeId> = self.children(id).collect();
+        for child in children {
+            let child = self.deep_clone(child);
+            self.link(Some(copy), None, child);
+        }
+        copy
+    }
+
+    fn node_id<N: Into<NewNode>>(&mut self, node: N) -> NodeId {
+        match node.into() {
+            NewNode::Id(id) => id,
+            NewNode::Node(node) => self.add(node),
+        }
+    }
+
+    /// Checks the node can be a child of the parent.
+    fn check_parent(&self, parent: NodeId, node: &NewNode) -> Result<(), HtmlDocError> {
+        if self.tag(parent).is_none() {
+            return Err(HtmlDocError::with_msg("only a tag can have children"));
+        }
+        let NewNode::Id(id) = *node else {
+            return Ok(());
+        };
+        if parent == id || self.ancestors(parent).any(|a| a == id) {
+            return Err(HtmlDocError::with_msg(
+                "can not put a node inside one of its descendants",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Links the detached node in before the sibling, or at the end of the children
+    /// of the parent (or the top level) without a sibling, and marks it attached if
+    /// its parent is.
+    fn attach(&mut self, parent: Option<NodeId>, before: Option<NodeId>, id: NodeId) {
+        self.link(parent, before, id);
+        let attached = match parent {
+            Some(p) => self.nodes[p.0].attached,
+            None => true,
+        };
+        self.set_attached(id, attached);
+    }
+
+    fn link(&mut self, parent: Option<NodeId>, before: Option<NodeId>, id: NodeId) {
+        let previous = match before {
+            Some(b) => self.nodes[b.0].previous_sibling,
+            None => *self.last_child_mut(parent),
+        };
+        match previous {
+            Some(p) => self.nodes[p.0].next_sibling = Some(id),
+            None => *self.first_child_mut(parent) = Some(id),
+        }
+        match before {
+            Some(b) => self.nodes[b.0].previous_sibling = Some(id),
+            None => *self.last_child_mut(parent) = Some(id),
+        }
+        let data = &mut self.nodes[id.0];
+        data.parent = parent;
+        data.previous_sibling = previous;
+        data.next_sibling = before;
+    }
+
+    fn set_attached(&mut self, id: NodeId, attached: bool) {
+        self.nodes[id.0].attached = attached;
+        let descendants: Vec<NodeId> = self.descendants(id).collect();
+        for d in descendants {
+            self.nodes[d.0].attached = attached;
+        }
+    }
+
+    fn first_child_mut(&mut self, parent: Option<NodeId>) -> &mut Option<NodeId> {
+        match parent {
+            Some(p) => &mut self.nodes[p.0].first_child,
+            None => &mut self.first_root,
+        }
+    }
+
+    fn last_child_mut(&mut self, parent: Option<NodeId>) -> &mut Option<NodeId> {
+        match parent {
+            Some(p) => &mut self.nodes[p.0].last_child,
+            None => &mut self.last_root,
+        }
+    }
+
+    fn siblings_before(&self, id: NodeId) -> usize {
+        std::iter::successors(self.previous_sibling(id), |&s| self.previous_sibling(s)).count()
+    }
+}
+
+impl From<&HtmlDocument> for NodeTree {
+    fn from(doc: &HtmlDocument) -> NodeTree {
+        NodeTree::from_document(doc)
+    }
+}
+
+/// A node given to [`NodeTree::append_child`] and the other methods which put nodes
+/// in the tree: either a node which is already in the list, or one to add to it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NewNode {
+    Id(NodeId),
+    Node(HtmlNode),
+}
+
+impl From<NodeId> for NewNode {
+    fn from(id: NodeId) -> NewNode {
+        NewNode::Id(id)
+    }
+}
+
+impl From<HtmlNode> for NewNode {
+    fn from(node: HtmlNode) -> NewNode {
+        NewNode::Node(node)
+    }
+}
+
+/// An iterator over a node and the siblings after it.
+pub struct Siblings<'a> {
+    tree: &'a NodeTree,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Siblings<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.tree.next_sibling(id);
+        Some(id)
+    }
+}
+
+/// An iterator over the descendants of a node in document order.
+pub struct Descendants<'a> {
+    tree: &'a NodeTree,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.tree.first_child(id).or_else(|| {
+            // go up until there is a next sibling, stopping at the root
+            let mut n = id;
+            loop {
+                if n == self.root {
+                    return None;
+                }
+                if let Some(s) = self.tree.next_sibling(n) {
+                    return Some(s);
+                }
+                n = self.tree.parent(n)?;
+            }
+        });
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod node_tree_tests {
+    use super::*;
+
+    #[test]
+    fn navigation_test() {
+        let doc = "<div><p>a<b>b</b></p><p>c</p></div><i>d</i>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let tree = NodeTree::from_document(&doc);
+        assert_eq!(tree.to_document(), doc);
+        let div = tree.node_at(&[0]).unwrap();
+        let b = tree.node_at(&[0, 0, 1]).unwrap();
+        assert_eq!(tree.tag(b).unwrap().tag, "b");
+        assert_eq!(tree.path(b), Some(vec![0, 0, 1]));
+        assert_eq!(
+            tree.ancestors(b).collect::<Vec<_>>(),
+            vec![tree.node_at(&[0, 0]).unwrap(), div]
+        );
+        let texts: Vec<String> = tree
+            .descendants(div)
+            .map(|d| tree.get(d).node().text())
+            .collect();
+        assert_eq!(texts, vec!["", "a", "", "b", "", "c"]);
+        assert_eq!(tree.text(div), "abc");
+        assert_eq!(tree.roots().count(), 2);
+        assert_eq!(
+            tree.previous_sibling(tree.node_at(&[1]).unwrap()),
+            Some(div)
+        );
+        assert_eq!(tree.last_child(div), tree.node_at(&[0, 1]));
+        assert_eq!(tree.node_at(&[0, 2]), None);
+    }
+
+    #[test]
+    fn mutation_test() {
+        let doc = "<ul><li>a</li><li>b</li></ul><p>c</p>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let mut tree = NodeTree::from_document(&doc);
+        let ul = tree.node_at(&[0]).unwrap();
+        let a = tree.node_at(&[0, 0]).unwrap();
+        let p = tree.node_at(&[1]).unwrap();
+        tree.detach(ul);
+        assert!(!tree.is_attached(a));
+        assert_eq!(tree.path(a), None);
+        assert_eq!(tree.to_document().to_html_string(), "<p>c</p>");
+        tree.append_child(p, ul).unwrap();
+        assert!(tree.is_attached(a));
+        assert_eq!(tree.path(a), Some(vec![0, 1, 0]));
+
+        // errors leave the tree as it was
+        let text = tree.first_child(p).unwrap();
+        assert!(tree.append_child(a, p).is_err());
+        assert!(tree
+            .append_child(text, HtmlNode::Text("x".to_owned()))
+            .is_err());
+        assert!(tree.insert_before(ul, ul).is_err());
+        assert_eq!(
+            tree.to_document().to_html_string(),
+            "<p>c<ul><li>a</li><li>b</li></ul></p>"
+        );
+
+        let new = HtmlNode::Tag(HtmlTag::new("hr"));
+        let hr = tree.replace_with(text, new).unwrap();
+        assert_eq!(tree.path(hr), Some(vec![0, 0]));
+        assert!(!tree.is_attached(text));
+        let copy = tree.deep_clone(p);
+        tree.append_root(copy);
+        if let HtmlNode::Tag(tag) = tree.node_mut(a) {
+            tag.classes.push("x".to_owned());
+        }
+        tree.append_child(a, HtmlNode::Text("z".to_owned()))
+            .unwrap();
+        assert_eq!(
+            tree.to_document().to_html_string(),
+            "<p><hr></hr><ul><li class=\"x\">az</li><li>b</li></ul></p><p><hr></hr><ul><li>a</li><li>b</li></ul></p>"
+        );
+        tree.insert_before(p, copy).unwrap();
+        assert_eq!(tree.roots().collect::<Vec<_>>(), vec![copy, p]);
+        // the 7 parsed nodes, the hr, the 7 copied nodes and the z, but not the x
+        // which could not be added
+        assert_eq!(tree.len(), 16);
+    }
+}
@@ -0,0 +1,493 @@
+//! Turning fragments and partial documents into complete documents.
+//!
+//! Rewriters often start from a snippet such as `<p>Hello</p>` but must write out
+//! a whole page. [`HtmlDocument::normalize_document`] adds whatever is missing of
+//! the doctype, `<html>`, `<head>`, `<body>` and a `<meta charset>`, and the
+//! `ensure_` helpers each add one part, returning the element so more can be added
+//! to it. Existing elements are never replaced.
+//!
+//! When a `<head>` is added the metadata elements at the top level (`<title>`,
+//! `<meta>`, `<link>`, `<style>` and `<base>`) are moved into it, and when a
+//! `<body>` is added everything else is moved into that. Comments before the first
+//! element stay outside of `<html>`.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! let mut doc = "<title>Hi</title><p>Hello</p>".parse::<HtmlDocument>().unwrap();
+//! doc.normalize_document();
+//! assert_eq!(
+//!     doc.to_html_string(),
+//!     concat!(
+//!         r#"<!DOCTYPE html><html><head><meta charset="utf-8"></meta>"#,
+//!         "<title>Hi</title></head><body><p>Hello</p></body></html>"
+//!     )
+//! );
+//! ```
+//!
+//! # Canonical form for diffs
+//!
+//! Generated HTML often changes in ways which do not matter, such as the order of
+//! attributes or the indentation, and this hides the real changes when the output
+//! is diffed. [`normalize_for_diff`] writes a document in a form which only
+//! changes when the content does, as set by the [`DiffRules`]. Unlike pretty
+//! printing nothing is indented, so moving an element into a new parent only
+//! changes the lines of the parent.
+//!
+//! ```
+//! use hb_html::normalize::{normalize_for_diff, DiffRules};
+//! use hb_html::objects::HtmlDocument;
+//! let doc = "<ul class=\"b a b\">\n  <li title=t id=x>One  <br>two</li><!-- generated -->\n</ul>"
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! assert_eq!(
+//!     normalize_for_diff(&doc, &DiffRules::default()),
+//!     "<ul class=\"a b\">\n<li id=\"x\" title=\"t\">One <br>two</li>\n</ul>\n"
+//! );
+//! ```
+use crate::content_model::{element_info, ContentCategory};
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::serialize::{AttributeOrder, SerializeOptions, VoidElements};
+
+/// Finds the index of the first tag in the nodes with the name, ignoring case.
+pub(crate) fn find_tag(nodes: &[HtmlNode], name: &str) -> Option<usize> {
+    nodes
+        .iter()
+        .position(|n| matches!(n, HtmlNode::Tag(t) if t.tag.eq_ignore_ascii_case(name)))
+}
+
+fn as_tag_mut(node: &mut HtmlNode) -> &mut HtmlTag {
+    match node {
+        HtmlNode::Tag(t) => t,
+        _ => unreachable!("the node was found as a tag"),
+    }
+}
+
+/// Checks whether the node is an element which can only be in the `<head>`.
+fn is_head_only(node: &HtmlNode) -> bool {
+    match node {
+        HtmlNode::Tag(t) => element_info(&t.tag.to_ascii_lowercase())
+            .is_some_and(|e| e.categories == [ContentCategory::Metadata]),
+        _ => false,
+    }
+}
+
+/// Checks whether the tag declares the character encoding of the document.
+fn is_charset_meta(node: &HtmlNode) -> bool {
+    match node {
+        HtmlNode::Tag(t) if t.tag.eq_ignore_ascii_case("meta") => {
+            t.attributes.contains_key("charset")
+                || t.attributes
+                    .get("http-equiv")
+                    .is_some_and(|v| v.eq_ignore_ascii_case("content-type"))
+        }
+        _ => false,
+    }
+}
+
+/// Finds the index after the `<meta charset>` at the start of the `<head>`
+/// contents, or 0 if there is not one, where new metadata elements go.
+pub(crate) fn after_charset(head: &HtmlTag) -> usize {
+    head.contents
+        .iter()
+        .position(|n| matches!(n, HtmlNode::Tag(t) if t.tag.eq_ignore_ascii_case("meta") && t.attributes.contains_key("charset")))
+        .map_or(0, |i| i + 1)
+}
+
+impl HtmlDocument {
+    pub fn set_doctype<T: Into<String>>(&mut self, doctype: T) {
+        self.doctype = doctype.into();
+    }
+
+    /// Gets the path of the `<head>` element without adding it, looking at the top
+    /// level and inside `<html>`.
+    pub fn head_path(&self) -> Option<NodePath> {
+        let mut path = vec![];
+        let mut nodes = &self.nodes;
+        if let Some(html) = find_tag(nodes, "html") {
+            path.push(html);
+            nodes = match &nodes[html] {
+                HtmlNode::Tag(t) => &t.contents,
+                _ => unreachable!("the node was found as a tag"),
+            };
+        }
+        path.push(find_tag(nodes, "head")?);
+        Some(path)
+    }
+
+    /// Gets the `<html>` element, adding it around the top level nodes if there is
+    /// not one.
+    pub fn ensure_html(&mut self) -> &mut HtmlTag {
+        let index = match find_tag(&self.nodes, "html") {
+            Some(i) => i,
+            None => {
+                // leading comments and whitespace stay before <html>
+                let start = self
+                    .nodes
+                    .iter()
+                    .position(|n| match n {
+                        HtmlNode::Comment(_) => false,
+                        HtmlNode::Text(t) => !t.trim().is_empty(),
+                        _ => true,
+                    })
+                    .unwrap_or(self.nodes.len());
+                let contents = self.nodes.split_off(start);
+                self.nodes
+                    .push(HtmlNode::Tag(HtmlTag::new("html").contents(contents)));
+                start
+            }
+        };
+        as_tag_mut(&mut self.nodes[index])
+    }
+
+    /// Gets the `<head>` element, adding it as the first child of `<html>` if there
+    /// is not one.
+    pub fn ensure_head(&mut self) -> &mut HtmlTag {
+        let html = self.ensure_html();
+        let index = match find_tag(&html.contents, "head") {
+            Some(i) => i,
+            None => {
+                let (metadata, others) = std::mem::take(&mut html.contents)
+                    .into_iter()
+                    .partition(is_head_only);
+                html.contents = others;
+                html.contents
+                    .insert(0, HtmlNode::Tag(HtmlTag::new("head").contents(metadata)));
+                0
+            }
+        };
+        as_tag_mut(&mut html.contents[index])
+    }
+
+    /// Gets the `<body>` element, adding it after the `<head>` if there is not one.
+    pub fn ensure_body(&mut self) -> &mut HtmlTag {
+        self.ensure_head();
+        let html = self.ensure_html();
+        let index = match find_tag(&html.contents, "body") {
+            Some(i) => i,
+            None => {
+                let head = find_tag(&html.contents, "head").expect("the head was just added");
+                let contents: Vec<HtmlNode> = html.contents.drain(head + 1..).collect();
+                html.contents
+                    .push(HtmlNode::Tag(HtmlTag::new("body").contents(contents)));
+                head + 1
+            }
+        };
+        as_tag_mut(&mut html.contents[index])
+    }
+
+    /// Adds a `<meta charset>` with the encoding to the start of the `<head>` if the
+    /// document does not declare its encoding.
+    pub fn ensure_charset(&mut self, charset: &str) {
+        let head = self.ensure_head();
+        if !head.contents.iter().any(is_charset_meta) {
+            let meta = HtmlTag::new("meta").attributes(vec![("charset", charset)]);
+            head.contents.insert(0, HtmlNode::Tag(meta));
+        }
+    }
+
+    /// Makes the document complete, adding the html doctype, the `<html>`, `<head>`
+    /// and `<body>` elements and a UTF-8 `<meta charset>` if they are missing.
+    pub fn normalize_document(&mut self) {
+        if self.doctype.is_empty() {
+            self.set_doctype("html");
+        }
+        self.ensure_body();
+        self.ensure_charset("utf-8");
+    }
+}
+
+/// The changes [`normalize_for_diff`] makes to the document before writing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRules {
+    /// Write the attributes of each element sorted by name.
+    pub sort_attributes: bool,
+    /// Sort the classes of each element and remove duplicates.
+    pub sort_classes: bool,
+    /// Collapse runs of whitespace in text to one space and remove the whitespace
+    /// next to block elements. The text of `<pre>`, `<textarea>`, `<script>` and
+    /// `<style>` is kept as it is.
+    pub collapse_whitespace: bool,
+    pub strip_comments: bool,
+    /// How elements like `<br>` are written.
+    pub void_elements: VoidElements,
+    /// Put each block element on its own line.
+    pub line_breaks: bool,
+}
+
+impl Default for DiffRules {
+    fn default() -> Self {
+        DiffRules {
+            sort_attributes: true,
+            sort_classes: true,
+            collapse_whitespace: true,
+            strip_comments: true,
+            void_elements: VoidElements::StartTag,
+            line_breaks: true,
+        }
+    }
+}
+
+impl DiffRules {
+    pub fn new() -> DiffRules {
+        DiffRules::default()
+    }
+
+    pub fn sort_attributes(mut self, sort_attributes: bool) -> DiffRules {
+        self.sort_attributes = sort_attributes;
+        self
+    }
+
+    pub fn sort_classes(mut self, sort_classes: bool) -> DiffRules {
+        self.sort_classes = sort_classes;
+        self
+    }
+
+    pub fn collapse_whitespace(mut self, collapse_whitespace: bool) -> DiffRules {
+        self.collapse_whitespace = collapse_whitespace;
+        self
+    }
+
+    pub fn strip_comments(mut self, strip_comments: bool) -> DiffRules {
+        self.strip_comments = strip_comments;
+        self
+    }
+
+    pub fn void_elements(mut self, void_elements: VoidElements) -> DiffRules {
+        self.void_elements = void_elements;
+        self
+    }
+
+    pub fn line_breaks(mut self, line_breaks: bool) -> DiffRules {
+        self.line_breaks = line_breaks;
+        self
+    }
+}
+
+/// Checks whether the element is not phrasing content, so the whitespace around
+/// it does not show. Unknown elements are treated as phrasing.
+fn is_block_tag(tag: &str) -> bool {
+    element_info(&tag.to_ascii_lowercase())
+        .is_some_and(|e| !e.categories.contains(&ContentCategory::Phrasing))
+}
+
+fn is_block(node: &HtmlNode) -> bool {
+    matches!(node, HtmlNode::Tag(t) if is_block_tag(&t.tag))
+}
+
+/// Checks whether the whitespace in the text of the element must be kept.
+fn keeps_whitespace(tag: &HtmlTag) -> bool {
+    ["pre", "textarea", "script", "style"]
+        .iter()
+        .any(|t| tag.tag.eq_ignore_ascii_case(t))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut last_space = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !last_space {
+                collapsed.push(' ');
+            }
+            last_space = true;
+        } else {
+            collapsed.push(c);
+            last_space = false;
+        }
+    }
+    collapsed
+}
+
+/// Applies the rules to the nodes, which are the contents of a block element or
+/// the top level when `in_block` is true.
+fn normalize_nodes(nodes: &mut Vec<HtmlNode>, rules: &DiffRules, in_block: bool) {
+    if rules.strip_comments {
+        nodes.retain(|n| !matches!(n, HtmlNode::Comment(_)));
+    }
+    for node in nodes.iter_mut() {
+        if let HtmlNode::Tag(tag) = node {
+            if rules.sort_classes {
+                tag.classes.sort();
+                tag.classes.dedup();
+            }
+            if !keeps_whitespace(tag) {
+                normalize_nodes(&mut tag.contents, rules, is_block_tag(&tag.tag));
+            }
+        }
+    }
+    if rules.collapse_whitespace {
+        for i in 0..nodes.len() {
+            let after_block = if i == 0 {
+                in_block
+            } else {
+                is_block(&nodes[i - 1])
+            };
+            let before_block = nodes.get(i + 1).map_or(in_block, is_block);
+            if let HtmlNode::Text(text) = &mut nodes[i] {
+                let mut collapsed = collapse_whitespace(text);
+                if before_block {
+                    collapsed.truncate(collapsed.trim_end().len());
+                }
+                if after_block {
+                    collapsed = collapsed.trim_start().to_owned();
+                }
+                *text = collapsed;
+            }
+        }
+        nodes.retain(|n| !matches!(n, HtmlNode::Text(t) if t.is_empty()));
+    }
+    if rules.line_breaks && nodes.iter().any(is_block) {
+        let mut lines = Vec::with_capacity(nodes.len() * 2);
+        for node in nodes.drain(..) {
+            let block = is_block(&node);
+            if block && !matches!(lines.last(), Some(HtmlNode::Text(t)) if t.ends_with('\n')) {
+                lines.push(HtmlNode::new_text("\n"));
+            }
+            lines.push(node);
+            if block {
+                lines.push(HtmlNode::new_text("\n"));
+            }
+        }
+        *nodes = lines;
+    }
+}
+
+/// Writes the document in a canonical form for diffing, changing a copy of the
+/// document by the rules. The output ends with a line break.
+pub fn normalize_for_diff(doc: &HtmlDocument, rules: &DiffRules) -> String {
+    let mut doc = doc.clone();
+    normalize_nodes(&mut doc.nodes, rules, true);
+    let order = match rules.sort_attributes {
+        true => AttributeOrder::Alphabetical,
+        false => AttributeOrder::Stored,
+    };
+    let options = SerializeOptions::new()
+        .attribute_order(order)
+        .void_elements(rules.void_elements);
+    let mut out = String::new();
+    if !doc.doctype.is_empty() {
+        out.push_str(&format!("<!DOCTYPE {}>\n", doc.doctype));
+    }
+    let nodes: String = doc
+        .nodes
+        .iter()
+        .map(|n| n.to_html_string_with(&options))
+        .collect();
+    let nodes = nodes.trim_start_matches('\n');
+    out.push_str(nodes);
+    if !nodes.is_empty() && !nodes.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod normalize_tests {
+    use super::*;
+    use crate::serialize::{AttributeOrder, SerializeOptions};
+
+    #[test]
+    fn normalize_fragment_test() {
+        let mut doc = "<!-- x --><link rel=icon href=/i.png><h1>A</h1><script src=a.js></script>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        doc.normalize_document();
+        let options = SerializeOptions::new().attribute_order(AttributeOrder::Alphabetical);
+        assert!(matches!(doc.nodes[0], HtmlNode::Comment(_)));
+        assert_eq!(
+            doc.nodes[1].to_html_string_with(&options),
+            concat!(
+                r#"<html><head><meta charset="utf-8"></meta>"#,
+                r#"<link href="/i.png" rel="icon"></link></head>"#,
+                r#"<body><h1>A</h1><script src="a.js"></script></body></html>"#
+            )
+        );
+        assert_eq!(doc.doctype, "html");
+        // normalizing again changes nothing
+        let normalized = doc.clone();
+        doc.normalize_document();
+        assert_eq!(doc, normalized);
+    }
+
+    #[test]
+    fn ensure_existing_parts_test() {
+        let mut doc = r#"<!DOCTYPE html5><html><body><p>x</p></body><head><meta http-equiv="Content-Type" content="text/html"></head></html>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let expected = doc.clone();
+        doc.normalize_document();
+        // the existing parts are kept where they are
+        assert_eq!(doc, expected);
+        assert_eq!(doc.head_path(), Some(vec![0, 1]));
+        doc.ensure_body().contents.push(HtmlNode::new_text("y"));
+        assert_eq!(doc.ensure_body().text(), "xy");
+        doc.set_doctype("html");
+        assert_eq!(doc.doctype, "html");
+    }
+
+    #[test]
+    fn ensure_head_only_test() {
+        let mut doc = HtmlDocument::new();
+        assert_eq!(doc.head_path(), None);
+        doc.ensure_head()
+            .contents
+            .push(HtmlNode::Tag(HtmlTag::new("title")));
+        assert_eq!(
+            doc.to_html_string(),
+            "<html><head><title></title></head></html>"
+        );
+        doc.ensure_body();
+        assert_eq!(
+            doc.to_html_string(),
+            "<html><head><title></title></head><body></body></html>"
+        );
+    }
+
+    #[test]
+    fn normalize_for_diff_test() {
+        let a = concat!(
+            "<!DOCTYPE html><html><body>\n  <div class=\"x y\" id=\"d\">\n",
+            "    <p>Some   <em>text</em>\n here</p><pre>  keep\n  this </pre>\n",
+            "  </div><!-- a -->\n</body></html>"
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let b = concat!(
+            "<!DOCTYPE html><html><body><div id=\"d\" class=\"y x\"><p>Some <em>text</em> here</p>",
+            "<pre>  keep\n  this </pre></div><!-- b --></body></html>"
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let expected = concat!(
+            "<!DOCTYPE html>\n<html>\n<body>\n<div class=\"x y\" id=\"d\">\n",
+            "<p>Some <em>text</em> here</p>\n<pre>  keep\n  this </pre>\n</div>\n</body>\n</html>\n"
+        );
+        assert_eq!(normalize_for_diff(&a, &DiffRules::new()), expected);
+        assert_eq!(normalize_for_diff(&b, &DiffRules::new()), expected);
+    }
+
+    #[test]
+    fn diff_rules_test() {
+        let doc = "<p>a  <br>\n<img src=i.png><!-- c --></p>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let rules = DiffRules::new()
+            .collapse_whitespace(false)
+            .strip_comments(false)
+            .line_breaks(false)
+            .void_elements(VoidElements::SelfClosing);
+        let written = normalize_for_diff(&doc, &rules);
+        assert!(written.starts_with("<p>a  <br/>\n<img src=\"i.png\"/><!-- "));
+        assert!(written.ends_with("</p>\n"));
+        assert_eq!(
+            normalize_for_diff(&doc, &DiffRules::new()),
+            "<p>a <br> <img src=\"i.png\"></p>\n"
+        );
+        assert_eq!(
+            normalize_for_diff(&HtmlDocument::new(), &DiffRules::new()),
+            ""
+        );
+    }
+}
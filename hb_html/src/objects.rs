@@ -1,6 +1,15 @@
-use crate::error::ParseHtmlError;
-use crate::parsing::{parse_css_selector_rule, parse_html_tag, ParsedTagType};
-use crate::querying::{HtmlQuery, HtmlQueryable};
+use crate::error::{ParseErrorKind, ParseHtmlError};
+use crate::metrics;
+use crate::options::{NoscriptMode, ParseOptions, ParseProfile, TreeConstruction};
+use crate::parsing::{
+    end_tag_strategy, parse_attibute_value, parse_css_selector_rule, parse_html_tag,
+    parse_html_tag_with, push_element, read_template, OpenElements, ParsedTagType, SpanRecorder,
+};
+use crate::querying::{HtmlQuery, HtmlQueryResult, HtmlQueryable};
+use crate::repair::RepairStrategy;
+use crate::serialize::{SerializeOptions, Serializer, MARKUP_OPTIONS};
+use crate::source_map::NodeSpan;
+use crate::tree_construction::stray_end_tag_node;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
@@ -104,6 +113,13 @@ impl HtmlTag {
         }
     }
 
+    /// Checks whether the tag is a component of a framework such as Vue or React
+    /// rather than a HTML element, which is the case when its name starts with an
+    /// uppercase letter or contains a `-` (eg `<MyButton>` or `<my-button>`).
+    pub fn is_component(&self) -> bool {
+        self.tag.starts_with(|c: char| c.is_ascii_uppercase()) || self.tag.contains('-')
+    }
+
     pub fn tag<T: Into<String>>(mut self, tag: T) -> HtmlTag {
         self.tag = tag.into();
         self
@@ -148,14 +164,14 @@ impl FromStr for HtmlTag {
             if ch == '<' {
                 if buffer.len() > 0 {
                     return Err(ParseHtmlError::new(format!(
-                        "Found text {} before start of the tag.",
+                        "Found text '{}' before start of the tag.",
                         buffer
                     )));
                 }
                 match parse_html_tag(&mut chs)? {
                     ParsedTagType::EndTag(t) => {
                         return Err(ParseHtmlError::new(format!(
-                            "Found end tag {} before start tag.",
+                            "Found end tag '{}' before start tag.",
                             t
                         )))
                     }
@@ -165,20 +181,20 @@ impl FromStr for HtmlTag {
                         }
                         Some(t) => {
                             return Err(ParseHtmlError::new(format!(
-                                "Found second tag {:?} after the first tag {:?}.",
+                                "Found second tag '{:?}' after the first tag '{:?}'.",
                                 tag, t
                             )))
                         }
                     },
                     ParsedTagType::Comment(c) => {
                         return Err(ParseHtmlError::new(format!(
-                            "Found HTML comment {} before a tag",
+                            "Found HTML comment '{}' before a tag",
                             c
                         )))
                     }
                     ParsedTagType::DocType(doctype) => {
                         return Err(ParseHtmlError::new(format!(
-                            "Found HTML DOCTYPE tag {} before a standard HTML tag",
+                            "Found HTML DOCTYPE tag '{}' before a standard HTML tag",
                             doctype
                         )))
                     }
@@ -203,32 +219,38 @@ impl fmt::Display for HtmlTag {
 }
 
 impl HtmlTag {
-    /// Converts the HtmlTag into a string formatted as HTML.
-    fn to_html_string(&self) -> String {
-        let mut res = format!("<{}", self.tag);
-        if self.ids.len() > 0 {
-            res.push_str(" id=\"");
-            res.push_str(self.ids.join(" ").as_str());
-            res.push_str("\"")
-        }
-        if self.classes.len() > 0 {
-            res.push_str(" class=\"");
-            res.push_str(self.classes.join(" ").as_str());
-            res.push_str("\"")
-        }
-        if self.attributes.len() > 0 {
-            for attr in self.attributes.keys() {
-                res.push_str(format!(" {}=\"{}\"", attr, self.attributes[attr]).as_str());
-            }
+    /// Gets the value of the attribute with the name provided, including the id and
+    /// class attributes which are joined back into a space separated string.
+    pub fn get_attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "id" if !self.ids.is_empty() => Some(self.ids.join(" ")),
+            "class" if !self.classes.is_empty() => Some(self.classes.join(" ")),
+            "id" | "class" => None,
+            _ => self.attributes.get(name).cloned(),
         }
-        res.push_str(">");
-        if self.contents.len() > 0 {
-            for content in &self.contents {
-                res.push_str(content.to_html_string().as_str())
+    }
+
+    /// Sets the value of the attribute with the name provided. The id and class
+    /// attributes are split on whitespace and stored in the ids and classes lists.
+    pub fn set_attribute<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        let name = name.into();
+        let value = value.into();
+        match name.as_str() {
+            "id" => self.ids = parse_attibute_value(value),
+            "class" => self.classes = parse_attibute_value(value),
+            _ => {
+                self.attributes.insert(name, value);
             }
         }
-        res.push_str(format!("</{}>", self.tag).as_str());
-        res
+    }
+
+    /// Removes the attribute with the name provided, returning true if it was present.
+    pub fn remove_attribute(&mut self, name: &str) -> bool {
+        match name {
+            "id" => !std::mem::take(&mut self.ids).is_empty(),
+            "class" => !std::mem::take(&mut self.classes).is_empty(),
+            _ => self.attributes.remove(name).is_some(),
+        }
     }
 
     pub fn text(&self) -> String {
@@ -250,6 +272,9 @@ pub enum HtmlNode {
     Comment(String),
     /// Text content.
     Text(String),
+    /// A template tag such as `{{ name }}` kept as it was written, see
+    /// [`ParseOptions`].
+    Template(String),
 }
 
 impl HtmlQueryable for Vec<HtmlNode> {
@@ -261,10 +286,61 @@ impl HtmlQueryable for Vec<HtmlNode> {
 impl HtmlNode {
     /// Converts the HtmlNode into a string formatted as HTML.
     pub fn to_html_string(&self) -> String {
-        match &self {
-            HtmlNode::Comment(c) => format!("<!-- {} --!>", c),
-            HtmlNode::Tag(t) => t.to_html_string(),
-            HtmlNode::Text(t) => t.to_string(),
+        self.to_html_string_with(&SerializeOptions::default())
+    }
+
+    /// Converts the HtmlNode into a string formatted as HTML using the options.
+    pub fn to_html_string_with(&self, options: &SerializeOptions) -> String {
+        let mut serializer = Serializer::new(options);
+        serializer.write_node(self);
+        serializer.finish()
+    }
+
+    /// Converts the node into indented HTML which is easier to read, with each tag
+    /// and piece of text on its own line, using the `indent`, `max_line_width` and
+    /// `preserve_whitespace_tags` of the options. The whitespace in text is
+    /// collapsed, which changes how some documents are displayed, apart from in
+    /// `<pre>`, `<script>`, `<style>` and `<textarea>` elements, which are written
+    /// as they are.
+    ///
+    /// ```
+    /// use hb_html::objects::{HtmlNode, HtmlTag};
+    /// use hb_html::serialize::{SerializeOptions, VoidElements};
+    /// let tag = "<div><h1>Title</h1><p>Some   text<br></p><pre> a\n  b</pre></div>"
+    ///     .parse::<HtmlTag>()
+    ///     .unwrap();
+    /// let node = HtmlNode::Tag(tag);
+    /// let options = SerializeOptions::new()
+    ///     .indent(2)
+    ///     .void_elements(VoidElements::StartTag);
+    /// assert_eq!(
+    ///     node.to_pretty_html(&options),
+    ///     "<div>\n  <h1>Title</h1>\n  <p>\n    Some text\n    <br>\n  </p>\n  <pre> a\n  b</pre>\n</div>"
+    /// );
+    /// ```
+    pub fn to_pretty_html(&self, options: &SerializeOptions) -> String {
+        let mut serializer = Serializer::new(options);
+        serializer.write_pretty_node(self, 0);
+        serializer.finish()
+    }
+
+    /// Converts the node, including its start and end tags, into HTML in the same
+    /// way as [`HtmlDocument::to_html`].
+    pub fn outer_html(&self) -> String {
+        self.to_html_string_with(&MARKUP_OPTIONS)
+    }
+
+    /// Converts the contents of the node into HTML in the same way as
+    /// [`HtmlDocument::to_html`]. Only tags have contents, so this is empty for
+    /// the other nodes.
+    pub fn inner_html(&self) -> String {
+        match self {
+            HtmlNode::Tag(t) => {
+                let mut serializer = Serializer::new(&MARKUP_OPTIONS);
+                serializer.write_contents(t);
+                serializer.finish()
+            }
+            _ => String::new(),
         }
     }
 
@@ -281,7 +357,7 @@ impl HtmlNode {
     pub fn text(&self) -> String {
         match self {
             HtmlNode::Tag(t) => t.text(),
-            HtmlNode::Comment(_) => String::new(),
+            HtmlNode::Comment(_) | HtmlNode::Template(_) => String::new(),
             HtmlNode::Text(t) => t.clone(),
         }
     }
@@ -305,13 +381,19 @@ mod html_tag_tests {
         ];
         for test in &tests {
             assert_eq!(
-                test.parse::<HtmlTag>().unwrap().to_html_string().as_str(),
+                HtmlNode::Tag(test.parse::<HtmlTag>().unwrap())
+                    .to_html_string()
+                    .as_str(),
                 *test
             );
         }
     }
 }
 
+/// The location of a node in a [`HtmlDocument`], stored as the index of the node
+/// in each level of contents going down the tree starting with the top level nodes.
+pub type NodePath = Vec<usize>;
+
 #[derive(Debug, Clone, PartialEq)]
 /// Represents a whole HTML document.
 ///
@@ -354,6 +436,79 @@ impl HtmlDocument {
         query.find_str(selector);
         query
     }
+
+    /// Gets all of the nodes which match the CSS selector, in document order.
+    /// Returns an error if the selector can not be parsed.
+    pub fn select(&self, selector: &str) -> Result<Vec<&HtmlNode>, ParseHtmlError> {
+        let selector = CssSelector::from_str(selector)?;
+        let mut query = self.query();
+        query.find(&selector);
+        Ok(query.results.iter().filter_map(|r| r.get_node()).collect())
+    }
+
+    /// Gets the first node which matches the CSS selector, stopping the search as
+    /// soon as it is found.
+    /// Returns an error if the selector can not be parsed.
+    pub fn select_first(&self, selector: &str) -> Result<Option<&HtmlNode>, ParseHtmlError> {
+        let selector = CssSelector::from_str(selector)?;
+        if self.nodes.is_empty() {
+            return Ok(None);
+        }
+        let mut res = HtmlQueryResult {
+            path: vec![(&self.nodes, 0)],
+        };
+        loop {
+            if res.matches(&selector) {
+                return Ok(res.get_node());
+            }
+            if res.walk_next().is_none() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Gets the node found by following the child indexes in the path provided.
+    /// Returns None if the path is empty or does not point at a node.
+    pub fn node_at(&self, path: &[usize]) -> Option<&HtmlNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.nodes.get(*first)?;
+        for i in rest {
+            node = match node {
+                HtmlNode::Tag(t) => t.contents.get(*i)?,
+                _ => return None,
+            };
+        }
+        Some(node)
+    }
+
+    /// Gets a mutable reference to the node found by following the child indexes
+    /// in the path provided.
+    /// Returns None if the path is empty or does not point at a node.
+    pub fn node_at_mut(&mut self, path: &[usize]) -> Option<&mut HtmlNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.nodes.get_mut(*first)?;
+        for i in rest {
+            node = match node {
+                HtmlNode::Tag(t) => t.contents.get_mut(*i)?,
+                _ => return None,
+            };
+        }
+        Some(node)
+    }
+
+    /// Gets the list of nodes which contains the node pointed to by the path, this
+    /// is either the top level nodes of the document or the contents of a tag.
+    /// Returns None if the path is empty or the parent of the path is not a tag.
+    pub fn siblings_at_mut(&mut self, path: &[usize]) -> Option<&mut Vec<HtmlNode>> {
+        let (_, parent) = path.split_last()?;
+        if parent.is_empty() {
+            return Some(&mut self.nodes);
+        }
+        match self.node_at_mut(parent)? {
+            HtmlNode::Tag(t) => Some(&mut t.contents),
+            _ => None,
+        }
+    }
 }
 
 impl HtmlQueryable for HtmlDocument {
@@ -366,39 +521,258 @@ impl HtmlQueryable for HtmlDocument {
 impl FromStr for HtmlDocument {
     type Err = ParseHtmlError;
     fn from_str(html_str: &str) -> Result<Self, <Self as std::str::FromStr>::Err> {
-        let mut doc = HtmlDocument::new();
-        let mut chs = html_str.chars();
-        let mut buffer = String::new();
-        while let Some(ch) = chs.next() {
-            if ch == '<' {
-                if buffer.len() > 0 {
-                    doc.nodes.push(HtmlNode::Text(buffer));
-                    buffer = String::new();
+        parse_document(html_str, &ParseOptions::default(), None)
+    }
+}
+
+impl HtmlDocument {
+    /// Parses the document using the options.
+    pub fn parse_with(
+        html_str: &str,
+        options: &ParseOptions,
+    ) -> Result<HtmlDocument, ParseHtmlError> {
+        parse_document(html_str, options, None)
+    }
+
+    /// Parses the text of the `<noscript>` elements of a document which was parsed
+    /// with [`NoscriptMode::RawText`] as markup using the options, so that queries
+    /// and extraction see the fallbacks in them. Returns an error if the text of
+    /// one of them can not be parsed, in which case the elements before it have
+    /// been expanded.
+    pub fn expand_noscript(&mut self, options: &ParseOptions) -> Result<(), ParseHtmlError> {
+        let options = options.clone().noscript(NoscriptMode::Markup);
+        expand_noscript_in(&mut self.nodes, &options)
+    }
+}
+
+fn expand_noscript_in(
+    nodes: &mut [HtmlNode],
+    options: &ParseOptions,
+) -> Result<(), ParseHtmlError> {
+    for node in nodes {
+        let HtmlNode::Tag(tag) = node else {
+            continue;
+        };
+        let is_text = tag.contents.iter().all(|n| matches!(n, HtmlNode::Text(_)));
+        if tag.tag.eq_ignore_ascii_case("noscript") && is_text {
+            let text: String = tag.contents.iter().map(HtmlNode::text).collect();
+            // parsed inside the element, as text is not kept at the top level
+            let source = format!("<{0}>{1}</{0}>", tag.tag, text);
+            if let Some(HtmlNode::Tag(parsed)) =
+                parse_document_nodes(&source, options, None)?.nodes.pop()
+            {
+                tag.contents = parsed.contents;
+            }
+        } else {
+            expand_noscript_in(&mut tag.contents, options)?;
+        }
+    }
+    Ok(())
+}
+
+/// Parses the document, also returning the byte range in the string of each of the
+/// top level nodes.
+pub(crate) fn parse_document_with_spans(
+    html_str: &str,
+) -> Result<(HtmlDocument, Vec<std::ops::Range<usize>>), ParseHtmlError> {
+    let (doc, source_map) = HtmlDocument::parse_with_source_map(html_str)?;
+    let spans = source_map
+        .iter()
+        .filter(|s| s.path.len() == 1)
+        .map(|s| s.range.clone())
+        .collect();
+    Ok((doc, spans))
+}
+
+/// Parses the document, adding the span of each node to the recorder if one is provided.
+/// Every parse of a document goes through here, so it is where the metrics are recorded.
+pub(crate) fn parse_document(
+    html_str: &str,
+    options: &ParseOptions,
+    recorder: Option<&mut SpanRecorder>,
+) -> Result<HtmlDocument, ParseHtmlError> {
+    let previous = metrics::start_document();
+    let mut result = parse_document_nodes(html_str, options, recorder);
+    if options.verify_lossless {
+        result = result.and_then(|doc| {
+            // decoded text is written back escaped, which is not always how it was
+            // written, so the check is on the document with its references kept
+            if options.decode_entities {
+                let raw_options = options.clone().decode_entities(false);
+                verify_lossless(
+                    html_str,
+                    &parse_document_nodes(html_str, &raw_options, None)?,
+                )?;
+            } else {
+                verify_lossless(html_str, &doc)?;
+            }
+            Ok(doc)
+        });
+    }
+    metrics::record_parse(html_str.len(), &result, previous);
+    result
+}
+
+/// Checks the document, parsed without decoding its references, is written back the
+/// same as the source it was parsed from.
+fn verify_lossless(html_str: &str, doc: &HtmlDocument) -> Result<(), ParseHtmlError> {
+    let output = doc.to_html_string_with(&SerializeOptions::new().escape(false));
+    let diverges = html_str
+        .char_indices()
+        .zip(output.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((i, _), _)| i)
+        .or_else(|| (html_str.len() != output.len()).then(|| html_str.len().min(output.len())));
+    let offset = match diverges {
+        None => return Ok(()),
+        Some(offset) => offset,
+    };
+    let snippet = |s: &str| s.chars().take(20).collect::<String>();
+    Err(ParseHtmlError::with_msg(format!(
+        "the document is not written back the same as its source, at byte {} the source has '{}' but the output has '{}'",
+        offset,
+        snippet(&html_str[offset..]),
+        snippet(output.get(offset..).unwrap_or_default())
+    ))
+    .with_kind(ParseErrorKind::Lossy)
+    .at_offset(offset))
+}
+
+fn parse_document_nodes(
+    html_str: &str,
+    options: &ParseOptions,
+    mut recorder: Option<&mut SpanRecorder>,
+) -> Result<HtmlDocument, ParseHtmlError> {
+    let mut doc = HtmlDocument::new();
+    // component templates can be fragments with text at the top level
+    let keep_text = options.profile == ParseProfile::Component;
+    let mut chs = html_str.chars();
+    let mut buffer = String::new();
+    let mut text_start = 0;
+    let mut open = OpenElements::new();
+    while let Some(ch) = chs.next() {
+        let tag_start = html_str.len() - chs.as_str().len() - ch.len_utf8();
+        let mut template = String::new();
+        let is_template = read_template(ch, &mut chs, options, &mut template);
+        if !is_template && ch != '<' {
+            if keep_text {
+                if buffer.is_empty() {
+                    text_start = tag_start;
                 }
-                match parse_html_tag(&mut chs)? {
-                    ParsedTagType::EndTag(t) => {
+                buffer.push(ch);
+            }
+            continue;
+        }
+        push_top_level_text(
+            &mut doc,
+            options.decode_text(std::mem::take(&mut buffer)),
+            text_start..tag_start,
+            recorder.as_deref_mut(),
+        );
+        if is_template {
+            if let Some(r) = recorder.as_deref_mut() {
+                r.spans.push(NodeSpan {
+                    path: vec![doc.nodes.len()],
+                    range: tag_start..r.offset(&chs),
+                    content: None,
+                });
+            }
+            doc.nodes.push(HtmlNode::Template(template));
+        } else {
+            if let Some(r) = recorder.as_deref_mut() {
+                r.path = vec![doc.nodes.len()];
+            }
+            let parsed = parse_html_tag_with(&mut chs, options, recorder.as_deref_mut(), &mut open)
+                .map_err(|e| e.at_offset(html_str.len() - chs.as_str().len()))?;
+            match parsed {
+                ParsedTagType::EndTag(t)
+                    if options.tree_construction == TreeConstruction::Browser =>
+                {
+                    // there are no open elements, so the end tag is always stray
+                    if let Some(node) = stray_end_tag_node(&t) {
+                        if let Some(r) = recorder.as_deref_mut() {
+                            let end = r.offset(&chs);
+                            r.spans.push(NodeSpan {
+                                path: vec![doc.nodes.len()],
+                                range: tag_start..end,
+                                content: Some(end..end),
+                            });
+                        }
+                        doc.nodes.push(node);
+                    }
+                }
+                ParsedTagType::EndTag(t) => match end_tag_strategy(&t, options, &open) {
+                    RepairStrategy::Strict => {
                         return Err(ParseHtmlError::new(format!(
-                            "Found end tag {} before start tag.",
+                            "Found end tag '{}' before start tag.",
                             t
-                        )))
+                        ))
+                        .at_offset(tag_start))
                     }
-                    ParsedTagType::NewTag(tag) => doc.nodes.push(HtmlNode::Tag(tag)),
-                    ParsedTagType::Comment(c) => doc.nodes.push(HtmlNode::Comment(c)),
-                    ParsedTagType::DocType(doctype) => {
-                        if doc.doctype.len() > 0 {
-                            //it was already defined..
-                            return Err(ParseHtmlError::new(format!(
-                                "Doctype was defined twice, first {} and second {}",
-                                doc.doctype, doctype,
-                            )));
+                    RepairStrategy::Spec | RepairStrategy::Drop => {}
+                    RepairStrategy::KeepAsText => {
+                        if keep_text {
+                            let end = html_str.len() - chs.as_str().len();
+                            let text = format!("</{}>", t);
+                            push_top_level_text(
+                                &mut doc,
+                                text,
+                                tag_start..end,
+                                recorder.as_deref_mut(),
+                            );
                         }
-                        doc.doctype = doctype;
                     }
+                },
+                ParsedTagType::NewTag(tag) => {
+                    if let Some(r) = recorder.as_deref_mut() {
+                        r.path.clear();
+                    }
+                    push_element(&mut doc.nodes, tag, options, &open, recorder.as_deref_mut());
+                }
+                ParsedTagType::Comment(c) => {
+                    doc.nodes.push(HtmlNode::Comment(c));
+                }
+                ParsedTagType::DocType(doctype) => {
+                    if doc.doctype.len() > 0 {
+                        //it was already defined..
+                        return Err(ParseHtmlError::new(format!(
+                            "Doctype was defined twice, first '{}' and second '{}'",
+                            doc.doctype, doctype,
+                        ))
+                        .at_offset(tag_start));
+                    }
+                    doc.doctype = doctype;
                 }
             }
         }
-        Ok(doc)
     }
+    push_top_level_text(
+        &mut doc,
+        options.decode_text(buffer),
+        text_start..html_str.len(),
+        recorder,
+    );
+    Ok(doc)
+}
+
+/// Adds text to the top level of the document, unless it is only whitespace.
+fn push_top_level_text(
+    doc: &mut HtmlDocument,
+    text: String,
+    range: std::ops::Range<usize>,
+    recorder: Option<&mut SpanRecorder>,
+) {
+    if text.trim().is_empty() {
+        return;
+    }
+    if let Some(r) = recorder {
+        r.spans.push(NodeSpan {
+            path: vec![doc.nodes.len()],
+            range,
+            content: None,
+        });
+    }
+    doc.nodes.push(HtmlNode::Text(text));
 }
 
 //Example complex selector...
@@ -529,15 +903,17 @@ impl FromStr for CssSelector {
         let mut rules: Vec<CssSelectorRule> = vec![];
         for s in selector.split(",").map(|x| x.trim()) {
             //parse rule and add to rules;
-            rules.push(parse_css_selector_rule(s)?);
+            rules.push(
+                parse_css_selector_rule(s).map_err(|e| e.with_kind(ParseErrorKind::Selector))?,
+            );
         }
         if rules.len() > 0 {
             return Ok(CssSelector::Specific(rules));
         }
-        Err(ParseHtmlError::with_msg(format!(
-            "No valid CSS selector found in {}",
-            selector
-        )))
+        Err(
+            ParseHtmlError::with_msg(format!("No valid CSS selector found in '{}'", selector))
+                .with_kind(ParseErrorKind::Selector),
+        )
     }
 }
 
@@ -550,14 +926,98 @@ impl TryFrom<&str> for CssSelector {
         let mut rules: Vec<CssSelectorRule> = vec![];
         for s in selector.split(",").map(|x| x.trim()) {
             //parse rule and add to rules;
-            rules.push(parse_css_selector_rule(s)?);
+            rules.push(
+                parse_css_selector_rule(s).map_err(|e| e.with_kind(ParseErrorKind::Selector))?,
+            );
         }
         if rules.len() > 0 {
             return Ok(CssSelector::Specific(rules));
         }
-        Err(ParseHtmlError::with_msg(format!(
-            "No valid CSS selector found in {}",
-            selector
-        )))
+        Err(
+            ParseHtmlError::with_msg(format!("No valid CSS selector found in '{}'", selector))
+                .with_kind(ParseErrorKind::Selector),
+        )
+    }
+}
+
+#[cfg(test)]
+mod html_document_tests {
+    use super::*;
+
+    #[test]
+    fn noscript_mode_test() {
+        let source = r#"<div><noscript><img src="full.jpg" alt="a &amp; b"> Enable scripts</noscript><img data-src="full.jpg" src="blank.gif"></div>"#;
+        let doc = source.parse::<HtmlDocument>().unwrap();
+        assert_eq!(doc.select("img").unwrap().len(), 2);
+
+        let options = ParseOptions::new().noscript(NoscriptMode::RawText);
+        let mut doc = HtmlDocument::parse_with(source, &options).unwrap();
+        assert_eq!(doc.select("img").unwrap().len(), 1);
+        // the text is kept as it is written, without decoding
+        assert_eq!(
+            doc.nodes[0].text(),
+            r#"<img src="full.jpg" alt="a &amp; b"> Enable scripts"#
+        );
+        let serialize_options = SerializeOptions::new().noscript(NoscriptMode::RawText);
+        assert_eq!(
+            doc.node_at(&[0, 0])
+                .unwrap()
+                .to_html_string_with(&serialize_options),
+            r#"<noscript><img src="full.jpg" alt="a &amp; b"> Enable scripts</noscript>"#
+        );
+        // otherwise it is escaped like any other text
+        assert!(doc
+            .node_at(&[0, 0])
+            .unwrap()
+            .to_html_string()
+            .contains("&lt;img"));
+
+        doc.expand_noscript(&options).unwrap();
+        let images = doc.select("img").unwrap();
+        assert_eq!(images.len(), 2);
+        match images[0] {
+            HtmlNode::Tag(img) => assert_eq!(img.get_attribute("alt"), Some("a & b".to_owned())),
+            _ => panic!("expected a tag"),
+        }
+        assert_eq!(doc.nodes[0].text(), " Enable scripts");
+
+        // markup which is broken inside a noscript does not change the document
+        let source = "<p><noscript></div></noscript>text</p>";
+        assert!(source.parse::<HtmlDocument>().is_err());
+        let mut doc = HtmlDocument::parse_with(source, &options).unwrap();
+        assert_eq!(doc.nodes[0].text(), "</div>text");
+        assert!(doc.expand_noscript(&options).is_err());
+    }
+
+    #[test]
+    fn html_document_error_position_test() {
+        let src = "<div>\n  <p>a</q>\n</div>";
+        let err = src.parse::<HtmlDocument>().unwrap_err();
+        assert_eq!(err.offset(), Some(16));
+        assert_eq!(err.line_col(src), Some((2, 11)));
+        let src = "<p>a</p>\n</div>";
+        let err = src.parse::<HtmlDocument>().unwrap_err();
+        assert_eq!(err.line_col(src), Some((2, 1)));
+        assert_eq!(ParseHtmlError::with_msg("x").line_col(src), None);
+    }
+
+    #[test]
+    fn select_test() {
+        let doc = r#"<div id=main><p class=a>One</p><span><p>Two</p></span></div><p class=a lang=en>Three</p>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let text = |nodes: Vec<&HtmlNode>| nodes.iter().map(|n| n.text()).collect::<Vec<_>>();
+        assert_eq!(text(doc.select("p").unwrap()), vec!["One", "Two", "Three"]);
+        assert_eq!(text(doc.select("#main p").unwrap()), vec!["One", "Two"]);
+        assert_eq!(text(doc.select("div > p").unwrap()), vec!["One"]);
+        assert_eq!(text(doc.select("p.a[lang]").unwrap()), vec!["Three"]);
+        assert!(doc.select("table").unwrap().is_empty());
+
+        let first = doc.select_first(".a").unwrap().unwrap();
+        assert_eq!(first.text(), "One");
+        assert_eq!(doc.select_first("span p").unwrap().unwrap().text(), "Two");
+        assert_eq!(doc.select_first("ul").unwrap(), None);
+        assert_eq!(HtmlDocument::new().select_first("p").unwrap(), None);
+        assert!(doc.select("p[").is_err());
     }
 }
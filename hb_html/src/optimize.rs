@@ -0,0 +1,411 @@
+//! An opinionated pass which makes pages load faster.
+//!
+//! [`OptimizeLoading`] makes these changes, each of which can be turned off:
+//!
+//! - Images after the first few in the document, which are likely to be below the
+//!   fold, get `loading="lazy"` and `decoding="async"`. Images which already have a
+//!   `loading` attribute or have `fetchpriority="high"` are left alone.
+//! - Classic `<script src>` elements without `async` or `defer` block rendering
+//!   while they load and run, so they get `defer`. Module scripts are already
+//!   deferred.
+//! - A `<link rel=preconnect>` is added to the `<head>` for each third party
+//!   origin the scripts, stylesheets and images are loaded from, so the
+//!   connection is started before the browser finds the element. Links are only
+//!   added when the document has a `<head>`.
+//!
+//! The [`LoadingReport`] lists every change with the path of the element, paths
+//! being those of the document after the changes.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::optimize::{LoadingChange, OptimizeLoading};
+//! use hb_html::serialize::{AttributeOrder, SerializeOptions};
+//! let mut doc = r#"<html><head><script src="https://cdn.example/lib.js"></script></head>
+//! <body><img src="hero.jpg"><img src="photo.jpg"></body></html>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let report = OptimizeLoading::new().optimize(&mut doc);
+//! let changes: Vec<&LoadingChange> = report.changes.iter().map(|(_, c)| c).collect();
+//! assert_eq!(
+//!     changes,
+//!     vec![
+//!         &LoadingChange::Preconnect("https://cdn.example".to_owned()),
+//!         &LoadingChange::DeferScript,
+//!         &LoadingChange::LazyImage,
+//!     ]
+//! );
+//! let options = SerializeOptions::new().attribute_order(AttributeOrder::Alphabetical);
+//! assert_eq!(
+//!     doc.find("head").nodes()[0].to_html_string_with(&options),
+//!     concat!(
+//!         r#"<head><link href="https://cdn.example" rel="preconnect"></link>"#,
+//!         r#"<script defer src="https://cdn.example/lib.js"></script></head>"#
+//!     )
+//! );
+//! ```
+use crate::error::HtmlDocError;
+use crate::normalize::after_charset;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::pipeline::{Pass, PassContext};
+use crate::resources::{same_origin, url_origin};
+use crate::visit::{Visit, VisitAction, VisitContext};
+
+/// A change made by [`OptimizeLoading`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadingChange {
+    /// `loading="lazy"` and `decoding="async"` were added to an image.
+    LazyImage,
+    /// `defer` was added to a script.
+    DeferScript,
+    /// A `<link rel=preconnect>` was added for the origin.
+    Preconnect(String),
+}
+
+/// The changes made by [`OptimizeLoading::optimize`], with the path of the
+/// element which was changed or added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadingReport {
+    pub changes: Vec<(NodePath, LoadingChange)>,
+}
+
+impl LoadingReport {
+    pub fn count(&self, change: &LoadingChange) -> usize {
+        self.changes.iter().filter(|(_, c)| c == change).count()
+    }
+}
+
+/// A [`Pass`] which adds lazy loading to images, defers scripts and preconnects
+/// to third party origins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptimizeLoading {
+    /// The number of images at the start of the document which are left to load
+    /// straight away, as they are likely to be above the fold.
+    pub eager_images: usize,
+    pub lazy_images: bool,
+    pub defer_scripts: bool,
+    pub preconnect: bool,
+    /// The origin of the site, eg `https://example.com`, so its absolute URLs are
+    /// not treated as third party. Relative URLs are never third party.
+    pub site_origin: Option<String>,
+    /// The most preconnect links to add, as each open connection has a cost.
+    pub max_preconnects: usize,
+}
+
+impl Default for OptimizeLoading {
+    fn default() -> Self {
+        OptimizeLoading {
+            eager_images: 1,
+            lazy_images: true,
+            defer_scripts: true,
+            preconnect: true,
+            site_origin: None,
+            max_preconnects: 4,
+        }
+    }
+}
+
+impl OptimizeLoading {
+    /// Creates the pass with all of the changes turned on, leaving the first image
+    /// eager and adding at most 4 preconnect links.
+    pub fn new() -> OptimizeLoading {
+        OptimizeLoading::default()
+    }
+
+    pub fn eager_images(mut self, eager_images: usize) -> OptimizeLoading {
+        self.eager_images = eager_images;
+        self
+    }
+
+    pub fn lazy_images(mut self, lazy_images: bool) -> OptimizeLoading {
+        self.lazy_images = lazy_images;
+        self
+    }
+
+    pub fn defer_scripts(mut self, defer_scripts: bool) -> OptimizeLoading {
+        self.defer_scripts = defer_scripts;
+        self
+    }
+
+    pub fn preconnect(mut self, preconnect: bool) -> OptimizeLoading {
+        self.preconnect = preconnect;
+        self
+    }
+
+    pub fn site_origin<T: Into<String>>(mut self, site_origin: T) -> OptimizeLoading {
+        self.site_origin = Some(site_origin.into());
+        self
+    }
+
+    pub fn max_preconnects(mut self, max_preconnects: usize) -> OptimizeLoading {
+        self.max_preconnects = max_preconnects;
+        self
+    }
+
+    fn is_third_party(&self, origin: &str) -> bool {
+        !self
+            .site_origin
+            .as_ref()
+            .is_some_and(|site| same_origin(origin, site))
+    }
+
+    /// Makes the changes to the document, returning what was changed.
+    pub fn optimize(&self, doc: &mut HtmlDocument) -> LoadingReport {
+        let mut report = LoadingReport::default();
+        // the links are added first so the paths of the other changes are correct
+        if self.preconnect {
+            self.add_preconnects(doc, &mut report);
+        }
+        let mut visitor = LoadingVisitor {
+            options: self,
+            images: 0,
+            report,
+        };
+        doc.visit(&mut visitor);
+        visitor.report
+    }
+
+    fn add_preconnects(&self, doc: &mut HtmlDocument, report: &mut LoadingReport) {
+        let head_path = match doc.head_path() {
+            Some(path) => path,
+            None => return,
+        };
+        let mut collector = OriginCollector {
+            origins: vec![],
+            existing: vec![],
+        };
+        doc.visit(&mut collector);
+        let origins: Vec<String> = collector
+            .origins
+            .into_iter()
+            .filter(|o| self.is_third_party(o))
+            .filter(|o| !collector.existing.iter().any(|e| e.eq_ignore_ascii_case(o)))
+            .take(self.max_preconnects)
+            .collect();
+        let head = match doc.node_at_mut(&head_path) {
+            Some(HtmlNode::Tag(t)) => t,
+            _ => unreachable!("the head was found as a tag"),
+        };
+        // the links go after a <meta charset>, which must be near the start
+        let start = after_charset(head);
+        for (index, origin) in (start..).zip(origins) {
+            let link = HtmlTag::new("link")
+                .attributes(vec![("rel", "preconnect"), ("href", origin.as_str())]);
+            head.contents.insert(index, HtmlNode::Tag(link));
+            let mut path = head_path.clone();
+            path.push(index);
+            report
+                .changes
+                .push((path, LoadingChange::Preconnect(origin)));
+        }
+    }
+}
+
+/// Finds the origins of the resources in document order, and the origins which
+/// already have a preconnect link.
+struct OriginCollector {
+    origins: Vec<String>,
+    existing: Vec<String>,
+}
+
+impl Visit for OriginCollector {
+    fn enter_element(&mut self, tag: &mut HtmlTag, _ctx: &VisitContext) -> VisitAction {
+        let url = match tag.tag.to_ascii_lowercase().as_str() {
+            "script" | "img" => tag.attributes.get("src"),
+            "link" => {
+                let rel = tag
+                    .attributes
+                    .get("rel")
+                    .map(|r| r.to_ascii_lowercase())
+                    .unwrap_or_default();
+                let href = tag.attributes.get("href");
+                let rels: Vec<&str> = rel.split_ascii_whitespace().collect();
+                if rels.contains(&"preconnect") {
+                    if let Some(origin) = href.and_then(|h| url_origin(h)) {
+                        self.existing.push(origin.to_owned());
+                    }
+                    None
+                } else if rels.contains(&"stylesheet") {
+                    href
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(origin) = url.and_then(|u| url_origin(u)) {
+            if !self.origins.iter().any(|o| o.eq_ignore_ascii_case(origin)) {
+                self.origins.push(origin.to_owned());
+            }
+        }
+        VisitAction::Continue
+    }
+}
+
+struct LoadingVisitor<'a> {
+    options: &'a OptimizeLoading,
+    images: usize,
+    report: LoadingReport,
+}
+
+impl Visit for LoadingVisitor<'_> {
+    fn enter_element(&mut self, tag: &mut HtmlTag, ctx: &VisitContext) -> VisitAction {
+        match tag.tag.to_ascii_lowercase().as_str() {
+            "img" => {
+                self.images += 1;
+                let is_eager = self.images <= self.options.eager_images
+                    || tag.attributes.contains_key("loading")
+                    || tag
+                        .attributes
+                        .get("fetchpriority")
+                        .is_some_and(|p| p.eq_ignore_ascii_case("high"));
+                if self.options.lazy_images && !is_eager {
+                    tag.attributes
+                        .insert("loading".to_owned(), "lazy".to_owned());
+                    tag.attributes
+                        .entry("decoding".to_owned())
+                        .or_insert_with(|| "async".to_owned());
+                    self.report
+                        .changes
+                        .push((ctx.path.clone(), LoadingChange::LazyImage));
+                }
+            }
+            "script" => {
+                let is_classic = tag.attributes.get("type").is_none_or(|t| {
+                    let t = t.trim().to_ascii_lowercase();
+                    t.is_empty() || t == "text/javascript" || t == "application/javascript"
+                });
+                let is_blocking = tag.attributes.contains_key("src")
+                    && !tag.attributes.contains_key("async")
+                    && !tag.attributes.contains_key("defer");
+                if self.options.defer_scripts && is_classic && is_blocking {
+                    tag.attributes.insert("defer".to_owned(), String::new());
+                    self.report
+                        .changes
+                        .push((ctx.path.clone(), LoadingChange::DeferScript));
+                }
+            }
+            _ => {}
+        }
+        VisitAction::Continue
+    }
+}
+
+impl Pass for OptimizeLoading {
+    fn name(&self) -> &str {
+        "optimize-loading"
+    }
+
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError> {
+        let report = self.optimize(doc);
+        for (path, change) in report.changes {
+            let message = match change {
+                LoadingChange::LazyImage => "made an image lazy".to_owned(),
+                LoadingChange::DeferScript => "deferred a script".to_owned(),
+                LoadingChange::Preconnect(origin) => format!("preconnected to {}", origin),
+            };
+            ctx.info(message, Some(path));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod optimize_tests {
+    use super::*;
+    use crate::serialize::{AttributeOrder, SerializeOptions};
+
+    #[test]
+    fn optimize_images_and_scripts_test() {
+        let mut doc = concat!(
+            r#"<div><img src="a.png"><img src="b.png" loading="eager"><img src="c.png">"#,
+            r#"<img src="d.png" fetchpriority="HIGH"><img src="e.png" decoding="sync">"#,
+            r#"<script src="a.js" async></script><script src="b.js" type="module"></script>"#,
+            r#"<script>inline()</script><script src="c.js" type="text/JavaScript"></script></div>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let report = OptimizeLoading::new().optimize(&mut doc);
+        assert_eq!(
+            report.changes,
+            vec![
+                (vec![0, 2], LoadingChange::LazyImage),
+                (vec![0, 4], LoadingChange::LazyImage),
+                (vec![0, 8], LoadingChange::DeferScript),
+            ]
+        );
+        let options = SerializeOptions::new().attribute_order(AttributeOrder::Alphabetical);
+        let html = doc.to_html_string_with(&options);
+        assert!(html.contains(r#"<img decoding="async" loading="lazy" src="c.png">"#));
+        // existing attributes are kept
+        assert!(html.contains(r#"<img decoding="sync" loading="lazy" src="e.png">"#));
+        assert!(html.contains(r#"<script defer src="c.js" type="text/JavaScript">"#));
+        // there is no head so no links are added
+        assert_eq!(report.count(&LoadingChange::Preconnect("".to_owned())), 0);
+
+        let mut doc = "<img src=a.png><img src=b.png><script src=a.js></script>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let unchanged = doc.clone();
+        let report = OptimizeLoading::new()
+            .eager_images(2)
+            .defer_scripts(false)
+            .optimize(&mut doc);
+        assert!(report.changes.is_empty());
+        assert_eq!(doc, unchanged);
+    }
+
+    #[test]
+    fn preconnect_test() {
+        let mut doc = concat!(
+            r#"<html><head><meta charset="utf-8">"#,
+            r#"<link rel="preconnect" href="https://fonts.example">"#,
+            r#"<link rel="stylesheet" href="https://fonts.example/css">"#,
+            r#"<link rel="stylesheet" href="https://www.site.example/a.css">"#,
+            r#"<script src="https://cdn.example/a.js" defer></script></head>"#,
+            r#"<body><img src="//img.example/a.png"><img src="/local.png">"#,
+            r#"<script src="https://CDN.example/b.js" async></script></body></html>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let report = OptimizeLoading::new()
+            .site_origin("https://www.site.example/")
+            .optimize(&mut doc);
+        assert_eq!(
+            report.changes,
+            vec![
+                (
+                    vec![0, 0, 1],
+                    LoadingChange::Preconnect("https://cdn.example".to_owned())
+                ),
+                (
+                    vec![0, 0, 2],
+                    LoadingChange::Preconnect("//img.example".to_owned())
+                ),
+                (vec![0, 1, 1], LoadingChange::LazyImage),
+            ]
+        );
+        let link = match doc.node_at(&[0, 0, 2]) {
+            Some(HtmlNode::Tag(t)) => t,
+            _ => panic!("expected the link"),
+        };
+        assert_eq!(link.get_attribute("href").unwrap(), "//img.example");
+
+        let mut doc = r#"<head><script src="https://a.example/a.js"></script><script src="https://b.example/b.js"></script></head>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let report = OptimizeLoading::new()
+            .max_preconnects(1)
+            .lazy_images(false)
+            .optimize(&mut doc);
+        assert_eq!(
+            report.changes[0],
+            (
+                vec![0, 0],
+                LoadingChange::Preconnect("https://a.example".to_owned())
+            )
+        );
+        assert_eq!(report.count(&LoadingChange::DeferScript), 2);
+    }
+}
@@ -0,0 +1,482 @@
+//! Options which change how documents are parsed.
+//!
+//! # Template syntax
+//!
+//! Source files for template engines contain tags like `{{ user.name }}` or
+//! `{% if admin %}`, which can contain characters such as `<`, `>` and `"` that
+//! would otherwise confuse the parser. When the delimiters of the template tags
+//! are added to the [`ParseOptions`] each template tag is kept as it was written:
+//! in the content of elements as a [`HtmlNode::Template`](crate::objects::HtmlNode::Template) node, and in start tags
+//! as part of the attribute value (or of an attribute name with an empty value
+//! if it is between attributes). [`ParseOptions::template_parts`] splits an
+//! attribute value into its text and template parts.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::{HtmlDocument, HtmlNode};
+//! use hb_html::options::{ParseOptions, TemplatePart};
+//! let options = ParseOptions::new().common_templates();
+//! let doc = HtmlDocument::parse_with(
+//!     r#"<a title="{{ a > b ? "x" : "y" }}!">{% if n < 3 %}few{% endif %}</a>"#,
+//!     &options,
+//! )
+//! .unwrap();
+//! let a = match &doc.nodes[0] {
+//!     HtmlNode::Tag(t) => t,
+//!     _ => panic!(),
+//! };
+//! assert_eq!(a.contents[0], HtmlNode::Template("{% if n < 3 %}".to_owned()));
+//! assert_eq!(a.contents[1], HtmlNode::Text("few".to_owned()));
+//! assert_eq!(
+//!     options.template_parts(&a.attributes["title"]),
+//!     vec![
+//!         TemplatePart::Template(r#"{{ a > b ? "x" : "y" }}"#),
+//!         TemplatePart::Text("!")
+//!     ]
+//! );
+//! ```
+use crate::entities::decode_entities;
+use crate::repair::{Misplaced, RepairHook, RepairStrategy};
+
+/// The start and end of a template tag, eg `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateDelimiter {
+    pub open: String,
+    pub close: String,
+}
+
+/// A part of a piece of text split by [`ParseOptions::template_parts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplatePart<'a> {
+    Text(&'a str),
+    /// A whole template tag, including its delimiters.
+    Template(&'a str),
+}
+
+/// The dialect of HTML a document is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ParseProfile {
+    /// Standard HTML.
+    #[default]
+    Html,
+    /// The templates of component frameworks such as Vue, Svelte and JSX, which
+    /// also allow:
+    /// - any element to be closed with `/>`, eg `<MyComponent />`,
+    /// - attribute values in braces, eg `onClick={() => go("home")}`,
+    /// - text at the top level, as a template can be a fragment with many roots.
+    ///
+    /// Tag names are always kept as written, so `<Link>` is a component and not
+    /// the void `<link>` element. Attribute names such as `:prop`, `@click` and
+    /// `v-on:click.prevent` are kept as they are in both profiles.
+    Component,
+}
+
+/// How the contents of `<noscript>` elements are parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NoscriptMode {
+    /// As markup, as a browser with scripting disabled does, so the fallbacks in
+    /// them, such as the `<img>` of an image which a script loads lazily, are part
+    /// of the document.
+    #[default]
+    Markup,
+    /// As text up to the `</noscript>`, as a browser with scripting enabled does,
+    /// so markup which is broken inside them does not change the rest of the
+    /// document. [`HtmlDocument::expand_noscript`](crate::objects::HtmlDocument::expand_noscript)
+    /// parses the text as markup afterwards.
+    RawText,
+}
+
+/// How elements whose end tags are left out are put in the tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TreeConstruction {
+    /// Each element contains everything up to its end tag, so a missing end tag is
+    /// an error unless it is repaired, see [`ParseOptions::repair`].
+    #[default]
+    AsWritten,
+    /// As browsers do, following the insertion modes of the HTML tree
+    /// construction rules for the head, body, table and select elements:
+    /// - a `<p>`, `<li>`, `<dt>`, `<dd>` or `<option>` is closed by the start tags
+    ///   which close it, eg `<p>a<div>` is `<p>a</p><div>`,
+    /// - table rows and cells are closed by the next row or cell, and the
+    ///   `<tbody>` and `<tr>` around rows and cells directly in a table are added,
+    /// - the `<head>` is closed by the first element which can not be in it, and
+    ///   the `<body>` is added if it is missing,
+    /// - an end tag, or the end of the source, closes the elements inside it whose
+    ///   end tags are optional,
+    /// - an end tag which matches none of the open elements is ignored, except for
+    ///   a `</p>` which adds an empty `<p>`.
+    ///
+    /// Content which browsers move out of a table is left where it is.
+    Browser,
+}
+
+/// Options for [`crate::objects::HtmlDocument::parse_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// The delimiters of template tags which are kept as they are written.
+    pub template_delimiters: Vec<TemplateDelimiter>,
+    pub profile: ParseProfile,
+    /// Whether to check that the document is written back the same as its source.
+    pub verify_lossless: bool,
+    /// Whether to decode character references in text and attribute values, which
+    /// is on by default.
+    pub decode_entities: bool,
+    /// What to do with markup which is in the wrong place, unless there is a hook.
+    pub repair: RepairStrategy,
+    /// Chooses what to do with each piece of markup which is in the wrong place.
+    pub repair_hook: Option<RepairHook>,
+    pub noscript: NoscriptMode,
+    pub tree_construction: TreeConstruction,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            template_delimiters: vec![],
+            profile: ParseProfile::default(),
+            verify_lossless: false,
+            decode_entities: true,
+            repair: RepairStrategy::default(),
+            repair_hook: None,
+            noscript: NoscriptMode::default(),
+            tree_construction: TreeConstruction::default(),
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    pub fn profile(mut self, profile: ParseProfile) -> ParseOptions {
+        self.profile = profile;
+        self
+    }
+
+    /// Checks that the parsed document is written back by
+    /// [`HtmlDocument::to_html_string`](crate::objects::HtmlDocument::to_html_string)
+    /// exactly as it was in the source, so nothing was lost or changed by parsing.
+    /// If it is not, parsing fails with an error of the kind
+    /// [`ParseErrorKind::Lossy`](crate::error::ParseErrorKind::Lossy) at the first
+    /// byte of the source which is different.
+    ///
+    /// The check is for trying the parser on a set of documents before relying on
+    /// it to change them without other changes. Attributes other than `id` and
+    /// `class` are written in the order they are stored, so a tag with more than
+    /// one of them may be reported even though nothing was lost.
+    pub fn verify_lossless(mut self, verify_lossless: bool) -> ParseOptions {
+        self.verify_lossless = verify_lossless;
+        self
+    }
+
+    /// Decodes the character references, such as `&amp;`, `&#x27;` and `&nbsp;`, in
+    /// text and attribute values as they are parsed, see
+    /// [`decode_entities`](crate::entities::decode_entities). Template tags are kept
+    /// as they are written.
+    ///
+    /// This is on by default, so the text of a document is as it reads and the
+    /// serializer escapes it again when it is written, see
+    /// [`SerializeOptions::escape`](crate::serialize::SerializeOptions::escape).
+    /// When it is turned off the references are kept in the text as they are
+    /// written, and the document should be written with escaping turned off too,
+    /// otherwise they are escaped twice.
+    pub fn decode_entities(mut self, decode_entities: bool) -> ParseOptions {
+        self.decode_entities = decode_entities;
+        self
+    }
+
+    pub fn noscript(mut self, noscript: NoscriptMode) -> ParseOptions {
+        self.noscript = noscript;
+        self
+    }
+
+    pub fn tree_construction(mut self, tree_construction: TreeConstruction) -> ParseOptions {
+        self.tree_construction = tree_construction;
+        self
+    }
+
+    /// Sets what to do with markup which is in the wrong place, see the
+    /// [`repair`](crate::repair) module.
+    pub fn repair(mut self, repair: RepairStrategy) -> ParseOptions {
+        self.repair = repair;
+        self
+    }
+
+    /// Sets a function which chooses what to do with each piece of markup which is
+    /// in the wrong place, which is used instead of [`ParseOptions::repair`].
+    pub fn repair_hook<F>(mut self, hook: F) -> ParseOptions
+    where
+        F: Fn(&Misplaced) -> RepairStrategy + Send + Sync + 'static,
+    {
+        self.repair_hook = Some(RepairHook::new(hook));
+        self
+    }
+
+    /// Gets the strategy to use for the misplaced markup.
+    pub(crate) fn repair_strategy(&self, misplaced: &Misplaced) -> RepairStrategy {
+        match &self.repair_hook {
+            Some(hook) => hook.strategy(misplaced),
+            None => self.repair,
+        }
+    }
+
+    /// Adds the delimiters of a template tag syntax.
+    pub fn template_delimiter<O: Into<String>, C: Into<String>>(
+        mut self,
+        open: O,
+        close: C,
+    ) -> ParseOptions {
+        self.template_delimiters.push(TemplateDelimiter {
+            open: open.into(),
+            close: close.into(),
+        });
+        self
+    }
+
+    /// Adds the delimiters used by most template engines: `{{ }}` for expressions
+    /// (Handlebars, Jinja, Vue), `{% %}` for statements (Jinja, Liquid, Django) and
+    /// `<% %>` for embedded code (ERB, EJS, which also covers `<%= %>`).
+    pub fn common_templates(self) -> ParseOptions {
+        self.template_delimiter("{{", "}}")
+            .template_delimiter("{%", "%}")
+            .template_delimiter("<%", "%>")
+    }
+
+    /// Gets the length in bytes of the template tag at the start of the text, or
+    /// None if a template tag does not start there or is not closed. If more than
+    /// one delimiter matches the longest opening delimiter is used.
+    fn template_len(&self, text: &str) -> Option<usize> {
+        self.template_delimiters
+            .iter()
+            .filter(|d| !d.open.is_empty())
+            .filter_map(|d| {
+                let after_open = text.strip_prefix(d.open.as_str())?;
+                let close = after_open.find(d.close.as_str())?;
+                Some((d.open.len(), d.open.len() + close + d.close.len()))
+            })
+            .max_by_key(|(open_len, _)| *open_len)
+            .map(|(_, len)| len)
+    }
+
+    /// Decodes the references in the text if the options ask for it, leaving any
+    /// template tags as they are.
+    pub(crate) fn decode_text(&self, text: String) -> String {
+        if !self.decode_entities || !text.contains('&') {
+            return text;
+        }
+        if self.template_delimiters.is_empty() {
+            return decode_entities(&text);
+        }
+        let mut decoded = String::with_capacity(text.len());
+        for part in self.template_parts(&text) {
+            match part {
+                TemplatePart::Text(t) => decoded.push_str(&decode_entities(t)),
+                TemplatePart::Template(t) => decoded.push_str(t),
+            }
+        }
+        decoded
+    }
+
+    /// Splits the text into the template tags and the text between them. A template
+    /// tag which is not closed is left as text.
+    pub fn template_parts<'a>(&self, text: &'a str) -> Vec<TemplatePart<'a>> {
+        let mut parts = vec![];
+        let mut text_start = 0;
+        let mut i = 0;
+        while i < text.len() {
+            match self.template_len(&text[i..]) {
+                Some(len) => {
+                    if text_start < i {
+                        parts.push(TemplatePart::Text(&text[text_start..i]));
+                    }
+                    parts.push(TemplatePart::Template(&text[i..i + len]));
+                    i += len;
+                    text_start = i;
+                }
+                None => i += text[i..].chars().next().map_or(1, |c| c.len_utf8()),
+            }
+        }
+        if text_start < text.len() {
+            parts.push(TemplatePart::Text(&text[text_start..]));
+        }
+        parts
+    }
+}
+
+#[cfg(test)]
+mod options_tests {
+    use super::*;
+    use crate::error::ParseErrorKind;
+    use crate::objects::{HtmlDocument, HtmlNode, HtmlTag};
+    use std::collections::HashMap;
+
+    #[test]
+    fn verify_lossless_test() {
+        let options = ParseOptions::new().verify_lossless(true);
+        let source = "<!DOCTYPE html><div id=\"a\"><p>Hi &amp; bye</p></div>";
+        HtmlDocument::parse_with(source, &options).unwrap();
+        let error = HtmlDocument::parse_with("<div>\n<p>a<br>b</p></div>", &options).unwrap_err();
+        assert_eq!(error.kind(), ParseErrorKind::Lossy);
+        assert_eq!(error.offset(), Some(14));
+        assert_eq!(
+            error.msg,
+            "the document is not written back the same as its source, at byte 14 the source has 'b</p></div>' but the output has '</br>b</p></div>'"
+        );
+        let error = HtmlDocument::parse_with("<p class=a>x</p>", &options).unwrap_err();
+        assert_eq!(error.offset(), Some(9));
+        // without the option the document is parsed as normal
+        assert!(HtmlDocument::parse_with("<p class=a>x</p>", &ParseOptions::new()).is_ok());
+    }
+
+    #[test]
+    fn template_parts_test() {
+        let options = ParseOptions::new().common_templates();
+        assert_eq!(
+            options.template_parts("a{{b}}é<%= c %>{% d"),
+            vec![
+                TemplatePart::Text("a"),
+                TemplatePart::Template("{{b}}"),
+                TemplatePart::Text("é"),
+                TemplatePart::Template("<%= c %>"),
+                TemplatePart::Text("{% d"),
+            ]
+        );
+        assert_eq!(
+            ParseOptions::new().template_parts("{{b}}"),
+            vec![TemplatePart::Text("{{b}}")]
+        );
+        assert!(options.template_parts("").is_empty());
+    }
+
+    #[test]
+    fn parse_templates_test() {
+        let options = ParseOptions::new().common_templates();
+        let source = "<ul class=\"{{ cls }} x\" {% if a %}hidden{% endif %} data-n={{n}}>\
+                      <% for i in items %><li><%= i > 2 %></li><% end %></ul>";
+        let doc = HtmlDocument::parse_with(source, &options).unwrap();
+        let ul = match &doc.nodes[0] {
+            HtmlNode::Tag(t) => t,
+            _ => panic!("expected a tag"),
+        };
+        assert_eq!(ul.classes, vec!["{{ cls }}", "x"]);
+        // template tags between attributes are kept with the attribute they are next to
+        assert_eq!(ul.attributes["{% if a %}hidden{% endif %}"], "");
+        assert_eq!(ul.attributes["data-n"], "{{n}}");
+        assert_eq!(
+            ul.contents,
+            vec![
+                HtmlNode::Template("<% for i in items %>".to_owned()),
+                HtmlNode::Tag(
+                    HtmlTag::new("li")
+                        .contents(vec![HtmlNode::Template("<%= i > 2 %>".to_owned())])
+                ),
+                HtmlNode::Template("<% end %>".to_owned()),
+            ]
+        );
+        assert_eq!(doc.nodes[0].to_html_string().matches("<%").count(), 3);
+        // without the options the template tags are parsed as markup
+        assert!(source.parse::<HtmlDocument>().is_err());
+        // template tags which are not closed are text
+        let doc = HtmlDocument::parse_with("<p>{{ x </p>", &options).unwrap();
+        assert_eq!(doc.nodes[0].text(), "{{ x ");
+    }
+
+    #[test]
+    fn component_profile_test() {
+        let options = ParseOptions::new()
+            .profile(ParseProfile::Component)
+            .template_delimiter("{{", "}}");
+        let source = r#"<MyList :items="items" @select="pick"><Item v-for="i in items" :key=i/>
+<Link to="/" onClick={() => go({ to: "}" })} disabled/></MyList>
+Count: {{ n }}<p/>"#;
+        let doc = HtmlDocument::parse_with(source, &options).unwrap();
+        let list = match &doc.nodes[0] {
+            HtmlNode::Tag(t) => t,
+            _ => panic!("expected a tag"),
+        };
+        assert_eq!(list.tag, "MyList");
+        assert!(list.is_component());
+        assert_eq!(list.attributes[":items"], "items");
+        assert_eq!(list.attributes["@select"], "pick");
+        let item = HtmlTag::new("Item").attributes(vec![("v-for", "i in items"), (":key", "i")]);
+        assert_eq!(list.contents[0], HtmlNode::Tag(item));
+        let link = match &list.contents[2] {
+            HtmlNode::Tag(t) => t,
+            _ => panic!("expected a tag"),
+        };
+        assert_eq!(
+            link.attributes,
+            HashMap::from([
+                ("to".to_owned(), "/".to_owned()),
+                (
+                    "onClick".to_owned(),
+                    r#"{() => go({ to: "}" })}"#.to_owned()
+                ),
+                ("disabled".to_owned(), String::new()),
+            ])
+        );
+        assert!(link.contents.is_empty());
+        // many roots, including text
+        assert_eq!(
+            doc.nodes[1..],
+            [
+                HtmlNode::Text("\nCount: ".to_owned()),
+                HtmlNode::Template("{{ n }}".to_owned()),
+                HtmlNode::Tag(HtmlTag::new("p")),
+            ]
+        );
+        // in the html profile <Link> is not void so needs an end tag
+        assert!(HtmlDocument::parse_with(source, &ParseOptions::new()).is_err());
+        assert_eq!(source.parse::<HtmlDocument>().ok(), None);
+    }
+
+    #[test]
+    fn decode_entities_test() {
+        let source = r#"<p title="Tom &amp; Jerry" class="a&#32;b">1 &lt; 2&nbsp;&amp; {{ a &amp;&amp; b }}</p>"#;
+        let options = ParseOptions::new()
+            .decode_entities(true)
+            .template_delimiter("{{", "}}");
+        let doc = HtmlDocument::parse_with(source, &options).unwrap();
+        let p = match &doc.nodes[0] {
+            HtmlNode::Tag(t) => t,
+            _ => panic!("expected a tag"),
+        };
+        assert_eq!(p.attributes["title"], "Tom & Jerry");
+        assert_eq!(p.classes, vec!["a", "b"]);
+        assert_eq!(
+            p.contents,
+            vec![
+                HtmlNode::Text("1 < 2\u{A0}& ".to_owned()),
+                HtmlNode::Template("{{ a &amp;&amp; b }}".to_owned())
+            ]
+        );
+        // decoding is the default, and the references are kept when it is off
+        assert_eq!(
+            HtmlDocument::parse_with(source, &ParseOptions::new().template_delimiter("{{", "}}"))
+                .unwrap(),
+            doc
+        );
+        let options = ParseOptions::new().decode_entities(false);
+        let doc = HtmlDocument::parse_with(source, &options).unwrap();
+        assert_eq!(
+            doc.nodes[0].text(),
+            "1 &lt; 2&nbsp;&amp; {{ a &amp;&amp; b }}"
+        );
+
+        let options = ParseOptions::new()
+            .decode_entities(true)
+            .profile(ParseProfile::Component);
+        let doc =
+            HtmlDocument::parse_with("a &gt; b<C x={a &amp;&amp; b} y=&lt;/>", &options).unwrap();
+        assert_eq!(doc.nodes[0], HtmlNode::Text("a > b".to_owned()));
+        match &doc.nodes[1] {
+            HtmlNode::Tag(t) => {
+                assert_eq!(t.attributes["x"], "{a &amp;&amp; b}");
+                assert_eq!(t.attributes["y"], "<");
+            }
+            _ => panic!("expected a tag"),
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Copy-on-write views of a shared [`HtmlDocument`].
+//!
+//! A [`DocumentOverlay`] records mutations against a base document without copying
+//! it, which allows many candidate rewrites to be evaluated against the same base
+//! and then either materialized into a new document or discarded.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::{CssSelector, HtmlDocument};
+//! use hb_html::overlay::DocumentOverlay;
+//! use std::sync::Arc;
+//! let base = Arc::new(
+//!     "<div><p class=old>Hello</p></div>"
+//!         .parse::<HtmlDocument>()
+//!         .unwrap(),
+//! );
+//! let mut overlay = DocumentOverlay::new(base.clone());
+//! overlay.set_attribute(vec![0, 0], "class", "new");
+//! let selector = "p.new".parse::<CssSelector>().unwrap();
+//! assert_eq!(overlay.find(&selector).unwrap(), vec![vec![0, 0]]);
+//! // the base document is left untouched
+//! assert_eq!(base.find("p.new").nodes().len(), 0);
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{CssSelector, HtmlDocument, HtmlNode, NodePath};
+use crate::querying::HtmlQueryable;
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+/// A single change recorded by a [`DocumentOverlay`]. Paths are resolved against
+/// the view of the document after all of the previously recorded mutations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverlayMutation {
+    /// Sets an attribute on the tag at the path.
+    SetAttribute {
+        path: NodePath,
+        name: String,
+        value: String,
+    },
+    /// Removes an attribute from the tag at the path.
+    RemoveAttribute { path: NodePath, name: String },
+    /// Replaces the contents of the tag (or the text node) at the path with the text.
+    SetText { path: NodePath, text: String },
+    /// Replaces the node at the path.
+    Replace { path: NodePath, node: HtmlNode },
+    /// Removes the node at the path.
+    Remove { path: NodePath },
+    /// Inserts a node into the contents of the tag at the path (or the top level of
+    /// the document if the path is empty) at the index provided.
+    InsertChild {
+        path: NodePath,
+        index: usize,
+        node: HtmlNode,
+    },
+}
+
+impl OverlayMutation {
+    /// Applies the mutation to the document provided.
+    pub fn apply(&self, doc: &mut HtmlDocument) -> Result<(), HtmlDocError> {
+        match self {
+            OverlayMutation::SetAttribute { path, name, value } => match doc.node_at_mut(path) {
+                Some(HtmlNode::Tag(t)) => t.set_attribute(name.as_str(), value.as_str()),
+                _ => return Err(no_tag_error(path)),
+            },
+            OverlayMutation::RemoveAttribute { path, name } => match doc.node_at_mut(path) {
+                Some(HtmlNode::Tag(t)) => {
+                    t.remove_attribute(name);
+                }
+                _ => return Err(no_tag_error(path)),
+            },
+            OverlayMutation::SetText { path, text } => match doc.node_at_mut(path) {
+                Some(HtmlNode::Tag(t)) => t.contents = vec![HtmlNode::Text(text.clone())],
+                Some(HtmlNode::Text(t)) => *t = text.clone(),
+                _ => {
+                    return Err(HtmlDocError::with_msg(format!(
+                        "could not set text as there is no tag or text node at {:?}",
+                        path
+                    )))
+                }
+            },
+            OverlayMutation::Replace { path, node } => match doc.node_at_mut(path) {
+                Some(n) => *n = node.clone(),
+                None => return Err(no_node_error(path)),
+            },
+            OverlayMutation::Remove { path } => {
+                let i = *path.last().ok_or_else(|| no_node_error(path))?;
+                match doc.siblings_at_mut(path) {
+                    Some(siblings) if i < siblings.len() => {
+                        siblings.remove(i);
+                    }
+                    _ => return Err(no_node_error(path)),
+                }
+            }
+            OverlayMutation::InsertChild { path, index, node } => {
+                let contents = if path.is_empty() {
+                    &mut doc.nodes
+                } else {
+                    match doc.node_at_mut(path) {
+                        Some(HtmlNode::Tag(t)) => &mut t.contents,
+                        _ => return Err(no_tag_error(path)),
+                    }
+                };
+                if *index > contents.len() {
+                    return Err(HtmlDocError::with_msg(format!(
+                        "could not insert child at index {} of {:?} which only has {} children",
+                        index,
+                        path,
+                        contents.len()
+                    )));
+                }
+                contents.insert(*index, node.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the mutation on one line, with nodes written as HTML, eg
+/// `set-attribute [0, 1] class="new"`.
+impl fmt::Display for OverlayMutation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OverlayMutation::SetAttribute { path, name, value } => {
+                write!(f, "set-attribute {:?} {}={:?}", path, name, value)
+            }
+            OverlayMutation::RemoveAttribute { path, name } => {
+                write!(f, "remove-attribute {:?} {}", path, name)
+            }
+            OverlayMutation::SetText { path, text } => write!(f, "set-text {:?} {:?}", path, text),
+            OverlayMutation::Replace { path, node } => {
+                write!(f, "replace {:?} {}", path, node.to_html_string())
+            }
+            OverlayMutation::Remove { path } => write!(f, "remove {:?}", path),
+            OverlayMutation::InsertChild { path, index, node } => write!(
+                f,
+                "insert-child {:?} {} {}",
+                path,
+                index,
+                node.to_html_string()
+            ),
+        }
+    }
+}
+
+fn no_node_error(path: &[usize]) -> HtmlDocError {
+    HtmlDocError::with_msg(format!("there is no node at {:?}", path))
+}
+
+fn no_tag_error(path: &[usize]) -> HtmlDocError {
+    HtmlDocError::with_msg(format!("there is no tag at {:?}", path))
+}
+
+/// A view of a shared base document with a list of recorded mutations on top.
+///
+/// The base is never modified or copied while recording mutations. A copy of the
+/// base is only made when the combined view is needed, either by calling
+/// [`DocumentOverlay::materialize`] or by querying an overlay that has mutations.
+/// The combined view used for queries is cached until the next mutation.
+#[derive(Debug, Clone)]
+pub struct DocumentOverlay {
+    base: Arc<HtmlDocument>,
+    mutations: Vec<OverlayMutation>,
+    view: RefCell<Option<HtmlDocument>>,
+}
+
+impl DocumentOverlay {
+    /// Creates an overlay with no mutations over the base document.
+    pub fn new(base: Arc<HtmlDocument>) -> DocumentOverlay {
+        DocumentOverlay {
+            base,
+            mutations: vec![],
+            view: RefCell::new(None),
+        }
+    }
+
+    /// The shared base document.
+    pub fn base(&self) -> &Arc<HtmlDocument> {
+        &self.base
+    }
+
+    /// The mutations which have been recorded, in the order they will be applied.
+    pub fn mutations(&self) -> &[OverlayMutation] {
+        &self.mutations
+    }
+
+    /// Returns true if any mutations have been recorded.
+    pub fn is_modified(&self) -> bool {
+        !self.mutations.is_empty()
+    }
+
+    /// Records a mutation to apply on top of the base document.
+    pub fn record(&mut self, mutation: OverlayMutation) -> &mut DocumentOverlay {
+        self.mutations.push(mutation);
+        self.view.replace(None);
+        self
+    }
+
+    pub fn set_attribute<N: Into<String>, V: Into<String>>(
+        &mut self,
+        path: NodePath,
+        name: N,
+        value: V,
+    ) -> &mut DocumentOverlay {
+        self.record(OverlayMutation::SetAttribute {
+            path,
+            name: name.into(),
+            value: value.into(),
+        })
+    }
+
+    pub fn remove_attribute<N: Into<String>>(
+        &mut self,
+        path: NodePath,
+        name: N,
+    ) -> &mut DocumentOverlay {
+        self.record(OverlayMutation::RemoveAttribute {
+            path,
+            name: name.into(),
+        })
+    }
+
+    pub fn set_text<T: Into<String>>(&mut self, path: NodePath, text: T) -> &mut DocumentOverlay {
+        self.record(OverlayMutation::SetText {
+            path,
+            text: text.into(),
+        })
+    }
+
+    pub fn replace_node(&mut self, path: NodePath, node: HtmlNode) -> &mut DocumentOverlay {
+        self.record(OverlayMutation::Replace { path, node })
+    }
+
+    pub fn remove_node(&mut self, path: NodePath) -> &mut DocumentOverlay {
+        self.record(OverlayMutation::Remove { path })
+    }
+
+    pub fn insert_child(
+        &mut self,
+        path: NodePath,
+        index: usize,
+        node: HtmlNode,
+    ) -> &mut DocumentOverlay {
+        self.record(OverlayMutation::InsertChild { path, index, node })
+    }
+
+    /// Throws away all of the recorded mutations, leaving a view of the base document.
+    pub fn discard(&mut self) {
+        self.mutations.clear();
+        self.view.replace(None);
+    }
+
+    /// Creates a new document by applying the recorded mutations to a copy of the base.
+    pub fn materialize(&self) -> Result<HtmlDocument, HtmlDocError> {
+        if let Some(view) = &*self.view.borrow() {
+            return Ok(view.clone());
+        }
+        self.build_view()
+    }
+
+    fn build_view(&self) -> Result<HtmlDocument, HtmlDocError> {
+        let mut doc = (*self.base).clone();
+        for m in &self.mutations {
+            m.apply(&mut doc)?;
+        }
+        Ok(doc)
+    }
+
+    /// Finds the paths of all of the nodes in the combined view which match the selector.
+    /// If no mutations have been recorded then the base is queried directly.
+    pub fn find(&self, selector: &CssSelector) -> Result<Vec<NodePath>, HtmlDocError> {
+        if !self.is_modified() {
+            return Ok(find_paths(&self.base, selector));
+        }
+        if self.view.borrow().is_none() {
+            let view = self.build_view()?;
+            self.view.replace(Some(view));
+        }
+        Ok(find_paths(self.view.borrow().as_ref().unwrap(), selector))
+    }
+
+    /// Gets a copy of the node at the path in the combined view.
+    pub fn node_at(&self, path: &[usize]) -> Result<Option<HtmlNode>, HtmlDocError> {
+        if !self.is_modified() {
+            return Ok(self.base.node_at(path).cloned());
+        }
+        if self.view.borrow().is_none() {
+            let view = self.build_view()?;
+            self.view.replace(Some(view));
+        }
+        Ok(self.view.borrow().as_ref().unwrap().node_at(path).cloned())
+    }
+}
+
+fn find_paths(doc: &HtmlDocument, selector: &CssSelector) -> Vec<NodePath> {
+    let mut query = doc.query();
+    query.find(selector);
+    query.results.iter().map(|r| r.node_path()).collect()
+}
+
+#[cfg(test)]
+mod overlay_tests {
+    use super::*;
+    use crate::objects::HtmlTag;
+
+    fn base() -> Arc<HtmlDocument> {
+        Arc::new(
+            "<div id=main><p class=a>One</p><p class=b>Two</p></div>"
+                .parse::<HtmlDocument>()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn overlay_without_mutations_queries_base() {
+        let overlay = DocumentOverlay::new(base());
+        assert!(!overlay.is_modified());
+        let selector = "p".parse::<CssSelector>().unwrap();
+        assert_eq!(
+            overlay.find(&selector).unwrap(),
+            vec![vec![0, 0], vec![0, 1]]
+        );
+        assert_eq!(overlay.materialize().unwrap(), *overlay.base().as_ref());
+    }
+
+    #[test]
+    fn overlay_mutations_test() {
+        let base = base();
+        let mut overlay = DocumentOverlay::new(base.clone());
+        overlay
+            .set_attribute(vec![0, 0], "class", "c")
+            .set_text(vec![0, 1], "Changed")
+            .insert_child(vec![0], 0, HtmlNode::Tag(HtmlTag::new("h1")))
+            .remove_attribute(vec![0], "id");
+        let c = "p.c".parse::<CssSelector>().unwrap();
+        assert_eq!(overlay.find(&c).unwrap(), vec![vec![0, 1]]);
+        let doc = overlay.materialize().unwrap();
+        assert_eq!(doc.node_at(&[0, 2]).unwrap().text(), "Changed");
+        match doc.node_at(&[0]).unwrap() {
+            HtmlNode::Tag(t) => assert_eq!(t.get_attribute("id"), None),
+            n => panic!("expected a tag but found {:?}", n),
+        }
+        // base is untouched
+        assert_eq!(base.find("p.c").nodes().len(), 0);
+        assert_eq!(base.node_at(&[0, 1]).unwrap().text(), "Two");
+
+        overlay.remove_node(vec![0, 0]);
+        let h1 = "h1".parse::<CssSelector>().unwrap();
+        assert!(overlay.find(&h1).unwrap().is_empty());
+
+        overlay.discard();
+        assert_eq!(overlay.find(&c).unwrap(), Vec::<NodePath>::new());
+    }
+
+    #[test]
+    fn overlay_invalid_path_test() {
+        let mut overlay = DocumentOverlay::new(base());
+        overlay.set_attribute(vec![5], "class", "x");
+        assert_eq!(
+            format!("{}", overlay.materialize().unwrap_err()),
+            "Html Doc Error: 'there is no tag at [5]'"
+        );
+        let any = "*".parse::<CssSelector>().unwrap();
+        assert!(overlay.find(&any).is_err());
+    }
+}
@@ -1,14 +1,28 @@
-use crate::error::ParseHtmlError;
+use crate::error::{ParseErrorKind, ParseHtmlError};
+use crate::memfind::find_byte;
+use crate::metrics;
 use crate::objects::{
     CssAttributeCompareType, CssRefiner, CssRefinerNumberType, CssSelector, CssSelectorItem,
-    CssSelectorRelationship, CssSelectorRule, HtmlNode, HtmlTag,
+    CssSelectorRelationship, CssSelectorRule, HtmlNode, HtmlTag, NodePath,
+};
+use crate::options::{NoscriptMode, ParseOptions, ParseProfile, TemplatePart, TreeConstruction};
+use crate::repair::{Misplaced, MisplacedKind, RepairStrategy};
+use crate::small_string::SmallString;
+use crate::source_map::NodeSpan;
+use crate::tree_construction::{
+    closes_implied, has_optional_end_tag, start_tag_action, start_tag_name, stray_end_tag_node,
+    StartTagAction,
 };
 use std::collections::VecDeque;
 use std::str::FromStr;
 
 // Read from the iterator until a quoted string or word is found (ignoring leading whitespace) then return the string and the character that ended the string
 // Endings of a single word can be whitespace or >
-pub fn parse_string(chs: &mut std::str::Chars) -> Result<(String, char), ParseHtmlError> {
+// Any template tags in the string are kept as they are written
+fn parse_string_with(
+    chs: &mut std::str::Chars,
+    options: &ParseOptions,
+) -> Result<(String, char), ParseHtmlError> {
     let mut buffer = String::with_capacity(50);
     let mut is_quoted = false;
     //consume leading whitespace
@@ -16,14 +30,31 @@ pub fn parse_string(chs: &mut std::str::Chars) -> Result<(String, char), ParseHt
     //check if first char is a "
     if ch == '"' {
         is_quoted = true;
-    } else {
+    } else if ch == '{'
+        && options.profile == ParseProfile::Component
+        && !read_template(ch, chs, options, &mut buffer)
+    {
+        return parse_braced(chs).map(|value| (value, '}'));
+    } else if !read_template(ch, chs, options, &mut buffer) {
         buffer.push(ch);
     }
     while let Some(ch) = chs.next() {
+        if read_template(ch, chs, options, &mut buffer) {
+            continue;
+        }
         if is_quoted {
             if ch == '"' {
                 return Ok((buffer, ch));
             }
+            if options.template_delimiters.is_empty() {
+                // the value runs until the closing quote
+                let rest = chs.as_str();
+                let len = find_byte(rest.as_bytes(), b'"').unwrap_or(rest.len());
+                buffer.push(ch);
+                buffer.push_str(&rest[..len]);
+                *chs = rest[len..].chars();
+                continue;
+            }
         } else {
             if ch.is_ascii_whitespace() || ch == '>' {
                 return Ok((buffer, ch));
@@ -38,7 +69,9 @@ pub fn parse_string(chs: &mut std::str::Chars) -> Result<(String, char), ParseHt
         )));
     }
     if buffer.len() == 0 {
-        return Err(ParseHtmlError::with_msg("No chars found."));
+        return Err(
+            ParseHtmlError::with_msg("No chars found.").with_kind(ParseErrorKind::UnexpectedEnd)
+        );
     }
     Ok((buffer, ' '))
 }
@@ -63,7 +96,8 @@ pub fn parse_until_one_of(
     return Err(ParseHtmlError::new(format!(
         "end of string '{}' encountered before any end char '{:?}' was found",
         buffer, end_chars
-    )));
+    ))
+    .with_kind(ParseErrorKind::UnexpectedEnd));
 }
 
 pub fn parse_until_end_or_one_of_peekable(
@@ -101,7 +135,8 @@ pub fn parse_until_one_of_peekable(
     return Err(ParseHtmlError::new(format!(
         "end of string '{}' encountered before any end char '{:?}' was found",
         buffer, end_chars
-    )));
+    ))
+    .with_kind(ParseErrorKind::UnexpectedEnd));
 }
 
 pub fn parse_until_char(
@@ -122,7 +157,8 @@ pub fn parse_until_char(
     return Err(ParseHtmlError::new(format!(
         "end of string '{}' encountered before end char '{}' was found",
         buffer, end_char
-    )));
+    ))
+    .with_kind(ParseErrorKind::UnexpectedEnd));
 }
 
 pub fn parse_until_str(
@@ -157,16 +193,23 @@ pub fn parse_until_str(
         buffer,
         ending_buffer.iter().collect::<String>(),
         end_str
-    )));
+    ))
+    .with_kind(ParseErrorKind::UnexpectedEnd));
 }
 
-pub fn parse_until<F: Fn(&char) -> bool>(
+/// Reads until a char which passes the check, keeping any template tags as they are
+/// written without checking their chars for the ending.
+fn parse_until_with<F: Fn(&char) -> bool>(
     chs: &mut std::str::Chars,
+    options: &ParseOptions,
     check_ending: F,
     include_ending: bool,
 ) -> Result<String, ParseHtmlError> {
     let mut buffer = String::with_capacity(50);
     while let Some(ch) = chs.next() {
+        if read_template(ch, chs, options, &mut buffer) {
+            continue;
+        }
         if check_ending(&ch) {
             if include_ending {
                 buffer.push(ch);
@@ -178,7 +221,8 @@ pub fn parse_until<F: Fn(&char) -> bool>(
     return Err(ParseHtmlError::new(format!(
         "end of string encountered without terminating character in string '{}'",
         buffer
-    )));
+    ))
+    .with_kind(ParseErrorKind::UnexpectedEnd));
 }
 
 pub fn parse_until_and_including_char(
@@ -196,7 +240,8 @@ pub fn parse_until_and_including_char(
     return Err(ParseHtmlError::new(format!(
         "end of string '{}' encountered before ending '{}' was found",
         buffer, ending
-    )));
+    ))
+    .with_kind(ParseErrorKind::UnexpectedEnd));
 }
 
 pub fn parse_until_char_peekable(
@@ -213,7 +258,8 @@ pub fn parse_until_char_peekable(
     return Err(ParseHtmlError::new(format!(
         "end of string '{}' encountered before ending '{}' was found",
         buffer, ending
-    )));
+    ))
+    .with_kind(ParseErrorKind::UnexpectedEnd));
 }
 
 pub fn parse_contents_of_braces(
@@ -222,7 +268,10 @@ pub fn parse_contents_of_braces(
     let mut buffer = String::new();
     let mut level = 0;
     match chs.peek() {
-        None => return Err(ParseHtmlError::new(format!("no characters found",))),
+        None => {
+            return Err(ParseHtmlError::new(format!("no characters found",))
+                .with_kind(ParseErrorKind::UnexpectedEnd))
+        }
         Some(c) => {
             if *c != '(' {
                 return Err(ParseHtmlError::new(format!("no opening brace was found")));
@@ -248,7 +297,8 @@ pub fn parse_contents_of_braces(
     return Err(ParseHtmlError::new(format!(
         "end of string '{}' encountered before closing brace ')' was found",
         buffer
-    )));
+    ))
+    .with_kind(ParseErrorKind::UnexpectedEnd));
 }
 
 pub fn parse_attibute_value(attr_value: String) -> Vec<String> {
@@ -259,15 +309,104 @@ pub fn parse_attibute_value(attr_value: String) -> Vec<String> {
     return v;
 }
 
+/// Reads a value in braces, such as a JSX expression, where the `{` has just been
+/// read. Returns the value including the braces. Braces in quoted strings inside
+/// the value are ignored.
+fn parse_braced(chs: &mut std::str::Chars) -> Result<String, ParseHtmlError> {
+    let mut buffer = String::from('{');
+    let mut depth = 1;
+    let mut quote = None;
+    for ch in chs.by_ref() {
+        buffer.push(ch);
+        match (quote, ch) {
+            (Some(q), ch) if ch == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(ch),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(buffer);
+                }
+            }
+            (None, _) => {}
+        }
+    }
+    Err(ParseHtmlError::with_msg(format!(
+        "Closing '}}' for value '{}' not found",
+        buffer
+    )))
+}
+
+/// Splits the value as [`parse_attibute_value`] does, without splitting template tags.
+fn parse_attibute_value_with(attr_value: String, options: &ParseOptions) -> Vec<String> {
+    if options.template_delimiters.is_empty() {
+        return parse_attibute_value(attr_value);
+    }
+    let mut v = vec![];
+    let mut current = String::new();
+    for part in options.template_parts(&attr_value) {
+        match part {
+            TemplatePart::Template(t) => current.push_str(t),
+            TemplatePart::Text(t) => {
+                for ch in t.chars() {
+                    if !ch.is_ascii_whitespace() {
+                        current.push(ch);
+                    } else if !current.is_empty() {
+                        v.push(std::mem::take(&mut current));
+                    }
+                }
+            }
+        }
+    }
+    if !current.is_empty() {
+        v.push(current);
+    }
+    v
+}
+
+/// Checks whether a template tag starts with the char which has just been read
+/// from the iterator, and if it does reads the rest of the tag and adds all of it
+/// to the buffer. Template tags which are not closed are not read.
+pub(crate) fn read_template(
+    first: char,
+    chs: &mut std::str::Chars,
+    options: &ParseOptions,
+    buffer: &mut String,
+) -> bool {
+    let rest = chs.as_str();
+    let found = options
+        .template_delimiters
+        .iter()
+        .filter(|d| !d.open.is_empty())
+        .filter_map(|d| {
+            let open_rest = d.open.strip_prefix(first)?;
+            let after_open = rest.strip_prefix(open_rest)?;
+            let close = after_open.find(d.close.as_str())?;
+            Some((d.open.len(), open_rest.len() + close + d.close.len()))
+        })
+        .max_by_key(|(open_len, _)| *open_len);
+    match found {
+        Some((_, len)) => {
+            buffer.push(first);
+            buffer.push_str(&rest[..len]);
+            *chs = rest[len..].chars();
+            true
+        }
+        None => false,
+    }
+}
+
 pub fn get_next_non_whitespace(chs: &mut std::str::Chars) -> Result<char, ParseHtmlError> {
     while let Some(ch) = chs.next() {
         if !ch.is_ascii_whitespace() {
             return Ok(ch);
         }
     }
-    return Err(ParseHtmlError::new(format!(
-        "End found while consuming whitespace."
-    )));
+    return Err(
+        ParseHtmlError::new(format!("End found while consuming whitespace."))
+            .with_kind(ParseErrorKind::UnexpectedEnd),
+    );
 }
 
 #[cfg(test)]
@@ -308,32 +447,33 @@ mod parse_iter_tests {
             parse_until_str(&mut "Something else <".chars(), &"else", true).unwrap(),
             "Something else"
         );
+        let options = ParseOptions::default();
         let cl = |c: &char| -> bool {
             return c.is_ascii_whitespace();
         };
         assert_eq!(
-            parse_until(&mut "Something else".chars(), cl, false).unwrap(),
+            parse_until_with(&mut "Something else".chars(), &options, cl, false).unwrap(),
             "Something"
         );
         assert_eq!(
-            parse_until(&mut "Something else".chars(), cl, true).unwrap(),
+            parse_until_with(&mut "Something else".chars(), &options, cl, true).unwrap(),
             "Something "
         );
 
         assert_eq!(
-            parse_string(&mut " test ".chars()).unwrap(),
+            parse_string_with(&mut " test ".chars(), &options).unwrap(),
             ("test".to_string(), ' ')
         );
         assert_eq!(
-            parse_string(&mut " test>".chars()).unwrap(),
+            parse_string_with(&mut " test>".chars(), &options).unwrap(),
             ("test".to_string(), '>')
         );
         assert_eq!(
-            parse_string(&mut " \"test \"".chars()).unwrap(),
+            parse_string_with(&mut " \"test \"".chars(), &options).unwrap(),
             ("test ".to_string(), '"')
         );
         assert_eq!(
-            parse_string(&mut "\"test \"".chars()).unwrap(),
+            parse_string_with(&mut "\"test \"".chars(), &options).unwrap(),
             ("test ".to_string(), '"')
         );
     }
@@ -341,25 +481,213 @@ mod parse_iter_tests {
 
 #[derive(Debug, PartialEq)]
 pub enum ParsedTagType {
-    EndTag(String),  //eg </div>
-    NewTag(HtmlTag), //eg <div class="test">
-    Comment(String), //eg <!-- text --!>
+    EndTag(SmallString), //eg </div>
+    NewTag(HtmlTag),     //eg <div class="test">
+    Comment(String),     //eg <!-- text --!>
     DocType(String),
 }
 
+/// Records the byte range in the source of each node as the document is parsed.
+pub(crate) struct SpanRecorder {
+    source_len: usize,
+    /// The path of the node which is being parsed.
+    pub(crate) path: NodePath,
+    pub(crate) spans: Vec<NodeSpan>,
+    /// The byte offset of the last end tag read.
+    end_tag_start: usize,
+}
+
+impl SpanRecorder {
+    pub(crate) fn new(source: &str) -> SpanRecorder {
+        SpanRecorder {
+            source_len: source.len(),
+            path: vec![],
+            spans: vec![],
+            end_tag_start: 0,
+        }
+    }
+
+    /// Gets the byte offset in the source of the next char of the iterator.
+    pub(crate) fn offset(&self, chs: &std::str::Chars) -> usize {
+        self.source_len - chs.as_str().len()
+    }
+
+    /// Updates the spans after the node at the path is taken out of the tree. If
+    /// its children are kept they take its place, otherwise they are taken out too.
+    fn remove_node(&mut self, path: &[usize], keep_children: bool) {
+        self.spans.retain_mut(|span| {
+            if !span.path.starts_with(path) {
+                return true;
+            }
+            if span.path.len() == path.len() || !keep_children {
+                return false;
+            }
+            let child = span.path.remove(path.len());
+            span.path[path.len() - 1] += child;
+            true
+        });
+    }
+
+    /// Updates the spans after the element at the path is replaced by its source
+    /// as text.
+    fn replace_with_text(&mut self, path: &[usize]) {
+        self.spans
+            .retain(|span| span.path.len() <= path.len() || !span.path.starts_with(path));
+        for span in self.spans.iter_mut().filter(|span| span.path == path) {
+            span.content = None;
+        }
+    }
+}
+
+/// The elements which are open while a document is parsed, used to repair markup
+/// which is in the wrong place.
+#[derive(Default)]
+pub(crate) struct OpenElements {
+    /// The names of the open elements, starting with the outermost.
+    names: Vec<String>,
+    /// An end tag which closes elements above the one which read it.
+    pending_end: Option<SmallString>,
+}
+
+impl OpenElements {
+    pub(crate) fn new() -> OpenElements {
+        OpenElements::default()
+    }
+}
+
+/// The elements which can only be in a table.
+const TABLE_PARTS: [&str; 9] = [
+    "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+/// Adds an element which has been parsed to the nodes, first repairing it if it
+/// is a table element which is not in a table. The path of the recorder must be
+/// the path of the parent of the nodes.
+pub(crate) fn push_element(
+    nodes: &mut Vec<HtmlNode>,
+    tag: HtmlTag,
+    options: &ParseOptions,
+    open: &OpenElements,
+    recorder: Option<&mut SpanRecorder>,
+) {
+    let in_table = open.names.iter().any(|n| n == "table" || n == "template");
+    if in_table || !TABLE_PARTS.contains(&tag.tag.as_str()) {
+        nodes.push(HtmlNode::Tag(tag));
+        return;
+    }
+    let misplaced = Misplaced {
+        kind: MisplacedKind::TablePart,
+        tag: &tag.tag,
+        open: &open.names,
+    };
+    let strategy = options.repair_strategy(&misplaced);
+    if strategy != RepairStrategy::Strict {
+        metrics::record_recovered(ParseErrorKind::Syntax);
+    }
+    let path = recorder.as_ref().map(|r| {
+        let mut path = r.path.clone();
+        path.push(nodes.len());
+        path
+    });
+    match strategy {
+        RepairStrategy::Strict => nodes.push(HtmlNode::Tag(tag)),
+        RepairStrategy::Spec => {
+            if let (Some(r), Some(path)) = (recorder, path) {
+                r.remove_node(&path, true);
+            }
+            nodes.extend(tag.contents);
+        }
+        RepairStrategy::Drop => {
+            if let (Some(r), Some(path)) = (recorder, path) {
+                r.remove_node(&path, false);
+            }
+        }
+        RepairStrategy::KeepAsText => {
+            if let (Some(r), Some(path)) = (recorder, path) {
+                r.replace_with_text(&path);
+            }
+            nodes.push(HtmlNode::Text(HtmlNode::Tag(tag).to_html_string()));
+        }
+    }
+}
+
+/// Gets the strategy for an end tag which does not match the element it is in.
+pub(crate) fn end_tag_strategy(
+    end_tag: &str,
+    options: &ParseOptions,
+    open: &OpenElements,
+) -> RepairStrategy {
+    let strategy = options.repair_strategy(&Misplaced {
+        kind: MisplacedKind::EndTag,
+        tag: end_tag,
+        open: &open.names,
+    });
+    if strategy != RepairStrategy::Strict {
+        metrics::record_recovered(ParseErrorKind::Syntax);
+    }
+    strategy
+}
+
 pub fn parse_html_tag(chs: &mut std::str::Chars) -> Result<ParsedTagType, ParseHtmlError> {
+    parse_html_tag_with(
+        chs,
+        &ParseOptions::default(),
+        None,
+        &mut OpenElements::new(),
+    )
+}
+
+/// Parses a tag as [`parse_html_tag`] does using the options, adding the span of the
+/// tag and all of the nodes inside it to the recorder. The `<` of the tag must have
+/// just been read.
+pub(crate) fn parse_html_tag_with(
+    chs: &mut std::str::Chars,
+    options: &ParseOptions,
+    mut recorder: Option<&mut SpanRecorder>,
+    open: &mut OpenElements,
+) -> Result<ParsedTagType, ParseHtmlError> {
+    let start = recorder.as_ref().map(|r| r.offset(chs) - 1);
+    let mut content = None;
+    let parsed = parse_tag_and_content(chs, options, &mut recorder, &mut content, open)?;
+    if let (Some(r), Some(start)) = (recorder, start) {
+        if matches!(parsed, ParsedTagType::NewTag(_) | ParsedTagType::Comment(_)) {
+            r.spans.push(NodeSpan {
+                path: r.path.clone(),
+                range: start..r.offset(chs),
+                content,
+            });
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_tag_and_content(
+    chs: &mut std::str::Chars,
+    options: &ParseOptions,
+    recorder: &mut Option<&mut SpanRecorder>,
+    content: &mut Option<std::ops::Range<usize>>,
+    open: &mut OpenElements,
+) -> Result<ParsedTagType, ParseHtmlError> {
     // read chars into the buffer until a > or ' ' is found
     let mut buffer = String::with_capacity(50);
     //read first character and determine if this is an end tag
     match chs.next() {
         Some(ch) => {
             if ch == '/' {
-                return Ok(ParsedTagType::EndTag(
-                    parse_until_char(chs, '>', false)
-                        .map_err(|e| e.add_context(format!("Could not parse end tag")))?
-                        .trim_end()
-                        .to_owned(),
-                ));
+                // end tag names are short, so are read without allocating
+                let mut name: SmallString = SmallString::new();
+                for ch in chs.by_ref() {
+                    if ch == '>' {
+                        return Ok(ParsedTagType::EndTag(SmallString::from(name.trim_end())));
+                    }
+                    name.push(ch);
+                }
+                return Err(ParseHtmlError::new(format!(
+                    "end of string '{}' encountered before end char '>' was found",
+                    name
+                ))
+                .with_kind(ParseErrorKind::UnexpectedEnd)
+                .add_context("Could not parse end tag"));
             } else {
                 buffer.push(ch);
             }
@@ -373,7 +701,7 @@ pub fn parse_html_tag(chs: &mut std::str::Chars) -> Result<ParsedTagType, ParseH
     //Parse until we get the end of tag or a space
     buffer.push_str(
         parse_until_one_of(chs, vec![' ', '>'], true)
-            .map_err(|e| e.add_context(format!("Could parse starting tag after {}", buffer)))?
+            .map_err(|e| e.add_context(format!("Could parse starting tag after '{}'", buffer)))?
             .as_str(),
     );
     if buffer.starts_with("!--") {
@@ -396,10 +724,17 @@ pub fn parse_html_tag(chs: &mut std::str::Chars) -> Result<ParsedTagType, ParseH
                 .map_err(|e| e.add_context("Could not parse DOCTYPE"))?,
         ));
     }
-    let (tag_str, ending) = buffer.split_at(buffer.len() - 1);
+    let (mut tag_str, ending) = buffer.split_at(buffer.len() - 1);
+    let mut is_a_closed_tag = false;
+    if options.profile == ParseProfile::Component && ending == ">" {
+        if let Some(t) = tag_str.strip_suffix('/') {
+            // <Component/>
+            tag_str = t;
+            is_a_closed_tag = true;
+        }
+    }
     let tag = tag_str.trim().to_owned();
     let mut node = HtmlTag::new(tag_str);
-    let mut is_a_closed_tag = false;
     if ending != ">" {
         //define the some checking closures
         let is_ws_eq_or_gt = |ch: &char| -> bool {
@@ -410,15 +745,20 @@ pub fn parse_html_tag(chs: &mut std::str::Chars) -> Result<ParsedTagType, ParseH
             buffer.clear();
             match residual {
                 None => {
-                    buffer.push(get_next_non_whitespace(chs).map_err(|e| {
+                    let ch = get_next_non_whitespace(chs).map_err(|e| {
                         e.add_context(format!(
-                            "Could not get next attribute or '>' for node {}",
+                            "Could not get next attribute or '>' for node '{}'",
                             node
                         ))
-                    })?);
+                    })?;
+                    if !read_template(ch, chs, options, &mut buffer) {
+                        buffer.push(ch);
+                    }
                 }
                 Some(c) => {
-                    buffer.push(c);
+                    if !read_template(c, chs, options, &mut buffer) {
+                        buffer.push(c);
+                    }
                 }
             }
             if buffer == ">" {
@@ -426,7 +766,7 @@ pub fn parse_html_tag(chs: &mut std::str::Chars) -> Result<ParsedTagType, ParseH
                 break;
             }
             buffer.push_str(
-                parse_until(chs, is_ws_eq_or_gt, true)
+                parse_until_with(chs, options, is_ws_eq_or_gt, true)
                     .map_err(|e| {
                         e.add_context(format!("Could not get find end of attribute '{}'", buffer))
                     })?
@@ -436,8 +776,15 @@ pub fn parse_html_tag(chs: &mut std::str::Chars) -> Result<ParsedTagType, ParseH
                 is_a_closed_tag = true;
                 break;
             }
-            let (attr_str, attr_ending_str) = buffer.split_at(buffer.len() - 1);
+            let (mut attr_str, attr_ending_str) = buffer.split_at(buffer.len() - 1);
             let mut attr_ending = attr_ending_str.chars().next().unwrap();
+            if options.profile == ParseProfile::Component && attr_ending == '>' {
+                if let Some(a) = attr_str.strip_suffix('/') {
+                    // <Component disabled/>
+                    attr_str = a;
+                    is_a_closed_tag = true;
+                }
+            }
             if attr_ending.is_ascii_whitespace() {
                 let ch = get_next_non_whitespace(chs).map_err(|e| {
                     e.add_context(format!(
@@ -471,13 +818,27 @@ pub fn parse_html_tag(chs: &mut std::str::Chars) -> Result<ParsedTagType, ParseH
                 break;
             } else if attr_ending == '=' {
                 //We have 'attr =' now need to read in the value
-                let (attr_value_string, attr_value_ending) = parse_string(chs).map_err(|e| {
-                    e.add_context(format!("could not get value of attribute '{}'", attr_str))
-                })?;
+                let (mut attr_value_string, attr_value_ending) = parse_string_with(chs, options)
+                    .map_err(|e| {
+                        e.add_context(format!("could not get value of attribute '{}'", attr_str))
+                    })?;
+                if options.profile == ParseProfile::Component
+                    && attr_value_ending == '>'
+                    && attr_value_string.ends_with('/')
+                {
+                    // <Component key=value/>
+                    attr_value_string.pop();
+                    is_a_closed_tag = true;
+                }
+                let is_braced = options.profile == ParseProfile::Component
+                    && attr_value_string.starts_with('{');
+                if !is_braced {
+                    attr_value_string = options.decode_text(attr_value_string);
+                }
                 if attr_str == "class" {
-                    node.classes = parse_attibute_value(attr_value_string);
+                    node.classes = parse_attibute_value_with(attr_value_string, options);
                 } else if attr_str == "id" {
-                    node.ids = parse_attibute_value(attr_value_string);
+                    node.ids = parse_attibute_value_with(attr_value_string, options);
                 } else {
                     node.attributes
                         .insert(attr_str.to_string(), attr_value_string);
@@ -517,7 +878,24 @@ pub fn parse_html_tag(chs: &mut std::str::Chars) -> Result<ParsedTagType, ParseH
         _ => (),
     }
     if !is_a_closed_tag {
-        node.contents = parse_html_content(chs, tag)?;
+        let content_start = recorder.as_ref().map(|r| r.offset(chs));
+        if drops_leading_newline(&tag) {
+            skip_leading_newline(chs);
+        }
+        if tag.eq_ignore_ascii_case("textarea") {
+            node.contents = read_raw_text(chs, &tag, true, options, recorder.as_deref_mut())?;
+        } else if tag.eq_ignore_ascii_case("noscript") && options.noscript == NoscriptMode::RawText
+        {
+            node.contents = read_raw_text(chs, &tag, false, options, recorder.as_deref_mut())?;
+        } else {
+            open.names.push(tag.clone());
+            node.contents =
+                parse_html_content_with(chs, tag, options, recorder.as_deref_mut(), open)?;
+            open.names.pop();
+        }
+        if let (Some(r), Some(start)) = (recorder.as_ref(), content_start) {
+            *content = Some(start..r.end_tag_start);
+        }
     }
     Ok(ParsedTagType::NewTag(node))
 }
@@ -552,11 +930,11 @@ mod parse_html_tag_tests {
     fn parse_html_end_tag_test() {
         assert_eq!(
             parse_html_tag(&mut "/div>".chars()).unwrap(),
-            ParsedTagType::EndTag("div".to_string())
+            ParsedTagType::EndTag("div".into())
         );
         assert_eq!(
             parse_html_tag(&mut "/div >".chars()).unwrap(),
-            ParsedTagType::EndTag("div".to_string())
+            ParsedTagType::EndTag("div".into())
         );
     }
 
@@ -576,11 +954,11 @@ mod parse_html_tag_tests {
     fn parse_html_tag_errors() {
         assert_eq!(
             parse_html_tag(&mut "div".chars()),
-            Err(ParseHtmlError::with_msg("Could parse starting tag after d because end of string 'iv' encountered before any end char '[' ', '>']' was found"))
+            Err(ParseHtmlError::with_msg("Could parse starting tag after 'd' because end of string 'iv' encountered before any end char '[' ', '>']' was found"))
         );
         assert_eq!(
             parse_html_tag(&mut "div class=c1".chars()),
-            Err(ParseHtmlError::with_msg("Could not get next attribute or '>' for node <Tag: div , IDs: [], Classes: [\"c1\"], Attributes: {}, Contents: []> because End found while consuming whitespace."))
+            Err(ParseHtmlError::with_msg("Could not get next attribute or '>' for node '<Tag: div , IDs: [], Classes: [\"c1\"], Attributes: {}, Contents: []>' because End found while consuming whitespace."))
         );
         assert_eq!(
             parse_html_tag(&mut "div class   ".chars()),
@@ -604,31 +982,255 @@ mod parse_html_tag_tests {
         );
         assert_eq!(
             parse_html_tag(&mut "!DOCTYPE".chars()),
-            Err(ParseHtmlError::with_msg("Could parse starting tag after ! because end of string 'DOCTYPE' encountered before any end char '[' ', '>']' was found"))
+            Err(ParseHtmlError::with_msg("Could parse starting tag after '!' because end of string 'DOCTYPE' encountered before any end char '[' ', '>']' was found"))
         );
     }
 }
 
-pub fn parse_html_content(
+/// Checks whether a newline straight after the start tag of the element is not
+/// part of its contents, which is the case for the elements whose text is kept as
+/// it is written so that the text can start on the line after the tag.
+pub(crate) fn drops_leading_newline(tag: &str) -> bool {
+    ["pre", "textarea", "listing"]
+        .iter()
+        .any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+fn skip_leading_newline(chs: &mut std::str::Chars) {
+    let rest = chs.as_str();
+    if let Some(rest) = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))
+    {
+        *chs = rest.chars();
+    }
+}
+
+/// Reads the contents of an element which can only contain text, such as
+/// `<textarea>`, as it is written up to the end tag of the element, which is read
+/// too. The character references in the text are decoded if `decode` is true and
+/// the options say so.
+fn read_raw_text(
+    chs: &mut std::str::Chars,
+    tag: &str,
+    decode: bool,
+    options: &ParseOptions,
+    recorder: Option<&mut SpanRecorder>,
+) -> Result<Vec<HtmlNode>, ParseHtmlError> {
+    let rest = chs.as_str();
+    let end_tag = format!("</{}", tag);
+    let mut from = 0;
+    let (text_end, tag_end) = loop {
+        // the end tag is found ignoring case, eg </TEXTAREA> ends a <textarea>
+        let found = rest[from..]
+            .match_indices("</")
+            .map(|(i, _)| from + i)
+            .find(|&i| {
+                rest.as_bytes()[i..]
+                    .get(..end_tag.len())
+                    .is_some_and(|t| t.eq_ignore_ascii_case(end_tag.as_bytes()))
+            });
+        let i = match found {
+            Some(i) => i,
+            None => {
+                return Err(ParseHtmlError::new(format!(
+                    "End of file without finding tag '{}'.",
+                    tag
+                ))
+                .with_kind(ParseErrorKind::UnexpectedEnd))
+            }
+        };
+        let after = rest[i + end_tag.len()..].trim_start();
+        if after.starts_with('>') {
+            break (i, rest.len() - after.len() + 1);
+        }
+        from = i + end_tag.len();
+    };
+    let text = &rest[..text_end];
+    if let Some(r) = recorder {
+        let start = r.offset(chs);
+        if !text.is_empty() {
+            let mut path = r.path.clone();
+            path.push(0);
+            r.spans.push(NodeSpan {
+                path,
+                range: start..start + text_end,
+                content: None,
+            });
+        }
+        r.end_tag_start = start + text_end;
+    }
+    let mut contents = vec![];
+    if !text.is_empty() {
+        let text = text.to_owned();
+        contents.push(HtmlNode::Text(if decode {
+            options.decode_text(text)
+        } else {
+            text
+        }));
+    }
+    *chs = rest[tag_end..].chars();
+    Ok(contents)
+}
+
+/// Parses the content of a tag up to its end tag using the options, adding the
+/// span of each node to the recorder. The tag must be the last of the open
+/// elements.
+fn parse_html_content_with(
     chs: &mut std::str::Chars,
     tag: String,
+    options: &ParseOptions,
+    mut recorder: Option<&mut SpanRecorder>,
+    open: &mut OpenElements,
 ) -> Result<Vec<HtmlNode>, ParseHtmlError> {
     let mut text_content = String::new();
+    let mut text_start = recorder.as_ref().map_or(0, |r| r.offset(chs));
     let mut content: Vec<HtmlNode> = Vec::new();
-    while let Some(cur_char) = chs.next() {
-        if cur_char == '<' {
-            if text_content.len() > 0 {
-                content.push(HtmlNode::Text(text_content));
-                text_content = String::new();
+    let browser = options.tree_construction == TreeConstruction::Browser;
+    loop {
+        // kept so a start tag can be read again by the element it belongs in
+        let before = chs.clone();
+        let Some(cur_char) = chs.next() else {
+            break;
+        };
+        let tag_start = recorder
+            .as_ref()
+            .map_or(0, |r| r.offset(chs) - cur_char.len_utf8());
+        let mut template = String::new();
+        let is_template = read_template(cur_char, chs, options, &mut template);
+        if cur_char == '<' || is_template {
+            push_text(
+                &mut content,
+                std::mem::take(&mut text_content),
+                text_start..tag_start,
+                options,
+                recorder.as_deref_mut(),
+            );
+            if is_template {
+                if let Some(r) = recorder.as_deref_mut() {
+                    text_start = r.offset(chs);
+                    let mut path = r.path.clone();
+                    path.push(content.len());
+                    r.spans.push(NodeSpan {
+                        path,
+                        range: tag_start..text_start,
+                        content: None,
+                    });
+                }
+                content.push(HtmlNode::Template(template));
+                continue;
+            }
+            let action = match start_tag_name(chs.as_str()) {
+                Some(name) if browser => start_tag_action(&open.names, &content, name),
+                _ => StartTagAction::Insert,
+            };
+            match action {
+                StartTagAction::Insert => {}
+                StartTagAction::Close(i) => {
+                    *chs = before;
+                    if let Some(r) = recorder {
+                        r.end_tag_start = tag_start;
+                    }
+                    if i + 1 < open.names.len() {
+                        open.pending_end = Some(SmallString::from(open.names[i].as_str()));
+                    }
+                    return Ok(content);
+                }
+                StartTagAction::Imply(name) => {
+                    *chs = before;
+                    push_implied(
+                        &mut content,
+                        name,
+                        chs,
+                        options,
+                        recorder.as_deref_mut(),
+                        open,
+                    )?;
+                    if let Some(r) = recorder.as_deref_mut() {
+                        text_start = r.offset(chs);
+                    }
+                    if let Some(end_tag) = open.pending_end.take() {
+                        if end_tag != tag {
+                            open.pending_end = Some(end_tag);
+                        }
+                        return Ok(content);
+                    }
+                    continue;
+                }
             }
             //Read rest of tag - passing along any errors that were encountered.
-            match parse_html_tag(chs)? {
-                ParsedTagType::EndTag(end_tag) => {
-                    if end_tag != tag {
-                        return Err(ParseHtmlError::new(format!(
-                            "Incorrect end tag found {} but expected {}.",
-                            end_tag, tag
-                        )));
+            if let Some(r) = recorder.as_deref_mut() {
+                r.path.push(content.len());
+            }
+            let parsed = parse_html_tag_with(chs, options, recorder.as_deref_mut(), open);
+            if let Some(r) = recorder.as_deref_mut() {
+                r.path.pop();
+                text_start = r.offset(chs);
+            }
+            match parsed? {
+                ParsedTagType::EndTag(end_tag)
+                    if end_tag != tag && browser && closes_implied(&open.names, &end_tag) =>
+                {
+                    // close the elements up to the one it matches, whose end tags
+                    // can be left out
+                    if let Some(r) = recorder {
+                        r.end_tag_start = tag_start;
+                    }
+                    open.pending_end = Some(end_tag);
+                    return Ok(content);
+                }
+                ParsedTagType::EndTag(end_tag)
+                    if end_tag != tag
+                        && browser
+                        && !open.names.iter().any(|n| n.eq_ignore_ascii_case(&end_tag)) =>
+                {
+                    if let Some(node) = stray_end_tag_node(&end_tag) {
+                        if let Some(r) = recorder.as_deref_mut() {
+                            let mut path = r.path.clone();
+                            path.push(content.len());
+                            r.spans.push(NodeSpan {
+                                path,
+                                range: tag_start..text_start,
+                                content: Some(text_start..text_start),
+                            });
+                        }
+                        content.push(node);
+                    }
+                }
+                ParsedTagType::EndTag(end_tag) if end_tag != tag => {
+                    match end_tag_strategy(&end_tag, options, open) {
+                        RepairStrategy::Strict => {
+                            return Err(ParseHtmlError::new(format!(
+                                "Incorrect end tag found '{}' but expected '{}'.",
+                                end_tag, tag
+                            )));
+                        }
+                        RepairStrategy::Spec if open.names.iter().any(|n| *n == *end_tag) => {
+                            // close this element, and the others up to the one it matches
+                            if let Some(r) = recorder {
+                                r.end_tag_start = tag_start;
+                            }
+                            open.pending_end = Some(end_tag);
+                            return Ok(content);
+                        }
+                        RepairStrategy::Spec | RepairStrategy::Drop => {}
+                        RepairStrategy::KeepAsText => {
+                            if let Some(r) = recorder.as_deref_mut() {
+                                let mut path = r.path.clone();
+                                path.push(content.len());
+                                r.spans.push(NodeSpan {
+                                    path,
+                                    range: tag_start..text_start,
+                                    content: None,
+                                });
+                            }
+                            content.push(HtmlNode::Text(format!("</{}>", end_tag)));
+                        }
+                    }
+                }
+                ParsedTagType::EndTag(_) => {
+                    if let Some(r) = recorder {
+                        r.end_tag_start = tag_start;
                     }
                     //Got the correct end tag
                     if content.len() > 0 {
@@ -638,7 +1240,19 @@ pub fn parse_html_content(
                     }
                 }
                 ParsedTagType::NewTag(node_rc) => {
-                    content.push(HtmlNode::Tag(node_rc));
+                    push_element(
+                        &mut content,
+                        node_rc,
+                        options,
+                        open,
+                        recorder.as_deref_mut(),
+                    );
+                    if let Some(end_tag) = open.pending_end.take() {
+                        if end_tag != tag {
+                            open.pending_end = Some(end_tag);
+                        }
+                        return Ok(content);
+                    }
                 }
                 ParsedTagType::Comment(comment) => {
                     content.push(HtmlNode::Comment(comment));
@@ -652,15 +1266,96 @@ pub fn parse_html_content(
             }
         } else {
             text_content.push(cur_char);
+            if options.template_delimiters.is_empty() {
+                // the text runs until the next tag
+                let rest = chs.as_str();
+                let len = find_byte(rest.as_bytes(), b'<').unwrap_or(rest.len());
+                text_content.push_str(&rest[..len]);
+                *chs = rest[len..].chars();
+            }
         }
     }
+    if browser && has_optional_end_tag(&tag) {
+        if let Some(r) = recorder {
+            let end = r.offset(chs);
+            push_text(
+                &mut content,
+                text_content,
+                text_start..end,
+                options,
+                Some(&mut *r),
+            );
+            r.end_tag_start = end;
+        } else {
+            push_text(&mut content, text_content, 0..0, options, None);
+        }
+        return Ok(content);
+    }
     //Parse HTML until end tag </tag> is found
     return Err(ParseHtmlError::new(format!(
-        "End of file without finding tag {}.",
+        "End of file without finding tag '{}'.",
         tag
     )));
 }
 
+/// Adds the text to the content, unless it is empty.
+fn push_text(
+    content: &mut Vec<HtmlNode>,
+    text: String,
+    range: std::ops::Range<usize>,
+    options: &ParseOptions,
+    recorder: Option<&mut SpanRecorder>,
+) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(r) = recorder {
+        let mut path = r.path.clone();
+        path.push(content.len());
+        r.spans.push(NodeSpan {
+            path,
+            range,
+            content: None,
+        });
+    }
+    content.push(HtmlNode::Text(options.decode_text(text)));
+}
+
+/// Adds an element which the tree construction rules imply but which is not in
+/// the source, such as the `<tbody>` of a `<tr>` directly in a `<table>`, parsing
+/// the source up to where the element is closed into it.
+fn push_implied(
+    content: &mut Vec<HtmlNode>,
+    name: &str,
+    chs: &mut std::str::Chars,
+    options: &ParseOptions,
+    mut recorder: Option<&mut SpanRecorder>,
+    open: &mut OpenElements,
+) -> Result<(), ParseHtmlError> {
+    let start = recorder.as_ref().map_or(0, |r| r.offset(chs));
+    if let Some(r) = recorder.as_deref_mut() {
+        r.path.push(content.len());
+    }
+    open.names.push(name.to_owned());
+    let parsed =
+        parse_html_content_with(chs, name.to_owned(), options, recorder.as_deref_mut(), open);
+    open.names.pop();
+    if let Some(r) = recorder {
+        // the element starts and ends where its contents do
+        let range = start..r.end_tag_start;
+        r.spans.push(NodeSpan {
+            path: r.path.clone(),
+            range: range.clone(),
+            content: Some(range),
+        });
+        r.path.pop();
+    }
+    let mut tag = HtmlTag::new(name);
+    tag.contents = parsed?;
+    content.push(HtmlNode::Tag(tag));
+    Ok(())
+}
+
 #[cfg(test)]
 mod parse_html_document_tests {
     use super::*;
@@ -786,6 +1481,23 @@ mod parse_html_document_tests {
 
         assert_eq!(doc.nodes, doc_from_str.nodes);
     }
+
+    #[test]
+    fn parse_raw_text_end_tag_case_test() {
+        let doc = "<textarea>a <b>c</b></TEXTAREA><p>b</p>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        assert_eq!(doc.nodes[0].text(), "a <b>c</b>");
+        assert_eq!(doc.nodes[1].text(), "b");
+        let options = ParseOptions::new().noscript(NoscriptMode::RawText);
+        let doc = HtmlDocument::parse_with("<NoScript><p>a</p></NOSCRIPT >", &options).unwrap();
+        assert_eq!(doc.nodes[0].text(), "<p>a</p>");
+        // an end tag of another element with the same start is not the end
+        let doc = "<textarea>a</textareas>b</Textarea>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        assert_eq!(doc.nodes[0].text(), "a</textareas>b");
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -806,7 +1518,7 @@ pub fn parse_css_selector_rule(selector_rule: &str) -> Result<CssSelectorRule, P
             Some(c) => match c {
                 ',' => {
                     return Err(ParseHtmlError::with_msg(format!(
-                        "unexpected ',' in css selector rule {}",
+                        "unexpected ',' in css selector rule '{}'",
                         selector_rule
                     )))
                 }
@@ -1071,14 +1783,14 @@ fn parse_css_attribute_rule(
         match chs.peek() {
             None => {
                 return Err(ParseHtmlError::with_msg(format!(
-                    "Attribute rule not finished {}{}.",
+                    "Attribute rule not finished '{}{}'.",
                     attr, sep
                 )));
             }
             Some(c) => {
                 if *c != '=' {
                     return Err(ParseHtmlError::with_msg(format!(
-                        "unknown attribute rule qualifier {}{}.",
+                        "unknown attribute rule qualifier '{}{}'.",
                         sep, c
                     )));
                 }
@@ -1097,7 +1809,7 @@ fn parse_css_attribute_rule(
         "~=" => Ok(CssAttributeCompareType::ContainsWord((attr, value))),
         _ => {
             return Err(ParseHtmlError::with_msg(format!(
-                "unknown attribute rule qualifier {}.",
+                "unknown attribute rule qualifier '{}'.",
                 sep
             )));
         }
@@ -1149,7 +1861,7 @@ fn parse_css_refiner(
         return Ok(CssRefiner::NthChild(parse_css_refiner_number(
             &parse_until_and_including_char(chs, ')').map_err(|e| {
                 e.add_context(format!(
-                    "error while trying to read CSS refiner number after {}",
+                    "error while trying to read CSS refiner number after '{}'",
                     refiner
                 ))
             })?,
@@ -1158,7 +1870,7 @@ fn parse_css_refiner(
         return Ok(CssRefiner::NthLastChild(parse_css_refiner_number(
             &parse_until_and_including_char(chs, ')').map_err(|e| {
                 e.add_context(format!(
-                    "error while trying to read CSS refiner number after {}",
+                    "error while trying to read CSS refiner number after '{}'",
                     refiner
                 ))
             })?,
@@ -1173,7 +1885,7 @@ fn parse_css_refiner(
         return Ok(CssRefiner::NthOfType(parse_css_refiner_number(
             &parse_until_and_including_char(chs, ')').map_err(|e| {
                 e.add_context(format!(
-                    "error while trying to read CSS refiner number after {}",
+                    "error while trying to read CSS refiner number after '{}'",
                     refiner
                 ))
             })?,
@@ -1182,13 +1894,13 @@ fn parse_css_refiner(
         return Ok(CssRefiner::NthLastOfType(
             parse_css_refiner_number(&parse_until_and_including_char(chs, ')').map_err(|e| {
                 e.add_context(format!(
-                    "error while trying to read CSS refiner number after {}",
+                    "error while trying to read CSS refiner number after '{}'",
                     refiner
                 ))
             })?)
             .map_err(|e| {
                 e.add_context(format!(
-                    "error while trying to read CSS refiner number after {}",
+                    "error while trying to read CSS refiner number after '{}'",
                     refiner
                 ))
             })?,
@@ -1210,7 +1922,7 @@ fn parse_css_refiner(
         return Ok(CssRefiner::Root);
     }
     return Err(ParseHtmlError::with_msg(format!(
-        "unknown refiner type {}.",
+        "unknown refiner type '{}'.",
         refiner
     )));
 }
@@ -1232,14 +1944,14 @@ fn parse_css_refiner_number(raw_str: &str) -> Result<CssRefinerNumberType, Parse
     match str_iter.next() {
         None => {
             return Err(ParseHtmlError::with_msg(format!(
-                "No number found for refiner, expected a ( at the start of {}.",
+                "No number found for refiner, expected a ( at the start of '{}'.",
                 raw_str
             )))
         }
         Some(c) => {
             if c != '(' {
                 return Err(ParseHtmlError::with_msg(format!(
-                    "Expected ( after refiner which needs a number/even/odd/function but got {}",
+                    "Expected ( after refiner which needs a number/even/odd/function but got '{}'",
                     c
                 )));
             }
@@ -1248,14 +1960,14 @@ fn parse_css_refiner_number(raw_str: &str) -> Result<CssRefinerNumberType, Parse
     match str_iter.last() {
         None => {
             return Err(ParseHtmlError::with_msg(format!(
-                "No ) found after ( in refiner {}.",
+                "No ) found after ( in refiner '{}'.",
                 raw_str
             )))
         }
         Some(c) => {
             if c != ')' {
                 return Err(ParseHtmlError::with_msg(format!(
-                    "Expected ) at end of refiner number/even/odd/function but got {}",
+                    "Expected ) at end of refiner number/even/odd/function but got '{}'",
                     c
                 )));
             }
@@ -1266,7 +1978,7 @@ fn parse_css_refiner_number(raw_str: &str) -> Result<CssRefinerNumberType, Parse
     let parts: Vec<&str> = num_str.split('+').map(|x| x.trim()).collect();
     if parts.len() > 2 {
         return Err(ParseHtmlError::with_msg(format!(
-            "too many +'s present in refiner number {}",
+            "too many + signs present in refiner number '{}'",
             raw_str
         )));
     }
@@ -1278,7 +1990,7 @@ fn parse_css_refiner_number(raw_str: &str) -> Result<CssRefinerNumberType, Parse
             p => match p.parse::<usize>() {
                 Err(_) => {
                     return Err(ParseHtmlError::with_msg(format!(
-                        "could not parse number in refiner {}",
+                        "could not parse number in refiner '{}'",
                         raw_str
                     )))
                 }
@@ -1289,7 +2001,7 @@ fn parse_css_refiner_number(raw_str: &str) -> Result<CssRefinerNumberType, Parse
 
     if parts[0].chars().last().unwrap() != 'n' {
         return Err(ParseHtmlError::with_msg(format!(
-            "error parsing functional refiner, expected a 'n' at the end of {}",
+            "error parsing functional refiner, expected a 'n' at the end of '{}'",
             parts[0]
         )));
     }
@@ -1297,7 +2009,7 @@ fn parse_css_refiner_number(raw_str: &str) -> Result<CssRefinerNumberType, Parse
     let multi = match parts[0][0..parts[0].len() - 1].parse::<i32>() {
         Err(_) => {
             return Err(ParseHtmlError::with_msg(format!(
-                "could not parse int before the n in {}",
+                "could not parse int before the n in '{}'",
                 parts[0]
             )))
         }
@@ -1306,7 +2018,7 @@ fn parse_css_refiner_number(raw_str: &str) -> Result<CssRefinerNumberType, Parse
     let b = match parts[1].parse::<i32>() {
         Err(_) => {
             return Err(ParseHtmlError::with_msg(format!(
-                "could not parse int in {}",
+                "could not parse int in '{}'",
                 parts[1]
             )))
         }
@@ -1398,14 +2110,14 @@ mod parse_css_selector_tests {
         let tests = vec![(
             "nth-last-of-type(1a)",
             ParseHtmlError::with_msg(
-                "error while trying to read CSS refiner number after nth-last-of-type because could not parse number in refiner (1a)",
+                "error while trying to read CSS refiner number after 'nth-last-of-type' because could not parse number in refiner '(1a)'",
             )),
             ("nth-last-of-type(1",
             ParseHtmlError::with_msg(
-                "error while trying to read CSS refiner number after nth-last-of-type because end of string '(1' encountered before ending ')' was found",
+                "error while trying to read CSS refiner number after 'nth-last-of-type' because end of string '(1' encountered before ending ')' was found",
             )),
             ("something-not-a-refiner",
-             ParseHtmlError::with_msg("unknown refiner type something-not-a-refiner.")
+             ParseHtmlError::with_msg("unknown refiner type 'something-not-a-refiner'.")
             ),
         ];
 
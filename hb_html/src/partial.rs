@@ -0,0 +1,373 @@
+//! Parsing only as much of a document as is needed to find some elements.
+//!
+//! [`parse_head`] reads only the head of a document, for getting the metadata of a
+//! page.
+//!
+//! Scraping the title or canonical link of a page only needs the first few
+//! kilobytes of it. [`parse_until_match`] scans the source for the start of a tag a
+//! little way in, closes the tags which are still open at that point and parses
+//! what comes before it. If the selector does not have a match in that part of the
+//! document, twice as much is parsed, and so on until it does or the whole
+//! document has been parsed. Nothing after the point where it stopped is read, so
+//! markup there which could not be parsed does not cause an error.
+//!
+//! An element only counts as a match once its end tag has been parsed, so its
+//! contents are complete. Refiners which look at later siblings or at the
+//! contents of an element, such as `:last-child`, are matched against the part of
+//! the document which was parsed.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::partial::parse_until_match;
+//! let mut html = String::from("<html><head><title>Home</title></head><body>");
+//! html.push_str(&"<p>text</p>".repeat(10_000));
+//! html.push_str("</body></html>");
+//! let partial = parse_until_match(&html, "head title").unwrap();
+//! assert!(!partial.complete);
+//! assert!(partial.parsed_len < html.len());
+//! let title = partial.document.select_first("title").unwrap().unwrap();
+//! assert_eq!(title.text(), "Home");
+//! ```
+use crate::error::ParseHtmlError;
+use crate::memfind::find_byte;
+use crate::objects::{CssSelector, HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::querying::HtmlQueryable;
+use std::str::FromStr;
+
+/// How many bytes are parsed the first time.
+const FIRST_CHUNK: usize = 16 * 1024;
+
+/// The elements which hold the metadata of a page, which are all that can be in
+/// the head of a document.
+const METADATA_TAGS: [&str; 8] = [
+    "base", "link", "meta", "noscript", "script", "style", "template", "title",
+];
+
+const VOID_TAGS: [&str; 16] = [
+    "area", "base", "br", "col", "command", "embed", "hr", "img", "input", "keygen", "link",
+    "meta", "param", "source", "track", "wbr",
+];
+
+/// The part of a document which was parsed by [`parse_until_match`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialDocument {
+    /// The nodes which were parsed, tags which were open where parsing stopped are
+    /// closed there.
+    pub document: HtmlDocument,
+    /// The number of bytes of the source which were parsed.
+    pub parsed_len: usize,
+    /// True if the whole source was parsed.
+    pub complete: bool,
+}
+
+/// Parses the start of the document until an element matching the selector has
+/// been parsed, see the [module documentation](self).
+pub fn parse_until_match(html: &str, selector: &str) -> Result<PartialDocument, ParseHtmlError> {
+    parse_until_matches(html, selector, 1)
+}
+
+/// Parses the start of the document until `count` elements matching the selector
+/// have been parsed, or the whole document if there are fewer than that.
+pub fn parse_until_matches(
+    html: &str,
+    selector: &str,
+    count: usize,
+) -> Result<PartialDocument, ParseHtmlError> {
+    let selector = CssSelector::from_str(selector)?;
+    let mut scanner = TagScanner::new(html);
+    let mut limit = FIRST_CHUNK;
+    loop {
+        let cut = scanner.scan_to(limit);
+        let complete = cut.is_none();
+        let parsed_len = cut.unwrap_or(html.len());
+        let document = if complete {
+            html.parse::<HtmlDocument>()?
+        } else {
+            scanner.closed_prefix(parsed_len).parse::<HtmlDocument>()?
+        };
+        if complete || count_closed_matches(&document, &selector, scanner.open.len()) >= count {
+            return Ok(PartialDocument {
+                document,
+                parsed_len,
+                complete,
+            });
+        }
+        limit = limit.saturating_mul(2);
+    }
+}
+
+/// Parses only the head of the document, giving the metadata elements in it such
+/// as `title`, `meta` and `link`. Parsing stops after `</head>`, or where the
+/// first element which can not be in the head starts when the head is not closed
+/// or the `head` (or `html`) start tag is left out, as it is in
+/// `<meta charset="utf-8"><title>Home</title><p>Hello</p>`. The rest of the
+/// document is not read, so it does not need to be valid.
+///
+/// # Example
+///
+/// ```
+/// use hb_html::partial::parse_head;
+/// let html = r#"<html><title>Home</title><link rel="canonical" href="/"><body><p>Hello</q>"#;
+/// let head = parse_head(html).unwrap();
+/// let names: Vec<&str> = head.iter().map(|t| t.tag.as_str()).collect();
+/// assert_eq!(names, vec!["title", "link"]);
+/// assert_eq!(head[1].attributes["href"], "/");
+/// ```
+pub fn parse_head(html: &str) -> Result<Vec<HtmlTag>, ParseHtmlError> {
+    let mut scanner = TagScanner::new(html);
+    let end = loop {
+        let start = match scanner.next_start() {
+            Some(start) => start,
+            None => break html.len(),
+        };
+        // the contents of metadata elements are read without looking at them
+        let in_head = scanner
+            .open
+            .iter()
+            .all(|name| is_one_of(name, &["html", "head"]));
+        let name = scanner.tag_name(start);
+        if let (true, Some((name, false))) = (in_head, name) {
+            if !is_one_of(name, &["html", "head"]) && !is_one_of(name, &METADATA_TAGS) {
+                break start;
+            }
+        }
+        if !scanner.read_tag(start) {
+            break start;
+        }
+        if let Some((name, true)) = name {
+            if name.trim_end().eq_ignore_ascii_case("head") {
+                break scanner.pos;
+            }
+        }
+    };
+    let doc = scanner.closed_prefix(end).parse::<HtmlDocument>()?;
+    let mut found = vec![];
+    collect_metadata(doc.nodes, &mut found);
+    Ok(found)
+}
+
+fn is_one_of(name: &str, names: &[&str]) -> bool {
+    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+/// Adds the metadata elements in the nodes, or in the `html` and `head` elements
+/// in them, to the list.
+fn collect_metadata(nodes: Vec<HtmlNode>, found: &mut Vec<HtmlTag>) {
+    for node in nodes {
+        if let HtmlNode::Tag(mut tag) = node {
+            if is_one_of(&tag.tag, &["html", "head"]) {
+                collect_metadata(std::mem::take(&mut tag.contents), found);
+            } else if is_one_of(&tag.tag, &METADATA_TAGS) {
+                found.push(tag);
+            }
+        }
+    }
+}
+
+/// Counts the matches of the selector, leaving out the elements which were still
+/// open where the source was cut. Those are the last node at each level of the
+/// tree, down to the number of tags which were open.
+fn count_closed_matches(doc: &HtmlDocument, selector: &CssSelector, open: usize) -> usize {
+    let mut open_paths: Vec<NodePath> = Vec::with_capacity(open);
+    let mut nodes = &doc.nodes;
+    let mut path = vec![];
+    while open_paths.len() < open && !nodes.is_empty() {
+        path.push(nodes.len() - 1);
+        open_paths.push(path.clone());
+        nodes = match &nodes[nodes.len() - 1] {
+            HtmlNode::Tag(t) => &t.contents,
+            _ => break,
+        };
+    }
+    let mut query = doc.query();
+    query.find(selector);
+    query
+        .results
+        .iter()
+        .filter(|r| !open_paths.contains(&r.node_path()))
+        .count()
+}
+
+/// Finds where tags start in the source while keeping track of which tags are open.
+struct TagScanner<'a> {
+    html: &'a str,
+    pos: usize,
+    open: Vec<&'a str>,
+}
+
+impl<'a> TagScanner<'a> {
+    fn new(html: &'a str) -> TagScanner<'a> {
+        TagScanner {
+            html,
+            pos: 0,
+            open: vec![],
+        }
+    }
+
+    /// Moves to the start of the first tag at or after the limit, giving its offset,
+    /// or None if the end of the source is reached first.
+    fn scan_to(&mut self, limit: usize) -> Option<usize> {
+        loop {
+            let start = self.next_start()?;
+            if start >= limit {
+                self.pos = start;
+                return Some(start);
+            }
+            // an unfinished tag is left for the parser to report
+            if !self.read_tag(start) {
+                return None;
+            }
+        }
+    }
+
+    /// Finds the offset of the start of the next tag without reading it.
+    fn next_start(&self) -> Option<usize> {
+        find_byte(&self.html.as_bytes()[self.pos..], b'<').map(|i| self.pos + i)
+    }
+
+    /// Gets the name of the tag which starts at the offset, along with whether it is
+    /// an end tag, or None for comments and doctypes.
+    fn tag_name(&self, start: usize) -> Option<(&'a str, bool)> {
+        let rest = &self.html[start + 1..];
+        if rest.starts_with('!') {
+            return None;
+        }
+        let (rest, is_end) = match rest.strip_prefix('/') {
+            Some(rest) => (rest, true),
+            None => (rest, false),
+        };
+        let len = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        Some((&rest[..len], is_end))
+    }
+
+    /// Reads the tag which starts at the offset, moving past it. Returns false if
+    /// the tag is not finished.
+    fn read_tag(&mut self, start: usize) -> bool {
+        let rest = &self.html[start + 1..];
+        let len = if let Some(comment) = rest.strip_prefix("!--") {
+            comment.find("-->").map(|i| i + 6)
+        } else if rest.starts_with('!') {
+            find_byte(rest.as_bytes(), b'>').map(|i| i + 1)
+        } else if let Some(end) = rest.strip_prefix('/') {
+            find_byte(end.as_bytes(), b'>').map(|i| {
+                if self.open.last() == Some(&end[..i].trim_end()) {
+                    self.open.pop();
+                }
+                i + 2
+            })
+        } else {
+            self.start_tag(rest)
+        };
+        match len {
+            None => false,
+            Some(len) => {
+                self.pos = start + 1 + len;
+                true
+            }
+        }
+    }
+
+    /// Gets the source up to the offset with the tags which are open closed.
+    fn closed_prefix(&self, end: usize) -> String {
+        let mut prefix = String::with_capacity(end + self.open.len() * 8);
+        prefix.push_str(&self.html[..end]);
+        for name in self.open.iter().rev() {
+            prefix.push_str("</");
+            prefix.push_str(name);
+            prefix.push('>');
+        }
+        prefix
+    }
+
+    /// Reads a start tag, giving the length of the tag after the `<`.
+    fn start_tag(&mut self, rest: &'a str) -> Option<usize> {
+        let name_len = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let mut quote = None;
+        for (i, c) in rest.char_indices() {
+            match (quote, c) {
+                _ if i < name_len => (),
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => (),
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '>') => {
+                    let name = &rest[..name_len];
+                    if !VOID_TAGS.contains(&name) && !rest[..i].ends_with('/') {
+                        self.open.push(name);
+                    }
+                    return Some(i + 1);
+                }
+                (None, _) => (),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod partial_tests {
+    use super::*;
+
+    #[test]
+    fn parse_until_match_test() {
+        let mut html = String::from(
+            r#"<!DOCTYPE html><html><head><!-- <p> --><meta charset="utf-8"><link rel="canonical" href="/a>b"></head><body>"#,
+        );
+        html.push_str(&"<div><p>text</p></div>".repeat(2_000));
+        // never read, so does not stop the head being found
+        html.push_str("<p></q></body></html>");
+        let partial = parse_until_match(&html, "link[rel=canonical]").unwrap();
+        assert!(!partial.complete);
+        assert_eq!(partial.document.doctype, "html");
+        match partial.document.select_first("link").unwrap() {
+            Some(HtmlNode::Tag(link)) => assert_eq!(link.attributes["href"], "/a>b"),
+            other => panic!("expected the link tag, got {:?}", other),
+        }
+        // the body was still open where parsing stopped
+        assert_eq!(partial.document.select("body").unwrap().len(), 1);
+        assert!(html.parse::<HtmlDocument>().is_err());
+
+        // a match which is still open is not counted until it is closed
+        let partial = parse_until_matches(&html, "body > div", 1_000).unwrap();
+        assert!(partial.parsed_len > 1_000 * 22);
+        assert!(partial.document.select("body > div").unwrap().len() >= 1_000);
+
+        let small = "<ul><li>a</li><li>b</li></ul>";
+        let partial = parse_until_matches(small, "li", 5).unwrap();
+        assert!(partial.complete);
+        assert_eq!(partial.parsed_len, small.len());
+        assert_eq!(partial.document, small.parse::<HtmlDocument>().unwrap());
+        assert!(parse_until_match(small, "li[").is_err());
+    }
+
+    #[test]
+    fn parse_head_test() {
+        let names = |head: Vec<HtmlTag>| head.into_iter().map(|t| t.tag).collect::<Vec<_>>();
+        let html = r#"<!DOCTYPE html><HTML><HEAD><meta charset="utf-8"><!-- <div> -->
+<title>A title</title><script>var a = 1;</script></HEAD><body><p>Hi</q></body></HTML>"#;
+        assert_eq!(
+            names(parse_head(html).unwrap()),
+            vec!["meta", "title", "script"]
+        );
+        // without a head end tag the head ends at the first element which is not metadata
+        let html = "<html><head><title>T</title><style>p {}</style><div><meta name=x>";
+        assert_eq!(names(parse_head(html).unwrap()), vec!["title", "style"]);
+        let html = r#"<meta name="description" content="About"><base href="/"><p>text"#;
+        let head = parse_head(html).unwrap();
+        assert_eq!(head[0].attributes["content"], "About");
+        assert_eq!(names(head), vec!["meta", "base"]);
+        // an unfinished tag ends the head
+        assert_eq!(
+            names(parse_head("<title>T</title><link").unwrap()),
+            vec!["title"]
+        );
+        assert!(parse_head("<body><title>T</title></body>")
+            .unwrap()
+            .is_empty());
+        assert!(parse_head("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,732 @@
+//! Composable document transforms.
+//!
+//! A [`Pipeline`] runs a list of [`Pass`] objects over a document one after the other.
+//! All of the passes share a [`PassContext`] which gives them access to the
+//! [`PipelineOptions`], a store of values for passing data between passes and a
+//! list of diagnostics which are returned in a [`PipelineReport`].
+//!
+//! A pass which changes attributes can tell the context with
+//! [`PassContext::attribute_changed`]. Later passes which list the attributes they
+//! depend on in [`Pass::watches`] get the changes to them from
+//! [`PassContext::watched_changes`], so they can update just the elements which
+//! were changed instead of looking through the whole document again. Changes are
+//! only recorded for attributes which some pass in the pipeline watches.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::pipeline::Pipeline;
+//! use hb_html::rewrite::RewriteUrls;
+//! use hb_html::sanitize::Sanitize;
+//! let mut doc = r#"<div><a href="http://old.example/page" onclick="x()">Link</a><script>x()</script></div>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let report = Pipeline::new()
+//!     .add(Sanitize::default())
+//!     .add(RewriteUrls::new().rule("http://old.example/", "https://new.example/"))
+//!     .run(&mut doc)
+//!     .unwrap();
+//! assert_eq!(
+//!     doc.nodes[0].to_html_string(),
+//!     r#"<div><a href="https://new.example/page">Link</a></div>"#
+//! );
+//! assert_eq!(report.passes_run, vec!["sanitize", "rewrite-urls"]);
+//! ```
+use crate::error::{HtmlDocError, ParseHtmlError};
+use crate::objects::{CssSelector, HtmlDocument, HtmlNode, NodePath};
+use crate::querying::HtmlQueryResult;
+use crate::stable_id::StableIds;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// A transform which can be run over a document as part of a [`Pipeline`].
+pub trait Pass {
+    /// The name of the pass, used to label diagnostics.
+    fn name(&self) -> &str;
+    /// Runs the pass over the document.
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError>;
+    /// The attributes the pass wants to hear about changes to from the passes
+    /// before it, see [`PassContext::watched_changes`].
+    fn watches(&self) -> Vec<AttributeWatch> {
+        vec![]
+    }
+}
+
+/// An attribute of the elements matching a selector which a [`Pass`] watches for
+/// changes to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeWatch {
+    /// The name of the attribute, which is compared ignoring case.
+    pub attribute: String,
+    pub selector: CssSelector,
+}
+
+impl AttributeWatch {
+    /// Watches the attribute on the elements matching the selector, returning an
+    /// error if the selector can not be parsed.
+    pub fn new(attribute: &str, selector: &str) -> Result<AttributeWatch, ParseHtmlError> {
+        Ok(AttributeWatch {
+            attribute: attribute.to_ascii_lowercase(),
+            selector: CssSelector::from_str(selector)?,
+        })
+    }
+}
+
+/// A change to an attribute made by a pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeChange {
+    /// The name of the pass which made the change.
+    pub pass: String,
+    /// The path of the element at the time of the change.
+    pub path: NodePath,
+    pub attribute: String,
+    /// The value before the change, None if the attribute was added.
+    pub old: Option<String>,
+    /// The value after the change, None if the attribute was removed.
+    pub new: Option<String>,
+}
+
+/// Options which apply to the whole pipeline and are available to every pass.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct PipelineOptions {
+    /// Stop running passes after the first pass which returns an error.
+    pub stop_on_error: bool,
+    /// Free-form settings which passes can read by name.
+    pub settings: HashMap<String, String>,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        PipelineOptions {
+            stop_on_error: true,
+            settings: HashMap::new(),
+        }
+    }
+}
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A message reported by a pass while it was running.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The name of the pass that reported the message.
+    pub pass: String,
+    pub severity: Severity,
+    pub message: String,
+    /// The node the message is about, if it is about a specific node.
+    pub path: Option<NodePath>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {:?}: {}", self.pass, self.severity, self.message)?;
+        if let Some(path) = &self.path {
+            write!(f, " (at {:?})", path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Limits on the number of diagnostics kept for a document, so that a document
+/// which causes a huge number of diagnostics does not use a huge amount of memory.
+///
+/// Diagnostics of the same kind (from the same pass with the same severity) are
+/// all kept up to `max_per_kind`, after which only one in every `sample_every` is
+/// kept. No more than `max_total` are kept in all. The diagnostics which are not
+/// kept are counted, and a summary of them is added to the [`PipelineReport`].
+///
+/// The same limits are used for the errors recovered from while parsing a
+/// document, see [`Metrics::diagnostic_limits`](crate::metrics::Metrics::diagnostic_limits),
+/// where the kind is the [`ParseErrorKind`](crate::error::ParseErrorKind).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiagnosticLimits {
+    /// The most diagnostics kept for the document, None for no limit.
+    pub max_total: Option<usize>,
+    /// The number of diagnostics of each kind which are kept before sampling
+    /// starts, None for no limit.
+    pub max_per_kind: Option<usize>,
+    /// Keep one in every this many diagnostics of a kind after `max_per_kind`. Zero
+    /// (the default) keeps none of them.
+    pub sample_every: usize,
+}
+
+impl DiagnosticLimits {
+    /// Creates limits which keep every diagnostic.
+    pub fn new() -> DiagnosticLimits {
+        DiagnosticLimits::default()
+    }
+
+    pub fn max_total(mut self, max_total: usize) -> DiagnosticLimits {
+        self.max_total = Some(max_total);
+        self
+    }
+
+    pub fn max_per_kind(mut self, max_per_kind: usize) -> DiagnosticLimits {
+        self.max_per_kind = Some(max_per_kind);
+        self
+    }
+
+    pub fn sample_every(mut self, sample_every: usize) -> DiagnosticLimits {
+        self.sample_every = sample_every;
+        self
+    }
+
+    /// Returns true if the diagnostic should be kept, when `seen` diagnostics of
+    /// its kind (including it) have been reported and `kept` have been kept in all.
+    pub(crate) fn keeps(&self, seen: usize, kept: usize) -> bool {
+        if self.max_total.is_some_and(|max| kept >= max) {
+            return false;
+        }
+        match self.max_per_kind {
+            Some(max) if seen > max => {
+                self.sample_every > 0 && (seen - max).is_multiple_of(self.sample_every)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// State shared between all of the passes in a single run of a [`Pipeline`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassContext {
+    /// The options for the pipeline.
+    pub options: PipelineOptions,
+    /// Values stored by earlier passes for later passes to use.
+    pub values: HashMap<String, String>,
+    /// The stable IDs of the elements, stored by [`StableIdPass`](crate::stable_id::StableIdPass).
+    pub stable_ids: Option<StableIds>,
+    diagnostics: Vec<Diagnostic>,
+    current_pass: String,
+    limits: DiagnosticLimits,
+    /// The number of diagnostics reported and dropped, by pass and severity.
+    seen: HashMap<(String, Severity), (usize, usize)>,
+    /// The attributes watched by any of the passes, in lower case.
+    watched: HashSet<String>,
+    /// The watches of the pass which is currently running.
+    watches: Vec<AttributeWatch>,
+    changes: Vec<AttributeChange>,
+    /// The number of changes which were made before the current pass started.
+    changes_before: usize,
+}
+
+impl PassContext {
+    /// Creates a context with no values or diagnostics.
+    pub fn new(options: PipelineOptions) -> PassContext {
+        PassContext {
+            options,
+            values: HashMap::new(),
+            stable_ids: None,
+            diagnostics: vec![],
+            current_pass: String::new(),
+            limits: DiagnosticLimits::new(),
+            seen: HashMap::new(),
+            watched: HashSet::new(),
+            watches: vec![],
+            changes: vec![],
+            changes_before: 0,
+        }
+    }
+
+    /// Sets the limits on the number of diagnostics which are kept.
+    pub fn with_limits(mut self, limits: DiagnosticLimits) -> PassContext {
+        self.limits = limits;
+        self
+    }
+
+    /// Gets a setting from the pipeline options.
+    pub fn setting(&self, name: &str) -> Option<&str> {
+        self.options.settings.get(name).map(|s| s.as_str())
+    }
+
+    /// Adds a diagnostic for the pass which is currently running.
+    pub fn report<S: Into<String>>(
+        &mut self,
+        severity: Severity,
+        message: S,
+        path: Option<NodePath>,
+    ) {
+        let counts = self
+            .seen
+            .entry((self.current_pass.clone(), severity))
+            .or_default();
+        counts.0 += 1;
+        if !self.limits.keeps(counts.0, self.diagnostics.len()) {
+            counts.1 += 1;
+            return;
+        }
+        self.diagnostics.push(Diagnostic {
+            pass: self.current_pass.clone(),
+            severity,
+            message: message.into(),
+            path,
+        });
+    }
+
+    pub fn info<S: Into<String>>(&mut self, message: S, path: Option<NodePath>) {
+        self.report(Severity::Info, message, path)
+    }
+
+    pub fn warning<S: Into<String>>(&mut self, message: S, path: Option<NodePath>) {
+        self.report(Severity::Warning, message, path)
+    }
+
+    /// All of the diagnostics reported so far which were kept.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Returns true if a pass watches the attribute, so changes to it are recorded.
+    pub fn is_watched(&self, attribute: &str) -> bool {
+        self.watched.contains(&attribute.to_ascii_lowercase())
+    }
+
+    /// Records a change the current pass made to an attribute of the element at the
+    /// path, if any pass watches the attribute.
+    pub fn attribute_changed(
+        &mut self,
+        path: NodePath,
+        attribute: &str,
+        old: Option<String>,
+        new: Option<String>,
+    ) {
+        if !self.is_watched(attribute) {
+            return;
+        }
+        self.changes.push(AttributeChange {
+            pass: self.current_pass.clone(),
+            path,
+            attribute: attribute.to_owned(),
+            old,
+            new,
+        });
+    }
+
+    /// All of the attribute changes recorded so far.
+    pub fn changes(&self) -> &[AttributeChange] {
+        &self.changes
+    }
+
+    /// Gets the changes made by the earlier passes to the attributes the current
+    /// pass watches, where the element at the path of the change matches the
+    /// selector of the watch. The paths are not updated when nodes are added or
+    /// removed, so a change is left out if its element has since moved out from
+    /// under a matching path.
+    pub fn watched_changes(&self, doc: &HtmlDocument) -> Vec<&AttributeChange> {
+        self.changes[..self.changes_before]
+            .iter()
+            .filter(|c| {
+                self.watches.iter().any(|w| {
+                    w.attribute.eq_ignore_ascii_case(&c.attribute)
+                        && element_at(doc, &c.path).is_some_and(|r| r.matches(&w.selector))
+                })
+            })
+            .collect()
+    }
+
+    /// The number of diagnostics which were dropped because of the limits.
+    pub fn dropped(&self) -> usize {
+        self.seen.values().map(|(_, dropped)| dropped).sum()
+    }
+
+    /// Gets a diagnostic for each kind of diagnostic which had some dropped, saying
+    /// how many, ordered by pass name and then severity.
+    fn overflow_summary(&self) -> Vec<Diagnostic> {
+        let mut dropped: Vec<(&(String, Severity), usize)> = self
+            .seen
+            .iter()
+            .filter(|(_, (_, dropped))| *dropped > 0)
+            .map(|(kind, (_, dropped))| (kind, *dropped))
+            .collect();
+        dropped.sort();
+        dropped
+            .into_iter()
+            .map(|((pass, severity), count)| Diagnostic {
+                pass: pass.clone(),
+                severity: *severity,
+                message: format!("{} more diagnostics were dropped", count),
+                path: None,
+            })
+            .collect()
+    }
+}
+
+/// The result of running a [`Pipeline`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineReport {
+    /// The names of the passes which were run, in order.
+    pub passes_run: Vec<String>,
+    /// The diagnostics reported by all of the passes.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The values left in the context by the passes.
+    pub values: HashMap<String, String>,
+    /// The stable IDs left in the context by a
+    /// [`StableIdPass`](crate::stable_id::StableIdPass).
+    pub stable_ids: Option<StableIds>,
+    /// The number of diagnostics which were dropped because of the [`DiagnosticLimits`].
+    /// The diagnostics end with a summary of each kind which had some dropped.
+    pub dropped_diagnostics: usize,
+    /// The changes to watched attributes made by the passes, in order.
+    pub attribute_changes: Vec<AttributeChange>,
+}
+
+impl PipelineReport {
+    /// Gets the diagnostics reported by the pass with the name provided.
+    pub fn diagnostics_for(&self, pass: &str) -> Vec<&Diagnostic> {
+        self.diagnostics.iter().filter(|d| d.pass == pass).collect()
+    }
+
+    /// Returns true if any pass reported an error.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// An ordered list of passes which are run over a document.
+pub struct Pipeline {
+    passes: Vec<Box<dyn Pass>>,
+    options: PipelineOptions,
+    limits: DiagnosticLimits,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pipeline {
+    /// Creates an empty pipeline with the default options.
+    pub fn new() -> Pipeline {
+        Pipeline {
+            passes: vec![],
+            options: PipelineOptions::default(),
+            limits: DiagnosticLimits::new(),
+        }
+    }
+
+    /// Sets the options used when running the pipeline.
+    pub fn options(mut self, options: PipelineOptions) -> Pipeline {
+        self.options = options;
+        self
+    }
+
+    /// Sets the limits on the number of diagnostics kept for each run.
+    pub fn diagnostic_limits(mut self, limits: DiagnosticLimits) -> Pipeline {
+        self.limits = limits;
+        self
+    }
+
+    /// Adds a pass to the end of the pipeline.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add<P: Pass + 'static>(mut self, pass: P) -> Pipeline {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Adds an already boxed pass to the end of the pipeline.
+    pub fn add_boxed(mut self, pass: Box<dyn Pass>) -> Pipeline {
+        self.passes.push(pass);
+        self
+    }
+
+    /// The names of the passes in the pipeline, in the order they will be run.
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.passes.iter().map(|p| p.name()).collect()
+    }
+
+    /// Runs all of the passes over the document.
+    ///
+    /// An error from a pass is recorded as a diagnostic. If the stop_on_error option
+    /// is set then the error is also returned and no more passes are run.
+    pub fn run(&mut self, doc: &mut HtmlDocument) -> Result<PipelineReport, HtmlDocError> {
+        let mut ctx = PassContext::new(self.options.clone()).with_limits(self.limits.clone());
+        let mut passes_run = vec![];
+        ctx.watched = self
+            .passes
+            .iter()
+            .flat_map(|p| p.watches())
+            .map(|w| w.attribute.to_ascii_lowercase())
+            .collect();
+        for pass in &mut self.passes {
+            ctx.current_pass = pass.name().to_owned();
+            ctx.watches = pass.watches();
+            ctx.changes_before = ctx.changes.len();
+            passes_run.push(ctx.current_pass.clone());
+            if let Err(e) = pass.run(doc, &mut ctx) {
+                ctx.report(Severity::Error, format!("{}", e), None);
+                if ctx.options.stop_on_error {
+                    return Err(HtmlDocError::with_msg(format!(
+                        "pass '{}' failed: {}",
+                        ctx.current_pass, e
+                    )));
+                }
+            }
+        }
+        let overflow = ctx.overflow_summary();
+        let dropped_diagnostics = ctx.dropped();
+        let mut diagnostics = ctx.diagnostics;
+        diagnostics.extend(overflow);
+        Ok(PipelineReport {
+            passes_run,
+            diagnostics,
+            values: ctx.values,
+            stable_ids: ctx.stable_ids,
+            dropped_diagnostics,
+            attribute_changes: ctx.changes,
+        })
+    }
+}
+
+/// Gets a query result pointing at the element at the path.
+fn element_at<'a>(doc: &'a HtmlDocument, path: &[usize]) -> Option<HtmlQueryResult<'a>> {
+    let mut result = HtmlQueryResult { path: vec![] };
+    let mut nodes = &doc.nodes;
+    for i in path {
+        match nodes.get(*i) {
+            Some(HtmlNode::Tag(tag)) => {
+                result.path.push((nodes, *i));
+                nodes = &tag.contents;
+            }
+            _ => return None,
+        }
+    }
+    (!result.path.is_empty()).then_some(result)
+}
+
+#[cfg(test)]
+mod pipeline_tests {
+    use super::*;
+    use crate::objects::HtmlNode;
+
+    struct Counter;
+    impl Pass for Counter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+        fn run(
+            &mut self,
+            doc: &mut HtmlDocument,
+            ctx: &mut PassContext,
+        ) -> Result<(), HtmlDocError> {
+            let count = doc.find("*").nodes().len();
+            ctx.values.insert("count".to_owned(), count.to_string());
+            ctx.info(format!("found {} nodes", count), None);
+            Ok(())
+        }
+    }
+
+    struct AppendSetting;
+    impl Pass for AppendSetting {
+        fn name(&self) -> &str {
+            "append"
+        }
+        fn run(
+            &mut self,
+            doc: &mut HtmlDocument,
+            ctx: &mut PassContext,
+        ) -> Result<(), HtmlDocError> {
+            let text = ctx.setting("text").unwrap_or("missing").to_owned();
+            let count = ctx.values.get("count").cloned().unwrap_or_default();
+            doc.nodes
+                .push(HtmlNode::new_text(format!("{}{}", text, count)));
+            Ok(())
+        }
+    }
+
+    struct Fails;
+    impl Pass for Fails {
+        fn name(&self) -> &str {
+            "fails"
+        }
+        fn run(
+            &mut self,
+            _doc: &mut HtmlDocument,
+            _ctx: &mut PassContext,
+        ) -> Result<(), HtmlDocError> {
+            Err(HtmlDocError::with_msg("broken"))
+        }
+    }
+
+    #[test]
+    fn pipeline_shared_context_test() {
+        let mut doc = "<div><p>a</p></div>".parse::<HtmlDocument>().unwrap();
+        let mut options = PipelineOptions::default();
+        options
+            .settings
+            .insert("text".to_owned(), "nodes: ".to_owned());
+        let report = Pipeline::new()
+            .options(options)
+            .add(Counter)
+            .add(AppendSetting)
+            .run(&mut doc)
+            .unwrap();
+        assert_eq!(doc.nodes[1], HtmlNode::new_text("nodes: 3"));
+        assert_eq!(report.passes_run, vec!["counter", "append"]);
+        assert_eq!(
+            report.diagnostics_for("counter")[0].to_string(),
+            "[counter] Info: found 3 nodes"
+        );
+        assert!(!report.has_errors());
+    }
+
+    #[test]
+    fn pipeline_errors_test() {
+        let mut doc = "<div></div>".parse::<HtmlDocument>().unwrap();
+        let mut pipeline = Pipeline::new().add(Fails).add(Counter);
+        assert_eq!(pipeline.pass_names(), vec!["fails", "counter"]);
+        assert_eq!(
+            format!("{}", pipeline.run(&mut doc).unwrap_err()),
+            "Html Doc Error: 'pass 'fails' failed: Html Doc Error: 'broken''"
+        );
+        let report = Pipeline::new()
+            .options(PipelineOptions {
+                stop_on_error: false,
+                settings: HashMap::new(),
+            })
+            .add(Fails)
+            .add(Counter)
+            .run(&mut doc)
+            .unwrap();
+        assert!(report.has_errors());
+        assert_eq!(report.passes_run, vec!["fails", "counter"]);
+    }
+
+    struct Noisy;
+    impl Pass for Noisy {
+        fn name(&self) -> &str {
+            "noisy"
+        }
+        fn run(
+            &mut self,
+            _doc: &mut HtmlDocument,
+            ctx: &mut PassContext,
+        ) -> Result<(), HtmlDocError> {
+            for i in 0..10 {
+                ctx.warning(format!("warning {}", i), None);
+            }
+            ctx.info("done", None);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn diagnostic_limits_test() {
+        let mut doc = "<div></div>".parse::<HtmlDocument>().unwrap();
+        let report = Pipeline::new()
+            .diagnostic_limits(DiagnosticLimits::new().max_per_kind(3).sample_every(4))
+            .add(Noisy)
+            .run(&mut doc)
+            .unwrap();
+        let messages: Vec<String> = report.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "[noisy] Warning: warning 0",
+                "[noisy] Warning: warning 1",
+                "[noisy] Warning: warning 2",
+                "[noisy] Warning: warning 6",
+                "[noisy] Info: done",
+                "[noisy] Warning: 6 more diagnostics were dropped",
+            ]
+        );
+        assert_eq!(report.dropped_diagnostics, 6);
+        let report = Pipeline::new()
+            .diagnostic_limits(DiagnosticLimits::new().max_total(2))
+            .add(Noisy)
+            .run(&mut doc)
+            .unwrap();
+        assert_eq!(report.dropped_diagnostics, 9);
+        assert_eq!(report.diagnostics.len(), 4);
+        assert_eq!(
+            report.diagnostics[2].to_string(),
+            "[noisy] Info: 1 more diagnostics were dropped"
+        );
+        assert_eq!(
+            report.diagnostics[3].to_string(),
+            "[noisy] Warning: 8 more diagnostics were dropped"
+        );
+        let report = Pipeline::new().add(Noisy).run(&mut doc).unwrap();
+        assert_eq!(report.diagnostics.len(), 11);
+        assert_eq!(report.dropped_diagnostics, 0);
+    }
+
+    /// Stores the changed `src` attributes of the scripts in the values.
+    struct ScriptWatcher;
+    impl Pass for ScriptWatcher {
+        fn name(&self) -> &str {
+            "script-watcher"
+        }
+        fn run(
+            &mut self,
+            doc: &mut HtmlDocument,
+            ctx: &mut PassContext,
+        ) -> Result<(), HtmlDocError> {
+            let changed: Vec<String> = ctx
+                .watched_changes(doc)
+                .iter()
+                .map(|c| format!("{:?} {:?}", c.path, c.new))
+                .collect();
+            ctx.values.insert("changed".to_owned(), changed.join(", "));
+            // a change made by this pass is not seen by it
+            ctx.attribute_changed(vec![0, 0], "src", None, Some("x".to_owned()));
+            assert_eq!(ctx.watched_changes(doc).len(), changed.len());
+            Ok(())
+        }
+        fn watches(&self) -> Vec<AttributeWatch> {
+            vec![AttributeWatch::new("SRC", "script").unwrap()]
+        }
+    }
+
+    #[test]
+    fn attribute_watch_test() {
+        use crate::rewrite::RewriteUrls;
+        let source = r#"<div><script src="/a.js"></script><img src="/b.png"><script src="https://cdn/c.js"></script><a href="/d">d</a></div>"#;
+        let mut doc = source.parse::<HtmlDocument>().unwrap();
+        let report = Pipeline::new()
+            .add(RewriteUrls::new().rule("/", "https://static/"))
+            .add(ScriptWatcher)
+            .run(&mut doc)
+            .unwrap();
+        assert_eq!(
+            report.values["changed"],
+            r#"[0, 0] Some("https://static/a.js")"#
+        );
+        // only the watched attribute is recorded, along with the change made by the watcher
+        let changes: Vec<(&str, &[usize], Option<&str>)> = report
+            .attribute_changes
+            .iter()
+            .map(|c| (c.pass.as_str(), c.path.as_slice(), c.old.as_deref()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("rewrite-urls", &[0, 0][..], Some("/a.js")),
+                ("rewrite-urls", &[0, 1][..], Some("/b.png")),
+                ("script-watcher", &[0, 0][..], None),
+            ]
+        );
+
+        // nothing is recorded when no pass watches the attributes
+        let mut doc = source.parse::<HtmlDocument>().unwrap();
+        let report = Pipeline::new()
+            .add(RewriteUrls::new().rule("/", "https://static/"))
+            .run(&mut doc)
+            .unwrap();
+        assert!(report.attribute_changes.is_empty());
+    }
+}
@@ -1,7 +1,7 @@
 use crate::error::{HtmlMatchError, ParseHtmlError};
 use crate::objects::{
     CssAttributeCompareType, CssRefiner, CssRefinerNumberType, CssSelector, CssSelectorItem,
-    CssSelectorRelationship, CssSelectorRule, HtmlDocument, HtmlNode, HtmlTag,
+    CssSelectorRelationship, CssSelectorRule, HtmlDocument, HtmlNode, HtmlTag, NodePath,
 };
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
@@ -31,6 +31,12 @@ impl<'a> HtmlQueryResult<'a> {
         return Some(&path_point.0[path_point.1]);
     }
 
+    /// Gets the [`NodePath`] of the node pointed to by this result, which can be used
+    /// to find the node again without holding onto the borrow of the document.
+    pub fn node_path(&self) -> NodePath {
+        self.path.iter().map(|(_, i)| *i).collect()
+    }
+
     /// Attempts to get the parent to the node pointed to by the path.
     /// Returns None if the path is empty or is only the single node on the path.
     pub fn get_parent_node(&self) -> Option<&HtmlNode> {
@@ -452,7 +458,8 @@ impl<'a> HtmlQueryResult<'a> {
                         CssRefiner::Empty => {
                             for content in &tag_node.contents {
                                 match content {
-                                    HtmlNode::Tag(_) => {
+                                    // a template tag can output content
+                                    HtmlNode::Tag(_) | HtmlNode::Template(_) => {
                                         all_found = false;
                                         break;
                                     }
@@ -0,0 +1,261 @@
+//! Choosing how the parser handles markup which is in the wrong place.
+//!
+//! By default the parser is strict: an end tag which does not match the element
+//! it is in is an error. Pages from the wild are often broken in the same few
+//! ways, so [`ParseOptions::repair`](crate::options::ParseOptions::repair) sets a
+//! [`RepairStrategy`] which is used instead, and a [`RepairHook`] can decide for
+//! each piece of [`Misplaced`] markup, for ingesting sources which are known to be
+//! broken in a particular way.
+//!
+//! The strategies are used for:
+//! - [`MisplacedKind::EndTag`]: an end tag which does not match the element it is
+//!   in, eg the `</div>` in `<div><p>text</div>`.
+//! - [`MisplacedKind::TablePart`]: a table element such as `<tr>` or `<td>`
+//!   which is not inside a `<table>` (or a `<template>`). The strict parser keeps
+//!   these as they are.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::options::ParseOptions;
+//! use hb_html::repair::{MisplacedKind, RepairStrategy};
+//! let source = "<div><p>One<b>Two</div><tr><td>Three</td></tr>";
+//! assert!(source.parse::<HtmlDocument>().is_err());
+//! let options = ParseOptions::new().repair(RepairStrategy::Spec);
+//! let doc = HtmlDocument::parse_with(source, &options).unwrap();
+//! assert_eq!(doc.to_html_string(), "<div><p>One<b>Two</b></p></div>Three");
+//! // stray end tags are kept as text, everything else is repaired as browsers do
+//! let options = ParseOptions::new().repair_hook(|m| match m.kind {
+//!     MisplacedKind::EndTag if !m.is_open(m.tag) => RepairStrategy::KeepAsText,
+//!     _ => RepairStrategy::Spec,
+//! });
+//! let doc = HtmlDocument::parse_with("<p>a</span>b</p>", &options).unwrap();
+//! assert_eq!(doc.nodes[0].text(), "a</span>b");
+//! ```
+use std::fmt;
+use std::sync::Arc;
+
+/// What the parser does with markup which is in the wrong place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RepairStrategy {
+    /// Return an error for an end tag which does not match, and keep table
+    /// elements where they are.
+    #[default]
+    Strict,
+    /// Do what the HTML spec does. An end tag closes the elements which are open
+    /// inside the element it matches, or is ignored if it does not match any of
+    /// them. A table element outside of a table is ignored, keeping its contents.
+    Spec,
+    /// Leave the markup out: an end tag is ignored and a table element outside of a
+    /// table is left out along with its contents.
+    Drop,
+    /// Keep the markup as text.
+    KeepAsText,
+}
+
+/// The kinds of markup which a [`RepairStrategy`] is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MisplacedKind {
+    /// An end tag which does not match the element it is in.
+    EndTag,
+    /// A table element, such as `<tr>`, which is not in a table.
+    TablePart,
+}
+
+/// Markup which is in the wrong place, given to a [`RepairHook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Misplaced<'a> {
+    pub kind: MisplacedKind,
+    /// The name of the tag.
+    pub tag: &'a str,
+    /// The names of the elements which are open, starting with the outermost.
+    pub open: &'a [String],
+}
+
+impl Misplaced<'_> {
+    /// Checks whether an element with the name is open.
+    pub fn is_open(&self, tag: &str) -> bool {
+        self.open.iter().any(|t| t == tag)
+    }
+}
+
+/// A function which chooses the strategy for each piece of misplaced markup.
+#[derive(Clone)]
+pub struct RepairHook(Arc<dyn Fn(&Misplaced) -> RepairStrategy + Send + Sync>);
+
+impl RepairHook {
+    pub fn new<F>(hook: F) -> RepairHook
+    where
+        F: Fn(&Misplaced) -> RepairStrategy + Send + Sync + 'static,
+    {
+        RepairHook(Arc::new(hook))
+    }
+
+    pub fn strategy(&self, misplaced: &Misplaced) -> RepairStrategy {
+        (self.0)(misplaced)
+    }
+}
+
+impl fmt::Debug for RepairHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RepairHook")
+    }
+}
+
+/// Hooks are only equal if they are the same function.
+impl PartialEq for RepairHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for RepairHook {}
+
+#[cfg(test)]
+mod repair_tests {
+    use super::*;
+    use crate::objects::{parse_document, HtmlDocument, HtmlNode};
+    use crate::options::ParseOptions;
+    use crate::parsing::SpanRecorder;
+    use std::sync::Mutex;
+
+    fn parse(source: &str, strategy: RepairStrategy) -> String {
+        let options = ParseOptions::new().repair(strategy);
+        HtmlDocument::parse_with(source, &options)
+            .unwrap()
+            .to_html_string()
+    }
+
+    #[test]
+    fn end_tag_repair_test() {
+        let source = "<div><p>a<i>b</div>c</span></div>";
+        assert!(HtmlDocument::parse_with(source, &ParseOptions::new()).is_err());
+        // the first </div> closes the i, p and div, so the second is stray
+        assert_eq!(
+            parse(source, RepairStrategy::Spec),
+            "<div><p>a<i>b</i></p></div>"
+        );
+        assert_eq!(
+            parse("<div><p>a</span>b</p></div>", RepairStrategy::Drop),
+            "<div><p>ab</p></div>"
+        );
+        assert_eq!(
+            parse("<p>a</div>b</p>", RepairStrategy::KeepAsText),
+            "<p>a&lt;/div&gt;b</p>"
+        );
+        match &HtmlDocument::parse_with(
+            "<p>a</div>b</p>",
+            &ParseOptions::new().repair(RepairStrategy::KeepAsText),
+        )
+        .unwrap()
+        .nodes[0]
+        {
+            HtmlNode::Tag(p) => assert_eq!(p.contents.len(), 3),
+            _ => panic!("expected a tag"),
+        }
+        // end tags at the top level
+        assert_eq!(parse("</p><b>x</b>", RepairStrategy::Spec), "<b>x</b>");
+        assert!(HtmlDocument::parse_with("</p><b>x</b>", &ParseOptions::new()).is_err());
+    }
+
+    #[test]
+    fn table_part_repair_test() {
+        let source = "<div><tr><td>a</td></tr></div><table><tr><td>b</td></tr></table>";
+        assert_eq!(
+            parse(source, RepairStrategy::Strict),
+            HtmlDocument::parse_with(source, &ParseOptions::new())
+                .unwrap()
+                .to_html_string()
+        );
+        assert_eq!(
+            parse(source, RepairStrategy::Spec),
+            "<div>a</div><table><tr><td>b</td></tr></table>"
+        );
+        assert_eq!(
+            parse(source, RepairStrategy::Drop),
+            "<div></div><table><tr><td>b</td></tr></table>"
+        );
+        let doc = HtmlDocument::parse_with(
+            "<div><td>a</td></div>",
+            &ParseOptions::new().repair(RepairStrategy::KeepAsText),
+        )
+        .unwrap();
+        match &doc.nodes[0] {
+            HtmlNode::Tag(div) => {
+                assert_eq!(div.contents, vec![HtmlNode::Text("<td>a</td>".to_owned())])
+            }
+            _ => panic!("expected a tag"),
+        }
+        assert_eq!(
+            parse("<template><tr></tr></template>", RepairStrategy::Spec),
+            "<template><tr></tr></template>"
+        );
+    }
+
+    #[test]
+    fn repair_hook_test() {
+        let seen = Mutex::new(vec![]);
+        let seen = std::sync::Arc::new(seen);
+        let hook_seen = seen.clone();
+        let options = ParseOptions::new().repair_hook(move |m| {
+            hook_seen
+                .lock()
+                .unwrap()
+                .push((m.kind, m.tag.to_owned(), m.open.to_vec()));
+            match m.kind {
+                MisplacedKind::EndTag => RepairStrategy::Spec,
+                MisplacedKind::TablePart => RepairStrategy::Drop,
+            }
+        });
+        let doc = HtmlDocument::parse_with("<ul><li><td>x</td>y</ul>", &options).unwrap();
+        assert_eq!(doc.to_html_string(), "<ul><li>y</li></ul>");
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (
+                    MisplacedKind::TablePart,
+                    "td".to_owned(),
+                    vec!["ul".to_owned(), "li".to_owned()]
+                ),
+                (
+                    MisplacedKind::EndTag,
+                    "ul".to_owned(),
+                    vec!["ul".to_owned(), "li".to_owned()]
+                ),
+            ]
+        );
+        // options with the same hook are equal
+        assert_eq!(options.clone(), options);
+        assert_ne!(
+            options,
+            ParseOptions::new().repair_hook(|_| RepairStrategy::Spec)
+        );
+    }
+
+    #[test]
+    fn repair_spans_test() {
+        let source = "<div><tr><td>a</td><td>b</td></tr><p>c</div>";
+        let options = ParseOptions::new().repair(RepairStrategy::Spec);
+        let mut recorder = SpanRecorder::new(source);
+        let doc = parse_document(source, &options, Some(&mut recorder)).unwrap();
+        let mut spans: Vec<_> = recorder
+            .spans
+            .iter()
+            .map(|s| (s.path.clone(), &source[s.range.clone()]))
+            .collect();
+        spans.sort();
+        assert_eq!(
+            spans,
+            vec![
+                (vec![0], source),
+                (vec![0, 0], "a"),
+                (vec![0, 1], "b"),
+                // the p is closed by the </div>
+                (vec![0, 2], "<p>c</div>"),
+                (vec![0, 2, 0], "c"),
+            ]
+        );
+        assert_eq!(doc.nodes[0].text(), "abc");
+    }
+}
@@ -0,0 +1,573 @@
+//! Finding the external resources a document depends on.
+//!
+//! [`ResourceGraph::from_document`] collects the scripts, module scripts,
+//! stylesheets, preloads and prefetches referenced by a document along with the
+//! attributes which change how they are loaded, eg `async`, `defer` and
+//! `integrity`. The `import` and `export ... from` statements of inline module
+//! scripts are scanned for the modules they import, and
+//! [`ResourceGraph::from_document_with_loader`] also scans the source of external
+//! modules, following the imports as far as the loader can fetch them.
+//!
+//! Each URL is one resource, and the edges of the graph say which resource (or
+//! the document itself) loads which. Relative module specifiers are resolved
+//! against the URL of the module importing them, other URLs are kept as they are
+//! written.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::resources::{ResourceGraph, ResourceKind};
+//! let doc = r#"<html><head>
+//! <link rel=stylesheet href=/site.css integrity="sha384-abc">
+//! <script type=module src=/js/app.js></script>
+//! <script src=/analytics.js async></script>
+//! </head></html>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let graph = ResourceGraph::from_document_with_loader(&doc, |url| match url {
+//!     "/js/app.js" => Some("import { go } from './nav.js';\ngo();".to_owned()),
+//!     _ => None,
+//! });
+//! let urls: Vec<&str> = graph.resources.iter().map(|r| r.url.as_str()).collect();
+//! assert_eq!(urls, vec!["/site.css", "/js/app.js", "/analytics.js", "/js/nav.js"]);
+//! assert_eq!(graph.resources[0].integrity.as_deref(), Some("sha384-abc"));
+//! assert!(graph.resources[2].is_async);
+//! let nav = graph.get("/js/nav.js").unwrap();
+//! assert_eq!(nav.kind, ResourceKind::ModuleScript);
+//! assert_eq!(graph.imported_by(3), vec![Some(1)]);
+//! ```
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+
+/// How a resource is used by the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    /// A classic `<script src>`.
+    Script,
+    /// A `<script type=module src>` or a module imported by another module.
+    ModuleScript,
+    /// A `<link rel=stylesheet>`.
+    Stylesheet,
+    /// A `<link rel=preload>`, see [`Resource::as_type`].
+    Preload,
+    /// A `<link rel=modulepreload>`.
+    ModulePreload,
+    /// A `<link rel=prefetch>`.
+    Prefetch,
+}
+
+/// An external resource used by a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    pub kind: ResourceKind,
+    pub url: String,
+    /// The element which first references the resource, or None for modules which
+    /// are only imported by other external modules.
+    pub path: Option<NodePath>,
+    pub is_async: bool,
+    pub defer: bool,
+    pub integrity: Option<String>,
+    pub crossorigin: Option<String>,
+    /// The `as` attribute of preloads, eg `font` or `image`.
+    pub as_type: Option<String>,
+    /// The `media` attribute of stylesheets and preloads.
+    pub media: Option<String>,
+}
+
+/// Which resource loads which. A `from` of None is the document itself, including
+/// its inline scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceEdge {
+    pub from: Option<usize>,
+    pub to: usize,
+}
+
+/// The resources of a document and the edges between them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceGraph {
+    /// The resources in the order they were found, the document first followed by
+    /// the modules imported by external modules.
+    pub resources: Vec<Resource>,
+    pub edges: Vec<ResourceEdge>,
+}
+
+impl ResourceGraph {
+    /// Finds the resources of the document without fetching anything, so only
+    /// the imports of inline module scripts are followed.
+    pub fn from_document(doc: &HtmlDocument) -> ResourceGraph {
+        ResourceGraph::from_document_with_loader(doc, |_| None)
+    }
+
+    /// Finds the resources of the document, calling the loader with the URL of each
+    /// module script to get its source so its imports can be followed. The loader
+    /// returns None for modules which cannot be fetched.
+    pub fn from_document_with_loader<F: FnMut(&str) -> Option<String>>(
+        doc: &HtmlDocument,
+        mut loader: F,
+    ) -> ResourceGraph {
+        let mut graph = ResourceGraph::default();
+        let mut path = vec![];
+        graph.add_nodes(&doc.nodes, &mut path);
+        // follow the imports of the external modules, including the ones they import
+        let mut i = 0;
+        while i < graph.resources.len() {
+            let resource = &graph.resources[i];
+            let is_module = matches!(
+                resource.kind,
+                ResourceKind::ModuleScript | ResourceKind::ModulePreload
+            );
+            if is_module {
+                if let Some(source) = loader(&resource.url) {
+                    let base = resource.url.clone();
+                    for specifier in static_imports(&source) {
+                        let url = resolve_specifier(&base, &specifier);
+                        let to = graph.add_resource(Resource::module(url, None));
+                        graph.add_edge(Some(i), to);
+                    }
+                }
+            }
+            i += 1;
+        }
+        graph
+    }
+
+    pub fn get(&self, url: &str) -> Option<&Resource> {
+        self.resources.iter().find(|r| r.url == url)
+    }
+
+    /// Gets the resources which load the resource at the index, None being the
+    /// document.
+    pub fn imported_by(&self, index: usize) -> Vec<Option<usize>> {
+        self.edges
+            .iter()
+            .filter(|e| e.to == index)
+            .map(|e| e.from)
+            .collect()
+    }
+
+    /// Gets the indexes of the resources which the resource at the index loads, or
+    /// which the document loads for None.
+    pub fn dependencies(&self, from: Option<usize>) -> Vec<usize> {
+        self.edges
+            .iter()
+            .filter(|e| e.from == from)
+            .map(|e| e.to)
+            .collect()
+    }
+
+    /// Adds the resource if there is not already one with the URL, returning its index.
+    fn add_resource(&mut self, resource: Resource) -> usize {
+        match self.resources.iter().position(|r| r.url == resource.url) {
+            Some(i) => i,
+            None => {
+                self.resources.push(resource);
+                self.resources.len() - 1
+            }
+        }
+    }
+
+    fn add_edge(&mut self, from: Option<usize>, to: usize) {
+        let edge = ResourceEdge { from, to };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    fn add_nodes(&mut self, nodes: &[HtmlNode], path: &mut NodePath) {
+        for (i, node) in nodes.iter().enumerate() {
+            if let HtmlNode::Tag(tag) = node {
+                path.push(i);
+                self.add_tag(tag, path);
+                self.add_nodes(&tag.contents, path);
+                path.pop();
+            }
+        }
+    }
+
+    fn add_tag(&mut self, tag: &HtmlTag, path: &NodePath) {
+        let resource = match tag.tag.to_ascii_lowercase().as_str() {
+            "script" => {
+                let is_module = tag
+                    .attributes
+                    .get("type")
+                    .is_some_and(|t| t.trim().eq_ignore_ascii_case("module"));
+                match tag.attributes.get("src") {
+                    Some(src) if is_module => Resource::module(src.clone(), Some(path.clone())),
+                    Some(src) => {
+                        Resource::new(ResourceKind::Script, src.clone(), Some(path.clone()))
+                    }
+                    None if is_module => {
+                        // the imports of inline modules are loaded by the document
+                        for specifier in static_imports(&tag.text()) {
+                            let to =
+                                self.add_resource(Resource::module(specifier, Some(path.clone())));
+                            self.add_edge(None, to);
+                        }
+                        return;
+                    }
+                    None => return,
+                }
+            }
+            "link" => {
+                let href = match tag.attributes.get("href") {
+                    Some(href) => href.clone(),
+                    None => return,
+                };
+                let rel = tag
+                    .attributes
+                    .get("rel")
+                    .map(|r| r.to_ascii_lowercase())
+                    .unwrap_or_default();
+                let kind = rel.split_ascii_whitespace().find_map(|r| match r {
+                    "stylesheet" => Some(ResourceKind::Stylesheet),
+                    "preload" => Some(ResourceKind::Preload),
+                    "modulepreload" => Some(ResourceKind::ModulePreload),
+                    "prefetch" => Some(ResourceKind::Prefetch),
+                    _ => None,
+                });
+                match kind {
+                    Some(kind) => Resource::new(kind, href, Some(path.clone())),
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+        let resource = resource.with_attributes(tag);
+        let to = self.add_resource(resource);
+        self.add_edge(None, to);
+    }
+}
+
+impl Resource {
+    fn new(kind: ResourceKind, url: String, path: Option<NodePath>) -> Resource {
+        Resource {
+            kind,
+            url,
+            path,
+            is_async: false,
+            defer: false,
+            integrity: None,
+            crossorigin: None,
+            as_type: None,
+            media: None,
+        }
+    }
+
+    fn module(url: String, path: Option<NodePath>) -> Resource {
+        Resource::new(ResourceKind::ModuleScript, url, path)
+    }
+
+    fn with_attributes(mut self, tag: &HtmlTag) -> Resource {
+        let attribute = |name: &str| tag.attributes.get(name).cloned();
+        self.is_async = tag.attributes.contains_key("async");
+        self.defer = tag.attributes.contains_key("defer");
+        self.integrity = attribute("integrity");
+        self.crossorigin = attribute("crossorigin");
+        self.as_type = attribute("as");
+        self.media = attribute("media");
+        self
+    }
+}
+
+/// Resolves a module specifier which starts with `/`, `./` or `../` against the
+/// URL of the module importing it. Other specifiers, such as full URLs and bare
+/// package names, are returned as they are.
+pub fn resolve_specifier(base: &str, specifier: &str) -> String {
+    if specifier.starts_with('/') && !specifier.starts_with("//") {
+        // keep the scheme and host of the base, if it has them
+        let origin_end = base
+            .find("://")
+            .map(|i| base[i + 3..].find('/').map_or(base.len(), |j| i + 3 + j))
+            .unwrap_or(0);
+        return format!("{}{}", &base[..origin_end], specifier);
+    }
+    if !specifier.starts_with("./") && !specifier.starts_with("../") {
+        return specifier.to_owned();
+    }
+    let origin_end = base.find("://").map_or(0, |i| i + 3);
+    let dir_end = base
+        .rfind('/')
+        .filter(|i| *i >= origin_end)
+        .map_or(0, |i| i + 1);
+    let (origin, dir) = base[..dir_end].split_at(origin_end.min(dir_end));
+    let mut segments: Vec<&str> = dir.split('/').collect();
+    // the last segment is the empty string after the final /
+    segments.pop();
+    for segment in specifier.split('/') {
+        match segment {
+            "." => {}
+            ".." => {
+                // stay below the host of a full url
+                if segments.len() > 1 || (origin.is_empty() && !segments.is_empty()) {
+                    segments.pop();
+                }
+            }
+            s => segments.push(s),
+        }
+    }
+    format!("{}{}", origin, segments.join("/"))
+}
+
+/// Gets the origin of an `http`, `https` or protocol relative URL, eg
+/// `https://cdn.example` for `https://cdn.example/a.js` and `//cdn.example` for
+/// `//cdn.example/a.js`. Other URLs, including relative URLs, have no origin.
+pub fn url_origin(url: &str) -> Option<&str> {
+    let url = url.trim();
+    let scheme_end = match url.find("//")? {
+        0 => 0,
+        i if url[..i].eq_ignore_ascii_case("http:") || url[..i].eq_ignore_ascii_case("https:") => i,
+        _ => return None,
+    };
+    let host_start = scheme_end + 2;
+    let end = url[host_start..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |i| host_start + i);
+    (end > host_start).then(|| &url[..end])
+}
+
+/// Checks whether two origins from [`url_origin`] are the same, ignoring case. A
+/// protocol relative origin is compared by its host.
+pub fn same_origin(a: &str, b: &str) -> bool {
+    let (a, b) = (a.trim_end_matches('/'), b.trim_end_matches('/'));
+    if a.starts_with("//") || b.starts_with("//") {
+        let host = |o: &str| o.split_once("//").map_or("", |(_, h)| h).to_owned();
+        host(a).eq_ignore_ascii_case(&host(b))
+    } else {
+        a.eq_ignore_ascii_case(b)
+    }
+}
+
+fn is_identifier_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$'
+}
+
+/// Finds the end of the string literal starting with the quote at the position,
+/// or None if it is not closed.
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() && bytes[i] != quote {
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    (i < bytes.len()).then_some(i + 1)
+}
+
+/// Finds the position after the first match of the needle at or after the position.
+fn find_after(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| from + i + needle.len())
+}
+
+/// Skips whitespace and comments from the position.
+fn skip_trivia(bytes: &[u8], mut i: usize) -> usize {
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let rest = &bytes[i.min(bytes.len())..];
+        if rest.starts_with(b"//") {
+            i = find_after(bytes, i, b"\n").unwrap_or(bytes.len());
+        } else if rest.starts_with(b"/*") {
+            i = find_after(bytes, i + 2, b"*/").unwrap_or(bytes.len());
+        } else {
+            return i;
+        }
+    }
+}
+
+/// Reads the module specifier of an `import` or `export` statement, the keyword
+/// ending at the position.
+fn read_specifier(source: &str, keyword_end: usize, is_export: bool) -> Option<String> {
+    let bytes = source.as_bytes();
+    let mut i = skip_trivia(bytes, keyword_end);
+    match bytes.get(i)? {
+        // import "./side-effect.js"
+        b'"' | b'\'' if !is_export => {}
+        // import(...) and import.meta are not static imports
+        b'(' | b'.' => return None,
+        b'{' | b'*' => {}
+        b if !is_export && is_identifier_byte(*b) => {}
+        _ => return None,
+    }
+    // the specifier is the string after `from`, before the end of the statement
+    while !matches!(bytes.get(i), Some(b'"') | Some(b'\'')) {
+        match bytes.get(i)? {
+            b';' | b'(' | b'=' => return None,
+            b'f' if bytes[i..].starts_with(b"from")
+                && !is_identifier_byte(bytes[i - 1])
+                && !bytes.get(i + 4).is_some_and(|b| is_identifier_byte(*b)) =>
+            {
+                i = skip_trivia(bytes, i + 4);
+            }
+            _ => i = skip_trivia(bytes, i + 1),
+        }
+    }
+    let end = string_end(bytes, i)?;
+    Some(source[i + 1..end - 1].to_owned())
+}
+
+/// Finds the module specifiers of the static `import` and `export ... from`
+/// statements in the source of a JavaScript module, in source order. Dynamic
+/// `import()` calls are not included as their specifiers are not always known.
+pub fn static_imports(source: &str) -> Vec<String> {
+    let bytes = source.as_bytes();
+    let mut specifiers = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if bytes[i..].starts_with(b"//") || bytes[i..].starts_with(b"/*") {
+            i = skip_trivia(bytes, i);
+        } else if b == b'"' || b == b'\'' || b == b'`' {
+            i = string_end(bytes, i).unwrap_or(bytes.len());
+        } else if is_identifier_byte(b) {
+            let start = i;
+            while i < bytes.len() && is_identifier_byte(bytes[i]) {
+                i += 1;
+            }
+            let after_dot = start > 0 && bytes[start - 1] == b'.';
+            let word = &source[start..i];
+            if !after_dot && (word == "import" || word == "export") {
+                if let Some(specifier) = read_specifier(source, i, word == "export") {
+                    specifiers.push(specifier);
+                }
+            }
+        } else {
+            i += 1;
+        }
+    }
+    specifiers
+}
+
+#[cfg(test)]
+mod resources_tests {
+    use super::*;
+
+    #[test]
+    fn static_imports_test() {
+        let source = r#"
+import def, { a as b } from "./a.js";
+import * as ns from './b.js'
+import {
+    c,
+    d,
+} from "/c.js";
+import "./side-effect.js";
+export { e } from "./e.js";
+export * from './f.js';
+export const g = 1;
+// import "./commented.js";
+/* import "./block.js"; */
+const s = "import './string.js'";
+const t = `export * from './template.js'`;
+const lazy = import("./lazy.js");
+console.log(import.meta.url, obj.import);
+import from from "./from.js";
+"#;
+        assert_eq!(
+            static_imports(source),
+            vec![
+                "./a.js",
+                "./b.js",
+                "/c.js",
+                "./side-effect.js",
+                "./e.js",
+                "./f.js",
+                "./from.js"
+            ]
+        );
+        assert!(static_imports("").is_empty());
+        assert!(static_imports("import").is_empty());
+        assert!(static_imports("import { a } from").is_empty());
+        assert!(static_imports("import { é } from \"./é.js").is_empty());
+        assert_eq!(static_imports("import 'é.js'"), vec!["é.js"]);
+    }
+
+    #[test]
+    fn resolve_specifier_test() {
+        assert_eq!(resolve_specifier("/js/app.js", "./a.js"), "/js/a.js");
+        assert_eq!(resolve_specifier("/js/app.js", "../lib/a.js"), "/lib/a.js");
+        assert_eq!(resolve_specifier("js/app.js", "../../a.js"), "a.js");
+        assert_eq!(
+            resolve_specifier("https://cdn.x/pkg/v1/index.js", "../../../a.js"),
+            "https://cdn.x/a.js"
+        );
+        assert_eq!(
+            resolve_specifier("https://cdn.x/pkg/index.js", "/root.js"),
+            "https://cdn.x/root.js"
+        );
+        assert_eq!(resolve_specifier("/js/app.js", "/root.js"), "/root.js");
+        assert_eq!(resolve_specifier("/js/app.js", "lodash"), "lodash");
+        assert_eq!(
+            resolve_specifier("/js/app.js", "https://esm.sh/react"),
+            "https://esm.sh/react"
+        );
+    }
+
+    #[test]
+    fn url_origin_test() {
+        assert_eq!(
+            url_origin("https://cdn.x:8080/a.js?v=1"),
+            Some("https://cdn.x:8080")
+        );
+        assert_eq!(url_origin("HTTP://x.y"), Some("HTTP://x.y"));
+        assert_eq!(url_origin("//fonts.x#f"), Some("//fonts.x"));
+        assert_eq!(url_origin("/a.js"), None);
+        assert_eq!(url_origin("a/b//c"), None);
+        assert_eq!(url_origin("data:text/js,//x"), None);
+        assert_eq!(url_origin("https:///a"), None);
+        assert!(same_origin("https://A.x", "https://a.x/"));
+        assert!(same_origin("//a.x", "http://a.x"));
+        assert!(!same_origin("http://a.x", "https://a.x"));
+    }
+
+    #[test]
+    fn resource_graph_test() {
+        let doc = r#"<html><head>
+<link rel="preload" href="/font.woff2" as="font" crossorigin>
+<link rel="modulepreload" href="/js/a.js">
+<link rel="icon" href="/favicon.ico">
+<link rel="prefetch" href="/next.html">
+<script src="/legacy.js" defer></script>
+<script>var inline = 1;</script>
+<script type="module">import "/js/a.js"; import "/js/b.js";</script>
+</head><body><script type="module" src="/js/a.js"></script></body></html>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let graph = ResourceGraph::from_document_with_loader(&doc, |url| match url {
+            "/js/a.js" => Some("import './b.js'; import './c.js';".to_owned()),
+            "/js/c.js" => Some("export * from './a.js';".to_owned()),
+            _ => None,
+        });
+        let found: Vec<(ResourceKind, &str)> = graph
+            .resources
+            .iter()
+            .map(|r| (r.kind, r.url.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (ResourceKind::Preload, "/font.woff2"),
+                (ResourceKind::ModulePreload, "/js/a.js"),
+                (ResourceKind::Prefetch, "/next.html"),
+                (ResourceKind::Script, "/legacy.js"),
+                (ResourceKind::ModuleScript, "/js/b.js"),
+                (ResourceKind::ModuleScript, "/js/c.js"),
+            ]
+        );
+        let font = &graph.resources[0];
+        assert_eq!(font.as_type.as_deref(), Some("font"));
+        assert_eq!(font.crossorigin.as_deref(), Some(""));
+        assert_eq!(font.path, Some(vec![0, 0, 1]));
+        assert!(graph.resources[3].defer);
+        // the module preload is the same url as the module, so they are one resource
+        assert_eq!(graph.dependencies(None), vec![0, 1, 2, 3, 4]);
+        assert_eq!(graph.imported_by(4), vec![None, Some(1)]);
+        assert_eq!(graph.dependencies(Some(1)), vec![4, 5]);
+        assert_eq!(graph.resources[5].path, None);
+        // the cycle between a.js and c.js does not loop forever
+        assert_eq!(graph.imported_by(1), vec![None, Some(5)]);
+    }
+}
@@ -0,0 +1,214 @@
+//! Rewrites the URLs found in the attributes of a document.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::rewrite::RewriteUrls;
+//! let mut doc = r#"<img src="http://cdn.old/a.png">"#.parse::<HtmlDocument>().unwrap();
+//! let count = RewriteUrls::new()
+//!     .rule("http://cdn.old/", "https://cdn.new/")
+//!     .rewrite(&mut doc);
+//! assert_eq!(count, 1);
+//! assert_eq!(doc.nodes[0].to_html_string(), r#"<img src="https://cdn.new/a.png"></img>"#);
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlTag, NodePath};
+use crate::pipeline::{Pass, PassContext};
+use crate::visit::{Visit, VisitAction, VisitContext};
+
+/// The attributes which contain a single URL.
+pub const URL_ATTRIBUTES: [&str; 8] = [
+    "href",
+    "src",
+    "action",
+    "formaction",
+    "poster",
+    "cite",
+    "data",
+    "background",
+];
+
+/// Replaces a URL prefix with a different prefix.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct UrlRewriteRule {
+    pub from_prefix: String,
+    pub to_prefix: String,
+}
+
+impl UrlRewriteRule {
+    /// Applies the rule to the URL, returning None if the URL does not start with the prefix.
+    pub fn apply(&self, url: &str) -> Option<String> {
+        url.strip_prefix(self.from_prefix.as_str())
+            .map(|rest| format!("{}{}", self.to_prefix, rest))
+    }
+}
+
+/// A function which gets the new URL for a URL, or None if it is unchanged.
+pub type UrlRewriteFn = Box<dyn Fn(&str) -> Option<String>>;
+
+/// A [`Pass`] which rewrites URLs using prefix rules (the first matching rule is used)
+/// and an optional function which is used if no rules match.
+pub struct RewriteUrls {
+    /// The attributes which are rewritten.
+    pub attributes: Vec<String>,
+    /// The prefix rules, tried in order.
+    pub rules: Vec<UrlRewriteRule>,
+    rewriter: Option<UrlRewriteFn>,
+}
+
+impl Default for RewriteUrls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RewriteUrls {
+    /// Creates a rewriter with no rules for all of the [`URL_ATTRIBUTES`].
+    pub fn new() -> RewriteUrls {
+        RewriteUrls {
+            attributes: URL_ATTRIBUTES.iter().map(|a| a.to_string()).collect(),
+            rules: vec![],
+            rewriter: None,
+        }
+    }
+
+    /// Adds a rule which replaces the from prefix with the to prefix.
+    pub fn rule<F: Into<String>, T: Into<String>>(mut self, from: F, to: T) -> RewriteUrls {
+        self.rules.push(UrlRewriteRule {
+            from_prefix: from.into(),
+            to_prefix: to.into(),
+        });
+        self
+    }
+
+    /// Sets a function which is called with URLs that did not match any rules.
+    /// Returning None leaves the URL unchanged.
+    pub fn with_fn<F: Fn(&str) -> Option<String> + 'static>(mut self, f: F) -> RewriteUrls {
+        self.rewriter = Some(Box::new(f));
+        self
+    }
+
+    /// Sets the attributes which are rewritten.
+    pub fn attributes<T: Into<String>>(mut self, attributes: Vec<T>) -> RewriteUrls {
+        self.attributes = attributes.into_iter().map(|a| a.into()).collect();
+        self
+    }
+
+    /// Gets the new URL, or None if the URL is not changed.
+    pub fn rewrite_url(&self, url: &str) -> Option<String> {
+        for rule in &self.rules {
+            if let Some(new_url) = rule.apply(url) {
+                return Some(new_url);
+            }
+        }
+        match &self.rewriter {
+            Some(f) => f(url),
+            None => None,
+        }
+    }
+
+    /// Rewrites the URLs in the document, returning the number of URLs changed.
+    pub fn rewrite(&self, doc: &mut HtmlDocument) -> usize {
+        self.rewrite_changes(doc).len()
+    }
+
+    fn rewrite_changes(&self, doc: &mut HtmlDocument) -> Vec<UrlChange> {
+        let mut visitor = UrlVisitor {
+            rewriter: self,
+            changes: vec![],
+        };
+        doc.visit(&mut visitor);
+        visitor.changes
+    }
+}
+
+/// A URL which was rewritten: the path of the element, the attribute and the old
+/// and new URLs.
+type UrlChange = (NodePath, String, String, String);
+
+struct UrlVisitor<'a> {
+    rewriter: &'a RewriteUrls,
+    changes: Vec<UrlChange>,
+}
+
+impl Visit for UrlVisitor<'_> {
+    fn enter_element(&mut self, tag: &mut HtmlTag, ctx: &VisitContext) -> VisitAction {
+        for attr in &self.rewriter.attributes {
+            if let Some(url) = tag.attributes.get_mut(attr) {
+                if let Some(new_url) = self.rewriter.rewrite_url(url) {
+                    if new_url != *url {
+                        let old_url = std::mem::replace(url, new_url.clone());
+                        self.changes
+                            .push((ctx.path.clone(), attr.clone(), old_url, new_url));
+                    }
+                }
+            }
+        }
+        VisitAction::Continue
+    }
+}
+
+impl Pass for RewriteUrls {
+    fn name(&self) -> &str {
+        "rewrite-urls"
+    }
+
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError> {
+        let changes = self.rewrite_changes(doc);
+        ctx.info(format!("rewrote {} urls", changes.len()), None);
+        for (path, attr, old, new) in changes {
+            ctx.attribute_changed(path, &attr, Some(old), Some(new));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod rewrite_tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_urls_test() {
+        let mut doc =
+            r#"<div><a href="/a">a</a><a href="/b/c">b</a><form action="/b/x"></form></div>"#
+                .parse::<HtmlDocument>()
+                .unwrap();
+        let rewriter = RewriteUrls::new()
+            .rule("/b/", "/new/")
+            .with_fn(|u| u.strip_prefix('/').map(|r| format!("https://site/{}", r)));
+        assert_eq!(rewriter.rewrite(&mut doc), 3);
+        assert_eq!(
+            doc.find("a")
+                .nodes()
+                .iter()
+                .map(|n| match n {
+                    crate::objects::HtmlNode::Tag(t) => t.attributes["href"].clone(),
+                    _ => String::new(),
+                })
+                .collect::<Vec<String>>(),
+            vec!["https://site/a", "/new/c"]
+        );
+    }
+
+    #[test]
+    fn rewrite_url_attributes_test() {
+        let mut doc = r#"<img src="/a" data-src="/a">"#.parse::<HtmlDocument>().unwrap();
+        let rewriter = RewriteUrls::new()
+            .attributes(vec!["data-src"])
+            .rule("/", "/static/");
+        assert_eq!(rewriter.rewrite(&mut doc), 1);
+        match &doc.nodes[0] {
+            crate::objects::HtmlNode::Tag(t) => {
+                assert_eq!(t.attributes["src"], "/a");
+                assert_eq!(t.attributes["data-src"], "/static/a");
+            }
+            _ => panic!("expected a tag"),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! HTML which is known to be safe to put in a page, and the escaping used by the
+//! `format_html!` macro in `hb_html_macros` to build it.
+//!
+//! `format_html!` works out where each value goes in the template when it is
+//! compiled, and escapes it for that place:
+//! - in text, and in `<title>` or `<textarea>`, the value is escaped as text,
+//!   except a [`SafeHtml`] which is put in as it is so pieces can be built up;
+//! - in a quoted attribute value, both kinds of quote are escaped;
+//! - at the start of a URL attribute such as `href` or `src`, a URL with a scheme
+//!   other than those in [`SAFE_URL_SCHEMES`] is replaced with `#`, see
+//!   [`safe_url`];
+//! - later in a URL attribute, the value is percent-encoded so it can not end
+//!   the path or query it is in, see [`encode_url_component`].
+//!
+//! Scripts, styles, event handler and `style` attributes, comments, unquoted
+//! attribute values and the insides of tags can not be made safe by escaping,
+//! so a template with a value in any of those places does not compile.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::safe_html::{safe_url, SafeHtml};
+//! let link = SafeHtml::from_text("Tom & Jerry");
+//! assert_eq!(link.as_str(), "Tom &amp; Jerry");
+//! assert_eq!(safe_url("javascript:alert(1)"), "#");
+//! assert_eq!(safe_url("/a?b=1&c=\"2\""), "/a?b=1&amp;c=&quot;2&quot;");
+//! ```
+use crate::entities::{escape_attribute, escape_text};
+use crate::sanitize::url_scheme;
+use std::fmt;
+
+/// The URL schemes which [`safe_url`] lets through. URLs without a scheme are
+/// always allowed.
+pub const SAFE_URL_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tel"];
+
+/// A string of HTML which is safe to put in a page as it is, such as the output of
+/// `format_html!`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SafeHtml(String);
+
+impl SafeHtml {
+    /// Creates the HTML for some text, escaping it.
+    pub fn from_text(text: &str) -> SafeHtml {
+        SafeHtml(escape_text(text))
+    }
+
+    /// Trusts the string to be safe HTML without checking or escaping it. Only use
+    /// this for HTML which did not come from a user, or which has been sanitized.
+    pub fn from_trusted<S: Into<String>>(html: S) -> SafeHtml {
+        SafeHtml(html.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for SafeHtml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<SafeHtml> for String {
+    fn from(html: SafeHtml) -> String {
+        html.0
+    }
+}
+
+/// Escapes a value for a quoted attribute value, with either kind of quote.
+pub fn escape_quoted_attribute(value: &str) -> String {
+    escape_attribute(value).replace('\'', "&#39;")
+}
+
+/// Escapes the URL for the start of a URL attribute, replacing it with `#` if it
+/// has a scheme which is not in [`SAFE_URL_SCHEMES`].
+pub fn safe_url(url: &str) -> String {
+    match url_scheme(url) {
+        Some(scheme) if !SAFE_URL_SCHEMES.contains(&scheme.as_str()) => "#".to_owned(),
+        _ => escape_quoted_attribute(url),
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters of a URL (letters,
+/// digits, `-`, `.`, `_` and `~`), for a value inside a URL path or query.
+pub fn encode_url_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Used by the code `format_html!` expands to, this is not a stable API.
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    /// Wraps a value so the text of a [`SafeHtml`] is not escaped, while any other
+    /// value is escaped as text. The method is found through auto-ref: the
+    /// [`SafeHtmlText`] impl is on `Text<SafeHtml>` itself so it is picked first,
+    /// and the [`DisplayText`] impl is on `&Text<T>`.
+    pub struct Text<'a, T: ?Sized>(pub &'a T);
+
+    pub trait SafeHtmlText {
+        fn html_text(&self) -> String;
+    }
+
+    impl SafeHtmlText for Text<'_, SafeHtml> {
+        fn html_text(&self) -> String {
+            self.0 .0.clone()
+        }
+    }
+
+    pub trait DisplayText {
+        fn html_text(&self) -> String;
+    }
+
+    impl<T: fmt::Display + ?Sized> DisplayText for &Text<'_, T> {
+        fn html_text(&self) -> String {
+            escape_text(&self.0.to_string())
+        }
+    }
+
+    pub fn attribute<T: fmt::Display + ?Sized>(value: &T) -> String {
+        escape_quoted_attribute(&value.to_string())
+    }
+
+    pub fn url<T: fmt::Display + ?Sized>(value: &T) -> String {
+        safe_url(&value.to_string())
+    }
+
+    pub fn url_component<T: fmt::Display + ?Sized>(value: &T) -> String {
+        encode_url_component(&value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod safe_html_tests {
+    use super::__private::*;
+    use super::*;
+
+    #[test]
+    fn escaping_test() {
+        assert_eq!(
+            escape_quoted_attribute(r#"a"b'c&<"#),
+            "a&quot;b&#39;c&amp;<"
+        );
+        assert_eq!(safe_url(" JavaScript:x"), "#");
+        assert_eq!(safe_url("data:text/html,x"), "#");
+        assert_eq!(safe_url("mailto:a@b.c"), "mailto:a@b.c");
+        assert_eq!(safe_url("page?a=1'"), "page?a=1&#39;");
+        assert_eq!(encode_url_component("a b/c?d=é"), "a%20b%2Fc%3Fd%3D%C3%A9");
+        // SafeHtml is put in as it is, anything else is escaped
+        let html = SafeHtml::from_trusted("<b>x</b>");
+        assert_eq!(Text(&html).html_text(), "<b>x</b>");
+        assert_eq!((&Text("<b>x</b>")).html_text(), "&lt;b&gt;x&lt;/b&gt;");
+        assert_eq!((&Text(&5)).html_text(), "5");
+        assert_eq!(attribute(&html), "<b>x</b>");
+    }
+}
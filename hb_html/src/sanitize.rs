@@ -0,0 +1,420 @@
+//! Removes unsafe markup from documents using an allowlist [`SanitizePolicy`].
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::sanitize::{sanitize, SanitizePolicy};
+//! let mut doc = r#"<p onclick="evil()">Hi <blink>there</blink><script>evil()</script></p>"#
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! sanitize(&mut doc, &SanitizePolicy::default());
+//! assert_eq!(doc.nodes[0].to_html_string(), "<p>Hi there</p>");
+//! ```
+use crate::entities::decode_entities;
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::pipeline::{Pass, PassContext};
+use crate::visit::{Visit, VisitAction, VisitContext};
+use std::collections::{HashMap, HashSet};
+
+/// The rules used to decide which tags and attributes are kept by the sanitizer.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct SanitizePolicy {
+    /// Tags which are kept. Any other tag is removed but its contents are kept.
+    pub allowed_tags: HashSet<String>,
+    /// Tags which are removed along with all of their contents.
+    pub removed_with_contents: HashSet<String>,
+    /// Attributes which are allowed on any of the allowed tags.
+    pub allowed_attributes: HashSet<String>,
+    /// Attributes which are allowed on specific tags.
+    pub tag_attributes: HashMap<String, HashSet<String>>,
+    /// Attributes which contain URLs which need their scheme checked.
+    pub url_attributes: HashSet<String>,
+    /// The URL schemes which are allowed in the url attributes. Relative URLs
+    /// without a scheme are always allowed.
+    pub allowed_url_schemes: HashSet<String>,
+    /// Removes all comments when true.
+    pub strip_comments: bool,
+}
+
+fn string_set(items: &[&str]) -> HashSet<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+impl Default for SanitizePolicy {
+    /// A policy which allows common formatting markup, links and images.
+    fn default() -> Self {
+        let mut tag_attributes = HashMap::new();
+        tag_attributes.insert("a".to_owned(), string_set(&["href", "rel", "target"]));
+        tag_attributes.insert(
+            "img".to_owned(),
+            string_set(&["src", "alt", "width", "height"]),
+        );
+        tag_attributes.insert("td".to_owned(), string_set(&["colspan", "rowspan"]));
+        tag_attributes.insert("th".to_owned(), string_set(&["colspan", "rowspan"]));
+        SanitizePolicy {
+            allowed_tags: string_set(&[
+                "a",
+                "abbr",
+                "b",
+                "blockquote",
+                "body",
+                "br",
+                "code",
+                "div",
+                "em",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "head",
+                "hr",
+                "html",
+                "i",
+                "img",
+                "li",
+                "ol",
+                "p",
+                "pre",
+                "span",
+                "strong",
+                "table",
+                "tbody",
+                "td",
+                "th",
+                "thead",
+                "title",
+                "tr",
+                "u",
+                "ul",
+            ]),
+            removed_with_contents: string_set(&[
+                "script", "style", "iframe", "object", "embed", "noscript", "template",
+            ]),
+            allowed_attributes: string_set(&["id", "class", "title", "lang", "dir"]),
+            tag_attributes,
+            url_attributes: string_set(&["href", "src"]),
+            allowed_url_schemes: string_set(&["http", "https", "mailto"]),
+            strip_comments: true,
+        }
+    }
+}
+
+impl SanitizePolicy {
+    /// Returns true if the attribute is allowed on the tag.
+    pub fn is_attribute_allowed(&self, tag: &str, attribute: &str) -> bool {
+        if attribute.starts_with("on") {
+            return false;
+        }
+        self.allowed_attributes.contains(attribute)
+            || self
+                .tag_attributes
+                .get(tag)
+                .map(|a| a.contains(attribute))
+                .unwrap_or(false)
+    }
+
+    /// Returns true if the URL has no scheme or an allowed scheme.
+    pub fn is_url_allowed(&self, url: &str) -> bool {
+        match url_scheme(url) {
+            None => true,
+            Some(scheme) => self.allowed_url_schemes.contains(&scheme),
+        }
+    }
+}
+
+/// Gets the scheme of a URL in lower case, ignoring whitespace and control characters
+/// which browsers also ignore (eg "java\tscript:"). Character references are decoded
+/// first, as they are when a browser reads an attribute value (eg "javascript&colon;").
+pub fn url_scheme(url: &str) -> Option<String> {
+    let mut scheme = String::new();
+    for c in decode_entities(url).chars() {
+        if c == ':' {
+            return if scheme.is_empty() {
+                None
+            } else {
+                Some(scheme)
+            };
+        } else if c == '/' || c == '?' || c == '#' {
+            return None;
+        } else if !c.is_whitespace() && !c.is_control() {
+            scheme.push(c.to_ascii_lowercase());
+        }
+    }
+    None
+}
+
+/// The changes made by the sanitizer. Paths are recorded as they were when the node
+/// was visited, so they can be shifted by earlier removals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SanitizeReport {
+    /// Paths and names of the tags which were removed or unwrapped.
+    pub removed_tags: Vec<(NodePath, String)>,
+    /// Paths of the tags and names of the attributes which were removed.
+    pub removed_attributes: Vec<(NodePath, String)>,
+    pub removed_comments: usize,
+}
+
+/// The elements whose contents are parsed as text, which could be read as markup if
+/// the element was unwrapped, so they are removed with their contents instead.
+const TEXT_ELEMENTS: [&str; 10] = [
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+    "script",
+    "style",
+    "textarea",
+    "title",
+    "xmp",
+];
+
+struct Sanitizer<'a> {
+    policy: &'a SanitizePolicy,
+    report: SanitizeReport,
+}
+
+impl Sanitizer<'_> {
+    fn clean_attributes(&mut self, tag: &mut HtmlTag, path: &NodePath) {
+        let policy = self.policy;
+        for special in ["id", "class"] {
+            if !policy.is_attribute_allowed(&tag.tag, special) && tag.remove_attribute(special) {
+                self.report
+                    .removed_attributes
+                    .push((path.clone(), special.to_owned()));
+            }
+        }
+        let mut names: Vec<String> = tag.attributes.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            let keep = policy.is_attribute_allowed(&tag.tag, &name)
+                && (!policy.url_attributes.contains(&name)
+                    || policy.is_url_allowed(&tag.attributes[&name]));
+            if !keep {
+                tag.attributes.remove(&name);
+                self.report.removed_attributes.push((path.clone(), name));
+            }
+        }
+    }
+}
+
+impl Visit for Sanitizer<'_> {
+    fn enter_element(&mut self, tag: &mut HtmlTag, ctx: &VisitContext) -> VisitAction {
+        let path = ctx.path.clone();
+        let removed = &self.policy.removed_with_contents;
+        if removed.iter().any(|t| t.eq_ignore_ascii_case(&tag.tag)) {
+            self.report.removed_tags.push((path, tag.tag.clone()));
+            return VisitAction::Remove;
+        }
+        self.clean_attributes(tag, &path);
+        VisitAction::Continue
+    }
+
+    fn exit_element(&mut self, tag: &mut HtmlTag, ctx: &VisitContext) -> VisitAction {
+        if self.policy.allowed_tags.contains(&tag.tag) {
+            return VisitAction::Continue;
+        }
+        let path = ctx.path.clone();
+        self.report.removed_tags.push((path, tag.tag.clone()));
+        if TEXT_ELEMENTS
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(&tag.tag))
+        {
+            return VisitAction::Remove;
+        }
+        // the contents have already been sanitized so they can be kept
+        VisitAction::Replace(std::mem::take(&mut tag.contents))
+    }
+
+    fn comment(&mut self, _comment: &mut String, _ctx: &VisitContext) -> VisitAction {
+        if self.policy.strip_comments {
+            self.report.removed_comments += 1;
+            return VisitAction::Remove;
+        }
+        VisitAction::Continue
+    }
+}
+
+/// Sanitizes the document in place using the policy provided.
+pub fn sanitize(doc: &mut HtmlDocument, policy: &SanitizePolicy) -> SanitizeReport {
+    sanitize_nodes(&mut doc.nodes, policy)
+}
+
+/// Sanitizes a list of nodes in place using the policy provided.
+pub fn sanitize_nodes(nodes: &mut Vec<HtmlNode>, policy: &SanitizePolicy) -> SanitizeReport {
+    let mut sanitizer = Sanitizer {
+        policy,
+        report: SanitizeReport::default(),
+    };
+    let mut ctx = VisitContext {
+        path: vec![],
+        ancestors: vec![],
+    };
+    crate::visit::walk_nodes(nodes, &mut sanitizer, &mut ctx);
+    sanitizer.report
+}
+
+/// A [`Pass`] which sanitizes the document using a [`SanitizePolicy`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sanitize {
+    pub policy: SanitizePolicy,
+}
+
+impl Sanitize {
+    pub fn new(policy: SanitizePolicy) -> Sanitize {
+        Sanitize { policy }
+    }
+}
+
+impl Pass for Sanitize {
+    fn name(&self) -> &str {
+        "sanitize"
+    }
+
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError> {
+        let report = sanitize(doc, &self.policy);
+        for (path, tag) in report.removed_tags {
+            ctx.info(format!("removed <{}>", tag), Some(path));
+        }
+        for (path, attr) in report.removed_attributes {
+            ctx.info(format!("removed attribute '{}'", attr), Some(path));
+        }
+        if report.removed_comments > 0 {
+            ctx.info(
+                format!("removed {} comments", report.removed_comments),
+                None,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod sanitize_tests {
+    use super::*;
+
+    fn sanitized(html: &str, policy: &SanitizePolicy) -> String {
+        let mut doc = html.parse::<HtmlDocument>().unwrap();
+        sanitize(&mut doc, policy);
+        doc.nodes
+            .iter()
+            .map(|n| n.to_html_string())
+            .collect::<String>()
+    }
+
+    #[test]
+    fn sanitize_default_policy_test() {
+        let policy = SanitizePolicy::default();
+        assert_eq!(
+            sanitized("<div class=a style=\"x\">Hi<!-- c --></div>", &policy),
+            "<div class=\"a\">Hi</div>"
+        );
+        assert_eq!(
+            sanitized(
+                "<p><font><b>bold</b><iframe src=x>inner</iframe></font></p>",
+                &policy
+            ),
+            "<p><b>bold</b></p>"
+        );
+        assert_eq!(
+            sanitized(
+                r#"<a href="java	script:alert(1)" onmouseover="x">a</a><a href="/rel">b</a>"#,
+                &policy
+            ),
+            "<a>a</a><a href=\"/rel\">b</a>"
+        );
+    }
+
+    #[test]
+    fn sanitize_custom_policy_test() {
+        let mut policy = SanitizePolicy {
+            strip_comments: false,
+            ..Default::default()
+        };
+        policy.allowed_attributes.remove("class");
+        policy.allowed_tags.insert("section".to_owned());
+        assert_eq!(
+            sanitized("<section class=a><!-- c --></section>", &policy),
+            "<section><!--  c  --!></section>"
+        );
+    }
+
+    #[test]
+    fn sanitize_report_test() {
+        let mut doc = "<div onclick=x><script>y</script></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let report = sanitize(&mut doc, &SanitizePolicy::default());
+        assert_eq!(report.removed_tags, vec![(vec![0, 0], "script".to_owned())]);
+        assert_eq!(
+            report.removed_attributes,
+            vec![(vec![0], "onclick".to_owned())]
+        );
+    }
+
+    #[test]
+    fn url_scheme_test() {
+        assert_eq!(url_scheme("https://a.b/c"), Some("https".to_owned()));
+        assert_eq!(url_scheme(" JavaScript:x"), Some("javascript".to_owned()));
+        assert_eq!(url_scheme("/a:b"), None);
+        assert_eq!(url_scheme("page?x=a:b"), None);
+        assert_eq!(url_scheme("page"), None);
+        // references are decoded before the scheme is read
+        assert_eq!(
+            url_scheme("javascript&#58;x"),
+            Some("javascript".to_owned())
+        );
+        assert_eq!(
+            url_scheme("javascript&colon;x"),
+            Some("javascript".to_owned())
+        );
+        assert_eq!(
+            url_scheme("java&#x09;script:x"),
+            Some("javascript".to_owned())
+        );
+        assert_eq!(url_scheme("page&#63;a:b"), None);
+        let policy = SanitizePolicy::default();
+        for url in [
+            "javascript&#58;x",
+            "javascript&colon;x",
+            "java&#x09;script:x",
+        ] {
+            assert!(!policy.is_url_allowed(url), "{} should not be allowed", url);
+        }
+    }
+
+    #[test]
+    fn sanitize_text_elements_test() {
+        let policy = SanitizePolicy::default();
+        // the text of an unwrapped textarea would otherwise be read as markup
+        for source in [
+            "<div><textarea><img src=x onerror=alert(1)></textarea></div>",
+            "<div><SCRIPT>alert(1)</SCRIPT><STYLE>a {}</STYLE></div>",
+        ] {
+            let mut doc = source.parse::<HtmlDocument>().unwrap();
+            sanitize(&mut doc, &policy);
+            assert_eq!(doc.to_html_string(), "<div></div>", "sanitizing {}", source);
+        }
+        let mut doc = "<div><SCRIPT>alert(1)</SCRIPT></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let report = sanitize(&mut doc, &policy);
+        assert_eq!(report.removed_tags, vec![(vec![0, 0], "SCRIPT".to_owned())]);
+        // references in URLs are decoded when they are checked
+        let options = crate::options::ParseOptions::new().decode_entities(false);
+        let mut doc =
+            HtmlDocument::parse_with(r#"<a href="javascript&colon;alert(1)">a</a>"#, &options)
+                .unwrap();
+        sanitize(&mut doc, &policy);
+        assert_eq!(doc.nodes[0].to_html_string(), "<a>a</a>");
+    }
+}
@@ -0,0 +1,201 @@
+//! Scanning the text of a document for many keywords at once.
+//! This module is only available with the `aho-corasick` feature.
+//!
+//! A [`KeywordScanner`] builds an Aho-Corasick automaton from the keywords, so each
+//! text node is only read once however many keywords there are, which is much
+//! faster than searching for each keyword with [`HtmlDocument::find_text`].
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::scan::{KeywordScanner, ScanOptions};
+//! let doc = "<p>Acme and ACME Widgets</p><p>acmewidgets</p>"
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let options = ScanOptions::new()
+//!     .ascii_case_insensitive(true)
+//!     .whole_word(true);
+//! let scanner = KeywordScanner::with_options(["acme", "acme widgets"], &options).unwrap();
+//! let hits = scanner.scan(&doc);
+//! let found: Vec<&str> = hits.iter().map(|h| h.text.as_str()).collect();
+//! assert_eq!(found, vec!["Acme", "ACME", "ACME Widgets"]);
+//! ```
+use crate::error::ConfigError;
+use crate::objects::{HtmlDocument, NodePath};
+use aho_corasick::AhoCorasick;
+use std::ops::Range;
+
+/// Options for [`KeywordScanner::with_options`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanOptions {
+    /// Ignore the case of ASCII letters, other letters must match exactly.
+    pub ascii_case_insensitive: bool,
+    /// Only report hits with no letters or digits directly before and after them.
+    pub whole_word: bool,
+}
+
+impl ScanOptions {
+    pub fn new() -> ScanOptions {
+        ScanOptions::default()
+    }
+
+    pub fn ascii_case_insensitive(mut self, ascii_case_insensitive: bool) -> ScanOptions {
+        self.ascii_case_insensitive = ascii_case_insensitive;
+        self
+    }
+
+    pub fn whole_word(mut self, whole_word: bool) -> ScanOptions {
+        self.whole_word = whole_word;
+        self
+    }
+}
+
+/// A keyword found by a [`KeywordScanner`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeywordHit {
+    /// The index of the keyword in the list the scanner was created with.
+    pub keyword: usize,
+    /// The path of the text node the keyword was found in.
+    pub path: NodePath,
+    /// The byte range of the hit in the text node.
+    pub range: Range<usize>,
+    /// The text which matched the keyword.
+    pub text: String,
+}
+
+/// Finds every occurrence of a set of keywords in one pass over the text.
+#[derive(Debug, Clone)]
+pub struct KeywordScanner {
+    automaton: AhoCorasick,
+    keywords: Vec<String>,
+    whole_word: bool,
+}
+
+impl KeywordScanner {
+    /// Creates a case sensitive scanner for the keywords.
+    pub fn new<I, K>(keywords: I) -> Result<KeywordScanner, ConfigError>
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        KeywordScanner::with_options(keywords, &ScanOptions::default())
+    }
+
+    pub fn with_options<I, K>(
+        keywords: I,
+        options: &ScanOptions,
+    ) -> Result<KeywordScanner, ConfigError>
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        let keywords: Vec<String> = keywords.into_iter().map(|k| k.into()).collect();
+        if keywords.iter().any(|k| k.is_empty()) {
+            return Err(ConfigError::with_msg("keywords can not be empty"));
+        }
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(options.ascii_case_insensitive)
+            .build(&keywords)
+            .map_err(|e| {
+                ConfigError::with_msg(format!("could not build the keyword scanner: {}", e))
+            })?;
+        Ok(KeywordScanner {
+            automaton,
+            keywords,
+            whole_word: options.whole_word,
+        })
+    }
+
+    pub fn keywords(&self) -> &[String] {
+        &self.keywords
+    }
+
+    /// Finds every occurrence of the keywords in the text, including ones which
+    /// overlap, as the index of the keyword and its byte range. Hits are ordered by
+    /// where they end in the text.
+    pub fn scan_text(&self, text: &str) -> Vec<(usize, Range<usize>)> {
+        let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric());
+        self.automaton
+            .find_overlapping_iter(text)
+            .filter(|m| {
+                !self.whole_word
+                    || (!is_word_char(text[..m.start()].chars().next_back())
+                        && !is_word_char(text[m.end()..].chars().next()))
+            })
+            .map(|m| (m.pattern().as_usize(), m.range()))
+            .collect()
+    }
+
+    /// Finds every occurrence of the keywords in the text nodes of the document. The
+    /// text inside `<script>` and `<style>` elements is not scanned.
+    pub fn scan(&self, doc: &HtmlDocument) -> Vec<KeywordHit> {
+        let mut hits = vec![];
+        for (path, text) in doc.text_nodes() {
+            for (keyword, range) in self.scan_text(text) {
+                hits.push(KeywordHit {
+                    keyword,
+                    path: path.clone(),
+                    text: text[range.clone()].to_owned(),
+                    range,
+                });
+            }
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod scan_tests {
+    use super::*;
+
+    #[test]
+    fn scan_text_overlapping_test() {
+        let scanner = KeywordScanner::new(["he", "she", "his", "hers"]).unwrap();
+        let hits = scanner.scan_text("ushers");
+        assert_eq!(hits, vec![(1, 1..4), (0, 2..4), (3, 2..6)]);
+        assert_eq!(scanner.keywords()[3], "hers");
+        assert!(scanner.scan_text("HERS").is_empty());
+    }
+
+    #[test]
+    fn scan_options_test() {
+        let options = ScanOptions::new()
+            .ascii_case_insensitive(true)
+            .whole_word(true);
+        let scanner = KeywordScanner::with_options(["rust", "ÉCOLE"], &options).unwrap();
+        let text = "Rust, trusty RUST école ÉCOLE";
+        let found: Vec<&str> = scanner
+            .scan_text(text)
+            .into_iter()
+            .map(|(_, r)| &text[r])
+            .collect();
+        // non-ascii letters are still case sensitive
+        assert_eq!(found, vec!["Rust", "RUST", "ÉCOLE"]);
+        assert_eq!(
+            format!("{}", KeywordScanner::new(["a", ""]).unwrap_err()),
+            "Config Error: 'keywords can not be empty'"
+        );
+    }
+
+    #[test]
+    fn scan_document_test() {
+        let doc = "<div><p>Acme rocks</p><script>acme</script><ul><li>Try acme</li></ul></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let scanner = KeywordScanner::new(["Acme", "acme", "rocks"]).unwrap();
+        let hits = scanner.scan(&doc);
+        let found: Vec<(usize, &[usize], Range<usize>)> = hits
+            .iter()
+            .map(|h| (h.keyword, h.path.as_slice(), h.range.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0, &[0, 0, 0][..], 0..4),
+                (2, &[0, 0, 0][..], 5..10),
+                (1, &[0, 2, 0, 0][..], 4..8)
+            ]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Searching the text of a document.
+//!
+//! Matches are found within single text nodes and are returned with the path of
+//! the text node, the byte range of the match in its text and the text around the
+//! match, which can be used to highlight or excerpt the results.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::{CssSelector, HtmlDocument};
+//! use hb_html::search::SearchOptions;
+//! let doc = "<nav>Rust</nav><main><p>Learning rust, trusting Rust.</p></main>"
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! let main = "main".parse::<CssSelector>().unwrap();
+//! let options = SearchOptions::new().case_insensitive(true).whole_word(true);
+//! let matches = doc.find_text_with("rust", Some(&main), &options);
+//! assert_eq!(matches.len(), 2);
+//! assert_eq!(matches[0].path, vec![1, 0, 0]);
+//! assert_eq!(matches[0].range, 9..13);
+//! assert_eq!(matches[1].text, "Rust");
+//! assert_eq!(matches[1].before, "Learning rust, trusting ");
+//! ```
+use crate::objects::{CssSelector, HtmlDocument, NodePath};
+use crate::querying::HtmlQueryable;
+use std::ops::Range;
+
+/// Options for [`HtmlDocument::find_text_with`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOptions {
+    /// Compare the text with the case folded, so `rust` matches `Rust`.
+    pub case_insensitive: bool,
+    /// Only match if there are no letters or digits directly before and after the match.
+    pub whole_word: bool,
+    /// The number of chars of the text node before and after the match to include
+    /// in the results.
+    pub context_chars: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            case_insensitive: false,
+            whole_word: false,
+            context_chars: 30,
+        }
+    }
+}
+
+impl SearchOptions {
+    pub fn new() -> SearchOptions {
+        SearchOptions::default()
+    }
+
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> SearchOptions {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    pub fn whole_word(mut self, whole_word: bool) -> SearchOptions {
+        self.whole_word = whole_word;
+        self
+    }
+
+    pub fn context_chars(mut self, context_chars: usize) -> SearchOptions {
+        self.context_chars = context_chars;
+        self
+    }
+}
+
+/// A match found by [`HtmlDocument::find_text`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMatch {
+    /// The path of the text node the match is in.
+    pub path: NodePath,
+    /// The byte range of the match in the text node.
+    pub range: Range<usize>,
+    /// The text which matched.
+    pub text: String,
+    /// The text of the node before the match, up to [`SearchOptions::context_chars`] chars.
+    pub before: String,
+    /// The text of the node after the match, up to [`SearchOptions::context_chars`] chars.
+    pub after: String,
+}
+
+/// Finds the byte ranges of the non-overlapping matches of the pattern in the text.
+pub fn find_in_text(text: &str, pattern: &str, options: &SearchOptions) -> Vec<Range<usize>> {
+    if pattern.is_empty() {
+        return vec![];
+    }
+    let fold = |s: &str| -> Vec<(usize, char)> {
+        if options.case_insensitive {
+            s.char_indices()
+                .flat_map(|(i, c)| c.to_lowercase().map(move |l| (i, l)))
+                .collect()
+        } else {
+            s.char_indices().collect()
+        }
+    };
+    // case folding can change the number of chars, so keep the position of the
+    // original char for each folded char
+    let folded = fold(text);
+    let pattern: Vec<char> = fold(pattern).into_iter().map(|(_, c)| c).collect();
+    let byte_at = |i: usize| folded.get(i).map_or(text.len(), |(b, _)| *b);
+    // a match has to start and end on the boundary of an original char
+    let is_boundary = |i: usize| i == 0 || i == folded.len() || folded[i - 1].0 != folded[i].0;
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric());
+    let mut matches = vec![];
+    let mut i = 0;
+    while i + pattern.len() <= folded.len() {
+        let end = i + pattern.len();
+        let is_match = is_boundary(i)
+            && is_boundary(end)
+            && folded[i..end]
+                .iter()
+                .map(|(_, c)| *c)
+                .eq(pattern.iter().copied());
+        let range = byte_at(i)..byte_at(end);
+        if is_match
+            && (!options.whole_word
+                || (!is_word_char(text[..range.start].chars().next_back())
+                    && !is_word_char(text[range.end..].chars().next())))
+        {
+            matches.push(range);
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
+
+impl HtmlDocument {
+    /// Finds the text in the document, using the default [`SearchOptions`]. If a
+    /// selector is provided only the text inside the elements it matches is searched.
+    pub fn find_text(&self, pattern: &str, within: Option<&CssSelector>) -> Vec<TextMatch> {
+        self.find_text_with(pattern, within, &SearchOptions::default())
+    }
+
+    /// Finds the text in the document using the options provided. If a selector is
+    /// provided only the text inside the elements it matches is searched. The text
+    /// inside `<script>` and `<style>` elements is not searched.
+    pub fn find_text_with(
+        &self,
+        pattern: &str,
+        within: Option<&CssSelector>,
+        options: &SearchOptions,
+    ) -> Vec<TextMatch> {
+        let scopes: Option<Vec<NodePath>> = within.map(|selector| {
+            let mut query = self.query();
+            query.find(selector);
+            query.results.iter().map(|r| r.node_path()).collect()
+        });
+        let mut matches = vec![];
+        for (path, text) in self.text_nodes() {
+            if let Some(scopes) = &scopes {
+                if !scopes.iter().any(|s| path.starts_with(s)) {
+                    continue;
+                }
+            }
+            for range in find_in_text(text, pattern, options) {
+                let before = &text[..range.start];
+                let skip = before.chars().count().saturating_sub(options.context_chars);
+                matches.push(TextMatch {
+                    path: path.clone(),
+                    text: text[range.clone()].to_owned(),
+                    before: before.chars().skip(skip).collect(),
+                    after: text[range.end..]
+                        .chars()
+                        .take(options.context_chars)
+                        .collect(),
+                    range,
+                });
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use super::*;
+
+    fn found<'a>(text: &'a str, pattern: &str, options: &SearchOptions) -> Vec<&'a str> {
+        find_in_text(text, pattern, options)
+            .into_iter()
+            .map(|r| &text[r])
+            .collect()
+    }
+
+    #[test]
+    fn find_in_text_options_test() {
+        let text = "Rust rust RUST trust rusty";
+        let default = SearchOptions::new();
+        assert_eq!(found(text, "rust", &default), vec!["rust", "rust", "rust"]);
+        let folded = SearchOptions::new().case_insensitive(true);
+        assert_eq!(found(text, "rust", &folded).len(), 5);
+        let words = folded.clone().whole_word(true);
+        assert_eq!(found(text, "rust", &words), vec!["Rust", "rust", "RUST"]);
+        assert_eq!(found(text, "", &folded), Vec::<&str>::new());
+        // matches do not overlap
+        assert_eq!(found("aaaa", "aa", &default).len(), 2);
+    }
+
+    #[test]
+    fn find_in_text_unicode_test() {
+        let folded = SearchOptions::new().case_insensitive(true);
+        assert_eq!(
+            find_in_text("Ünïcode ÜNÏCODE", "ünïcode", &folded),
+            vec![0..9, 10..19]
+        );
+        // 'İ' folds to two chars, so only matches the whole of the folded form
+        assert_eq!(
+            found("İstanbul", "i\u{307}stanbul", &folded),
+            vec!["İstanbul"]
+        );
+        assert_eq!(
+            found("İstanbul", "\u{307}stanbul", &folded),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn find_text_scoped_test() {
+        let doc = "<div id=a>one apple</div><div id=b><p>two apples</p><script>apple</script>\
+                   <p>an apple a day keeps the doctor away</p></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let all = doc.find_text("apple", None);
+        assert_eq!(all.len(), 3);
+        let b = "#b".parse::<CssSelector>().unwrap();
+        let scoped = doc.find_text_with("apple", Some(&b), &SearchOptions::new().context_chars(4));
+        assert_eq!(scoped.len(), 2);
+        assert_eq!(scoped[0].path, vec![1, 0, 0]);
+        assert_eq!(scoped[0].range, 4..9);
+        assert_eq!(scoped[0].before, "two ");
+        assert_eq!(scoped[0].after, "s");
+        assert_eq!(scoped[1].before, "an ");
+        assert_eq!(scoped[1].after, " a d");
+        let missing = "#missing".parse::<CssSelector>().unwrap();
+        assert!(doc.find_text("apple", Some(&missing)).is_empty());
+    }
+}
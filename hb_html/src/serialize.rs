@@ -0,0 +1,1394 @@
+//! Writing documents back out as HTML.
+//!
+//! [`HtmlDocument::to_html_string`] writes the doctype and all of the nodes of a
+//! document in the same way as [`HtmlNode::to_html_string`]. The `_with` versions
+//! take [`SerializeOptions`] to change how the output is written.
+//!
+//! Boolean attributes such as `disabled` are written with just their name when
+//! their value is empty or the same as their name, see [`is_boolean_attribute`].
+//!
+//! # XML output
+//!
+//! With [`OutputMode::Xml`] the output is well-formed XML (XHTML) as long as the
+//! text and attribute values are: elements without contents are written as
+//! `<br/>`, boolean attributes are written as `disabled="disabled"`, and the
+//! namespace declarations which are needed are added. These are the default
+//! namespace of the top element and of `<svg>` and `<math>` elements, and the
+//! well known prefixes such as `xlink` when they are used without being declared.
+//! Declarations and prefixed attributes which are in the document are kept as
+//! they are in both modes, see [`crate::namespace`].
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::serialize::{OutputMode, SerializeOptions};
+//! let doc = r##"<p>Icon<svg><use xlink:href="#i"></use></svg><br></p>"##
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! assert_eq!(
+//!     doc.to_html_string_with(&SerializeOptions::new().mode(OutputMode::Xml)),
+//!     concat!(
+//!         r#"<p xmlns="http://www.w3.org/1999/xhtml">Icon"#,
+//!         r#"<svg xmlns="http://www.w3.org/2000/svg">"#,
+//!         r##"<use xmlns:xlink="http://www.w3.org/1999/xlink" xlink:href="#i"/></svg><br/></p>"##,
+//!     )
+//! );
+//! ```
+//!
+//! # Attribute order
+//!
+//! The attributes of a tag other than `id` and `class` are stored in a map, so by
+//! default they are written in whatever order the map holds them, which can change
+//! between runs. [`AttributeOrder`] sorts them so the output is the same every
+//! time, eg for snapshot tests.
+//!
+//! ```
+//! use hb_html::objects::{HtmlNode, HtmlTag};
+//! use hb_html::serialize::{AttributeOrder, SerializeOptions};
+//! let tag = "<a title=t href=/ class=c id=i></a>".parse::<HtmlTag>().unwrap();
+//! let node = HtmlNode::Tag(tag);
+//! let options = SerializeOptions::new().attribute_order(AttributeOrder::Canonical);
+//! assert_eq!(
+//!     node.to_html_string_with(&options),
+//!     r#"<a id="i" class="c" href="/" title="t"></a>"#
+//! );
+//! let options = SerializeOptions::new().attribute_order(AttributeOrder::Alphabetical);
+//! assert_eq!(
+//!     node.to_html_string_with(&options),
+//!     r#"<a class="c" href="/" id="i" title="t"></a>"#
+//! );
+//! ```
+//!
+//! # Pretty output
+//!
+//! [`HtmlNode::to_pretty_html`] and [`HtmlDocument::to_pretty_html`] write each
+//! tag and piece of text on its own line, indented by
+//! [`SerializeOptions::indent`] spaces for each level and with text wrapped at
+//! [`SerializeOptions::max_line_width`], for output which is read by people rather
+//! than browsers.
+//!
+//! # Writing to a stream
+//!
+//! [`HtmlDocument::serialize_to`] writes the document to an [`io::Write`], such as
+//! a file or a socket, as it goes rather than building the whole output as a
+//! `String` first, which for a large document would hold it in memory twice.
+//! [`HtmlDocument::serialize_to_fmt`] does the same for a [`fmt::Write`]. The
+//! output is written in many small pieces, so an unbuffered writer should be
+//! wrapped in a [`std::io::BufWriter`].
+//!
+//! When the output is sent by something which pulls it, such as the body of a
+//! response in a web framework, [`HtmlDocument::serialize_chunks`] gives the
+//! output as an iterator of chunks of bytes instead, writing the next part of the
+//! document each time a chunk is taken.
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::serialize::SerializeOptions;
+//! let doc = "<ul><li>One</li><li>Two</li></ul>".parse::<HtmlDocument>().unwrap();
+//! let mut out: Vec<u8> = vec![];
+//! doc.serialize_to(&mut out, &SerializeOptions::new()).unwrap();
+//! assert_eq!(out, doc.to_html_string().into_bytes());
+//! ```
+//!
+//! # Output maps
+//!
+//! After a document has been changed the output no longer lines up with the
+//! source it was parsed from. [`HtmlDocument::to_html_string_with_map`] also
+//! returns an [`OutputMap`], which holds the byte range in the output of every
+//! node and, for the nodes which were in the original document, the byte range of
+//! the node in the source. Nodes are matched to the [`SourceMap`] of the source by
+//! their [`NodePath`] and kind, so a node which was added to the document has no
+//! source range unless it replaced a node of the same kind. With the `serde`
+//! feature the map can be written as JSON for other tools.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::{HtmlDocument, HtmlNode};
+//! use hb_html::serialize::SerializeOptions;
+//! let source = "<div>\n  <p>Hello</p>\n</div>";
+//! let (mut doc, source_map) = HtmlDocument::parse_with_source_map(source).unwrap();
+//! if let HtmlNode::Tag(div) = &mut doc.nodes[0] {
+//!     div.contents.retain(|n| !matches!(n, HtmlNode::Text(_)));
+//!     div.contents.push(HtmlNode::new_tag("hr"));
+//! }
+//! let (output, map) = doc.to_html_string_with_map(&SerializeOptions::new(), source, &source_map);
+//! assert_eq!(output, "<div><p>Hello</p><hr></hr></div>");
+//! // the <p> is no longer at the same path, so the closest node from the source
+//! // is the <div>
+//! let div = map.original_at(output.find("Hello").unwrap()).unwrap();
+//! assert_eq!(div.path, vec![0]);
+//! assert_eq!(&source[div.input.clone().unwrap()], source);
+//! ```
+use crate::content_model::{element_info, ContentModel};
+use crate::entities::{escape_attribute, escape_text};
+use crate::namespace::{well_known_prefix, Namespaces};
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::options::NoscriptMode;
+use crate::parsing::drops_leading_newline;
+use crate::source_map::SourceMap;
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+
+/// The attributes whose value is true when they are there and false when they are
+/// missing, in alphabetical order.
+pub const BOOLEAN_ATTRIBUTES: [&str; 26] = [
+    "allowfullscreen",
+    "async",
+    "autofocus",
+    "autoplay",
+    "checked",
+    "controls",
+    "default",
+    "defer",
+    "disabled",
+    "formnovalidate",
+    "hidden",
+    "inert",
+    "ismap",
+    "itemscope",
+    "loop",
+    "multiple",
+    "muted",
+    "nomodule",
+    "novalidate",
+    "open",
+    "playsinline",
+    "readonly",
+    "required",
+    "reversed",
+    "selected",
+    "shadowrootdelegatesfocus",
+];
+
+/// The elements whose text is written as it is, without escaping, as the parser
+/// does not decode character references in them.
+const RAW_TEXT_ELEMENTS: [&str; 7] = [
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+    "script",
+    "style",
+    "xmp",
+];
+
+pub(crate) fn is_raw_text_element(tag: &str) -> bool {
+    RAW_TEXT_ELEMENTS
+        .iter()
+        .any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+/// Checks whether the attribute is a boolean attribute, ignoring case.
+pub fn is_boolean_attribute(name: &str) -> bool {
+    BOOLEAN_ATTRIBUTES
+        .binary_search(&name.to_ascii_lowercase().as_str())
+        .is_ok()
+}
+
+/// The order the attributes of a tag are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AttributeOrder {
+    /// `id` and `class` followed by the other attributes in the order they are
+    /// stored, which is not the same every time.
+    #[default]
+    Stored,
+    /// All attributes sorted by name, including `id` and `class`.
+    Alphabetical,
+    /// `id` and `class` followed by the other attributes sorted by name.
+    Canonical,
+}
+
+/// The syntax documents are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OutputMode {
+    #[default]
+    Html,
+    /// XML, for XHTML documents and for embedding in other XML documents.
+    Xml,
+}
+
+/// How elements which can have no contents, eg `<br>`, are written in HTML mode.
+/// Elements which have contents anyway are always written with an end tag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum VoidElements {
+    /// With an end tag, `<br></br>`.
+    #[default]
+    EndTag,
+    /// Without an end tag, `<br>`.
+    StartTag,
+    /// As a self-closing tag, `<br/>`.
+    SelfClosing,
+}
+
+/// Options for writing documents as HTML. The default options write the same
+/// output as [`HtmlNode::to_html_string`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializeOptions {
+    pub attribute_order: AttributeOrder,
+    pub mode: OutputMode,
+    pub void_elements: VoidElements,
+    /// Escape text and attribute values, see [`SerializeOptions::escape`].
+    pub escape: bool,
+    /// How the text of `<noscript>` elements is written, see
+    /// [`SerializeOptions::noscript`].
+    pub noscript: NoscriptMode,
+    /// The number of spaces each level of pretty output is indented by.
+    pub indent: usize,
+    /// The width in chars that text in pretty output is wrapped at, None to never
+    /// wrap it.
+    pub max_line_width: Option<usize>,
+    /// The elements whose contents are written as they are in pretty output, as
+    /// well as `<pre>`, `<script>`, `<style>` and `<textarea>`.
+    pub preserve_whitespace_tags: Vec<String>,
+    /// The attributes which are left out of the output.
+    pub skip_attributes: Vec<String>,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> SerializeOptions {
+        DEFAULT_OPTIONS.clone()
+    }
+}
+
+impl SerializeOptions {
+    pub fn new() -> SerializeOptions {
+        SerializeOptions::default()
+    }
+
+    pub fn attribute_order(mut self, order: AttributeOrder) -> SerializeOptions {
+        self.attribute_order = order;
+        self
+    }
+
+    pub fn mode(mut self, mode: OutputMode) -> SerializeOptions {
+        self.mode = mode;
+        self
+    }
+
+    pub fn void_elements(mut self, void_elements: VoidElements) -> SerializeOptions {
+        self.void_elements = void_elements;
+        self
+    }
+
+    /// Escapes `&`, `<` and `>` in text and `&` and `"` in attribute values, so
+    /// text which holds these characters reads the same when the output is parsed.
+    /// The text of elements such as `<script>`, which is not parsed for character
+    /// references, is never escaped. This is on by default, as the parser decodes
+    /// the character references in text. Turn it off for documents parsed with
+    /// [`ParseOptions::decode_entities`](crate::options::ParseOptions::decode_entities)
+    /// turned off, whose text still has its references in it.
+    pub fn escape(mut self, escape: bool) -> SerializeOptions {
+        self.escape = escape;
+        self
+    }
+
+    /// Writes the text of `<noscript>` elements without escaping it with
+    /// [`NoscriptMode::RawText`], for documents parsed with the same mode whose
+    /// `<noscript>` elements hold their markup as text.
+    pub fn noscript(mut self, noscript: NoscriptMode) -> SerializeOptions {
+        self.noscript = noscript;
+        self
+    }
+
+    pub fn indent(mut self, indent: usize) -> SerializeOptions {
+        self.indent = indent;
+        self
+    }
+
+    pub fn max_line_width(mut self, width: usize) -> SerializeOptions {
+        self.max_line_width = Some(width);
+        self
+    }
+
+    /// Adds an element whose contents are written as they are in pretty output.
+    pub fn preserve_whitespace_tag<T: Into<String>>(mut self, tag: T) -> SerializeOptions {
+        self.preserve_whitespace_tags.push(tag.into());
+        self
+    }
+
+    /// Leaves the attribute out of the output, such as the
+    /// [stable IDs](crate::stable_id) which are only used by tools.
+    pub fn skip_attribute<T: Into<String>>(mut self, name: T) -> SerializeOptions {
+        self.skip_attributes.push(name.into());
+        self
+    }
+
+    /// Checks whether the contents of the element are written as they are in
+    /// pretty output.
+    fn preserves_whitespace(&self, tag: &str) -> bool {
+        ["pre", "script", "style", "textarea"]
+            .iter()
+            .copied()
+            .chain(self.preserve_whitespace_tags.iter().map(String::as_str))
+            .any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// Where a node of the output came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputMapping {
+    /// The path of the node in the document which was written.
+    pub path: NodePath,
+    /// The byte range of the node in the output.
+    pub output: Range<usize>,
+    /// The byte range of the node in the source, or None if the node was not in
+    /// the source.
+    pub input: Option<Range<usize>>,
+}
+
+/// The mappings of all of the nodes of a written document, in document order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputMap {
+    mappings: Vec<OutputMapping>,
+}
+
+impl OutputMap {
+    pub fn iter(&self) -> std::slice::Iter<'_, OutputMapping> {
+        self.mappings.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mappings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+
+    /// Gets the innermost node containing the byte offset of the output which was
+    /// in the source. For nodes which were added this is their closest ancestor
+    /// from the source.
+    pub fn original_at(&self, offset: usize) -> Option<&OutputMapping> {
+        self.mappings
+            .iter()
+            .filter(|m| m.input.is_some() && m.output.contains(&offset))
+            .max_by_key(|m| m.path.len())
+    }
+
+    /// Writes the map as JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("output maps only contain numbers and lists")
+    }
+}
+
+impl<'a> IntoIterator for &'a OutputMap {
+    type Item = &'a OutputMapping;
+    type IntoIter = std::slice::Iter<'a, OutputMapping>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.mappings.iter()
+    }
+}
+
+/// Checks that the source of a span could have been parsed into the node.
+fn is_same_kind(node: &HtmlNode, source: &str) -> bool {
+    let is_comment = source.starts_with("<!--");
+    let is_element = source.starts_with('<')
+        && source[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic());
+    match node {
+        HtmlNode::Tag(t) => {
+            is_element
+                && source[1..]
+                    .get(..t.tag.len())
+                    .is_some_and(|name| name.eq_ignore_ascii_case(&t.tag))
+        }
+        HtmlNode::Comment(_) => is_comment,
+        HtmlNode::Text(_) | HtmlNode::Template(_) => !is_comment && !is_element,
+    }
+}
+
+/// Writes nodes as HTML, optionally recording where each node was written.
+pub(crate) struct Serializer<'a, W: fmt::Write = String> {
+    options: &'a SerializeOptions,
+    out: W,
+    /// The number of bytes written to `out`.
+    len: usize,
+    /// The first error returned by `out`, after which nothing more is written.
+    error: fmt::Result,
+    /// The source the document was parsed from and its source map, when the output
+    /// is being mapped.
+    original: Option<(&'a str, &'a SourceMap)>,
+    path: NodePath,
+    mappings: Vec<OutputMapping>,
+    /// The namespaces in scope for each open element, only kept for XML output.
+    scopes: Vec<Namespaces>,
+    /// The number of open elements whose text is not escaped.
+    raw_text_depth: usize,
+    /// The number of chars written since the last newline.
+    column: usize,
+}
+
+impl<'a> Serializer<'a> {
+    pub(crate) fn new(options: &'a SerializeOptions) -> Serializer<'a> {
+        Serializer::with_writer(options, String::new())
+    }
+
+    fn with_map(
+        options: &'a SerializeOptions,
+        source: &'a str,
+        source_map: &'a SourceMap,
+    ) -> Serializer<'a> {
+        Serializer {
+            original: Some((source, source_map)),
+            ..Serializer::new(options)
+        }
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.out
+    }
+}
+
+impl<'a, W: fmt::Write> Serializer<'a, W> {
+    pub(crate) fn with_writer(options: &'a SerializeOptions, out: W) -> Serializer<'a, W> {
+        Serializer {
+            options,
+            out,
+            len: 0,
+            error: Ok(()),
+            original: None,
+            path: vec![],
+            mappings: vec![],
+            scopes: vec![],
+            raw_text_depth: 0,
+            column: 0,
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        if self.error.is_ok() {
+            self.error = self.out.write_str(s);
+            self.len += s.len();
+            match s.rfind('\n') {
+                Some(i) => self.column = s[i + 1..].chars().count(),
+                None => self.column += s.chars().count(),
+            }
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    pub(crate) fn write_node(&mut self, node: &HtmlNode) {
+        let start = self.len;
+        let mapping = match self.original {
+            Some((source, source_map)) => {
+                let input = source_map
+                    .get(&self.path)
+                    .map(|span| span.range.clone())
+                    .filter(|range| is_same_kind(node, &source[range.clone()]));
+                self.mappings.push(OutputMapping {
+                    path: self.path.clone(),
+                    output: start..start,
+                    input,
+                });
+                Some(self.mappings.len() - 1)
+            }
+            None => None,
+        };
+        match node {
+            HtmlNode::Comment(c) => {
+                self.push_str("<!-- ");
+                self.push_str(c);
+                self.push_str(" --!>");
+            }
+            HtmlNode::Tag(t) => self.write_tag(t),
+            HtmlNode::Text(t) => self.push_str(&self.text_to_write(t)),
+            HtmlNode::Template(t) => self.push_str(t),
+        }
+        if let Some(i) = mapping {
+            self.mappings[i].output.end = self.len;
+        }
+    }
+
+    pub(crate) fn write_tag(&mut self, tag: &HtmlTag) {
+        if self.write_start_tag(tag) {
+            self.write_contents(tag);
+            self.write_end_tag(tag);
+        }
+    }
+
+    /// Gets the text as it is written, which is escaped if the options say so and it
+    /// is not in a raw text element.
+    fn text_to_write<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        if self.options.escape && self.raw_text_depth == 0 {
+            Cow::Owned(escape_text(text))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// Writes the node on a new line indented to the depth, with each of the
+    /// contents of a tag on their own line one level deeper. Tags which only
+    /// contain text are kept on one line if they fit. The whitespace in text is
+    /// collapsed, apart from in the tags which preserve it.
+    pub(crate) fn write_pretty_node(&mut self, node: &HtmlNode, depth: usize) {
+        match node {
+            HtmlNode::Tag(t) => self.write_pretty_tag(t, depth),
+            HtmlNode::Text(t) => {
+                if t.split_whitespace().next().is_some() {
+                    self.new_line(depth);
+                    self.write_wrapped(t, depth);
+                }
+            }
+            HtmlNode::Comment(_) | HtmlNode::Template(_) => {
+                self.new_line(depth);
+                self.write_node(node);
+            }
+        }
+    }
+
+    fn write_pretty_tag(&mut self, tag: &HtmlTag, depth: usize) {
+        self.new_line(depth);
+        if self.options.preserves_whitespace(&tag.tag) {
+            self.write_tag(tag);
+            return;
+        }
+        if !self.write_start_tag(tag) {
+            return;
+        }
+        let mut texts = vec![];
+        for content in &tag.contents {
+            match content {
+                HtmlNode::Text(t) => texts.push(t.as_str()),
+                _ => break,
+            }
+        }
+        if texts.len() == tag.contents.len() {
+            let words: Vec<&str> = texts.iter().flat_map(|t| t.split_whitespace()).collect();
+            let text = self.text_to_write(&words.join(" ")).into_owned();
+            let line_len = self.column + text.chars().count() + tag.tag.chars().count() + 3;
+            if self
+                .options
+                .max_line_width
+                .is_none_or(|width| line_len <= width)
+            {
+                self.push_str(&text);
+                self.write_end_tag(tag);
+                return;
+            }
+        }
+        for (i, content) in tag.contents.iter().enumerate() {
+            self.path.push(i);
+            self.write_pretty_node(content, depth + 1);
+            self.path.pop();
+        }
+        self.new_line(depth);
+        self.write_end_tag(tag);
+    }
+
+    /// Starts a new line indented to the depth, unless nothing has been written.
+    fn new_line(&mut self, depth: usize) {
+        if self.len > 0 {
+            self.push('\n');
+        }
+        self.push_str(&" ".repeat(depth * self.options.indent));
+    }
+
+    /// Writes the words of the text separated by single spaces, starting a new line
+    /// indented to the depth before a word which would go past the line width.
+    fn write_wrapped(&mut self, text: &str, depth: usize) {
+        for (i, word) in text.split_whitespace().enumerate() {
+            let word = self.text_to_write(word);
+            let too_long = self
+                .options
+                .max_line_width
+                .is_some_and(|width| self.column + 1 + word.chars().count() > width);
+            if i > 0 && too_long {
+                self.new_line(depth);
+            } else if i > 0 {
+                self.push(' ');
+            }
+            self.push_str(&word);
+        }
+    }
+
+    /// Checks whether the text in the tag is written without escaping.
+    fn is_raw_text(&self, tag: &HtmlTag) -> bool {
+        is_raw_text_element(&tag.tag)
+            || (self.options.noscript == NoscriptMode::RawText
+                && tag.tag.eq_ignore_ascii_case("noscript"))
+    }
+
+    /// Writes the contents of the tag without its start and end tags.
+    pub(crate) fn write_contents(&mut self, tag: &HtmlTag) {
+        let is_raw_text = self.is_raw_text(tag);
+        if is_raw_text {
+            self.raw_text_depth += 1;
+        }
+        for (i, content) in tag.contents.iter().enumerate() {
+            self.path.push(i);
+            self.write_node(content);
+            self.path.pop();
+        }
+        if is_raw_text {
+            self.raw_text_depth -= 1;
+        }
+    }
+
+    /// Writes the start tag, returning true if the contents and end tag need to
+    /// be written after it.
+    fn write_start_tag(&mut self, tag: &HtmlTag) -> bool {
+        self.push('<');
+        self.push_str(&tag.tag);
+        let ids = tag.ids.join(" ");
+        let classes = tag.classes.join(" ");
+        let mut attributes: Vec<(&str, &str)> = vec![];
+        if !tag.ids.is_empty() {
+            attributes.push(("id", &ids));
+        }
+        if !tag.classes.is_empty() {
+            attributes.push(("class", &classes));
+        }
+        let first_other = attributes.len();
+        attributes.extend(tag.attributes.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        match self.options.attribute_order {
+            AttributeOrder::Stored => {}
+            AttributeOrder::Alphabetical => attributes.sort(),
+            AttributeOrder::Canonical => attributes[first_other..].sort(),
+        }
+        let is_xml = self.options.mode == OutputMode::Xml;
+        if is_xml {
+            for (name, value) in self.enter_scope(tag) {
+                self.write_attribute(&name, &value);
+            }
+        }
+        for (name, value) in attributes {
+            if !self.options.skip_attributes.iter().any(|s| s == name) {
+                self.write_attribute(name, value);
+            }
+        }
+        let name = tag.tag.to_ascii_lowercase();
+        // an iframe has no contents but, unlike the void elements, needs its end tag
+        let is_void = !is_xml
+            && tag.contents.is_empty()
+            && name != "iframe"
+            && element_info(&name).is_some_and(|e| e.content == ContentModel::Empty);
+        if is_xml && tag.contents.is_empty() {
+            self.push_str("/>");
+        } else if is_void && self.options.void_elements == VoidElements::StartTag {
+            self.push('>');
+        } else if is_void && self.options.void_elements == VoidElements::SelfClosing {
+            self.push_str("/>");
+        } else {
+            self.push('>');
+            // the parser drops a newline at the start of the contents, so another
+            // is written to keep one which is part of the text
+            let starts_with_newline = matches!(
+                tag.contents.first(),
+                Some(HtmlNode::Text(t)) if t.starts_with('\n') || t.starts_with("\r\n")
+            );
+            if !is_xml && starts_with_newline && drops_leading_newline(&tag.tag) {
+                self.push('\n');
+            }
+            if self.is_raw_text(tag) {
+                self.raw_text_depth += 1;
+            }
+            return true;
+        }
+        if is_xml {
+            self.scopes.pop();
+        }
+        false
+    }
+
+    fn write_end_tag(&mut self, tag: &HtmlTag) {
+        if self.is_raw_text(tag) {
+            self.raw_text_depth -= 1;
+        }
+        self.push_str("</");
+        self.push_str(&tag.tag);
+        self.push('>');
+        if self.options.mode == OutputMode::Xml {
+            self.scopes.pop();
+        }
+    }
+
+    /// Adds the namespaces in scope for the tag, returning the namespace
+    /// declarations which are needed but are not attributes of the tag.
+    fn enter_scope(&mut self, tag: &HtmlTag) -> Vec<(String, String)> {
+        let parent = self.scopes.last().cloned().unwrap_or_default();
+        let mut scope = parent.enter(tag);
+        let mut declarations = vec![];
+        if scope.default_namespace() != parent.default_namespace()
+            && !tag.attributes.contains_key("xmlns")
+        {
+            if let Some(ns) = scope.default_namespace() {
+                declarations.push(("xmlns".to_owned(), ns.to_owned()));
+            }
+        }
+        let names = std::iter::once(&tag.tag).chain(tag.attributes.keys());
+        for name in names {
+            let prefix = match Namespaces::split_name(name) {
+                (Some(prefix), _) if scope.resolve_prefix(prefix).is_none() => prefix,
+                _ => continue,
+            };
+            if let Some(ns) = well_known_prefix(prefix) {
+                scope.declare(prefix, ns);
+                declarations.push((format!("xmlns:{}", prefix), ns.to_owned()));
+            }
+        }
+        self.scopes.push(scope);
+        declarations
+    }
+
+    fn write_attribute(&mut self, name: &str, value: &str) {
+        self.push(' ');
+        self.push_str(name);
+        if is_boolean_attribute(name) && (value.is_empty() || value.eq_ignore_ascii_case(name)) {
+            if self.options.mode == OutputMode::Xml {
+                self.push_str("=\"");
+                self.push_str(name);
+                self.push('"');
+            }
+            return;
+        }
+        self.push_str("=\"");
+        if self.options.escape {
+            self.push_str(&escape_attribute(value));
+        } else {
+            self.push_str(value);
+        }
+        self.push('"');
+    }
+
+    fn write_document(&mut self, doc: &HtmlDocument) {
+        self.write_doctype(doc);
+        for (i, node) in doc.nodes.iter().enumerate() {
+            self.path.push(i);
+            self.write_node(node);
+            self.path.pop();
+        }
+    }
+
+    fn write_doctype(&mut self, doc: &HtmlDocument) {
+        if !doc.doctype.is_empty() {
+            self.push_str("<!DOCTYPE ");
+            self.push_str(&doc.doctype);
+            self.push('>');
+        }
+    }
+}
+
+static DEFAULT_OPTIONS: SerializeOptions = SerializeOptions {
+    attribute_order: AttributeOrder::Stored,
+    mode: OutputMode::Html,
+    void_elements: VoidElements::EndTag,
+    escape: true,
+    noscript: NoscriptMode::Markup,
+    indent: 0,
+    max_line_width: None,
+    preserve_whitespace_tags: vec![],
+    skip_attributes: vec![],
+};
+
+/// The options for writing markup which a browser reads back as the same tree,
+/// used by [`HtmlDocument::to_html`] and [`HtmlNode::outer_html`].
+pub(crate) static MARKUP_OPTIONS: SerializeOptions = SerializeOptions {
+    attribute_order: AttributeOrder::Stored,
+    mode: OutputMode::Html,
+    void_elements: VoidElements::StartTag,
+    escape: true,
+    noscript: NoscriptMode::Markup,
+    indent: 0,
+    max_line_width: None,
+    preserve_whitespace_tags: vec![],
+    skip_attributes: vec![],
+};
+
+/// An iterator over the HTML of a document in chunks of bytes, from
+/// [`HtmlDocument::serialize_chunks`].
+pub struct HtmlChunks<'a> {
+    serializer: Serializer<'a>,
+    /// The nodes being written and the index of the next one to write, with the
+    /// tag they are the contents of.
+    stack: Vec<(&'a [HtmlNode], usize, Option<&'a HtmlTag>)>,
+    chunk_size: usize,
+}
+
+impl<'a> HtmlChunks<'a> {
+    /// Writes the next node, or the end tag of the tag it is in, returning false
+    /// if everything has been written.
+    fn write_next(&mut self) -> bool {
+        let Some((nodes, next, tag)) = self.stack.last_mut() else {
+            return false;
+        };
+        match nodes.get(*next) {
+            Some(node) => {
+                *next += 1;
+                match node {
+                    HtmlNode::Tag(t) => {
+                        if self.serializer.write_start_tag(t) {
+                            self.stack.push((&t.contents, 0, Some(t)));
+                        }
+                    }
+                    _ => self.serializer.write_node(node),
+                }
+            }
+            None => {
+                if let Some(t) = tag {
+                    self.serializer.write_end_tag(t);
+                }
+                self.stack.pop();
+            }
+        }
+        true
+    }
+}
+
+impl Iterator for HtmlChunks<'_> {
+    /// The bytes of the chunk, which can be turned into the `Bytes` of the `bytes`
+    /// crate without copying.
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        while self.serializer.out.len() < self.chunk_size && self.write_next() {}
+        let out = &mut self.serializer.out;
+        if out.is_empty() {
+            return None;
+        }
+        let mut chunk = std::mem::take(out).into_bytes();
+        if chunk.len() > self.chunk_size {
+            let rest = chunk.split_off(self.chunk_size);
+            // only the bytes are split, so the rest may start part way through a char
+            match String::from_utf8(rest) {
+                Ok(rest) => *out = rest,
+                Err(e) => {
+                    let rest = e.into_bytes();
+                    let boundary = rest
+                        .iter()
+                        .position(|b| (*b as i8) >= -0x40)
+                        .unwrap_or(rest.len());
+                    chunk.extend_from_slice(&rest[..boundary]);
+                    *out = String::from_utf8(rest[boundary..].to_vec()).unwrap_or_default();
+                }
+            }
+        }
+        Some(chunk)
+    }
+}
+
+impl HtmlDocument {
+    /// Converts the whole document, including the doctype, into a string formatted
+    /// as HTML.
+    pub fn to_html_string(&self) -> String {
+        self.to_html_string_with(&SerializeOptions::default())
+    }
+
+    /// Converts the whole document into HTML as a browser would write it: text and
+    /// attribute values are escaped and void elements such as `<br>` are written
+    /// without an end tag. The text of the document should be as it reads, which
+    /// it is when it was parsed with the default options or added in code, but not
+    /// when it was parsed with
+    /// [`ParseOptions::decode_entities`](crate::options::ParseOptions::decode_entities)
+    /// turned off, as its character references would be escaped again.
+    ///
+    /// ```
+    /// use hb_html::objects::HtmlDocument;
+    /// let source = r#"<p title="&quot;">1 &lt; 2<br></p>"#;
+    /// let doc = source.parse::<HtmlDocument>().unwrap();
+    /// assert_eq!(doc.to_html(), source);
+    /// ```
+    pub fn to_html(&self) -> String {
+        self.to_html_string_with(&MARKUP_OPTIONS)
+    }
+
+    /// Converts the whole document into indented HTML which is easier to read, see
+    /// [`HtmlNode::to_pretty_html`].
+    pub fn to_pretty_html(&self, options: &SerializeOptions) -> String {
+        let mut serializer = Serializer::new(options);
+        serializer.write_doctype(self);
+        for (i, node) in self.nodes.iter().enumerate() {
+            serializer.path.push(i);
+            serializer.write_pretty_node(node, 0);
+            serializer.path.pop();
+        }
+        serializer.finish()
+    }
+
+    /// Converts the whole document into a string formatted as HTML using the
+    /// options.
+    pub fn to_html_string_with(&self, options: &SerializeOptions) -> String {
+        let mut serializer = Serializer::new(options);
+        serializer.write_document(self);
+        serializer.finish()
+    }
+
+    /// Converts the document into a string formatted as HTML, also returning where
+    /// each node of the output came from in the source the document was parsed
+    /// from. The source map is the one returned by
+    /// [`HtmlDocument::parse_with_source_map`] for the source.
+    pub fn to_html_string_with_map(
+        &self,
+        options: &SerializeOptions,
+        source: &str,
+        source_map: &SourceMap,
+    ) -> (String, OutputMap) {
+        let mut serializer = Serializer::with_map(options, source, source_map);
+        serializer.write_document(self);
+        let mappings = std::mem::take(&mut serializer.mappings);
+        (serializer.finish(), OutputMap { mappings })
+    }
+
+    /// Writes the whole document as HTML to the writer using the options, without
+    /// building the output as a string first.
+    pub fn serialize_to<W: io::Write>(
+        &self,
+        writer: W,
+        options: &SerializeOptions,
+    ) -> io::Result<()> {
+        let mut serializer = Serializer::with_writer(
+            options,
+            IoWriter {
+                inner: writer,
+                error: None,
+            },
+        );
+        serializer.write_document(self);
+        match serializer.error {
+            Ok(()) => Ok(()),
+            Err(_) => Err(serializer
+                .out
+                .error
+                .unwrap_or_else(|| io::Error::other("could not write the document"))),
+        }
+    }
+
+    /// Gets the HTML of the whole document as chunks of at least `chunk_size` bytes,
+    /// apart from the last. Each chunk is written when it is taken, so the whole
+    /// output is never held in memory at once. A chunk is only longer than
+    /// `chunk_size` by the rest of a char which it would otherwise split.
+    pub fn serialize_chunks(&self, chunk_size: usize) -> HtmlChunks<'_> {
+        self.serialize_chunks_with(chunk_size, &DEFAULT_OPTIONS)
+    }
+
+    /// Gets the HTML of the whole document as chunks using the options, as
+    /// [`HtmlDocument::serialize_chunks`] does.
+    pub fn serialize_chunks_with<'a>(
+        &'a self,
+        chunk_size: usize,
+        options: &'a SerializeOptions,
+    ) -> HtmlChunks<'a> {
+        let mut serializer = Serializer::new(options);
+        serializer.write_doctype(self);
+        HtmlChunks {
+            serializer,
+            stack: vec![(&self.nodes, 0, None)],
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    /// Writes the whole document as HTML to the writer using the options, as
+    /// [`HtmlDocument::serialize_to`] does for an [`io::Write`].
+    pub fn serialize_to_fmt<W: fmt::Write>(
+        &self,
+        writer: W,
+        options: &SerializeOptions,
+    ) -> fmt::Result {
+        let mut serializer = Serializer::with_writer(options, writer);
+        serializer.write_document(self);
+        serializer.error
+    }
+}
+
+/// Passes text to an [`io::Write`], keeping the io error which a [`fmt::Error`]
+/// can not hold.
+struct IoWriter<W: io::Write> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+#[cfg(test)]
+mod serialize_tests {
+    use super::*;
+
+    #[test]
+    fn document_to_html_string_test() {
+        let source = "<!DOCTYPE html>\n<p id=\"a\">x</p>";
+        let doc = source.parse::<HtmlDocument>().unwrap();
+        // whitespace between top level nodes is not kept by the parser
+        assert_eq!(doc.to_html_string(), "<!DOCTYPE html><p id=\"a\">x</p>");
+        assert_eq!(HtmlDocument::new().to_html_string(), "");
+    }
+
+    #[test]
+    fn attribute_order_test() {
+        let mut tag = HtmlTag::new("input").attributes(vec![
+            ("value", "1"),
+            ("type", "text"),
+            ("name", "n"),
+            ("autofocus", ""),
+        ]);
+        tag.classes = vec!["b".to_owned(), "a".to_owned()];
+        tag.ids.push("x".to_owned());
+        let node = HtmlNode::Tag(tag);
+        let written =
+            |order| node.to_html_string_with(&SerializeOptions::new().attribute_order(order));
+        assert_eq!(
+            written(AttributeOrder::Canonical),
+            r#"<input id="x" class="b a" autofocus name="n" type="text" value="1"></input>"#
+        );
+        assert_eq!(
+            written(AttributeOrder::Alphabetical),
+            r#"<input autofocus class="b a" id="x" name="n" type="text" value="1"></input>"#
+        );
+        // the default keeps id and class first
+        assert!(written(AttributeOrder::Stored).starts_with(r#"<input id="x" class="b a" "#));
+        assert_eq!(written(AttributeOrder::Stored), node.to_html_string());
+    }
+
+    #[test]
+    fn boolean_attributes_test() {
+        assert!(BOOLEAN_ATTRIBUTES.windows(2).all(|w| w[0] < w[1]));
+        assert!(is_boolean_attribute("Disabled"));
+        assert!(!is_boolean_attribute("value"));
+        let tag = HtmlTag::new("option").attributes(vec![
+            ("selected", "selected"),
+            ("disabled", "false"),
+            ("value", ""),
+        ]);
+        let options = SerializeOptions::new().attribute_order(AttributeOrder::Alphabetical);
+        // a boolean attribute with any other value is still true, so the value is kept
+        assert_eq!(
+            HtmlNode::Tag(tag).to_html_string_with(&options),
+            r#"<option disabled="false" selected value=""></option>"#
+        );
+        let tag = "<input hidden required=REQUIRED>"
+            .parse::<HtmlTag>()
+            .unwrap();
+        let written = HtmlNode::Tag(tag).to_html_string_with(&options);
+        assert_eq!(written, "<input hidden required></input>");
+    }
+
+    #[test]
+    fn void_elements_test() {
+        let node = "<p>a<br>b<img src=x.png></p>"
+            .parse::<HtmlTag>()
+            .map(HtmlNode::Tag)
+            .unwrap();
+        let written = |void_elements| {
+            node.to_html_string_with(&SerializeOptions::new().void_elements(void_elements))
+        };
+        assert_eq!(
+            written(VoidElements::EndTag),
+            r#"<p>a<br></br>b<img src="x.png"></img></p>"#
+        );
+        assert_eq!(
+            written(VoidElements::StartTag),
+            r#"<p>a<br>b<img src="x.png"></p>"#
+        );
+        assert_eq!(
+            written(VoidElements::SelfClosing),
+            r#"<p>a<br/>b<img src="x.png"/></p>"#
+        );
+        // other empty elements keep their end tag
+        for name in ["div", "iframe"] {
+            let node = HtmlNode::Tag(HtmlTag::new(name));
+            assert_eq!(
+                node.to_html_string_with(
+                    &SerializeOptions::new().void_elements(VoidElements::SelfClosing)
+                ),
+                format!("<{0}></{0}>", name)
+            );
+        }
+    }
+
+    #[test]
+    fn leading_newline_test() {
+        let contents = |source: &str| match source.parse::<HtmlTag>().unwrap().contents.first() {
+            Some(HtmlNode::Text(t)) => t.clone(),
+            _ => String::new(),
+        };
+        assert_eq!(contents("<pre>\ncode\n</pre>"), "code\n");
+        assert_eq!(contents("<PRE>\r\n  code</PRE>"), "  code");
+        assert_eq!(contents("<pre>\n\ncode</pre>"), "\ncode");
+        assert_eq!(contents("<div>\ncode</div>"), "\ncode");
+        // textarea can only hold text, so tags in it are text
+        assert_eq!(
+            contents("<textarea>\na < b <i>c</i></textarea >"),
+            "a < b <i>c</i>"
+        );
+        assert!("<textarea>a</p>".parse::<HtmlTag>().is_err());
+
+        for source in [
+            "<pre>code</pre>",
+            "<pre>\n\ncode</pre>",
+            "<textarea>\n\n  x</textarea>",
+            "<pre>\n\n<b>x</b></pre>",
+        ] {
+            let node = HtmlNode::Tag(source.parse::<HtmlTag>().unwrap());
+            assert_eq!(node.to_html_string(), source);
+        }
+        // the dropped newline is not written back
+        let node = HtmlNode::Tag("<pre>\ncode</pre>".parse::<HtmlTag>().unwrap());
+        assert_eq!(node.to_html_string(), "<pre>code</pre>");
+    }
+
+    #[test]
+    fn to_html_test() {
+        let mut p = HtmlTag::new("p").attributes(vec![("title", "say \"hi\" & go")]);
+        p.contents = vec![
+            HtmlNode::new_text("1 < 2 & 3 > 2"),
+            HtmlNode::new_tag("br"),
+            HtmlNode::Tag(HtmlTag::new("img").attributes(vec![("src", "a.png?x=1&y=2")])),
+            HtmlNode::Template("{{ a < b }}".to_owned()),
+        ];
+        let mut script = HtmlTag::new("script");
+        script.contents = vec![HtmlNode::new_text("if (a < b && c) {}")];
+        let mut doc = HtmlDocument::new();
+        doc.doctype = "html".to_owned();
+        doc.nodes = vec![HtmlNode::Tag(p), HtmlNode::Tag(script)];
+        assert_eq!(
+            doc.to_html(),
+            concat!(
+                r#"<!DOCTYPE html><p title="say &quot;hi&quot; &amp; go">"#,
+                r#"1 &lt; 2 &amp; 3 &gt; 2<br><img src="a.png?x=1&amp;y=2">{{ a < b }}</p>"#,
+                "<script>if (a < b && c) {}</script>"
+            )
+        );
+        assert_eq!(
+            doc.nodes[0].inner_html(),
+            r#"1 &lt; 2 &amp; 3 &gt; 2<br><img src="a.png?x=1&amp;y=2">{{ a < b }}"#
+        );
+        assert_eq!(doc.nodes[1].inner_html(), "if (a < b && c) {}");
+        assert_eq!(
+            doc.nodes[1].outer_html(),
+            "<script>if (a < b && c) {}</script>"
+        );
+        assert_eq!(HtmlNode::new_text("a<b").outer_html(), "a&lt;b");
+        assert_eq!(HtmlNode::new_text("a<b").inner_html(), "");
+        // the default output is escaped too, unless the text kept its references
+        assert_eq!(
+            HtmlNode::new_text("a &amp; b").to_html_string(),
+            "a &amp;amp; b"
+        );
+        assert_eq!(
+            HtmlNode::new_text("a &amp; b")
+                .to_html_string_with(&SerializeOptions::new().escape(false)),
+            "a &amp; b"
+        );
+
+        // text parsed with the default options is written back as it was written
+        let source = "<p>a &amp; b &lt;script&gt;</p>";
+        let doc = source.parse::<HtmlDocument>().unwrap();
+        assert_eq!(doc.nodes[0].text(), "a & b <script>");
+        assert_eq!(doc.to_html_string(), source);
+        assert_eq!(doc.to_html(), source);
+        assert_eq!(doc.nodes[0].outer_html(), source);
+        assert_eq!(doc.nodes[0].inner_html(), "a &amp; b &lt;script&gt;");
+
+        // the escaped output of decoded text parses back to the same tree
+        let source = r#"<div title="&lt;&quot;&gt;"><p>a &amp;lt; b</p><textarea>&lt;/textarea&gt;&lt;img&gt;</textarea></div>"#;
+        let doc = source.parse::<HtmlDocument>().unwrap();
+        assert_eq!(doc.select("img").unwrap().len(), 0);
+        for output in [doc.to_html(), doc.to_html_string()] {
+            assert_eq!(output.parse::<HtmlDocument>().unwrap(), doc);
+        }
+    }
+
+    #[test]
+    fn pretty_html_test() {
+        let source = "<!DOCTYPE html><html><body><p class=\"intro\">The quick brown fox \
+                      jumps over the lazy dog</p><ul><li>One</li><li></li></ul>\
+                      <textarea>\n\nkeep   this\n</textarea><code>  also   kept </code></body></html>";
+        let doc = source.parse::<HtmlDocument>().unwrap();
+        let options = SerializeOptions::new()
+            .indent(2)
+            .max_line_width(31)
+            .preserve_whitespace_tag("CODE");
+        assert_eq!(
+            doc.to_pretty_html(&options),
+            r#"<!DOCTYPE html>
+<html>
+  <body>
+    <p class="intro">
+      The quick brown fox jumps
+      over the lazy dog
+    </p>
+    <ul>
+      <li>One</li>
+      <li></li>
+    </ul>
+    <textarea>
+
+keep   this
+</textarea>
+    <code>  also   kept </code>
+  </body>
+</html>"#
+        );
+        // without a width text is never wrapped, and without an indent lines are
+        // not indented
+        let p = HtmlNode::Tag("<p>a  b <b>c</b>\n</p>".parse::<HtmlTag>().unwrap());
+        assert_eq!(
+            p.to_pretty_html(&SerializeOptions::new()),
+            "<p>\na b\n<b>c</b>\n</p>"
+        );
+        let options = SerializeOptions::new().indent(1).escape(true);
+        assert_eq!(
+            HtmlNode::new_text("  1 <  2 ").to_pretty_html(&options),
+            "1 &lt; 2"
+        );
+        let options = SerializeOptions::new().indent(1).mode(OutputMode::Xml);
+        assert_eq!(
+            p.to_pretty_html(&options),
+            "<p xmlns=\"http://www.w3.org/1999/xhtml\">\n a b\n <b>c</b>\n</p>"
+        );
+    }
+
+    #[test]
+    fn xml_mode_test() {
+        let source = r#"<html xml:lang="en" xmlns:og="http://ogp.me/ns#"><body><input disabled><svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><a xlink:href="/"></a></svg><og:tag og:x="1"></og:tag></body></html>"#;
+        let doc = source.parse::<HtmlDocument>().unwrap();
+        let options = SerializeOptions::new().attribute_order(AttributeOrder::Alphabetical);
+        // declarations and prefixed attributes are kept in html output
+        assert_eq!(
+            doc.to_html_string_with(&options),
+            source.replace("<input disabled>", "<input disabled></input>")
+        );
+        let xml = doc.to_html_string_with(&options.mode(OutputMode::Xml));
+        assert_eq!(
+            xml,
+            concat!(
+                r#"<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="en" xmlns:og="http://ogp.me/ns#">"#,
+                r#"<body><input disabled="disabled"/>"#,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#,
+                r#"<a xlink:href="/"/></svg><og:tag og:x="1"/></body></html>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn output_map_test() {
+        let source = "<ul>\n<li class=a>One</li>\n<li>Two</li>\n</ul>";
+        let (mut doc, source_map) = HtmlDocument::parse_with_source_map(source).unwrap();
+        let ul = match &mut doc.nodes[0] {
+            HtmlNode::Tag(t) => t,
+            _ => panic!("expected a tag"),
+        };
+        // replace the whitespace between the items with a new element
+        ul.contents[2] = HtmlNode::Tag(HtmlTag::new("hr"));
+        ul.contents[3] = HtmlNode::Text("2".to_owned());
+        let (output, map) =
+            doc.to_html_string_with_map(&SerializeOptions::default(), source, &source_map);
+        assert_eq!(output, doc.nodes[0].to_html_string());
+        let found: Vec<(&[usize], &str, Option<&str>)> = map
+            .iter()
+            .map(|m| {
+                (
+                    m.path.as_slice(),
+                    &output[m.output.clone()],
+                    m.input.clone().map(|r| &source[r]),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (&[0][..], output.as_str(), Some(source)),
+                (&[0, 0][..], "\n", Some("\n")),
+                (
+                    &[0, 1][..],
+                    "<li class=\"a\">One</li>",
+                    Some("<li class=a>One</li>")
+                ),
+                (&[0, 1, 0][..], "One", Some("One")),
+                (&[0, 2][..], "<hr></hr>", None),
+                (&[0, 3][..], "2", None),
+                (&[0, 4][..], "\n", Some("\n")),
+            ]
+        );
+        // inside the new element the closest source node is the list
+        assert_eq!(
+            map.original_at(output.find("<hr>").unwrap()).unwrap().path,
+            vec![0]
+        );
+        assert_eq!(map.original_at(output.len()), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn output_map_json_test() {
+        let source = "<p>a</p>";
+        let (doc, source_map) = HtmlDocument::parse_with_source_map(source).unwrap();
+        let (_, map) =
+            doc.to_html_string_with_map(&SerializeOptions::default(), source, &source_map);
+        assert_eq!(
+            map.to_json(),
+            r#"{"mappings":[{"path":[0],"output":{"start":0,"end":8},"input":{"start":0,"end":8}},{"path":[0,0],"output":{"start":3,"end":4},"input":{"start":3,"end":4}}]}"#
+        );
+    }
+
+    #[test]
+    fn serialize_to_test() {
+        let doc = "<!DOCTYPE html><div id=d><p>Hello</p>é</div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let options = SerializeOptions::new().mode(OutputMode::Xml);
+        let mut out = String::new();
+        doc.serialize_to_fmt(&mut out, &options).unwrap();
+        assert_eq!(out, doc.to_html_string_with(&options));
+
+        // a writer which fails after a few bytes
+        struct Full(usize);
+        impl io::Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0 < buf.len() {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero, "full"));
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let err = doc.serialize_to(Full(20), &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+        assert!(doc.serialize_to(Full(1000), &options).is_ok());
+    }
+
+    #[test]
+    fn serialize_chunks_test() {
+        let doc = "<!DOCTYPE html><div id=d><p>Héllo</p><br><!-- c --></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let expected = doc.to_html_string();
+        for chunk_size in [0, 1, 2, 7, 1000] {
+            let chunks: Vec<Vec<u8>> = doc.serialize_chunks(chunk_size).collect();
+            assert!(chunks
+                .iter()
+                .all(|c| std::str::from_utf8(c).is_ok() && c.len() >= chunk_size.min(1)));
+            assert_eq!(chunks.concat(), expected.as_bytes());
+        }
+        // the é is not split between chunks
+        let mut chunks = doc.serialize_chunks(1);
+        assert!(chunks.any(|c| c == "é".as_bytes()));
+        let options = SerializeOptions::new()
+            .mode(OutputMode::Xml)
+            .void_elements(VoidElements::StartTag);
+        let chunks: Vec<Vec<u8>> = doc.serialize_chunks_with(3, &options).collect();
+        assert_eq!(
+            chunks.concat(),
+            doc.to_html_string_with(&options).into_bytes()
+        );
+        assert!(HtmlDocument::new().serialize_chunks(10).next().is_none());
+    }
+}
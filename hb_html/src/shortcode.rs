@@ -0,0 +1,605 @@
+//! Converting shortcodes and BBCode, such as `[b]bold[/b]` or
+//! `[gallery id=3 size="large"]`, into HTML nodes.
+//!
+//! A [`ShortcodeRegistry`] holds a handler for each tag name, which is given the
+//! parsed [`Shortcode`] and its converted contents and returns the nodes to put in
+//! its place. [`ShortcodeRegistry::bbcode`] has handlers for the common BBCode
+//! tags, and more handlers can be added to it or to an empty registry with
+//! [`ShortcodeRegistry::register`].
+//!
+//! Anything which is not a shortcode of a registered tag is kept as text: unknown
+//! tags, end tags without a start tag and start tags which are never ended, apart
+//! from tags of [`ShortcodeKind::OptionalEnd`], which end at the next tag of the
+//! same name or at the end of the tag they are in. Tag names are compared ignoring
+//! case.
+//!
+//! The text is put in the nodes as it was written, and is escaped when the nodes
+//! are written like the decoded text of a parsed document. Turn off
+//! [`SerializeOptions::escape`] only if the text is known to be HTML.
+//!
+//! [`SerializeOptions::escape`]: crate::serialize::SerializeOptions::escape
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::{HtmlNode, HtmlTag};
+//! use hb_html::shortcode::{ShortcodeKind, ShortcodeRegistry};
+//! let registry = ShortcodeRegistry::bbcode().register("youtube", ShortcodeKind::Verbatim, |code, _| {
+//!     let mut tag = HtmlTag::new("iframe");
+//!     tag.set_attribute("src", format!("https://www.youtube.com/embed/{}", code.source));
+//!     vec![HtmlNode::Tag(tag)]
+//! });
+//! let nodes = registry.convert("[b]Hi[/b] [url=https://example.com]<there>[/url] [youtube]abc[/youtube] [x]");
+//! let html: String = nodes.iter().map(|n| n.to_html_string()).collect();
+//! assert_eq!(
+//!     html,
+//!     concat!(
+//!         r#"<strong>Hi</strong> <a href="https://example.com">&lt;there&gt;</a> "#,
+//!         r#"<iframe src="https://www.youtube.com/embed/abc"></iframe> [x]"#
+//!     )
+//! );
+//! ```
+use crate::objects::{HtmlNode, HtmlTag};
+use crate::sanitize::url_scheme;
+use std::collections::HashMap;
+use std::fmt;
+
+/// How the contents of a shortcode are read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ShortcodeKind {
+    /// The shortcode has contents and an end tag, eg `[b]text[/b]`.
+    #[default]
+    Normal,
+    /// The shortcode has no contents or end tag, eg `[hr]`.
+    Empty,
+    /// The contents are kept as text without looking for shortcodes in them, eg
+    /// `[code][b][/code]`.
+    Verbatim,
+    /// The end tag can be left out, in which case the shortcode ends at the next
+    /// shortcode of the same name or at the end of the shortcode it is in, eg the
+    /// `[*]` items of a `[list]`.
+    OptionalEnd,
+}
+
+/// A shortcode found in the text, which is passed to its handler.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Shortcode {
+    /// The name of the tag in lower case.
+    pub name: String,
+    /// The value given to the tag itself, eg `http://x` for `[url=http://x]`.
+    pub value: Option<String>,
+    /// The other attributes in the order they were written. Attributes without a
+    /// value have an empty value.
+    pub attributes: Vec<(String, String)>,
+    /// The contents as they were written, for handlers of
+    /// [`ShortcodeKind::Verbatim`] shortcodes which want the raw text.
+    pub source: String,
+}
+
+impl Shortcode {
+    /// Gets the value of the attribute with the name.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A function which converts a shortcode and its converted contents into nodes.
+pub type ShortcodeHandler = Box<dyn Fn(&Shortcode, Vec<HtmlNode>) -> Vec<HtmlNode>>;
+
+struct Entry {
+    kind: ShortcodeKind,
+    handler: ShortcodeHandler,
+}
+
+/// The handlers for the shortcodes which are converted, by tag name.
+#[derive(Default)]
+pub struct ShortcodeRegistry {
+    entries: HashMap<String, Entry>,
+    line_breaks: bool,
+}
+
+impl fmt::Debug for ShortcodeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<&String> = self.entries.keys().collect();
+        names.sort();
+        f.debug_struct("ShortcodeRegistry")
+            .field("names", &names)
+            .field("line_breaks", &self.line_breaks)
+            .finish()
+    }
+}
+
+impl ShortcodeRegistry {
+    /// Creates a registry without any handlers.
+    pub fn new() -> ShortcodeRegistry {
+        ShortcodeRegistry::default()
+    }
+
+    /// Creates a registry with handlers for the common BBCode tags: `b`, `i`, `u`,
+    /// `s`, `url`, `img`, `quote`, `code`, `list` with `*` items, `color` and
+    /// `hr`. Links and images with URL schemes other than http, https and mailto
+    /// are left out, keeping the contents of a link. Newlines are converted into
+    /// `<br>` elements.
+    pub fn bbcode() -> ShortcodeRegistry {
+        let wrap = |tag: &'static str| move |_: &Shortcode, contents| element(tag, contents);
+        ShortcodeRegistry::new()
+            .line_breaks(true)
+            .register("b", ShortcodeKind::Normal, wrap("strong"))
+            .register("i", ShortcodeKind::Normal, wrap("em"))
+            .register("u", ShortcodeKind::Normal, wrap("u"))
+            .register("s", ShortcodeKind::Normal, wrap("s"))
+            .register("hr", ShortcodeKind::Empty, wrap("hr"))
+            .register("*", ShortcodeKind::OptionalEnd, wrap("li"))
+            .register("list", ShortcodeKind::Normal, |code, contents| {
+                let tag = match code.value.as_deref() {
+                    Some("1" | "a" | "A" | "i" | "I") => "ol",
+                    _ => "ul",
+                };
+                // only the items are kept, as text can not be put in a list
+                let items = contents
+                    .into_iter()
+                    .filter(|n| matches!(n, HtmlNode::Tag(_)))
+                    .collect();
+                element(tag, items)
+            })
+            .register("code", ShortcodeKind::Verbatim, |code, _| {
+                let inner = element("code", vec![HtmlNode::Text(code.source.clone())]);
+                element("pre", inner)
+            })
+            .register("quote", ShortcodeKind::Normal, |code, mut contents| {
+                if let Some(cite) = &code.value {
+                    let cite = element("cite", vec![HtmlNode::Text(cite.clone())]);
+                    contents.splice(0..0, cite);
+                }
+                element("blockquote", contents)
+            })
+            .register("url", ShortcodeKind::Normal, |code, contents| {
+                let href = code.value.clone().unwrap_or_else(|| code.source.clone());
+                if !is_safe_url(&href) {
+                    return contents;
+                }
+                let mut nodes = element("a", contents);
+                set_attribute(&mut nodes, "href", href.trim());
+                nodes
+            })
+            .register("img", ShortcodeKind::Verbatim, |code, _| {
+                if !is_safe_url(&code.source) {
+                    return vec![];
+                }
+                let mut nodes = element("img", vec![]);
+                set_attribute(&mut nodes, "src", code.source.trim());
+                if let Some(alt) = code.attribute("alt") {
+                    set_attribute(&mut nodes, "alt", alt);
+                }
+                nodes
+            })
+            .register("color", ShortcodeKind::Normal, |code, contents| match code
+                .value
+                .as_deref()
+                .filter(|c| is_safe_color(c))
+            {
+                Some(color) => {
+                    let mut nodes = element("span", contents);
+                    set_attribute(&mut nodes, "style", &format!("color: {}", color));
+                    nodes
+                }
+                None => contents,
+            })
+    }
+
+    /// Converts newlines in the text into `<br>` elements when true.
+    pub fn line_breaks(mut self, line_breaks: bool) -> ShortcodeRegistry {
+        self.line_breaks = line_breaks;
+        self
+    }
+
+    /// Adds the handler for shortcodes with the name, replacing any handler the
+    /// name already has.
+    pub fn register<F>(mut self, name: &str, kind: ShortcodeKind, handler: F) -> ShortcodeRegistry
+    where
+        F: Fn(&Shortcode, Vec<HtmlNode>) -> Vec<HtmlNode> + 'static,
+    {
+        self.entries.insert(
+            name.to_ascii_lowercase(),
+            Entry {
+                kind,
+                handler: Box::new(handler),
+            },
+        );
+        self
+    }
+
+    /// Returns true if there is a handler for the name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(&name.to_ascii_lowercase())
+    }
+
+    /// Converts the text, calling the handlers for the shortcodes in it.
+    pub fn convert(&self, text: &str) -> Vec<HtmlNode> {
+        let mut converter = Converter {
+            registry: self,
+            text,
+            open: vec![Open {
+                code: Shortcode::default(),
+                kind: ShortcodeKind::Normal,
+                tag_start: 0,
+                contents_start: 0,
+                contents: vec![],
+            }],
+        };
+        converter.run();
+        let root = converter.open.pop().unwrap();
+        root.contents
+    }
+}
+
+/// A shortcode which has been started and not yet ended.
+struct Open {
+    code: Shortcode,
+    kind: ShortcodeKind,
+    /// Where the start tag begins in the text.
+    tag_start: usize,
+    /// Where the contents begin in the text.
+    contents_start: usize,
+    contents: Vec<HtmlNode>,
+}
+
+struct Converter<'r, 't> {
+    registry: &'r ShortcodeRegistry,
+    text: &'t str,
+    /// The shortcodes which are open, starting with one for the whole text.
+    open: Vec<Open>,
+}
+
+impl Converter<'_, '_> {
+    fn run(&mut self) {
+        let mut pos = 0;
+        let mut text_start = 0;
+        while let Some(offset) = self.text[pos..].find('[') {
+            let start = pos + offset;
+            pos = start + 1;
+            let Some((tag, len)) = read_tag(&self.text[start..]) else {
+                continue;
+            };
+            let end = start + len;
+            match tag {
+                Tag::Start(code) => {
+                    let Some(entry) = self.registry.entries.get(&code.name) else {
+                        continue;
+                    };
+                    if entry.kind == ShortcodeKind::Verbatim {
+                        // the contents are kept as they are, up to the end tag
+                        let close = format!("[/{}]", code.name);
+                        let Some(i) = find_ignoring_case(&self.text[end..], &close) else {
+                            continue;
+                        };
+                        self.push_text(text_start, start);
+                        self.start(code, entry.kind, start, end);
+                        self.end(self.open.len() - 1, end + i);
+                        pos = end + i + close.len();
+                    } else {
+                        self.push_text(text_start, start);
+                        self.start(code, entry.kind, start, end);
+                        pos = end;
+                    }
+                }
+                Tag::End(name) => match self.open.iter().rposition(|o| o.code.name == name) {
+                    Some(i) if i > 0 => {
+                        self.push_text(text_start, start);
+                        self.end(i, start);
+                        pos = end;
+                    }
+                    _ => continue,
+                },
+            }
+            text_start = pos;
+        }
+        self.push_text(text_start, self.text.len());
+        // shortcodes which were never ended are put back as text
+        while self.open.len() > 1 {
+            let i = self.open.len() - 1;
+            if self.open[i].kind == ShortcodeKind::OptionalEnd {
+                self.end(i, self.text.len());
+            } else {
+                self.unwind();
+            }
+        }
+    }
+
+    fn start(&mut self, code: Shortcode, kind: ShortcodeKind, start: usize, end: usize) {
+        if kind == ShortcodeKind::OptionalEnd {
+            // a new item ends the one before it
+            if let Some(i) = self.open.iter().rposition(|o| o.code.name == code.name) {
+                if i > 0
+                    && self.open[i + 1..]
+                        .iter()
+                        .all(|o| o.kind == ShortcodeKind::OptionalEnd)
+                {
+                    self.end(i, start);
+                }
+            }
+        }
+        let open = Open {
+            code,
+            kind,
+            tag_start: start,
+            contents_start: end,
+            contents: vec![],
+        };
+        if kind == ShortcodeKind::Empty {
+            let nodes = self.call(open);
+            self.top().contents.extend(nodes);
+        } else {
+            self.open.push(open);
+        }
+    }
+
+    /// Ends the open shortcode at i, and the ones inside it, where the end tag
+    /// starts at end.
+    fn end(&mut self, i: usize, end: usize) {
+        while self.open.len() > i + 1 {
+            if self
+                .open
+                .last()
+                .is_some_and(|o| o.kind == ShortcodeKind::OptionalEnd)
+            {
+                let last = self.open.len() - 1;
+                self.end(last, end);
+            } else {
+                self.unwind();
+            }
+        }
+        let mut open = self.open.pop().unwrap();
+        open.code.source = self.text[open.contents_start..end].to_owned();
+        let nodes = self.call(open);
+        self.top().contents.extend(nodes);
+    }
+
+    /// Puts the innermost open shortcode back as its text followed by its contents.
+    fn unwind(&mut self) {
+        let open = self.open.pop().unwrap();
+        let tag = self.text[open.tag_start..open.contents_start].to_owned();
+        self.push_node(HtmlNode::Text(tag));
+        for node in open.contents {
+            self.push_node(node);
+        }
+    }
+
+    fn call(&self, open: Open) -> Vec<HtmlNode> {
+        let entry = &self.registry.entries[&open.code.name];
+        let contents = match open.kind {
+            ShortcodeKind::Verbatim if !open.code.source.is_empty() => {
+                vec![HtmlNode::Text(open.code.source.clone())]
+            }
+            _ => open.contents,
+        };
+        (entry.handler)(&open.code, contents)
+    }
+
+    fn top(&mut self) -> &mut Open {
+        self.open.last_mut().unwrap()
+    }
+
+    fn push_text(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        let text = &self.text[start..end];
+        if !self.registry.line_breaks {
+            self.push_node(HtmlNode::Text(text.to_owned()));
+            return;
+        }
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.push_node(HtmlNode::Tag(HtmlTag::new("br")));
+            }
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if !line.is_empty() {
+                self.push_node(HtmlNode::Text(line.to_owned()));
+            }
+        }
+    }
+
+    /// Adds the node to the innermost open shortcode, joining text nodes.
+    fn push_node(&mut self, node: HtmlNode) {
+        let contents = &mut self.top().contents;
+        match (contents.last_mut(), node) {
+            (Some(HtmlNode::Text(last)), HtmlNode::Text(text)) => last.push_str(&text),
+            (_, node) => contents.push(node),
+        }
+    }
+}
+
+enum Tag {
+    Start(Shortcode),
+    End(String),
+}
+
+/// Reads a start or end tag from the start of the text, giving the tag and the
+/// length of the text it was read from.
+fn read_tag(text: &str) -> Option<(Tag, usize)> {
+    let close = text.find(']')?;
+    let inside = &text[1..close];
+    if let Some(name) = inside.strip_prefix('/') {
+        let name = name.trim();
+        return is_name(name).then(|| (Tag::End(name.to_ascii_lowercase()), close + 1));
+    }
+    let name_end = inside
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(inside.len());
+    let name = &inside[..name_end];
+    if !is_name(name) {
+        return None;
+    }
+    let mut code = Shortcode {
+        name: name.to_ascii_lowercase(),
+        ..Shortcode::default()
+    };
+    let mut rest = &inside[name_end..];
+    if let Some(value) = rest.strip_prefix('=') {
+        let (value, after) = read_value(value)?;
+        code.value = Some(value);
+        rest = after;
+    }
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Some((Tag::Start(code), close + 1));
+        }
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        if !is_name(name) {
+            return None;
+        }
+        rest = &rest[end..];
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let (value, after) = read_value(value)?;
+                rest = after;
+                value
+            }
+            None => String::new(),
+        };
+        code.attributes.push((name.to_owned(), value));
+    }
+}
+
+/// Reads a quoted value or a value up to the next whitespace, giving the value and
+/// the text after it.
+fn read_value(text: &str) -> Option<(String, &str)> {
+    match text.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let end = text[1..].find(quote)? + 1;
+            Some((text[1..end].to_owned(), &text[end + 1..]))
+        }
+        _ => {
+            let end = text.find(char::is_whitespace).unwrap_or(text.len());
+            Some((text[..end].to_owned(), &text[end..]))
+        }
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '*')
+}
+
+fn find_ignoring_case(text: &str, needle: &str) -> Option<usize> {
+    text.char_indices().map(|(i, _)| i).find(|&i| {
+        text[i..]
+            .get(..needle.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(needle))
+    })
+}
+
+fn is_safe_url(url: &str) -> bool {
+    match url_scheme(url) {
+        None => !url.trim().is_empty(),
+        Some(scheme) => matches!(scheme.as_str(), "http" | "https" | "mailto"),
+    }
+}
+
+/// Colours can be a name or a hex colour, which keeps anything else out of the
+/// style attribute.
+fn is_safe_color(color: &str) -> bool {
+    let hex = color.strip_prefix('#');
+    match hex {
+        Some(hex) => matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic()),
+    }
+}
+
+fn element(tag: &str, contents: Vec<HtmlNode>) -> Vec<HtmlNode> {
+    vec![HtmlNode::Tag(HtmlTag::new(tag).contents(contents))]
+}
+
+fn set_attribute(nodes: &mut [HtmlNode], name: &str, value: &str) {
+    if let Some(HtmlNode::Tag(tag)) = nodes.first_mut() {
+        tag.set_attribute(name, value);
+    }
+}
+
+#[cfg(test)]
+mod shortcode_tests {
+    use super::*;
+    use crate::serialize::{AttributeOrder, SerializeOptions};
+
+    fn to_html(nodes: &[HtmlNode]) -> String {
+        let options = SerializeOptions::new()
+            .escape(true)
+            .attribute_order(AttributeOrder::Alphabetical);
+        nodes
+            .iter()
+            .map(|n| n.to_html_string_with(&options))
+            .collect()
+    }
+
+    #[test]
+    fn bbcode_test() {
+        let registry = ShortcodeRegistry::bbcode();
+        let html = to_html(&registry.convert(concat!(
+            "[B]bold [i]both[/I][/b]\n",
+            "[list][*]one[*][u]two[/u]\n[list=1][*]inner[/list][*]three[/list]",
+            "[quote=\"Ann\"]Hi[/quote][code][b]x < y[/code][hr]",
+            "[url]https://a.example[/url][url=javascript:alert(1)]bad[/url]",
+            "[img alt=cat]/cat.png[/img][img]data:x[/img]",
+            "[color=#f00]red[/color][color=red;x]plain[/color]",
+        )));
+        assert_eq!(
+            html,
+            concat!(
+                "<strong>bold <em>both</em></strong><br></br>",
+                "<ul><li>one</li><li><u>two</u><br></br><ol><li>inner</li></ol></li><li>three</li></ul>",
+                "<blockquote><cite>Ann</cite>Hi</blockquote><pre><code>[b]x &lt; y</code></pre><hr></hr>",
+                r#"<a href="https://a.example">https://a.example</a>bad"#,
+                r#"<img alt="cat" src="/cat.png"></img>"#,
+                r#"<span style="color: #f00">red</span>plain"#,
+            )
+        );
+    }
+
+    #[test]
+    fn unmatched_tags_test() {
+        let registry = ShortcodeRegistry::bbcode().line_breaks(false);
+        // tags which are not ended, end tags which were not started and unknown tags
+        // are kept as text
+        let nodes = registry.convert("[b]a [i]b[/b] [/u] [size=3]c[/size] [code]d [b x=\"1]");
+        assert_eq!(
+            to_html(&nodes),
+            "<strong>a [i]b</strong> [/u] [size=3]c[/size] [code]d [b x=\"1]"
+        );
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(to_html(&registry.convert("x\n[*]item")), "x\n<li>item</li>");
+    }
+
+    #[test]
+    fn registry_test() {
+        let registry =
+            ShortcodeRegistry::new().register("Gallery", ShortcodeKind::Empty, |code, contents| {
+                assert!(contents.is_empty());
+                let mut tag = HtmlTag::new("div").classes(vec!["gallery"]);
+                for (name, value) in &code.attributes {
+                    tag.set_attribute(format!("data-{}", name), value.as_str());
+                }
+                vec![HtmlNode::Tag(tag)]
+            });
+        assert!(registry.contains("gallery") && !registry.contains("b"));
+        let nodes = registry.convert(r#"[gallery id=3 size="very large" lazy] [b]x[/b]"#);
+        match &nodes[0] {
+            HtmlNode::Tag(t) => {
+                assert_eq!(t.attributes["data-id"], "3");
+                assert_eq!(t.attributes["data-size"], "very large");
+                assert_eq!(t.attributes["data-lazy"], "");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(nodes[1], HtmlNode::Text(" [b]x[/b]".to_owned()));
+    }
+}
@@ -0,0 +1,216 @@
+//! A string which keeps short text inline instead of allocating.
+//!
+//! Most of the names read while parsing, such as the names of end tags, are only a
+//! few bytes long. A [`SmallString`] keeps up to `N` bytes in place, only moving
+//! to a `String` on the heap when it grows past that, so reading them does not
+//! allocate. It dereferences to `str`, so it can be used wherever a `&str` is
+//! expected.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::small_string::SmallString;
+//! let mut name: SmallString = SmallString::new();
+//! for c in "div".chars() {
+//!     name.push(c);
+//! }
+//! assert!(name.is_inline());
+//! assert_eq!(name, "div");
+//! assert_eq!(name.to_uppercase(), "DIV");
+//! let long = SmallString::<4>::from("blockquote");
+//! assert!(!long.is_inline());
+//! assert_eq!(String::from(long), "blockquote");
+//! ```
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+/// A string which is stored inline while it is at most `N` bytes long.
+#[derive(Clone)]
+pub struct SmallString<const N: usize = 23> {
+    repr: Repr<N>,
+}
+
+#[derive(Clone)]
+enum Repr<const N: usize> {
+    Inline { len: usize, bytes: [u8; N] },
+    Heap(String),
+}
+
+impl<const N: usize> SmallString<N> {
+    pub fn new() -> SmallString<N> {
+        SmallString {
+            repr: Repr::Inline {
+                len: 0,
+                bytes: [0; N],
+            },
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.repr {
+            // only whole chars are copied in, so the bytes are always valid
+            Repr::Inline { len, bytes } => std::str::from_utf8(&bytes[..*len]).unwrap_or_default(),
+            Repr::Heap(s) => s.as_str(),
+        }
+    }
+
+    /// Returns true if the text is stored inline rather than on the heap.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.repr, Repr::Inline { .. })
+    }
+
+    pub fn push(&mut self, c: char) {
+        let mut buf = [0; 4];
+        self.push_str(c.encode_utf8(&mut buf));
+    }
+
+    /// Adds the text to the end, moving to the heap if it no longer fits inline.
+    pub fn push_str(&mut self, s: &str) {
+        match &mut self.repr {
+            Repr::Inline { len, bytes } if *len + s.len() <= N => {
+                bytes[*len..*len + s.len()].copy_from_slice(s.as_bytes());
+                *len += s.len();
+            }
+            Repr::Inline { .. } => {
+                let mut heap = String::with_capacity(self.len() + s.len());
+                heap.push_str(self.as_str());
+                heap.push_str(s);
+                self.repr = Repr::Heap(heap);
+            }
+            Repr::Heap(heap) => heap.push_str(s),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = SmallString::new();
+    }
+}
+
+impl<const N: usize> Default for SmallString<N> {
+    fn default() -> Self {
+        SmallString::new()
+    }
+}
+
+impl<const N: usize> Deref for SmallString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> AsRef<str> for SmallString<N> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> Borrow<str> for SmallString<N> {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> From<&str> for SmallString<N> {
+    fn from(s: &str) -> SmallString<N> {
+        let mut small = SmallString::new();
+        small.push_str(s);
+        small
+    }
+}
+
+impl<const N: usize> From<String> for SmallString<N> {
+    /// Keeps the allocation of a string which is too long to go inline.
+    fn from(s: String) -> SmallString<N> {
+        if s.len() <= N {
+            SmallString::from(s.as_str())
+        } else {
+            SmallString {
+                repr: Repr::Heap(s),
+            }
+        }
+    }
+}
+
+impl<const N: usize> From<SmallString<N>> for String {
+    fn from(s: SmallString<N>) -> String {
+        match s.repr {
+            Repr::Heap(heap) => heap,
+            Repr::Inline { .. } => s.as_str().to_owned(),
+        }
+    }
+}
+
+impl<const N: usize> fmt::Display for SmallString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for SmallString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+// compared and hashed by their text, wherever it is stored
+impl<const N: usize> PartialEq for SmallString<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<const N: usize> Eq for SmallString<N> {}
+
+impl<const N: usize> PartialEq<str> for SmallString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for SmallString<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<const N: usize> PartialEq<String> for SmallString<N> {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const N: usize> Hash for SmallString<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+#[cfg(test)]
+mod small_string_tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn push_test() {
+        let mut s = SmallString::<4>::new();
+        s.push_str("ab");
+        s.push('é');
+        assert!(s.is_inline());
+        assert_eq!(s.len(), 4);
+        // a char which does not fit moves everything to the heap
+        s.push('c');
+        assert!(!s.is_inline());
+        assert_eq!(s, "abéc");
+        s.clear();
+        assert!(s.is_inline() && s.is_empty());
+
+        let mut names = HashSet::new();
+        names.insert(SmallString::<4>::from("a long name".to_owned()));
+        assert!(names.contains("a long name"));
+        assert_eq!(format!("{:?}", SmallString::<4>::from("\"")), "\"\\\"\"");
+    }
+}
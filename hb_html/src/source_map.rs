@@ -0,0 +1,241 @@
+//! Where each node of a parsed document came from in its source.
+//!
+//! [`HtmlDocument::parse_with_source_map`] parses a document in the same way as
+//! [`str::parse`], also returning a [`SourceMap`] which holds the byte range in the
+//! source of every tag, comment and text node, found by its [`NodePath`].
+//! [`SourceMap::span`] also gives the line and column the node starts at, eg for
+//! the messages of a linter which walks the document with
+//! [`crate::visit`] and looks up each node by the path of its
+//! [`VisitContext`](crate::visit::VisitContext).
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! let source = "<div id=main><p>Hi <b>there</b></p></div>";
+//! let (doc, map) = HtmlDocument::parse_with_source_map(source).unwrap();
+//! let b = map.get(&[0, 0, 1]).unwrap();
+//! assert_eq!(&source[b.range.clone()], "<b>there</b>");
+//! assert_eq!(&source[b.content.clone().unwrap()], "there");
+//! assert_eq!(map.node_at(23).unwrap().path, vec![0, 0, 1, 0]);
+//! let text = map.span(&[0, 0, 1, 0]).unwrap();
+//! assert_eq!((text.start, text.end, text.line, text.col), (22, 27, 1, 23));
+//! ```
+use crate::error::ParseHtmlError;
+use crate::objects::{parse_document, HtmlDocument, NodePath};
+use crate::options::ParseOptions;
+use crate::parsing::SpanRecorder;
+use std::ops::Range;
+
+/// The location of a node in the source of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeSpan {
+    pub path: NodePath,
+    /// The byte range of the whole node, from the `<` of the start tag to the `>`
+    /// of the end tag.
+    pub range: Range<usize>,
+    /// The byte range between the start and end tags of an element, or None for
+    /// comments, text and elements without an end tag (eg `<br>`).
+    pub content: Option<Range<usize>>,
+}
+
+/// Where a node or token is in its source: its byte range and the line and
+/// column of its first char, both starting from 1 with the column counted in
+/// chars as [`ParseHtmlError::line_col`] does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Finds the lines and columns of offsets in a source. Each offset is counted on
+/// from the one before, so finding them in order reads the source once.
+pub(crate) struct LineCounter<'a> {
+    source: &'a str,
+    offset: usize,
+    line: usize,
+    line_start: usize,
+    col: usize,
+}
+
+impl<'a> LineCounter<'a> {
+    pub(crate) fn new(source: &'a str) -> LineCounter<'a> {
+        LineCounter {
+            source,
+            offset: 0,
+            line: 1,
+            line_start: 0,
+            col: 1,
+        }
+    }
+
+    pub(crate) fn span(&mut self, range: Range<usize>) -> Span {
+        if range.start < self.offset {
+            *self = LineCounter::new(self.source);
+        }
+        let skipped = &self.source[self.offset..range.start];
+        match skipped.rfind('\n') {
+            Some(i) => {
+                self.line += skipped.matches('\n').count();
+                self.line_start = self.offset + i + 1;
+                self.col = self.source[self.line_start..range.start].chars().count() + 1;
+            }
+            None => self.col += skipped.chars().count(),
+        }
+        self.offset = range.start;
+        Span {
+            start: range.start,
+            end: range.end,
+            line: self.line,
+            col: self.col,
+        }
+    }
+}
+
+/// The spans of all of the nodes of a document, in document order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    spans: Vec<NodeSpan>,
+    /// The line and column of the start of each span.
+    positions: Vec<(usize, usize)>,
+}
+
+impl SourceMap {
+    pub(crate) fn from_recorder(recorder: SpanRecorder, source: &str) -> SourceMap {
+        let mut spans = recorder.spans;
+        // nodes are recorded when they end, so children come before their parents
+        spans.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut lines = LineCounter::new(source);
+        let positions = spans
+            .iter()
+            .map(|s| {
+                let span = lines.span(s.range.clone());
+                (span.line, span.col)
+            })
+            .collect();
+        SourceMap { spans, positions }
+    }
+
+    /// Gets where the node at the path is in the source, with its line and column.
+    pub fn span(&self, path: &[usize]) -> Option<Span> {
+        let i = self
+            .spans
+            .binary_search_by(|s| s.path.as_slice().cmp(path))
+            .ok()?;
+        let (line, col) = self.positions[i];
+        Some(Span {
+            start: self.spans[i].range.start,
+            end: self.spans[i].range.end,
+            line,
+            col,
+        })
+    }
+
+    pub fn get(&self, path: &[usize]) -> Option<&NodeSpan> {
+        self.spans
+            .binary_search_by(|s| s.path.as_slice().cmp(path))
+            .ok()
+            .map(|i| &self.spans[i])
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, NodeSpan> {
+        self.spans.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Gets the innermost node whose span contains the byte offset.
+    pub fn node_at(&self, offset: usize) -> Option<&NodeSpan> {
+        self.spans
+            .iter()
+            .filter(|s| s.range.contains(&offset))
+            .max_by_key(|s| s.path.len())
+    }
+}
+
+impl<'a> IntoIterator for &'a SourceMap {
+    type Item = &'a NodeSpan;
+    type IntoIter = std::slice::Iter<'a, NodeSpan>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.spans.iter()
+    }
+}
+
+impl HtmlDocument {
+    /// Parses the document, also returning where each of its nodes is in the source.
+    pub fn parse_with_source_map(
+        html_str: &str,
+    ) -> Result<(HtmlDocument, SourceMap), ParseHtmlError> {
+        let mut recorder = SpanRecorder::new(html_str);
+        let doc = parse_document(html_str, &ParseOptions::default(), Some(&mut recorder))?;
+        Ok((doc, SourceMap::from_recorder(recorder, html_str)))
+    }
+}
+
+#[cfg(test)]
+mod source_map_tests {
+    use super::*;
+
+    #[test]
+    fn source_map_nested_test() {
+        let source = "<!DOCTYPE html><!-- c --><ul class=x>\n <li>a<br>b</li><li /></ul>";
+        let (doc, map) = HtmlDocument::parse_with_source_map(source).unwrap();
+        assert_eq!(doc, source.parse::<HtmlDocument>().unwrap());
+        let found: Vec<(&[usize], &str)> = map
+            .iter()
+            .map(|s| (s.path.as_slice(), &source[s.range.clone()]))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (&[0][..], "<!-- c -->"),
+                (&[1][..], "<ul class=x>\n <li>a<br>b</li><li /></ul>"),
+                (&[1, 0][..], "\n "),
+                (&[1, 1][..], "<li>a<br>b</li>"),
+                (&[1, 1, 0][..], "a"),
+                (&[1, 1, 1][..], "<br>"),
+                (&[1, 1, 2][..], "b"),
+                (&[1, 2][..], "<li />"),
+            ]
+        );
+        let ul = map.get(&[1]).unwrap();
+        assert_eq!(
+            &source[ul.content.clone().unwrap()],
+            "\n <li>a<br>b</li><li />"
+        );
+        assert_eq!(map.get(&[1, 1, 1]).unwrap().content, None);
+        assert_eq!(map.get(&[1, 2]).unwrap().content, None);
+        assert_eq!(map.get(&[2]), None);
+        assert_eq!(map.node_at(0), None);
+        assert_eq!(map.node_at(44).unwrap().path, vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn span_line_col_test() {
+        let source = "<div>\n  <p>café <b>x</b></p>\r\n<i>é</i></div>";
+        let (_, map) = HtmlDocument::parse_with_source_map(source).unwrap();
+        let position = |path: &[usize]| {
+            let span = map.span(path).unwrap();
+            assert_eq!(span.start, map.get(path).unwrap().range.start);
+            assert_eq!(span.end, map.get(path).unwrap().range.end);
+            (span.line, span.col)
+        };
+        assert_eq!(position(&[0]), (1, 1));
+        assert_eq!(position(&[0, 0]), (1, 6));
+        assert_eq!(position(&[0, 1]), (2, 3));
+        // columns are counted in chars, not bytes
+        assert_eq!(position(&[0, 1, 1]), (2, 11));
+        assert_eq!(position(&[0, 1, 1, 0]), (2, 14));
+        assert_eq!(position(&[0, 3]), (3, 1));
+        assert_eq!(position(&[0, 3, 0]), (3, 4));
+        assert_eq!(map.span(&[0, 4]), None);
+    }
+}
@@ -0,0 +1,395 @@
+//! Parsing of the `srcset` and `sizes` attributes and choosing the image a browser
+//! would load from an `<img>` or `<picture>` element.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::media::Viewport;
+//! use hb_html::objects::HtmlTag;
+//! let picture = r#"<picture>
+//! <source media="(max-width: 600px)" srcset="small.webp 1x, small@2x.webp 2x" type="image/webp">
+//! <img src="large.jpg" srcset="medium.jpg 800w, large.jpg 1600w" sizes="(max-width: 1000px) 100vw, 50vw">
+//! </picture>"#
+//!     .parse::<HtmlTag>()
+//!     .unwrap();
+//! let phone = Viewport::new(400.0, 800.0).device_pixel_ratio(2.0);
+//! assert_eq!(picture.resolve_image(&phone), Some("small@2x.webp".to_owned()));
+//! let laptop = Viewport::new(1200.0, 800.0);
+//! assert_eq!(picture.resolve_image(&laptop), Some("medium.jpg".to_owned()));
+//! ```
+use std::ops::Range;
+
+use crate::error::ParseHtmlError;
+use crate::media::{Length, LengthUnit, MediaCondition, MediaQueryList, Viewport};
+use crate::objects::{HtmlNode, HtmlTag};
+
+/// The image types which are treated as supported when checking the `type` of a
+/// `<source>` element.
+pub const SUPPORTED_IMAGE_TYPES: [&str; 8] = [
+    "image/apng",
+    "image/avif",
+    "image/bmp",
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/svg+xml",
+    "image/webp",
+];
+
+/// The descriptor after a URL in a `srcset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageDescriptor {
+    /// The pixel density, eg `2x`. A candidate without a descriptor is `1x`.
+    Density(f64),
+    /// The width of the image in pixels, eg `800w`.
+    Width(u32),
+}
+
+/// One of the images in a `srcset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageCandidate {
+    pub url: String,
+    pub descriptor: ImageDescriptor,
+}
+
+impl ImageCandidate {
+    /// The density of the image when it is displayed at the size provided (in CSS pixels).
+    pub fn density(&self, source_size: f64) -> f64 {
+        match self.descriptor {
+            ImageDescriptor::Density(d) => d,
+            ImageDescriptor::Width(w) => w as f64 / source_size,
+        }
+    }
+}
+
+fn parse_descriptor(descriptor: &str) -> Result<ImageDescriptor, ParseHtmlError> {
+    let invalid =
+        || ParseHtmlError::invalid_value(format!("invalid srcset descriptor '{}'", descriptor));
+    let (number, kind) = descriptor.split_at(descriptor.len().saturating_sub(1));
+    match kind {
+        "x" => match number.parse::<f64>() {
+            Ok(d) if d > 0.0 && d.is_finite() => Ok(ImageDescriptor::Density(d)),
+            _ => Err(invalid()),
+        },
+        "w" => match number.parse::<u32>() {
+            Ok(w) if w > 0 => Ok(ImageDescriptor::Width(w)),
+            _ => Err(invalid()),
+        },
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses the value of a `srcset` attribute. URLs may contain commas, so a comma
+/// only separates candidates when it follows whitespace or a descriptor.
+pub fn parse_srcset(srcset: &str) -> Result<Vec<ImageCandidate>, ParseHtmlError> {
+    let mut candidates = vec![];
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+        let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (url, after) = rest.split_at(url_end);
+        rest = after;
+        let descriptors = if url.ends_with(',') {
+            vec![]
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let (descriptors, after) = rest.split_at(end);
+            rest = after;
+            descriptors.split_whitespace().collect()
+        };
+        let url = url.trim_end_matches(',');
+        let descriptor = match descriptors.as_slice() {
+            [] => ImageDescriptor::Density(1.0),
+            [d] => parse_descriptor(d)?,
+            // a height descriptor is only allowed alongside a width
+            [w, h] if h.ends_with('h') => match parse_descriptor(w)? {
+                ImageDescriptor::Width(w) => ImageDescriptor::Width(w),
+                _ => {
+                    return Err(ParseHtmlError::invalid_value(format!(
+                        "a height descriptor must follow a width descriptor for '{}'",
+                        url
+                    )))
+                }
+            },
+            _ => {
+                return Err(ParseHtmlError::invalid_value(format!(
+                    "too many descriptors for '{}' in srcset",
+                    url
+                )))
+            }
+        };
+        candidates.push(ImageCandidate {
+            url: url.to_owned(),
+            descriptor,
+        });
+    }
+    Ok(candidates)
+}
+
+/// Gets where the URLs in the value of a `srcset` attribute are, using the same
+/// splitting as [parse_srcset] but without checking the descriptors.
+pub(crate) fn srcset_url_ranges(srcset: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut offset = 0;
+    loop {
+        let rest = &srcset[offset..];
+        offset += rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_whitespace() || c == ',')
+                .len();
+        let rest = &srcset[offset..];
+        if rest.is_empty() {
+            break;
+        }
+        let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let url = &rest[..url_end];
+        let trimmed = url.trim_end_matches(',');
+        ranges.push(offset..offset + trimmed.len());
+        offset += url_end;
+        if !url.ends_with(',') {
+            offset += srcset[offset..].find(',').unwrap_or(srcset.len() - offset);
+        }
+    }
+    ranges
+}
+
+/// Works out the size (in CSS pixels) the image will be displayed at from the
+/// value of a `sizes` attribute. Invalid entries are skipped and the default is 100vw.
+pub fn source_size(sizes: &str, viewport: &Viewport) -> f64 {
+    for entry in sizes.split(',') {
+        let entry = entry.trim();
+        let (condition, length) = match entry.rfind(|c: char| c.is_whitespace() || c == ')') {
+            Some(i) => entry.split_at(i + 1),
+            None => ("", entry),
+        };
+        let length = match length.trim().parse::<Length>() {
+            Ok(l) => l,
+            Err(_) => continue,
+        };
+        let condition = condition.trim();
+        if condition.is_empty() {
+            return length.to_px(viewport);
+        }
+        match condition.parse::<MediaCondition>() {
+            Ok(c) if c.evaluate(viewport) => return length.to_px(viewport),
+            _ => continue,
+        }
+    }
+    Length::new(100.0, LengthUnit::Vw).to_px(viewport)
+}
+
+/// Chooses the candidate with the lowest density which is at least the device pixel
+/// ratio, or the highest density if none are dense enough.
+pub fn choose_candidate<'a>(
+    candidates: &'a [ImageCandidate],
+    source_size: f64,
+    viewport: &Viewport,
+) -> Option<&'a ImageCandidate> {
+    let mut best: Option<(&ImageCandidate, f64)> = None;
+    for candidate in candidates {
+        let density = candidate.density(source_size);
+        best = match best {
+            None => Some((candidate, density)),
+            Some((b, best_density)) => {
+                let better = if best_density >= viewport.device_pixel_ratio {
+                    density >= viewport.device_pixel_ratio && density < best_density
+                } else {
+                    density > best_density
+                };
+                if better {
+                    Some((candidate, density))
+                } else {
+                    Some((b, best_density))
+                }
+            }
+        };
+    }
+    best.map(|(c, _)| c)
+}
+
+impl HtmlTag {
+    /// Chooses the image from the srcset (and src) of the tag for the viewport,
+    /// returning None if there is no valid candidate.
+    fn resolve_srcset(&self, viewport: &Viewport) -> Option<String> {
+        // an invalid srcset is ignored so the src can still be used
+        let mut candidates = match self.get_attribute("srcset") {
+            Some(srcset) => parse_srcset(&srcset).unwrap_or_default(),
+            None => vec![],
+        };
+        // the src is only a candidate if the srcset does not already cover 1x
+        let covers_1x = candidates.iter().any(|c| match c.descriptor {
+            ImageDescriptor::Density(d) => d == 1.0,
+            ImageDescriptor::Width(_) => true,
+        });
+        if let Some(src) = self.get_attribute("src") {
+            if !src.is_empty() && !covers_1x {
+                candidates.push(ImageCandidate {
+                    url: src,
+                    descriptor: ImageDescriptor::Density(1.0),
+                });
+            }
+        }
+        let size = match self.get_attribute("sizes") {
+            Some(sizes) => source_size(&sizes, viewport),
+            None => viewport.width,
+        };
+        choose_candidate(&candidates, size, viewport).map(|c| c.url.clone())
+    }
+
+    fn source_matches(&self, viewport: &Viewport) -> bool {
+        if let Some(media) = self.get_attribute("media") {
+            match media.parse::<MediaQueryList>() {
+                Ok(m) if m.evaluate(viewport) => {}
+                _ => return false,
+            }
+        }
+        match self.get_attribute("type") {
+            Some(t) => SUPPORTED_IMAGE_TYPES.contains(&t.trim().to_ascii_lowercase().as_str()),
+            None => true,
+        }
+    }
+
+    /// Gets the URL of the image a browser would load for an `<img>` or `<picture>`
+    /// element. For a picture the first `<source>` with a matching media query and a
+    /// supported type is used, falling back to the `<img>` inside the picture.
+    /// URLs are returned as they are written in the document.
+    pub fn resolve_image(&self, viewport: &Viewport) -> Option<String> {
+        match self.tag.as_str() {
+            "img" => self.resolve_srcset(viewport),
+            "picture" => {
+                for node in &self.contents {
+                    let tag = match node {
+                        HtmlNode::Tag(t) => t,
+                        _ => continue,
+                    };
+                    if tag.tag == "source" && tag.source_matches(viewport) {
+                        if let Some(url) = tag.resolve_srcset(viewport) {
+                            return Some(url);
+                        }
+                    } else if tag.tag == "img" {
+                        return tag.resolve_srcset(viewport);
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod srcset_tests {
+    use super::*;
+
+    fn candidate(url: &str, descriptor: ImageDescriptor) -> ImageCandidate {
+        ImageCandidate {
+            url: url.to_owned(),
+            descriptor,
+        }
+    }
+
+    #[test]
+    fn parse_srcset_test() {
+        assert_eq!(
+            parse_srcset(" a.jpg, b.jpg 2x,c.jpg 1.5x ,").unwrap(),
+            vec![
+                candidate("a.jpg", ImageDescriptor::Density(1.0)),
+                candidate("b.jpg", ImageDescriptor::Density(2.0)),
+                candidate("c.jpg", ImageDescriptor::Density(1.5)),
+            ]
+        );
+        assert_eq!(
+            parse_srcset("img.php?a=1,2 100w 50h, data:image/png,abc,").unwrap(),
+            vec![
+                candidate("img.php?a=1,2", ImageDescriptor::Width(100)),
+                candidate("data:image/png,abc", ImageDescriptor::Density(1.0)),
+            ]
+        );
+        assert_eq!(parse_srcset("").unwrap(), vec![]);
+        for invalid in [
+            "a.jpg 2",
+            "a.jpg 0x",
+            "a.jpg -1w",
+            "a.jpg 2x 3x",
+            "a.jpg 2x 50h",
+        ] {
+            assert!(
+                parse_srcset(invalid).is_err(),
+                "{} should not parse",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn source_size_test() {
+        let viewport = Viewport::new(800.0, 600.0);
+        let sizes = "(max-width: 600px) 100vw, (max-width: 1000px) 50vw, 300px";
+        assert_eq!(source_size(sizes, &viewport), 400.0);
+        assert_eq!(source_size(sizes, &Viewport::new(500.0, 600.0)), 500.0);
+        assert_eq!(source_size(sizes, &Viewport::new(1500.0, 600.0)), 300.0);
+        assert_eq!(source_size("calc(100vw - 2em), 20em", &viewport), 320.0);
+        assert_eq!(source_size("", &viewport), 800.0);
+    }
+
+    #[test]
+    fn choose_candidate_test() {
+        let candidates = parse_srcset("a 400w, b 800w, c 1600w").unwrap();
+        let choose = |size: f64, dpr: f64| {
+            let viewport = Viewport::new(1000.0, 1000.0).device_pixel_ratio(dpr);
+            choose_candidate(&candidates, size, &viewport)
+                .unwrap()
+                .url
+                .clone()
+        };
+        assert_eq!(choose(400.0, 1.0), "a");
+        assert_eq!(choose(400.0, 2.0), "b");
+        assert_eq!(choose(500.0, 1.0), "b");
+        assert_eq!(choose(1000.0, 3.0), "c");
+        assert_eq!(
+            choose_candidate(&[], 100.0, &Viewport::new(100.0, 100.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_image_test() {
+        let img = r#"<img src="a.jpg" srcset="a@2x.jpg 2x">"#.parse::<HtmlTag>().unwrap();
+        let viewport = Viewport::new(800.0, 600.0);
+        assert_eq!(img.resolve_image(&viewport), Some("a.jpg".to_owned()));
+        assert_eq!(
+            img.resolve_image(&viewport.clone().device_pixel_ratio(2.0)),
+            Some("a@2x.jpg".to_owned())
+        );
+
+        let invalid = r#"<img src="a.jpg" srcset="b.jpg 2q">"#.parse::<HtmlTag>().unwrap();
+        assert_eq!(invalid.resolve_image(&viewport), Some("a.jpg".to_owned()));
+
+        let picture = r#"<picture><source srcset="a.jxl" type="image/jxl"><source media="print" srcset="print.png"><source srcset="a.avif" type="image/avif"><img src="a.jpg"></picture>"#
+            .parse::<HtmlTag>()
+            .unwrap();
+        assert_eq!(picture.resolve_image(&viewport), Some("a.avif".to_owned()));
+        assert_eq!(
+            picture.resolve_image(&viewport.clone().media_type("print")),
+            Some("print.png".to_owned())
+        );
+
+        let fallback = r#"<picture><source media="(min-width: 2000px)" srcset="big.jpg"><img src="small.jpg"></picture>"#
+            .parse::<HtmlTag>()
+            .unwrap();
+        assert_eq!(
+            fallback.resolve_image(&viewport),
+            Some("small.jpg".to_owned())
+        );
+        assert_eq!(
+            "<div></div>"
+                .parse::<HtmlTag>()
+                .unwrap()
+                .resolve_image(&viewport),
+            None
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Subresource Integrity for the scripts and stylesheets of a document.
+//!
+//! [`AddIntegrity`] calls a resolver with the URL of each `<script src>`,
+//! `<link rel=stylesheet>` and `<link rel=modulepreload>` to get the bytes of the
+//! resource, then writes their digests to the `integrity` attribute so browsers
+//! refuse to run a resource which has been changed. A `crossorigin` attribute is
+//! also added, as browsers only check the integrity of cross origin resources
+//! which are fetched with CORS.
+//!
+//! Existing `integrity` attributes are kept unless [`AddIntegrity::overwrite`] is
+//! set, and existing `crossorigin` attributes are always kept.
+//!
+//! This module needs the `sri` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::serialize::{AttributeOrder, SerializeOptions};
+//! use hb_html::sri::AddIntegrity;
+//! let mut doc = r#"<script src="/hello.js"></script>"#.parse::<HtmlDocument>().unwrap();
+//! let report = AddIntegrity::new(|url| match url {
+//!     "/hello.js" => Some(b"alert('Hello, world.');".to_vec()),
+//!     _ => None,
+//! })
+//! .add(&mut doc);
+//! assert_eq!(report.added, 1);
+//! let options = SerializeOptions::new().attribute_order(AttributeOrder::Alphabetical);
+//! assert_eq!(
+//!     doc.to_html_string_with(&options),
+//!     concat!(
+//!         r#"<script crossorigin="anonymous" "#,
+//!         r#"integrity="sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO" "#,
+//!         r#"src="/hello.js"></script>"#
+//!     )
+//! );
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlTag, NodePath};
+use crate::pipeline::{Pass, PassContext};
+use crate::visit::{Visit, VisitAction, VisitContext};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::HashMap;
+
+/// The hash functions which can be used for integrity metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SriAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl SriAlgorithm {
+    /// The prefix of the integrity metadata, eg `sha384`.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            SriAlgorithm::Sha256 => "sha256",
+            SriAlgorithm::Sha384 => "sha384",
+            SriAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// Gets the base64 encoded digest of the bytes.
+    pub fn digest(&self, bytes: &[u8]) -> String {
+        match self {
+            SriAlgorithm::Sha256 => STANDARD.encode(Sha256::digest(bytes)),
+            SriAlgorithm::Sha384 => STANDARD.encode(Sha384::digest(bytes)),
+            SriAlgorithm::Sha512 => STANDARD.encode(Sha512::digest(bytes)),
+        }
+    }
+}
+
+/// Gets the integrity metadata for the bytes, eg `sha384-<base64 digest>`.
+pub fn integrity_value(bytes: &[u8], algorithm: SriAlgorithm) -> String {
+    format!("{}-{}", algorithm.prefix(), algorithm.digest(bytes))
+}
+
+/// What [`AddIntegrity::add`] changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// The number of elements which were given an `integrity` attribute.
+    pub added: usize,
+    /// The elements whose URL the resolver could not get the bytes of, with the URL.
+    pub unresolved: Vec<(NodePath, String)>,
+}
+
+/// A [`Pass`] which adds `integrity` and `crossorigin` attributes to scripts and
+/// stylesheets using a resolver which gets the bytes of a URL.
+pub struct AddIntegrity<F> {
+    resolver: F,
+    /// The algorithms used, the digests are written in this order.
+    pub algorithms: Vec<SriAlgorithm>,
+    /// The value written to `crossorigin` when there is not one already, or None to
+    /// leave it out.
+    pub crossorigin: Option<String>,
+    /// Whether existing `integrity` attributes are replaced.
+    pub overwrite: bool,
+    digests: HashMap<String, Option<String>>,
+}
+
+impl<F: FnMut(&str) -> Option<Vec<u8>>> AddIntegrity<F> {
+    /// Creates the pass using SHA-384 and `crossorigin="anonymous"`. The resolver
+    /// returns None for URLs it cannot get the bytes of.
+    pub fn new(resolver: F) -> AddIntegrity<F> {
+        AddIntegrity {
+            resolver,
+            algorithms: vec![SriAlgorithm::Sha384],
+            crossorigin: Some("anonymous".to_owned()),
+            overwrite: false,
+            digests: HashMap::new(),
+        }
+    }
+
+    /// Sets the algorithms used. A browser checks the strongest one it supports.
+    pub fn algorithms(mut self, algorithms: Vec<SriAlgorithm>) -> AddIntegrity<F> {
+        self.algorithms = algorithms;
+        self
+    }
+
+    pub fn crossorigin<T: Into<String>>(mut self, crossorigin: Option<T>) -> AddIntegrity<F> {
+        self.crossorigin = crossorigin.map(|c| c.into());
+        self
+    }
+
+    pub fn overwrite(mut self, overwrite: bool) -> AddIntegrity<F> {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Gets the integrity metadata for the URL, calling the resolver once per URL.
+    pub fn integrity_for(&mut self, url: &str) -> Option<String> {
+        if let Some(value) = self.digests.get(url) {
+            return value.clone();
+        }
+        let value = (self.resolver)(url).map(|bytes| {
+            self.algorithms
+                .iter()
+                .map(|a| integrity_value(&bytes, *a))
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+        self.digests.insert(url.to_owned(), value.clone());
+        value
+    }
+
+    /// Adds the attributes to the scripts and stylesheets of the document.
+    pub fn add(&mut self, doc: &mut HtmlDocument) -> IntegrityReport {
+        let mut visitor = IntegrityVisitor {
+            pass: self,
+            report: IntegrityReport::default(),
+        };
+        doc.visit(&mut visitor);
+        visitor.report
+    }
+}
+
+/// Gets the URL of the resource the tag loads, if its integrity can be checked.
+fn resource_url(tag: &HtmlTag) -> Option<&str> {
+    match tag.tag.to_ascii_lowercase().as_str() {
+        "script" => tag.attributes.get("src").map(|s| s.as_str()),
+        "link" => {
+            let rel = tag.attributes.get("rel")?.to_ascii_lowercase();
+            rel.split_ascii_whitespace()
+                .any(|r| r == "stylesheet" || r == "modulepreload")
+                .then(|| tag.attributes.get("href").map(|h| h.as_str()))?
+        }
+        _ => None,
+    }
+}
+
+struct IntegrityVisitor<'a, F> {
+    pass: &'a mut AddIntegrity<F>,
+    report: IntegrityReport,
+}
+
+impl<F: FnMut(&str) -> Option<Vec<u8>>> Visit for IntegrityVisitor<'_, F> {
+    fn enter_element(&mut self, tag: &mut HtmlTag, ctx: &VisitContext) -> VisitAction {
+        let url = match resource_url(tag) {
+            Some(url) => url.to_owned(),
+            None => return VisitAction::Continue,
+        };
+        if tag.attributes.contains_key("integrity") && !self.pass.overwrite {
+            return VisitAction::Continue;
+        }
+        match self.pass.integrity_for(&url) {
+            Some(integrity) => {
+                tag.attributes.insert("integrity".to_owned(), integrity);
+                if let Some(crossorigin) = &self.pass.crossorigin {
+                    if !tag.attributes.contains_key("crossorigin") {
+                        tag.attributes
+                            .insert("crossorigin".to_owned(), crossorigin.clone());
+                    }
+                }
+                self.report.added += 1;
+            }
+            None => self.report.unresolved.push((ctx.path.clone(), url)),
+        }
+        VisitAction::Continue
+    }
+}
+
+impl<F: FnMut(&str) -> Option<Vec<u8>>> Pass for AddIntegrity<F> {
+    fn name(&self) -> &str {
+        "subresource-integrity"
+    }
+
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError> {
+        let report = self.add(doc);
+        ctx.info(
+            format!("added integrity to {} elements", report.added),
+            None,
+        );
+        for (path, url) in report.unresolved {
+            ctx.warning(format!("could not resolve {}", url), Some(path));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod sri_tests {
+    use super::*;
+    use crate::objects::HtmlNode;
+    use crate::pipeline::Pipeline;
+
+    #[test]
+    fn integrity_value_test() {
+        assert_eq!(
+            integrity_value(b"", SriAlgorithm::Sha256),
+            "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+        assert!(integrity_value(b"x", SriAlgorithm::Sha512).starts_with("sha512-"));
+        // 64 bytes of digest is 88 characters of base64
+        assert_eq!(SriAlgorithm::Sha512.digest(b"x").len(), 88);
+    }
+
+    fn attribute(doc: &HtmlDocument, path: &[usize], name: &str) -> Option<String> {
+        match doc.node_at(path) {
+            Some(HtmlNode::Tag(t)) => t.get_attribute(name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn add_integrity_test() {
+        let mut doc = concat!(
+            r#"<head><link rel="stylesheet" href="/a.css"><link rel="icon" href="/i.png">"#,
+            r#"<script src="/a.js" crossorigin="use-credentials"></script>"#,
+            r#"<script src="/old.js" integrity="sha256-old"></script>"#,
+            r#"<script src="/missing.js"></script><script>inline()</script>"#,
+            r#"<script src="/a.js"></script></head>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let mut calls = vec![];
+        let report = AddIntegrity::new(|url| {
+            calls.push(url.to_owned());
+            (url != "/missing.js").then(|| url.as_bytes().to_vec())
+        })
+        .algorithms(vec![SriAlgorithm::Sha256, SriAlgorithm::Sha512])
+        .add(&mut doc);
+        assert_eq!(report.added, 3);
+        assert_eq!(
+            report.unresolved,
+            vec![(vec![0, 4], "/missing.js".to_owned())]
+        );
+        // each url is only resolved once
+        assert_eq!(calls, vec!["/a.css", "/a.js", "/missing.js"]);
+        assert_eq!(
+            attribute(&doc, &[0, 0], "integrity").unwrap(),
+            format!(
+                "{} {}",
+                integrity_value(b"/a.css", SriAlgorithm::Sha256),
+                integrity_value(b"/a.css", SriAlgorithm::Sha512)
+            )
+        );
+        assert_eq!(
+            attribute(&doc, &[0, 0], "crossorigin").unwrap(),
+            "anonymous"
+        );
+        assert_eq!(attribute(&doc, &[0, 1], "integrity"), None);
+        assert_eq!(
+            attribute(&doc, &[0, 2], "crossorigin").unwrap(),
+            "use-credentials"
+        );
+        assert_eq!(attribute(&doc, &[0, 3], "integrity").unwrap(), "sha256-old");
+        assert_eq!(attribute(&doc, &[0, 4], "integrity"), None);
+        assert_eq!(attribute(&doc, &[0, 5], "integrity"), None);
+        assert_eq!(
+            attribute(&doc, &[0, 6], "integrity"),
+            attribute(&doc, &[0, 2], "integrity")
+        );
+    }
+
+    #[test]
+    fn integrity_pass_test() {
+        let mut doc = r#"<script src="/old.js" integrity="sha256-old"></script><script src="/x.js"></script>"#
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let report = Pipeline::new()
+            .add(
+                AddIntegrity::new(|url| (url == "/old.js").then(|| b"new".to_vec()))
+                    .overwrite(true)
+                    .crossorigin(None::<String>),
+            )
+            .run(&mut doc)
+            .unwrap();
+        assert_eq!(
+            attribute(&doc, &[0], "integrity").unwrap(),
+            integrity_value(b"new", SriAlgorithm::Sha384)
+        );
+        assert_eq!(attribute(&doc, &[0], "crossorigin"), None);
+        let diagnostics = report.diagnostics_for("subresource-integrity");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].message, "could not resolve /x.js");
+        assert_eq!(diagnostics[1].path, Some(vec![1]));
+    }
+}
@@ -0,0 +1,289 @@
+//! Stable IDs for the elements of a document, for tools which keep track of
+//! nodes while the document is edited and parsed again.
+//!
+//! A [`NodePath`] changes whenever an element is added before a node, so it can
+//! not be used to refer to a node in a later version of the document.
+//! [`HtmlDocument::assign_stable_ids`] gives each element a `data-hb-id`
+//! attribute ([`STABLE_ID_ATTRIBUTE`]) which is written out and read back with
+//! the document. Elements which already have an ID keep it, so the IDs of the
+//! elements stay the same across any number of passes. The returned
+//! [`StableIds`] is a table of the IDs by path, for looking up nodes without
+//! reading the attributes.
+//!
+//! The IDs are only for tooling, so they are left out of the published page with
+//! [`SerializeOptions::skip_attribute`], or taken out of the document with
+//! [`HtmlDocument::strip_stable_ids`].
+//!
+//! In a [`Pipeline`](crate::pipeline::Pipeline), [`StableIdPass`] assigns the IDs
+//! and leaves the table in [`PassContext::stable_ids`] for the passes after it. A
+//! second one at the end with [`StableIdPass::strip`] set takes the IDs out again
+//! before the page is published.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::serialize::SerializeOptions;
+//! use hb_html::stable_id::STABLE_ID_ATTRIBUTE;
+//! let mut doc = "<ul><li>a</li></ul>".parse::<HtmlDocument>().unwrap();
+//! doc.assign_stable_ids();
+//! let saved = doc.to_html_string();
+//! assert_eq!(saved, r#"<ul data-hb-id="1"><li data-hb-id="2">a</li></ul>"#);
+//!
+//! // an item is added before the first one and the document is parsed again
+//! let edited = saved.replace("<ul data-hb-id=\"1\">", "<ul data-hb-id=\"1\"><li>new</li>");
+//! let mut doc = edited.parse::<HtmlDocument>().unwrap();
+//! let ids = doc.assign_stable_ids();
+//! assert_eq!(ids.path_of("2"), Some(&[0, 1][..]));
+//! assert_eq!(ids.id(&[0, 0]), Some("3"));
+//!
+//! let options = SerializeOptions::new().skip_attribute(STABLE_ID_ATTRIBUTE);
+//! assert_eq!(doc.to_html_string_with(&options), "<ul><li>new</li><li>a</li></ul>");
+//! ```
+use crate::error::HtmlDocError;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use crate::pipeline::{Pass, PassContext};
+use crate::visit::{Visit, VisitAction, VisitContext};
+use std::collections::{BTreeMap, HashSet};
+
+/// The attribute which holds the stable ID of an element.
+pub const STABLE_ID_ATTRIBUTE: &str = "data-hb-id";
+
+/// The stable IDs of the elements of a document by their paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StableIds {
+    ids: BTreeMap<NodePath, String>,
+}
+
+impl StableIds {
+    /// Gets the ID of the element at the path.
+    pub fn id(&self, path: &[usize]) -> Option<&str> {
+        self.ids.get(path).map(String::as_str)
+    }
+
+    /// Gets the path of the element with the ID.
+    pub fn path_of(&self, id: &str) -> Option<&[usize]> {
+        self.ids
+            .iter()
+            .find(|(_, i)| *i == id)
+            .map(|(path, _)| path.as_slice())
+    }
+
+    /// Iterates over the paths and IDs in document order.
+    pub fn iter(&self) -> impl Iterator<Item = (&NodePath, &str)> {
+        self.ids.iter().map(|(path, id)| (path, id.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+impl HtmlDocument {
+    /// Gives each element without a stable ID the next unused number as its ID,
+    /// returning the IDs of all of the elements. If two elements have the same ID,
+    /// such as when an element was copied, the later one is given a new ID.
+    pub fn assign_stable_ids(&mut self) -> StableIds {
+        let mut next = 1;
+        find_stable_ids(&self.nodes, &mut |id| {
+            if let Ok(n) = id.parse::<u64>() {
+                next = next.max(n + 1);
+            }
+        });
+        let mut assigner = IdAssigner {
+            next,
+            seen: HashSet::new(),
+            ids: StableIds::default(),
+        };
+        self.visit(&mut assigner);
+        assigner.ids
+    }
+
+    /// Gets the stable IDs the elements have, without giving IDs to the others.
+    pub fn stable_ids(&self) -> StableIds {
+        let mut ids = StableIds::default();
+        let mut path = vec![];
+        collect_stable_ids(&self.nodes, &mut path, &mut ids);
+        ids
+    }
+
+    /// Removes the stable IDs from all of the elements.
+    pub fn strip_stable_ids(&mut self) {
+        self.visit(&mut IdStripper);
+    }
+}
+
+/// A pass which gives the elements stable IDs, storing the table of them in
+/// [`PassContext::stable_ids`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StableIdPass {
+    /// Take the IDs out of the document instead, storing the ones it had.
+    pub strip: bool,
+}
+
+impl StableIdPass {
+    pub fn new() -> StableIdPass {
+        StableIdPass::default()
+    }
+
+    pub fn strip(mut self, strip: bool) -> StableIdPass {
+        self.strip = strip;
+        self
+    }
+}
+
+impl Pass for StableIdPass {
+    fn name(&self) -> &str {
+        "stable-ids"
+    }
+
+    fn run(&mut self, doc: &mut HtmlDocument, ctx: &mut PassContext) -> Result<(), HtmlDocError> {
+        let ids = if self.strip {
+            let ids = doc.stable_ids();
+            doc.strip_stable_ids();
+            ctx.info(format!("removed {} stable IDs", ids.len()), None);
+            ids
+        } else {
+            let ids = doc.assign_stable_ids();
+            ctx.info(format!("{} elements have stable IDs", ids.len()), None);
+            ids
+        };
+        ctx.stable_ids = Some(ids);
+        Ok(())
+    }
+}
+
+/// Calls found with each stable ID in the nodes.
+fn find_stable_ids<F: FnMut(&str)>(nodes: &[HtmlNode], found: &mut F) {
+    for node in nodes {
+        if let HtmlNode::Tag(tag) = node {
+            if let Some(id) = tag.attributes.get(STABLE_ID_ATTRIBUTE) {
+                found(id);
+            }
+            find_stable_ids(&tag.contents, found);
+        }
+    }
+}
+
+fn collect_stable_ids(nodes: &[HtmlNode], path: &mut NodePath, ids: &mut StableIds) {
+    for (i, node) in nodes.iter().enumerate() {
+        if let HtmlNode::Tag(tag) = node {
+            path.push(i);
+            if let Some(id) = tag.attributes.get(STABLE_ID_ATTRIBUTE) {
+                ids.ids.insert(path.clone(), id.clone());
+            }
+            collect_stable_ids(&tag.contents, path, ids);
+            path.pop();
+        }
+    }
+}
+
+struct IdAssigner {
+    next: u64,
+    /// The IDs of the elements which have been walked.
+    seen: HashSet<String>,
+    ids: StableIds,
+}
+
+impl Visit for IdAssigner {
+    fn enter_element(&mut self, tag: &mut HtmlTag, ctx: &VisitContext) -> VisitAction {
+        let id = match tag.attributes.get(STABLE_ID_ATTRIBUTE) {
+            Some(id) if !id.is_empty() && !self.seen.contains(id) => id.clone(),
+            _ => {
+                let id = self.next.to_string();
+                self.next += 1;
+                tag.attributes
+                    .insert(STABLE_ID_ATTRIBUTE.to_owned(), id.clone());
+                id
+            }
+        };
+        self.seen.insert(id.clone());
+        self.ids.ids.insert(ctx.path.clone(), id);
+        VisitAction::Continue
+    }
+}
+
+struct IdStripper;
+
+impl Visit for IdStripper {
+    fn enter_element(&mut self, tag: &mut HtmlTag, _ctx: &VisitContext) -> VisitAction {
+        tag.attributes.remove(STABLE_ID_ATTRIBUTE);
+        VisitAction::Continue
+    }
+}
+
+#[cfg(test)]
+mod stable_id_tests {
+    use super::*;
+    use crate::pipeline::Pipeline;
+    use crate::serialize::SerializeOptions;
+
+    #[test]
+    fn assign_stable_ids_test() {
+        let mut doc =
+            r#"<div data-hb-id="7"><p>a</p><p data-hb-id="7">b</p><p data-hb-id="x">c</p></div>"#
+                .parse::<HtmlDocument>()
+                .unwrap();
+        let ids = doc.assign_stable_ids();
+        // the copy of 7 is given a new ID after the largest one
+        assert_eq!(
+            ids.iter().collect::<Vec<_>>(),
+            vec![
+                (&vec![0], "7"),
+                (&vec![0, 0], "8"),
+                (&vec![0, 1], "9"),
+                (&vec![0, 2], "x"),
+            ]
+        );
+        assert_eq!(doc.stable_ids(), ids);
+        // a second pass changes nothing
+        let before = doc.clone();
+        assert_eq!(doc.assign_stable_ids(), ids);
+        assert_eq!(doc, before);
+        assert_eq!(ids.path_of("9"), Some(&[0, 1][..]));
+        assert_eq!(ids.path_of("1"), None);
+    }
+
+    #[test]
+    fn strip_stable_ids_test() {
+        let source = r#"<div class="a"><p title="t">a</p></div>"#;
+        let mut doc = source.parse::<HtmlDocument>().unwrap();
+        doc.assign_stable_ids();
+        assert_eq!(doc.stable_ids().len(), 2);
+        let options = SerializeOptions::new().skip_attribute(STABLE_ID_ATTRIBUTE);
+        assert_eq!(doc.to_html_string_with(&options), source);
+        doc.strip_stable_ids();
+        assert!(doc.stable_ids().is_empty());
+        assert_eq!(doc.to_html_string(), source);
+    }
+
+    #[test]
+    fn stable_id_pass_test() {
+        let mut doc =
+            r#"<ul><li data-hb-id="5">a</li><li>b</li></ul>"#.parse::<HtmlDocument>().unwrap();
+        let report = Pipeline::new()
+            .add(StableIdPass::new())
+            .run(&mut doc)
+            .unwrap();
+        let ids = report.stable_ids.clone().unwrap();
+        assert_eq!(ids, doc.stable_ids());
+        assert_eq!(ids.id(&[0]), Some("6"));
+        assert_eq!(ids.path_of("5"), Some(&[0, 0][..]));
+        assert_eq!(
+            report.diagnostics_for("stable-ids")[0].message,
+            "3 elements have stable IDs"
+        );
+
+        let report = Pipeline::new()
+            .add(StableIdPass::new().strip(true))
+            .run(&mut doc)
+            .unwrap();
+        assert_eq!(report.stable_ids.unwrap(), ids);
+        assert!(doc.stable_ids().is_empty());
+        assert_eq!(doc.to_html_string(), "<ul><li>a</li><li>b</li></ul>");
+    }
+}
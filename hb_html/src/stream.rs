@@ -0,0 +1,382 @@
+//! Reading a document as a stream of events, without building a tree.
+//!
+//! [`parse_with`] tokenizes the source and calls the [`HtmlHandler`] as each start
+//! tag, end tag, piece of text and comment is read. Nothing is kept once the
+//! handler has been called, and the names, attributes and text given to it are
+//! slices of the source, so scraping a very large document takes the same memory
+//! as scraping a small one.
+//!
+//! The events are the tokens as they are written, without checking that the tags
+//! are nested correctly: void elements such as `<br>` do not have an end tag
+//! event, and an end tag which does not match is still reported. Text is given as
+//! it is written, [`decode_entities`](crate::entities::decode_entities) decodes
+//! the character references in it, and may be split into more than one event. The
+//! contents of `<script>` and `<style>` elements are read as text up to their end
+//! tag. [`HtmlHandler::span`] is called before each event with where its token
+//! is in the source.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::stream::{parse_with, HtmlHandler, StartTag};
+//! #[derive(Default)]
+//! struct Links {
+//!     hrefs: Vec<String>,
+//! }
+//! impl HtmlHandler for Links {
+//!     fn start_tag(&mut self, tag: &StartTag) {
+//!         if let (true, Some(href)) = (tag.name == "a", tag.attribute("href")) {
+//!             self.hrefs.push(href.to_owned());
+//!         }
+//!     }
+//! }
+//! let mut links = Links::default();
+//! parse_with(r#"<p><a href="/one">1</a> <a href='/two'>2</a></p>"#, &mut links).unwrap();
+//! assert_eq!(links.hrefs, vec!["/one", "/two"]);
+//! ```
+use crate::error::{ParseErrorKind, ParseHtmlError};
+use crate::memfind::find_byte;
+use crate::source_map::{LineCounter, Span};
+
+/// The elements whose content is read as text up to their end tag.
+const RAW_TEXT_TAGS: [&str; 2] = ["script", "style"];
+
+/// A start tag given to [`HtmlHandler::start_tag`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartTag<'a> {
+    pub name: &'a str,
+    /// The attributes in the order they are written, with the quotes taken off
+    /// their values. Attributes without a value have an empty value.
+    pub attributes: Vec<(&'a str, &'a str)>,
+    /// True if the tag ends with `/>`.
+    pub self_closing: bool,
+}
+
+impl<'a> StartTag<'a> {
+    /// Gets the value of the first attribute with the name.
+    pub fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+}
+
+/// Receives the events of [`parse_with`]. Each event does nothing unless it is
+/// overridden.
+pub trait HtmlHandler {
+    /// Called before each of the other events with the span of its token, eg the
+    /// whole of a start tag from the `<` to the `>`.
+    fn span(&mut self, _span: Span) {}
+
+    /// Called with the text after `<!DOCTYPE`, eg `html`.
+    fn doctype(&mut self, _doctype: &str) {}
+
+    fn start_tag(&mut self, _tag: &StartTag) {}
+
+    fn end_tag(&mut self, _name: &str) {}
+
+    fn text(&mut self, _text: &str) {}
+
+    /// Called with the text between `<!--` and `-->`.
+    fn comment(&mut self, _comment: &str) {}
+
+    /// Checked after each event, parsing stops as soon as this returns true.
+    fn is_done(&self) -> bool {
+        false
+    }
+}
+
+/// Reads the source, calling the handler for each token. Returns an error if the
+/// source ends part way through a tag or comment, after the handler has been
+/// given everything before it.
+pub fn parse_with<H: HtmlHandler + ?Sized>(
+    source: &str,
+    handler: &mut H,
+) -> Result<(), ParseHtmlError> {
+    let bytes = source.as_bytes();
+    let mut tag = StartTag {
+        name: "",
+        attributes: vec![],
+        self_closing: false,
+    };
+    let mut lines = LineCounter::new(source);
+    let mut pos = 0;
+    while pos < source.len() {
+        let lt = match find_byte(&bytes[pos..], b'<') {
+            Some(i) => pos + i,
+            None => {
+                handler.span(lines.span(pos..source.len()));
+                handler.text(&source[pos..]);
+                return Ok(());
+            }
+        };
+        if lt > pos {
+            handler.span(lines.span(pos..lt));
+            handler.text(&source[pos..lt]);
+            if handler.is_done() {
+                return Ok(());
+            }
+        }
+        let rest = &source[lt + 1..];
+        let unfinished = |what: &str| {
+            ParseHtmlError::new(format!("the source ends before the end of the {}", what))
+                .with_kind(ParseErrorKind::UnexpectedEnd)
+                .at_offset(lt)
+        };
+        if let Some(comment) = rest.strip_prefix("!--") {
+            let end = comment.find("-->").ok_or_else(|| unfinished("comment"))?;
+            pos = lt + 4 + end + 3;
+            handler.span(lines.span(lt..pos));
+            handler.comment(&comment[..end]);
+        } else if let Some(bang) = rest.strip_prefix('!') {
+            let end = find_byte(bang.as_bytes(), b'>').ok_or_else(|| unfinished("doctype"))?;
+            let body = &bang[..end];
+            pos = lt + 2 + end + 1;
+            handler.span(lines.span(lt..pos));
+            match body.get(..7) {
+                Some(d) if d.eq_ignore_ascii_case("doctype") => handler.doctype(body[7..].trim()),
+                _ => handler.comment(body),
+            }
+        } else if let Some(end_tag) = rest.strip_prefix('/') {
+            let end = find_byte(end_tag.as_bytes(), b'>').ok_or_else(|| unfinished("end tag"))?;
+            pos = lt + 2 + end + 1;
+            handler.span(lines.span(lt..pos));
+            handler.end_tag(end_tag[..end].trim());
+        } else if rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let len = read_start_tag(rest, &mut tag).ok_or_else(|| unfinished("start tag"))?;
+            pos = lt + 1 + len;
+            handler.span(lines.span(lt..pos));
+            handler.start_tag(&tag);
+            let is_raw = RAW_TEXT_TAGS
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(tag.name));
+            if is_raw && !tag.self_closing {
+                if handler.is_done() {
+                    return Ok(());
+                }
+                let text_end =
+                    find_end_tag(&source[pos..], tag.name).map_or(source.len(), |i| pos + i);
+                if text_end > pos {
+                    handler.span(lines.span(pos..text_end));
+                    handler.text(&source[pos..text_end]);
+                }
+                pos = text_end;
+            }
+        } else {
+            // a < which does not start a tag is text
+            pos = lt + 1;
+            handler.span(lines.span(lt..pos));
+            handler.text("<");
+        }
+        if handler.is_done() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Reads the start tag at the start of the text, which is just after the `<`,
+/// into the tag. Returns the length of the tag, or None if it is not finished.
+fn read_start_tag<'a>(text: &'a str, tag: &mut StartTag<'a>) -> Option<usize> {
+    let is_name_end = |c: char| c.is_ascii_whitespace() || c == '>' || c == '/';
+    let name_len = text.find(is_name_end)?;
+    tag.name = &text[..name_len];
+    tag.attributes.clear();
+    tag.self_closing = false;
+    let mut pos = name_len;
+    loop {
+        pos += text[pos..].find(|c: char| !c.is_ascii_whitespace())?;
+        let rest = &text[pos..];
+        if rest.starts_with('>') {
+            return Some(pos + 1);
+        }
+        if rest.starts_with("/>") {
+            tag.self_closing = true;
+            return Some(pos + 2);
+        }
+        if rest.starts_with('/') {
+            pos += 1;
+            continue;
+        }
+        let name_len = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len())
+            .max(1);
+        let name = &rest[..name_len];
+        pos += name_len;
+        let after_name = pos + text[pos..].find(|c: char| !c.is_ascii_whitespace())?;
+        if !text[after_name..].starts_with('=') {
+            tag.attributes.push((name, ""));
+            continue;
+        }
+        pos = after_name + 1;
+        pos += text[pos..].find(|c: char| !c.is_ascii_whitespace())?;
+        let value = match text[pos..].chars().next()? {
+            quote @ ('"' | '\'') => {
+                let len = text[pos + 1..].find(quote)?;
+                let value = &text[pos + 1..pos + 1 + len];
+                pos += len + 2;
+                value
+            }
+            _ => {
+                let len = text[pos..]
+                    .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                    .unwrap_or(text.len() - pos);
+                let value = &text[pos..pos + len];
+                pos += len;
+                value
+            }
+        };
+        tag.attributes.push((name, value));
+    }
+}
+
+/// Finds the offset of the end tag with the name in the text, ignoring case.
+fn find_end_tag(text: &str, name: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut from = 0;
+    while let Some(i) = find_byte(&bytes[from..], b'<') {
+        let start = from + i;
+        let after = &bytes[start + 1..];
+        let is_end = after.first() == Some(&b'/')
+            && after.len() > name.len()
+            && after[1..=name.len()].eq_ignore_ascii_case(name.as_bytes())
+            && matches!(
+                after.get(name.len() + 1),
+                None | Some(b'>' | b' ' | b'\t' | b'\n' | b'\r' | b'\x0C')
+            );
+        if is_end {
+            return Some(start);
+        }
+        from = start + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod stream_tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Events {
+        events: Vec<String>,
+        stop_after: Option<usize>,
+    }
+
+    impl HtmlHandler for Events {
+        fn doctype(&mut self, doctype: &str) {
+            self.events.push(format!("doctype {}", doctype));
+        }
+        fn start_tag(&mut self, tag: &StartTag) {
+            let attributes: Vec<String> = tag
+                .attributes
+                .iter()
+                .map(|(n, v)| format!(" {}={}", n, v))
+                .collect();
+            let end = if tag.self_closing { "/" } else { "" };
+            self.events
+                .push(format!("<{}{}{}>", tag.name, attributes.concat(), end));
+        }
+        fn end_tag(&mut self, name: &str) {
+            self.events.push(format!("</{}>", name));
+        }
+        fn text(&mut self, text: &str) {
+            self.events.push(format!("text {}", text));
+        }
+        fn comment(&mut self, comment: &str) {
+            self.events.push(format!("comment {}", comment));
+        }
+        fn is_done(&self) -> bool {
+            self.stop_after.is_some_and(|n| self.events.len() >= n)
+        }
+    }
+
+    fn events(source: &str) -> Vec<String> {
+        let mut handler = Events::default();
+        parse_with(source, &mut handler).unwrap();
+        handler.events
+    }
+
+    #[test]
+    fn stream_events_test() {
+        let source = "<!DOCTYPE html><!-- c --><div id=a class='b c' hidden data-x = \"1 > 0\">\
+                      Hi &amp; 1 < 2<br/><img src=x.png alt></div></p>";
+        assert_eq!(
+            events(source),
+            vec![
+                "doctype html",
+                "comment  c ",
+                "<div id=a class=b c hidden= data-x=1 > 0>",
+                "text Hi &amp; 1 ",
+                "text <",
+                "text  2",
+                "<br/>",
+                "<img src=x.png alt=>",
+                "</div>",
+                "</p>",
+            ]
+        );
+        assert_eq!(
+            events("<script>if (a<b) { x = '</p>'; }</SCRIPT ><style/>text"),
+            vec![
+                "<script>",
+                "text if (a<b) { x = '</p>'; }",
+                "</SCRIPT>",
+                "<style/>",
+                "text text",
+            ]
+        );
+        assert!(events("").is_empty());
+    }
+
+    #[derive(Default)]
+    struct Spans {
+        spans: Vec<(usize, usize, usize, usize)>,
+    }
+
+    impl HtmlHandler for Spans {
+        fn span(&mut self, span: Span) {
+            self.spans.push((span.start, span.end, span.line, span.col));
+        }
+    }
+
+    #[test]
+    fn stream_spans_test() {
+        let source = "<p>\né<br>\n<!-- c --></p><script>x</script>";
+        let mut handler = Spans::default();
+        parse_with(source, &mut handler).unwrap();
+        assert_eq!(
+            handler.spans,
+            vec![
+                (0, 3, 1, 1),
+                (3, 6, 1, 4),
+                (6, 10, 2, 2),
+                (10, 11, 2, 6),
+                (11, 21, 3, 1),
+                (21, 25, 3, 11),
+                (25, 33, 3, 15),
+                (33, 34, 3, 23),
+                (34, 43, 3, 24),
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_stop_and_errors_test() {
+        let mut handler = Events {
+            stop_after: Some(2),
+            ..Events::default()
+        };
+        parse_with("<title>Home</title><p>never read", &mut handler).unwrap();
+        assert_eq!(handler.events, vec!["<title>", "text Home"]);
+
+        for (source, offset) in [("<p>a<!-- b", 4), ("<p>a<div class='x>", 4), ("a</p", 1)] {
+            let mut handler = Events::default();
+            let err = parse_with(source, &mut handler).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::UnexpectedEnd);
+            assert_eq!(err.offset(), Some(offset), "error offset in {}", source);
+            assert!(!handler.events.is_empty());
+        }
+    }
+}
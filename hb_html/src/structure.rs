@@ -0,0 +1,395 @@
+//! Finding the repeated parts of a document, such as the cards of a product list
+//! or the rows of search results, to work out the template a page was made from.
+//!
+//! The [structural hash](HtmlTag::structural_hash) of an element covers its tag
+//! name, classes, the names of its attributes and the structure of its contents,
+//! but not the text or the attribute values, so elements made from the same
+//! template have the same hash. [`HtmlDocument::components`] groups the elements
+//! by their hash and reports each group which repeats as a [`Component`], along
+//! with the text and attribute values which vary between the repeats.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::structure::ComponentOptions;
+//! let doc = concat!(
+//!     r#"<ul><li class="card"><a href="/a">A</a><span>1</span></li>"#,
+//!     r#"<li class="card"><a href="/b">B</a><span>1</span></li></ul>"#
+//! )
+//! .parse::<HtmlDocument>()
+//! .unwrap();
+//! let components = doc.components(&ComponentOptions::new());
+//! assert_eq!(components.len(), 1);
+//! assert_eq!(components[0].classes, vec!["card"]);
+//! assert_eq!(components[0].occurrences, vec![vec![0, 0], vec![0, 1]]);
+//! // the span has the same text in both, so only the link varies
+//! let variations = &components[0].variations;
+//! assert_eq!(variations.len(), 2);
+//! assert_eq!(variations[0].attribute.as_deref(), Some("href"));
+//! assert_eq!(variations[0].values, vec!["/a", "/b"]);
+//! assert_eq!(variations[1].path, vec![0, 0]);
+//! assert_eq!(variations[1].values, vec!["A", "B"]);
+//! ```
+use crate::hash::fnv1a_64;
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+use std::collections::HashMap;
+
+/// Options for finding the components of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentOptions {
+    /// The number of times a structure must appear to be reported.
+    pub min_occurrences: usize,
+    /// The number of elements a structure must have to be reported, so single
+    /// elements such as `<br>` are not reported by default.
+    pub min_elements: usize,
+}
+
+impl Default for ComponentOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComponentOptions {
+    pub fn new() -> ComponentOptions {
+        ComponentOptions {
+            min_occurrences: 2,
+            min_elements: 2,
+        }
+    }
+
+    pub fn min_occurrences(mut self, min_occurrences: usize) -> ComponentOptions {
+        self.min_occurrences = min_occurrences;
+        self
+    }
+
+    pub fn min_elements(mut self, min_elements: usize) -> ComponentOptions {
+        self.min_elements = min_elements;
+        self
+    }
+}
+
+/// A text node or attribute whose value is not the same in every occurrence of a
+/// component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variation {
+    /// The path of the node from the root of the component, using the indexes in
+    /// the first occurrence. The root itself has an empty path.
+    pub path: NodePath,
+    /// The attribute which varies, or None for a text node. The ids of a tag are
+    /// reported as the `id` attribute.
+    pub attribute: Option<String>,
+    /// The value in each occurrence, with the whitespace around text trimmed.
+    pub values: Vec<String>,
+}
+
+/// A structure which repeats in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// The structural hash shared by the occurrences.
+    pub hash: u64,
+    /// The tag name of the root of the component.
+    pub tag: String,
+    /// The classes of the root of the component, in alphabetical order.
+    pub classes: Vec<String>,
+    /// The number of elements in each occurrence, including the root.
+    pub elements: usize,
+    /// The paths of the occurrences, in document order.
+    pub occurrences: Vec<NodePath>,
+    pub variations: Vec<Variation>,
+}
+
+/// Returns true if the node is part of the structure, rather than a comment or
+/// whitespace between tags.
+fn is_structural(node: &HtmlNode) -> bool {
+    match node {
+        HtmlNode::Tag(_) | HtmlNode::Template(_) => true,
+        HtmlNode::Text(t) => !t.trim().is_empty(),
+        HtmlNode::Comment(_) => false,
+    }
+}
+
+/// Gets the structural contents of the tag along with their indexes.
+fn structural_contents(tag: &HtmlTag) -> Vec<(usize, &HtmlNode)> {
+    tag.contents
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| is_structural(n))
+        .collect()
+}
+
+/// Gets the structural hash and number of elements of the tag, calling the
+/// function with those of the tag and each of its descendants. With `text` the
+/// hash also covers the text of the text nodes, with the whitespace normalized.
+fn hash_tag<F: FnMut(&NodePath, u64, usize)>(
+    tag: &HtmlTag,
+    text: bool,
+    path: &mut NodePath,
+    f: &mut F,
+) -> (u64, usize) {
+    let mut classes: Vec<&str> = tag.classes.iter().map(|c| c.as_str()).collect();
+    classes.sort_unstable();
+    let mut attributes: Vec<&str> = tag.attributes.keys().map(|a| a.as_str()).collect();
+    attributes.sort_unstable();
+    let mut signature = format!(
+        "{}.{}[{}]{}(",
+        tag.tag.to_ascii_lowercase(),
+        classes.join("."),
+        attributes.join(","),
+        if tag.ids.is_empty() { "" } else { "#" }
+    );
+    let mut elements = 1;
+    for (i, node) in structural_contents(tag) {
+        match node {
+            HtmlNode::Tag(child) => {
+                path.push(i);
+                let (hash, size) = hash_tag(child, text, path, f);
+                path.pop();
+                elements += size;
+                signature.push_str(&format!("{:016x}", hash));
+            }
+            HtmlNode::Text(t) | HtmlNode::Template(t) if text => {
+                let words: Vec<&str> = t.split_whitespace().collect();
+                signature.push_str(&format!("{:?}", words.join(" ")));
+            }
+            _ => signature.push('*'),
+        }
+    }
+    signature.push(')');
+    let hash = fnv1a_64(signature.as_bytes());
+    f(path, hash, elements);
+    (hash, elements)
+}
+
+/// Adds a variation if the values are not all the same.
+fn push_variation(
+    variations: &mut Vec<Variation>,
+    path: &NodePath,
+    attribute: Option<&str>,
+    values: Vec<String>,
+) {
+    if values.iter().any(|v| *v != values[0]) {
+        variations.push(Variation {
+            path: path.clone(),
+            attribute: attribute.map(|a| a.to_owned()),
+            values,
+        });
+    }
+}
+
+/// Adds the values which are not the same in all of the tags, which have the same
+/// structure.
+fn collect_variations(tags: &[&HtmlTag], path: &mut NodePath, variations: &mut Vec<Variation>) {
+    if !tags[0].ids.is_empty() {
+        push_variation(
+            variations,
+            path,
+            Some("id"),
+            tags.iter().map(|t| t.ids.join(" ")).collect(),
+        );
+    }
+    let mut attributes: Vec<&String> = tags[0].attributes.keys().collect();
+    attributes.sort();
+    for attribute in attributes {
+        let values = tags
+            .iter()
+            .map(|t| t.attributes.get(attribute).cloned().unwrap_or_default())
+            .collect();
+        push_variation(variations, path, Some(attribute), values);
+    }
+    let contents: Vec<Vec<(usize, &HtmlNode)>> =
+        tags.iter().map(|t| structural_contents(t)).collect();
+    for (j, (i, first)) in contents[0].iter().enumerate() {
+        path.push(*i);
+        let nodes: Vec<&HtmlNode> = contents
+            .iter()
+            .filter_map(|c| c.get(j).map(|n| n.1))
+            .collect();
+        match first {
+            HtmlNode::Tag(_) => {
+                let children: Vec<&HtmlTag> = nodes
+                    .iter()
+                    .filter_map(|n| match n {
+                        HtmlNode::Tag(t) => Some(t),
+                        _ => None,
+                    })
+                    .collect();
+                if children.len() == tags.len() {
+                    collect_variations(&children, path, variations);
+                }
+            }
+            _ => {
+                let values = nodes
+                    .iter()
+                    .map(|n| match n {
+                        HtmlNode::Text(t) | HtmlNode::Template(t) => t.trim().to_owned(),
+                        _ => String::new(),
+                    })
+                    .collect();
+                push_variation(variations, path, None, values);
+            }
+        }
+        path.pop();
+    }
+}
+
+impl HtmlTag {
+    /// Gets a hash of the structure of the tag: its name, classes, the names of
+    /// its attributes, whether it has an id, and the same for its descendants,
+    /// with each text node counting the same whatever its text. Comments and
+    /// whitespace between tags are ignored.
+    pub fn structural_hash(&self) -> u64 {
+        hash_tag(self, false, &mut vec![], &mut |_, _, _| {}).0
+    }
+}
+
+/// Gets the hash and number of elements of every element in the document, in
+/// document order, covering the text as well as the structure if `text` is true.
+pub(crate) fn element_hashes(doc: &HtmlDocument, text: bool) -> Vec<(NodePath, u64, usize)> {
+    let mut hashes = vec![];
+    for (i, node) in doc.nodes.iter().enumerate() {
+        if let HtmlNode::Tag(tag) = node {
+            hash_tag(tag, text, &mut vec![i], &mut |p, hash, size| {
+                hashes.push((p.clone(), hash, size))
+            });
+        }
+    }
+    hashes.sort_by(|a, b| a.0.cmp(&b.0));
+    hashes
+}
+
+impl HtmlDocument {
+    /// Gets the structural hash and number of elements of every element in the
+    /// document, in document order.
+    pub fn structural_hashes(&self) -> Vec<(NodePath, u64, usize)> {
+        element_hashes(self, false)
+    }
+
+    /// Finds the structures which repeat in the document, largest first. An
+    /// element inside an occurrence of a larger component is not reported, so the
+    /// parts of a repeated card are left out, as they are covered by the variations
+    /// of the card.
+    pub fn components(&self, options: &ComponentOptions) -> Vec<Component> {
+        let mut groups: HashMap<u64, (usize, Vec<NodePath>)> = HashMap::new();
+        for (path, hash, elements) in self.structural_hashes() {
+            if elements >= options.min_elements {
+                groups
+                    .entry(hash)
+                    .or_insert((elements, vec![]))
+                    .1
+                    .push(path);
+            }
+        }
+        let mut groups: Vec<(u64, usize, Vec<NodePath>)> = groups
+            .into_iter()
+            .filter(|(_, (_, paths))| paths.len() >= options.min_occurrences.max(1))
+            .map(|(hash, (elements, paths))| (hash, elements, paths))
+            .collect();
+        groups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.2[0].cmp(&b.2[0])));
+        let mut claimed: Vec<NodePath> = vec![];
+        let mut components = vec![];
+        for (hash, elements, paths) in groups {
+            let occurrences: Vec<NodePath> = paths
+                .into_iter()
+                .filter(|p| !claimed.iter().any(|c| p.starts_with(c)))
+                .collect();
+            if occurrences.len() < options.min_occurrences.max(1) {
+                continue;
+            }
+            let tags: Vec<&HtmlTag> = occurrences
+                .iter()
+                .filter_map(|p| match self.node_at(p) {
+                    Some(HtmlNode::Tag(t)) => Some(t),
+                    _ => None,
+                })
+                .collect();
+            let mut classes = tags[0].classes.clone();
+            classes.sort();
+            let mut variations = vec![];
+            collect_variations(&tags, &mut vec![], &mut variations);
+            claimed.extend(occurrences.iter().cloned());
+            components.push(Component {
+                hash,
+                tag: tags[0].tag.clone(),
+                classes,
+                elements,
+                occurrences,
+                variations,
+            });
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod structure_tests {
+    use super::*;
+
+    #[test]
+    fn structural_hash_test() {
+        let hash = |html: &str| html.parse::<HtmlTag>().unwrap().structural_hash();
+        let card =
+            hash(r#"<div class="a b" id="x"><h2>One</h2><!-- c --> <img src="1.png"></div>"#);
+        assert_eq!(
+            card,
+            hash(r#"<div id="y" class="b a"><h2>Two</h2><img src="2.png"></div>"#)
+        );
+        assert_ne!(
+            card,
+            hash(r#"<div class="a" id="x"><h2>One</h2><img src="1.png"></div>"#)
+        );
+        assert_ne!(
+            card,
+            hash(r#"<div class="a b"><h2>One</h2><img src="1.png"></div>"#)
+        );
+        assert_ne!(
+            card,
+            hash(r#"<div class="a b" id="x"><h2><b>One</b></h2><img src="1.png"></div>"#)
+        );
+        assert_ne!(
+            card,
+            hash(r#"<div class="a b" id="x"><h2>One</h2><img alt="" src="1.png"></div>"#)
+        );
+    }
+
+    #[test]
+    fn components_test() {
+        let doc = concat!(
+            r#"<main><article id="p1" class="post"><h2>First</h2><p>Hello <b>there</b></p></article>"#,
+            r#"<article id="p2" class="post"><h2>Second</h2><p>Bye <b>there</b></p></article>"#,
+            r#"<article id="p3" class="post"><h2>First</h2><p>Hi <b>all</b></p></article>"#,
+            r#"<footer><p>a <b>b</b></p></footer></main>"#
+        )
+        .parse::<HtmlDocument>()
+        .unwrap();
+        let components = doc.components(&ComponentOptions::new());
+        // the paragraph in the footer has the same structure as those in the posts,
+        // but only appears once outside of them
+        assert_eq!(components.len(), 1);
+        let post = &components[0];
+        assert_eq!(post.tag, "article");
+        assert_eq!(post.elements, 4);
+        assert_eq!(post.occurrences, vec![vec![0, 0], vec![0, 1], vec![0, 2]]);
+        let found: Vec<(NodePath, Option<&str>, Vec<&str>)> = post
+            .variations
+            .iter()
+            .map(|v| {
+                let values = v.values.iter().map(|s| s.as_str()).collect();
+                (v.path.clone(), v.attribute.as_deref(), values)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (vec![], Some("id"), vec!["p1", "p2", "p3"]),
+                (vec![0, 0], None, vec!["First", "Second", "First"]),
+                (vec![1, 0], None, vec!["Hello", "Bye", "Hi"]),
+                (vec![1, 1, 0], None, vec!["there", "there", "all"]),
+            ]
+        );
+        let paragraphs =
+            doc.components(&ComponentOptions::new().min_elements(4).min_occurrences(4));
+        assert!(paragraphs.is_empty());
+    }
+}
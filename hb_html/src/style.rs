@@ -0,0 +1,344 @@
+//! Reading and changing the `style` attribute of a tag as a list of declarations.
+//!
+//! A [`StyleDeclaration`] is the ordered list of `property: value` declarations in
+//! a `style` attribute, with at most one declaration for each property. Adding a
+//! declaration for a property already in the list follows the cascade: a
+//! declaration marked `!important` is only replaced by another `!important`
+//! declaration, and otherwise the later declaration wins and moves to the end.
+//!
+//! [`HtmlTag::merge_style`] merges declarations into the style of a tag, with a
+//! [`Precedence`] saying which side wins when both have a declaration for the same
+//! property. Inlining the rules of a style sheet uses [`Precedence::Existing`], as
+//! the declarations already in the `style` attribute are more specific than any
+//! rule, while code setting styles directly usually wants [`Precedence::Incoming`].
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlTag;
+//! use hb_html::style::{Precedence, StyleDeclaration};
+//! let mut tag = r#"<p style="color: red; margin: 0 !important"></p>"#
+//!     .parse::<HtmlTag>()
+//!     .unwrap();
+//! let rules = StyleDeclaration::parse("color: blue; margin: 4px; padding: 1px");
+//! tag.merge_style(&rules, Precedence::Existing);
+//! assert_eq!(
+//!     tag.attributes["style"],
+//!     "padding: 1px; color: red; margin: 0 !important"
+//! );
+//! tag.merge_style(&rules, Precedence::Incoming);
+//! assert_eq!(
+//!     tag.attributes["style"],
+//!     "margin: 0 !important; color: blue; padding: 1px"
+//! );
+//! ```
+use crate::objects::HtmlTag;
+use std::fmt;
+
+/// One `property: value` declaration.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Declaration {
+    /// The name of the property, in lowercase unless it is a custom property.
+    pub property: String,
+    /// The value, without the `!important`.
+    pub value: String,
+    pub important: bool,
+}
+
+impl Declaration {
+    pub fn new<P: Into<String>, V: Into<String>>(property: P, value: V) -> Declaration {
+        Declaration {
+            property: property_name(property.into()),
+            value: value.into(),
+            important: false,
+        }
+    }
+
+    pub fn important(mut self, important: bool) -> Self {
+        self.important = important;
+        self
+    }
+
+    /// Returns true if the declaration replaces the other one, which is for the
+    /// same property and comes before it.
+    fn overrides(&self, earlier: &Declaration) -> bool {
+        self.important || !earlier.important
+    }
+}
+
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.property, self.value)?;
+        if self.important {
+            f.write_str(" !important")?;
+        }
+        Ok(())
+    }
+}
+
+/// Makes the name of a property lowercase, unless it is a custom property as the
+/// case of those matters.
+fn property_name(property: String) -> String {
+    if property.starts_with("--") {
+        property
+    } else {
+        property.to_ascii_lowercase()
+    }
+}
+
+/// Which declarations win when merging two lists with the same property and the
+/// same importance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Precedence {
+    /// The declarations already on the tag win.
+    Existing,
+    /// The declarations being merged in win.
+    Incoming,
+}
+
+/// The declarations of a `style` attribute, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StyleDeclaration {
+    declarations: Vec<Declaration>,
+}
+
+/// Splits the text at the character, ignoring it in quotes, brackets and comments.
+fn split_outside(text: &str, at: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth = depth.saturating_sub(1),
+            (None, '/') if chars.peek().is_some_and(|(_, n)| *n == '*') => {
+                chars.next();
+                let mut last = ' ';
+                for (_, n) in chars.by_ref() {
+                    if last == '*' && n == '/' {
+                        break;
+                    }
+                    last = n;
+                }
+            }
+            (None, c) if c == at && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Splits `!important` off the end of a value.
+fn split_important(value: &str) -> (&str, bool) {
+    let trimmed = value.trim_end();
+    let lower = trimmed.to_ascii_lowercase();
+    if let Some(rest) = lower.strip_suffix("important") {
+        let rest = rest.trim_end();
+        if let Some(without) = rest.strip_suffix('!') {
+            return (trimmed[..without.len()].trim_end(), true);
+        }
+    }
+    (trimmed, false)
+}
+
+impl StyleDeclaration {
+    pub fn new() -> StyleDeclaration {
+        StyleDeclaration::default()
+    }
+
+    /// Parses the value of a `style` attribute. Declarations without a property or
+    /// a value are skipped, as browsers do.
+    pub fn parse(css: &str) -> StyleDeclaration {
+        let mut style = StyleDeclaration::new();
+        for part in split_outside(css, ';') {
+            let halves = split_outside(part, ':');
+            let property = halves[0].trim();
+            if property.is_empty() || halves.len() < 2 {
+                continue;
+            }
+            // any later colons are part of the value, eg in a url
+            let value = &part[halves[0].len() + 1..];
+            let (value, important) = split_important(value.trim());
+            if value.is_empty() {
+                continue;
+            }
+            style.push(Declaration::new(property, value).important(important));
+        }
+        style
+    }
+
+    /// Adds the declaration after the others, unless the list already has an
+    /// `!important` declaration for the property and this one is not.
+    pub fn push(&mut self, declaration: Declaration) {
+        if let Some(i) = self
+            .declarations
+            .iter()
+            .position(|d| d.property == declaration.property)
+        {
+            if !declaration.overrides(&self.declarations[i]) {
+                return;
+            }
+            self.declarations.remove(i);
+        }
+        self.declarations.push(declaration);
+    }
+
+    /// Sets the value of the property, replacing any declaration for it even if
+    /// that was `!important`.
+    pub fn set<P: Into<String>, V: Into<String>>(&mut self, property: P, value: V) {
+        let declaration = Declaration::new(property, value);
+        self.remove(&declaration.property);
+        self.declarations.push(declaration);
+    }
+
+    pub fn get(&self, property: &str) -> Option<&Declaration> {
+        self.declarations
+            .iter()
+            .find(|d| d.property == property_name(property.to_owned()))
+    }
+
+    /// Removes the declaration for the property, returning it if there was one.
+    pub fn remove(&mut self, property: &str) -> Option<Declaration> {
+        let i = self
+            .declarations
+            .iter()
+            .position(|d| d.property == property_name(property.to_owned()))?;
+        Some(self.declarations.remove(i))
+    }
+
+    /// Merges the other declarations into these. The important declarations of
+    /// either side beat the other declarations, and otherwise the precedence says
+    /// which side wins. The declarations which lost are removed, and those which
+    /// are left are ordered with the losing side first so that shorthand and
+    /// longhand properties still cascade in the same way.
+    pub fn merge(&mut self, other: &StyleDeclaration, precedence: Precedence) {
+        let (low, high) = match precedence {
+            Precedence::Existing => (other, &*self),
+            Precedence::Incoming => (&*self, other),
+        };
+        let mut merged = low.clone();
+        for declaration in &high.declarations {
+            merged.push(declaration.clone());
+        }
+        *self = merged;
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Declaration> {
+        self.declarations.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.declarations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.declarations.is_empty()
+    }
+}
+
+impl fmt::Display for StyleDeclaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, declaration) in self.declarations.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", declaration)?;
+        }
+        Ok(())
+    }
+}
+
+impl HtmlTag {
+    /// Gets the declarations in the `style` attribute.
+    pub fn style(&self) -> StyleDeclaration {
+        self.attributes
+            .get("style")
+            .map(|s| StyleDeclaration::parse(s))
+            .unwrap_or_default()
+    }
+
+    /// Replaces the `style` attribute with the declarations, removing the attribute
+    /// if there are none.
+    pub fn set_style(&mut self, style: &StyleDeclaration) {
+        if style.is_empty() {
+            self.remove_attribute("style");
+        } else {
+            self.set_attribute("style", style.to_string());
+        }
+    }
+
+    /// Merges the declarations into the `style` attribute, see
+    /// [`StyleDeclaration::merge`].
+    pub fn merge_style(&mut self, style: &StyleDeclaration, precedence: Precedence) {
+        let mut merged = self.style();
+        merged.merge(style, precedence);
+        self.set_style(&merged);
+    }
+}
+
+#[cfg(test)]
+mod style_tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let style = StyleDeclaration::parse(concat!(
+            "COLOR: Red ; background: url('a;b.png') /* x; y */; ;margin:;",
+            "font-family: \"A;B\", serif!IMPORTANT; font-family: mono; --Gap: 1px"
+        ));
+        assert_eq!(
+            style.to_string(),
+            concat!(
+                "color: Red; background: url('a;b.png') /* x; y */; ",
+                "font-family: \"A;B\", serif !important; --Gap: 1px"
+            )
+        );
+        assert!(style.get("Font-Family").unwrap().important);
+        assert_eq!(style.get("margin"), None);
+        assert_eq!(
+            StyleDeclaration::parse("a: x:y").get("a").unwrap().value,
+            "x:y"
+        );
+    }
+
+    #[test]
+    fn merge_style_test() {
+        let mut tag = r#"<p style="margin-left: 1px; color: red !important"></p>"#
+            .parse::<HtmlTag>()
+            .unwrap();
+        let rules = StyleDeclaration::parse("margin: 0; color: blue; top: 0 !important");
+        let mut existing = tag.clone();
+        existing.merge_style(&rules, Precedence::Existing);
+        // the shorthand comes first so the longhand already on the tag still applies
+        assert_eq!(
+            existing.attributes["style"],
+            "margin: 0; top: 0 !important; margin-left: 1px; color: red !important"
+        );
+        tag.merge_style(&rules, Precedence::Incoming);
+        assert_eq!(
+            tag.attributes["style"],
+            "margin-left: 1px; color: red !important; margin: 0; top: 0 !important"
+        );
+        let mut style = tag.style();
+        style.set("color", "green");
+        style.remove("margin-left");
+        assert_eq!(
+            style.to_string(),
+            "margin: 0; top: 0 !important; color: green"
+        );
+        tag.set_style(&StyleDeclaration::new());
+        assert!(!tag.attributes.contains_key("style"));
+    }
+}
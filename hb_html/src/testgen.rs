@@ -0,0 +1,489 @@
+//! Random documents for testing parsers and pipelines.
+//!
+//! [`DocGenerator`] makes documents from a seed, so a failing case can be made
+//! again from the seed alone. The elements are chosen using the
+//! [`crate::content_model`] tables, so the documents are valid: every element is
+//! allowed in its parent and has attributes it supports. Text is made of plain
+//! words, so it never needs escaping.
+//!
+//! Setting [`GenOptions::defects`] breaks the written HTML in that many places,
+//! eg by dropping an end tag or adding one which was never opened, to test how
+//! errors are handled. The [`Generated`] output has both the HTML and the valid
+//! document it was written from.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::HtmlDocument;
+//! use hb_html::testgen::{DocGenerator, GenOptions};
+//! let options = GenOptions::new().max_depth(4).max_nodes(50);
+//! let mut generator = DocGenerator::new(7).options(options.clone());
+//! let generated = generator.generate();
+//! // the same seed always makes the same document
+//! assert_eq!(DocGenerator::new(7).options(options).generate(), generated);
+//! let parsed = generated.html.parse::<HtmlDocument>().unwrap();
+//! assert_eq!(parsed.nodes, generated.document.nodes);
+//! ```
+use crate::content_model::{
+    allowed_children, attributes, element_info, ContentCategory, ContentModel,
+};
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag};
+use crate::serialize::{is_boolean_attribute, AttributeOrder, SerializeOptions, VoidElements};
+
+const WORDS: [&str; 16] = [
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "magna",
+];
+
+/// Elements which are not generated inside the body: the document structure,
+/// which is made separately, and elements the tree does not model as tags.
+const SKIPPED_ELEMENTS: [&str; 4] = ["body", "head", "html", "template"];
+
+/// A small, fast random number generator (SplitMix64) so the output only depends
+/// on the seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Gets a number below the bound, which must not be 0.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// Returns true with the chance given as a percentage.
+    fn percent(&mut self, chance: usize) -> bool {
+        self.below(100) < chance
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// A way the written HTML was broken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Defect {
+    /// The end tag of the element was removed.
+    MissingEndTag(String),
+    /// An end tag was added for an element which was not open.
+    StrayEndTag(String),
+    /// Two end tags next to each other were swapped, eg `</b></i>` for `</i></b>`.
+    MisnestedEndTags(String, String),
+    /// The closing quote of an attribute value was removed.
+    UnclosedAttributeValue(String),
+    /// A comment was started and never ended.
+    UnclosedComment,
+}
+
+/// The size and shape of the generated documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenOptions {
+    /// The most elements inside each other, not counting `<html>` and `<body>`.
+    pub max_depth: usize,
+    pub max_children: usize,
+    /// The most nodes in a document, counting elements and text.
+    pub max_nodes: usize,
+    pub max_attributes: usize,
+    /// Make a whole document with a doctype, `<html>`, `<head>` and `<body>` rather
+    /// than a fragment of flow content.
+    pub full_document: bool,
+    /// The number of places the written HTML is broken in.
+    pub defects: usize,
+}
+
+impl Default for GenOptions {
+    fn default() -> Self {
+        GenOptions {
+            max_depth: 6,
+            max_children: 5,
+            max_nodes: 200,
+            max_attributes: 2,
+            full_document: false,
+            defects: 0,
+        }
+    }
+}
+
+impl GenOptions {
+    pub fn new() -> GenOptions {
+        GenOptions::default()
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> GenOptions {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_children(mut self, max_children: usize) -> GenOptions {
+        self.max_children = max_children;
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: usize) -> GenOptions {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    pub fn max_attributes(mut self, max_attributes: usize) -> GenOptions {
+        self.max_attributes = max_attributes;
+        self
+    }
+
+    pub fn full_document(mut self, full_document: bool) -> GenOptions {
+        self.full_document = full_document;
+        self
+    }
+
+    pub fn defects(mut self, defects: usize) -> GenOptions {
+        self.defects = defects;
+        self
+    }
+}
+
+/// A generated document and the HTML written from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generated {
+    pub document: HtmlDocument,
+    /// The document written as HTML, broken by the defects if there are any.
+    pub html: String,
+    /// The ways the HTML was broken, in the order they were made.
+    pub defects: Vec<Defect>,
+}
+
+/// Makes random documents from a seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocGenerator {
+    rng: Rng,
+    options: GenOptions,
+}
+
+impl DocGenerator {
+    pub fn new(seed: u64) -> DocGenerator {
+        DocGenerator {
+            rng: Rng(seed),
+            options: GenOptions::default(),
+        }
+    }
+
+    pub fn options(mut self, options: GenOptions) -> DocGenerator {
+        self.options = options;
+        self
+    }
+
+    /// Makes the next document and writes it as HTML with the defects. Each call
+    /// makes a different document.
+    pub fn generate(&mut self) -> Generated {
+        let document = self.document();
+        // the attributes are sorted so the same seed always writes the same HTML
+        let options = SerializeOptions::new()
+            .attribute_order(AttributeOrder::Alphabetical)
+            .void_elements(VoidElements::StartTag);
+        let mut html = document.to_html_string_with(&options);
+        let defects = (0..self.options.defects)
+            .filter_map(|_| self.add_defect(&mut html))
+            .collect();
+        Generated {
+            document,
+            html,
+            defects,
+        }
+    }
+
+    /// Makes the next valid document.
+    pub fn document(&mut self) -> HtmlDocument {
+        let mut budget = self.options.max_nodes;
+        let mut doc = HtmlDocument::new();
+        if self.options.full_document {
+            let mut head = HtmlTag::new("head");
+            let mut title = HtmlTag::new("title");
+            title.contents.push(HtmlNode::new_text(self.words()));
+            head.contents.push(HtmlNode::Tag(title));
+            budget = budget.saturating_sub(4);
+            let metadata = ["base", "link", "meta", "style"];
+            for _ in 0..self.rng.below(self.options.max_children + 1) {
+                if budget == 0 {
+                    break;
+                }
+                let name = *self.rng.pick(&metadata);
+                let tag = self.element(name, &mut vec!["html", "head"], &mut budget);
+                head.contents.push(HtmlNode::Tag(tag));
+            }
+            let body = self.element("body", &mut vec!["html"], &mut budget);
+            doc.doctype = "html".to_owned();
+            doc.nodes.push(HtmlNode::Tag(
+                HtmlTag::new("html").contents(vec![HtmlNode::Tag(head), HtmlNode::Tag(body)]),
+            ));
+        } else {
+            doc.nodes = self.contents(&mut vec![], &mut budget);
+        }
+        doc
+    }
+
+    fn words(&mut self) -> String {
+        let count = 1 + self.rng.below(4);
+        (0..count)
+            .map(|_| *self.rng.pick(&WORDS))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Makes the element with attributes and contents which are valid for it.
+    fn element(
+        &mut self,
+        name: &'static str,
+        ancestors: &mut Vec<&'static str>,
+        budget: &mut usize,
+    ) -> HtmlTag {
+        *budget = budget.saturating_sub(1);
+        let mut tag = HtmlTag::new(name);
+        let supported = attributes(name);
+        let mut has_boolean = false;
+        let mut has_valued = false;
+        for _ in 0..self.rng.below(self.options.max_attributes + 1) {
+            let attribute = *self.rng.pick(&supported);
+            // the parser misreads a valued attribute written after one without a
+            // value, so an element gets one kind or the other
+            let value = match attribute {
+                "class" => self.words(),
+                "id" => self.rng.pick(&WORDS).to_string(),
+                _ if is_boolean_attribute(attribute) => {
+                    if has_valued {
+                        continue;
+                    }
+                    has_boolean = true;
+                    String::new()
+                }
+                _ => {
+                    if has_boolean {
+                        continue;
+                    }
+                    has_valued = true;
+                    self.rng.pick(&WORDS).to_string()
+                }
+            };
+            tag.set_attribute(attribute, value);
+        }
+        let content = element_info(name).map(|e| e.content);
+        match content {
+            Some(ContentModel::Empty) => {}
+            Some(ContentModel::Text) => {
+                *budget = budget.saturating_sub(1);
+                tag.contents.push(HtmlNode::new_text(self.words()));
+            }
+            _ => {
+                ancestors.push(name);
+                tag.contents = self.contents(ancestors, budget);
+                ancestors.pop();
+            }
+        }
+        tag
+    }
+
+    /// Makes the contents of the last of the ancestors.
+    fn contents(&mut self, ancestors: &mut Vec<&'static str>, budget: &mut usize) -> Vec<HtmlNode> {
+        let depth = ancestors
+            .iter()
+            .filter(|a| !matches!(**a, "html" | "body"))
+            .count();
+        if depth >= self.options.max_depth {
+            return vec![];
+        }
+        let children: Vec<&'static str> = allowed_children(ancestors)
+            .into_iter()
+            .filter(|c| !SKIPPED_ELEMENTS.contains(c))
+            .collect();
+        // text at the top level of a fragment is dropped by the parser
+        let text_allowed = !ancestors.is_empty() && allows_text(ancestors);
+        let mut contents = vec![];
+        for _ in 0..self.rng.below(self.options.max_children + 1) {
+            if *budget == 0 {
+                break;
+            }
+            // text next to text would be read back as one text node
+            let after_text = matches!(contents.last(), Some(HtmlNode::Text(_)));
+            if text_allowed && !after_text && (children.is_empty() || self.rng.percent(40)) {
+                *budget -= 1;
+                contents.push(HtmlNode::new_text(self.words()));
+            } else if !children.is_empty() {
+                let name = *self.rng.pick(&children);
+                contents.push(HtmlNode::Tag(self.element(name, ancestors, budget)));
+            }
+        }
+        contents
+    }
+
+    /// Breaks the HTML in one place, returning how, or None if there was nothing to
+    /// break.
+    fn add_defect(&mut self, html: &mut String) -> Option<Defect> {
+        let end_tags: Vec<(usize, usize)> = tag_ranges(html, "</");
+        match self.rng.below(5) {
+            0 if !end_tags.is_empty() => {
+                let (start, end) = *self.rng.pick(&end_tags);
+                let name = html[start + 2..end - 1].to_owned();
+                html.replace_range(start..end, "");
+                Some(Defect::MissingEndTag(name))
+            }
+            1 => {
+                let name = *self.rng.pick(&["b", "div", "p", "span", "table"]);
+                let at = self.tag_boundary(html);
+                html.insert_str(at, &format!("</{}>", name));
+                Some(Defect::StrayEndTag(name.to_owned()))
+            }
+            2 => {
+                let pairs: Vec<usize> = (1..end_tags.len())
+                    .filter(|i| end_tags[i - 1].1 == end_tags[*i].0)
+                    .filter(|i| {
+                        html[end_tags[i - 1].0..end_tags[i - 1].1]
+                            != html[end_tags[*i].0..end_tags[*i].1]
+                    })
+                    .collect();
+                if pairs.is_empty() {
+                    return self.unclosed_comment(html);
+                }
+                let i = *self.rng.pick(&pairs);
+                let (first, second) = (end_tags[i - 1], end_tags[i]);
+                let a = html[first.0..first.1].to_owned();
+                let b = html[second.0..second.1].to_owned();
+                html.replace_range(first.0..second.1, &format!("{}{}", b, a));
+                Some(Defect::MisnestedEndTags(
+                    a[2..a.len() - 1].to_owned(),
+                    b[2..b.len() - 1].to_owned(),
+                ))
+            }
+            3 => {
+                let quotes: Vec<usize> = html.match_indices("=\"").map(|(i, _)| i).collect();
+                if quotes.is_empty() {
+                    return self.unclosed_comment(html);
+                }
+                let start = *self.rng.pick(&quotes);
+                let name_start = html[..start].rfind(' ').map_or(start, |i| i + 1);
+                let name = html[name_start..start].to_owned();
+                let close = start + 2 + html[start + 2..].find('"')?;
+                html.remove(close);
+                Some(Defect::UnclosedAttributeValue(name))
+            }
+            _ => self.unclosed_comment(html),
+        }
+    }
+
+    fn unclosed_comment(&mut self, html: &mut String) -> Option<Defect> {
+        let at = self.tag_boundary(html);
+        html.insert_str(at, "<!-- ");
+        Some(Defect::UnclosedComment)
+    }
+
+    /// Picks the start of a tag or the end of the HTML.
+    fn tag_boundary(&mut self, html: &str) -> usize {
+        let mut starts: Vec<usize> = html.match_indices('<').map(|(i, _)| i).collect();
+        starts.push(html.len());
+        *self.rng.pick(&starts)
+    }
+}
+
+/// Checks whether text is allowed in the last of the ancestors.
+fn allows_text(ancestors: &[&str]) -> bool {
+    let model = ancestors
+        .iter()
+        .rev()
+        .filter_map(|tag| element_info(tag).map(|e| e.content))
+        .find(|model| *model != ContentModel::Transparent);
+    match model {
+        Some(ContentModel::Categories(categories)) => {
+            categories.contains(&ContentCategory::Flow)
+                || categories.contains(&ContentCategory::Phrasing)
+        }
+        Some(ContentModel::Text) | None => true,
+        _ => false,
+    }
+}
+
+/// Finds the byte ranges of the tags starting with the prefix, eg `</`.
+fn tag_ranges(html: &str, prefix: &str) -> Vec<(usize, usize)> {
+    html.match_indices(prefix)
+        .filter_map(|(start, _)| html[start..].find('>').map(|end| (start, start + end + 1)))
+        .collect()
+}
+
+#[cfg(test)]
+mod testgen_tests {
+    use super::*;
+
+    #[test]
+    fn generated_documents_are_valid_test() {
+        for seed in 0..50 {
+            let options = GenOptions::new().full_document(seed % 2 == 0);
+            let generated = DocGenerator::new(seed).options(options).generate();
+            let parsed = generated.html.parse::<HtmlDocument>().unwrap();
+            assert_eq!(parsed.nodes, generated.document.nodes, "seed {}", seed);
+            assert!(generated.defects.is_empty());
+        }
+    }
+
+    #[test]
+    fn generator_options_test() {
+        let mut generator = DocGenerator::new(1).options(
+            GenOptions::new()
+                .max_depth(2)
+                .max_children(3)
+                .max_nodes(10)
+                .max_attributes(0),
+        );
+        for _ in 0..20 {
+            let doc = generator.document();
+            let html = doc.to_html_string();
+            assert!(!html.contains("=\""));
+            fn depth(nodes: &[HtmlNode]) -> usize {
+                nodes
+                    .iter()
+                    .map(|n| match n {
+                        HtmlNode::Tag(t) => 1 + depth(&t.contents),
+                        _ => 0,
+                    })
+                    .max()
+                    .unwrap_or(0)
+            }
+            // the text of elements like <title> is not limited by the depth
+            assert!(depth(&doc.nodes) <= 2);
+        }
+        // each call makes a new document
+        let mut generator = DocGenerator::new(3);
+        assert_ne!(generator.document(), generator.document());
+    }
+
+    #[test]
+    fn defects_test() {
+        let mut generator =
+            DocGenerator::new(11).options(GenOptions::new().full_document(true).defects(3));
+        for _ in 0..20 {
+            let generated = generator.generate();
+            assert_eq!(generated.defects.len(), 3);
+            let options = SerializeOptions::new()
+                .attribute_order(AttributeOrder::Alphabetical)
+                .void_elements(VoidElements::StartTag);
+            let valid = generated.document.to_html_string_with(&options);
+            assert_ne!(generated.html, valid);
+        }
+    }
+}
@@ -0,0 +1,493 @@
+//! The insertion modes of the HTML tree construction rules, which decide where
+//! browsers put elements whose end tags are left out.
+//!
+//! The parser reads each element up to its end tag. With
+//! [`TreeConstruction::Browser`](crate::options::TreeConstruction::Browser) it
+//! asks [`start_tag_action`] what to do before it reads each start tag. The
+//! answer depends on the insertion mode, which is worked out from the open
+//! elements as the spec does when it resets the insertion mode. A start tag can
+//! close open elements, such as a `<p>` closing the `<p>` before it. It can also
+//! imply an element which is not in the source, such as the `<tbody>` around a
+//! `<tr>` which is directly in a `<table>`. An end tag, or the end of the source,
+//! closes the elements whose end tags are optional, see [`closes_implied`]. An
+//! end tag which matches none of the open elements is ignored, except for a
+//! `</p>` which adds an empty `<p>`, see [`stray_end_tag_node`].
+//!
+//! Foster parenting of content misplaced in tables and the reconstruction of
+//! formatting elements are not done. Misplaced markup is left to the
+//! [`repair`](crate::repair) strategies.
+use crate::objects::{HtmlNode, HtmlTag};
+
+/// The insertion modes which have rules for start tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InsertionMode {
+    /// In an `<html>` element, before its `<body>`.
+    BeforeBody,
+    InHead,
+    InBody,
+    InTable,
+    InTableBody,
+    InRow,
+    InCell,
+    InCaption,
+    InColumnGroup,
+    InSelect,
+}
+
+/// What the parser does with a start tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StartTagAction {
+    /// Parse it as a child of the current element.
+    Insert,
+    /// Close the open element at the index, and the elements inside it, then read
+    /// the tag again.
+    Close(usize),
+    /// Add the element with the name, then read the tag again inside it.
+    Imply(&'static str),
+}
+
+/// Elements whose start tags close an open `<p>`.
+const CLOSES_P: [&str; 38] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "center",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "listing",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "plaintext",
+    "pre",
+    "search",
+    "section",
+    "summary",
+    "table",
+    "ul",
+    "xmp",
+];
+
+/// Elements which are allowed in `<head>`.
+const METADATA: [&str; 11] = [
+    "base", "basefont", "bgsound", "link", "meta", "noframes", "noscript", "script", "style",
+    "template", "title",
+];
+
+/// Elements whose end tags can be left out.
+const OPTIONAL_END: [&str; 19] = [
+    "body", "caption", "colgroup", "dd", "dt", "head", "html", "li", "optgroup", "option", "p",
+    "rp", "rt", "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+/// The elements which end the button scope of an element.
+const BUTTON_SCOPE: [&str; 10] = [
+    "applet", "button", "caption", "html", "marquee", "object", "table", "td", "template", "th",
+];
+
+/// The containers which a `<li>`, `<dd>` or `<dt>` does not close a list item
+/// outside of.
+const LIST_ITEM_BOUNDARY: [&str; 38] = [
+    "applet",
+    "aside",
+    "article",
+    "blockquote",
+    "body",
+    "button",
+    "caption",
+    "center",
+    "details",
+    "dialog",
+    "dir",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "header",
+    "hgroup",
+    "html",
+    "main",
+    "marquee",
+    "menu",
+    "nav",
+    "object",
+    "ol",
+    "section",
+    "select",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+const TABLE_PARTS: [&str; 9] = [
+    "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+fn is_one_of(name: &str, names: &[&str]) -> bool {
+    names.iter().any(|n| name.eq_ignore_ascii_case(n))
+}
+
+fn is_heading(name: &str) -> bool {
+    is_one_of(name, &["h1", "h2", "h3", "h4", "h5", "h6"])
+}
+
+/// Checks whether the end tag of the element can be left out.
+pub(crate) fn has_optional_end_tag(name: &str) -> bool {
+    is_one_of(name, &OPTIONAL_END)
+}
+
+/// Works out the insertion mode from the open elements, along with the index of
+/// the element which sets it.
+pub(crate) fn insertion_mode(open: &[String]) -> (InsertionMode, usize) {
+    let last = open.len().saturating_sub(1);
+    for (i, name) in open.iter().enumerate().rev() {
+        let mode = match name.to_ascii_lowercase().as_str() {
+            "select" => InsertionMode::InSelect,
+            "td" | "th" => InsertionMode::InCell,
+            "tr" => InsertionMode::InRow,
+            "tbody" | "thead" | "tfoot" => InsertionMode::InTableBody,
+            "caption" => InsertionMode::InCaption,
+            "colgroup" => InsertionMode::InColumnGroup,
+            "table" => InsertionMode::InTable,
+            "head" if i == last => InsertionMode::InHead,
+            "html" if i == last => InsertionMode::BeforeBody,
+            "body" | "head" | "html" | "template" => InsertionMode::InBody,
+            _ => continue,
+        };
+        return (mode, i);
+    }
+    (InsertionMode::InBody, 0)
+}
+
+/// Decides what to do with a start tag with the name, from the open elements and
+/// the nodes which have been parsed in the current element.
+pub(crate) fn start_tag_action(
+    open: &[String],
+    siblings: &[HtmlNode],
+    name: &str,
+) -> StartTagAction {
+    let Some(last) = open.len().checked_sub(1) else {
+        return StartTagAction::Insert;
+    };
+    let is_table_part = is_one_of(name, &TABLE_PARTS);
+    let (mode, index) = insertion_mode(open);
+    match mode {
+        InsertionMode::BeforeBody => {
+            let has = |tag: &str| {
+                siblings
+                    .iter()
+                    .any(|n| matches!(n, HtmlNode::Tag(t) if t.tag.eq_ignore_ascii_case(tag)))
+            };
+            if is_one_of(name, &["body", "head", "html"]) || has("body") {
+                StartTagAction::Insert
+            } else if !is_one_of(name, &METADATA) {
+                StartTagAction::Imply("body")
+            } else if !has("head") {
+                StartTagAction::Imply("head")
+            } else {
+                StartTagAction::Insert
+            }
+        }
+        InsertionMode::InHead if !is_one_of(name, &METADATA) => StartTagAction::Close(last),
+        InsertionMode::InTable if is_one_of(name, &["td", "th", "tr"]) => {
+            StartTagAction::Imply("tbody")
+        }
+        InsertionMode::InTable if name.eq_ignore_ascii_case("col") => {
+            StartTagAction::Imply("colgroup")
+        }
+        InsertionMode::InTableBody if is_one_of(name, &["td", "th"]) => StartTagAction::Imply("tr"),
+        InsertionMode::InTableBody if is_table_part && !name.eq_ignore_ascii_case("tr") => {
+            StartTagAction::Close(index)
+        }
+        InsertionMode::InRow if is_table_part && !is_one_of(name, &["td", "th"]) => {
+            StartTagAction::Close(index)
+        }
+        InsertionMode::InCell | InsertionMode::InCaption if is_table_part => {
+            StartTagAction::Close(index)
+        }
+        InsertionMode::InColumnGroup if !is_one_of(name, &["col", "template"]) => {
+            StartTagAction::Close(index)
+        }
+        InsertionMode::InSelect => select_start_tag(open, name),
+        InsertionMode::InBody | InsertionMode::InCell | InsertionMode::InCaption => {
+            body_start_tag(open, name)
+        }
+        _ => StartTagAction::Insert,
+    }
+}
+
+/// The rules of the "in body" insertion mode.
+fn body_start_tag(open: &[String], name: &str) -> StartTagAction {
+    let last = open.len() - 1;
+    let close_p = || match in_button_scope(open, "p") {
+        Some(i) => StartTagAction::Close(i),
+        None => StartTagAction::Insert,
+    };
+    if is_one_of(name, &CLOSES_P) {
+        match close_p() {
+            StartTagAction::Insert if is_heading(name) && is_heading(&open[last]) => {
+                StartTagAction::Close(last)
+            }
+            action => action,
+        }
+    } else if is_one_of(name, &["li", "dd", "dt"]) {
+        let items: &[&str] = if name.eq_ignore_ascii_case("li") {
+            &["li"]
+        } else {
+            &["dd", "dt"]
+        };
+        for (i, open_name) in open.iter().enumerate().rev() {
+            if is_one_of(open_name, items) {
+                return StartTagAction::Close(i);
+            }
+            if is_one_of(open_name, &LIST_ITEM_BOUNDARY) {
+                break;
+            }
+        }
+        close_p()
+    } else if is_one_of(name, &["option", "optgroup"]) && open[last].eq_ignore_ascii_case("option")
+    {
+        StartTagAction::Close(last)
+    } else {
+        StartTagAction::Insert
+    }
+}
+
+/// The rules of the "in select" insertion mode.
+fn select_start_tag(open: &[String], name: &str) -> StartTagAction {
+    let last = open.len() - 1;
+    let current_is = |i: usize, tag: &str| open[i].eq_ignore_ascii_case(tag);
+    if name.eq_ignore_ascii_case("option") && current_is(last, "option") {
+        StartTagAction::Close(last)
+    } else if name.eq_ignore_ascii_case("optgroup") {
+        if current_is(last, "option") && last > 0 && current_is(last - 1, "optgroup") {
+            StartTagAction::Close(last - 1)
+        } else if current_is(last, "option") || current_is(last, "optgroup") {
+            StartTagAction::Close(last)
+        } else {
+            StartTagAction::Insert
+        }
+    } else {
+        StartTagAction::Insert
+    }
+}
+
+/// Finds the innermost open element with the name, unless an element which ends
+/// the button scope is open inside it.
+fn in_button_scope(open: &[String], name: &str) -> Option<usize> {
+    for (i, open_name) in open.iter().enumerate().rev() {
+        if open_name.eq_ignore_ascii_case(name) {
+            return Some(i);
+        }
+        if is_one_of(open_name, &BUTTON_SCOPE) {
+            return None;
+        }
+    }
+    None
+}
+
+/// Checks whether an end tag which does not match the current element closes it
+/// because the end tags of it, and of the other elements inside the one which
+/// matches, can be left out.
+pub(crate) fn closes_implied(open: &[String], end_tag: &str) -> bool {
+    match open.iter().rposition(|n| n == end_tag) {
+        Some(i) => open[i + 1..].iter().all(|n| has_optional_end_tag(n)),
+        None => false,
+    }
+}
+
+/// Gets the node which browsers add for an end tag which matches none of the open
+/// elements: a `</p>` adds an empty `<p>`, and any other end tag is ignored.
+pub(crate) fn stray_end_tag_node(end_tag: &str) -> Option<HtmlNode> {
+    end_tag
+        .eq_ignore_ascii_case("p")
+        .then(|| HtmlNode::Tag(HtmlTag::new(end_tag)))
+}
+
+/// Gets the name of the start tag at the start of the text, which is just after
+/// a `<`.
+pub(crate) fn start_tag_name(text: &str) -> Option<&str> {
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let end = text
+        .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        .unwrap_or(text.len());
+    Some(&text[..end])
+}
+
+#[cfg(test)]
+mod tree_construction_tests {
+    use super::*;
+    use crate::objects::{parse_document, HtmlDocument};
+    use crate::options::{ParseOptions, TreeConstruction};
+    use crate::parsing::SpanRecorder;
+
+    fn parse(source: &str) -> String {
+        let options = ParseOptions::new().tree_construction(TreeConstruction::Browser);
+        HtmlDocument::parse_with(source, &options)
+            .unwrap()
+            .to_html_string()
+    }
+
+    #[test]
+    fn body_mode_test() {
+        assert!("<p>One<p>Two".parse::<HtmlDocument>().is_err());
+        assert_eq!(
+            parse("<p>One<p>Two<div>Three</div>"),
+            "<p>One</p><p>Two</p><div>Three</div>"
+        );
+        // a block closes the p through the elements inside it
+        assert_eq!(
+            parse("<p>a<span>b<ul><li>c</ul>"),
+            "<p>a<span>b</span></p><ul><li>c</li></ul>"
+        );
+        assert_eq!(
+            parse("<ul><li>a<li>b<ul><li>c<li>d</ul><li>e</ul>"),
+            "<ul><li>a</li><li>b<ul><li>c</li><li>d</li></ul></li><li>e</li></ul>"
+        );
+        assert_eq!(
+            parse("<dl><dt>a<dd>b<dt>c</dl>"),
+            "<dl><dt>a</dt><dd>b</dd><dt>c</dt></dl>"
+        );
+        assert_eq!(parse("<h1>a<h2>b</h2>"), "<h1>a</h1><h2>b</h2>");
+        assert_eq!(
+            parse("<select><optgroup><option>a<optgroup><option>b<option>c</select>"),
+            "<select><optgroup><option>a</option></optgroup><optgroup><option>b</option><option>c</option></optgroup></select>"
+        );
+        // inside a button the p is out of scope
+        assert_eq!(
+            parse("<p><button>a<div>b</div></button></p>"),
+            "<p><button>a<div>b</div></button></p>"
+        );
+    }
+
+    #[test]
+    fn stray_end_tag_test() {
+        assert!("<p>a</p></p>".parse::<HtmlDocument>().is_err());
+        assert_eq!(parse("<p>a</p></p>"), "<p>a</p><p></p>");
+        assert_eq!(parse("</div><p>a</p>"), "<p>a</p>");
+        assert_eq!(parse("<div>a</span>b</p></div>"), "<div>ab<p></p></div>");
+        // an end tag which matches an open element still closes it
+        assert_eq!(parse("<ul><li>a</ul>"), "<ul><li>a</li></ul>");
+    }
+
+    #[test]
+    fn table_mode_test() {
+        assert_eq!(
+            parse("<table><tr><td>a<td>b<tr><th>c</table>"),
+            "<table><tbody><tr><td>a</td><td>b</td></tr><tr><th>c</th></tr></tbody></table>"
+        );
+        assert_eq!(
+            parse("<table><thead><tr><th>h<tbody><tr><td><p>x</table>"),
+            "<table><thead><tr><th>h</th></tr></thead><tbody><tr><td><p>x</p></td></tr></tbody></table>"
+        );
+        assert_eq!(
+            parse("<table><caption>c<tr><td>a</td></tr></table>"),
+            "<table><caption>c</caption><tbody><tr><td>a</td></tr></tbody></table>"
+        );
+        // tables inside cells have their own modes
+        assert_eq!(
+            parse("<table><td><table><td>a</table>b</table>"),
+            "<table><tbody><tr><td><table><tbody><tr><td>a</td></tr></tbody></table>b</td></tr></tbody></table>"
+        );
+    }
+
+    #[test]
+    fn head_mode_test() {
+        assert_eq!(
+            parse("<html><head><title>T</title><p>x</p></html>"),
+            "<html><head><title>T</title></head><body><p>x</p></body></html>"
+        );
+        assert_eq!(
+            parse("<html><meta charset=utf-8><div>x</div></html>"),
+            "<html><head><meta charset=\"utf-8\"></meta></head><body><div>x</div></body></html>"
+        );
+        assert_eq!(
+            parse("<html><head></head><body><p>x</body></html>"),
+            "<html><head></head><body><p>x</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn tree_construction_spans_test() {
+        let source = "<p>a<table><td>b</table>c";
+        let options = ParseOptions::new().tree_construction(TreeConstruction::Browser);
+        let mut recorder = SpanRecorder::new(source);
+        parse_document(source, &options, Some(&mut recorder)).unwrap();
+        let mut spans: Vec<_> = recorder
+            .spans
+            .iter()
+            .map(|s| (s.path.clone(), &source[s.range.clone()]))
+            .collect();
+        spans.sort();
+        assert_eq!(
+            spans,
+            vec![
+                (vec![0], "<p>a"),
+                (vec![0, 0], "a"),
+                (vec![1], "<table><td>b</table>"),
+                // the implied elements start and end with their contents
+                (vec![1, 0], "<td>b"),
+                (vec![1, 0, 0], "<td>b"),
+                // the td is closed by the </table>
+                (vec![1, 0, 0, 0], "<td>b</table>"),
+                (vec![1, 0, 0, 0, 0], "b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn insertion_mode_test() {
+        let open = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            insertion_mode(&open(&[
+                "html", "body", "table", "tbody", "tr", "td", "div"
+            ])),
+            (InsertionMode::InCell, 5)
+        );
+        assert_eq!(
+            insertion_mode(&open(&["html", "head"])),
+            (InsertionMode::InHead, 1)
+        );
+        assert_eq!(
+            insertion_mode(&open(&["html", "head", "noscript"])),
+            (InsertionMode::InBody, 1)
+        );
+        assert_eq!(insertion_mode(&open(&["div"])), (InsertionMode::InBody, 0));
+        assert!(closes_implied(&open(&["ul", "li", "p"]), "ul"));
+        assert!(!closes_implied(&open(&["ul", "li", "b"]), "ul"));
+        assert_eq!(start_tag_name("td class=a>"), Some("td"));
+        assert_eq!(start_tag_name("br/>"), Some("br"));
+        assert_eq!(start_tag_name("/td>"), None);
+    }
+}
@@ -0,0 +1,228 @@
+//! A visitor interface for walking and rewriting the nodes of a [`HtmlDocument`].
+//!
+//! Passes such as sanitizers, rewriters and linters implement the [`Visit`] trait,
+//! only overriding the hooks they care about, and use [`walk_document`] (or
+//! [`HtmlDocument::visit`]) to drive the walk. Each hook returns a [`VisitAction`]
+//! which tells the driver whether to continue, skip the children of an element,
+//! remove the node or replace it with other nodes.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::objects::{HtmlDocument, HtmlTag};
+//! use hb_html::visit::{Visit, VisitAction, VisitContext};
+//! struct DropScripts;
+//! impl Visit for DropScripts {
+//!     fn enter_element(&mut self, tag: &mut HtmlTag, _ctx: &VisitContext) -> VisitAction {
+//!         if tag.tag == "script" {
+//!             return VisitAction::Remove;
+//!         }
+//!         VisitAction::Continue
+//!     }
+//! }
+//! let mut doc = "<div><script>alert(1)</script><p>Hi</p></div>"
+//!     .parse::<HtmlDocument>()
+//!     .unwrap();
+//! doc.visit(&mut DropScripts);
+//! assert_eq!(doc.find("script").nodes().len(), 0);
+//! assert_eq!(doc.find("p").nodes().len(), 1);
+//! ```
+use crate::objects::{HtmlDocument, HtmlNode, HtmlTag, NodePath};
+
+/// What the driver should do after a hook has been called for a node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VisitAction {
+    /// Keep walking as normal.
+    Continue,
+    /// Do not walk the contents of the element. This is the same as Continue when
+    /// returned from anything other than [`Visit::enter_element`].
+    SkipChildren,
+    /// Replace the node with the nodes provided. The replacement nodes are not walked.
+    Replace(Vec<HtmlNode>),
+    /// Remove the node (and its contents) from the tree.
+    Remove,
+}
+
+/// Information about where the node being visited is in the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisitContext {
+    /// The path to the node being visited.
+    pub path: NodePath,
+    /// The tag names of the elements containing the node, starting from the top level.
+    pub ancestors: Vec<String>,
+}
+
+impl VisitContext {
+    /// Returns true if the node is somewhere inside an element with the tag provided.
+    pub fn is_inside(&self, tag: &str) -> bool {
+        self.ancestors.iter().any(|t| t == tag)
+    }
+}
+
+/// Hooks which are called while walking a tree of [`HtmlNode`] objects.
+/// All hooks default to returning [`VisitAction::Continue`] without changing anything.
+pub trait Visit {
+    /// Called for an element before its contents are walked.
+    fn enter_element(&mut self, _tag: &mut HtmlTag, _ctx: &VisitContext) -> VisitAction {
+        VisitAction::Continue
+    }
+    /// Called for an element after its contents have been walked.
+    fn exit_element(&mut self, _tag: &mut HtmlTag, _ctx: &VisitContext) -> VisitAction {
+        VisitAction::Continue
+    }
+    /// Called for each text node.
+    fn text(&mut self, _text: &mut String, _ctx: &VisitContext) -> VisitAction {
+        VisitAction::Continue
+    }
+    /// Called for each comment node.
+    fn comment(&mut self, _comment: &mut String, _ctx: &VisitContext) -> VisitAction {
+        VisitAction::Continue
+    }
+    /// Called for each template tag node.
+    fn template(&mut self, _template: &mut String, _ctx: &VisitContext) -> VisitAction {
+        VisitAction::Continue
+    }
+}
+
+/// Walks all of the nodes in the document, calling the hooks on the visitor.
+pub fn walk_document<V: Visit + ?Sized>(doc: &mut HtmlDocument, visitor: &mut V) {
+    let mut ctx = VisitContext {
+        path: vec![],
+        ancestors: vec![],
+    };
+    walk_nodes(&mut doc.nodes, visitor, &mut ctx);
+}
+
+/// Walks a list of nodes (and their contents), calling the hooks on the visitor.
+/// The context provided is used as the position of the list in the wider tree.
+pub fn walk_nodes<V: Visit + ?Sized>(
+    nodes: &mut Vec<HtmlNode>,
+    visitor: &mut V,
+    ctx: &mut VisitContext,
+) {
+    let mut i = 0;
+    while i < nodes.len() {
+        ctx.path.push(i);
+        let action = match &mut nodes[i] {
+            HtmlNode::Tag(t) => {
+                let mut action = visitor.enter_element(t, ctx);
+                if action == VisitAction::Continue || action == VisitAction::SkipChildren {
+                    if action == VisitAction::Continue {
+                        ctx.ancestors.push(t.tag.clone());
+                        walk_nodes(&mut t.contents, visitor, ctx);
+                        ctx.ancestors.pop();
+                    }
+                    action = visitor.exit_element(t, ctx);
+                }
+                action
+            }
+            HtmlNode::Text(t) => visitor.text(t, ctx),
+            HtmlNode::Comment(c) => visitor.comment(c, ctx),
+            HtmlNode::Template(t) => visitor.template(t, ctx),
+        };
+        ctx.path.pop();
+        match action {
+            VisitAction::Continue | VisitAction::SkipChildren => i += 1,
+            VisitAction::Remove => {
+                nodes.remove(i);
+            }
+            VisitAction::Replace(replacements) => {
+                let n = replacements.len();
+                nodes.splice(i..i + 1, replacements);
+                i += n;
+            }
+        }
+    }
+}
+
+impl HtmlDocument {
+    /// Walks the document with the visitor provided. See [`walk_document`].
+    pub fn visit<V: Visit + ?Sized>(&mut self, visitor: &mut V) {
+        walk_document(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod visit_tests {
+    use super::*;
+
+    /// Records the order in which hooks are called.
+    struct Recorder {
+        events: Vec<String>,
+    }
+    impl Visit for Recorder {
+        fn enter_element(&mut self, tag: &mut HtmlTag, ctx: &VisitContext) -> VisitAction {
+            self.events
+                .push(format!("enter {} {:?}", tag.tag, ctx.path));
+            if tag.tag == "skip" {
+                return VisitAction::SkipChildren;
+            }
+            VisitAction::Continue
+        }
+        fn exit_element(&mut self, tag: &mut HtmlTag, _ctx: &VisitContext) -> VisitAction {
+            self.events.push(format!("exit {}", tag.tag));
+            VisitAction::Continue
+        }
+        fn text(&mut self, text: &mut String, ctx: &VisitContext) -> VisitAction {
+            self.events
+                .push(format!("text {} {:?}", text, ctx.ancestors));
+            VisitAction::Continue
+        }
+        fn comment(&mut self, comment: &mut String, _ctx: &VisitContext) -> VisitAction {
+            self.events.push(format!("comment {}", comment));
+            VisitAction::Continue
+        }
+    }
+
+    #[test]
+    fn visit_order_test() {
+        let mut doc = "<div>a<skip>b</skip><p>c<!-- d --></p></div>"
+            .parse::<HtmlDocument>()
+            .unwrap();
+        let mut recorder = Recorder { events: vec![] };
+        doc.visit(&mut recorder);
+        assert_eq!(
+            recorder.events,
+            vec![
+                "enter div [0]",
+                "text a [\"div\"]",
+                "enter skip [0, 1]",
+                "exit skip",
+                "enter p [0, 2]",
+                "text c [\"div\", \"p\"]",
+                "comment  d ",
+                "exit p",
+                "exit div",
+            ]
+        );
+    }
+
+    struct Rewriter;
+    impl Visit for Rewriter {
+        fn enter_element(&mut self, tag: &mut HtmlTag, _ctx: &VisitContext) -> VisitAction {
+            match tag.tag.as_str() {
+                "b" => VisitAction::Replace(vec![
+                    HtmlNode::Tag(HtmlTag::new("strong").contents(tag.contents.clone())),
+                    HtmlNode::new_text("!"),
+                ]),
+                "i" => VisitAction::Remove,
+                _ => VisitAction::Continue,
+            }
+        }
+        fn text(&mut self, text: &mut String, ctx: &VisitContext) -> VisitAction {
+            if ctx.is_inside("p") {
+                *text = text.to_uppercase();
+            }
+            VisitAction::Continue
+        }
+    }
+
+    #[test]
+    fn visit_mutation_test() {
+        let mut doc = "<p>x<b>y</b><i>z</i>w</p>".parse::<HtmlDocument>().unwrap();
+        doc.visit(&mut Rewriter);
+        let p = doc.node_at(&[0]).unwrap();
+        // replacements are not walked so the text inside strong is not upper cased
+        assert_eq!(p.to_html_string(), "<p>X<strong>y</strong>!W</p>");
+    }
+}
@@ -0,0 +1,820 @@
+//! A set of documents which make up a site, for checks which need more than one
+//! file.
+//!
+//! A [`Workspace`] holds documents by name, which is their path in the site such
+//! as `index.html` or `blog/post.html`. It can find the pages matching a selector,
+//! find links between the pages which do not go anywhere, and keeps the
+//! [`Diagnostic`]s of each file, such as parse errors and the messages from a
+//! [`Pipeline`] run over every file.
+//!
+//! The links between the pages form a [`LinkGraph`], which finds the pages that
+//! can not be reached from the home page and is used to write a sitemap.
+//!
+//! Links are resolved against the name of the file they are in. Links to a
+//! directory, eg `/blog/`, go to its `index.html`, and links without an extension
+//! also match the file with `.html` added.
+//!
+//! # Example
+//!
+//! ```
+//! use hb_html::workspace::{BrokenLinkReason, Workspace};
+//! let mut site = Workspace::new();
+//! site.insert_source("index.html", r##"<a href="about.html#team">About</a><a href="/blog/">Blog</a>"##);
+//! site.insert_source("about.html", r#"<h2 id="people">People</h2>"#);
+//! site.insert_source("blog/index.html", r#"<a href="../index.html">Home</a><p class="post"></p>"#);
+//! let broken = site.broken_links();
+//! assert_eq!(broken.len(), 1);
+//! assert_eq!(broken[0].file, "index.html");
+//! assert_eq!(broken[0].reason, BrokenLinkReason::MissingAnchor("team".to_owned()));
+//! assert_eq!(site.pages_matching("p.post").unwrap(), vec!["blog/index.html"]);
+//! ```
+use crate::entities::escape_text;
+use crate::error::ParseHtmlError;
+use crate::fingerprint::{FingerprintAssets, FingerprintReport};
+use crate::hints::walk_tags;
+use crate::objects::{CssSelector, HtmlDocument, NodePath};
+use crate::pipeline::{Diagnostic, Pipeline, Severity};
+use crate::querying::HtmlQuery;
+use crate::resources::{resolve_specifier, url_origin};
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+
+/// A document in a [`Workspace`] and the diagnostics reported for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceFile {
+    /// The document, or an empty document if the source could not be parsed.
+    pub document: HtmlDocument,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Where a link in a file of a [`Workspace`] goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// Somewhere outside the workspace, eg another site or a `mailto:` address.
+    External,
+    /// A file in the workspace, and the fragment of the link if it has one.
+    File {
+        name: String,
+        fragment: Option<String>,
+    },
+    /// A path in the site with no file in the workspace.
+    Missing(String),
+}
+
+/// Why a link in the workspace does not go anywhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrokenLinkReason {
+    /// There is no file at the path.
+    MissingFile(String),
+    /// The file exists but has no element with the id.
+    MissingAnchor(String),
+}
+
+/// A link in the workspace which does not go anywhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// The name of the file the link is in.
+    pub file: String,
+    /// The path of the link element in the file.
+    pub path: NodePath,
+    pub href: String,
+    pub reason: BrokenLinkReason,
+}
+
+/// The documents of a site, by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Workspace {
+    files: BTreeMap<String, WorkspaceFile>,
+}
+
+impl Workspace {
+    pub fn new() -> Workspace {
+        Workspace::default()
+    }
+
+    /// Adds the document, replacing any file with the same name along with its
+    /// diagnostics.
+    pub fn insert<N: Into<String>>(&mut self, name: N, document: HtmlDocument) {
+        self.files.insert(
+            name.into(),
+            WorkspaceFile {
+                document,
+                diagnostics: vec![],
+            },
+        );
+    }
+
+    /// Parses the source and adds it. If it can not be parsed the file is added
+    /// with an empty document and the error as a diagnostic of the `parse` pass,
+    /// and false is returned.
+    pub fn insert_source<N: Into<String>>(&mut self, name: N, source: &str) -> bool {
+        let name = name.into();
+        match source.parse::<HtmlDocument>() {
+            Ok(document) => {
+                self.insert(name, document);
+                true
+            }
+            Err(e) => {
+                self.insert(name.clone(), HtmlDocument::new());
+                self.report(&name, "parse", Severity::Error, e.to_string(), None);
+                false
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<WorkspaceFile> {
+        self.files.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HtmlDocument> {
+        self.files.get(name).map(|f| &f.document)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut HtmlDocument> {
+        self.files.get_mut(name).map(|f| &mut f.document)
+    }
+
+    pub fn file(&self, name: &str) -> Option<&WorkspaceFile> {
+        self.files.get(name)
+    }
+
+    /// The names of the files, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(|n| n.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Adds a diagnostic to the file, doing nothing if there is no file with the
+    /// name.
+    pub fn report<S: Into<String>>(
+        &mut self,
+        name: &str,
+        pass: &str,
+        severity: Severity,
+        message: S,
+        path: Option<NodePath>,
+    ) {
+        if let Some(file) = self.files.get_mut(name) {
+            file.diagnostics.push(Diagnostic {
+                pass: pass.to_owned(),
+                severity,
+                message: message.into(),
+                path,
+            });
+        }
+    }
+
+    /// Gets the diagnostics of the file, which is empty if there is no file with
+    /// the name.
+    pub fn diagnostics(&self, name: &str) -> &[Diagnostic] {
+        self.files.get(name).map_or(&[], |f| &f.diagnostics)
+    }
+
+    /// Gets the diagnostics of all of the files, with the name of the file.
+    pub fn all_diagnostics(&self) -> Vec<(&str, &Diagnostic)> {
+        self.files
+            .iter()
+            .flat_map(|(name, file)| file.diagnostics.iter().map(move |d| (name.as_str(), d)))
+            .collect()
+    }
+
+    /// Returns true if any file has a diagnostic which is an error.
+    pub fn has_errors(&self) -> bool {
+        self.all_diagnostics()
+            .iter()
+            .any(|(_, d)| d.severity == Severity::Error)
+    }
+
+    /// Runs the pipeline over every file, adding the diagnostics to the files. A
+    /// file whose pipeline stopped on an error gets the error as a diagnostic of
+    /// the `pipeline` pass.
+    pub fn run_pipeline(&mut self, pipeline: &mut Pipeline) {
+        for file in self.files.values_mut() {
+            match pipeline.run(&mut file.document) {
+                Ok(report) => file.diagnostics.extend(report.diagnostics),
+                Err(e) => file.diagnostics.push(Diagnostic {
+                    pass: "pipeline".to_owned(),
+                    severity: Severity::Error,
+                    message: e.to_string(),
+                    path: None,
+                }),
+            }
+        }
+    }
+
+    /// Gets the names of the files with an element matching the selector.
+    pub fn pages_matching(&self, selector: &str) -> Result<Vec<&str>, ParseHtmlError> {
+        Ok(self
+            .find_all(selector)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    /// Finds the elements matching the selector in every file, giving the name of
+    /// each file with matches and the paths of the matches in it.
+    pub fn find_all(&self, selector: &str) -> Result<Vec<(&str, Vec<NodePath>)>, ParseHtmlError> {
+        let selector = CssSelector::from_str(selector)?;
+        let mut found = vec![];
+        // querying an empty document is not supported, and it has no matches
+        for (name, file) in self
+            .files
+            .iter()
+            .filter(|(_, f)| !f.document.nodes.is_empty())
+        {
+            let mut query = HtmlQuery::new(&file.document.nodes);
+            query.find(&selector);
+            let paths: Vec<NodePath> = query.results.iter().map(|r| r.node_path()).collect();
+            if !paths.is_empty() {
+                found.push((name.as_str(), paths));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Works out where a link in the file goes.
+    pub fn resolve_link(&self, from: &str, href: &str) -> LinkTarget {
+        let fragment = href.trim().split_once('#').map(|(_, f)| f.to_owned());
+        let path = match site_path(from, href) {
+            Some(path) => path,
+            None => return LinkTarget::External,
+        };
+        let candidates = if path.is_empty() || path.ends_with('/') {
+            vec![format!("{}index.html", path)]
+        } else {
+            vec![
+                path.to_owned(),
+                format!("{}.html", path),
+                format!("{}/index.html", path),
+            ]
+        };
+        match candidates.into_iter().find(|c| self.files.contains_key(c)) {
+            Some(name) => LinkTarget::File { name, fragment },
+            None => LinkTarget::Missing(path),
+        }
+    }
+
+    /// Finds the links in the files which go to a missing file, or to a fragment
+    /// which is not the id of an element in the file. Empty fragments and `#top`
+    /// always go to the top of the page.
+    pub fn broken_links(&self) -> Vec<BrokenLink> {
+        let mut anchors: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+        let mut broken = vec![];
+        for (name, file) in &self.files {
+            for link in links(&file.document) {
+                let reason = match self.resolve_link(name, &link.href) {
+                    LinkTarget::External => continue,
+                    LinkTarget::Missing(path) => BrokenLinkReason::MissingFile(path),
+                    LinkTarget::File {
+                        fragment: Some(fragment),
+                        name: target,
+                    } if !fragment.is_empty() && !fragment.eq_ignore_ascii_case("top") => {
+                        let ids = anchors
+                            .entry(target.clone())
+                            .or_insert_with(|| anchor_names(&self.files[&target].document));
+                        if ids.contains(&fragment) {
+                            continue;
+                        }
+                        BrokenLinkReason::MissingAnchor(fragment)
+                    }
+                    LinkTarget::File { .. } => continue,
+                };
+                broken.push(BrokenLink {
+                    file: name.clone(),
+                    path: link.path,
+                    href: link.href,
+                    reason,
+                });
+            }
+        }
+        broken
+    }
+
+    /// Adds a warning to the files for each of their [`broken_links`](Workspace::broken_links),
+    /// from the `links` pass, returning the number of broken links.
+    pub fn check_links(&mut self) -> usize {
+        let broken = self.broken_links();
+        for link in &broken {
+            let message = match &link.reason {
+                BrokenLinkReason::MissingFile(path) => {
+                    format!("link to {} goes to a missing file {}", link.href, path)
+                }
+                BrokenLinkReason::MissingAnchor(id) => {
+                    format!("link to {} goes to a missing anchor {}", link.href, id)
+                }
+            };
+            self.report(
+                &link.file,
+                "links",
+                Severity::Warning,
+                message,
+                Some(link.path.clone()),
+            );
+        }
+        broken.len()
+    }
+
+    /// Rewrites the asset URLs in every file to their fingerprinted files. URLs to
+    /// files of the workspace are not reported as unmapped, and a warning from the
+    /// `fingerprint` pass is added for each URL which is.
+    pub fn fingerprint_assets(&mut self, assets: &FingerprintAssets) -> FingerprintReport {
+        let mut report = FingerprintReport::default();
+        for (name, file) in self.files.iter_mut() {
+            report.merge(assets.rewrite(name, &mut file.document));
+        }
+        report
+            .unmapped
+            .retain(|u| !matches!(self.resolve_link(&u.file, &u.url), LinkTarget::File { .. }));
+        for unmapped in &report.unmapped {
+            self.report(
+                &unmapped.file,
+                "fingerprint",
+                Severity::Warning,
+                format!("no fingerprinted file for {}", unmapped.url),
+                Some(unmapped.path.clone()),
+            );
+        }
+        report
+    }
+
+    /// Gets the graph of the links between the files. Links out of the workspace
+    /// and to missing files are left out, and links within a page are edges from
+    /// the page to itself.
+    pub fn link_graph(&self) -> LinkGraph {
+        let mut edges = vec![];
+        for (name, file) in &self.files {
+            for link in links(&file.document) {
+                if let LinkTarget::File { name: to, fragment } = self.resolve_link(name, &link.href)
+                {
+                    edges.push(LinkEdge {
+                        from: name.clone(),
+                        to,
+                        fragment,
+                        path: link.path,
+                        rel: link.rel,
+                        text: link.text,
+                    });
+                }
+            }
+        }
+        LinkGraph {
+            pages: self.files.keys().cloned().collect(),
+            edges,
+        }
+    }
+
+    /// Writes an XML sitemap of the pages which can be reached from the roots, or
+    /// of every page if there are no roots, in order. Pages with a `noindex` robots meta tag
+    /// are left out. The URLs are the names of the pages after the base URL, with
+    /// `index.html` pages written as their directory.
+    ///
+    /// ```
+    /// use hb_html::workspace::Workspace;
+    /// let mut site = Workspace::new();
+    /// site.insert_source("index.html", r#"<a href="a.html">A</a>"#);
+    /// site.insert_source("a.html", "<p>A</p>");
+    /// site.insert_source("draft.html", "<p>Draft</p>");
+    /// assert_eq!(
+    ///     site.sitemap("https://site.example/", &["index.html"]),
+    ///     concat!(
+    ///         "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    ///         "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    ///         "  <url><loc>https://site.example/a.html</loc></url>\n",
+    ///         "  <url><loc>https://site.example/</loc></url>\n",
+    ///         "</urlset>\n"
+    ///     )
+    /// );
+    /// ```
+    pub fn sitemap(&self, base_url: &str, roots: &[&str]) -> String {
+        let graph = self.link_graph();
+        let pages = if roots.is_empty() {
+            self.names().collect()
+        } else {
+            graph.reachable_from(roots)
+        };
+        let base_url = base_url.trim_end_matches('/');
+        let mut sitemap = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        sitemap.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        for page in pages {
+            if is_noindex(&self.files[page].document) {
+                continue;
+            }
+            let path = match page.strip_suffix("index.html") {
+                Some(dir) if dir.is_empty() || dir.ends_with('/') => dir,
+                _ => page,
+            };
+            let url = format!("{}/{}", base_url, path);
+            sitemap.push_str(&format!("  <url><loc>{}</loc></url>\n", escape_text(&url)));
+        }
+        sitemap.push_str("</urlset>\n");
+        sitemap
+    }
+}
+
+/// A link from one page of a [`Workspace`] to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkEdge {
+    pub from: String,
+    pub to: String,
+    pub fragment: Option<String>,
+    /// The path of the link element in the page it is in.
+    pub path: NodePath,
+    /// The link types in the `rel` attribute in lower case, eg `nofollow`.
+    pub rel: Vec<String>,
+    /// The text of the link with the whitespace collapsed, or the `alt` text of
+    /// an `<area>`.
+    pub text: String,
+}
+
+/// The pages of a [`Workspace`] and the links between them, from
+/// [`Workspace::link_graph`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkGraph {
+    /// The names of the pages, in order.
+    pub pages: Vec<String>,
+    /// The links in the order of the pages they are in, then document order.
+    pub edges: Vec<LinkEdge>,
+}
+
+impl LinkGraph {
+    pub fn links_from(&self, page: &str) -> Vec<&LinkEdge> {
+        self.edges.iter().filter(|e| e.from == page).collect()
+    }
+
+    pub fn links_to(&self, page: &str) -> Vec<&LinkEdge> {
+        self.edges.iter().filter(|e| e.to == page).collect()
+    }
+
+    /// Gets the pages which can be reached by following links from the roots,
+    /// including the roots, in order.
+    pub fn reachable_from(&self, roots: &[&str]) -> Vec<&str> {
+        let mut reached: HashSet<&str> = HashSet::new();
+        let mut queue: Vec<&str> = roots
+            .iter()
+            .filter_map(|r| self.pages.iter().find(|p| p == r))
+            .map(|p| p.as_str())
+            .collect();
+        while let Some(page) = queue.pop() {
+            if reached.insert(page) {
+                queue.extend(self.links_from(page).iter().map(|e| e.to.as_str()));
+            }
+        }
+        self.pages
+            .iter()
+            .map(|p| p.as_str())
+            .filter(|p| reached.contains(p))
+            .collect()
+    }
+
+    /// Gets the pages which can not be reached by following links from the
+    /// roots, which are usually the home page, in order.
+    pub fn orphans(&self, roots: &[&str]) -> Vec<&str> {
+        let reached: HashSet<&str> = self.reachable_from(roots).into_iter().collect();
+        self.pages
+            .iter()
+            .map(|p| p.as_str())
+            .filter(|p| !reached.contains(p))
+            .collect()
+    }
+}
+
+/// Gets the path in the site, without a leading `/`, of a URL in the file with
+/// the name provided, leaving out the query and fragment. Returns None for URLs
+/// which go outside the site, such as full URLs and `mailto:` addresses.
+pub(crate) fn site_path(from: &str, url: &str) -> Option<String> {
+    let url = url.trim();
+    let url = url.split(['?', '#']).next().unwrap_or("");
+    let has_scheme = url
+        .find(':')
+        .is_some_and(|i| i > 0 && !url[..i].contains('/'));
+    if has_scheme || url_origin(url).is_some() {
+        return None;
+    }
+    let path = if url.is_empty() {
+        from.to_owned()
+    } else if let Some(absolute) = url.strip_prefix('/') {
+        absolute.to_owned()
+    } else if url.starts_with("./") || url.starts_with("../") {
+        resolve_specifier(from, url)
+    } else {
+        resolve_specifier(from, &format!("./{}", url))
+    };
+    Some(path.trim_start_matches('/').to_owned())
+}
+
+/// An `<a>` or `<area>` element with an `href`.
+struct PageLink {
+    path: NodePath,
+    href: String,
+    rel: Vec<String>,
+    text: String,
+}
+
+/// Gets the links in the document, in document order.
+fn links(doc: &HtmlDocument) -> Vec<PageLink> {
+    let mut links = vec![];
+    walk_tags(&doc.nodes, &mut vec![], &mut vec![], &mut |tag, path, _| {
+        let is_area = tag.tag.eq_ignore_ascii_case("area");
+        if !is_area && !tag.tag.eq_ignore_ascii_case("a") {
+            return;
+        }
+        if let Some(href) = tag.attributes.get("href") {
+            let text = if is_area {
+                tag.attributes.get("alt").cloned().unwrap_or_default()
+            } else {
+                tag.text()
+            };
+            links.push(PageLink {
+                path: path.clone(),
+                href: href.clone(),
+                rel: tag
+                    .attributes
+                    .get("rel")
+                    .map(|r| {
+                        r.split_whitespace()
+                            .map(|t| t.to_ascii_lowercase())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            });
+        }
+    });
+    links
+}
+
+/// Checks whether the document has a `<meta name="robots">` with `noindex`.
+fn is_noindex(doc: &HtmlDocument) -> bool {
+    let mut noindex = false;
+    walk_tags(&doc.nodes, &mut vec![], &mut vec![], &mut |tag, _, _| {
+        let is_robots = tag.tag.eq_ignore_ascii_case("meta")
+            && tag
+                .attributes
+                .get("name")
+                .is_some_and(|n| n.eq_ignore_ascii_case("robots"));
+        if is_robots {
+            let content = tag
+                .attributes
+                .get("content")
+                .map(|c| c.to_ascii_lowercase());
+            noindex |= content.is_some_and(|c| c.split(',').any(|d| d.trim() == "noindex"));
+        }
+    });
+    noindex
+}
+
+/// Gets the names a fragment can go to in the document: the ids of the elements
+/// and the names of `<a>` elements.
+fn anchor_names(doc: &HtmlDocument) -> HashSet<String> {
+    let mut names = HashSet::new();
+    walk_tags(&doc.nodes, &mut vec![], &mut vec![], &mut |tag, _, _| {
+        names.extend(tag.ids.iter().cloned());
+        if tag.tag.eq_ignore_ascii_case("a") {
+            if let Some(name) = tag.attributes.get("name") {
+                names.insert(name.clone());
+            }
+        }
+    });
+    names
+}
+
+#[cfg(test)]
+mod workspace_tests {
+    use super::*;
+    use crate::error::HtmlDocError;
+    use crate::pipeline::{Pass, PassContext};
+
+    fn site() -> Workspace {
+        let mut site = Workspace::new();
+        site.insert_source(
+            "index.html",
+            concat!(
+                r#"<a href="about">About</a><a href="./blog/post.html#intro">Post</a>"#,
+                r#"<a href="https://other.example/x">Other</a><a href="mailto:a@b.example">Mail</a>"#,
+                r##"<a href="#top">Top</a><a href="?page=2#main">Next</a><a href="missing.html">Gone</a>"##
+            ),
+        );
+        site.insert_source("about.html", r#"<main id="main"></main>"#);
+        site.insert_source(
+            "blog/post.html",
+            r##"<a name="intro"></a><a href="../index.html#main">Home</a><map><area href="/img/"></map>"##,
+        );
+        site
+    }
+
+    #[test]
+    fn resolve_link_test() {
+        let site = site();
+        assert_eq!(
+            site.resolve_link("blog/post.html", "../about#x"),
+            LinkTarget::File {
+                name: "about.html".to_owned(),
+                fragment: Some("x".to_owned())
+            }
+        );
+        assert_eq!(
+            site.resolve_link("blog/post.html", "/"),
+            LinkTarget::File {
+                name: "index.html".to_owned(),
+                fragment: None
+            }
+        );
+        assert_eq!(
+            site.resolve_link("about.html", "blog/post.html"),
+            LinkTarget::File {
+                name: "blog/post.html".to_owned(),
+                fragment: None
+            }
+        );
+        assert_eq!(
+            site.resolve_link("about.html", "//cdn.example/a"),
+            LinkTarget::External
+        );
+        assert_eq!(
+            site.resolve_link("about.html", "#main"),
+            LinkTarget::File {
+                name: "about.html".to_owned(),
+                fragment: Some("main".to_owned())
+            }
+        );
+    }
+
+    #[test]
+    fn broken_links_test() {
+        let mut site = site();
+        let broken = site.broken_links();
+        let found: Vec<(&str, &str, &BrokenLinkReason)> = broken
+            .iter()
+            .map(|b| (b.file.as_str(), b.href.as_str(), &b.reason))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "blog/post.html",
+                    "../index.html#main",
+                    &BrokenLinkReason::MissingAnchor("main".to_owned())
+                ),
+                (
+                    "blog/post.html",
+                    "/img/",
+                    &BrokenLinkReason::MissingFile("img/".to_owned())
+                ),
+                (
+                    "index.html",
+                    "?page=2#main",
+                    &BrokenLinkReason::MissingAnchor("main".to_owned())
+                ),
+                (
+                    "index.html",
+                    "missing.html",
+                    &BrokenLinkReason::MissingFile("missing.html".to_owned())
+                ),
+            ]
+        );
+        assert_eq!(broken[1].path, vec![2, 0]);
+        assert_eq!(site.check_links(), 4);
+        assert_eq!(site.diagnostics("index.html").len(), 2);
+        assert_eq!(site.diagnostics("index.html")[1].pass, "links");
+        assert!(!site.has_errors());
+    }
+
+    struct CountLinks;
+    impl Pass for CountLinks {
+        fn name(&self) -> &str {
+            "count-links"
+        }
+        fn run(
+            &mut self,
+            doc: &mut HtmlDocument,
+            ctx: &mut PassContext,
+        ) -> Result<(), HtmlDocError> {
+            let count = if doc.nodes.is_empty() {
+                0
+            } else {
+                doc.find("a").nodes().len()
+            };
+            if count == 0 {
+                return Err(HtmlDocError::with_msg("no links"));
+            }
+            ctx.info(format!("{} links", count), None);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn diagnostics_test() {
+        let mut site = site();
+        assert!(!site.insert_source("broken.html", "<div></p>"));
+        assert_eq!(site.len(), 4);
+        assert_eq!(site.diagnostics("broken.html")[0].pass, "parse");
+        assert!(site.has_errors());
+        site.run_pipeline(&mut Pipeline::new().add(CountLinks));
+        assert_eq!(site.diagnostics("index.html")[0].message, "7 links");
+        assert_eq!(site.diagnostics("about.html")[0].pass, "pipeline");
+        let all = site.all_diagnostics();
+        let names: Vec<&str> = all.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "about.html",
+                "blog/post.html",
+                "broken.html",
+                "broken.html",
+                "index.html"
+            ]
+        );
+        // replacing a file clears its diagnostics
+        site.insert("broken.html", HtmlDocument::new());
+        assert!(site.diagnostics("broken.html").is_empty());
+        assert!(site.remove("broken.html").is_some());
+        assert!(site.diagnostics("missing.html").is_empty());
+    }
+
+    #[test]
+    fn find_all_test() {
+        let site = site();
+        assert_eq!(
+            site.find_all("a[name]").unwrap(),
+            vec![("blog/post.html", vec![vec![0]])]
+        );
+        assert_eq!(
+            site.pages_matching("a").unwrap(),
+            vec!["blog/post.html", "index.html"]
+        );
+        assert!(site.pages_matching("p").unwrap().is_empty());
+        assert_eq!(site.names().collect::<Vec<_>>().len(), 3);
+    }
+
+    #[test]
+    fn link_graph_test() {
+        let mut site = site();
+        site.insert_source(
+            "old.html",
+            r#"<meta name="robots" content="NoIndex, follow"><a href="about" rel="Nofollow  help">  About
+            us </a>"#,
+        );
+        let graph = site.link_graph();
+        assert_eq!(graph.pages.len(), 4);
+        let from_index: Vec<(&str, Option<&str>, &str)> = graph
+            .links_from("index.html")
+            .iter()
+            .map(|e| (e.to.as_str(), e.fragment.as_deref(), e.text.as_str()))
+            .collect();
+        assert_eq!(
+            from_index,
+            vec![
+                ("about.html", None, "About"),
+                ("blog/post.html", Some("intro"), "Post"),
+                ("index.html", Some("top"), "Top"),
+                ("index.html", Some("main"), "Next"),
+            ]
+        );
+        let to_about = graph.links_to("about.html");
+        assert_eq!(to_about.len(), 2);
+        assert_eq!(to_about[0].from, "index.html");
+        assert_eq!(to_about[1].rel, vec!["nofollow", "help"]);
+        assert_eq!(to_about[1].text, "About us");
+        assert_eq!(to_about[1].path, vec![1]);
+
+        assert_eq!(
+            graph.reachable_from(&["blog/post.html"]),
+            vec!["about.html", "blog/post.html", "index.html"]
+        );
+        assert_eq!(graph.orphans(&["index.html"]), vec!["old.html"]);
+        assert_eq!(graph.orphans(&["missing.html"]).len(), 4);
+        // old.html is not indexed even though it is reachable
+        let sitemap = site.sitemap("https://site.example", &["old.html", "index.html"]);
+        assert!(sitemap.contains("<loc>https://site.example/blog/post.html</loc>"));
+        assert!(!sitemap.contains("old.html"));
+        assert_eq!(sitemap.matches("<url>").count(), 3);
+    }
+
+    #[test]
+    fn fingerprint_assets_test() {
+        let mut site = Workspace::new();
+        site.insert_source(
+            "index.html",
+            r#"<div><img src="img/a.png"><script src="/js/app.js"></script><a href="about">About</a></div>"#,
+        );
+        site.insert_source("about.html", r#"<img src="/img/a.png">"#);
+        let assets = FingerprintAssets::new().asset("img/a.png", "img/a.5e.png");
+        let report = site.fingerprint_assets(&assets);
+        assert_eq!(report.rewritten, 2);
+        assert_eq!(report.unmapped.len(), 1);
+        assert_eq!(report.unmapped[0].url, "/js/app.js");
+        let warnings = site.diagnostics("index.html");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].pass, "fingerprint");
+        assert!(site.get("about.html").unwrap().nodes[0]
+            .to_html_string()
+            .contains("/img/a.5e.png"));
+    }
+}
@@ -0,0 +1,479 @@
+// Reads the template of the format_html! macro, working out the context of each
+// value so it can be escaped for where it is, and expands it into the code which
+// builds the SafeHtml.
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, Ident, LitStr, Token};
+
+/// The elements whose contents are text up to their end tag.
+const RAW_TEXT_TAGS: [&str; 4] = ["script", "style", "textarea", "title"];
+
+/// The attributes which hold a URL, or a list of them, where a value at the start
+/// can set the scheme. They are matched on the name without any namespace prefix, so
+/// `xlink:href` is a URL attribute too.
+const URL_ATTRIBUTES: [&str; 19] = [
+    "action",
+    "archive",
+    "background",
+    "cite",
+    "classid",
+    "codebase",
+    "data",
+    "dynsrc",
+    "formaction",
+    "href",
+    "icon",
+    "itemtype",
+    "longdesc",
+    "lowsrc",
+    "manifest",
+    "ping",
+    "poster",
+    "src",
+    "srcset",
+];
+
+fn is_url_attribute(name: &str) -> bool {
+    let local = name.rsplit(':').next().unwrap_or(name);
+    URL_ATTRIBUTES.contains(&local)
+}
+
+pub struct FormatHtmlInput {
+    template: LitStr,
+    positional: Vec<Expr>,
+    named: Vec<(Ident, Expr)>,
+}
+
+impl Parse for FormatHtmlInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let template = input.parse()?;
+        let mut positional = vec![];
+        let mut named = vec![];
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            if input.peek(Ident) && input.peek2(Token![=]) && !input.peek2(Token![==]) {
+                let name: Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
+                named.push((name, input.parse()?));
+            } else if named.is_empty() {
+                positional.push(input.parse()?);
+            } else {
+                return Err(input.error("positional arguments must come before named ones"));
+            }
+        }
+        Ok(FormatHtmlInput {
+            template,
+            positional,
+            named,
+        })
+    }
+}
+
+/// Where a value is put in the template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    Text,
+    Attribute,
+    Url,
+    UrlComponent,
+}
+
+/// Which argument a value comes from.
+enum ArgRef {
+    Index(usize),
+    Name(String),
+}
+
+enum Piece {
+    Literal(String),
+    Value {
+        arg: ArgRef,
+        spec: String,
+        context: Context,
+    },
+}
+
+/// What the scanner is reading in the template.
+#[derive(Debug, Clone)]
+enum State {
+    Text,
+    RawText(String),
+    TagOpen,
+    TagName {
+        name: String,
+        end: bool,
+    },
+    InTag(String),
+    InEndTag,
+    AttrName {
+        tag: String,
+        name: String,
+    },
+    AfterAttrName {
+        tag: String,
+        name: String,
+    },
+    BeforeValue {
+        tag: String,
+        name: String,
+    },
+    Value {
+        tag: String,
+        name: String,
+        quote: Option<char>,
+        empty: bool,
+    },
+    Comment,
+    Declaration,
+}
+
+/// Follows the HTML of the template through its literal text, far enough to know
+/// what kind of place each value is in.
+struct Scanner {
+    state: State,
+}
+
+impl Scanner {
+    fn read(&mut self, text: &str) {
+        let mut i = 0;
+        while let Some(c) = text[i..].chars().next() {
+            let rest = &text[i + c.len_utf8()..];
+            i += c.len_utf8();
+            self.state = match std::mem::replace(&mut self.state, State::Text) {
+                State::Text => match c {
+                    '<' => State::TagOpen,
+                    _ => State::Text,
+                },
+                State::RawText(tag) => {
+                    let end = format!("/{}", tag);
+                    let is_end = c == '<'
+                        && rest
+                            .get(..end.len())
+                            .is_some_and(|r| r.eq_ignore_ascii_case(&end))
+                        && !rest[end.len()..]
+                            .starts_with(|c: char| c.is_ascii_alphanumeric() || c == '-');
+                    if is_end {
+                        i += end.len();
+                        State::TagName {
+                            name: tag,
+                            end: true,
+                        }
+                    } else {
+                        State::RawText(tag)
+                    }
+                }
+                State::TagOpen => match c {
+                    '/' => State::TagName {
+                        name: String::new(),
+                        end: true,
+                    },
+                    '!' if rest.starts_with("--") => {
+                        i += 2;
+                        State::Comment
+                    }
+                    '!' | '?' => State::Declaration,
+                    c if c.is_ascii_alphabetic() => State::TagName {
+                        name: c.to_ascii_lowercase().to_string(),
+                        end: false,
+                    },
+                    _ => State::Text,
+                },
+                State::TagName { mut name, end } => match c {
+                    '>' => finish_tag(name, end),
+                    c if c.is_whitespace() || c == '/' => {
+                        if end {
+                            State::InEndTag
+                        } else {
+                            State::InTag(name)
+                        }
+                    }
+                    c => {
+                        name.push(c.to_ascii_lowercase());
+                        State::TagName { name, end }
+                    }
+                },
+                State::InTag(tag) => match c {
+                    '>' => finish_tag(tag, false),
+                    c if c.is_whitespace() || c == '/' => State::InTag(tag),
+                    c => State::AttrName {
+                        tag,
+                        name: c.to_ascii_lowercase().to_string(),
+                    },
+                },
+                State::InEndTag => match c {
+                    '>' => State::Text,
+                    _ => State::InEndTag,
+                },
+                State::AttrName { tag, mut name } => match c {
+                    '>' => finish_tag(tag, false),
+                    '=' => State::BeforeValue { tag, name },
+                    '/' => State::InTag(tag),
+                    c if c.is_whitespace() => State::AfterAttrName { tag, name },
+                    c => {
+                        name.push(c.to_ascii_lowercase());
+                        State::AttrName { tag, name }
+                    }
+                },
+                State::AfterAttrName { tag, name } => match c {
+                    '>' => finish_tag(tag, false),
+                    '=' => State::BeforeValue { tag, name },
+                    '/' => State::InTag(tag),
+                    c if c.is_whitespace() => State::AfterAttrName { tag, name },
+                    c => State::AttrName {
+                        tag,
+                        name: c.to_ascii_lowercase().to_string(),
+                    },
+                },
+                State::BeforeValue { tag, name } => match c {
+                    '>' => finish_tag(tag, false),
+                    '"' | '\'' => State::Value {
+                        tag,
+                        name,
+                        quote: Some(c),
+                        empty: true,
+                    },
+                    c if c.is_whitespace() => State::BeforeValue { tag, name },
+                    _ => State::Value {
+                        tag,
+                        name,
+                        quote: None,
+                        empty: false,
+                    },
+                },
+                State::Value {
+                    tag, name, quote, ..
+                } => match (c, quote) {
+                    (c, Some(q)) if c == q => State::InTag(tag),
+                    ('>', None) => finish_tag(tag, false),
+                    (c, None) if c.is_whitespace() => State::InTag(tag),
+                    _ => State::Value {
+                        tag,
+                        name,
+                        quote,
+                        empty: false,
+                    },
+                },
+                State::Comment => {
+                    if c == '-' && rest.starts_with("->") {
+                        i += 2;
+                        State::Text
+                    } else {
+                        State::Comment
+                    }
+                }
+                State::Declaration => match c {
+                    '>' => State::Text,
+                    _ => State::Declaration,
+                },
+            };
+        }
+    }
+
+    /// Gets the context of a value at the current place, or why a value can not go
+    /// there.
+    fn value(&mut self) -> Result<Context, String> {
+        match &mut self.state {
+            State::Text => Ok(Context::Text),
+            State::RawText(tag) if tag == "script" || tag == "style" => Err(format!(
+                "a value can not be put inside a <{}> element, as it can not be escaped there",
+                tag
+            )),
+            State::RawText(_) => Ok(Context::Text),
+            State::Value {
+                name,
+                quote: Some(_),
+                empty,
+                ..
+            } => {
+                if name.starts_with("on") || name == "style" {
+                    return Err(format!(
+                        "a value can not be put in the {} attribute, as it is run as a script or style",
+                        name
+                    ));
+                }
+                if name == "srcdoc" {
+                    return Err(
+                        "a value can not be put in the srcdoc attribute, as it is read as a whole document"
+                            .to_owned(),
+                    );
+                }
+                let context = if !is_url_attribute(name) {
+                    Context::Attribute
+                } else if *empty {
+                    Context::Url
+                } else {
+                    Context::UrlComponent
+                };
+                *empty = false;
+                Ok(context)
+            }
+            State::Value { name, .. } | State::BeforeValue { name, .. } => Err(format!(
+                "the value of the {} attribute must be in quotes to put a value in it",
+                name
+            )),
+            State::Comment | State::Declaration => {
+                Err("a value can not be put inside a comment or declaration".to_owned())
+            }
+            _ => Err(
+                "a value can not be put inside a tag, only in a quoted attribute value".to_owned(),
+            ),
+        }
+    }
+}
+
+fn finish_tag(name: String, end: bool) -> State {
+    if !end && RAW_TEXT_TAGS.contains(&name.as_str()) {
+        State::RawText(name)
+    } else {
+        State::Text
+    }
+}
+
+/// Splits the template into literal text and values, with the context of each.
+fn parse_template(template: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = vec![];
+    let mut literal = String::new();
+    let mut scanner = Scanner { state: State::Text };
+    let mut next_index = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' => return Err("unmatched `}` in the template, use `}}` for a `}`".to_owned()),
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => inner.push(c),
+                        None => {
+                            return Err(
+                                "unmatched `{` in the template, use `{{` for a `{`".to_owned()
+                            )
+                        }
+                    }
+                }
+                let (arg, spec) = match inner.split_once(':') {
+                    Some((arg, spec)) => (arg.trim(), spec.to_owned()),
+                    None => (inner.trim(), String::new()),
+                };
+                let arg = if arg.is_empty() {
+                    next_index += 1;
+                    ArgRef::Index(next_index - 1)
+                } else if let Ok(i) = arg.parse() {
+                    ArgRef::Index(i)
+                } else {
+                    ArgRef::Name(arg.to_owned())
+                };
+                scanner.read(&literal);
+                let context = scanner.value()?;
+                pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                pieces.push(Piece::Value { arg, spec, context });
+            }
+            c => literal.push(c),
+        }
+    }
+    pieces.push(Piece::Literal(literal));
+    Ok(pieces)
+}
+
+/// Creates the expression which builds the HTML.
+pub fn format_html_tokens(input: FormatHtmlInput) -> syn::Result<TokenStream> {
+    let span = input.template.span();
+    let pieces = parse_template(&input.template.value()).map_err(|e| syn::Error::new(span, e))?;
+    // the arguments are bound once each: the positional ones, then the named ones,
+    // then any names from the template which are captured from the scope
+    let mut exprs: Vec<TokenStream> = input.positional.iter().map(|e| quote!(#e)).collect();
+    let mut names: Vec<String> = vec![];
+    for (name, expr) in &input.named {
+        exprs.push(quote!(#expr));
+        names.push(name.to_string());
+    }
+    let mut used = vec![false; exprs.len()];
+    let mut values = vec![];
+    for piece in &pieces {
+        let Piece::Value { arg, spec, context } = piece else {
+            continue;
+        };
+        let index = match arg {
+            ArgRef::Index(i) if *i < input.positional.len() => *i,
+            ArgRef::Index(i) => {
+                return Err(syn::Error::new(
+                    span,
+                    format!("there is no argument {} for the template", i),
+                ))
+            }
+            ArgRef::Name(name) => match names.iter().position(|n| n == name) {
+                Some(i) => input.positional.len() + i,
+                None => {
+                    let ident = syn::parse_str::<Ident>(name)
+                        .map_err(|_| syn::Error::new(span, format!("invalid argument {}", name)))?;
+                    let ident = Ident::new(&ident.to_string(), span);
+                    exprs.push(quote!(#ident));
+                    names.push(name.clone());
+                    used.push(false);
+                    exprs.len() - 1
+                }
+            },
+        };
+        used[index] = true;
+        values.push((index, spec.clone(), *context));
+    }
+    if let Some(unused) = used.iter().position(|u| !u) {
+        return Err(syn::Error::new(
+            span,
+            format!("argument {} is never used in the template", unused),
+        ));
+    }
+
+    let bindings: Vec<Ident> = (0..exprs.len())
+        .map(|i| format_ident!("__hb_arg{}", i))
+        .collect();
+    let private = quote!(::hb_html::safe_html::__private);
+    let mut values = values.into_iter();
+    let pushes = pieces.iter().map(|piece| match piece {
+        Piece::Literal(s) if s.is_empty() => quote!(),
+        Piece::Literal(s) => quote!(html.push_str(#s);),
+        Piece::Value { .. } => {
+            let (index, spec, context) = values.next().unwrap();
+            let binding = &bindings[index];
+            let value = if spec.is_empty() {
+                quote!(#binding)
+            } else {
+                let format = LitStr::new(&format!("{{:{}}}", spec), Span::call_site());
+                quote!(&::std::format!(#format, #binding))
+            };
+            match context {
+                Context::Text => quote!(html.push_str(&(&#private::Text(#value)).html_text());),
+                Context::Attribute => quote!(html.push_str(&#private::attribute(#value));),
+                Context::Url => quote!(html.push_str(&#private::url(#value));),
+                Context::UrlComponent => {
+                    quote!(html.push_str(&#private::url_component(#value));)
+                }
+            }
+        }
+    });
+    let pushes: Vec<TokenStream> = pushes.collect();
+    Ok(quote!({
+        #[allow(unused_imports)]
+        use #private::{DisplayText as _, SafeHtmlText as _};
+        match (#(&(#exprs),)*) {
+            (#(#bindings,)*) => {
+                let mut html = ::std::string::String::new();
+                #(#pushes)*
+                ::hb_html::safe_html::SafeHtml::from_trusted(html)
+            }
+        }
+    }))
+}
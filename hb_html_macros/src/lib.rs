@@ -0,0 +1,203 @@
+//! Macros which check and build HTML for hb_html at compile time.
+use hb_html::objects::{CssSelector, HtmlDocument};
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Expr, LitStr};
+
+mod format_html;
+mod selector;
+
+/// Parses a CSS selector at compile time.
+///
+/// Invalid selectors are reported as compile errors on the selector string. A valid
+/// selector is expanded into the code which constructs the [`CssSelector`], which is
+/// built the first time the expression is run and then reused, so the macro gives a
+/// `&'static CssSelector` without any parsing or unwrapping at runtime.
+///
+/// # Example
+/// ```
+/// use hb_html::objects::HtmlDocument;
+/// use hb_html::querying::HtmlQueryable;
+/// use hb_html_macros::selector;
+/// let doc = r#"<div class="x"><a href="/a">A</a></div><a href="/b">B</a>"#
+///     .parse::<HtmlDocument>()
+///     .unwrap();
+/// let mut query = doc.query();
+/// assert_eq!(query.find(selector!("div.x > a")).nodes().len(), 1);
+/// assert_eq!(selector!("div.x > a"), &"div.x > a".parse().unwrap());
+/// ```
+///
+/// ```compile_fail
+/// let s = hb_html_macros::selector!("div[href");
+/// ```
+#[proc_macro]
+pub fn selector(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    let selector = match lit.value().parse::<CssSelector>() {
+        Ok(s) => s,
+        Err(e) => {
+            return syn::Error::new(lit.span(), format!("invalid CSS selector: {}", e))
+                .to_compile_error()
+                .into();
+        }
+    };
+    let tokens = selector::selector_tokens(&selector);
+    quote!({
+        static SELECTOR: ::std::sync::OnceLock<::hb_html::objects::CssSelector> =
+            ::std::sync::OnceLock::new();
+        SELECTOR.get_or_init(|| #tokens)
+    })
+    .into()
+}
+
+/// Finds the file which an `include_str!` in the macro input refers to. The path is
+/// relative to the file containing the macro call, the same as `include_str!`.
+fn include_path(lit: &LitStr) -> std::path::PathBuf {
+    let path = std::path::PathBuf::from(lit.value());
+    if path.is_absolute() {
+        return path;
+    }
+    match lit.span().unwrap().local_file() {
+        Some(file) => file.parent().map(|d| d.join(&path)).unwrap_or(path),
+        None => std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default())
+            .join(path),
+    }
+}
+
+/// Parses HTML at compile time, failing the build if it is malformed.
+///
+/// The input is either a string literal or an `include_str!` of a file. The macro
+/// expands to the `&'static str` it was given so it can be used anywhere the
+/// string would be, and the error message gives the line and column of the problem.
+///
+/// # Example
+/// ```
+/// use hb_html::objects::HtmlDocument;
+/// use hb_html_macros::check_html;
+/// const PAGE: &str = check_html!("<div>\n  <p>Hello</p>\n</div>");
+/// assert_eq!(PAGE.parse::<HtmlDocument>().unwrap().nodes.len(), 1);
+/// ```
+///
+/// ```compile_fail
+/// const PAGE: &str = hb_html_macros::check_html!("<div>\n  <p>Hello</q>\n</div>");
+/// ```
+#[proc_macro]
+pub fn check_html(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as Expr);
+    let (html, lit, name) = match &input {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        }) => (lit.value(), lit.clone(), "HTML".to_owned()),
+        Expr::Macro(m) if m.mac.path.is_ident("include_str") => {
+            let lit = match m.mac.parse_body::<LitStr>() {
+                Ok(l) => l,
+                Err(e) => return e.to_compile_error().into(),
+            };
+            let path = include_path(&lit);
+            match std::fs::read_to_string(&path) {
+                Ok(html) => (html, lit, path.display().to_string()),
+                Err(e) => {
+                    return syn::Error::new(
+                        lit.span(),
+                        format!("could not read {}: {}", path.display(), e),
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+            }
+        }
+        _ => {
+            return syn::Error::new_spanned(
+                input,
+                "expected a string literal or include_str!(\"path\")",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    if let Err(e) = html.parse::<HtmlDocument>() {
+        let position = match e.line_col(&html) {
+            Some((line, col)) => format!(" at line {}, column {}", line, col),
+            None => String::new(),
+        };
+        return syn::Error::new(lit.span(), format!("malformed {}{}: {}", name, position, e))
+            .to_compile_error()
+            .into();
+    }
+    quote!(#input).into()
+}
+
+/// Builds a [`SafeHtml`](hb_html::safe_html::SafeHtml) from a template, escaping
+/// each value for the place in the HTML it is put.
+///
+/// The template and arguments are written as they are for `format!`, including
+/// names captured from the scope and format specs such as `{:.2}`. The template is
+/// read when the macro is compiled to find what each value is inside:
+/// - text: the value is escaped, unless it is a `SafeHtml` such as the output of
+///   another `format_html!`, which is put in as it is;
+/// - a quoted attribute value: the value is escaped for either quote;
+/// - the start of a URL attribute, eg `href="{}"`, `xlink:href="{}"` or
+///   `srcset="{}"`: a URL with a scheme which is not safe, such as `javascript:`,
+///   is replaced with `#`;
+/// - later in a URL attribute, eg `href="/users/{}"`: the value is percent-encoded.
+///
+/// A value inside a `<script>` or `<style>` element, an event handler, `style` or
+/// `srcdoc` attribute, a comment, an unquoted attribute value or a tag itself can not be
+/// made safe, so the macro gives a compile error instead.
+///
+/// # Example
+/// ```
+/// use hb_html_macros::format_html;
+/// let name = "<Tom & Jerry>";
+/// let url = "javascript:alert(1)";
+/// let item = format_html!("<li title='{name}'>{name}</li>");
+/// let page = format_html!(
+///     r#"<a href="{}">{}</a><a href="/search?q={query}">{:.1}</a><ul>{item}</ul>"#,
+///     url,
+///     name,
+///     2.25,
+///     query = "a&b c",
+/// );
+/// assert_eq!(
+///     page.as_str(),
+///     "<a href=\"#\">&lt;Tom &amp; Jerry&gt;</a>\
+///      <a href=\"/search?q=a%26b%20c\">2.2</a>\
+///      <ul><li title='<Tom &amp; Jerry>'>&lt;Tom &amp; Jerry&gt;</li></ul>"
+/// );
+/// let svg = format_html!(
+///     r#"<a xlink:href="{url}"><img SRCSET="{url} 2x, /b.png?{url}" ping="{url}"></a>"#
+/// );
+/// assert_eq!(
+///     svg.as_str(),
+///     "<a xlink:href=\"#\"><img SRCSET=\"# 2x, /b.png?javascript%3Aalert%281%29\" ping=\"#\"></a>"
+/// );
+/// ```
+///
+/// ```compile_fail
+/// let code = "alert(1)";
+/// let html = hb_html_macros::format_html!("<script>{code}</script>");
+/// ```
+///
+/// ```compile_fail
+/// let code = "alert(1)";
+/// let html = hb_html_macros::format_html!("<a onclick=\"{code}\">a</a>");
+/// ```
+///
+/// ```compile_fail
+/// let page = "<script>alert(1)</script>";
+/// let html = hb_html_macros::format_html!("<iframe srcdoc=\"{page}\"></iframe>");
+/// ```
+///
+/// ```compile_fail
+/// let class = "a onclick=alert(1)";
+/// let html = hb_html_macros::format_html!("<p class={class}>a</p>");
+/// ```
+#[proc_macro]
+pub fn format_html(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as format_html::FormatHtmlInput);
+    match format_html::format_html_tokens(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,169 @@
+// Converts a parsed CssSelector back into the Rust code which constructs it,
+// so the selector! macro can do the parsing at compile time.
+use hb_html::objects::{
+    CssAttributeCompareType, CssRefiner, CssRefinerNumberType, CssSelector, CssSelectorItem,
+    CssSelectorRelationship, CssSelectorRule,
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+fn string_tokens(s: &str) -> TokenStream {
+    quote! { ::std::string::String::from(#s) }
+}
+
+fn option_vec_tokens<T, F: Fn(&T) -> TokenStream>(items: &Option<Vec<T>>, f: F) -> TokenStream {
+    match items {
+        None => quote! { ::std::option::Option::None },
+        Some(items) => {
+            let items = items.iter().map(f);
+            quote! { ::std::option::Option::Some(::std::vec![#(#items),*]) }
+        }
+    }
+}
+
+fn number_type_tokens(n: &CssRefinerNumberType) -> TokenStream {
+    let path = quote! { ::hb_html::objects::CssRefinerNumberType };
+    match n {
+        CssRefinerNumberType::Odd => quote! { #path::Odd },
+        CssRefinerNumberType::Even => quote! { #path::Even },
+        CssRefinerNumberType::Specific(i) => quote! { #path::Specific(#i) },
+        CssRefinerNumberType::Functional((a, b)) => quote! { #path::Functional((#a, #b)) },
+    }
+}
+
+fn refiner_tokens(r: &CssRefiner) -> TokenStream {
+    let path = quote! { ::hb_html::objects::CssRefiner };
+    match r {
+        CssRefiner::Checked => quote! { #path::Checked },
+        CssRefiner::Default => quote! { #path::Default },
+        CssRefiner::Disabled => quote! { #path::Disabled },
+        CssRefiner::Enabled => quote! { #path::Enabled },
+        CssRefiner::Optional => quote! { #path::Optional },
+        CssRefiner::Required => quote! { #path::Required },
+        CssRefiner::ReadOnly => quote! { #path::ReadOnly },
+        CssRefiner::ReadWrite => quote! { #path::ReadWrite },
+        CssRefiner::Empty => quote! { #path::Empty },
+        CssRefiner::FirstChild => quote! { #path::FirstChild },
+        CssRefiner::LastChild => quote! { #path::LastChild },
+        CssRefiner::NthChild(n) => {
+            let n = number_type_tokens(n);
+            quote! { #path::NthChild(#n) }
+        }
+        CssRefiner::NthLastChild(n) => {
+            let n = number_type_tokens(n);
+            quote! { #path::NthLastChild(#n) }
+        }
+        CssRefiner::OnlyChild => quote! { #path::OnlyChild },
+        CssRefiner::FirstOfType => quote! { #path::FirstOfType },
+        CssRefiner::LastOfType => quote! { #path::LastOfType },
+        CssRefiner::NthOfType(n) => {
+            let n = number_type_tokens(n);
+            quote! { #path::NthOfType(#n) }
+        }
+        CssRefiner::NthLastOfType(n) => {
+            let n = number_type_tokens(n);
+            quote! { #path::NthLastOfType(#n) }
+        }
+        CssRefiner::OnlyOfType => quote! { #path::OnlyOfType },
+        CssRefiner::Not(s) => {
+            let s = selector_tokens(s);
+            quote! { #path::Not(#s) }
+        }
+        CssRefiner::Root => quote! { #path::Root },
+    }
+}
+
+fn attribute_tokens(a: &CssAttributeCompareType) -> TokenStream {
+    let path = quote! { ::hb_html::objects::CssAttributeCompareType };
+    let pair = |(name, value): &(String, String)| {
+        let name = string_tokens(name);
+        let value = string_tokens(value);
+        quote! { (#name, #value) }
+    };
+    match a {
+        CssAttributeCompareType::Present(name) => {
+            let name = string_tokens(name);
+            quote! { #path::Present(#name) }
+        }
+        CssAttributeCompareType::Equals(p) => {
+            let p = pair(p);
+            quote! { #path::Equals(#p) }
+        }
+        CssAttributeCompareType::EqualsOrBeingsWith(p) => {
+            let p = pair(p);
+            quote! { #path::EqualsOrBeingsWith(#p) }
+        }
+        CssAttributeCompareType::BeginsWith(p) => {
+            let p = pair(p);
+            quote! { #path::BeginsWith(#p) }
+        }
+        CssAttributeCompareType::EndsWith(p) => {
+            let p = pair(p);
+            quote! { #path::EndsWith(#p) }
+        }
+        CssAttributeCompareType::Contains(p) => {
+            let p = pair(p);
+            quote! { #path::Contains(#p) }
+        }
+        CssAttributeCompareType::ContainsWord(p) => {
+            let p = pair(p);
+            quote! { #path::ContainsWord(#p) }
+        }
+    }
+}
+
+fn item_tokens(item: &CssSelectorItem) -> TokenStream {
+    let tag = match &item.tag {
+        None => quote! { ::std::option::Option::None },
+        Some(t) => {
+            let t = string_tokens(t);
+            quote! { ::std::option::Option::Some(#t) }
+        }
+    };
+    let classes = option_vec_tokens(&item.classes, |c| string_tokens(c));
+    let ids = option_vec_tokens(&item.ids, |i| string_tokens(i));
+    let refiners = option_vec_tokens(&item.refiners, refiner_tokens);
+    let attributes = option_vec_tokens(&item.attributes, attribute_tokens);
+    quote! {
+        ::hb_html::objects::CssSelectorItem {
+            tag: #tag,
+            classes: #classes,
+            ids: #ids,
+            refiners: #refiners,
+            attributes: #attributes,
+        }
+    }
+}
+
+fn relationship_tokens(r: &CssSelectorRelationship) -> TokenStream {
+    let path = quote! { ::hb_html::objects::CssSelectorRelationship };
+    let (variant, item) = match r {
+        CssSelectorRelationship::Parent(i) => (quote! { Parent }, i),
+        CssSelectorRelationship::Ancestor(i) => (quote! { Ancestor }, i),
+        CssSelectorRelationship::PreviousSibling(i) => (quote! { PreviousSibling }, i),
+        CssSelectorRelationship::PreviousSiblingOnce(i) => (quote! { PreviousSiblingOnce }, i),
+        CssSelectorRelationship::Current(i) => (quote! { Current }, i),
+    };
+    let item = item_tokens(item);
+    quote! { #path::#variant(#item) }
+}
+
+fn rule_tokens(rule: &CssSelectorRule) -> TokenStream {
+    let rules = rule.rules.iter().map(relationship_tokens);
+    quote! {
+        ::hb_html::objects::CssSelectorRule {
+            rules: ::std::vec![#(#rules),*],
+        }
+    }
+}
+
+/// Creates an expression which constructs the selector provided.
+pub fn selector_tokens(selector: &CssSelector) -> TokenStream {
+    match selector {
+        CssSelector::Any => quote! { ::hb_html::objects::CssSelector::Any },
+        CssSelector::Specific(rules) => {
+            let rules = rules.iter().map(rule_tokens);
+            quote! { ::hb_html::objects::CssSelector::Specific(::std::vec![#(#rules),*]) }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Functions which combine parsers, such as the [`CommonParserFunctions`], into
+//! parsers for larger structures.
+//!
+//! Each combinator takes the parsers as closures over the source. When a parser
+//! fails, the pointer of the source is rolled back to where it was before that
+//! parser was tried, so the source is left ready for whatever is tried next. Chars
+//! which have been consumed can not be put back, so a closure which consumes
+//! something and then fails leaves it consumed.
+//!
+//! [`CommonParserFunctions`]: crate::CommonParserFunctions
+//!
+//! # Example
+//!
+//! ```
+//! use hb_parse::combinators::{alt, separated_list};
+//! use hb_parse::{CommonParserFunctions, StrParser};
+//! let mut source = StrParser::new("12, 'red', 3");
+//! let items = separated_list(
+//!     &mut source,
+//!     |s| alt(s, &[&|s| Ok(s.parse_num::<u32>()?.to_string()), &|s| s.parse_string()]),
+//!     |s| s.match_char(','),
+//! )
+//! .unwrap();
+//! assert_eq!(items, vec!["12", "red", "3"]);
+//! ```
+use crate::{ErrorContext, ParseError, ParseResult, Source};
+
+/// Tries the parser, giving `None` and rolling back if it fails.
+pub fn opt<S, T, F>(source: &mut S, parser: F) -> ParseResult<Option<T>>
+where
+    S: Source + ?Sized,
+    F: FnOnce(&mut S) -> ParseResult<T>,
+{
+    let start = source.checkpoint();
+    match parser(source) {
+        Ok(value) => Ok(Some(value)),
+        Err(_) => {
+            source.rollback(start)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Runs the parser until it fails, giving everything it parsed. The parser must
+/// read something each time it succeeds, otherwise this never stops.
+pub fn many0<S, T, F>(source: &mut S, mut parser: F) -> ParseResult<Vec<T>>
+where
+    S: Source + ?Sized,
+    F: FnMut(&mut S) -> ParseResult<T>,
+{
+    let mut values = vec![];
+    loop {
+        match opt(source, &mut parser)? {
+            Some(value) => values.push(value),
+            None => return Ok(values),
+        }
+    }
+}
+
+/// Runs the parser until it fails as [`many0`] does, but fails if the parser
+/// does not succeed at least once.
+pub fn many1<S, T, F>(source: &mut S, mut parser: F) -> ParseResult<Vec<T>>
+where
+    S: Source + ?Sized,
+    F: FnMut(&mut S) -> ParseResult<T>,
+{
+    let start = source.checkpoint();
+    let first = match parser(source) {
+        Ok(value) => value,
+        Err(e) => {
+            source.rollback(start)?;
+            return Err(e);
+        }
+    };
+    let mut values = vec![first];
+    values.append(&mut many0(source, parser)?);
+    Ok(values)
+}
+
+/// A parser which is one of the alternatives given to [`alt`].
+pub type Alternative<'a, S, T> = &'a dyn Fn(&mut S) -> ParseResult<T>;
+
+/// Tries each of the parsers in turn, giving the value of the first which
+/// succeeds. If they all fail the error of the last one is returned.
+pub fn alt<S, T>(source: &mut S, parsers: &[Alternative<S, T>]) -> ParseResult<T>
+where
+    S: Source + ?Sized,
+{
+    let start = source.checkpoint();
+    let mut last_error = None;
+    for parser in parsers {
+        match parser(source) {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                source.rollback(start)?;
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(match last_error {
+        Some(e) => e
+            .make_inner()
+            .msg("none of the alternatives could be parsed"),
+        None => ParseError::new().msg("there were no alternatives to parse"),
+    })
+}
+
+/// Parses zero or more items with a separator between each of them. The
+/// separator parser returning `Ok(false)`, as the `match_*` functions do, is
+/// treated as not finding a separator. A separator which is not followed by an
+/// item is not part of the list, so it is rolled back if it was not consumed.
+pub fn separated_list<S, T, F, G>(
+    source: &mut S,
+    mut item: F,
+    mut separator: G,
+) -> ParseResult<Vec<T>>
+where
+    S: Source + ?Sized,
+    F: FnMut(&mut S) -> ParseResult<T>,
+    G: FnMut(&mut S) -> ParseResult<bool>,
+{
+    let mut values = match opt(source, &mut item)? {
+        Some(value) => vec![value],
+        None => return Ok(vec![]),
+    };
+    loop {
+        let start = source.checkpoint();
+        let found = match separator(source) {
+            Ok(true) => opt(source, &mut item)?,
+            Ok(false) | Err(_) => None,
+        };
+        match found {
+            Some(value) => values.push(value),
+            None => {
+                source.rollback(start)?;
+                return Ok(values);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod combinators_tests {
+    use super::*;
+    use crate::{CommonParserFunctions, SourceCore, StrParser};
+
+    #[test]
+    fn combinator_tests() {
+        let mut source = StrParser::new("1 2 3 a b");
+        let nums = many1(&mut source, |s| s.parse_num::<u32>()).unwrap();
+        assert_eq!(nums, vec![1, 2, 3]);
+        assert!(many1(&mut source, |s| s.parse_num::<u32>()).is_err());
+        assert!(many0(&mut source, |s| s.parse_num::<u32>())
+            .unwrap()
+            .is_empty());
+        assert_eq!(opt(&mut source, |s| s.parse_num::<u32>()).unwrap(), None);
+        // the pointer is rolled back when the word is not followed by a comma
+        let word_then_comma = |s: &mut StrParser| -> ParseResult<String> {
+            let word = s.parse_word()?;
+            match s.next_if(|c| c == ',')? {
+                Some(_) => Ok(word),
+                None => Err(ParseError::new().msg("expected a comma")),
+            }
+        };
+        assert_eq!(opt(&mut source, word_then_comma).unwrap(), None);
+        assert_eq!(source.get_pointer_loc(), 0);
+        let err = alt(
+            &mut source,
+            &[&|s| s.parse_num::<u8>(), &|s| s.parse_num::<u8>()],
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("none of the alternatives could be parsed"));
+        assert_eq!(source.get_pointer_loc(), 0);
+
+        let mut source = StrParser::new("1; 2;3; x");
+        let nums =
+            separated_list(&mut source, |s| s.parse_num::<u32>(), |s| s.match_char(';')).unwrap();
+        assert_eq!(nums, vec![1, 2, 3]);
+        assert_eq!(source.get_pointer_loc(), 0);
+        // the last separator was consumed by match_char so can not be put back
+        assert_eq!(source.read_substr(0, 2).unwrap(), " x");
+    }
+}
@@ -18,7 +18,7 @@ pub struct UnexpectedChar {}
 #[hberror]
 pub struct SourceInvalidState {}
 
-#[hberror]
+#[hberror("{self.msg}{self.position_msg()}{self.inner_msgs.join(\"\n...because...\")}")]
 pub struct ParseError {
     #[Source]
     SourceError: SourceError,
@@ -28,6 +28,29 @@ pub struct ParseError {
     UnexpectedChar: UnexpectedChar,
     #[Source]
     SourceInvalidState: SourceInvalidState,
+    // boxed as results with large errors are slow to pass around
+    position: Option<Box<(usize, usize)>>,
+}
+
+impl ParseError {
+    /// Sets the line and column (both starting from 1) in the source where the
+    /// error happened.
+    pub fn at_position(mut self, line: usize, column: usize) -> ParseError {
+        self.position = Some(Box::new((line, column)));
+        self
+    }
+
+    /// Gets the line and column in the source where the error happened, if known.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.position.as_deref().copied()
+    }
+
+    fn position_msg(&self) -> String {
+        match self.position() {
+            Some((line, column)) => format!(" at line {}, column {}", line, column),
+            None => String::new(),
+        }
+    }
 }
 
 /*pub enum ParseInnerError {
@@ -0,0 +1,378 @@
+//! Writing small grammars as rules instead of parser functions.
+//!
+//! A [`Rule`] parses one part of a grammar. Rules for tokens, such as
+//! [`literal`], [`identifier`] and [`integer`], are joined into larger rules with
+//! methods for sequencing ([`Rule::then`], [`Rule::ignore_then`] and
+//! [`Rule::then_ignore`]), choice ([`Rule::or`]) and repetition ([`Rule::many0`],
+//! [`Rule::separated_by`]), which are built on the [`combinators`]. Naming a rule
+//! with [`Rule::named`] adds "could not parse ..." to the errors from inside it,
+//! so the errors read like the grammar. A repetition stops at the first item it can
+//! not parse rather than failing, so a rule which should read the whole source ends
+//! with [`end`].
+//!
+//! While a rule runs it only moves the pointer of the source, so when a rule fails
+//! everything it read can be tried again by the next alternative. The chars are
+//! consumed by [`Rule::parse`] once the whole rule has been parsed. The tokens
+//! skip any whitespace before them, apart from [`until`], which keeps it.
+//!
+//! Rules are cheap to clone, so a rule can be used in more than one place, and
+//! [`Rule::lazy`] allows a rule to refer to itself.
+//!
+//! [`combinators`]: crate::combinators
+//!
+//! # Example
+//!
+//! ```
+//! use hb_parse::grammar::{end, identifier, integer, literal, Rule};
+//! use hb_parse::{IdentRules, StrParser};
+//! // filters such as `stars >= 3, lang = rust`
+//! let op = literal(">=").or(literal("<=")).or(literal("="));
+//! let value = integer()
+//!     .map(|n| n.to_string())
+//!     .or(identifier(IdentRules::css()));
+//! let filter = identifier(IdentRules::css())
+//!     .then(op)
+//!     .then(value)
+//!     .map(|((key, op), value)| format!("{} {} {}", key, op, value))
+//!     .named("filter");
+//! let query = filter.clone().separated_by(literal(",")).then_ignore(end());
+//!
+//! let mut source = StrParser::new("stars >= 3, lang = rust");
+//! assert_eq!(query.parse(&mut source).unwrap(), vec!["stars >= 3", "lang = rust"]);
+//! let mut source = StrParser::new("stars > 3");
+//! let error = filter.parse(&mut source).unwrap_err().to_string();
+//! assert!(error.starts_with("could not parse filter at line 1, column 7"));
+//! ```
+use crate::combinators;
+use crate::error::UnexpectedChar;
+use crate::{
+    CharClass, CommonParserFunctions, ErrorContext, IdentRules, ParseError, ParseResult, Source,
+    SourceCore,
+};
+use std::rc::Rc;
+
+type RuleFn<T> = dyn Fn(&mut dyn SourceCore) -> ParseResult<T>;
+
+/// A rule of a grammar which parses a `T`.
+pub struct Rule<T> {
+    name: Option<Rc<str>>,
+    parser: Rc<RuleFn<T>>,
+}
+
+impl<T> Clone for Rule<T> {
+    fn clone(&self) -> Self {
+        Rule {
+            name: self.name.clone(),
+            parser: self.parser.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Rule<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Rule").field("name", &self.name).finish()
+    }
+}
+
+impl<T: 'static> Rule<T> {
+    /// Creates a rule from a parser function. The function should only move the
+    /// pointer of the source, rather than consuming chars, so that the rule can be
+    /// rolled back when it fails.
+    pub fn new<F: Fn(&mut dyn SourceCore) -> ParseResult<T> + 'static>(parser: F) -> Rule<T> {
+        Rule {
+            name: None,
+            parser: Rc::new(parser),
+        }
+    }
+
+    /// Creates a rule which is made by calling the function each time it is run,
+    /// so a rule can contain itself, eg a list which can hold lists.
+    pub fn lazy<F: Fn() -> Rule<T> + 'static>(make: F) -> Rule<T> {
+        Rule::new(move |s| make().run(s))
+    }
+
+    /// Names the rule, so its errors say that the rule could not be parsed.
+    pub fn named(mut self, name: &str) -> Rule<T> {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Runs the rule, moving the pointer past what it parsed. When the rule fails
+    /// the pointer is put back where it was.
+    pub fn run(&self, source: &mut dyn SourceCore) -> ParseResult<T> {
+        let start = source.checkpoint();
+        match (self.parser)(source) {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                source.rollback(start)?;
+                Err(match &self.name {
+                    Some(name) => locate(
+                        source,
+                        e.make_inner().msg(format!("could not parse {}", name)),
+                    ),
+                    None => e,
+                })
+            }
+        }
+    }
+
+    /// Runs the rule from the pointer and consumes what it parsed.
+    pub fn parse(&self, source: &mut dyn SourceCore) -> ParseResult<T> {
+        let value = self.run(source)?;
+        source.consume(source.get_pointer_loc())?;
+        Ok(value)
+    }
+
+    /// Converts the value the rule parses.
+    pub fn map<U: 'static, F: Fn(T) -> U + 'static>(self, f: F) -> Rule<U> {
+        Rule::new(move |s| self.run(s).map(&f))
+    }
+
+    /// Parses this rule and then the next, giving both values.
+    pub fn then<U: 'static>(self, next: Rule<U>) -> Rule<(T, U)> {
+        Rule::new(move |s| Ok((self.run(s)?, next.run(s)?)))
+    }
+
+    /// Parses this rule and then the next, giving the value of the next.
+    pub fn ignore_then<U: 'static>(self, next: Rule<U>) -> Rule<U> {
+        Rule::new(move |s| {
+            self.run(s)?;
+            next.run(s)
+        })
+    }
+
+    /// Parses this rule and then the next, giving the value of this rule.
+    pub fn then_ignore<U: 'static>(self, next: Rule<U>) -> Rule<T> {
+        Rule::new(move |s| {
+            let value = self.run(s)?;
+            next.run(s)?;
+            Ok(value)
+        })
+    }
+
+    /// Parses this rule between the open and close rules, eg brackets.
+    pub fn delimited_by<A: 'static, B: 'static>(self, open: Rule<A>, close: Rule<B>) -> Rule<T> {
+        open.ignore_then(self).then_ignore(close)
+    }
+
+    /// Tries this rule and then the other one if it fails, see [`combinators::alt`].
+    pub fn or(self, other: Rule<T>) -> Rule<T> {
+        Rule::new(move |s| combinators::alt(s, &[&|s| self.run(s), &|s| other.run(s)]))
+    }
+
+    /// Makes the rule optional, see [`combinators::opt`].
+    pub fn opt(self) -> Rule<Option<T>> {
+        Rule::new(move |s| combinators::opt(s, |s| self.run(s)))
+    }
+
+    /// Parses the rule as many times as it can, see [`combinators::many0`].
+    pub fn many0(self) -> Rule<Vec<T>> {
+        Rule::new(move |s| combinators::many0(s, |s| self.run(s)))
+    }
+
+    /// Parses the rule as many times as it can and at least once, see
+    /// [`combinators::many1`].
+    pub fn many1(self) -> Rule<Vec<T>> {
+        Rule::new(move |s| combinators::many1(s, |s| self.run(s)))
+    }
+
+    /// Parses zero or more of the rule with the separator between them, see
+    /// [`combinators::separated_list`].
+    pub fn separated_by<U: 'static>(self, separator: Rule<U>) -> Rule<Vec<T>> {
+        Rule::new(move |s| {
+            combinators::separated_list(s, |s| self.run(s), |s| separator.run(s).map(|_| true))
+        })
+    }
+}
+
+/// Adds the position of the pointer to the error, unless it already has one.
+fn locate(source: &dyn SourceCore, e: ParseError) -> ParseError {
+    match (e.position(), source.get_position()) {
+        (None, Some((line, column))) => e.at_position(line, column),
+        _ => e,
+    }
+}
+
+/// Matches the text exactly, giving the text.
+pub fn literal(text: &str) -> Rule<String> {
+    let text = text.to_owned();
+    Rule::new(move |s| {
+        s.skip_whitespace()?;
+        let start = s.get_pointer_loc();
+        for expected in text.chars() {
+            if s.next_if(|c| c == expected)?.is_none() {
+                s.set_pointer_loc(start)?;
+                return Err(locate(
+                    s,
+                    UnexpectedChar::new()
+                        .msg(format!("expected '{}'", text))
+                        .into(),
+                ));
+            }
+        }
+        Ok(text.clone())
+    })
+}
+
+/// Parses a word of letters and digits.
+pub fn word() -> Rule<String> {
+    chars_matching(char::is_alphanumeric, "expected a word")
+}
+
+/// Parses an identifier following the rules, see
+/// [`CommonParserFunctions::parse_identifier`].
+pub fn identifier(rules: IdentRules) -> Rule<String> {
+    Rule::new(move |s| s.parse_identifier(&rules))
+}
+
+/// Parses a whole number in decimal with an optional sign.
+pub fn integer() -> Rule<i64> {
+    Rule::new(|s| {
+        s.skip_whitespace()?;
+        let start = s.get_pointer_loc();
+        s.next_if(|c| c == '-' || c == '+')?;
+        let digits = s.skip_while(|c| c.is_ascii_digit())?;
+        let text = s.read_substr(start, s.get_pointer_loc() - start)?;
+        match text.parse::<i64>() {
+            Ok(n) if digits > 0 => Ok(n),
+            _ => {
+                s.set_pointer_loc(start)?;
+                Err(locate(
+                    s,
+                    UnexpectedChar::new().msg("expected a whole number").into(),
+                ))
+            }
+        }
+    })
+}
+
+/// Parses one or more chars of the class.
+pub fn chars(class: CharClass) -> Rule<String> {
+    chars_matching(
+        move |c| class.contains(c),
+        "expected one of the chars of the class",
+    )
+}
+
+fn chars_matching<F: Fn(char) -> bool + 'static>(f: F, expected: &'static str) -> Rule<String> {
+    Rule::new(move |s| {
+        s.skip_whitespace()?;
+        let start = s.get_pointer_loc();
+        match s.skip_while(&f)? {
+            0 => Err(locate(s, UnexpectedChar::new().msg(expected).into())),
+            n => Ok(s.read_substr(start, n)?),
+        }
+    })
+}
+
+/// Parses the text up to the terminator, which is not read, or up to the end of
+/// the source. Whitespace is kept, so this suits the content between tokens. It
+/// fails if there is no text before the terminator.
+pub fn until(terminator: &str) -> Rule<String> {
+    let terminator: Vec<char> = terminator.chars().collect();
+    Rule::new(move |s| {
+        let start = s.get_pointer_loc();
+        let mut n = 0;
+        while s.char_at(start + n)?.is_some() && !found_at(s, start + n, &terminator)? {
+            n += 1;
+        }
+        if n == 0 {
+            return Err(locate(
+                s,
+                UnexpectedChar::new().msg("expected some text").into(),
+            ));
+        }
+        s.move_forward(n)?;
+        Ok(s.read_substr(start, n)?)
+    })
+}
+
+/// Returns true if the chars from i on are the text.
+fn found_at(s: &mut dyn SourceCore, i: usize, text: &[char]) -> ParseResult<bool> {
+    for (j, c) in text.iter().enumerate() {
+        if s.char_at(i + j)? != Some(*c) {
+            return Ok(false);
+        }
+    }
+    Ok(!text.is_empty())
+}
+
+/// Succeeds at the end of the source, after any whitespace.
+pub fn end() -> Rule<()> {
+    Rule::new(|s| {
+        s.skip_whitespace()?;
+        match s.peek()? {
+            None => Ok(()),
+            Some(_) => Err(locate(
+                s,
+                UnexpectedChar::new()
+                    .msg("expected the end of the source")
+                    .into(),
+            )),
+        }
+    })
+}
+
+#[cfg(test)]
+mod grammar_tests {
+    use super::*;
+    use crate::StrParser;
+
+    /// A list of numbers and lists, eg `[1, [2, 3], []]`, added up.
+    fn list() -> Rule<i64> {
+        let item = integer().or(Rule::lazy(list));
+        item.separated_by(literal(","))
+            .delimited_by(literal("["), literal("]"))
+            .map(|items| items.iter().sum())
+            .named("list")
+    }
+
+    #[test]
+    fn grammar_tests() {
+        let mut source = StrParser::new(" [1, [2, 3], [], -4] rest");
+        assert_eq!(list().parse(&mut source).unwrap(), 2);
+        assert_eq!(word().parse(&mut source).unwrap(), "rest");
+        assert!(end().parse(&mut source).is_ok());
+
+        let mut source = StrParser::new("[1, [2 3]]");
+        let error = list().parse(&mut source).unwrap_err();
+        // the error is at the `,` where the list could not go on
+        assert!(error
+            .to_string()
+            .starts_with("could not parse list at line 1, column 3"));
+        // nothing is consumed when the rule fails
+        assert_eq!(source.get_pointer_loc(), 0);
+        assert_eq!(source.read_substr(0, 3).unwrap(), "[1,");
+    }
+
+    #[test]
+    fn token_tests() {
+        let pair = chars(CharClass::new().ascii_letters())
+            .then_ignore(literal("="))
+            .then(until(";").opt())
+            .then_ignore(literal(";").opt());
+        let mut source = StrParser::new("a=1 2; bc=;d=x");
+        assert_eq!(
+            pair.many1().then_ignore(end()).parse(&mut source).unwrap(),
+            vec![
+                ("a".to_owned(), Some("1 2".to_owned())),
+                ("bc".to_owned(), None),
+                ("d".to_owned(), Some("x".to_owned())),
+            ]
+        );
+
+        let keyword = literal("if").or(literal("else"));
+        let mut source = StrParser::new("elif");
+        assert!(keyword.run(&mut source).is_err());
+        assert_eq!(source.get_pointer_loc(), 0);
+        assert!(integer().run(&mut source).is_err());
+        let mut source = StrParser::new("+12-");
+        assert_eq!(integer().run(&mut source).unwrap(), 12);
+        assert!(integer().run(&mut source).is_err());
+        assert_eq!(source.get_pointer_loc(), 3);
+    }
+}
@@ -51,7 +51,7 @@
 //! trait NewParseFuncs {
 //!    fn new_func(&mut self) -> ParseResult<bool>;
 //! }
-//! impl <T: Source> NewParseFuncs for T {
+//! impl <T: Source + ?Sized> NewParseFuncs for T {
 //!    fn new_func(&mut self) -> ParseResult<bool> {
 //!    // some logic for the parsing...
 //!        match self.next() {
@@ -73,13 +73,19 @@
 //!assert_eq!(source.new_func().unwrap(), true);
 //!assert_eq!(source.new_func().unwrap(), false);
 //! ```
+pub mod combinators;
 pub mod error;
+pub mod grammar;
 pub mod parser_funcs;
+pub mod reader;
 pub mod source;
-pub use self::parser_funcs::CommonParserFunctions;
+pub use self::parser_funcs::{
+    CharClass, CommonParserFunctions, Currency, DecimalSep, IdentRules, NumOptions,
+};
 pub use error::{ParseError, ParseResult, SourceEmpty, SourceError, SourceResult};
 pub use hb_error::{context, ErrorContext};
-pub use source::Source;
+pub use reader::SourceReader;
+pub use source::{Checkpoint, FileSource, ReaderSource, Source, SourceCore};
 
 pub struct StrParser<'a> {
     s: &'a str,                                     // the raw source of chars
@@ -87,21 +93,70 @@ pub struct StrParser<'a> {
     window_start: usize, // the current start of the window of the str
     pointer: usize,      // the current location of the next char that will be provided
     iter: std::iter::Peekable<std::str::Chars<'a>>, //the iter used to extract chars
+    window_line: usize,  // the line of the start of the window in the enclosing file
+    window_column: usize, // the column of the start of the window in the enclosing file
 }
 
 impl<'a> StrParser<'a> {
     pub fn new(s: &'a str) -> StrParser<'a> {
+        StrParser::new_with_offset(s, 1, 1)
+    }
+
+    /// Creates a parser for a snippet taken from a larger file, such as HTML in a
+    /// Markdown code fence, where the snippet starts at the line and column given
+    /// (both starting from 1). The positions from [`SourceCore::get_position`] are then
+    /// positions in the enclosing file. The column only offsets the first line of
+    /// the snippet, as the lines after it start at the beginning of a line.
+    ///
+    /// ```
+    /// use hb_parse::{SourceCore, StrParser};
+    /// let file = "# Title\n\n```html\n<p>\n  text\n```\n";
+    /// let snippet = &file[17..28];
+    /// assert_eq!(snippet, "<p>\n  text\n");
+    /// let mut source = StrParser::new_with_offset(snippet, 4, 1);
+    /// assert_eq!(source.get_position(), Some((4, 1)));
+    /// source.move_forward(6).unwrap();
+    /// assert_eq!(source.next().unwrap(), Some((6, 't')));
+    /// assert_eq!(source.get_position(), Some((5, 4)));
+    /// ```
+    pub fn new_with_offset(s: &'a str, line: usize, column: usize) -> StrParser<'a> {
         StrParser {
             s,
             sub_s: s,
             window_start: 0,
             pointer: 0,
             iter: s.chars().peekable(),
+            window_line: line,
+            window_column: column,
+        }
+    }
+
+    /// Moves the start of the window forward by n bytes, keeping track of the line
+    /// and column it is at.
+    fn move_window(&mut self, byte_n: usize) {
+        let passed = &self.sub_s[..byte_n];
+        match passed.rfind('\n') {
+            Some(i) => {
+                self.window_line += passed.matches('\n').count();
+                self.window_column = passed[i + 1..].chars().count() + 1;
+            }
+            None => self.window_column += passed.chars().count(),
         }
+        self.window_start += byte_n;
+        self.sub_s = &self.s[self.window_start..];
     }
 }
 
-impl Source for StrParser<'_> {
+// The pointer and lengths are counted in chars, so they need converting to byte
+// positions to slice the str. None if the str has less than n chars.
+fn byte_index(s: &str, n: usize) -> Option<usize> {
+    s.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(s.len()))
+        .nth(n)
+}
+
+impl SourceCore for StrParser<'_> {
     fn next(&mut self) -> SourceResult<Option<(usize, char)>> {
         match self.iter.next() {
             Some(c) => {
@@ -154,24 +209,25 @@ impl Source for StrParser<'_> {
     }
 
     fn consume(&mut self, n: usize) -> SourceResult<()> {
-        if n > self.sub_s.len() {
-            return Err(SourceError::new().msg(format!(
-                "attempted to consume {} chars when only {} remain",
-                n,
-                self.sub_s.len()
-            )));
-        }
+        let byte_n = match byte_index(self.sub_s, n) {
+            Some(i) => i,
+            None => {
+                return Err(SourceError::new().msg(format!(
+                    "attempted to consume {} chars when only {} remain",
+                    n,
+                    self.sub_s.chars().count()
+                )))
+            }
+        };
         if n != 0 {
-            // move window
-            self.window_start += n;
+            self.move_window(byte_n);
             // move pointer
             if self.pointer > n {
                 self.pointer -= n;
             } else {
                 self.pointer = 0;
             }
-            // recreate sub-string and iter
-            self.sub_s = &self.s[self.window_start..];
+            // recreate iter
             self.iter = self.sub_s.chars().peekable();
             // move iter to pointer
             if self.pointer != 0 {
@@ -183,25 +239,26 @@ impl Source for StrParser<'_> {
     }
 
     fn extract(&mut self, n: usize) -> SourceResult<String> {
-        if n > self.sub_s.len() {
-            return Err(SourceError::new().msg(format!(
-                "attempted to extract {} chars when only {} remain",
-                n,
-                self.sub_s.len()
-            )));
-        }
-        let ret = self.sub_s[0..n].to_string();
+        let byte_n = match byte_index(self.sub_s, n) {
+            Some(i) => i,
+            None => {
+                return Err(SourceError::new().msg(format!(
+                    "attempted to extract {} chars when only {} remain",
+                    n,
+                    self.sub_s.chars().count()
+                )))
+            }
+        };
+        let ret = self.sub_s[0..byte_n].to_string();
         if n != 0 {
-            // move window
-            self.window_start += n;
+            self.move_window(byte_n);
             // move pointer
             if self.pointer > n {
                 self.pointer -= n;
             } else {
                 self.pointer = 0;
             }
-            // recreate sub-string and iter
-            self.sub_s = &self.s[self.window_start..];
+            // recreate iter
             self.iter = self.sub_s.chars().peekable();
             // move iter to pointer
             if self.pointer != 0 {
@@ -212,24 +269,31 @@ impl Source for StrParser<'_> {
         Ok(ret)
     }
     fn read_substr(&mut self, start: usize, n: usize) -> SourceResult<String> {
-        if start > self.sub_s.len() {
-            return Err(SourceError::new().msg(format!(
-                "attempted to read substring from start position {} when only {} remain",
-                start,
-                self.sub_s.len()
-            )));
-        }
-        if start + n > self.sub_s.len() {
-            return Err(SourceError::new().msg(format!(
-                "attempted to read a substring of {} chars when only {} remain",
-                n,
-                self.sub_s.len() - start
-            )));
-        }
-        if n == 0 {
-            return Ok(String::new());
-        }
-        Ok(self.sub_s[start..start + n].to_string())
+        let byte_start = match byte_index(self.sub_s, start) {
+            Some(i) => i,
+            None => {
+                return Err(SourceError::new().msg(format!(
+                    "attempted to read substring from start position {} when only {} remain",
+                    start,
+                    self.sub_s.chars().count()
+                )))
+            }
+        };
+        let byte_end = match byte_index(&self.sub_s[byte_start..], n) {
+            Some(i) => byte_start + i,
+            None => {
+                return Err(SourceError::new().msg(format!(
+                    "attempted to read a substring of {} chars when only {} remain",
+                    n,
+                    self.sub_s[byte_start..].chars().count()
+                )))
+            }
+        };
+        Ok(self.sub_s[byte_start..byte_end].to_string())
+    }
+
+    fn char_at(&mut self, i: usize) -> SourceResult<Option<char>> {
+        Ok(self.sub_s.chars().nth(i))
     }
 
     fn get_pointer_loc(&self) -> usize {
@@ -262,6 +326,21 @@ impl Source for StrParser<'_> {
             '^'
         )
     }
+
+    fn get_position(&self) -> Option<(usize, usize)> {
+        // only the part of the window before the pointer needs counting
+        let before = &self.sub_s[..byte_index(self.sub_s, self.pointer)?];
+        match before.rfind('\n') {
+            Some(i) => Some((
+                self.window_line + before.matches('\n').count(),
+                before[i + 1..].chars().count() + 1,
+            )),
+            None => Some((
+                self.window_line,
+                self.window_column + before.chars().count(),
+            )),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -341,6 +420,12 @@ mod tests {
         assert_eq!(source.next().unwrap(), Some((5, 'n')));
         assert_eq!(source.next().unwrap(), Some((6, 'g')));
         assert_eq!(source.next().unwrap(), None);
+        // positions are in chars rather than bytes
+        let mut source = StrParser::new("€5 £6");
+        assert_eq!(source.consume(3).unwrap(), ());
+        assert_eq!(source.next().unwrap(), Some((0, '£')));
+        assert_eq!(source.extract(2).unwrap(), "£6".to_string());
+        assert!(source.consume(1).is_err());
     }
     #[test]
     fn strsource_extract_tests() {
@@ -390,4 +475,40 @@ mod tests {
 
         assert_eq!(source.get_context(), "is a longer sentence, it has to be over 80 characters or my tests won't work...\n                                        ^\n".to_owned());
     }
+    #[test]
+    fn strsource_get_position_tests() {
+        let mut source = StrParser::new("ab\ncd");
+        assert_eq!(source.get_position(), Some((1, 1)));
+        source.move_forward(3).unwrap();
+        assert_eq!(source.get_position(), Some((2, 1)));
+        // the window moving does not change the positions
+        source.consume(2).unwrap();
+        assert_eq!(source.get_position(), Some((2, 1)));
+        assert_eq!(source.next().unwrap(), Some((1, 'c')));
+        assert_eq!(source.get_position(), Some((2, 2)));
+
+        let mut source = StrParser::new_with_offset("é<b>\n  x", 10, 5);
+        assert_eq!(source.get_position(), Some((10, 5)));
+        source.move_forward(2).unwrap();
+        assert_eq!(source.get_position(), Some((10, 7)));
+        source.extract(7).unwrap();
+        assert_eq!(source.get_position(), Some((11, 3)));
+        source.move_forward(1).unwrap();
+        // the end of the source has a position
+        assert_eq!(source.get_position(), Some((11, 4)));
+    }
+    #[test]
+    fn strsource_error_position_tests() {
+        let mut source = StrParser::new("first\n  ! second");
+        assert_eq!(source.parse_word().unwrap(), "first".to_owned());
+        source.consume(source.get_pointer_loc()).unwrap();
+        assert_eq!(source.current_line(), Some(1));
+        assert_eq!(source.current_column(), Some(6));
+        let error = source.parse_word().unwrap_err();
+        // the position is where the word was expected, after the whitespace
+        assert_eq!(error.position(), Some((2, 3)));
+        assert!(error
+            .to_string()
+            .starts_with("could not parse word at line 2, column 3\n"));
+    }
 }
@@ -6,6 +6,7 @@ use std::any::TypeId;
 use std::fmt::Display;
 use std::ops::{Add, Mul, Rem, Sub};
 use std::str::FromStr;
+use std::time::Duration;
 
 // Trait to mark number types that can be parsed
 pub trait ParsableInts {}
@@ -32,6 +33,222 @@ macro_rules! trait_parse_num {
 }
 trait_parse_num!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64, usize, isize);
 
+/// The character used between the whole and fractional parts of a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecimalSep {
+    /// `1,234.56`
+    Point,
+    /// `1.234,56`
+    Comma,
+}
+
+impl DecimalSep {
+    pub fn as_char(&self) -> char {
+        match self {
+            DecimalSep::Point => '.',
+            DecimalSep::Comma => ',',
+        }
+    }
+}
+
+/// Options for how numbers are written, used by
+/// [`CommonParserFunctions::parse_float_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumOptions {
+    pub decimal_sep: DecimalSep,
+    /// The character allowed between groups of 3 digits in the whole part of the
+    /// number, eg the ',' in `1,234`. None if grouping is not allowed.
+    pub group_sep: Option<char>,
+    /// Allow an exponent such as `e-3` at the end of the number.
+    pub exponent: bool,
+}
+
+impl Default for NumOptions {
+    /// Numbers like `1234.56e3` with no grouping.
+    fn default() -> Self {
+        NumOptions {
+            decimal_sep: DecimalSep::Point,
+            group_sep: None,
+            exponent: true,
+        }
+    }
+}
+
+impl NumOptions {
+    /// Options for numbers written with the decimal separator provided, and the
+    /// other of '.' and ',' used for grouping digits. For example
+    /// `NumOptions::locale(DecimalSep::Comma)` parses `1.234,56`.
+    pub fn locale(decimal_sep: DecimalSep) -> NumOptions {
+        NumOptions {
+            decimal_sep,
+            group_sep: Some(match decimal_sep {
+                DecimalSep::Point => ',',
+                DecimalSep::Comma => '.',
+            }),
+            exponent: true,
+        }
+    }
+
+    /// Sets the character allowed between groups of digits, None to disallow grouping.
+    pub fn group_sep(mut self, group_sep: Option<char>) -> NumOptions {
+        self.group_sep = group_sep;
+        self
+    }
+
+    pub fn exponent(mut self, exponent: bool) -> NumOptions {
+        self.exponent = exponent;
+        self
+    }
+}
+
+/// A set of chars allowed in an identifier, used by [`IdentRules`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CharClass {
+    /// Allow the ASCII letters.
+    pub ascii_letters: bool,
+    /// Allow the ASCII digits.
+    pub digits: bool,
+    /// Allow all chars outside of ASCII.
+    pub non_ascii: bool,
+    /// Other chars which are allowed, eg `-` and `_`.
+    pub chars: Vec<char>,
+}
+
+impl CharClass {
+    /// Creates a class with no chars in it.
+    pub fn new() -> CharClass {
+        CharClass::default()
+    }
+
+    pub fn ascii_letters(mut self) -> CharClass {
+        self.ascii_letters = true;
+        self
+    }
+
+    pub fn digits(mut self) -> CharClass {
+        self.digits = true;
+        self
+    }
+
+    pub fn non_ascii(mut self) -> CharClass {
+        self.non_ascii = true;
+        self
+    }
+
+    /// Adds each of the chars in the string to the class.
+    pub fn chars(mut self, chars: &str) -> CharClass {
+        self.chars.extend(chars.chars());
+        self
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        (self.ascii_letters && c.is_ascii_alphabetic())
+            || (self.digits && c.is_ascii_digit())
+            || (self.non_ascii && !c.is_ascii())
+            || self.chars.contains(&c)
+    }
+}
+
+/// The chars allowed at the start and in the rest of an identifier, used by
+/// [`CommonParserFunctions::parse_identifier`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentRules {
+    pub start: CharClass,
+    pub rest: CharClass,
+}
+
+impl IdentRules {
+    pub fn new(start: CharClass, rest: CharClass) -> IdentRules {
+        IdentRules { start, rest }
+    }
+
+    /// HTML tag names, which start with a letter and can contain `-` (custom
+    /// elements), `:` (namespace prefixes such as `svg:rect`), `_` and `.`.
+    pub fn html_tag() -> IdentRules {
+        IdentRules {
+            start: CharClass::new().ascii_letters(),
+            rest: CharClass::new()
+                .ascii_letters()
+                .digits()
+                .non_ascii()
+                .chars("-_.:"),
+        }
+    }
+
+    /// HTML attribute names, which can also start with `_`, `:` or `@`, as the
+    /// attributes of templates such as `:prop` and `@click` do.
+    pub fn html_attribute() -> IdentRules {
+        IdentRules {
+            start: CharClass::new().ascii_letters().non_ascii().chars("_:@"),
+            rest: CharClass::new()
+                .ascii_letters()
+                .digits()
+                .non_ascii()
+                .chars("-_.:@"),
+        }
+    }
+
+    /// CSS identifiers such as class names and properties, eg `-webkit-box` or
+    /// `_private`. Escapes are not read.
+    pub fn css() -> IdentRules {
+        IdentRules {
+            start: CharClass::new().ascii_letters().non_ascii().chars("-_"),
+            rest: CharClass::new()
+                .ascii_letters()
+                .digits()
+                .non_ascii()
+                .chars("-_"),
+        }
+    }
+}
+
+/// A currency found by [`CommonParserFunctions::parse_money`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Currency {
+    /// A three letter ISO 4217 code, eg `USD`.
+    Code(String),
+    /// A currency symbol, eg `$`, `€` or `US$`.
+    Symbol(String),
+}
+
+impl Currency {
+    /// Gets the ISO 4217 code of the currency, or None if the symbol is used by more
+    /// than one currency (eg `$`).
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Currency::Code(code) => Some(code),
+            Currency::Symbol(symbol) => CURRENCY_SYMBOLS
+                .iter()
+                .find(|(s, _)| s == symbol)
+                .and_then(|(_, code)| *code),
+        }
+    }
+}
+
+/// The currency symbols which are recognised, with the ones which start with
+/// another symbol first, and the code of the currency if only one currency uses it.
+const CURRENCY_SYMBOLS: [(&str, Option<&str>); 19] = [
+    ("US$", Some("USD")),
+    ("CA$", Some("CAD")),
+    ("AU$", Some("AUD")),
+    ("NZ$", Some("NZD")),
+    ("HK$", Some("HKD")),
+    ("R$", Some("BRL")),
+    ("$", None),
+    ("€", Some("EUR")),
+    ("£", Some("GBP")),
+    ("¥", None),
+    ("₹", Some("INR")),
+    ("₩", Some("KRW")),
+    ("₽", Some("RUB")),
+    ("₺", Some("TRY")),
+    ("₪", Some("ILS")),
+    ("₫", Some("VND")),
+    ("₱", Some("PHP")),
+    ("₴", Some("UAH")),
+    ("฿", Some("THB")),
+];
+
 pub trait CommonParserFunctions {
     // Check functions are the basic level which checks the upcoming chars and moves the pointer if
     // it is there. They are used by the other functions.
@@ -56,20 +273,73 @@ pub trait CommonParserFunctions {
     /// Parses a word from the upcoming chars.
     /// A word is a all alphanumeric characters leading up to a non-alphanumeric character.
     fn parse_word(&mut self) -> ParseResult<String>;
+    /// Parses an identifier, such as a tag name or a CSS class, from the upcoming
+    /// chars. The first char must be in the start class of the rules and the
+    /// identifier runs until a char which is not in the rest class.
+    fn parse_identifier(&mut self, rules: &IdentRules) -> ParseResult<String>;
     /// Parses a string from the upcoming chars..
     /// A string is either a word, or a set of chars enclosed by " or '.
     fn parse_string(&mut self) -> ParseResult<String>;
+    /// Parses a string enclosed by " or ' from the upcoming chars, returning its
+    /// contents with the escape sequences `\n`, `\t`, `\"`, `\'` and `\\` replaced, so
+    /// `"say \"hi\""` is `say "hi"`.
+    fn parse_quoted_string(&mut self) -> ParseResult<String>;
+    /// Parses a quoted string as [`CommonParserFunctions::parse_quoted_string`] does,
+    /// with escape sequences starting with the escape char provided rather than `\`.
+    fn parse_quoted_string_with(&mut self, escape: char) -> ParseResult<String>;
     /// Parses the contents of some brackets from the upcoming chars.
     fn parse_brackets(&mut self) -> ParseResult<String>;
     /// Parses a float from the upcoming chars.
     fn parse_float<N: ParsableNums + ParsableFloats + std::str::FromStr>(
         &mut self,
     ) -> ParseResult<N>;
+    /// Parses a finite float which is written using the [`NumOptions`] provided, eg with
+    /// a decimal comma. Unlike [`CommonParserFunctions::parse_float`] inf and nan are
+    /// not allowed.
+    fn parse_float_with(&mut self, options: &NumOptions) -> ParseResult<f64>;
     /// Parses a integer from the upcoming chars.
+    /// Hex, octal and binary are read when prefixed with `0x`, `0o` or `0b`, in which
+    /// case the digits may be separated with `_` (eg `0xFF_FF`).
     fn parse_num<N: ParsableNums + ParsableInts + std::str::FromStr>(&mut self) -> ParseResult<N>;
     /// Parses a symbol from the upcoming chars.
     /// A symbol is defined as non-alphanumeric and non-whitespace.
     fn parse_symbol(&mut self) -> ParseResult<char>;
+    /// Parses a percentage (eg `42%` or `42.5 %`) from the upcoming chars, returning
+    /// the number before the `%`.
+    fn parse_percentage(&mut self) -> ParseResult<f64>;
+    /// Parses a ratio (eg `16/9`, `16:9` or `1.85`) from the upcoming chars. A single
+    /// number is a ratio with a second number of 1.
+    fn parse_ratio(&mut self) -> ParseResult<(f64, f64)>;
+    /// Parses a roman numeral (eg `XIV` or `xiv`) from the upcoming chars. Only the
+    /// standard form of numbers from 1 to 3999 is allowed, so `IIII` is an error.
+    fn parse_roman_numeral(&mut self) -> ParseResult<u32>;
+    /// Parses an ordinal (eg `1st`, `22nd` or `13th`) from the upcoming chars,
+    /// returning the number. The suffix must be the correct one for the number.
+    fn parse_ordinal(&mut self) -> ParseResult<u32>;
+    /// Parses an amount of money (eg `$1,299.99`, `€ 12,50`, `5.00 USD` or `($3.50)`)
+    /// from the upcoming chars, returning the amount and the currency. The decimal
+    /// separator is worked out from the amount, with a single separator followed by 3
+    /// digits read as grouping (so `€1.299` is 1299). Amounts in brackets are negative.
+    fn parse_money(&mut self) -> ParseResult<(f64, Currency)>;
+    /// Parses a duration from the upcoming chars, written either
+    /// - with units, eg `1h 30m`, `2 hours 5 mins` or `500ms`.
+    /// - as an ISO 8601 duration, eg `PT1H30M` or `P1DT12H`. Years and months are not
+    ///   allowed as they do not have a fixed length.
+    /// - as a clock, eg `01:30:00` or `3:45` (minutes and seconds).
+    fn parse_duration(&mut self) -> ParseResult<Duration>;
+    /// Parses the upcoming chars up to the terminator, such as the text of an HTML
+    /// text node up to the next `<`, returning them without the terminator. The
+    /// pointer is left on the terminator, or after it if `consume_terminator` is
+    /// true. It is an error if the terminator is not found.
+    fn parse_until(&mut self, terminator: &str, consume_terminator: bool) -> ParseResult<String>;
+
+    // Peek functions look at the upcoming chars without moving the pointer, so they can be
+    // used to decide which parse function to call.
+    /// Gets the upcoming word, skipping any whitespace before it, or None if the
+    /// upcoming chars are not a word.
+    fn peek_word(&mut self) -> ParseResult<Option<String>>;
+    /// Gets the next `n` chars, or fewer if the source runs out.
+    fn peek_str(&mut self, n: usize) -> ParseResult<String>;
 
     // Read functions build on the parse functions but also shift the internal buffer.
     /// Reads a word from the upcoming chars.
@@ -89,6 +359,9 @@ pub trait CommonParserFunctions {
     /// Reads a symbol from the upcoming chars.
     /// A symbol is defined as non-alphanumeric and non-whitespace.
     fn read_symbol(&mut self) -> ParseResult<char>;
+    /// Reads the upcoming chars up to the terminator, returning them without the
+    /// terminator, which is also read if `consume_terminator` is true.
+    fn read_until(&mut self, terminator: char, consume_terminator: bool) -> ParseResult<String>;
 
     // Match functions use the parse function to extract some data and then compares it to the
     // value provided.
@@ -116,21 +389,226 @@ pub trait CommonParserFunctions {
     fn skip_whitespace(&mut self) -> ParseResult<()>;
 }
 
-impl<T: Source> CommonParserFunctions for T {
+impl<T: Source + ?Sized> CommonParserFunctions for T {
     // WORD - Implementations for check, parse and read.
-    #[context("could not check word")]
     fn check_word(&mut self) -> ParseResult<bool> {
+        let result = self.check_word_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_word(&mut self) -> ParseResult<String> {
+        let result = self.parse_word_unlocated();
+        self.locate(result)
+    }
+
+    fn read_word(&mut self) -> ParseResult<String> {
+        let result = self.read_word_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_identifier(&mut self, rules: &IdentRules) -> ParseResult<String> {
+        let result = self.parse_identifier_unlocated(rules);
+        self.locate(result)
+    }
+
+    fn check_string(&mut self) -> ParseResult<bool> {
+        todo!()
+    }
+
+    fn check_num(&mut self) -> ParseResult<bool> {
+        todo!()
+    }
+
+    fn check_float(&mut self) -> ParseResult<bool> {
+        todo!()
+    }
+
+    fn check_bracket_contents(&mut self) -> ParseResult<bool> {
+        todo!()
+    }
+
+    fn check_symbol(&mut self) -> ParseResult<bool> {
+        todo!()
+    }
+
+    fn parse_string(&mut self) -> ParseResult<String> {
+        let result = self.parse_string_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_quoted_string(&mut self) -> ParseResult<String> {
+        self.parse_quoted_string_with('\\')
+    }
+
+    fn parse_quoted_string_with(&mut self, escape: char) -> ParseResult<String> {
+        let result = self.parse_quoted_string_with_unlocated(escape);
+        self.locate(result)
+    }
+
+    fn parse_brackets(&mut self) -> ParseResult<String> {
+        let result = self.parse_brackets_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_num<N: ParsableNums + ParsableInts + std::str::FromStr>(&mut self) -> ParseResult<N> {
+        let result = self.parse_num_unlocated::<N>();
+        self.locate(result)
+    }
+
+    fn parse_float<N: ParsableNums + ParsableFloats + std::str::FromStr>(
+        &mut self,
+    ) -> ParseResult<N> {
+        let result = self.parse_float_unlocated::<N>();
+        self.locate(result)
+    }
+
+    fn parse_float_with(&mut self, options: &NumOptions) -> ParseResult<f64> {
+        let result = self.parse_float_with_unlocated(options);
+        self.locate(result)
+    }
+
+    fn parse_percentage(&mut self) -> ParseResult<f64> {
+        let result = self.parse_percentage_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_ratio(&mut self) -> ParseResult<(f64, f64)> {
+        let result = self.parse_ratio_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_roman_numeral(&mut self) -> ParseResult<u32> {
+        let result = self.parse_roman_numeral_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_ordinal(&mut self) -> ParseResult<u32> {
+        let result = self.parse_ordinal_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_money(&mut self) -> ParseResult<(f64, Currency)> {
+        let result = self.parse_money_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_duration(&mut self) -> ParseResult<Duration> {
+        let result = self.parse_duration_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_symbol(&mut self) -> ParseResult<char> {
+        let result = self.parse_symbol_unlocated();
+        self.locate(result)
+    }
+
+    fn read_symbol(&mut self) -> ParseResult<char> {
+        let result = self.read_symbol_unlocated();
+        self.locate(result)
+    }
+
+    fn parse_until(&mut self, terminator: &str, consume_terminator: bool) -> ParseResult<String> {
+        let result = self.parse_until_unlocated(terminator, consume_terminator);
+        self.locate(result)
+    }
+
+    fn read_until(&mut self, terminator: char, consume_terminator: bool) -> ParseResult<String> {
+        let result = self.read_until_unlocated(terminator, consume_terminator);
+        self.locate(result)
+    }
+
+    fn peek_word(&mut self) -> ParseResult<Option<String>> {
+        let result = self.peek_word_unlocated();
+        self.locate(result)
+    }
+
+    fn peek_str(&mut self, n: usize) -> ParseResult<String> {
+        let result = self.peek_str_unlocated(n);
+        self.locate(result)
+    }
+
+    fn match_char(&mut self, val: char) -> ParseResult<bool> {
+        let result = self.match_char_unlocated(val);
+        self.locate(result)
+    }
+
+    fn match_num<N: ParsableInts + Display + std::str::FromStr>(
+        &mut self,
+        val: N,
+    ) -> ParseResult<bool> {
+        let result = self.match_num_unlocated::<N>(val);
+        self.locate(result)
+    }
+
+    fn match_str(&mut self, val: &str) -> ParseResult<bool> {
+        let result = self.match_str_unlocated(val);
+        self.locate(result)
+    }
+
+    fn consume_whitespace(&mut self) -> ParseResult<()> {
+        let result = self.consume_whitespace_unlocated();
+        self.locate(result)
+    }
+
+    fn skip_whitespace(&mut self) -> ParseResult<()> {
+        let result = self.skip_whitespace_unlocated();
+        self.locate(result)
+    }
+
+    fn read_string(&mut self) -> ParseResult<String> {
+        todo!()
+    }
+
+    fn read_bracket_contents(&mut self) -> ParseResult<String> {
+        todo!()
+    }
+
+    fn read_float<N: ParsableNums + ParsableFloats + FromStr>(&mut self) -> ParseResult<N> {
+        todo!()
+    }
+
+    fn read_num<N: ParsableNums + ParsableInts + FromStr>(&mut self) -> ParseResult<N> {
+        todo!()
+    }
+
+    fn match_bracket_contents(&mut self, val: &str) -> ParseResult<bool> {
+        todo!()
+    }
+
+    fn match_float<N: ParsableFloats + Display + FromStr>(&mut self, val: N) -> ParseResult<bool> {
+        todo!()
+    }
+
+    fn match_symbol(&mut self, val: char) -> ParseResult<bool> {
+        todo!()
+    }
+}
+
+/// The implementations of the [`CommonParserFunctions`] which can fail, which are
+/// wrapped to add the position of the pointer to any error.
+trait UnlocatedParserFunctions: Source {
+    /// Adds the position of the pointer to the error, unless the error already has
+    /// the position from a parser function it called.
+    fn locate<V>(&self, result: ParseResult<V>) -> ParseResult<V> {
+        result.map_err(|e| match (e.position(), self.get_position()) {
+            (None, Some((line, column))) => e.at_position(line, column),
+            _ => e,
+        })
+    }
+
+    #[context("could not check word")]
+    fn check_word_unlocated(&mut self) -> ParseResult<bool> {
         self.skip_whitespace()?;
-        let start_i = self.get_pointer_loc();
+        let start = self.checkpoint();
         let mut has_char = false;
         loop {
             match self.peek() {
                 Err(e) => {
-                    self.set_pointer_loc(start_i);
+                    self.rollback(start)?;
                     return Err(e);
                 }
                 Ok(None) => {
-                    if self.get_pointer_loc() == start_i {
+                    if self.checkpoint() == start {
                         return Err(SourceEmpty::new());
                     }
                     return Ok(true);
@@ -148,16 +626,16 @@ impl<T: Source> CommonParserFunctions for T {
     }
 
     #[context("could not parse word")]
-    fn parse_word(&mut self) -> ParseResult<String> {
+    fn parse_word_unlocated(&mut self) -> ParseResult<String> {
         self.skip_whitespace()?;
-        let start_i = self.get_pointer_loc();
+        let start = self.checkpoint();
         match self.check_word() {
             Err(e) => {
-                self.set_pointer_loc(start_i);
+                self.rollback(start)?;
                 return Err(e);
             }
             Ok(true) => {
-                return Ok(self.read_substr(start_i, self.get_pointer_loc())?);
+                return Ok(self.read_substr(start.pointer_loc(), self.get_pointer_loc())?);
             }
             Ok(false) => {
                 return Err(UnexpectedChar::new());
@@ -166,7 +644,7 @@ impl<T: Source> CommonParserFunctions for T {
     }
 
     #[context("could not read word")]
-    fn read_word(&mut self) -> ParseResult<String> {
+    fn read_word_unlocated(&mut self) -> ParseResult<String> {
         if self.get_pointer_loc() != 0 {
             return Err(SourceInvalidState::new());
         }
@@ -175,24 +653,33 @@ impl<T: Source> CommonParserFunctions for T {
         Ok(word)
     }
 
-    fn check_string(&mut self) -> ParseResult<bool> {
-        todo!()
-    }
-    fn check_num(&mut self) -> ParseResult<bool> {
-        todo!()
-    }
-    fn check_float(&mut self) -> ParseResult<bool> {
-        todo!()
-    }
-    fn check_bracket_contents(&mut self) -> ParseResult<bool> {
-        todo!()
-    }
-    fn check_symbol(&mut self) -> ParseResult<bool> {
-        todo!()
+    #[context("could not parse identifier")]
+    fn parse_identifier_unlocated(&mut self, rules: &IdentRules) -> ParseResult<String> {
+        self.skip_whitespace()?;
+        let start = self.checkpoint();
+        match self.peek()? {
+            Some((_, c)) if rules.start.contains(c) => {
+                self.next()?;
+            }
+            Some((_, c)) => {
+                return Err(
+                    UnexpectedChar::new().msg(format!("'{}' can not start an identifier", c))
+                );
+            }
+            None => return Err(SourceEmpty::new()),
+        }
+        let len = 1 + self.skip_while(|c| rules.rest.contains(c))?;
+        match self.read_substr(start.pointer_loc(), len) {
+            Ok(ident) => Ok(ident),
+            Err(e) => {
+                self.rollback(start)?;
+                Err(e.into())
+            }
+        }
     }
 
     #[context("could not parse string")]
-    fn parse_string(&mut self) -> ParseResult<String> {
+    fn parse_string_unlocated(&mut self) -> ParseResult<String> {
         if self.get_pointer_loc() != 0 {
             return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
         }
@@ -242,8 +729,65 @@ impl<T: Source> CommonParserFunctions for T {
         }
     }
 
+    #[context("could not parse quoted string")]
+    fn parse_quoted_string_with_unlocated(&mut self, escape: char) -> ParseResult<String> {
+        if self.get_pointer_loc() != 0 {
+            return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
+        }
+        self.skip_whitespace()?;
+        let quote = match self.next()? {
+            Some((_, c)) if c == '"' || c == '\'' => c,
+            Some((_, c)) => {
+                self.reset_pointer_loc();
+                return Err(UnexpectedChar::new().msg(format!(
+                    "expected a quote at the start of the string but found '{}'",
+                    c
+                )));
+            }
+            None => {
+                self.reset_pointer_loc();
+                return Err(SourceEmpty::new());
+            }
+        };
+        let mut text = String::new();
+        loop {
+            let c = match self.next()? {
+                Some((_, c)) => c,
+                None => {
+                    self.reset_pointer_loc();
+                    return Err(SourceEmpty::new()
+                        .msg(format!("the closing {} of the string was not found", quote)));
+                }
+            };
+            if c == quote {
+                break;
+            }
+            if c != escape {
+                text.push(c);
+                continue;
+            }
+            match self.next()? {
+                Some((_, 'n')) => text.push('\n'),
+                Some((_, 't')) => text.push('\t'),
+                Some((_, c)) if c == '"' || c == '\'' || c == escape => text.push(c),
+                Some((_, c)) => {
+                    self.reset_pointer_loc();
+                    return Err(UnexpectedChar::new()
+                        .msg(format!("unknown escape sequence '{}{}'", escape, c)));
+                }
+                None => {
+                    self.reset_pointer_loc();
+                    return Err(SourceEmpty::new()
+                        .msg(format!("the closing {} of the string was not found", quote)));
+                }
+            }
+        }
+        self.consume(self.get_pointer_loc())?;
+        Ok(text)
+    }
+
     #[context("could not parse brackets")]
-    fn parse_brackets(&mut self) -> ParseResult<String> {
+    fn parse_brackets_unlocated(&mut self) -> ParseResult<String> {
         if self.get_pointer_loc() != 0 {
             return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
         }
@@ -303,46 +847,77 @@ impl<T: Source> CommonParserFunctions for T {
     }
 
     #[context("could not parse num")]
-    fn parse_num<N: ParsableNums + ParsableInts + std::str::FromStr>(&mut self) -> ParseResult<N> {
+    fn parse_num_unlocated<N: ParsableNums + ParsableInts + std::str::FromStr>(
+        &mut self,
+    ) -> ParseResult<N> {
         if self.get_pointer_loc() != 0 {
             return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
         }
         self.skip_whitespace()?;
         let start_i = self.get_pointer_loc();
         // skip a +/-
+        let mut negative = false;
         match self.peek()? {
             None => {
                 return Err(SourceEmpty::new());
             }
             Some((_, c)) => {
                 if c == '-' || c == '+' {
+                    negative = c == '-';
                     self.next()?;
                 }
             }
         }
+        // 0x, 0o and 0b prefixes give the radix
+        let mut radix = 10;
+        if let Some((_, '0')) = self.peek()? {
+            self.next()?;
+            radix = match self.peek()? {
+                Some((_, 'x' | 'X')) => 16,
+                Some((_, 'o' | 'O')) => 8,
+                Some((_, 'b' | 'B')) => 2,
+                _ => 10,
+            };
+            if radix != 10 {
+                self.next()?;
+            }
+        }
+        let mut digits = String::new();
         while let Some((_, c)) = self.peek()? {
-            if !c.is_ascii_digit() {
+            if c.is_digit(radix) {
+                digits.push(c);
+            } else if c != '_' || radix == 10 {
                 break;
-            } else {
-                self.next()?;
             }
+            self.next()?;
         }
         let substr = self
             .read_substr(start_i, self.get_pointer_loc() - start_i)
             .unwrap();
-        match substr.parse::<N>() {
-            Err(_) => {
+        let parsed = if radix == 10 {
+            substr.parse::<N>().ok()
+        } else {
+            // the value is converted through its decimal form, which also checks
+            // it fits in N
+            u128::from_str_radix(&digits, radix).ok().and_then(|value| {
+                let sign = if negative { "-" } else { "" };
+                format!("{}{}", sign, value).parse::<N>().ok()
+            })
+        };
+        match parsed {
+            None => {
                 self.reset_pointer_loc();
                 return Err(ParseError::new().msg(format!("'{}' is not a valid number", substr)));
             }
-            Ok(n) => {
+            Some(n) => {
                 self.consume(self.get_pointer_loc())?;
                 Ok(n)
             }
         }
     }
+
     #[context("could not parse num")]
-    fn parse_float<N: ParsableNums + ParsableFloats + std::str::FromStr>(
+    fn parse_float_unlocated<N: ParsableNums + ParsableFloats + std::str::FromStr>(
         &mut self,
     ) -> ParseResult<N> {
         if self.get_pointer_loc() != 0 {
@@ -472,8 +1047,314 @@ impl<T: Source> CommonParserFunctions for T {
         }
     }
 
+    #[context("could not parse float")]
+    fn parse_float_with_unlocated(&mut self, options: &NumOptions) -> ParseResult<f64> {
+        if self.get_pointer_loc() != 0 {
+            return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
+        }
+        self.skip_whitespace()?;
+        match scan_decimal(self, options) {
+            Ok(Some(n)) => {
+                self.consume(self.get_pointer_loc())?;
+                Ok(n)
+            }
+            Ok(None) => {
+                self.reset_pointer_loc();
+                Err(ParseError::new().msg("no number found"))
+            }
+            Err(e) => {
+                self.reset_pointer_loc();
+                Err(e)
+            }
+        }
+    }
+
+    #[context("could not parse percentage")]
+    fn parse_percentage_unlocated(&mut self) -> ParseResult<f64> {
+        if self.get_pointer_loc() != 0 {
+            return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
+        }
+        self.skip_whitespace()?;
+        let value = match scan_decimal(self, &NumOptions::default())? {
+            Some(v) => v,
+            None => {
+                self.reset_pointer_loc();
+                return Err(ParseError::new().msg("no number found before the '%'"));
+            }
+        };
+        self.skip_whitespace()?;
+        match self.peek()? {
+            Some((_, '%')) => {
+                self.next()?;
+                self.consume(self.get_pointer_loc())?;
+                Ok(value)
+            }
+            Some((_, c)) => {
+                self.reset_pointer_loc();
+                Err(ParseError::new()
+                    .msg(format!("expected '%' after {} but found '{}'", value, c)))
+            }
+            None => {
+                self.reset_pointer_loc();
+                Err(SourceEmpty::new().into())
+            }
+        }
+    }
+
+    #[context("could not parse ratio")]
+    fn parse_ratio_unlocated(&mut self) -> ParseResult<(f64, f64)> {
+        if self.get_pointer_loc() != 0 {
+            return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
+        }
+        self.skip_whitespace()?;
+        let first = match scan_decimal(self, &NumOptions::default())? {
+            Some(v) => v,
+            None => {
+                self.reset_pointer_loc();
+                return Err(ParseError::new().msg("no number found at the start of the ratio"));
+            }
+        };
+        // the separator and second number are optional, so remember where the first ended
+        let first_end = self.get_pointer_loc();
+        self.skip_whitespace()?;
+        let second = match self.peek()? {
+            Some((_, '/')) | Some((_, ':')) => {
+                self.next()?;
+                self.skip_whitespace()?;
+                match scan_decimal(self, &NumOptions::default())? {
+                    Some(v) => v,
+                    None => {
+                        self.reset_pointer_loc();
+                        return Err(ParseError::new().msg(format!(
+                            "no number found after the separator in the ratio starting {}",
+                            first
+                        )));
+                    }
+                }
+            }
+            _ => {
+                self.set_pointer_loc(first_end)?;
+                1.0
+            }
+        };
+        if first < 0.0 || second < 0.0 {
+            self.reset_pointer_loc();
+            return Err(ParseError::new().msg(format!(
+                "the ratio {}/{} can not be negative",
+                first, second
+            )));
+        }
+        self.consume(self.get_pointer_loc())?;
+        Ok((first, second))
+    }
+
+    #[context("could not parse roman numeral")]
+    fn parse_roman_numeral_unlocated(&mut self) -> ParseResult<u32> {
+        if self.get_pointer_loc() != 0 {
+            return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
+        }
+        self.skip_whitespace()?;
+        let start_i = self.get_pointer_loc();
+        let mut numeral = String::new();
+        while let Some((_, c)) = self.peek()? {
+            if !c.is_alphanumeric() {
+                break;
+            }
+            numeral.push(c);
+            self.next()?;
+        }
+        if numeral.is_empty() {
+            self.reset_pointer_loc();
+            match self.peek()? {
+                None => return Err(SourceEmpty::new()),
+                Some((_, c)) => {
+                    return Err(UnexpectedChar::new()
+                        .msg(format!("'{}' was found instead of a roman numeral", c)))
+                }
+            }
+        }
+        match roman_value(&numeral) {
+            Some(n) => {
+                self.consume(start_i + numeral.len())?;
+                Ok(n)
+            }
+            None => {
+                self.reset_pointer_loc();
+                Err(ParseError::new().msg(format!("'{}' is not a valid roman numeral", numeral)))
+            }
+        }
+    }
+
+    #[context("could not parse ordinal")]
+    fn parse_ordinal_unlocated(&mut self) -> ParseResult<u32> {
+        if self.get_pointer_loc() != 0 {
+            return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
+        }
+        self.skip_whitespace()?;
+        let mut digits = String::new();
+        while let Some((_, c)) = self.peek()? {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.next()?;
+        }
+        let mut suffix = String::new();
+        while let Some((_, c)) = self.peek()? {
+            if !c.is_alphanumeric() {
+                break;
+            }
+            suffix.push(c);
+            self.next()?;
+        }
+        if digits.is_empty() {
+            self.reset_pointer_loc();
+            return match self.peek()? {
+                None => Err(SourceEmpty::new().into()),
+                Some(_) => {
+                    Err(ParseError::new().msg("no number found at the start of the ordinal"))
+                }
+            };
+        }
+        let n = match digits.parse::<u32>() {
+            Ok(n) => n,
+            Err(_) => {
+                self.reset_pointer_loc();
+                return Err(ParseError::new().msg(format!("'{}' is not a valid number", digits)));
+            }
+        };
+        let expected = ordinal_suffix(n);
+        if !suffix.eq_ignore_ascii_case(expected) {
+            self.reset_pointer_loc();
+            return Err(ParseError::new().msg(format!(
+                "expected '{}{}' but found '{}{}'",
+                digits, expected, digits, suffix
+            )));
+        }
+        self.consume(self.get_pointer_loc())?;
+        Ok(n)
+    }
+
+    #[context("could not parse money")]
+    fn parse_money_unlocated(&mut self) -> ParseResult<(f64, Currency)> {
+        if self.get_pointer_loc() != 0 {
+            return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
+        }
+        self.skip_whitespace()?;
+        if self.peek()?.is_none() {
+            return Err(SourceEmpty::new());
+        }
+        let in_brackets = matches!(self.peek()?, Some((_, '(')));
+        if in_brackets {
+            self.next()?;
+            self.skip_whitespace()?;
+        }
+        // a '-' before the amount is handled when reading the amount, so only take it
+        // here if it is before the currency, eg -$5
+        let mut negative = false;
+        let sign_i = self.get_pointer_loc();
+        if let Some((_, '-')) = self.peek()? {
+            self.next()?;
+            negative = true;
+        }
+        let mut currency = scan_currency(self)?;
+        if currency.is_some() {
+            self.skip_whitespace()?;
+        } else if negative {
+            self.set_pointer_loc(sign_i)?;
+            negative = false;
+        }
+        let amount = match scan_amount(self)? {
+            Some(a) => a,
+            None => {
+                self.reset_pointer_loc();
+                return Err(ParseError::new().msg("no amount found"));
+            }
+        };
+        if currency.is_none() {
+            self.skip_whitespace()?;
+            currency = scan_currency(self)?;
+        }
+        let currency = match currency {
+            Some(c) => c,
+            None => {
+                self.reset_pointer_loc();
+                return Err(
+                    ParseError::new().msg(format!("no currency found before or after {}", amount))
+                );
+            }
+        };
+        if in_brackets {
+            self.skip_whitespace()?;
+            match self.peek()? {
+                Some((_, ')')) => {
+                    self.next()?;
+                }
+                _ => {
+                    self.reset_pointer_loc();
+                    return Err(ParseError::new().msg(format!(
+                        "expected ')' after the amount {} in brackets",
+                        amount
+                    )));
+                }
+            }
+        }
+        self.consume(self.get_pointer_loc())?;
+        if negative || in_brackets {
+            Ok((-amount, currency))
+        } else {
+            Ok((amount, currency))
+        }
+    }
+
+    #[context("could not parse duration")]
+    fn parse_duration_unlocated(&mut self) -> ParseResult<Duration> {
+        if self.get_pointer_loc() != 0 {
+            return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
+        }
+        self.skip_whitespace()?;
+        if self.peek()?.is_none() {
+            return Err(SourceEmpty::new());
+        }
+        let start_i = self.get_pointer_loc();
+        let secs = match self.peek()? {
+            Some((_, 'P')) => scan_iso_duration(self),
+            _ => {
+                let is_clock =
+                    !scan_digits(self)?.is_empty() && matches!(self.peek()?, Some((_, ':')));
+                self.set_pointer_loc(start_i)?;
+                if is_clock {
+                    scan_clock_duration(self)
+                } else {
+                    scan_unit_duration(self)
+                }
+            }
+        };
+        let secs = match secs {
+            Ok(Some(secs)) => secs,
+            Ok(None) => {
+                self.reset_pointer_loc();
+                return Err(ParseError::new().msg("no duration found"));
+            }
+            Err(e) => {
+                self.reset_pointer_loc();
+                return Err(e);
+            }
+        };
+        match Duration::try_from_secs_f64(secs) {
+            Ok(d) => {
+                self.consume(self.get_pointer_loc())?;
+                Ok(d)
+            }
+            Err(_) => {
+                self.reset_pointer_loc();
+                Err(ParseError::new().msg(format!("{} seconds is too long for a duration", secs)))
+            }
+        }
+    }
+
     #[context("could not parse symbol")]
-    fn parse_symbol(&mut self) -> ParseResult<char> {
+    fn parse_symbol_unlocated(&mut self) -> ParseResult<char> {
         if self.get_pointer_loc() != 0 {
             return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
         }
@@ -481,7 +1362,7 @@ impl<T: Source> CommonParserFunctions for T {
     }
 
     #[context("could not read symbol")]
-    fn read_symbol(&mut self) -> ParseResult<char> {
+    fn read_symbol_unlocated(&mut self) -> ParseResult<char> {
         self.skip_whitespace()?;
         match self.peek()? {
             None => Err(SourceEmpty::new().into()),
@@ -499,8 +1380,80 @@ impl<T: Source> CommonParserFunctions for T {
         }
     }
 
+    #[context("could not parse until the terminator")]
+    fn parse_until_unlocated(
+        &mut self,
+        terminator: &str,
+        consume_terminator: bool,
+    ) -> ParseResult<String> {
+        let start = self.checkpoint();
+        let mut text = String::new();
+        while !text.ends_with(terminator) {
+            match self.next()? {
+                Some((_, c)) => text.push(c),
+                None => {
+                    self.rollback(start)?;
+                    return Err(SourceEmpty::new()
+                        .msg(format!("the terminator '{}' was not found", terminator)));
+                }
+            }
+        }
+        text.truncate(text.len() - terminator.len());
+        if !consume_terminator {
+            self.move_back(terminator.chars().count())?;
+        }
+        Ok(text)
+    }
+
+    #[context("could not read until the terminator")]
+    fn read_until_unlocated(
+        &mut self,
+        terminator: char,
+        consume_terminator: bool,
+    ) -> ParseResult<String> {
+        if self.get_pointer_loc() != 0 {
+            return Err(SourceInvalidState::new());
+        }
+        let text = self.parse_until(terminator.encode_utf8(&mut [0; 4]), consume_terminator)?;
+        self.consume(self.get_pointer_loc())?;
+        Ok(text)
+    }
+
+    #[context("could not peek word")]
+    fn peek_word_unlocated(&mut self) -> ParseResult<Option<String>> {
+        let start = self.checkpoint();
+        let word = self.skip_whitespace().and_then(|_| {
+            let word_start = self.get_pointer_loc();
+            let len = self.skip_while(char::is_alphanumeric)?;
+            match len {
+                0 => Ok(None),
+                _ => Ok(Some(self.read_substr(word_start, len)?)),
+            }
+        });
+        self.rollback(start)?;
+        word
+    }
+
+    #[context("could not peek chars")]
+    fn peek_str_unlocated(&mut self, n: usize) -> ParseResult<String> {
+        let start = self.checkpoint();
+        let mut text = String::new();
+        let result = loop {
+            if text.chars().count() == n {
+                break Ok(text);
+            }
+            match self.next() {
+                Ok(Some((_, c))) => text.push(c),
+                Ok(None) => break Ok(text),
+                Err(e) => break Err(e.into()),
+            }
+        };
+        self.rollback(start)?;
+        result
+    }
+
     #[context("could not match char {val}")]
-    fn match_char(&mut self, val: char) -> ParseResult<bool> {
+    fn match_char_unlocated(&mut self, val: char) -> ParseResult<bool> {
         if self.get_pointer_loc() != 0 {
             return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
         }
@@ -520,7 +1473,7 @@ impl<T: Source> CommonParserFunctions for T {
     }
 
     #[context("could not match num {val}")]
-    fn match_num<N: ParsableInts + Display + std::str::FromStr>(
+    fn match_num_unlocated<N: ParsableInts + Display + std::str::FromStr>(
         &mut self,
         val: N,
     ) -> ParseResult<bool> {
@@ -528,7 +1481,7 @@ impl<T: Source> CommonParserFunctions for T {
     }
 
     #[context("could not match str {val}")]
-    fn match_str(&mut self, val: &str) -> ParseResult<bool> {
+    fn match_str_unlocated(&mut self, val: &str) -> ParseResult<bool> {
         if self.get_pointer_loc() != 0 {
             return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
         }
@@ -564,7 +1517,7 @@ impl<T: Source> CommonParserFunctions for T {
     }
 
     #[context("could not consume whitespace")]
-    fn consume_whitespace(&mut self) -> ParseResult<()> {
+    fn consume_whitespace_unlocated(&mut self) -> ParseResult<()> {
         if self.get_pointer_loc() != 0 {
             return Err(ParseError::new().msg(format!("Parser has already been used, and has left a pointer at position {} (which should be 0).", self.get_pointer_loc())));
         }
@@ -586,56 +1539,470 @@ impl<T: Source> CommonParserFunctions for T {
     }
 
     #[context("could not skip whitespace")]
-    fn skip_whitespace(&mut self) -> ParseResult<()> {
-        loop {
-            match self.peek()? {
-                None => {
-                    return Ok(());
-                }
-                Some((_, c)) => {
-                    if c.is_whitespace() {
-                        self.next()?;
-                    } else {
-                        return Ok(());
-                    }
+    fn skip_whitespace_unlocated(&mut self) -> ParseResult<()> {
+        self.skip_while(char::is_whitespace)?;
+        Ok(())
+    }
+}
+
+impl<T: Source + ?Sized> UnlocatedParserFunctions for T {}
+
+/// The value of each roman numeral and the pairs which are written subtractively,
+/// largest first.
+const ROMAN_NUMERALS: [(&str, u32); 13] = [
+    ("M", 1000),
+    ("CM", 900),
+    ("D", 500),
+    ("CD", 400),
+    ("C", 100),
+    ("XC", 90),
+    ("L", 50),
+    ("XL", 40),
+    ("X", 10),
+    ("IX", 9),
+    ("V", 5),
+    ("IV", 4),
+    ("I", 1),
+];
+
+/// Gets the value of a roman numeral written in either upper or lower case, or None
+/// if it is not the standard form of a number from 1 to 3999.
+fn roman_value(numeral: &str) -> Option<u32> {
+    let upper = numeral.to_ascii_uppercase();
+    if numeral != upper && numeral != numeral.to_ascii_lowercase() {
+        return None;
+    }
+    let mut rest = upper.as_str();
+    let mut value = 0;
+    for (symbol, n) in ROMAN_NUMERALS {
+        while rest.starts_with(symbol) {
+            rest = &rest[symbol.len()..];
+            value += n;
+        }
+    }
+    // anything which is not written the way the value would be written (eg IIII or
+    // IXI) is not in the standard form
+    if !rest.is_empty() || !(1..=3999).contains(&value) || to_roman(value) != upper {
+        return None;
+    }
+    Some(value)
+}
+
+fn to_roman(mut value: u32) -> String {
+    let mut numeral = String::new();
+    for (symbol, n) in ROMAN_NUMERALS {
+        while value >= n {
+            numeral.push_str(symbol);
+            value -= n;
+        }
+    }
+    numeral
+}
+
+/// Moves the pointer past a currency symbol or code and returns it, or returns None
+/// and leaves the pointer where it was if there is not one.
+fn scan_currency<T: Source + ?Sized>(source: &mut T) -> ParseResult<Option<Currency>> {
+    let start_i = source.get_pointer_loc();
+    for (symbol, _) in CURRENCY_SYMBOLS {
+        let mut is_match = true;
+        for c in symbol.chars() {
+            match source.next()? {
+                Some((_, n)) if n == c => {}
+                _ => {
+                    is_match = false;
+                    break;
                 }
             }
         }
+        if is_match {
+            return Ok(Some(Currency::Symbol(symbol.to_owned())));
+        }
+        source.set_pointer_loc(start_i)?;
+    }
+    // codes can be directly before the amount (USD5) but not part of a longer word
+    let mut code = String::new();
+    while let Some((_, c)) = source.peek()? {
+        if !c.is_alphabetic() {
+            break;
+        }
+        code.push(c);
+        source.next()?;
+    }
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()) {
+        return Ok(Some(Currency::Code(code)));
     }
+    source.set_pointer_loc(start_i)?;
+    Ok(None)
+}
 
-    fn read_string(&mut self) -> ParseResult<String> {
-        todo!()
+/// Works out which separator is the decimal separator in an amount such as
+/// `1.299,99`. If both are used the last one is the decimal separator, otherwise
+/// the separator is for grouping if it is used more than once or if it is used once
+/// with 1 to 3 digits before it and 3 digits after it.
+fn guess_decimal_sep(amount: &str) -> DecimalSep {
+    let last_i = match amount.rfind(['.', ',']) {
+        Some(i) => i,
+        None => return DecimalSep::Point,
+    };
+    let (sep, other) = if amount[last_i..].starts_with('.') {
+        (DecimalSep::Point, DecimalSep::Comma)
+    } else {
+        (DecimalSep::Comma, DecimalSep::Point)
+    };
+    if amount.contains(other.as_char()) {
+        return sep;
     }
+    let is_repeated = amount.matches(sep.as_char()).count() > 1;
+    let digits_before = amount[..last_i].len();
+    let digits_after = amount.len() - last_i - 1;
+    if is_repeated || ((1..=3).contains(&digits_before) && digits_after == 3) {
+        other
+    } else {
+        sep
+    }
+}
 
-    fn read_bracket_contents(&mut self) -> ParseResult<String> {
-        todo!()
+/// Moves the pointer past an amount of money, working out the decimal separator
+/// using [`guess_decimal_sep`], and returns its value (see [`scan_decimal`]).
+fn scan_amount<T: Source + ?Sized>(source: &mut T) -> ParseResult<Option<f64>> {
+    let start_i = source.get_pointer_loc();
+    if let Some((_, '-')) | Some((_, '+')) = source.peek()? {
+        source.next()?;
+    }
+    let mut amount = String::new();
+    while let Some((_, c)) = source.peek()? {
+        if !c.is_ascii_digit() && c != '.' && c != ',' {
+            break;
+        }
+        amount.push(c);
+        source.next()?;
     }
+    source.set_pointer_loc(start_i)?;
+    let decimal_sep = guess_decimal_sep(amount.trim_end_matches(['.', ',']));
+    scan_decimal(source, &NumOptions::locale(decimal_sep).exponent(false))
+}
 
-    fn read_float<N: ParsableNums + ParsableFloats + FromStr>(&mut self) -> ParseResult<N> {
-        todo!()
+/// Moves the pointer past any ascii digits and returns them.
+fn scan_digits<T: Source + ?Sized>(source: &mut T) -> ParseResult<String> {
+    let mut digits = String::new();
+    while let Some((_, c)) = source.peek()? {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        digits.push(c);
+        source.next()?;
     }
+    Ok(digits)
+}
 
-    fn read_num<N: ParsableNums + ParsableInts + FromStr>(&mut self) -> ParseResult<N> {
-        todo!()
+/// Moves the pointer past a number without a sign or exponent and returns its value,
+/// or None if there is not one.
+fn scan_unsigned<T: Source + ?Sized>(source: &mut T) -> ParseResult<Option<f64>> {
+    match source.peek()? {
+        Some((_, c)) if c.is_ascii_digit() || c == '.' => {
+            scan_decimal(source, &NumOptions::default().exponent(false))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Gets the number of seconds in the unit of a duration such as `h` or `minutes`.
+fn duration_unit_secs(unit: &str) -> Option<f64> {
+    let secs = match unit.to_ascii_lowercase().as_str() {
+        "ms" | "msec" | "msecs" | "millisecond" | "milliseconds" => 0.001,
+        "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+        "d" | "day" | "days" => 86400.0,
+        "w" | "wk" | "wks" | "week" | "weeks" => 604800.0,
+        _ => return None,
+    };
+    Some(secs)
+}
+
+/// Moves the pointer past a duration written with units (eg `1h 30m`) and returns the
+/// number of seconds, or None if there is not one. Parts after the first which do not
+/// have a known unit are not part of the duration.
+fn scan_unit_duration<T: Source + ?Sized>(source: &mut T) -> ParseResult<Option<f64>> {
+    let mut secs = None;
+    loop {
+        let part_i = source.get_pointer_loc();
+        if secs.is_some() {
+            while let Some((_, c)) = source.peek()? {
+                if !c.is_whitespace() {
+                    break;
+                }
+                source.next()?;
+            }
+        }
+        let n = match scan_unsigned(source)? {
+            Some(n) => n,
+            None => {
+                source.set_pointer_loc(part_i)?;
+                return Ok(secs);
+            }
+        };
+        while let Some((_, c)) = source.peek()? {
+            if !c.is_whitespace() {
+                break;
+            }
+            source.next()?;
+        }
+        let mut unit = String::new();
+        while let Some((_, c)) = source.peek()? {
+            if !c.is_alphabetic() {
+                break;
+            }
+            unit.push(c);
+            source.next()?;
+        }
+        match (duration_unit_secs(&unit), secs) {
+            (Some(unit_secs), _) => secs = Some(secs.unwrap_or(0.0) + n * unit_secs),
+            (None, Some(_)) => {
+                source.set_pointer_loc(part_i)?;
+                return Ok(secs);
+            }
+            (None, None) if unit.is_empty() => {
+                return Err(ParseError::new().msg(format!("{} does not have a unit", n)))
+            }
+            (None, None) => {
+                return Err(ParseError::new().msg(format!("'{}' is not a unit of time", unit)))
+            }
+        }
     }
+}
 
-    fn match_bracket_contents(&mut self, val: &str) -> ParseResult<bool> {
-        todo!()
+/// Moves the pointer past an ISO 8601 duration (eg `P1DT2H30M`) and returns the
+/// number of seconds.
+fn scan_iso_duration<T: Source + ?Sized>(source: &mut T) -> ParseResult<Option<f64>> {
+    match source.next()? {
+        Some((_, 'P')) => {}
+        _ => return Ok(None),
     }
+    let mut secs = 0.0;
+    let mut has_part = false;
+    let mut in_time = false;
+    let mut has_time_part = false;
+    loop {
+        if !in_time {
+            if let Some((_, 'T')) = source.peek()? {
+                source.next()?;
+                in_time = true;
+                continue;
+            }
+        }
+        let n = match scan_unsigned(source)? {
+            Some(n) => n,
+            None => break,
+        };
+        let designator = match source.next()? {
+            Some((_, c)) => c,
+            None => return Err(ParseError::new().msg(format!("{} is missing a designator", n))),
+        };
+        let unit_secs =
+            match (in_time, designator) {
+                (false, 'W') => 604800.0,
+                (false, 'D') => 86400.0,
+                (true, 'H') => 3600.0,
+                (true, 'M') => 60.0,
+                (true, 'S') => 1.0,
+                (false, 'Y') | (false, 'M') => return Err(ParseError::new().msg(
+                    "years and months do not have a fixed length so can not be used in a duration",
+                )),
+                (_, c) => {
+                    return Err(ParseError::new()
+                        .msg(format!("'{}' is not a valid designator in a duration", c)))
+                }
+            };
+        secs += n * unit_secs;
+        has_part = true;
+        has_time_part |= in_time;
+    }
+    if !has_part || (in_time && !has_time_part) {
+        return Err(ParseError::new().msg("the ISO 8601 duration is incomplete"));
+    }
+    Ok(Some(secs))
+}
 
-    fn match_float<N: ParsableFloats + Display + FromStr>(&mut self, val: N) -> ParseResult<bool> {
-        todo!()
+/// Moves the pointer past a duration written like a clock (`H:MM:SS` or `M:SS`, with
+/// optional fractions of a second) and returns the number of seconds.
+fn scan_clock_duration<T: Source + ?Sized>(source: &mut T) -> ParseResult<Option<f64>> {
+    let first = scan_digits(source)?;
+    if first.is_empty() {
+        return Ok(None);
+    }
+    let mut parts = vec![first];
+    while let Some((_, ':')) = source.peek()? {
+        source.next()?;
+        let part = scan_digits(source)?;
+        if part.len() != 2 {
+            return Err(
+                ParseError::new().msg(format!("expected 2 digits after ':' but found '{}'", part))
+            );
+        }
+        parts.push(part);
+    }
+    if parts.len() > 3 {
+        return Err(ParseError::new().msg("a duration can only have hours, minutes and seconds"));
     }
+    let mut secs = 0.0;
+    for (i, part) in parts.iter().enumerate() {
+        let n = part.parse::<f64>().unwrap_or(0.0);
+        if i > 0 && n >= 60.0 {
+            return Err(ParseError::new().msg(format!("{} must be less than 60", part)));
+        }
+        secs = secs * 60.0 + n;
+    }
+    // fractions of a second are only part of the duration if there are digits
+    if let Some((_, '.')) = source.peek()? {
+        let dot_i = source.get_pointer_loc();
+        source.next()?;
+        let fraction = scan_digits(source)?;
+        if fraction.is_empty() {
+            source.set_pointer_loc(dot_i)?;
+        } else {
+            secs += format!("0.{}", fraction).parse::<f64>().unwrap_or(0.0);
+        }
+    }
+    Ok(Some(secs))
+}
 
-    fn match_symbol(&mut self, val: char) -> ParseResult<bool> {
-        todo!()
+/// Gets the english ordinal suffix for the number, eg "nd" for 22.
+fn ordinal_suffix(n: u32) -> &'static str {
+    match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+/// Moves the pointer past a finite decimal number (sign, digits, decimal separator,
+/// digit groups and exponent) written using the options provided and returns its
+/// value, or None if there is no number at the pointer. Nothing is consumed, and the
+/// pointer is left where it was if there is no number.
+fn scan_decimal<T: Source + ?Sized>(
+    source: &mut T,
+    options: &NumOptions,
+) -> ParseResult<Option<f64>> {
+    let start_i = source.get_pointer_loc();
+    // the number rewritten in the form rust can parse
+    let mut number = String::new();
+    if let Some((_, c)) = source.peek()? {
+        if c == '+' || c == '-' {
+            number.push(c);
+            source.next()?;
+        }
+    }
+    let mut has_digits = false;
+    // the number of digits since the start or the last group separator
+    let mut group_len = 0;
+    let mut is_grouped = false;
+    while let Some((_, c)) = source.peek()? {
+        if c.is_ascii_digit() {
+            number.push(c);
+            has_digits = true;
+            group_len += 1;
+            source.next()?;
+        } else if Some(c) == options.group_sep && has_digits {
+            // only a separator if followed by digits, eg "1, 2" is 1 followed by ", 2"
+            let sep_i = source.get_pointer_loc();
+            source.next()?;
+            match source.peek()? {
+                Some((_, d)) if d.is_ascii_digit() => {}
+                _ => {
+                    source.set_pointer_loc(sep_i)?;
+                    break;
+                }
+            }
+            if group_len != 3 && (is_grouped || group_len > 3) {
+                source.set_pointer_loc(start_i)?;
+                return Err(ParseError::new().msg(format!(
+                    "digits must be in groups of 3 between each '{}'",
+                    c
+                )));
+            }
+            is_grouped = true;
+            group_len = 0;
+        } else {
+            break;
+        }
+    }
+    if is_grouped && group_len != 3 {
+        source.set_pointer_loc(start_i)?;
+        return Err(ParseError::new().msg("the last group of digits must have 3 digits"));
+    }
+    // the decimal separator is only part of the number if digits follow it
+    if let Some((_, c)) = source.peek()? {
+        if c == options.decimal_sep.as_char() {
+            let sep_i = source.get_pointer_loc();
+            source.next()?;
+            let mut has_fraction = false;
+            while let Some((_, d)) = source.peek()? {
+                if !d.is_ascii_digit() {
+                    break;
+                }
+                if !has_fraction {
+                    number.push('.');
+                    has_fraction = true;
+                }
+                number.push(d);
+                source.next()?;
+            }
+            if has_fraction {
+                has_digits = true;
+            } else {
+                source.set_pointer_loc(sep_i)?;
+            }
+        }
+    }
+    if !has_digits {
+        source.set_pointer_loc(start_i)?;
+        return Ok(None);
+    }
+    // only take the exponent if it has digits, so "5em" is 5 followed by "em"
+    if options.exponent {
+        let mantissa_end = source.get_pointer_loc();
+        let mut exponent = String::new();
+        if let Some((_, 'e')) | Some((_, 'E')) = source.peek()? {
+            source.next()?;
+            exponent.push('e');
+            if let Some((_, c)) = source.peek()? {
+                if c == '+' || c == '-' {
+                    exponent.push(c);
+                    source.next()?;
+                }
+            }
+            let mut has_exp_digits = false;
+            while let Some((_, c)) = source.peek()? {
+                if !c.is_ascii_digit() {
+                    break;
+                }
+                exponent.push(c);
+                has_exp_digits = true;
+                source.next()?;
+            }
+            if has_exp_digits {
+                number.push_str(&exponent);
+            } else {
+                source.set_pointer_loc(mantissa_end)?;
+            }
+        }
+    }
+    match number.parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(Some(n)),
+        _ => {
+            source.set_pointer_loc(start_i)?;
+            Err(ParseError::new().msg(format!("'{}' is not a valid number", number)))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::StrParser;
+    use crate::{SourceCore, StrParser};
     #[test]
     fn parser_func_tests() {
         let mut source = StrParser::new(
@@ -670,4 +2037,357 @@ mod tests {
         assert_eq!(source.parse_symbol().unwrap(), '.');
         assert_eq!(source.parse_num::<i64>().unwrap(), 0);
     }
+
+    #[test]
+    fn parse_num_radix_tests() {
+        let mut source = StrParser::new("0xFF 0o17 -0b1010 0x_ff_ff 0X1f 0 0xG 0x100");
+        assert_eq!(source.parse_num::<u8>().unwrap(), 255);
+        assert_eq!(source.parse_num::<u32>().unwrap(), 15);
+        assert_eq!(source.parse_num::<i32>().unwrap(), -10);
+        assert_eq!(source.parse_num::<u32>().unwrap(), 0xffff);
+        assert_eq!(source.parse_num::<i64>().unwrap(), 31);
+        assert_eq!(source.parse_num::<i64>().unwrap(), 0);
+        // a prefix needs digits after it
+        assert!(source.parse_num::<u32>().is_err());
+        assert_eq!(source.get_pointer_loc(), 0);
+        source.consume(4).unwrap();
+        // too big for a u8
+        assert!(source.parse_num::<u8>().is_err());
+        assert_eq!(source.parse_num::<u16>().unwrap(), 256);
+    }
+
+    #[test]
+    fn parse_percentage_tests() {
+        let mut source = StrParser::new("42% 42.5 % -0.5% +.5% 1e2% 12 %%");
+        assert_eq!(source.parse_percentage().unwrap(), 42.0);
+        assert_eq!(source.parse_percentage().unwrap(), 42.5);
+        assert_eq!(source.parse_percentage().unwrap(), -0.5);
+        assert_eq!(source.parse_percentage().unwrap(), 0.5);
+        assert_eq!(source.parse_percentage().unwrap(), 100.0);
+        assert_eq!(source.parse_percentage().unwrap(), 12.0);
+        // a failed parse leaves the source where it was
+        assert!(source.parse_percentage().is_err());
+        assert_eq!(source.parse_symbol().unwrap(), '%');
+        for invalid in ["42", "42px", "%", ".%", "1e400%", ""] {
+            let mut source = StrParser::new(invalid);
+            assert!(
+                source.parse_percentage().is_err(),
+                "{} should not parse",
+                invalid
+            );
+            assert_eq!(source.get_pointer_loc(), 0);
+        }
+    }
+
+    #[test]
+    fn parse_ratio_tests() {
+        let mut source = StrParser::new("16/9 16:9 4 / 3 1.85:1 2 ,21 : 9.5");
+        assert_eq!(source.parse_ratio().unwrap(), (16.0, 9.0));
+        assert_eq!(source.parse_ratio().unwrap(), (16.0, 9.0));
+        assert_eq!(source.parse_ratio().unwrap(), (4.0, 3.0));
+        assert_eq!(source.parse_ratio().unwrap(), (1.85, 1.0));
+        assert_eq!(source.parse_ratio().unwrap(), (2.0, 1.0));
+        assert!(source.match_char(',').unwrap());
+        assert_eq!(source.parse_ratio().unwrap(), (21.0, 9.5));
+        for invalid in ["/9", "16/", "16:x", "-16/9", "16/-9", ""] {
+            let mut source = StrParser::new(invalid);
+            assert!(
+                source.parse_ratio().is_err(),
+                "{} should not parse",
+                invalid
+            );
+            assert_eq!(source.get_pointer_loc(), 0);
+        }
+    }
+
+    #[test]
+    fn parse_float_with_tests() {
+        let comma = NumOptions::locale(DecimalSep::Comma);
+        let mut source = StrParser::new("1.234,56 -0,5 1.234.567 12 1,5e3 1,5E-1 3, 4 5.x");
+        assert_eq!(source.parse_float_with(&comma).unwrap(), 1234.56);
+        assert_eq!(source.parse_float_with(&comma).unwrap(), -0.5);
+        assert_eq!(source.parse_float_with(&comma).unwrap(), 1234567.0);
+        assert_eq!(source.parse_float_with(&comma).unwrap(), 12.0);
+        assert_eq!(source.parse_float_with(&comma).unwrap(), 1500.0);
+        assert_eq!(source.parse_float_with(&comma).unwrap(), 0.15);
+        // a separator which is not followed by digits ends the number
+        assert_eq!(source.parse_float_with(&comma).unwrap(), 3.0);
+        assert!(source.match_char(',').unwrap());
+        assert_eq!(source.parse_float_with(&comma).unwrap(), 4.0);
+        assert_eq!(source.parse_float_with(&comma).unwrap(), 5.0);
+        assert!(source.match_char('.').unwrap());
+
+        let point = NumOptions::locale(DecimalSep::Point);
+        let mut source = StrParser::new("1,234.5 1234.5 .5");
+        assert_eq!(source.parse_float_with(&point).unwrap(), 1234.5);
+        assert_eq!(source.parse_float_with(&point).unwrap(), 1234.5);
+        assert_eq!(source.parse_float_with(&point).unwrap(), 0.5);
+
+        let mut source = StrParser::new("1,5e3");
+        assert_eq!(
+            source
+                .parse_float_with(&NumOptions::default().exponent(false))
+                .unwrap(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn parse_float_with_invalid_tests() {
+        let comma = NumOptions::locale(DecimalSep::Comma);
+        for invalid in ["1.23", "1.2345", "1234.567", "1.234.56", "abc", ",", ""] {
+            let mut source = StrParser::new(invalid);
+            assert!(
+                source.parse_float_with(&comma).is_err(),
+                "{} should not parse",
+                invalid
+            );
+            assert_eq!(source.get_pointer_loc(), 0);
+        }
+        // grouping is off by default
+        let mut source = StrParser::new("1,234");
+        assert_eq!(
+            source.parse_float_with(&NumOptions::default()).unwrap(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn parse_roman_numeral_tests() {
+        let mut source = StrParser::new("XIV mcmxcix I. iv, MMMCMXCIX IIII");
+        assert_eq!(source.parse_roman_numeral().unwrap(), 14);
+        assert_eq!(source.parse_roman_numeral().unwrap(), 1999);
+        assert_eq!(source.parse_roman_numeral().unwrap(), 1);
+        assert!(source.match_char('.').unwrap());
+        assert_eq!(source.parse_roman_numeral().unwrap(), 4);
+        assert!(source.match_char(',').unwrap());
+        assert_eq!(source.parse_roman_numeral().unwrap(), 3999);
+        assert!(source.parse_roman_numeral().is_err());
+        assert!(source.match_str("IIII").unwrap());
+        for invalid in [
+            "IC", "VV", "XIIII", "MMMM", "IXI", "Xiv", "Mix", "X1", "", ".",
+        ] {
+            let mut source = StrParser::new(invalid);
+            assert!(
+                source.parse_roman_numeral().is_err(),
+                "{} should not parse",
+                invalid
+            );
+            assert_eq!(source.get_pointer_loc(), 0);
+        }
+    }
+
+    #[test]
+    fn parse_ordinal_tests() {
+        let mut source = StrParser::new("1st 2nd 3RD 4th 11th 12th 13th 21st 102nd 111th 0th");
+        for expected in [1, 2, 3, 4, 11, 12, 13, 21, 102, 111, 0] {
+            assert_eq!(source.parse_ordinal().unwrap(), expected);
+        }
+        for invalid in [
+            "1", "2st", "11st", "12nd", "21th", "3rdx", "first", "st", "",
+        ] {
+            let mut source = StrParser::new(invalid);
+            assert!(
+                source.parse_ordinal().is_err(),
+                "{} should not parse",
+                invalid
+            );
+            assert_eq!(source.get_pointer_loc(), 0);
+        }
+    }
+
+    #[test]
+    fn parse_money_tests() {
+        let usd = || Currency::Code("USD".to_owned());
+        let symbol = |s: &str| Currency::Symbol(s.to_owned());
+        let mut source = StrParser::new(
+            "$1,299.99 € 12,50 USD 5.00 5.00 USD ($3.50) (12,00 €) -$5 $-5 €1.299 1.234.567,8€ US$10 USD5 £0.99,",
+        );
+        assert_eq!(source.parse_money().unwrap(), (1299.99, symbol("$")));
+        assert_eq!(source.parse_money().unwrap(), (12.5, symbol("€")));
+        assert_eq!(source.parse_money().unwrap(), (5.0, usd()));
+        assert_eq!(source.parse_money().unwrap(), (5.0, usd()));
+        assert_eq!(source.parse_money().unwrap(), (-3.5, symbol("$")));
+        assert_eq!(source.parse_money().unwrap(), (-12.0, symbol("€")));
+        assert_eq!(source.parse_money().unwrap(), (-5.0, symbol("$")));
+        assert_eq!(source.parse_money().unwrap(), (-5.0, symbol("$")));
+        assert_eq!(source.parse_money().unwrap(), (1299.0, symbol("€")));
+        assert_eq!(source.parse_money().unwrap(), (1234567.8, symbol("€")));
+        assert_eq!(source.parse_money().unwrap(), (10.0, symbol("US$")));
+        assert_eq!(source.parse_money().unwrap(), (5.0, usd()));
+        assert_eq!(source.parse_money().unwrap(), (0.99, symbol("£")));
+        assert!(source.match_char(',').unwrap());
+
+        assert_eq!(symbol("US$").code(), Some("USD"));
+        assert_eq!(symbol("€").code(), Some("EUR"));
+        assert_eq!(symbol("$").code(), None);
+        assert_eq!(usd().code(), Some("USD"));
+
+        for invalid in ["12.50", "$", "USDX 5", "5 usd", "($5", "1.2.3 €", ""] {
+            let mut source = StrParser::new(invalid);
+            assert!(
+                source.parse_money().is_err(),
+                "{} should not parse",
+                invalid
+            );
+            assert_eq!(source.get_pointer_loc(), 0);
+        }
+    }
+
+    #[test]
+    fn parse_duration_tests() {
+        let tests = [
+            ("1h 30m", 5400.0),
+            ("1h30m", 5400.0),
+            ("2 hours 5 mins", 7500.0),
+            ("1.5h", 5400.0),
+            ("500ms", 0.5),
+            ("1 day", 86400.0),
+            ("PT1H30M", 5400.0),
+            ("P1DT12H", 129600.0),
+            ("P2W", 1209600.0),
+            ("PT1.5S", 1.5),
+            ("01:30:00", 5400.0),
+            ("3:45", 225.0),
+            ("100:00", 6000.0),
+            ("0:00:01.25", 1.25),
+        ];
+        for (input, secs) in tests {
+            let mut source = StrParser::new(input);
+            assert_eq!(
+                source.parse_duration().unwrap(),
+                Duration::from_secs_f64(secs),
+                "parsing {}",
+                input
+            );
+        }
+        // anything after the duration is left
+        let mut source = StrParser::new("5m long, PT2M. 1:30.");
+        assert_eq!(source.parse_duration().unwrap(), Duration::from_secs(300));
+        assert!(source.match_str("long").unwrap());
+        assert!(source.match_char(',').unwrap());
+        assert_eq!(source.parse_duration().unwrap(), Duration::from_secs(120));
+        assert!(source.match_char('.').unwrap());
+        assert_eq!(source.parse_duration().unwrap(), Duration::from_secs(90));
+        assert!(source.match_char('.').unwrap());
+
+        for invalid in [
+            "P1Y",
+            "P1M",
+            "PT",
+            "P",
+            "PT1X",
+            "1:60",
+            "1:5",
+            "1:00:00:00",
+            "5",
+            "5 parsecs",
+            "-5m",
+            "",
+        ] {
+            let mut source = StrParser::new(invalid);
+            assert!(
+                source.parse_duration().is_err(),
+                "{} should not parse",
+                invalid
+            );
+            assert_eq!(source.get_pointer_loc(), 0);
+        }
+    }
+    #[test]
+    fn until_tests() {
+        let mut source = StrParser::new("some text<p>é-->rest");
+        assert_eq!(source.parse_until("<", false).unwrap(), "some text");
+        assert_eq!(source.peek().unwrap(), Some((9, '<')));
+        source.reset_pointer_loc();
+        assert_eq!(source.read_until('<', true).unwrap(), "some text");
+        assert_eq!(source.read_until('>', true).unwrap(), "p");
+        assert_eq!(source.parse_until("-->", true).unwrap(), "é");
+        assert_eq!(source.get_pointer_loc(), 4);
+        source.consume(4).unwrap();
+        // the pointer is left where it was if the terminator is not found
+        assert!(source.parse_until("<", false).is_err());
+        assert_eq!(source.get_pointer_loc(), 0);
+        assert!(source.read_until('<', false).is_err());
+        assert_eq!(source.read_until('t', false).unwrap(), "res");
+        assert_eq!(source.peek().unwrap(), Some((0, 't')));
+    }
+
+    #[test]
+    fn identifier_tests() {
+        let mut source = StrParser::new("svg:rect my-el2 data-x=1");
+        let tag = IdentRules::html_tag();
+        assert_eq!(source.parse_identifier(&tag).unwrap(), "svg:rect");
+        assert_eq!(source.parse_identifier(&tag).unwrap(), "my-el2");
+        assert_eq!(
+            source
+                .parse_identifier(&IdentRules::html_attribute())
+                .unwrap(),
+            "data-x"
+        );
+        // like parse_word the pointer is moved but nothing is consumed
+        assert_eq!(source.peek().unwrap(), Some((22, '=')));
+
+        let mut source = StrParser::new(" @click.prevent");
+        assert_eq!(
+            source
+                .parse_identifier(&IdentRules::html_attribute())
+                .unwrap(),
+            "@click.prevent"
+        );
+        let mut source = StrParser::new("-webkit-box:_a1.b");
+        let css = IdentRules::css();
+        assert_eq!(source.parse_identifier(&css).unwrap(), "-webkit-box");
+        source.consume(source.get_pointer_loc()).unwrap();
+        assert_eq!(source.parse_symbol().unwrap(), ':');
+        assert_eq!(source.parse_identifier(&css).unwrap(), "_a1");
+
+        // the pointer is left where it was if the first char can not start one
+        let mut source = StrParser::new("1abc");
+        assert!(source.parse_identifier(&tag).is_err());
+        assert_eq!(source.get_pointer_loc(), 0);
+        let digits = IdentRules::new(
+            CharClass::new().digits(),
+            CharClass::new().digits().chars("_"),
+        );
+        assert_eq!(source.parse_identifier(&digits).unwrap(), "1");
+    }
+
+    #[test]
+    fn quoted_string_tests() {
+        let mut source = StrParser::new(r#" "say \"hi\"\n\tand\\or 'bye'" 'it\'s' "#);
+        assert_eq!(
+            source.parse_quoted_string().unwrap(),
+            "say \"hi\"\n\tand\\or 'bye'"
+        );
+        assert_eq!(source.parse_quoted_string().unwrap(), "it's");
+        let mut source = StrParser::new(r#""a^"b^^c\d""#);
+        assert_eq!(source.parse_quoted_string_with('^').unwrap(), "a\"b^c\\d");
+        let mut source = StrParser::new("''");
+        assert_eq!(source.parse_quoted_string().unwrap(), "");
+        // the pointer is left at the start when the string can not be parsed
+        for text in ["word", r#""no end"#, r#""end \""#, r#""bad \q""#] {
+            let mut source = StrParser::new(text);
+            assert!(source.parse_quoted_string().is_err(), "parsed {}", text);
+            assert_eq!(source.get_pointer_loc(), 0);
+        }
+    }
+
+    #[test]
+    fn peek_tests() {
+        let mut source = StrParser::new("  word1 = value");
+        assert_eq!(source.peek_word().unwrap(), Some("word1".to_owned()));
+        assert_eq!(source.peek_str(4).unwrap(), "  wo");
+        assert_eq!(source.get_pointer_loc(), 0);
+        // peeking does not affect the parse functions which follow
+        source.consume_whitespace().unwrap();
+        assert_eq!(source.parse_word().unwrap(), "word1");
+        source.consume(source.get_pointer_loc()).unwrap();
+        assert_eq!(source.peek_word().unwrap(), None);
+        assert_eq!(source.peek_str(2).unwrap(), " =");
+        assert_eq!(source.parse_symbol().unwrap(), '=');
+        // the pointer is left where it was rather than reset
+        assert_eq!(source.peek_str(100).unwrap(), " value");
+        assert_eq!(source.get_pointer_loc(), 2);
+    }
 }
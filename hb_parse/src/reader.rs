@@ -0,0 +1,136 @@
+//! A cursor which reads ahead in a source without moving its pointer or window.
+//!
+//! The parser functions move the pointer of the source as they read and so need
+//! it to start at 0, resetting it when they fail. A [`SourceReader`] instead keeps
+//! its own position, starting from the start of the window of the source, so any
+//! number of readers can be created to try something and dropped if it does not
+//! match. Only [`SourceReader::commit`] changes the source, consuming the chars
+//! the reader has read.
+//!
+//! ```
+//! use hb_parse::{Source, SourceCore, StrParser};
+//! let mut source = StrParser::new("<p>text");
+//! // a reader which does not match leaves the source as it was
+//! let mut comment = source.reader();
+//! assert!(comment.match_str("<!--").unwrap().is_none());
+//! drop(comment);
+//! let mut tag = source.reader();
+//! tag.next_if(|c| c == '<').unwrap();
+//! tag.skip_while(char::is_alphanumeric).unwrap();
+//! tag.next_if(|c| c == '>').unwrap();
+//! assert_eq!(tag.commit().unwrap(), "<p>".to_owned());
+//! assert_eq!(source.read_substr(0, 4).unwrap(), "text".to_owned());
+//! ```
+use crate::source::SourceCore;
+use crate::SourceResult;
+
+/// Reads the chars of a source from the start of its window, without moving the
+/// pointer of the source.
+pub struct SourceReader<'a, S: SourceCore + ?Sized = dyn SourceCore + 'a> {
+    source: &'a mut S,
+    offset: usize, // the number of chars read from the start of the window
+}
+
+impl<'a, S: SourceCore + ?Sized> SourceReader<'a, S> {
+    pub fn new(source: &'a mut S) -> SourceReader<'a, S> {
+        SourceReader { source, offset: 0 }
+    }
+
+    /// The number of chars read.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Gets the next char without reading it.
+    pub fn peek(&mut self) -> SourceResult<Option<char>> {
+        self.source.char_at(self.offset)
+    }
+
+    /// Reads the next char.
+    pub fn read_char(&mut self) -> SourceResult<Option<char>> {
+        let c = self.peek()?;
+        if c.is_some() {
+            self.offset += 1;
+        }
+        Ok(c)
+    }
+
+    /// Reads the next char if it matches.
+    pub fn next_if<F: FnOnce(char) -> bool>(&mut self, f: F) -> SourceResult<Option<char>> {
+        match self.peek()? {
+            Some(c) if f(c) => {
+                self.offset += 1;
+                Ok(Some(c))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads the upcoming chars which match, returning how many there were.
+    pub fn skip_while<F: FnMut(char) -> bool>(&mut self, mut f: F) -> SourceResult<usize> {
+        let start = self.offset;
+        while self.next_if(&mut f)?.is_some() {}
+        Ok(self.offset - start)
+    }
+
+    /// Reads the str if the upcoming chars match it, returning the chars read. If
+    /// they do not match, nothing is read.
+    pub fn match_str(&mut self, val: &str) -> SourceResult<Option<String>> {
+        let start = self.offset;
+        for expected in val.chars() {
+            if self.next_if(|c| c == expected)?.is_none() {
+                self.offset = start;
+                return Ok(None);
+            }
+        }
+        Ok(Some(val.to_owned()))
+    }
+
+    /// Goes back to an offset read before, such as one from
+    /// [`SourceReader::offset`].
+    pub fn rewind(&mut self, offset: usize) {
+        self.offset = self.offset.min(offset);
+    }
+
+    /// Gets the chars read so far.
+    pub fn text(&mut self) -> SourceResult<String> {
+        self.source.read_substr(0, self.offset)
+    }
+
+    /// Consumes the chars read from the source, returning them.
+    pub fn commit(self) -> SourceResult<String> {
+        self.source.extract(self.offset)
+    }
+}
+
+#[cfg(test)]
+mod reader_tests {
+    use super::*;
+    use crate::{ReaderSource, Source, StrParser};
+
+    #[test]
+    fn reader_tests() {
+        let mut source = ReaderSource::new("ab 12, rest".as_bytes());
+        source.move_forward(4).unwrap();
+        let mut reader = source.reader();
+        assert_eq!(reader.skip_while(char::is_alphabetic).unwrap(), 2);
+        let after_word = reader.offset();
+        reader.read_char().unwrap();
+        assert_eq!(reader.skip_while(|c| c.is_ascii_digit()).unwrap(), 2);
+        assert_eq!(reader.match_str(", x").unwrap(), None);
+        assert_eq!(reader.text().unwrap(), "ab 12".to_owned());
+        reader.rewind(after_word);
+        assert_eq!(reader.peek().unwrap(), Some(' '));
+        assert_eq!(reader.commit().unwrap(), "ab".to_owned());
+        // the pointer of the source moves with its window
+        assert_eq!(source.get_pointer_loc(), 2);
+        assert_eq!(source.get_position(), Some((1, 5)));
+
+        let mut parser = StrParser::new("é");
+        let dyn_source: &mut dyn SourceCore = &mut parser;
+        let mut reader: SourceReader = SourceReader::new(dyn_source);
+        assert_eq!(reader.read_char().unwrap(), Some('é'));
+        assert_eq!(reader.read_char().unwrap(), None);
+        assert_eq!(reader.offset(), 1);
+    }
+}
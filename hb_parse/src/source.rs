@@ -1,9 +1,28 @@
-use crate::SourceResult;
+use crate::{ErrorContext, SourceError, SourceReader, SourceResult};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
 
 /// Interface for different implementations of sources of data for the parser.
 /// Provides various function which will allow the parsing of data without having
 /// multiple buffers.
-pub trait Source {
+///
+/// The trait is object safe, so sources of different types can be kept together
+/// as `Box<dyn SourceCore>`. The generic helpers are in [`Source`], which every
+/// `SourceCore` implements, including `dyn SourceCore`.
+///
+/// ```
+/// use hb_parse::{CommonParserFunctions, ReaderSource, SourceCore, StrParser};
+/// let mut sources: Vec<Box<dyn SourceCore>> = vec![
+///     Box::new(StrParser::new("first")),
+///     Box::new(ReaderSource::new("second".as_bytes())),
+/// ];
+/// for source in sources.iter_mut() {
+///     assert!(source.parse_word().is_ok());
+/// }
+/// ```
+pub trait SourceCore {
     /// Gets the next char and moves the pointer
     fn next(&mut self) -> SourceResult<Option<(usize, char)>>;
     /// Gets the next char but does not move the pointer
@@ -27,6 +46,450 @@ pub trait Source {
     fn reset_pointer_loc(&mut self);
     /// Set the pointer to a specific value
     fn set_pointer_loc(&mut self, i: usize) -> SourceResult<()>;
+    /// Saves the location of the pointer, so that a parser function can go back to
+    /// it with [`SourceCore::rollback`] if what it is trying does not match, leaving
+    /// the pointer where the function which called it had it
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            pointer: self.get_pointer_loc(),
+        }
+    }
+    /// Moves the pointer back to where it was when the checkpoint was made. The
+    /// window must not have been moved since, as the chars consumed can not be
+    /// read again
+    fn rollback(&mut self, checkpoint: Checkpoint) -> SourceResult<()> {
+        let pointer = self.get_pointer_loc();
+        if checkpoint.pointer <= pointer {
+            self.move_back(pointer - checkpoint.pointer)
+        } else {
+            self.set_pointer_loc(checkpoint.pointer)
+        }
+    }
     /// Gets up to 80 chars around the current pointer
     fn get_context(&self) -> String;
+    /// Gets the char at a location in the window without affecting any pointers,
+    /// or None if the data ends before it
+    fn char_at(&mut self, i: usize) -> SourceResult<Option<char>> {
+        let pointer = self.get_pointer_loc();
+        self.reset_pointer_loc();
+        let mut c = None;
+        for _ in 0..=i {
+            c = self.next()?.map(|(_, c)| c);
+            if c.is_none() {
+                break;
+            }
+        }
+        self.reset_pointer_loc();
+        self.move_forward(pointer)?;
+        Ok(c)
+    }
+    /// Gets the line and column (both starting from 1) of the char at the pointer,
+    /// if the source keeps track of them
+    fn get_position(&self) -> Option<(usize, usize)> {
+        None
+    }
+    /// Gets the line (starting from 1) of the char at the pointer, if the source
+    /// keeps track of it
+    fn current_line(&self) -> Option<usize> {
+        self.get_position().map(|(line, _)| line)
+    }
+    /// Gets the column (starting from 1) of the char at the pointer, if the source
+    /// keeps track of it
+    fn current_column(&self) -> Option<usize> {
+        self.get_position().map(|(_, column)| column)
+    }
+}
+
+/// A saved location of the pointer of a source, see [`SourceCore::checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pointer: usize,
+}
+
+impl Checkpoint {
+    /// The location the pointer was at when the checkpoint was made.
+    pub fn pointer_loc(&self) -> usize {
+        self.pointer
+    }
+}
+
+/// Generic helpers for reading from a [`SourceCore`], which are implemented for
+/// every source. Parser functions should be implemented for `T: Source + ?Sized`
+/// so that they can also be used with `dyn SourceCore`.
+pub trait Source: SourceCore {
+    /// Gets the next char and moves the pointer, if the char matches
+    fn next_if<F: FnOnce(char) -> bool>(&mut self, f: F) -> SourceResult<Option<char>> {
+        match self.peek()? {
+            Some((_, c)) if f(c) => {
+                self.next()?;
+                Ok(Some(c))
+            }
+            _ => Ok(None),
+        }
+    }
+    /// Moves the pointer past the upcoming chars which match, returning how many
+    /// there were
+    fn skip_while<F: FnMut(char) -> bool>(&mut self, mut f: F) -> SourceResult<usize> {
+        let mut n = 0;
+        while self.next_if(&mut f)?.is_some() {
+            n += 1;
+        }
+        Ok(n)
+    }
+    /// Creates a [`SourceReader`] reading from the start of the window
+    fn reader(&mut self) -> SourceReader<'_, Self> {
+        SourceReader::new(self)
+    }
+}
+
+impl<T: SourceCore + ?Sized> Source for T {}
+
+/// The number of bytes read from the input at a time.
+const CHUNK_SIZE: usize = 8192;
+
+/// A [`SourceCore`] which reads the chars of UTF-8 text from a reader, such as a
+/// socket or a pipe, as they are needed, so large inputs can be parsed without
+/// reading all of them into memory. Chars which are split across two reads are
+/// put back together.
+///
+/// Only the window of chars which have not been consumed or extracted is kept,
+/// along with whatever has been read past the pointer, so the memory used depends
+/// on how far the parser looks ahead rather than on the size of the input.
+///
+/// ```
+/// use hb_parse::{CommonParserFunctions, ReaderSource};
+/// let mut source = ReaderSource::new("some words".as_bytes());
+/// assert_eq!(source.parse_word().unwrap(), "some".to_owned());
+/// ```
+pub struct ReaderSource<R: Read> {
+    reader: R,
+    window: VecDeque<char>, // the chars from the start of the window that have been read
+    pending: Vec<u8>,       // bytes of a char split across two reads
+    finished: bool,         // whether the end of the input has been reached
+    pointer: usize,         // the location of the next char that will be provided
+    line: usize,            // the line of the start of the window
+    column: usize,          // the column of the start of the window
+}
+
+/// A [`ReaderSource`] reading a file.
+///
+/// ```no_run
+/// use hb_parse::{CommonParserFunctions, FileSource};
+/// let mut source = FileSource::open("page.html").unwrap();
+/// let word = source.parse_word().unwrap();
+/// ```
+pub type FileSource = ReaderSource<File>;
+
+impl FileSource {
+    /// Opens the file at the path for reading.
+    pub fn open<P: AsRef<Path>>(path: P) -> SourceResult<FileSource> {
+        Ok(ReaderSource::new(
+            File::open(path).map_err(SourceError::from)?,
+        ))
+    }
+}
+
+impl<R: Read> ReaderSource<R> {
+    pub fn new(reader: R) -> ReaderSource<R> {
+        ReaderSource {
+            reader,
+            window: VecDeque::new(),
+            pending: vec![],
+            finished: false,
+            pointer: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Reads the next chunk of the input onto the end of the window.
+    fn read_chunk(&mut self) -> SourceResult<()> {
+        let mut bytes = [0u8; CHUNK_SIZE];
+        let read = loop {
+            match self.reader.read(&mut bytes) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(SourceError::from(e)),
+            }
+        };
+        if read == 0 {
+            self.finished = true;
+            if !self.pending.is_empty() {
+                return Err(SourceError::new().msg("the input ends part way through a UTF-8 char"));
+            }
+            return Ok(());
+        }
+        self.pending.extend_from_slice(&bytes[..read]);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(s) => s.len(),
+            // the end of the bytes read is the start of a char, which is finished
+            // by the next read
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => {
+                return Err(SourceError::new().msg(format!(
+                    "the input is not valid UTF-8, after {} chars",
+                    self.window.len() + e.valid_up_to()
+                )))
+            }
+        };
+        let text = std::str::from_utf8(&self.pending[..valid]).unwrap_or_default();
+        self.window.extend(text.chars());
+        self.pending.drain(..valid);
+        Ok(())
+    }
+
+    /// Reads until the window has at least n chars, returning false if the input
+    /// ends first.
+    fn fill(&mut self, n: usize) -> SourceResult<bool> {
+        while self.window.len() < n && !self.finished {
+            self.read_chunk()?;
+        }
+        Ok(self.window.len() >= n)
+    }
+
+    /// Removes n chars from the start of the window, which must have them.
+    fn drop_front(&mut self, n: usize) -> String {
+        let removed: String = self.window.drain(..n).collect();
+        for c in removed.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.pointer = self.pointer.saturating_sub(n);
+        removed
+    }
+}
+
+impl<R: Read> SourceCore for ReaderSource<R> {
+    fn next(&mut self) -> SourceResult<Option<(usize, char)>> {
+        if !self.fill(self.pointer + 1)? {
+            return Ok(None);
+        }
+        self.pointer += 1;
+        Ok(Some((self.pointer - 1, self.window[self.pointer - 1])))
+    }
+
+    fn peek(&mut self) -> SourceResult<Option<(usize, char)>> {
+        if !self.fill(self.pointer + 1)? {
+            return Ok(None);
+        }
+        Ok(Some((self.pointer, self.window[self.pointer])))
+    }
+
+    fn move_back(&mut self, n: usize) -> SourceResult<()> {
+        if self.pointer < n {
+            return Err(SourceError::new().msg(format!(
+                "attempted to move pointer ({}) back {} places past the start of the data",
+                self.pointer, n
+            )));
+        }
+        self.pointer -= n;
+        Ok(())
+    }
+
+    fn move_forward(&mut self, n: usize) -> SourceResult<()> {
+        if !self.fill(self.pointer + n)? {
+            return Err(SourceError::new().msg(format!(
+                "attempted to move pointer ({}) forward {} places past the end of the data ({})",
+                self.pointer,
+                n,
+                self.window.len()
+            )));
+        }
+        self.pointer += n;
+        Ok(())
+    }
+
+    fn consume(&mut self, n: usize) -> SourceResult<()> {
+        if !self.fill(n)? {
+            return Err(SourceError::new().msg(format!(
+                "attempted to consume {} chars when only {} remain",
+                n,
+                self.window.len()
+            )));
+        }
+        self.drop_front(n);
+        Ok(())
+    }
+
+    fn extract(&mut self, n: usize) -> SourceResult<String> {
+        if !self.fill(n)? {
+            return Err(SourceError::new().msg(format!(
+                "attempted to extract {} chars when only {} remain",
+                n,
+                self.window.len()
+            )));
+        }
+        Ok(self.drop_front(n))
+    }
+
+    fn read_substr(&mut self, start: usize, n: usize) -> SourceResult<String> {
+        if !self.fill(start + n)? {
+            return Err(SourceError::new().msg(format!(
+                "attempted to read a substring of {} chars from position {} when only {} remain",
+                n,
+                start,
+                self.window.len()
+            )));
+        }
+        Ok(self.window.range(start..start + n).collect())
+    }
+
+    fn get_pointer_loc(&self) -> usize {
+        self.pointer
+    }
+
+    fn reset_pointer_loc(&mut self) {
+        self.pointer = 0;
+    }
+
+    fn set_pointer_loc(&mut self, i: usize) -> SourceResult<()> {
+        self.reset_pointer_loc();
+        self.move_forward(i)
+    }
+
+    fn char_at(&mut self, i: usize) -> SourceResult<Option<char>> {
+        if !self.fill(i + 1)? {
+            return Ok(None);
+        }
+        Ok(Some(self.window[i]))
+    }
+
+    fn get_context(&self) -> String {
+        let start_i = self.pointer.saturating_sub(40);
+        let end_i = (start_i + 80).min(self.window.len());
+        format!(
+            "{}\n{}{}\n",
+            self.window.range(start_i..end_i).collect::<String>(),
+            " ".repeat(self.pointer - start_i),
+            '^'
+        )
+    }
+
+    fn get_position(&self) -> Option<(usize, usize)> {
+        let (mut line, mut column) = (self.line, self.column);
+        for c in self.window.range(..self.pointer) {
+            if *c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        Some((line, column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CommonParserFunctions;
+    use std::io::Write;
+
+    /// Writes the contents to a new file in the temp directory.
+    fn temp_file(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("hb_parse_{}_{}", std::process::id(), name));
+        File::create(&path).unwrap().write_all(contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn filesource_tests() {
+        // the euro sign is split across the first two reads
+        let contents = format!("{}€ word\nnext line", "a".repeat(CHUNK_SIZE - 1));
+        let path = temp_file("chunks.txt", contents.as_bytes());
+        let mut source = FileSource::open(&path).unwrap();
+        assert_eq!(source.consume(CHUNK_SIZE - 2).unwrap(), ());
+        assert_eq!(source.next().unwrap(), Some((0, 'a')));
+        assert_eq!(source.peek().unwrap(), Some((1, '€')));
+        assert_eq!(source.next().unwrap(), Some((1, '€')));
+        assert_eq!(source.get_position(), Some((1, CHUNK_SIZE + 1)));
+        assert_eq!(source.move_back(2).unwrap(), ());
+        assert_eq!(source.extract(3).unwrap(), "a€ ".to_string());
+        assert_eq!(source.parse_word().unwrap(), "word".to_owned());
+        assert_eq!(source.read_substr(5, 4).unwrap(), "next".to_string());
+        assert_eq!(source.get_position(), Some((1, CHUNK_SIZE + 6)));
+        assert_eq!(source.set_pointer_loc(7).unwrap(), ());
+        assert_eq!(source.get_position(), Some((2, 3)));
+        assert_eq!(source.get_context(), "word\nnext line\n       ^\n");
+        assert!(source.move_forward(8).is_err());
+        assert_eq!(source.move_forward(7).unwrap(), ());
+        assert_eq!(source.next().unwrap(), None);
+        assert!(source.consume(15).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let path = temp_file("invalid.txt", b"ok \xff\xfe");
+        let mut source = FileSource::open(&path).unwrap();
+        assert!(source.next().is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(FileSource::open(&path).is_err());
+    }
+
+    /// Gives the bytes one at a time, so every char of more than one byte is split.
+    struct OneByte<'a>(&'a [u8]);
+
+    impl Read for OneByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.split_first() {
+                Some((b, rest)) if !buf.is_empty() => {
+                    buf[0] = *b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn readersource_tests() {
+        let mut source = ReaderSource::new(OneByte("€5 £6 😀".as_bytes()));
+        assert_eq!(source.peek().unwrap(), Some((0, '€')));
+        assert_eq!(source.read_substr(3, 2).unwrap(), "£6".to_string());
+        assert_eq!(source.consume(3).unwrap(), ());
+        assert_eq!(source.next().unwrap(), Some((0, '£')));
+        assert_eq!(source.extract(3).unwrap(), "£6 ".to_string());
+        assert_eq!(source.next().unwrap(), Some((0, '😀')));
+        assert_eq!(source.next().unwrap(), None);
+        assert_eq!(source.get_position(), Some((1, 8)));
+        let mut source = ReaderSource::new(OneByte(b"ab\xe2\x82"));
+        assert_eq!(source.next().unwrap(), Some((0, 'a')));
+        assert_eq!(source.next().unwrap(), Some((1, 'b')));
+        assert!(source.next().is_err());
+    }
+
+    #[test]
+    fn dyn_source_tests() {
+        let mut parser = crate::StrParser::new("  \tword, rest");
+        let source: &mut dyn SourceCore = &mut parser;
+        assert_eq!(source.skip_while(char::is_whitespace).unwrap(), 3);
+        assert_eq!(source.next_if(|c| c == 'x').unwrap(), None);
+        assert_eq!(source.next_if(|c| c == 'w').unwrap(), Some('w'));
+        assert_eq!(source.get_pointer_loc(), 4);
+        source.consume(3).unwrap();
+        source.reset_pointer_loc();
+        assert_eq!(source.parse_word().unwrap(), "word".to_owned());
+    }
+
+    #[test]
+    fn checkpoint_tests() {
+        let mut source = ReaderSource::new("abcdef".as_bytes());
+        source.move_forward(1).unwrap();
+        let outer = source.checkpoint();
+        source.move_forward(2).unwrap();
+        let inner = source.checkpoint();
+        assert_eq!(inner.pointer_loc(), 3);
+        source.move_forward(2).unwrap();
+        // rolling back the inner attempt leaves the outer one where it was
+        source.rollback(inner).unwrap();
+        assert_eq!(source.next().unwrap(), Some((3, 'd')));
+        source.rollback(outer).unwrap();
+        assert_eq!(source.get_pointer_loc(), 1);
+        // a checkpoint can also be ahead of the pointer
+        source.rollback(inner).unwrap();
+        assert_eq!(source.peek().unwrap(), Some((3, 'd')));
+    }
 }